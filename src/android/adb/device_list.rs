@@ -2,17 +2,81 @@ use super::{device_name, get_prop};
 use crate::{
     android::{device::Device, env::Env, target::Target},
     env::ExplicitEnv as _,
-    util::cli::{Report, Reportable},
+    util::{
+        cli::{Report, Reportable},
+        Invocation,
+    },
 };
-use once_cell_regex::regex_multi_line;
+use once_cell_regex::{regex, regex_multi_line};
 use std::{collections::BTreeSet, str};
 
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DeviceStatus {
+    Ready,
+    Offline,
+    Unauthorized,
+    Other(String),
+}
+
+impl DeviceStatus {
+    fn from_adb(status: &str) -> Self {
+        match status {
+            "device" => Self::Ready,
+            "offline" => Self::Offline,
+            "unauthorized" => Self::Unauthorized,
+            _ => Self::Other(status.to_owned()),
+        }
+    }
+}
+
+/// An entry from `adb devices -l`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeviceListing {
+    pub serial: String,
+    pub status: DeviceStatus,
+    pub model: Option<String>,
+    pub product: Option<String>,
+    pub transport_id: Option<String>,
+}
+
+/// Parses the output of `adb devices -l`. Devices that aren't ready are still
+/// included, so callers can tell the user why they can't be used.
+pub fn parse_device_list_long(raw_list: &str) -> Vec<DeviceListing> {
+    let line_re = regex_multi_line!(
+        r"^(\S+)\s+(device|offline|unauthorized|bootloader|recovery|sideload|no permissions|unknown)\b(.*)$"
+    );
+    let attr_re = regex!(r"(\w+):(\S+)");
+    line_re
+        .captures_iter(raw_list)
+        .map(|caps| {
+            let mut listing = DeviceListing {
+                serial: caps[1].to_owned(),
+                status: DeviceStatus::from_adb(&caps[2]),
+                model: None,
+                product: None,
+                transport_id: None,
+            };
+            for attr in attr_re.captures_iter(&caps[3]) {
+                let value = Some(attr[2].to_owned());
+                match &attr[1] {
+                    "model" => listing.model = value,
+                    "product" => listing.product = value,
+                    "transport_id" => listing.transport_id = value,
+                    _ => (),
+                }
+            }
+            listing
+        })
+        .collect()
+}
+
 #[derive(Debug)]
 pub enum Error {
     DevicesFailed(super::RunCheckedError),
     InvalidUtf8(str::Utf8Error),
     NameFailed(device_name::Error),
     ModelFailed(get_prop::Error),
+    OsVersionFailed(get_prop::Error),
     AbiFailed(get_prop::Error),
     AbiInvalid(String),
 }
@@ -26,7 +90,9 @@ impl Reportable for Error {
                 Report::error(msg, format!("Device list contained invalid UTF-8: {}", err))
             }
             Self::NameFailed(err) => err.report(),
-            Self::ModelFailed(err) | Self::AbiFailed(err) => err.report(),
+            Self::ModelFailed(err) | Self::OsVersionFailed(err) | Self::AbiFailed(err) => {
+                err.report()
+            }
             Self::AbiInvalid(abi) => {
                 Report::error(msg, format!("{:?} isn't a valid target ABI.", abi))
             }
@@ -35,25 +101,89 @@ impl Reportable for Error {
 }
 
 pub fn device_list(env: &Env) -> Result<BTreeSet<Device<'static>>, Error> {
-    let serial_re = regex_multi_line!(r"^([\w\d]{6,20})	\b");
     let output = super::run_checked(
-        &mut bossy::Command::pure("adb")
+        &Invocation::pure("adb")
             .with_env_vars(env.explicit_env())
-            .with_args(&["devices"]),
+            .with_args(&["devices", "-l"]),
     )
     .map_err(Error::DevicesFailed)?;
     let raw_list = output.stdout_str().map_err(Error::InvalidUtf8)?;
-    serial_re
-        .captures_iter(raw_list)
-        .map(|caps| {
-            assert_eq!(caps.len(), 2);
-            let serial_no = caps.get(1).unwrap().as_str().to_owned();
+    parse_device_list_long(raw_list)
+        .into_iter()
+        .filter(|listing| {
+            log::debug!(
+                "found device {:?} (model {:?}, product {:?}, transport id {:?})",
+                listing.serial,
+                listing.model,
+                listing.product,
+                listing.transport_id
+            );
+            let ready = listing.status == DeviceStatus::Ready;
+            if !ready {
+                log::warn!(
+                    "skipping device {:?}, since its status is {:?}",
+                    listing.serial,
+                    listing.status
+                );
+            }
+            ready
+        })
+        .map(|listing| {
+            let serial_no = listing.serial;
             let name = device_name(env, &serial_no).map_err(Error::NameFailed)?;
             let model =
                 get_prop(env, &serial_no, "ro.product.model").map_err(Error::ModelFailed)?;
+            let os_version = get_prop(env, &serial_no, "ro.build.version.release")
+                .map_err(Error::OsVersionFailed)?;
             let abi = get_prop(env, &serial_no, "ro.product.cpu.abi").map_err(Error::AbiFailed)?;
             let target = Target::for_abi(&abi).ok_or_else(|| Error::AbiInvalid(abi.clone()))?;
-            Ok(Device::new(serial_no, name, model, target))
+            Ok(Device::new(serial_no, name, model, os_version, target))
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn devices_are_listed_with_their_status_and_attributes() {
+        let raw_list = "\
+List of devices attached
+emulator-5554          device product:sdk_gphone_x86 model:sdk_gphone_x86 device:generic_x86 transport_id:1
+0123456789ABCDEF       unauthorized usb:1-1 transport_id:2
+192.168.1.20:5555      offline transport_id:3
+R58M12345              no permissions (user in plugdev group); see [http://developer.android.com/tools/device.html] usb:1-2
+
+";
+        let listings = parse_device_list_long(raw_list);
+        assert_eq!(
+            listings[0],
+            DeviceListing {
+                serial: "emulator-5554".to_owned(),
+                status: DeviceStatus::Ready,
+                model: Some("sdk_gphone_x86".to_owned()),
+                product: Some("sdk_gphone_x86".to_owned()),
+                transport_id: Some("1".to_owned()),
+            }
+        );
+        let statuses = listings
+            .iter()
+            .map(|listing| (listing.serial.as_str(), listing.status.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            vec![
+                ("emulator-5554", DeviceStatus::Ready),
+                ("0123456789ABCDEF", DeviceStatus::Unauthorized),
+                ("192.168.1.20:5555", DeviceStatus::Offline),
+                (
+                    "R58M12345",
+                    DeviceStatus::Other("no permissions".to_owned())
+                ),
+            ]
+        );
+        assert_eq!(listings[1].model, None);
+        assert_eq!(listings[2].transport_id.as_deref(), Some("3"));
+    }
+}
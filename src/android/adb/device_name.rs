@@ -30,7 +30,7 @@ impl Reportable for Error {
 
 pub fn device_name(env: &Env, serial_no: &str) -> Result<String, Error> {
     let name_re = regex!(r"\bname: (?P<name>.*)");
-    let output = super::run_checked(&mut adb(env, serial_no).with_args(&[
+    let output = super::run_checked(&adb(env, serial_no).with_args(&[
         "shell",
         "dumpsys",
         "bluetooth_manager",
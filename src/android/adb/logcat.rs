@@ -0,0 +1,169 @@
+use super::super::env::Env;
+use crate::{
+    env::ExplicitEnv as _,
+    util::{CommandError, Invocation, Runner},
+};
+use colored::{Color, Colorize as _};
+use once_cell_regex::regex;
+use std::{
+    fmt::{self, Display},
+    io::{self, BufRead as _, BufReader},
+    process::Stdio,
+};
+
+#[derive(Debug)]
+pub enum LogcatError {
+    CommandFailed(CommandError),
+    ReadFailed(io::Error),
+}
+
+impl Display for LogcatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CommandFailed(err) => write!(f, "Failed to run `adb logcat`: {}", err),
+            Self::ReadFailed(err) => write!(f, "Failed to read `adb logcat` output: {}", err),
+        }
+    }
+}
+
+/// Gets the `logcat` args for only showing `tag_filters`. Filters without a
+/// priority (i.e. `RustStdoutStderr` vs `RustStdoutStderr:D`) show everything
+/// for that tag. Everything else gets silenced with `*:S`, unless there aren't
+/// any filters, in which case nothing is. Output always uses the `threadtime`
+/// format, so that it can be parsed by [`parse_line`].
+pub fn logcat_args(tag_filters: &[&str]) -> Vec<String> {
+    let mut args = vec![
+        "logcat".to_owned(),
+        "-v".to_owned(),
+        "threadtime".to_owned(),
+    ];
+    if !tag_filters.is_empty() {
+        args.extend(tag_filters.iter().map(|filter| {
+            if filter.contains(':') {
+                (*filter).to_owned()
+            } else {
+                format!("{}:V", filter)
+            }
+        }));
+        args.push("*:S".to_owned());
+    }
+    args
+}
+
+/// A line of `threadtime` output, which looks like this:
+///
+/// ```text
+/// 10-24 13:52:01.123  1234  1250 I RustStdoutStderr: hello from Rust!
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LogLine<'a> {
+    pub pid: u32,
+    pub priority: char,
+    pub tag: &'a str,
+    pub message: &'a str,
+}
+
+pub fn parse_line(line: &str) -> Option<LogLine<'_>> {
+    let caps =
+        regex!(r"^\d+-\d+ [\d:.]+\s+(\d+)\s+\d+ ([VDIWEFS]) (.*?)\s*: (.*)$").captures(line)?;
+    Some(LogLine {
+        pid: caps[1].parse().ok()?,
+        priority: caps[2].chars().next()?,
+        tag: caps.get(3)?.as_str(),
+        message: caps.get(4)?.as_str(),
+    })
+}
+
+/// The priorities in increasing order of severity, which is what `logcat`
+/// filters by.
+static PRIORITIES: &str = "VDIWEF";
+
+/// Checks if `priority` is at least as severe as `min_priority`.
+pub fn priority_at_least(priority: char, min_priority: char) -> bool {
+    PRIORITIES.find(priority) >= PRIORITIES.find(min_priority)
+}
+
+pub fn colorize(line: &LogLine<'_>) -> String {
+    let color = match line.priority {
+        'V' | 'D' => Color::BrightBlack,
+        'I' => Color::Green,
+        'W' => Color::Yellow,
+        _ => Color::BrightRed,
+    };
+    format!(
+        "{} {}",
+        format!("{} {}:", line.priority, line.tag)
+            .color(color)
+            .bold(),
+        line.message.color(color)
+    )
+}
+
+/// Streams `adb logcat`, calling `on_line` for each line as it comes in. This
+/// only returns once `logcat` exits, which it normally doesn't do on its own;
+/// it gets killed along with us when we're interrupted.
+pub fn adb_logcat(
+    env: &Env,
+    serial_no: Option<&str>,
+    tag_filters: &[&str],
+    mut on_line: impl FnMut(&str),
+) -> Result<(), LogcatError> {
+    let mut command = Invocation::pure("adb").with_env_vars(env.explicit_env());
+    if let Some(serial_no) = serial_no {
+        command.add_args(&["-s", serial_no]);
+    }
+    let mut child = Runner::global()
+        .spawn(
+            &command.with_args(logcat_args(tag_filters)),
+            Stdio::inherit(),
+            Stdio::piped(),
+            Stdio::inherit(),
+        )
+        .map_err(LogcatError::CommandFailed)?;
+    let reader = child
+        .take_stdout()
+        .expect("developer error: `adb logcat` stdout not captured");
+    for line in BufReader::new(reader).lines() {
+        on_line(&line.map_err(LogcatError::ReadFailed)?);
+    }
+    child.wait().map_err(LogcatError::CommandFailed)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_filtered_tags_are_shown() {
+        assert_eq!(logcat_args(&[]), ["logcat", "-v", "threadtime"]);
+        assert_eq!(
+            logcat_args(&["RustStdoutStderr", "ActivityManager:W"]),
+            [
+                "logcat",
+                "-v",
+                "threadtime",
+                "RustStdoutStderr:V",
+                "ActivityManager:W",
+                "*:S"
+            ]
+        );
+    }
+
+    #[test]
+    fn threadtime_lines_are_parsed() {
+        assert_eq!(
+            parse_line("10-24 13:52:01.123  1234  1250 I RustStdoutStderr: hello: from Rust!"),
+            Some(LogLine {
+                pid: 1234,
+                priority: 'I',
+                tag: "RustStdoutStderr",
+                message: "hello: from Rust!",
+            })
+        );
+        assert_eq!(parse_line("--------- beginning of main"), None);
+        assert!(priority_at_least('E', 'W'));
+        assert!(priority_at_least('W', 'W'));
+        assert!(!priority_at_least('D', 'I'));
+    }
+}
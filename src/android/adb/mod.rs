@@ -1,50 +1,49 @@
 pub mod device_list;
 pub mod device_name;
 pub mod get_prop;
+pub mod logcat;
+pub mod pull;
+pub mod push;
+pub mod wait_for_boot;
 
-pub use self::{device_list::device_list, device_name::device_name, get_prop::get_prop};
+pub use self::{
+    device_list::device_list, device_name::device_name, get_prop::get_prop, logcat::adb_logcat,
+    pull::pull, push::push, wait_for_boot::wait_for_emulator_boot,
+};
 
 use super::env::Env;
-use crate::{env::ExplicitEnv as _, util::cli::Report};
-use std::str;
+use crate::{
+    env::ExplicitEnv as _,
+    util::{cli::Report, CommandError, Invocation, Output, Runner},
+};
 
-pub fn adb(env: &Env, serial_no: &str) -> bossy::Command {
-    bossy::Command::pure("adb")
+pub fn adb(env: &Env, serial_no: &str) -> Invocation {
+    Invocation::pure("adb")
         .with_env_vars(env.explicit_env())
         .with_args(&["-s", serial_no])
 }
 
 #[derive(Debug)]
 pub enum RunCheckedError {
-    InvalidUtf8(str::Utf8Error),
     Unauthorized,
-    CommandFailed(bossy::Error),
+    CommandFailed(CommandError),
 }
 
 impl RunCheckedError {
     pub fn report(&self, msg: &str) -> Report {
         match self {
-            Self::InvalidUtf8(err) => {
-                Report::error(msg, format!("stderr contained invalid UTF-8: {}", err))
-            }
             Self::Unauthorized => Report::action_request(msg, "This device doesn't yet trust this computer. On the device, you should see a prompt like \"Allow USB debugging?\". Pressing \"Allow\" should fix this."),
-            Self::CommandFailed(err) => Report::error(msg, err),
+            Self::CommandFailed(err) => err.report(msg),
         }
     }
 }
 
-fn run_checked(command: &mut bossy::Command) -> Result<bossy::Output, RunCheckedError> {
-    let result = command.run_and_wait_for_output();
-    if let Err(err) = &result {
-        if let Some(stderr) = err
-            .stderr_str()
-            .transpose()
-            .map_err(RunCheckedError::InvalidUtf8)?
-        {
-            if stderr.contains("error: device unauthorized") {
-                return Err(RunCheckedError::Unauthorized);
-            }
+fn run_checked(command: &Invocation) -> Result<Output, RunCheckedError> {
+    Runner::global().capture(command).map_err(|err| {
+        if err.stderr_tail().contains("error: device unauthorized") {
+            RunCheckedError::Unauthorized
+        } else {
+            RunCheckedError::CommandFailed(err)
         }
-    }
-    result.map_err(RunCheckedError::CommandFailed)
+    })
 }
@@ -0,0 +1,18 @@
+use super::adb;
+use crate::android::env::Env;
+use std::path::Path;
+
+/// Copies `remote` off of the device to `local`.
+pub fn pull(
+    env: &Env,
+    serial_no: &str,
+    remote: &str,
+    local: &Path,
+) -> Result<(), super::RunCheckedError> {
+    super::run_checked(
+        &adb(env, serial_no)
+            .with_args(&["pull", remote])
+            .with_arg(local),
+    )
+    .map(|_| ())
+}
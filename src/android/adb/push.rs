@@ -0,0 +1,19 @@
+use super::adb;
+use crate::android::env::Env;
+use std::path::Path;
+
+/// Copies `local` onto the device at `remote`.
+pub fn push(
+    env: &Env,
+    serial_no: &str,
+    local: &Path,
+    remote: &str,
+) -> Result<(), super::RunCheckedError> {
+    super::run_checked(
+        &adb(env, serial_no)
+            .with_arg("push")
+            .with_arg(local)
+            .with_arg(remote),
+    )
+    .map(|_| ())
+}
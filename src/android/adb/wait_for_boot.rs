@@ -0,0 +1,133 @@
+use super::super::env::Env;
+use crate::util::{CommandError, CommandRunner, Invocation};
+use std::{
+    fmt::{self, Display},
+    thread,
+    time::{Duration, Instant},
+};
+
+static POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub enum WaitForBootError {
+    CommandFailed(CommandError),
+    TimedOut {
+        serial_no: String,
+        timeout: Duration,
+    },
+}
+
+impl Display for WaitForBootError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CommandFailed(err) => {
+                write!(f, "Failed to check if emulator finished booting: {}", err)
+            }
+            Self::TimedOut { serial_no, timeout } => write!(
+                f,
+                "Emulator {:?} didn't finish booting within {:?}",
+                serial_no, timeout
+            ),
+        }
+    }
+}
+
+// While an emulator is still starting up, `adb` can fail to talk to it at all.
+fn is_not_ready_yet(err: &CommandError) -> bool {
+    let stderr = err.stderr_tail();
+    stderr.contains("device offline")
+        || stderr.contains("no devices")
+        || stderr.contains("not found")
+        || stderr.contains("device still connecting")
+}
+
+/// Blocks until `sys.boot_completed` is set on the emulator, since installing
+/// onto one that's still booting fails.
+pub fn wait_for_emulator_boot(
+    runner: &impl CommandRunner,
+    env: &Env,
+    serial_no: &str,
+    timeout: Duration,
+) -> Result<(), WaitForBootError> {
+    let command = super::adb(env, serial_no).with_args(&["shell", "getprop", "sys.boot_completed"]);
+    poll_boot_completed(runner, &command, serial_no, timeout, POLL_INTERVAL)
+}
+
+fn poll_boot_completed(
+    runner: &impl CommandRunner,
+    command: &Invocation,
+    serial_no: &str,
+    timeout: Duration,
+    poll_interval: Duration,
+) -> Result<(), WaitForBootError> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match runner.capture(command) {
+            Ok(output) => {
+                if output.stdout_str().map_or(false, |out| out.trim() == "1") {
+                    log::info!("emulator {:?} finished booting", serial_no);
+                    return Ok(());
+                }
+            }
+            Err(err) if is_not_ready_yet(&err) => {
+                log::info!("emulator {:?} isn't reachable yet", serial_no);
+            }
+            Err(err) => return Err(WaitForBootError::CommandFailed(err)),
+        }
+        if Instant::now() >= deadline {
+            return Err(WaitForBootError::TimedOut {
+                serial_no: serial_no.to_owned(),
+                timeout,
+            });
+        }
+        thread::sleep(poll_interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::RecordingRunner;
+
+    fn poll(runner: &RecordingRunner, timeout: Duration) -> Result<(), WaitForBootError> {
+        let command = Invocation::impure("adb").with_args(&["shell", "getprop"]);
+        poll_boot_completed(
+            runner,
+            &command,
+            "emulator-5554",
+            timeout,
+            Duration::from_millis(1),
+        )
+    }
+
+    #[test]
+    fn polls_until_boot_completes() {
+        let runner = RecordingRunner::new()
+            .with_output("0\n")
+            .with_output("0\n")
+            .with_output("1\n");
+        poll(&runner, Duration::from_secs(10)).unwrap();
+        assert_eq!(runner.calls().len(), 3);
+    }
+
+    #[test]
+    fn unreachable_emulators_are_still_booting() {
+        let runner = RecordingRunner::new()
+            .with_failure(1, "error: device offline\n")
+            .with_output("1\n");
+        poll(&runner, Duration::from_secs(10)).unwrap();
+        assert_eq!(runner.calls().len(), 2);
+
+        let runner = RecordingRunner::new().with_failure(1, "error: permission denied\n");
+        let err = poll(&runner, Duration::from_secs(10)).unwrap_err();
+        assert!(matches!(err, WaitForBootError::CommandFailed(_)), "{}", err);
+    }
+
+    #[test]
+    fn gives_up_at_the_timeout() {
+        // Once the canned results run out, every poll gets empty output
+        let runner = RecordingRunner::new();
+        let err = poll(&runner, Duration::from_millis(20)).unwrap_err();
+        assert!(matches!(err, WaitForBootError::TimedOut { .. }), "{}", err);
+    }
+}
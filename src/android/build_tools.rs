@@ -0,0 +1,86 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub type BuildToolsVersion = (u32, u32, u32);
+
+fn parse_build_tools_version(name: &str) -> Option<BuildToolsVersion> {
+    let mut components = name.split('.').map(|component| component.parse::<u32>());
+    match (
+        components.next(),
+        components.next(),
+        components.next(),
+        components.next(),
+    ) {
+        (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch)), None) => Some((major, minor, patch)),
+        // Things like `31.0.0-rc1` and stray files aren't something we'd want
+        // to hand to Gradle anyway.
+        _ => None,
+    }
+}
+
+fn sdk_root_from_env() -> io::Result<PathBuf> {
+    std::env::var_os("ANDROID_SDK_ROOT")
+        .or_else(|| std::env::var_os("ANDROID_HOME"))
+        .map(PathBuf::from)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "neither `ANDROID_SDK_ROOT` nor `ANDROID_HOME` is set",
+            )
+        })
+}
+
+/// Lists the build-tools versions installed in the SDK, highest first. If
+/// `sdk_root` isn't given, it's taken from `ANDROID_SDK_ROOT` (or
+/// `ANDROID_HOME`).
+pub fn android_build_tools_versions(sdk_root: Option<&Path>) -> io::Result<Vec<BuildToolsVersion>> {
+    let dir = match sdk_root {
+        Some(sdk_root) => sdk_root.join("build-tools"),
+        None => sdk_root_from_env()?.join("build-tools"),
+    };
+    let mut versions = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let name = entry.file_name();
+        match name.to_str().and_then(parse_build_tools_version) {
+            Some(version) => versions.push(version),
+            None => log::debug!("skipping unrecognized build-tools dir {:?}", name),
+        }
+    }
+    versions.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(versions)
+}
+
+pub fn latest_android_build_tools_version(
+    sdk_root: Option<&Path>,
+) -> io::Result<Option<BuildToolsVersion>> {
+    android_build_tools_versions(sdk_root).map(|versions| versions.first().copied())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_tools_versions_are_listed_highest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let build_tools = dir.path().join("build-tools");
+        for name in &["29.0.3", "30.0.2", "31.0.0-rc1", "4.1.1", "30.0.10"] {
+            fs::create_dir_all(build_tools.join(name)).unwrap();
+        }
+        fs::write(build_tools.join("32.0.0"), "not a dir").unwrap();
+        assert_eq!(
+            android_build_tools_versions(Some(dir.path())).unwrap(),
+            vec![(30, 0, 10), (30, 0, 2), (29, 0, 3), (4, 1, 1)]
+        );
+        assert_eq!(
+            latest_android_build_tools_version(Some(dir.path())).unwrap(),
+            Some((30, 0, 10))
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use super::{
+    config::{Config, SigningError},
+    env::Env,
+    gradle,
+    jnilibs::{self, JniLibs},
+    outputs::{self, OutputsError},
+    target::Target,
+};
+use crate::{
+    opts::{NoiseLevel, Profile},
+    util::cli::{Report, Reportable},
+};
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum BundleError {
+    LibSymlinkCleaningFailed(jnilibs::RemoveBrokenLinksError),
+    SigningFailed(SigningError),
+    BundleFailed(gradle::Error),
+    OutputsMissing(OutputsError),
+}
+
+impl Reportable for BundleError {
+    fn report(&self) -> Report {
+        match self {
+            Self::LibSymlinkCleaningFailed(err) => err.report(),
+            Self::SigningFailed(err) => err.report(),
+            Self::BundleFailed(err) => err.report("Failed to build app bundle"),
+            Self::OutputsMissing(err) => {
+                Report::error("Failed to find app bundle Gradle built", err)
+            }
+        }
+    }
+}
+
+/// Builds an Android App Bundle for `target` via Gradle, returning the paths
+/// of the `.aab` files it produced. Release bundles are signed if
+/// `android.signing` is configured; otherwise, they'll need to be signed
+/// before they can be uploaded to the Play Console.
+pub fn bundle(
+    config: &Config,
+    env: &Env,
+    target: &Target<'_>,
+    noise_level: NoiseLevel,
+    profile: Profile,
+) -> Result<Vec<PathBuf>, BundleError> {
+    use heck::CamelCase as _;
+    JniLibs::remove_broken_links(config).map_err(BundleError::LibSymlinkCleaningFailed)?;
+    let signing_env = match (profile, config.signing()) {
+        (Profile::Release, Some(signing)) => {
+            signing.gradle_env().map_err(BundleError::SigningFailed)?
+        }
+        (Profile::Release, None) => {
+            log::warn!(
+                "`{}.signing` isn't configured, so the bundle will be unsigned",
+                super::NAME
+            );
+            Vec::new()
+        }
+        (Profile::Debug, _) => Vec::new(),
+    };
+    let flavor = target.arch.to_camel_case();
+    let build_ty = profile.as_str().to_camel_case();
+    gradle::run(
+        gradle::gradlew(config, env)
+            .with_env_vars(signing_env)
+            .with_arg(format!("bundle{}{}", flavor, build_ty))
+            .with_arg(gradle::log_level(noise_level)),
+        noise_level,
+    )
+    .map_err(BundleError::BundleFailed)?;
+    // Bundle outputs are named after the variant without a separator, unlike
+    // APK outputs.
+    let variant = format!("{}{}", target.arch, build_ty);
+    outputs::gradle_build_outputs(&config.project_dir().join("app"), &variant)
+        .map_err(BundleError::OutputsMissing)
+}
@@ -1,26 +1,35 @@
 use crate::{
     android::{
         adb,
+        bundle::{self, BundleError},
         config::{Config, Metadata},
-        device::{Device, RunError, StacktraceError},
-        env::{Env, Error as EnvError},
+        device::{self, Device, LogError, RunError, StacktraceError},
+        env::{self, Env, Error as EnvError},
+        manifest, ndk,
+        profiler::{self, Profiler},
         target::{BuildError, CompileLibError, Target},
+        test_runner::{self, TestError},
         NAME,
     },
     config::{
+        hooks::{self, Stage},
         metadata::{self, Metadata as OmniMetadata},
         Config as OmniConfig, LoadOrGenError,
     },
     define_device_prompt,
     device::PromptError,
-    opts, os,
-    target::{call_for_targets_with_fallback, TargetInvalid, TargetTrait as _},
+    opts, profiling,
+    target::{call_for_targets_with_fallback, get_targets, TargetInvalid, TargetTrait as _},
     util::{
+        self,
         cli::{
             self, Exec, GlobalFlags, Report, Reportable, TextWrapper, VERSION_LONG, VERSION_SHORT,
         },
+        output::{self, Event},
         prompt,
     },
+    version::{self, Stamp},
+    watch,
 };
 use std::path::PathBuf;
 use structopt::StructOpt;
@@ -55,22 +64,108 @@ pub enum Command {
         #[structopt(name = "targets", default_value = Target::DEFAULT_KEY, possible_values = Target::name_list())]
         targets: Vec<String>,
     },
+    #[structopt(
+        name = "clean",
+        about = "Removes build artifacts for target(s), leaving other targets alone"
+    )]
+    Clean {
+        #[structopt(name = "targets", default_value = Target::DEFAULT_KEY, possible_values = Target::name_list())]
+        targets: Vec<String>,
+        #[structopt(flatten)]
+        profile: cli::Profile,
+    },
     #[structopt(name = "build", about = "Builds dynamic libraries for target(s)")]
     Build {
         #[structopt(name = "targets", default_value = Target::DEFAULT_KEY, possible_values = Target::name_list())]
         targets: Vec<String>,
         #[structopt(flatten)]
         profile: cli::Profile,
+        #[structopt(
+            short = "j",
+            long = "jobs",
+            help = "Max number of targets to build at once (defaults to all of them)"
+        )]
+        jobs: Option<usize>,
+    },
+    #[structopt(name = "bundle", about = "Builds Android App Bundles for target(s)")]
+    Bundle {
+        #[structopt(name = "targets", default_value = Target::DEFAULT_KEY, possible_values = Target::name_list())]
+        targets: Vec<String>,
+        #[structopt(flatten)]
+        profile: cli::Profile,
+        #[structopt(flatten)]
+        build_number: cli::BuildNumber,
     },
     #[structopt(name = "run", about = "Deploys APK to connected device")]
     Run {
         #[structopt(flatten)]
         profile: cli::Profile,
+        #[structopt(flatten)]
+        device: cli::Device,
+        #[structopt(flatten)]
+        build_number: cli::BuildNumber,
+        #[structopt(
+            long = "watch",
+            help = "Rebuild and restart the app whenever your project changes"
+        )]
+        watch: bool,
+    },
+    #[structopt(
+        name = "profile",
+        about = "Profiles a release build on a connected device"
+    )]
+    Profile {
+        #[structopt(flatten)]
+        device: cli::Device,
+        #[structopt(flatten)]
+        duration: cli::ProfileDuration,
+        #[structopt(
+            long = "profiler",
+            help = "Which profiler to record with",
+            default_value = "simpleperf",
+            possible_values = Profiler::NAMES
+        )]
+        profiler: Profiler,
+    },
+    #[structopt(name = "test", about = "Runs tests on a connected device or emulator")]
+    Test {
+        #[structopt(flatten)]
+        device: cli::Device,
+        #[structopt(
+            name = "ARGS",
+            help = "Args to pass to each test harness, i.e. a filter for test names",
+            last = true
+        )]
+        args: Vec<String>,
+    },
+    #[structopt(name = "log", about = "Streams the running app's logs from a device")]
+    Log {
+        #[structopt(flatten)]
+        device: cli::Device,
+        #[structopt(
+            long = "symbolize",
+            help = "Run native crash dumps through `ndk-stack` to show Rust symbols"
+        )]
+        symbolize: bool,
     },
     #[structopt(name = "st", about = "Displays a detailed stacktrace for a device")]
-    Stacktrace,
+    Stacktrace {
+        #[structopt(flatten)]
+        device: cli::Device,
+    },
+    #[structopt(name = "install-ndk", about = "Installs the NDK using `sdkmanager`")]
+    InstallNdk {
+        #[structopt(
+            long = "ndk-version",
+            help = "The NDK revision to install, as listed by `sdkmanager --list`",
+            default_value = ndk::DEFAULT_INSTALL_VERSION
+        )]
+        version: String,
+    },
     #[structopt(name = "list", about = "Lists connected devices")]
     List,
+    #[structopt(name = "devices", about = "Shows details for connected devices")]
+    Devices,
 }
 
 #[derive(Debug)]
@@ -82,12 +177,21 @@ pub enum Error {
     MetadataFailed(metadata::Error),
     Unsupported,
     ProjectDirAbsent { project_dir: PathBuf },
-    OpenFailed(bossy::Error),
+    OpenFailed(util::OpenInError),
+    VersionStampFailed(version::Error),
     CheckFailed(CompileLibError),
+    CleanFailed(util::RunCargoError),
     BuildFailed(BuildError),
+    BundleFailed(BundleError),
     RunFailed(RunError),
+    ProfileFailed(profiler::ProfileError),
+    TestFailed(TestError),
+    WatchFailed(watch::Error),
+    LogFailed(LogError),
     StacktraceFailed(StacktraceError),
+    NdkInstallFailed(ndk::InstallError),
     ListFailed(adb::device_list::Error),
+    HookFailed(hooks::Error),
 }
 
 impl Reportable for Error {
@@ -107,11 +211,20 @@ impl Reportable for Error {
                 ),
             ),
             Self::OpenFailed(err) => Report::error("Failed to open project in Android Studio", err),
+            Self::VersionStampFailed(err) => err.report(),
             Self::CheckFailed(err) => err.report(),
+            Self::CleanFailed(err) => err.report("Failed to run `cargo clean`"),
             Self::BuildFailed(err) => err.report(),
+            Self::BundleFailed(err) => err.report(),
             Self::RunFailed(err) => err.report(),
+            Self::ProfileFailed(err) => err.report(),
+            Self::TestFailed(err) => err.report(),
+            Self::WatchFailed(err) => err.report(),
+            Self::LogFailed(err) => err.report(),
             Self::StacktraceFailed(err) => err.report(),
+            Self::NdkInstallFailed(err) => err.report(),
             Self::ListFailed(err) => err.report(),
+            Self::HookFailed(err) => err.report(),
         }
     }
 }
@@ -126,7 +239,9 @@ impl Exec for Input {
     fn exec(self, wrapper: &TextWrapper) -> Result<(), Self::Report> {
         define_device_prompt!(adb::device_list, adb::device_list::Error, Android);
         fn detect_target_ok<'a>(env: &Env) -> Option<&'a Target<'a>> {
-            device_prompt(env).map(|device| device.target()).ok()
+            device_prompt(env, None, opts::NonInteractive::No)
+                .map(|device| device.target())
+                .ok()
         }
 
         fn with_config(
@@ -134,8 +249,14 @@ impl Exec for Input {
             wrapper: &TextWrapper,
             f: impl FnOnce(&Config, &Metadata) -> Result<(), Error>,
         ) -> Result<(), Error> {
-            let (config, _origin) = OmniConfig::load_or_gen(".", non_interactive, wrapper)
-                .map_err(Error::ConfigFailed)?;
+            let (config, _origin) = OmniConfig::load_or_gen(
+                ".",
+                non_interactive,
+                &Default::default(),
+                &prompt::Stdin,
+                wrapper,
+            )
+            .map_err(Error::ConfigFailed)?;
             let metadata =
                 OmniMetadata::load(&config.app().root_dir()).map_err(Error::MetadataFailed)?;
             if metadata.android().supported() {
@@ -155,8 +276,30 @@ impl Exec for Input {
             }
         }
 
+        fn stamp_version(config: &Config, build_number: Option<u32>) -> Result<(), Error> {
+            let stamp = Stamp::new(config.app().root_dir(), build_number)
+                .map_err(Error::VersionStampFailed)?;
+            manifest::stamp(config, &stamp).map_err(Error::VersionStampFailed)
+        }
+
+        fn run_pre_run_hooks(
+            config: &Config,
+            target: &Target,
+            profile: opts::Profile,
+        ) -> Result<(), Error> {
+            config
+                .app()
+                .hooks()
+                .run(
+                    Stage::PreRun,
+                    &hooks::Context::new("android", Some(target.triple), profile.as_str()),
+                )
+                .map_err(Error::HookFailed)
+        }
+
         fn open_in_android_studio(config: &Config) -> Result<(), Error> {
-            os::open_file_with("Android Studio", config.project_dir()).map_err(Error::OpenFailed)
+            util::open_in(util::OpenTarget::AndroidStudio, &config.project_dir())
+                .map_err(Error::OpenFailed)
         }
 
         let Self {
@@ -164,9 +307,20 @@ impl Exec for Input {
                 GlobalFlags {
                     noise_level,
                     non_interactive,
+                    ..
                 },
             command,
         } = self;
+        // This is how you'd fix not having an NDK, so it can't require one.
+        if let Command::InstallNdk { version } = &command {
+            let sdk_root = env::sdk_root().map_err(Error::EnvInitFailed)?;
+            let ndk_home = ndk::install(&sdk_root, version).map_err(Error::NdkInstallFailed)?;
+            println!(
+                "Installed NDK at {:?}; the newest installed NDK is used unless `NDK_HOME` or similar is set.",
+                ndk_home
+            );
+            return Ok(());
+        }
         let env = Env::new().map_err(Error::EnvInitFailed)?;
         match command {
             Command::Open => with_config(non_interactive, wrapper, |config, _| {
@@ -175,7 +329,7 @@ impl Exec for Input {
             }),
             Command::Check { targets } => {
                 with_config(non_interactive, wrapper, |config, metadata| {
-                    let force_color = opts::ForceColor::Yes;
+                    let force_color = opts::ForceColor::from_bool(!util::is_ci());
                     call_for_targets_with_fallback(
                         targets.iter(),
                         &detect_target_ok,
@@ -189,45 +343,177 @@ impl Exec for Input {
                     .map_err(Error::TargetInvalid)?
                 })
             }
+            Command::Clean {
+                targets,
+                profile: cli::Profile { profile },
+            } => with_config(non_interactive, wrapper, |config, _| {
+                call_for_targets_with_fallback(
+                    targets.iter(),
+                    &detect_target_ok,
+                    &env,
+                    |target: &Target| {
+                        util::cargo_clean_target(config.app().root_dir(), target.triple, profile)
+                            .map_err(Error::CleanFailed)
+                    },
+                )
+                .map_err(Error::TargetInvalid)?
+            }),
             Command::Build {
                 targets,
                 profile: cli::Profile { profile },
+                jobs,
             } => with_config(non_interactive, wrapper, |config, metadata| {
                 ensure_init(config)?;
-                let force_color = opts::ForceColor::Yes;
+                let force_color = opts::ForceColor::from_bool(!util::is_ci());
+                let targets = get_targets(targets.iter(), Some((&detect_target_ok, &env)))
+                    .map_err(Error::TargetInvalid)?;
+                Target::build_all(
+                    &targets,
+                    config,
+                    metadata,
+                    &env,
+                    noise_level,
+                    force_color,
+                    profile,
+                    jobs.unwrap_or_else(|| targets.len()),
+                )
+                .map_err(Error::BuildFailed)
+            }),
+            Command::Bundle {
+                targets,
+                profile: cli::Profile { profile },
+                build_number: cli::BuildNumber { build_number },
+            } => with_config(non_interactive, wrapper, |config, _| {
+                ensure_init(config)?;
+                stamp_version(config, build_number)?;
                 call_for_targets_with_fallback(
                     targets.iter(),
                     &detect_target_ok,
                     &env,
                     |target: &Target| {
-                        target
-                            .build(config, metadata, &env, noise_level, force_color, profile)
-                            .map_err(Error::BuildFailed)
+                        let bundles = bundle::bundle(config, &env, target, noise_level, profile)
+                            .map_err(Error::BundleFailed)?;
+                        // Printed on their own lines so that CI scripts can
+                        // easily pick them up.
+                        for path in bundles {
+                            println!("{}", path.display());
+                            output::emit(&Event::Artifact {
+                                kind: "bundle",
+                                path: &path,
+                            });
+                        }
+                        Ok(())
                     },
                 )
                 .map_err(Error::TargetInvalid)?
             }),
             Command::Run {
                 profile: cli::Profile { profile },
+                device: cli::Device { device },
+                build_number: cli::BuildNumber { build_number },
+                watch,
             } => with_config(non_interactive, wrapper, |config, _| {
                 ensure_init(config)?;
-                device_prompt(&env)
+                stamp_version(config, build_number)?;
+                let device = device_prompt(&env, device.as_deref(), non_interactive)
+                    .map_err(Error::DevicePromptFailed)?;
+                run_pre_run_hooks(config, device.target(), profile)?;
+                if watch {
+                    println!(
+                        "Logs aren't shown while watching; run `cargo android log` to see them."
+                    );
+                    watch::watch(
+                        wrapper,
+                        config.app().root_dir(),
+                        &[config.project_dir()],
+                        || device.deploy(config, &env, noise_level, profile),
+                    )
+                    .map_err(Error::WatchFailed)
+                } else {
+                    device
+                        .run(config, &env, noise_level, profile)
+                        .map_err(Error::RunFailed)
+                }
+            }),
+            Command::Profile {
+                device: cli::Device { device },
+                duration: cli::ProfileDuration { duration },
+                profiler,
+            } => {
+                let (key, value) = profiling::DEBUG_INFO_VAR;
+                let env = env.with_var(key, value);
+                with_config(non_interactive, wrapper, |config, _| {
+                    ensure_init(config)?;
+                    let device = device_prompt(&env, device.as_deref(), non_interactive)
+                        .map_err(Error::DevicePromptFailed)?;
+                    run_pre_run_hooks(config, device.target(), opts::Profile::Release)?;
+                    let trace_path =
+                        profiler::profile(config, &env, &device, noise_level, profiler, duration)
+                            .map_err(Error::ProfileFailed)?;
+                    profiling::report_trace(&trace_path);
+                    Ok(())
+                })
+            }
+            Command::Test {
+                device: cli::Device { device },
+                args,
+            } => with_config(non_interactive, wrapper, |config, metadata| {
+                ensure_init(config)?;
+                let device = device_prompt(&env, device.as_deref(), non_interactive)
+                    .map_err(Error::DevicePromptFailed)?;
+                test_runner::test(
+                    config,
+                    metadata,
+                    &env,
+                    &device,
+                    noise_level,
+                    opts::ForceColor::from_bool(!util::is_ci()),
+                    &args,
+                )
+                .map_err(Error::TestFailed)
+            }),
+            Command::Log {
+                device: cli::Device { device },
+                symbolize,
+            } => with_config(non_interactive, wrapper, |config, _| {
+                ensure_init(config)?;
+                device_prompt(&env, device.as_deref(), non_interactive)
                     .map_err(Error::DevicePromptFailed)?
-                    .run(config, &env, noise_level, profile)
-                    .map_err(Error::RunFailed)
+                    .log(
+                        config,
+                        &env,
+                        device::min_log_priority(noise_level),
+                        symbolize,
+                    )
+                    .map_err(Error::LogFailed)
             }),
-            Command::Stacktrace => with_config(non_interactive, wrapper, |config, _| {
+            Command::Stacktrace {
+                device: cli::Device { device },
+            } => with_config(non_interactive, wrapper, |config, _| {
                 ensure_init(config)?;
-                device_prompt(&env)
+                device_prompt(&env, device.as_deref(), non_interactive)
                     .map_err(Error::DevicePromptFailed)?
                     .stacktrace(config, &env)
                     .map_err(Error::StacktraceFailed)
             }),
+            Command::InstallNdk { .. } => unreachable!("`install-ndk` was handled above"),
             Command::List => adb::device_list(&env)
                 .map_err(Error::ListFailed)
                 .map(|device_list| {
                     prompt::list_display_only(device_list.iter(), device_list.len());
                 }),
+            Command::Devices => {
+                adb::device_list(&env)
+                    .map_err(Error::ListFailed)
+                    .map(|device_list| {
+                        println!("Connected Android devices:");
+                        let summaries = device_list.iter().map(Device::summary).collect::<Vec<_>>();
+                        for summary in &summaries {
+                            output::emit(&Event::Device(summary));
+                        }
+                        prompt::list_display_only(summaries.iter(), summaries.len());
+                    })
+            }
         }
     }
 }
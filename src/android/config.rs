@@ -1,6 +1,10 @@
+use super::{gradle, ndk};
 use crate::{
-    config::app::App,
-    util::{self, cli::Report},
+    config::{app::App, metadata::CargoSettings},
+    util::{
+        self,
+        cli::{Report, Reportable},
+    },
 };
 use serde::{Deserialize, Serialize};
 use std::{
@@ -9,6 +13,7 @@ use std::{
 };
 
 const DEFAULT_MIN_SDK_VERSION: u32 = 24;
+static DEFAULT_STORE_PASSWORD_ENV: &str = "CARGO_MOBILE_ANDROID_STORE_PASSWORD";
 const DEFAULT_VULKAN_VALIDATION: bool = true;
 static DEFAULT_PROJECT_DIR: &str = "gen/android";
 
@@ -22,6 +27,8 @@ pub struct Metadata {
     supported: bool,
     #[serde(default)]
     features: Option<Vec<String>>,
+    #[serde(flatten)]
+    cargo: CargoSettings,
 }
 
 impl Default for Metadata {
@@ -29,6 +36,7 @@ impl Default for Metadata {
         Self {
             supported: true,
             features: None,
+            cargo: Default::default(),
         }
     }
 }
@@ -39,12 +47,16 @@ impl Metadata {
     }
 
     pub fn no_default_features(&self) -> bool {
-        self.features.is_some()
+        self.cargo.no_default_features(self.features())
     }
 
     pub fn features(&self) -> Option<&[String]> {
         self.features.as_deref()
     }
+
+    pub fn cargo(&self) -> &CargoSettings {
+        &self.cargo
+    }
 }
 
 #[derive(Debug)]
@@ -88,6 +100,7 @@ impl Display for ProjectDirInvalid {
 #[derive(Debug)]
 pub enum Error {
     ProjectDirInvalid(ProjectDirInvalid),
+    MinNdkVersionInvalid(ndk::VersionParseError),
 }
 
 impl Error {
@@ -97,18 +110,117 @@ impl Error {
                 msg,
                 format!("`{}.project-dir` invalid: {}", super::NAME, err),
             ),
+            Self::MinNdkVersionInvalid(err) => Report::error(
+                msg,
+                format!("`{}.min-ndk-version` invalid: {}", super::NAME, err),
+            ),
         }
     }
 }
 
+/// Passwords are never stored in the config; only the names of the environment
+/// variables to read them from are.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RawSigning {
+    keystore: String,
+    key_alias: String,
+    store_password_env: Option<String>,
+    key_password_env: Option<String>,
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct Raw {
     min_sdk_version: Option<u32>,
+    min_ndk_version: Option<String>,
     vulkan_validation: Option<bool>,
     project_dir: Option<String>,
     no_default_features: Option<bool>,
     features: Option<Vec<String>>,
+    signing: Option<RawSigning>,
+    gradle: Option<gradle::Raw>,
+}
+
+#[derive(Debug)]
+pub enum SigningError {
+    KeystoreMissing { keystore: PathBuf },
+    PasswordMissing { var: String },
+}
+
+impl Reportable for SigningError {
+    fn report(&self) -> Report {
+        match self {
+            Self::KeystoreMissing { keystore } => Report::action_request(
+                format!("Keystore {:?} doesn't exist", keystore),
+                format!(
+                    "Please fix `{}.signing.keystore` in your {}.",
+                    super::NAME,
+                    crate::config::file_name()
+                ),
+            ),
+            Self::PasswordMissing { var } => Report::action_request(
+                format!("Signing password environment variable `{}` isn't set", var),
+                "Please set it to the password for your keystore and try again.",
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Signing {
+    keystore: PathBuf,
+    key_alias: String,
+    store_password_env: String,
+    key_password_env: String,
+}
+
+impl Signing {
+    fn from_raw(app: &App, raw: RawSigning) -> Self {
+        let store_password_env = raw
+            .store_password_env
+            .unwrap_or_else(|| DEFAULT_STORE_PASSWORD_ENV.to_owned());
+        // Keystores made by Android Studio use the same password for both by
+        // default, so that's what we fall back to.
+        let key_password_env = raw
+            .key_password_env
+            .unwrap_or_else(|| store_password_env.clone());
+        Self {
+            keystore: app.prefix_path(raw.keystore),
+            key_alias: raw.key_alias,
+            store_password_env,
+            key_password_env,
+        }
+    }
+
+    /// The environment variables that make Gradle sign with this config, using
+    /// the same injected properties Android Studio uses. Passing these through
+    /// the environment rather than as `-P` args keeps the passwords out of the
+    /// process list.
+    pub fn gradle_env(&self) -> Result<Vec<(String, String)>, SigningError> {
+        if !self.keystore.is_file() {
+            return Err(SigningError::KeystoreMissing {
+                keystore: self.keystore.clone(),
+            });
+        }
+        let password = |var: &str| {
+            std::env::var(var).map_err(|_| SigningError::PasswordMissing {
+                var: var.to_owned(),
+            })
+        };
+        let property = |name: &str, value: String| {
+            (
+                format!("ORG_GRADLE_PROJECT_android.injected.signing.{}", name),
+                value,
+            )
+        };
+        Ok(vec![
+            property("store.file", self.keystore.display().to_string()),
+            property("store.password", password(&self.store_password_env)?),
+            property("key.alias", self.key_alias.clone()),
+            property("key.password", password(&self.key_password_env)?),
+        ])
+    }
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -117,8 +229,14 @@ pub struct Config {
     #[serde(skip_serializing)]
     app: App,
     min_sdk_version: u32,
+    #[serde(skip_serializing)]
+    min_ndk_version: ndk::Version,
     vulkan_validation: bool,
     project_dir: PathBuf,
+    #[serde(skip_serializing)]
+    signing: Option<Signing>,
+    #[serde(skip_serializing)]
+    gradle: gradle::Settings,
 }
 
 impl Config {
@@ -134,6 +252,28 @@ impl Config {
             DEFAULT_MIN_SDK_VERSION
         });
 
+        let min_ndk_version = if let Some(min_ndk_version) = raw.min_ndk_version {
+            let min_ndk_version = min_ndk_version
+                .parse::<ndk::Version>()
+                .map_err(Error::MinNdkVersionInvalid)?;
+            if min_ndk_version < ndk::MIN_NDK_VERSION {
+                log::warn!(
+                    "`{}.min-ndk-version` is set to {}, but cargo-mobile always requires at least {}",
+                    super::NAME,
+                    min_ndk_version,
+                    ndk::MIN_NDK_VERSION
+                );
+            }
+            min_ndk_version.max(ndk::MIN_NDK_VERSION)
+        } else {
+            log::info!(
+                "`{}.min-ndk-version` not set; defaulting to {}",
+                super::NAME,
+                ndk::MIN_NDK_VERSION
+            );
+            ndk::MIN_NDK_VERSION
+        };
+
         let vulkan_validation = raw.vulkan_validation.unwrap_or_else(|| {
             log::info!(
                 "`{}.vulkan-validation` not set; defaulting to {}",
@@ -180,11 +320,17 @@ impl Config {
             Ok(DEFAULT_PROJECT_DIR.into())
         }?;
 
+        let signing = raw.signing.map(|signing| Signing::from_raw(&app, signing));
+        let gradle = gradle::Settings::from_raw(&app, raw.gradle);
+
         Ok(Self {
             app,
             min_sdk_version,
+            min_ndk_version,
             vulkan_validation,
             project_dir,
+            signing,
+            gradle,
         })
     }
 
@@ -193,13 +339,17 @@ impl Config {
     }
 
     pub fn so_name(&self) -> String {
-        format!("lib{}.so", self.app().name_snake())
+        format!("lib{}.so", util::lib_name_from_crate(self.app().name()))
     }
 
     pub fn min_sdk_version(&self) -> u32 {
         self.min_sdk_version
     }
 
+    pub fn min_ndk_version(&self) -> ndk::Version {
+        self.min_ndk_version
+    }
+
     pub fn project_dir(&self) -> PathBuf {
         self.app
             .prefix_path(&self.project_dir)
@@ -209,4 +359,12 @@ impl Config {
     pub fn project_dir_exists(&self) -> bool {
         self.project_dir().is_dir()
     }
+
+    pub fn signing(&self) -> Option<&Signing> {
+        self.signing.as_ref()
+    }
+
+    pub fn gradle(&self) -> &gradle::Settings {
+        &self.gradle
+    }
 }
@@ -1,33 +1,40 @@
 use super::{
-    adb,
-    config::Config,
+    adb::{self, logcat},
+    config::{Config, SigningError},
     env::Env,
+    gradle,
     jnilibs::{self, JniLibs},
+    outputs,
     target::{BuildError, Target},
 };
 use crate::{
-    env::ExplicitEnv as _,
+    config::hooks::{self, Stage},
+    device::DeviceSummary,
     opts::{NoiseLevel, Profile},
     util::{
         self,
         cli::{Report, Reportable},
+        env::{EnvBuilder, PathInvalid},
+        CommandError, Invocation, Runner,
     },
 };
-use std::fmt::{self, Display};
+use once_cell_regex::regex;
+use std::{
+    fmt::{self, Display},
+    path::PathBuf,
+    process::Stdio,
+    thread,
+    time::Duration,
+};
 
-fn gradlew(config: &Config, env: &Env) -> bossy::Command {
-    let gradlew_path = config.project_dir().join("gradlew");
-    bossy::Command::pure(&gradlew_path)
-        .with_env_vars(env.explicit_env())
-        .with_arg("--project-dir")
-        .with_arg(config.project_dir())
-}
+const STACKTRACE_TIMEOUT: Duration = Duration::from_secs(60);
 
 #[derive(Debug)]
 pub enum ApkBuildError {
     LibSymlinkCleaningFailed(jnilibs::RemoveBrokenLinksError),
     LibBuildFailed(BuildError),
-    AssembleFailed(bossy::Error),
+    SigningFailed(SigningError),
+    AssembleFailed(gradle::Error),
 }
 
 impl Reportable for ApkBuildError {
@@ -35,20 +42,41 @@ impl Reportable for ApkBuildError {
         match self {
             Self::LibSymlinkCleaningFailed(err) => err.report(),
             Self::LibBuildFailed(err) => err.report(),
-            Self::AssembleFailed(err) => Report::error("Failed to assemble APK", err),
+            Self::SigningFailed(err) => err.report(),
+            Self::AssembleFailed(err) => err.report("Failed to assemble APK"),
         }
     }
 }
 
 #[derive(Debug)]
 pub enum ApkInstallError {
-    InstallFailed(bossy::Error),
+    InstallFailed(CommandError),
 }
 
 impl Reportable for ApkInstallError {
     fn report(&self) -> Report {
         match self {
-            Self::InstallFailed(err) => Report::error("Failed to install APK", err),
+            Self::InstallFailed(err) => err.report("Failed to install APK"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LogError {
+    PidFailed(CommandError),
+    NotRunning { package: String },
+    LogcatFailed(logcat::LogcatError),
+}
+
+impl Reportable for LogError {
+    fn report(&self) -> Report {
+        match self {
+            Self::PidFailed(err) => err.report("Failed to get app's process ID"),
+            Self::NotRunning { package } => Report::action_request(
+                format!("{:?} isn't running on the device", package),
+                "Please start the app (i.e. via `cargo android run`) and try again.",
+            ),
+            Self::LogcatFailed(err) => Report::error("Failed to log output", err),
         }
     }
 }
@@ -57,9 +85,10 @@ impl Reportable for ApkInstallError {
 pub enum RunError {
     ApkBuildFailed(ApkBuildError),
     ApkInstallFailed(ApkInstallError),
-    StartFailed(bossy::Error),
-    WakeScreenFailed(bossy::Error),
-    LogcatFailed(bossy::Error),
+    StartFailed(CommandError),
+    WakeScreenFailed(CommandError),
+    LogFailed(LogError),
+    HookFailed(hooks::Error),
 }
 
 impl Reportable for RunError {
@@ -67,31 +96,72 @@ impl Reportable for RunError {
         match self {
             Self::ApkBuildFailed(err) => err.report(),
             Self::ApkInstallFailed(err) => err.report(),
-            Self::StartFailed(err) => Report::error("Failed to start app on device", err),
-            Self::WakeScreenFailed(err) => Report::error("Failed to wake device screen", err),
-            Self::LogcatFailed(err) => Report::error("Failed to log output", err),
+            Self::StartFailed(err) => err.report("Failed to start app on device"),
+            Self::WakeScreenFailed(err) => err.report("Failed to wake device screen"),
+            Self::LogFailed(err) => err.report(),
+            Self::HookFailed(err) => err.report(),
         }
     }
 }
 
 #[derive(Debug)]
 pub enum StacktraceError {
+    NdkStackPathInvalid(PathInvalid),
     PipeFailed(util::PipeError),
 }
 
 impl Reportable for StacktraceError {
     fn report(&self) -> Report {
         match self {
+            Self::NdkStackPathInvalid(err) => {
+                Report::error("Failed to build `PATH` for `ndk-stack`", err)
+            }
             Self::PipeFailed(err) => Report::error("Failed to pipe stacktrace output", err),
         }
     }
 }
 
+pub(super) fn package(config: &Config) -> String {
+    format!(
+        "{}.{}",
+        config.app().reverse_domain(),
+        config.app().name_snake()
+    )
+}
+
+pub fn min_log_priority(noise_level: NoiseLevel) -> char {
+    match noise_level {
+        NoiseLevel::Polite => 'W',
+        NoiseLevel::LoudAndProud => 'I',
+        NoiseLevel::FranklyQuitePedantic => 'V',
+    }
+}
+
+/// Gets the PID of the process that just started, if `message` is the
+/// `ActivityManager` announcement for `package` starting.
+fn started_pid(message: &str, package: &str) -> Option<u32> {
+    let caps = regex!(r"^Start proc (\d+):([^/\s]+)").captures(message)?;
+    if &caps[2] == package {
+        caps[1].parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Native crash dumps get logged by `debuggerd` rather than the app itself,
+/// so they're picked out by tag instead of by PID.
+#[derive(Debug, Default)]
+struct CrashDump {
+    lines: Vec<String>,
+    ours: bool,
+}
+
 #[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Device<'a> {
     serial_no: String,
     name: String,
     model: String,
+    os_version: String,
     target: &'a Target<'a>,
 }
 
@@ -110,21 +180,42 @@ impl<'a> Device<'a> {
         serial_no: String,
         name: String,
         model: String,
+        os_version: String,
         target: &'a Target<'a>,
     ) -> Self {
         Self {
             serial_no,
             name,
             model,
+            os_version,
             target,
         }
     }
 
+    pub fn id(&self) -> &str {
+        &self.serial_no
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn target(&self) -> &'a Target<'a> {
         self.target
     }
 
-    fn adb(&self, env: &Env) -> bossy::Command {
+    pub fn summary(&self) -> DeviceSummary {
+        DeviceSummary {
+            id: self.serial_no.clone(),
+            name: self.name.clone(),
+            model: self.model.clone(),
+            os: "Android",
+            os_version: Some(self.os_version.clone()),
+            abi: self.target.abi.to_owned(),
+        }
+    }
+
+    fn adb(&self, env: &Env) -> Invocation {
         adb::adb(env, &self.serial_no)
     }
 
@@ -139,16 +230,20 @@ impl<'a> Device<'a> {
         JniLibs::remove_broken_links(config).map_err(ApkBuildError::LibSymlinkCleaningFailed)?;
         let flavor = self.target.arch.to_camel_case();
         let build_ty = profile.as_str().to_camel_case();
-        gradlew(config, env)
-            .with_arg(format!("assemble{}{}", flavor, build_ty))
-            .with_arg(match noise_level {
-                NoiseLevel::Polite => "--warn",
-                NoiseLevel::LoudAndProud => "--info",
-                NoiseLevel::FranklyQuitePedantic => "--debug",
-            })
-            .run_and_wait()
-            .map_err(ApkBuildError::AssembleFailed)?;
-        Ok(())
+        let signing_env = match (profile, config.signing()) {
+            (Profile::Release, Some(signing)) => {
+                signing.gradle_env().map_err(ApkBuildError::SigningFailed)?
+            }
+            _ => Vec::new(),
+        };
+        gradle::run(
+            gradle::gradlew(config, env)
+                .with_env_vars(signing_env)
+                .with_arg(format!("assemble{}{}", flavor, build_ty))
+                .with_arg(gradle::log_level(noise_level)),
+            noise_level,
+        )
+        .map_err(ApkBuildError::AssembleFailed)
     }
 
     fn install_apk(
@@ -156,34 +251,49 @@ impl<'a> Device<'a> {
         config: &Config,
         env: &Env,
         profile: Profile,
-    ) -> Result<(), ApkInstallError> {
+    ) -> Result<PathBuf, ApkInstallError> {
         let flavor = self.target.arch;
         let build_ty = profile.as_str();
         let apk_suffix = match profile {
             Profile::Debug => build_ty,
-            // TODO: how to handle signed APKs?
+            Profile::Release if config.signing().is_some() => build_ty,
             Profile::Release => "release-unsigned",
         };
-        let apk_path = config.project_dir().join(format!(
-            "app/build/outputs/apk/{}/{}/app-{}-{}.apk",
-            flavor, build_ty, flavor, apk_suffix
-        ));
-        self.adb(env)
-            .with_arg("install")
-            .with_arg(apk_path)
-            .run_and_wait()
+        let variant = format!("{}/{}", flavor, build_ty);
+        let apk_path = outputs::gradle_build_outputs(&config.project_dir().join("app"), &variant)
+            .ok()
+            .and_then(|outputs| {
+                outputs
+                    .into_iter()
+                    .find(|path| path.extension().map_or(false, |ext| ext == "apk"))
+            })
+            .unwrap_or_else(|| {
+                log::info!(
+                    "gradle didn't report any APK outputs for {:?}, so falling back to the default path",
+                    variant
+                );
+                config.project_dir().join(format!(
+                    "app/build/outputs/apk/{}/app-{}-{}.apk",
+                    variant, flavor, apk_suffix
+                ))
+            });
+        Runner::global()
+            .stream(&self.adb(env).with_arg("install").with_arg(&apk_path))
             .map_err(ApkInstallError::InstallFailed)?;
-        Ok(())
+        Ok(apk_path)
     }
 
-    fn wake_screen(&self, env: &Env) -> bossy::Result<()> {
-        self.adb(env)
-            .with_args(&["shell", "input", "keyevent", "KEYCODE_WAKEUP"])
-            .run_and_wait()?;
-        Ok(())
+    fn wake_screen(&self, env: &Env) -> Result<(), CommandError> {
+        util::run(
+            &self
+                .adb(env)
+                .with_args(&["shell", "input", "keyevent", "KEYCODE_WAKEUP"]),
+        )
     }
 
-    pub fn run(
+    /// Builds, installs, and starts the app, restarting it if it's already
+    /// running. Unlike `run`, this returns as soon as the app's started.
+    pub fn deploy(
         &self,
         config: &Config,
         env: &Env,
@@ -192,43 +302,140 @@ impl<'a> Device<'a> {
     ) -> Result<(), RunError> {
         self.build_apk(config, env, noise_level, profile)
             .map_err(RunError::ApkBuildFailed)?;
-        self.install_apk(config, env, profile)
+        let apk_path = self
+            .install_apk(config, env, profile)
             .map_err(RunError::ApkInstallFailed)?;
+        config
+            .app()
+            .hooks()
+            .run(
+                Stage::PostInstall,
+                &hooks::Context::new("android", Some(self.target.triple), profile.as_str())
+                    .with_artifact(apk_path),
+            )
+            .map_err(RunError::HookFailed)?;
         let activity = format!(
             "{}.{}/android.app.NativeActivity",
             config.app().reverse_domain(),
             config.app().name_snake(),
         );
-        self.adb(env)
-            .with_args(&["shell", "am", "start", "-n", &activity])
-            .run_and_wait()
-            .map_err(RunError::StartFailed)?;
-        self.wake_screen(env).map_err(RunError::WakeScreenFailed)?;
-        let filter = format!(
-            "{}:{}",
-            config.app().name(),
-            match noise_level {
-                NoiseLevel::Polite => "W",
-                NoiseLevel::LoudAndProud => "I",
-                NoiseLevel::FranklyQuitePedantic => "V",
-            },
-        );
-        adb::adb(env, &self.serial_no)
-            .with_args(&["logcat", "-v", "color", "-s", &filter])
-            .run_and_wait()
-            .map_err(RunError::LogcatFailed)?;
-        Ok(())
+        // `-S` force stops the app first, so we always get a fresh start.
+        util::run(
+            &self
+                .adb(env)
+                .with_args(&["shell", "am", "start", "-S", "-n", &activity]),
+        )
+        .map_err(RunError::StartFailed)?;
+        self.wake_screen(env).map_err(RunError::WakeScreenFailed)
     }
 
-    pub fn stacktrace(&self, config: &Config, env: &Env) -> Result<(), StacktraceError> {
-        // -d = print and exit
-        let logcat_command = adb::adb(env, &self.serial_no).with_args(&["logcat", "-d"]);
-        let stack_command = bossy::Command::pure("ndk-stack")
-            .with_env_vars(env.explicit_env())
-            .with_env_var(
-                "PATH",
-                util::prepend_to_path(env.ndk.home().display(), env.path()),
-            )
+    pub fn run(
+        &self,
+        config: &Config,
+        env: &Env,
+        noise_level: NoiseLevel,
+        profile: Profile,
+    ) -> Result<(), RunError> {
+        self.deploy(config, env, noise_level, profile)?;
+        self.log(config, env, min_log_priority(noise_level), false)
+            .map_err(RunError::LogFailed)
+    }
+
+    fn pid(&self, env: &Env, package: &str) -> Result<Option<u32>, CommandError> {
+        match Runner::global().capture(&self.adb(env).with_args(&["shell", "pidof", "-s", package]))
+        {
+            Ok(output) => Ok(output
+                .stdout_str()
+                .ok()
+                .and_then(|pid| pid.trim().parse().ok())),
+            // `pidof` exits with an error if there's no such process
+            Err(err) if err.status().is_some() => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Streams the app's logs, showing lines at least as severe as
+    /// `min_priority`. If `symbolize` is set, native crash dumps are also run
+    /// through `ndk-stack` to resolve Rust symbols from the built libs.
+    pub fn log(
+        &self,
+        config: &Config,
+        env: &Env,
+        min_priority: char,
+        symbolize: bool,
+    ) -> Result<(), LogError> {
+        let package = package(config);
+        // The app might still be starting up, so we give it a moment.
+        let mut pid = None;
+        for _ in 0..20 {
+            pid = self.pid(env, &package).map_err(LogError::PidFailed)?;
+            if pid.is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(250));
+        }
+        let mut pid = pid.ok_or_else(|| LogError::NotRunning {
+            package: package.clone(),
+        })?;
+        println!("Showing logs for {} (PID {})...", package, pid);
+        let crash_marker = format!(">>> {} <<<", package);
+        let mut crash: Option<CrashDump> = None;
+        adb::adb_logcat(env, Some(&self.serial_no), &[], |raw| {
+            let line = if let Some(line) = logcat::parse_line(raw) {
+                line
+            } else {
+                return;
+            };
+            if line.tag == "ActivityManager" {
+                if let Some(new_pid) = started_pid(line.message, &package) {
+                    println!("App restarted with PID {}", new_pid);
+                    pid = new_pid;
+                }
+            }
+            if line.tag == "DEBUG" {
+                if line.message.starts_with("*** ***") {
+                    crash = Some(Default::default());
+                }
+                if let Some(crash) = &mut crash {
+                    crash.ours |= line.message.contains(&crash_marker);
+                    crash.lines.push(raw.to_owned());
+                    if crash.ours {
+                        println!("{}", logcat::colorize(&line));
+                    }
+                }
+                return;
+            }
+            if let Some(crash) = crash.take() {
+                if crash.ours && symbolize {
+                    self.symbolize(config, env, &crash.lines.join("\n"));
+                }
+            }
+            if line.pid == pid && logcat::priority_at_least(line.priority, min_priority) {
+                println!("{}", logcat::colorize(&line));
+            }
+        })
+        .map_err(LogError::LogcatFailed)
+    }
+
+    fn symbolize(&self, config: &Config, env: &Env, dump: &str) {
+        println!("Symbolizing crash...");
+        let command = match self.ndk_stack(config, env) {
+            Ok(command) => command,
+            Err(err) => {
+                log::warn!("failed to symbolize crash via `ndk-stack`: {}", err);
+                return;
+            }
+        };
+        match util::run_with_stdin(&command, dump.as_bytes(), Stdio::piped(), Stdio::piped()) {
+            Ok(output) => println!("{}", String::from_utf8_lossy(output.stdout())),
+            Err(err) => log::warn!("failed to symbolize crash via `ndk-stack`: {}", err),
+        }
+    }
+
+    fn ndk_stack(&self, config: &Config, env: &Env) -> Result<Invocation, PathInvalid> {
+        let env = EnvBuilder::from_explicit(env).prepend_path("PATH", env.ndk.home())?;
+        Ok(Invocation::pure("ndk-stack")
+            .with_env_vars(env.vars())
             .with_arg("-sym")
             .with_arg(
                 config
@@ -239,8 +446,19 @@ impl<'a> Device<'a> {
                     // just use a relative path!
                     .unprefix_path(jnilibs::path(config, *self.target))
                     .expect("developer error: jnilibs subdir not prefixed"),
-            );
-        if !util::pipe(logcat_command, stack_command).map_err(StacktraceError::PipeFailed)? {
+            ))
+    }
+
+    pub fn stacktrace(&self, config: &Config, env: &Env) -> Result<(), StacktraceError> {
+        // -d = print and exit
+        let logcat_command = adb::adb(env, &self.serial_no).with_args(&["logcat", "-d"]);
+        let stack_command = self
+            .ndk_stack(config, env)
+            .map_err(StacktraceError::NdkStackPathInvalid)?;
+        // `adb` can hang forever if the device goes away partway through.
+        let found = util::pipe_with_timeout(&logcat_command, &stack_command, STACKTRACE_TIMEOUT)
+            .map_err(StacktraceError::PipeFailed)?;
+        if !found {
             println!("  -- no stacktrace --");
         }
         Ok(())
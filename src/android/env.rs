@@ -1,11 +1,13 @@
 use super::ndk;
 use crate::{
+    config::global,
     env::{Env as CoreEnv, Error as CoreError, ExplicitEnv},
     util::cli::{Report, Reportable},
 };
 use std::{
+    ffi::{OsStr, OsString},
     fmt::{self, Display},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 #[derive(Debug)]
@@ -22,7 +24,7 @@ impl Display for Error {
         match self {
             Self::CoreEnvError(err) => write!(f, "{}", err),
             Self::AndroidSdkRootNotSet(err) => {
-                write!(f, "Have you installed the Android SDK? The `ANDROID_SDK_ROOT` environment variable isn't set, and is required: {}", err)
+                write!(f, "Have you installed the Android SDK? Neither the `ANDROID_SDK_ROOT` environment variable nor `android.sdk-root` in your user config is set, and one of them is required: {}", err)
             }
             Self::AndroidSdkRootNotADir => write!(
                 f,
@@ -49,6 +51,71 @@ impl Error {
     }
 }
 
+// Where Android Studio installs the SDK by default, if it's there.
+#[cfg(windows)]
+pub(crate) fn default_sdk_root() -> Option<PathBuf> {
+    std::env::var_os("LOCALAPPDATA")
+        .map(|local_app_data| PathBuf::from(local_app_data).join("Android").join("Sdk"))
+        .filter(|sdk_root| sdk_root.is_dir())
+}
+
+#[cfg(not(windows))]
+pub(crate) fn default_sdk_root() -> Option<PathBuf> {
+    None
+}
+
+/// Finds the Android SDK, which doesn't need the NDK to be usable. The
+/// environment is checked first, then the user config, and then wherever
+/// Android Studio would've put it.
+pub fn sdk_root() -> Result<PathBuf, Error> {
+    std::env::var("ANDROID_SDK_ROOT")
+        .map_err(Error::AndroidSdkRootNotSet)
+        .map(PathBuf::from)
+        .and_then(|sdk_root| {
+            if sdk_root.is_dir() {
+                Ok(sdk_root)
+            } else {
+                Err(Error::AndroidSdkRootNotADir)
+            }
+        })
+        .or_else(|err| {
+            if let Some(android_home) = std::env::var("ANDROID_HOME")
+                .ok()
+                .map(PathBuf::from)
+                .filter(|android_home| android_home.is_dir())
+            {
+                log::warn!("`ANDROID_SDK_ROOT` isn't set; falling back to `ANDROID_HOME`, which is deprecated");
+                Ok(android_home)
+            } else {
+                Err(err)
+            }
+        })
+        .or_else(|err| {
+            if let Some(sdk_root) = global::get().android_sdk_root() {
+                if sdk_root.is_dir() {
+                    log::info!(
+                        "`ANDROID_SDK_ROOT` isn't set; using `android.sdk-root` {:?} from the user config",
+                        sdk_root
+                    );
+                    return Ok(sdk_root);
+                }
+                log::warn!(
+                    "`android.sdk-root` in the user config is set to {:?}, which isn't an existing directory",
+                    sdk_root
+                );
+            }
+            Err(err)
+        })
+        .or_else(|err| {
+            let sdk_root = default_sdk_root().ok_or(err)?;
+            log::info!(
+                "`ANDROID_SDK_ROOT` isn't set; using Android Studio's default SDK location {:?}",
+                sdk_root
+            );
+            Ok(sdk_root)
+        })
+}
+
 #[derive(Debug)]
 pub struct Env {
     base: CoreEnv,
@@ -59,50 +126,28 @@ pub struct Env {
 impl Env {
     pub fn new() -> Result<Self, Error> {
         let base = CoreEnv::new().map_err(Error::CoreEnvError)?;
-        let sdk_root = std::env::var("ANDROID_SDK_ROOT")
-            .map_err(Error::AndroidSdkRootNotSet)
-            .map(PathBuf::from)
-            .and_then(|sdk_root| {
-                if sdk_root.is_dir() {
-                    Ok(sdk_root)
-                } else {
-                    Err(Error::AndroidSdkRootNotADir)
-                }
-            })
-            .or_else(|err| {
-                if let Some(android_home) = std::env::var("ANDROID_HOME")
-                    .ok()
-                    .map(PathBuf::from)
-                    .filter(|android_home| android_home.is_dir())
-                {
-                    log::warn!("`ANDROID_SDK_ROOT` isn't set; falling back to `ANDROID_HOME`, which is deprecated");
-                    Ok(android_home)
-                } else {
-                    Err(err)
-                }
-            })
-            .or_else(|err| {
-                if let Some(android_home) = std::env::var("ANDROID_HOME")
-                    .ok()
-                    .map(PathBuf::from)
-                    .filter(|android_home| android_home.is_dir())
-                {
-                    log::warn!("`ANDROID_SDK_ROOT` isn't set; falling back to `ANDROID_HOME`, which is deprecated");
-                    Ok(android_home)
-                } else {
-                    Err(err)
-                }
-            })?;
+        let sdk_root = sdk_root()?;
         Ok(Self {
             base,
+            ndk: ndk::Env::new(&sdk_root).map_err(Error::NdkEnvError)?,
             sdk_root,
-            ndk: ndk::Env::new().map_err(Error::NdkEnvError)?,
         })
     }
 
-    pub fn path(&self) -> &str {
+    pub fn path(&self) -> &OsStr {
         self.base.path()
     }
+
+    pub fn sdk_root(&self) -> &Path {
+        &self.sdk_root
+    }
+
+    pub fn with_var(self, key: impl Into<String>, value: impl Into<OsString>) -> Self {
+        Self {
+            base: self.base.with_var(key, value),
+            ..self
+        }
+    }
 }
 
 impl ExplicitEnv for Env {
@@ -0,0 +1,224 @@
+//! Running Gradle the way `android.gradle` says to, and making sense of how
+//! it failed when it does.
+
+use super::{config::Config, env::Env};
+use crate::{
+    config::app::App,
+    env::ExplicitEnv as _,
+    opts::NoiseLevel,
+    util::{self, cli::Report, CommandError, Invocation, Runner},
+};
+use once_cell_regex::regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    io::{self, BufRead as _, BufReader},
+    path::PathBuf,
+    process::Stdio,
+};
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Raw {
+    offline: Option<bool>,
+    daemon: Option<bool>,
+    build_cache: Option<bool>,
+    user_home: Option<String>,
+    jvm_args: Option<Vec<String>>,
+    properties: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Settings {
+    offline: bool,
+    daemon: Option<bool>,
+    build_cache: Option<bool>,
+    user_home: Option<PathBuf>,
+    jvm_args: Vec<String>,
+    properties: BTreeMap<String, String>,
+}
+
+impl Settings {
+    pub fn from_raw(app: &App, raw: Option<Raw>) -> Self {
+        let raw = raw.unwrap_or_default();
+        Self {
+            offline: raw.offline.unwrap_or_default(),
+            daemon: raw.daemon,
+            build_cache: raw.build_cache,
+            user_home: raw.user_home.map(|user_home| app.prefix_path(user_home)),
+            jvm_args: raw.jvm_args.unwrap_or_default(),
+            properties: raw.properties.unwrap_or_default(),
+        }
+    }
+
+    // Gradle is kept off the network whenever we are, too.
+    fn offline(&self) -> bool {
+        self.offline || util::git_offline()
+    }
+
+    /// `GRADLE_USER_HOME` is where Gradle keeps its dependency cache, so
+    /// pointing CI at a persistent dir saves downloading everything each time.
+    fn apply(&self, mut command: Invocation) -> Invocation {
+        if self.offline() {
+            command.add_arg("--offline");
+        }
+        if let Some(daemon) = self.daemon {
+            command.add_arg(if daemon { "--daemon" } else { "--no-daemon" });
+        }
+        if let Some(build_cache) = self.build_cache {
+            command.add_arg(if build_cache {
+                "--build-cache"
+            } else {
+                "--no-build-cache"
+            });
+        }
+        if let Some(user_home) = &self.user_home {
+            command = command.with_env_var("GRADLE_USER_HOME", user_home);
+        }
+        if !self.jvm_args.is_empty() {
+            command.add_arg(format!("-Dorg.gradle.jvmargs={}", self.jvm_args.join(" ")));
+        }
+        for (key, value) in &self.properties {
+            command.add_arg(format!("-P{}={}", key, value));
+        }
+        command
+    }
+}
+
+/// A `gradlew` command for the project, with `android.gradle` applied.
+pub fn gradlew(config: &Config, env: &Env) -> Invocation {
+    let gradlew_path = config.project_dir().join(super::gradlew_file_name());
+    config.gradle().apply(
+        Invocation::pure(&gradlew_path)
+            .with_env_vars(env.explicit_env())
+            .with_arg("--project-dir")
+            .with_arg(config.project_dir()),
+    )
+}
+
+pub fn log_level(noise_level: NoiseLevel) -> &'static str {
+    match noise_level {
+        NoiseLevel::Polite => "--warn",
+        NoiseLevel::LoudAndProud => "--info",
+        NoiseLevel::FranklyQuitePedantic => "--debug",
+    }
+}
+
+/// What Gradle said went wrong, which is a lot easier to act on than the
+/// exit status.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Failure {
+    /// The task that failed, if it was a task that failed rather than e.g.
+    /// configuring the project.
+    pub task: Option<String>,
+    pub what_went_wrong: String,
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.what_went_wrong)
+    }
+}
+
+impl Failure {
+    /// Picks the first failure out of Gradle's output, which looks like this:
+    ///
+    /// ```text
+    /// FAILURE: Build failed with an exception.
+    ///
+    /// * What went wrong:
+    /// Execution failed for task ':app:mergeArm64DebugNativeLibs'.
+    /// > A failure occurred while executing a task action
+    ///
+    /// * Try:
+    /// Run with --stacktrace option to get the stack trace.
+    /// ```
+    pub fn parse(output: &str) -> Option<Self> {
+        let what_went_wrong = output
+            .lines()
+            .skip_while(|line| line.trim() != "* What went wrong:")
+            .skip(1)
+            .take_while(|line| !line.trim().is_empty() && !line.starts_with("* "))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if what_went_wrong.is_empty() {
+            return None;
+        }
+        let task = regex!(r"[Ee]xecution failed for task '([^']+)'")
+            .captures(&what_went_wrong)
+            .map(|caps| caps[1].to_owned());
+        Some(Self {
+            task,
+            what_went_wrong,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    ReadFailed(io::Error),
+    Failed {
+        failure: Option<Failure>,
+        cause: CommandError,
+    },
+}
+
+impl Error {
+    pub fn report(&self, msg: &str) -> Report {
+        match self {
+            Self::ReadFailed(err) => {
+                Report::error(msg, format!("Failed to read Gradle output: {}", err))
+            }
+            Self::Failed {
+                failure: Some(failure),
+                cause,
+            } => {
+                let msg = match &failure.task {
+                    Some(task) => format!("{}, since task `{}` failed", msg, task),
+                    None => msg.to_owned(),
+                };
+                Report::error(msg, format!("{}\n\n{}", failure, cause))
+            }
+            Self::Failed {
+                failure: None,
+                cause,
+            } => cause.report(msg),
+        }
+    }
+}
+
+/// Runs `command`, which should come from [`gradlew`]. Gradle's stderr is
+/// passed through as usual, but it's also read along the way so that a
+/// failure can say what actually went wrong.
+pub fn run(command: Invocation, noise_level: NoiseLevel) -> Result<(), Error> {
+    let failed = |failure, err: CommandError| Error::Failed {
+        failure,
+        cause: err.with_verbose_hint(noise_level, NoiseLevel::LoudAndProud),
+    };
+    let mut child = Runner::global()
+        .spawn(&command, Stdio::inherit(), Stdio::inherit(), Stdio::piped())
+        .map_err(|err| failed(None, err))?;
+    let reader = child
+        .take_stderr()
+        .expect("developer error: gradle stderr not captured");
+    // The failure summary comes at the very end, so there's no need to hang
+    // on to everything before it.
+    let mut failure_output: Option<String> = None;
+    for line in BufReader::new(reader).split(b'\n') {
+        let line = line.map_err(Error::ReadFailed)?;
+        let line = String::from_utf8_lossy(&line);
+        eprintln!("{}", line);
+        if line.starts_with("FAILURE:") {
+            failure_output = Some(String::new());
+        }
+        if let Some(output) = &mut failure_output {
+            output.push_str(&line);
+            output.push('\n');
+        }
+    }
+    child
+        .wait()
+        .map(|_| ())
+        .map_err(|err| failed(failure_output.as_deref().and_then(Failure::parse), err))
+}
@@ -0,0 +1,56 @@
+//! Launcher icons generated from `app.icon`: the PNGs for each density, plus
+//! the adaptive icon layers that Android 8 and up use instead.
+
+use crate::{
+    config::app::icon::Icon,
+    icon::{self, Source},
+};
+use std::path::Path;
+
+// Each density, with how big a 48dp legacy icon is there.
+static DENSITIES: &[(&str, u32)] = &[
+    ("mdpi", 48),
+    ("hdpi", 72),
+    ("xhdpi", 96),
+    ("xxhdpi", 144),
+    ("xxxhdpi", 192),
+];
+
+static ADAPTIVE_ICON: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<adaptive-icon xmlns:android="http://schemas.android.com/apk/res/android">
+    <background android:drawable="@color/ic_launcher_background" />
+    <foreground android:drawable="@mipmap/ic_launcher_foreground" />
+</adaptive-icon>
+"#;
+
+/// Writes the icons into the `res` dir of the project rendered at `dest`,
+/// replacing the ones that come with the template.
+pub fn gen(icon: &Icon, dest: &Path) -> Result<(), icon::Error> {
+    let source = Source::load(icon)?;
+    let res = dest.join("app/src/main/res");
+    for (density, size) in DENSITIES {
+        let mipmap = res.join(format!("mipmap-{}", density));
+        icon::write_png(&source.resized(*size), &mipmap.join("ic_launcher.png"))?;
+        icon::write_png(&source.round(*size), &mipmap.join("ic_launcher_round.png"))?;
+        // Adaptive icon layers are 108dp, but only the middle 72dp is sure to
+        // be visible once the launcher's mask is applied.
+        icon::write_png(
+            &source.padded(size * 9 / 4, size * 3 / 2),
+            &mipmap.join("ic_launcher_foreground.png"),
+        )?;
+    }
+    let anydpi = res.join("mipmap-anydpi-v26");
+    icon::write_file(&anydpi.join("ic_launcher.xml"), ADAPTIVE_ICON)?;
+    icon::write_file(&anydpi.join("ic_launcher_round.xml"), ADAPTIVE_ICON)?;
+    icon::write_file(
+        &res.join("values/ic_launcher_background.xml"),
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<resources>
+    <color name="ic_launcher_background">{}</color>
+</resources>
+"#,
+            source.background()
+        ),
+    )
+}
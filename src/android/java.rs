@@ -0,0 +1,113 @@
+use crate::util::{self, Invocation};
+use once_cell_regex::regex;
+use std::fmt::{self, Display};
+
+pub type JavaVersion = (u32, u32, u32);
+
+#[derive(Debug)]
+pub enum JavaVersionError {
+    CommandFailed(util::CaptureCombinedError),
+    ParseFailed(String),
+}
+
+impl Display for JavaVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CommandFailed(err) => write!(f, "Failed to run `java -version`: {}", err),
+            Self::ParseFailed(output) => write!(
+                f,
+                "Failed to find a version in `java -version` output: {:?}",
+                output
+            ),
+        }
+    }
+}
+
+/// Gets the version from `java -version` output, which varies by vendor but
+/// always has the version in quotes on the first line:
+///
+/// ```text
+/// openjdk version "17.0.1" 2021-10-19
+/// java version "1.8.0_292"
+/// ```
+///
+/// Java 8 and earlier use the legacy `1.x` scheme, so `1.8.0_292` is read as
+/// `(8, 0, 292)`.
+pub fn parse_java_version(output: &str) -> Option<JavaVersion> {
+    let caps = regex!(r#"version "(\d+)(?:\.(\d+))?(?:\.(\d+))?(?:_(\d+))?"#).captures(output)?;
+    let component = |index| {
+        caps.get(index)
+            .map_or(Some(0), |component| component.as_str().parse::<u32>().ok())
+    };
+    let (major, minor, patch, update) =
+        (component(1)?, component(2)?, component(3)?, component(4)?);
+    if major == 1 {
+        Some((minor, patch, update))
+    } else {
+        Some((major, minor, patch))
+    }
+}
+
+pub fn java_version() -> Result<JavaVersion, JavaVersionError> {
+    // `java -version` prints to stderr, but that's not something we want to
+    // rely on too much.
+    let (output, status) =
+        util::run_capture_combined(&Invocation::impure("java").with_arg("-version"))
+            .map_err(JavaVersionError::CommandFailed)?;
+    log::info!("`java -version` exited with {}", status);
+    parse_java_version(&output).ok_or(JavaVersionError::ParseFailed(output))
+}
+
+#[derive(Debug)]
+pub enum RequireJavaError {
+    VersionFailed(JavaVersionError),
+    TooOld {
+        you_have: JavaVersion,
+        you_need: JavaVersion,
+    },
+}
+
+impl Display for RequireJavaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::VersionFailed(err) => write!(f, "{}", err),
+            Self::TooOld { you_have, you_need } => write!(
+                f,
+                "Java {}.{}.{} is installed, but at least {}.{}.{} is required.",
+                you_have.0, you_have.1, you_have.2, you_need.0, you_need.1, you_need.2
+            ),
+        }
+    }
+}
+
+pub fn require_java_at_least(min: JavaVersion) -> Result<JavaVersion, RequireJavaError> {
+    let version = java_version().map_err(RequireJavaError::VersionFailed)?;
+    if version >= min {
+        Ok(version)
+    } else {
+        Err(RequireJavaError::TooOld {
+            you_have: version,
+            you_need: min,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_and_modern_java_versions_are_parsed() {
+        let parse = parse_java_version;
+        assert_eq!(
+            parse("openjdk version \"17.0.1\" 2021-10-19\n"),
+            Some((17, 0, 1))
+        );
+        assert_eq!(parse("java version \"1.8.0_292\"\n"), Some((8, 0, 292)));
+        assert_eq!(
+            parse("openjdk version \"11\" 2018-09-25\n"),
+            Some((11, 0, 0))
+        );
+        assert_eq!(parse("'java' is not recognized"), None);
+    }
+}
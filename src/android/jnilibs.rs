@@ -2,6 +2,7 @@ use super::{config::Config, target::Target};
 use crate::{
     target::TargetTrait as _,
     util::{
+        self,
         cli::{Report, Reportable},
         ln,
     },
@@ -62,6 +63,86 @@ impl Reportable for SymlinkLibError {
     }
 }
 
+#[derive(Debug)]
+pub enum InstallJniLibError {
+    UnknownAbi(String),
+    SourceMissing(PathBuf),
+    DirCreationFailed {
+        dir: PathBuf,
+        source: std::io::Error,
+    },
+    CopyFailed {
+        src: PathBuf,
+        dest: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+impl Reportable for InstallJniLibError {
+    fn report(&self) -> Report {
+        let msg = "Failed to install lib into jniLibs";
+        match self {
+            Self::UnknownAbi(abi) => Report::error(
+                msg,
+                format!(
+                    "{:?} isn't a known Android ABI; the known ABIs are {:?}",
+                    abi,
+                    Target::all()
+                        .values()
+                        .map(|target| target.abi)
+                        .collect::<Vec<_>>()
+                ),
+            ),
+            Self::SourceMissing(src) => Report::error(
+                msg,
+                format!(
+                    "The built lib should be at {:?}, but nothing exists there",
+                    src
+                ),
+            ),
+            Self::DirCreationFailed { dir, source } => Report::error(
+                msg,
+                format!("Failed to create ABI directory {:?}: {}", dir, source),
+            ),
+            Self::CopyFailed { src, dest, source } => Report::error(
+                msg,
+                format!("Failed to copy {:?} to {:?}: {}", src, dest, source),
+            ),
+        }
+    }
+}
+
+/// Copies `built_lib` to `<jni_libs_root>/<abi>/lib<lib_name>.so`, which is
+/// where Android expects to find it, and returns that path. Unlike
+/// [`JniLibs::symlink_lib`], this doesn't need a config, so it works for libs
+/// that are built outside of cargo-mobile.
+pub fn install_jni_lib(
+    built_lib: &Path,
+    jni_libs_root: &Path,
+    abi: &str,
+    lib_name: &str,
+) -> Result<PathBuf, InstallJniLibError> {
+    if Target::for_abi(abi).is_none() {
+        return Err(InstallJniLibError::UnknownAbi(abi.to_owned()));
+    }
+    if !built_lib.is_file() {
+        return Err(InstallJniLibError::SourceMissing(built_lib.to_owned()));
+    }
+    let dir = jni_libs_root.join(abi);
+    util::create_dir_all(&dir).map_err(|source| InstallJniLibError::DirCreationFailed {
+        dir: dir.clone(),
+        source,
+    })?;
+    let dest = dir.join(format!("lib{}.so", lib_name));
+    log::info!("installing lib {:?} to {:?}", built_lib, dest);
+    std::fs::copy(built_lib, &dest).map_err(|source| InstallJniLibError::CopyFailed {
+        src: built_lib.to_owned(),
+        dest: dest.clone(),
+        source,
+    })?;
+    Ok(dest)
+}
+
 pub fn path(config: &Config, target: Target<'_>) -> PathBuf {
     config
         .project_dir()
@@ -125,6 +206,7 @@ impl JniLibs {
                         .expect("developer error: file had no file name"),
                 ),
                 ln::TargetStyle::File,
+                ln::Parents::Create,
             )
             .map_err(SymlinkLibError::SymlinkFailed)
         } else {
@@ -132,3 +214,28 @@ impl JniLibs {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn libs_are_installed_into_their_abi_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let built_lib = dir.path().join("libexample.so");
+        fs::write(&built_lib, "elf").unwrap();
+        let jni_libs_root = dir.path().join("jniLibs");
+        let dest = install_jni_lib(&built_lib, &jni_libs_root, "arm64-v8a", "mobile").unwrap();
+        assert_eq!(dest, jni_libs_root.join("arm64-v8a/libmobile.so"));
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "elf");
+        assert!(matches!(
+            install_jni_lib(&built_lib, &jni_libs_root, "mips", "mobile"),
+            Err(InstallJniLibError::UnknownAbi(_))
+        ));
+        assert!(matches!(
+            install_jni_lib(&dir.path().join("nope.so"), &jni_libs_root, "x86", "mobile"),
+            Err(InstallJniLibError::SourceMissing(_))
+        ));
+    }
+}
@@ -0,0 +1,36 @@
+use super::config::Config;
+use crate::{
+    util::xml::{self, EditError},
+    version::{self, Stamp},
+};
+use std::path::PathBuf;
+
+pub fn path(config: &Config) -> PathBuf {
+    config
+        .project_dir()
+        .join("app/src/main/AndroidManifest.xml")
+}
+
+/// Sets `android:versionCode` and `android:versionName` on the root
+/// `<manifest>` element, leaving everything else alone.
+pub fn set_version(contents: &str, stamp: &Stamp) -> Result<String, EditError> {
+    let root = xml::root_tag(contents)?;
+    if root.name != "manifest" {
+        return Err(EditError::ElementMissing { name: "manifest" });
+    }
+    let contents = xml::set_attr(
+        contents,
+        &root,
+        "android:versionCode",
+        &stamp.build_number().to_string(),
+    )?;
+    // The first edit moved everything after it around.
+    let root = xml::root_tag(&contents)?;
+    xml::set_attr(&contents, &root, "android:versionName", stamp.version())
+}
+
+pub fn stamp(config: &Config, stamp: &Stamp) -> Result<(), version::Error> {
+    version::rewrite(config.app().root_dir(), &path(config), |contents| {
+        set_version(contents, stamp)
+    })
+}
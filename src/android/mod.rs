@@ -1,11 +1,33 @@
-mod adb;
+pub mod adb;
+pub mod build_tools;
+mod bundle;
 pub mod cli;
 pub(crate) mod config;
 mod device;
 pub(crate) mod env;
+mod gradle;
+mod icon;
+pub mod java;
 mod jnilibs;
-mod ndk;
+mod manifest;
+pub(crate) mod ndk;
+mod outputs;
+mod parallel;
+mod profiler;
 pub(crate) mod project;
-mod target;
+pub(crate) mod target;
+mod test_runner;
+
+pub use self::jnilibs::{install_jni_lib, InstallJniLibError};
 
 pub static NAME: &str = "android";
+
+/// The Gradle wrapper is a shell script, except on Windows, where it's a batch
+/// file instead.
+pub fn gradlew_file_name() -> &'static str {
+    if cfg!(windows) {
+        "gradlew.bat"
+    } else {
+        "gradlew"
+    }
+}
@@ -1,5 +1,13 @@
 use super::target::Target;
-use crate::util::cli::{Report, Reportable};
+use crate::{
+    config::global,
+    target::TargetTrait as _,
+    util::{
+        self,
+        cli::{Report, Reportable},
+        CommandError, Invocation, Runner,
+    },
+};
 use once_cell_regex::regex_multi_line;
 use std::{
     collections::HashSet,
@@ -8,14 +16,46 @@ use std::{
     io,
     num::ParseIntError,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 use thiserror::Error;
 
-const MIN_NDK_VERSION: Version = Version {
+pub const MIN_NDK_VERSION: Version = Version {
     major: 19,
     minor: 0,
 };
 
+// Each entry is the first NDK major version to drop support for API levels
+// below the given one, newest first.
+static MIN_SDK_CONSTRAINTS: &[(u32, u32)] = &[(26, 21), (24, 19), (18, 16), (16, 14)];
+
+#[derive(Debug, Error)]
+#[error("NDK {ndk_version} doesn't support API levels below {min_supported}, but `min-sdk-version` is set to {min_sdk}.")]
+pub struct MinSdkError {
+    min_sdk: u32,
+    ndk_version: Version,
+    min_supported: u32,
+}
+
+/// The oldest API level `ndk_version` can target, if it dropped any.
+pub fn min_supported_sdk(ndk_version: Version) -> Option<u32> {
+    MIN_SDK_CONSTRAINTS
+        .iter()
+        .find(|(ndk_major, _)| ndk_version.major >= *ndk_major)
+        .map(|(_, min_supported)| *min_supported)
+}
+
+pub fn validate_min_sdk(min_sdk: u32, ndk_version: Version) -> Result<(), MinSdkError> {
+    match min_supported_sdk(ndk_version) {
+        Some(min_supported) if min_sdk < min_supported => Err(MinSdkError {
+            min_sdk,
+            ndk_version,
+            min_supported,
+        }),
+        _ => Ok(()),
+    }
+}
+
 #[cfg(target_os = "macos")]
 pub fn host_tag() -> &'static str {
     "darwin-x86_64"
@@ -26,16 +66,147 @@ pub fn host_tag() -> &'static str {
     "linux-x86_64"
 }
 
-#[cfg(all(target_os = "window", target_pointer_width = "32"))]
+#[cfg(all(target_os = "windows", target_pointer_width = "32"))]
 pub fn host_tag() -> &'static str {
     "windows"
 }
 
-#[cfg(all(target_os = "window", target_pointer_width = "64"))]
+#[cfg(all(target_os = "windows", target_pointer_width = "64"))]
 pub fn host_tag() -> &'static str {
     "windows-x86_64"
 }
 
+// On Windows, the NDK's clang wrappers are batch scripts and everything else
+// is a normal executable.
+#[cfg(windows)]
+fn script_name(name: String) -> String {
+    format!("{}.cmd", name)
+}
+
+#[cfg(not(windows))]
+fn script_name(name: String) -> String {
+    name
+}
+
+#[cfg(windows)]
+fn exe_name(name: String) -> String {
+    format!("{}.exe", name)
+}
+
+#[cfg(not(windows))]
+fn exe_name(name: String) -> String {
+    name
+}
+
+// Checked in this order; the first one that's set wins, even if it's broken,
+// since pointing one of these at an NDK is an explicit choice.
+pub(crate) static NDK_HOME_VARS: &[&str] = &[
+    "NDK_HOME",
+    "ANDROID_NDK_HOME",
+    "ANDROID_NDK_ROOT",
+    "ANDROID_NDK",
+];
+
+// The NDK that `cargo android install-ndk` installs when no version is given,
+// which is r21e.
+pub static DEFAULT_INSTALL_VERSION: &str = "21.4.7075529";
+
+fn explicit_ndk_home() -> Option<(&'static str, PathBuf)> {
+    NDK_HOME_VARS.iter().find_map(|var| {
+        std::env::var_os(var)
+            .filter(|value| !value.is_empty())
+            .map(|value| (*var, PathBuf::from(value)))
+    })
+}
+
+// Where NDKs tend to end up when nobody told us where to look: the default SDK
+// locations used by Android Studio, and the places package managers put
+// standalone NDKs.
+fn common_locations() -> Vec<PathBuf> {
+    let mut sdk_roots = Vec::new();
+    if let Ok(home) = util::home_dir() {
+        sdk_roots.push(home.join("Library/Android/sdk"));
+        sdk_roots.push(home.join("Android/Sdk"));
+    }
+    if let Some(local_app_data) = std::env::var_os("LOCALAPPDATA") {
+        sdk_roots.push(PathBuf::from(local_app_data).join("Android").join("Sdk"));
+    }
+    let mut locations = sdk_roots
+        .iter()
+        .flat_map(|sdk_root| in_sdk(sdk_root))
+        .collect::<Vec<_>>();
+    locations.extend(
+        [
+            "/usr/local/share/android-ndk",
+            "/opt/homebrew/share/android-ndk",
+            "/opt/android-ndk",
+            "/usr/lib/android-ndk",
+        ]
+        .iter()
+        .map(PathBuf::from),
+    );
+    locations
+}
+
+// Side-by-side NDKs live in `ndk/<version>`, while the legacy one lives in
+// `ndk-bundle`.
+fn in_sdk(sdk_root: &Path) -> Vec<PathBuf> {
+    let mut locations = std::fs::read_dir(sdk_root.join("ndk"))
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .collect()
+        })
+        .unwrap_or_else(|_| Vec::new());
+    // Our `Version` doesn't capture the build number, so this makes sure the
+    // newest build of a release comes first.
+    locations.sort_by_key(|path| {
+        std::cmp::Reverse(
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| {
+                    name.split('.')
+                        .map(|part| part.parse::<u32>().unwrap_or_default())
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default(),
+        )
+    });
+    locations.push(sdk_root.join("ndk-bundle"));
+    locations
+}
+
+/// An NDK that was found by [`discover`].
+#[derive(Clone, Debug)]
+pub struct Installed {
+    pub home: PathBuf,
+    pub version: Version,
+}
+
+/// Finds every NDK installed in `sdk_root` or any of the usual places, newest
+/// first. Directories that don't contain a readable `source.properties` aren't
+/// NDKs (or are broken ones), so they're skipped.
+pub fn discover(sdk_root: &Path) -> Vec<Installed> {
+    let mut seen = HashSet::new();
+    let mut installed = in_sdk(sdk_root)
+        .into_iter()
+        .chain(common_locations())
+        .filter(|home| home.join("source.properties").is_file())
+        .filter(|home| seen.insert(home.canonicalize().unwrap_or_else(|_| home.clone())))
+        .filter_map(|home| match version_at(&home) {
+            Ok(version) => Some(Installed { home, version }),
+            Err(err) => {
+                log::info!("ignoring NDK at {:?}: {}", home, err);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    // This sort is stable, so the SDK's own NDK wins ties.
+    installed.sort_by(|a, b| b.version.cmp(&a.version));
+    installed
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum Compiler {
     Clang,
@@ -171,11 +342,65 @@ impl Display for Version {
     }
 }
 
+#[derive(Debug, Error)]
+#[error(
+    "{version:?} isn't a valid NDK version; it should look like \"r21\", \"r21e\", or \"21.4\"."
+)]
+pub struct VersionParseError {
+    version: String,
+}
+
+impl FromStr for Version {
+    type Err = VersionParseError;
+
+    // Accepts both release names (`r21e`) and revisions (`21.4.7075529`), since
+    // the former is what the NDK's download page uses and the latter is what
+    // `sdkmanager` and `source.properties` use.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || VersionParseError {
+            version: s.to_owned(),
+        };
+        let trimmed = s.trim();
+        if let Some(release) = trimmed
+            .strip_prefix('r')
+            .or_else(|| trimmed.strip_prefix('R'))
+        {
+            let (major, letter) = match release.char_indices().last() {
+                Some((index, c)) if c.is_ascii_alphabetic() => {
+                    (&release[..index], Some(c.to_ascii_lowercase()))
+                }
+                _ => (release, None),
+            };
+            Ok(Self {
+                major: major.parse().map_err(|_| invalid())?,
+                minor: letter.map_or(0, |letter| u32::from(letter as u8 - b'a')),
+            })
+        } else {
+            let mut components = trimmed.split('.');
+            let major = components
+                .next()
+                .and_then(|major| major.parse().ok())
+                .ok_or_else(invalid)?;
+            let minor = components
+                .next()
+                .map(|minor| minor.parse().map_err(|_| invalid()))
+                .transpose()?
+                .unwrap_or_default();
+            Ok(Self { major, minor })
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
-    // TODO: link to docs/etc.
-    NdkHomeNotSet(std::env::VarError),
-    NdkHomeNotADir,
+    NotFound,
+    NdkHomeNotADir {
+        var: &'static str,
+        path: PathBuf,
+    },
+    UserNdkHomeNotADir {
+        path: PathBuf,
+    },
     VersionLookupFailed(VersionError),
     VersionTooLow {
         you_have: Version,
@@ -186,14 +411,20 @@ pub enum Error {
 impl Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::NdkHomeNotSet(err) => write!(
+            Self::NotFound => write!(
                 f,
-                "Have you installed the NDK? The `NDK_HOME` environment variable isn't set, and is required: {}",
-                err,
+                "Have you installed the NDK? None of {} are set, `android.ndk-home` isn't set in your user config, and no NDK was found in your Android SDK or any of the usual places. You can install one by running `cargo android install-ndk`.",
+                util::list_display(&NDK_HOME_VARS.iter().map(|var| format!("`{}`", var)).collect::<Vec<_>>()),
             ),
-            Self::NdkHomeNotADir => write!(
+            Self::NdkHomeNotADir { var, path } => write!(
                 f,
-                "Have you installed the NDK? The `NDK_HOME` environment variable is set, but doesn't point to an existing directory."
+                "Have you installed the NDK? The `{}` environment variable is set to {:?}, which isn't an existing directory.",
+                var, path,
+            ),
+            Self::UserNdkHomeNotADir { path } => write!(
+                f,
+                "Have you installed the NDK? `android.ndk-home` in your user config is set to {:?}, which isn't an existing directory.",
+                path,
             ),
             Self::VersionLookupFailed(err) => {
                 write!(f, "Failed to lookup version of installed NDK: {}", err)
@@ -219,7 +450,7 @@ pub enum RequiredLibsError {
     #[error(transparent)]
     MissingTool(#[from] MissingToolError),
     #[error(transparent)]
-    ReadElfFailed(#[from] bossy::Error),
+    ReadElfFailed(#[from] CommandError),
     #[error("`readelf` output contained invalid UTF-8: {0}")]
     InvalidUtf8(#[from] std::str::Utf8Error),
 }
@@ -235,28 +466,104 @@ pub struct Env {
     ndk_home: PathBuf,
 }
 
+fn version_at(ndk_home: &Path) -> Result<Version, VersionError> {
+    let path = ndk_home.join("source.properties");
+    let file = File::open(&path).map_err(|cause| VersionError::OpenFailed {
+        path: path.clone(),
+        cause,
+    })?;
+    let props = java_properties::read(file).map_err(|cause| VersionError::ParseFailed {
+        path: path.clone(),
+        cause,
+    })?;
+    let revision = props
+        .get("Pkg.Revision")
+        .ok_or_else(|| VersionError::VersionMissing { path: path.clone() })?;
+    // The possible revision formats can be found in the comments of
+    // `$NDK_HOME/build/cmake/android.toolchain.cmake` - only the last component
+    // can be non-numerical, which we're not using anyway. If that changes,
+    // then the aforementioned file contains a regex we can use.
+    let components = revision
+        .split('.')
+        .take(2)
+        .map(|component| {
+            component
+                .parse::<u32>()
+                .map_err(|cause| VersionError::ComponentNotNumerical {
+                    path: path.clone(),
+                    component: component.to_owned(),
+                    cause,
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if components.len() == 2 {
+        Ok(Version {
+            major: components[0],
+            minor: components[1],
+        })
+    } else {
+        Err(VersionError::TooFewComponents {
+            path,
+            version: revision.to_owned(),
+        })
+    }
+}
+
+fn check_version(version: Version, min_version: Version) -> Result<(), Error> {
+    if version >= min_version {
+        Ok(())
+    } else {
+        Err(Error::VersionTooLow {
+            you_have: version,
+            you_need: min_version,
+        })
+    }
+}
+
 impl Env {
-    pub fn new() -> Result<Self, Error> {
-        let ndk_home = std::env::var("NDK_HOME")
-            .map_err(Error::NdkHomeNotSet)
-            .map(PathBuf::from)
-            .and_then(|ndk_home| {
-                if ndk_home.is_dir() {
-                    Ok(ndk_home)
-                } else {
-                    Err(Error::NdkHomeNotADir)
-                }
-            })?;
-        let env = Self { ndk_home };
-        let version = env.version().map_err(Error::VersionLookupFailed)?;
-        if version >= MIN_NDK_VERSION {
-            Ok(env)
-        } else {
-            Err(Error::VersionTooLow {
-                you_have: version,
-                you_need: MIN_NDK_VERSION,
-            })
+    /// Uses the NDK from the first of `NDK_HOME`, `ANDROID_NDK_HOME`,
+    /// `ANDROID_NDK_ROOT`, and `ANDROID_NDK` that's set, then the one
+    /// `android.ndk-home` in the user config points to, and otherwise the
+    /// newest one [`discover`] finds.
+    pub fn new(sdk_root: &Path) -> Result<Self, Error> {
+        if let Some((var, ndk_home)) = explicit_ndk_home() {
+            if !ndk_home.is_dir() {
+                return Err(Error::NdkHomeNotADir {
+                    var,
+                    path: ndk_home,
+                });
+            }
+            let version = version_at(&ndk_home).map_err(Error::VersionLookupFailed)?;
+            check_version(version, MIN_NDK_VERSION)?;
+            log::info!("using NDK {} from `{}` at {:?}", version, var, ndk_home);
+            return Ok(Self { ndk_home });
         }
+        if let Some(ndk_home) = global::get().android_ndk_home() {
+            if !ndk_home.is_dir() {
+                return Err(Error::UserNdkHomeNotADir { path: ndk_home });
+            }
+            let version = version_at(&ndk_home).map_err(Error::VersionLookupFailed)?;
+            check_version(version, MIN_NDK_VERSION)?;
+            log::info!(
+                "using NDK {} from `android.ndk-home` in the user config at {:?}",
+                version,
+                ndk_home
+            );
+            return Ok(Self { ndk_home });
+        }
+        let newest = discover(sdk_root)
+            .into_iter()
+            .next()
+            .ok_or(Error::NotFound)?;
+        check_version(newest.version, MIN_NDK_VERSION)?;
+        log::info!(
+            "no NDK environment variable is set; using NDK {} found at {:?}",
+            newest.version,
+            newest.home
+        );
+        Ok(Self {
+            ndk_home: newest.home,
+        })
     }
 
     pub fn home(&self) -> &Path {
@@ -264,46 +571,16 @@ impl Env {
     }
 
     pub fn version(&self) -> Result<Version, VersionError> {
-        let path = self.ndk_home.join("source.properties");
-        let file = File::open(&path).map_err(|cause| VersionError::OpenFailed {
-            path: path.clone(),
-            cause,
-        })?;
-        let props = java_properties::read(file).map_err(|cause| VersionError::ParseFailed {
-            path: path.clone(),
-            cause,
-        })?;
-        let revision = props
-            .get("Pkg.Revision")
-            .ok_or_else(|| VersionError::VersionMissing { path: path.clone() })?;
-        // The possible revision formats can be found in the comments of
-        // `$NDK_HOME/build/cmake/android.toolchain.cmake` - only the last component
-        // can be non-numerical, which we're not using anyway. If that changes,
-        // then the aforementioned file contains a regex we can use.
-        let components = revision
-            .split('.')
-            .take(2)
-            .map(|component| {
-                component
-                    .parse::<u32>()
-                    .map_err(|cause| VersionError::ComponentNotNumerical {
-                        path: path.clone(),
-                        component: component.to_owned(),
-                        cause,
-                    })
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-        if components.len() == 2 {
-            Ok(Version {
-                major: components[0],
-                minor: components[1],
-            })
-        } else {
-            Err(VersionError::TooFewComponents {
-                path,
-                version: revision.to_owned(),
-            })
-        }
+        version_at(&self.ndk_home)
+    }
+
+    /// Checks this NDK against a minimum on top of the one cargo-mobile
+    /// itself requires, i.e. `android.min-ndk-version`.
+    pub fn ensure_version_at_least(&self, min_version: Version) -> Result<(), Error> {
+        check_version(
+            self.version().map_err(Error::VersionLookupFailed)?,
+            min_version,
+        )
     }
 
     pub fn prebuilt_dir(&self) -> Result<PathBuf, MissingToolError> {
@@ -327,7 +604,7 @@ impl Env {
     ) -> Result<PathBuf, MissingToolError> {
         MissingToolError::check_file(
             self.tool_dir()?
-                .join(format!("{}{}-{}", triple, min_api, compiler.as_str())),
+                .join(compiler_file_name(compiler, triple, min_api)),
             compiler.as_str(),
         )
     }
@@ -339,11 +616,33 @@ impl Env {
     ) -> Result<PathBuf, MissingToolError> {
         MissingToolError::check_file(
             self.tool_dir()?
-                .join(format!("{}-{}", triple, binutil.as_str())),
+                .join(exe_name(format!("{}-{}", triple, binutil.as_str()))),
             binutil.as_str(),
         )
     }
 
+    /// The env vars that point cargo and `cc`-style build scripts at this NDK's
+    /// tools for `target`. They're all keyed by triple, so they can't leak
+    /// into host builds (i.e. build scripts and proc macros).
+    pub fn cargo_env(
+        &self,
+        target: &Target<'_>,
+        min_api: u32,
+    ) -> Result<Vec<(String, PathBuf)>, MissingToolError> {
+        let cc = self.compiler_path(Compiler::Clang, target.clang_triple(), min_api)?;
+        let cxx = self.compiler_path(Compiler::Clangxx, target.clang_triple(), min_api)?;
+        let ar = self.binutil_path(Binutil::Ar, target.binutils_triple())?;
+        let triple = target.triple.replace('-', "_");
+        Ok(vec![
+            (format!("CC_{}", triple), cc.clone()),
+            (format!("CXX_{}", triple), cxx),
+            (format!("AR_{}", triple), ar),
+            // Using clang as the linker seems to be the only way to get the
+            // right library search paths...
+            (format!("CARGO_TARGET_{}_LINKER", triple.to_uppercase()), cc),
+        ])
+    }
+
     pub fn libcxx_shared_path(&self, target: Target<'_>) -> Result<PathBuf, MissingToolError> {
         static LIB: &str = "libc++_shared.so";
         MissingToolError::check_file(
@@ -357,7 +656,8 @@ impl Env {
 
     fn readelf_path(&self, triple: &str) -> Result<PathBuf, MissingToolError> {
         MissingToolError::check_file(
-            self.tool_dir()?.join(format!("{}-readelf", triple)),
+            self.tool_dir()?
+                .join(exe_name(format!("{}-readelf", triple))),
             "readelf",
         )
     }
@@ -369,10 +669,12 @@ impl Env {
     ) -> Result<HashSet<String>, RequiredLibsError> {
         Ok(regex_multi_line!(r"\(NEEDED\)\s+Shared library: \[(.+)\]")
             .captures_iter(
-                bossy::Command::impure(self.readelf_path(triple)?)
-                    .with_arg("-d")
-                    .with_arg(elf)
-                    .run_and_wait_for_output()?
+                Runner::global()
+                    .capture(
+                        &Invocation::impure(self.readelf_path(triple)?)
+                            .with_arg("-d")
+                            .with_arg(elf),
+                    )?
                     .stdout_str()?,
             )
             .map(|caps| {
@@ -386,3 +688,165 @@ impl Env {
             .collect())
     }
 }
+
+#[derive(Debug)]
+pub enum InstallError {
+    SdkManagerMissing { sdk_root: PathBuf },
+    SdkManagerFailed(CommandError),
+    NotInstalled { path: PathBuf },
+}
+
+impl Reportable for InstallError {
+    fn report(&self) -> Report {
+        match self {
+            Self::SdkManagerMissing { sdk_root } => Report::action_request(
+                "Failed to find `sdkmanager`",
+                format!(
+                    "It wasn't in {:?} or on your `PATH`; please install the Android SDK Command-line Tools from Android Studio's SDK Manager and try again.",
+                    sdk_root,
+                ),
+            ),
+            Self::SdkManagerFailed(err) => err.report("Failed to install NDK"),
+            Self::NotInstalled { path } => Report::error(
+                "Failed to install NDK",
+                format!(
+                    "`sdkmanager` succeeded, but the NDK didn't end up at {:?}.",
+                    path
+                ),
+            ),
+        }
+    }
+}
+
+fn sdkmanager_file_name() -> &'static str {
+    if cfg!(windows) {
+        "sdkmanager.bat"
+    } else {
+        "sdkmanager"
+    }
+}
+
+// The command-line tools replaced the legacy SDK tools, so we prefer them.
+fn sdkmanager_path(sdk_root: &Path) -> Option<PathBuf> {
+    ["cmdline-tools/latest/bin", "tools/bin"]
+        .iter()
+        .map(|dir| sdk_root.join(dir).join(sdkmanager_file_name()))
+        .find(|path| path.is_file())
+        .or_else(|| util::resolve_program(sdkmanager_file_name()))
+}
+
+/// Installs the NDK with revision `version` (i.e. `21.4.7075529`) into
+/// `sdk_root` using `sdkmanager`, returning where it ended up.
+pub fn install(sdk_root: &Path, version: &str) -> Result<PathBuf, InstallError> {
+    let sdkmanager = sdkmanager_path(sdk_root).ok_or_else(|| InstallError::SdkManagerMissing {
+        sdk_root: sdk_root.to_owned(),
+    })?;
+    println!("Installing NDK {} into {:?}...", version, sdk_root);
+    Runner::global()
+        .stream(
+            &Invocation::impure(&sdkmanager)
+                .with_arg(format!("--sdk_root={}", sdk_root.display()))
+                .with_args(&["--install", &format!("ndk;{}", version)]),
+        )
+        .map_err(InstallError::SdkManagerFailed)?;
+    let path = sdk_root.join("ndk").join(version);
+    if path.join("source.properties").is_file() {
+        Ok(path)
+    } else {
+        Err(InstallError::NotInstalled { path })
+    }
+}
+
+// The compiler wrappers are named after the clang triple and the API level
+// they target.
+fn compiler_file_name(compiler: Compiler, clang_triple: &str, min_api: u32) -> String {
+    script_name(format!("{}{}-{}", clang_triple, min_api, compiler.as_str()))
+}
+
+/// Gets the path to the clang wrapper that links for `triple` at `api_level`,
+/// i.e. `aarch64-linux-android21-clang`. If it doesn't exist, the error says
+/// exactly which file was expected, which is a lot more useful than the
+/// "linker not found" you'd otherwise get from cargo.
+pub fn android_linker_path(ndk_bin: &Path, triple: &str, api_level: u32) -> io::Result<PathBuf> {
+    let clang_triple = Target::all()
+        .values()
+        .find(|target| target.triple == triple)
+        .map_or(triple, Target::clang_triple);
+    let file_name = compiler_file_name(Compiler::Clang, clang_triple, api_level);
+    let path = ndk_bin.join(&file_name);
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "the linker for {} at API level {} should be {:?} in {:?}, but it's not there; your NDK might be too old or too new for that API level",
+                triple, api_level, file_name, ndk_bin
+            ),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_cmdline_tools_sdkmanager_is_preferred() {
+        let dir = tempfile::tempdir().unwrap();
+        let sdk_root = dir.path();
+        for tools_dir in &["cmdline-tools/latest/bin", "tools/bin"] {
+            let tools_dir = sdk_root.join(tools_dir);
+            std::fs::create_dir_all(&tools_dir).unwrap();
+            std::fs::write(tools_dir.join(sdkmanager_file_name()), "").unwrap();
+        }
+        assert_eq!(
+            sdkmanager_path(sdk_root),
+            Some(
+                sdk_root
+                    .join("cmdline-tools/latest/bin")
+                    .join(sdkmanager_file_name())
+            )
+        );
+        std::fs::remove_dir_all(sdk_root.join("cmdline-tools")).unwrap();
+        assert_eq!(
+            sdkmanager_path(sdk_root),
+            Some(sdk_root.join("tools/bin").join(sdkmanager_file_name()))
+        );
+    }
+
+    #[test]
+    fn linkers_are_looked_up_by_clang_triple_and_api_level() {
+        let dir = tempfile::tempdir().unwrap();
+        let ndk_bin = dir.path();
+        for target in Target::all().values() {
+            let linker = ndk_bin.join(script_name(format!("{}21-clang", target.clang_triple())));
+            std::fs::write(&linker, "").unwrap();
+            assert_eq!(
+                android_linker_path(ndk_bin, target.triple, 21).unwrap(),
+                linker
+            );
+            let err = android_linker_path(ndk_bin, target.triple, 19).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::NotFound);
+            assert!(err
+                .to_string()
+                .contains(&format!("{}19-clang", target.clang_triple())));
+        }
+        // 32-bit ARM is the only one whose clang triple differs
+        assert!(ndk_bin
+            .join(script_name("armv7a-linux-androideabi21-clang".to_owned()))
+            .is_file());
+    }
+
+    #[test]
+    fn min_sdk_is_checked_against_what_the_ndk_supports() {
+        let version = |version: &str| version.parse::<Version>().unwrap();
+        assert!(validate_min_sdk(21, version("r26")).is_ok());
+        let err = validate_min_sdk(19, version("26.1.10909125")).unwrap_err();
+        assert_eq!(err.min_supported, 21);
+        assert!(validate_min_sdk(19, version("r24")).is_ok());
+        assert!(validate_min_sdk(15, version("r23c")).is_err());
+        // Older than anything we have constraints for
+        assert!(validate_min_sdk(9, version("r15")).is_ok());
+    }
+}
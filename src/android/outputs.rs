@@ -0,0 +1,143 @@
+use serde::Deserialize;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+pub static FILE_NAME: &str = "output-metadata.json";
+
+#[derive(Debug, thiserror::Error)]
+pub enum OutputsError {
+    #[error("No \"output-metadata.json\" was found for variant {variant:?} in {outputs_dir:?}")]
+    MetadataMissing {
+        variant: String,
+        outputs_dir: PathBuf,
+    },
+    #[error("Failed to read {path:?}: {cause}")]
+    ReadFailed { path: PathBuf, cause: io::Error },
+    #[error("Failed to parse {path:?}: {cause}")]
+    ParseFailed {
+        path: PathBuf,
+        cause: serde_json::Error,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Element {
+    output_file: PathBuf,
+}
+
+// There's a lot more in here, but the output files are all we care about.
+#[derive(Debug, Deserialize)]
+struct OutputMetadata {
+    elements: Vec<Element>,
+}
+
+/// Resolves the output files listed in an `output-metadata.json`, which are
+/// relative to the directory the metadata is in.
+fn resolve_outputs(path: &Path, raw: &[u8]) -> Result<Vec<PathBuf>, OutputsError> {
+    let metadata = serde_json::from_slice::<OutputMetadata>(raw).map_err(|cause| {
+        OutputsError::ParseFailed {
+            path: path.to_owned(),
+            cause,
+        }
+    })?;
+    let dir = path
+        .parent()
+        .expect("developer error: output metadata path had no parent");
+    Ok(metadata
+        .elements
+        .into_iter()
+        .map(|element| dir.join(element.output_file))
+        .collect())
+}
+
+/// Gets the artifacts Gradle reported building for `variant`, which is the
+/// variant's path under the outputs dir (i.e. `arm64/debug`). APK outputs
+/// always come with metadata, but bundle outputs might not, in which case any
+/// `.aab` files in the bundle dir are returned instead.
+pub fn gradle_build_outputs(
+    app_module_dir: &Path,
+    variant: &str,
+) -> Result<Vec<PathBuf>, OutputsError> {
+    let outputs_dir = app_module_dir.join("build/outputs");
+    for kind in &["apk", "bundle"] {
+        let dir = outputs_dir.join(kind).join(variant);
+        let path = dir.join(FILE_NAME);
+        if path.is_file() {
+            log::info!("reading gradle outputs from {:?}", path);
+            let raw = std::fs::read(&path).map_err(|cause| OutputsError::ReadFailed {
+                path: path.clone(),
+                cause,
+            })?;
+            return resolve_outputs(&path, &raw);
+        }
+        if *kind == "bundle" && dir.is_dir() {
+            let bundles = std::fs::read_dir(&dir)
+                .and_then(|entries| {
+                    entries
+                        .map(|entry| entry.map(|entry| entry.path()))
+                        .collect::<io::Result<Vec<_>>>()
+                })
+                .map_err(|cause| OutputsError::ReadFailed {
+                    path: dir.clone(),
+                    cause,
+                })?
+                .into_iter()
+                .filter(|path| path.extension().map_or(false, |ext| ext == "aab"))
+                .collect::<Vec<_>>();
+            if !bundles.is_empty() {
+                return Ok(bundles);
+            }
+        }
+    }
+    Err(OutputsError::MetadataMissing {
+        variant: variant.to_owned(),
+        outputs_dir,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn outputs_are_resolved_relative_to_their_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = dir.path();
+        let apk_dir = app.join("build/outputs/apk/arm64/debug");
+        fs::create_dir_all(&apk_dir).unwrap();
+        fs::write(
+            apk_dir.join(FILE_NAME),
+            r#"{
+                "version": 3,
+                "elements": [{ "type": "SINGLE", "outputFile": "app-arm64-debug.apk" }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            gradle_build_outputs(app, "arm64/debug").unwrap(),
+            vec![apk_dir.join("app-arm64-debug.apk")]
+        );
+        assert!(matches!(
+            gradle_build_outputs(app, "arm64/release"),
+            Err(OutputsError::MetadataMissing { .. })
+        ));
+    }
+
+    #[test]
+    fn bundles_without_metadata_are_found_by_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = dir.path();
+        let bundle_dir = app.join("build/outputs/bundle/universalRelease");
+        fs::create_dir_all(&bundle_dir).unwrap();
+        fs::write(bundle_dir.join("app-universal-release.aab"), "").unwrap();
+        fs::write(bundle_dir.join("notes.txt"), "").unwrap();
+        assert_eq!(
+            gradle_build_outputs(app, "universalRelease").unwrap(),
+            vec![bundle_dir.join("app-universal-release.aab")]
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use crate::{
+    os,
+    util::{CommandError, Invocation, Runner},
+};
+use colored::Colorize as _;
+use std::{
+    collections::VecDeque,
+    fmt::{self, Display},
+    io::{self, Read as _},
+    process::Stdio,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+};
+
+/// A command to run alongside others, along with the label to show for it.
+#[derive(Debug)]
+pub struct Job {
+    pub label: String,
+    pub command: Invocation,
+}
+
+#[derive(Debug)]
+pub enum Cause {
+    PipeFailed(io::Error),
+    CommandFailed(CommandError),
+    ReadFailed(io::Error),
+}
+
+#[derive(Debug)]
+pub struct Error {
+    label: String,
+    cause: Cause,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.cause {
+            Cause::PipeFailed(err) => {
+                write!(
+                    f,
+                    "Failed to create output pipe for {}: {}",
+                    self.label, err
+                )
+            }
+            Cause::CommandFailed(err) => write!(f, "Build for {} failed: {}", self.label, err),
+            Cause::ReadFailed(err) => {
+                write!(
+                    f,
+                    "Failed to read output of build for {}: {}",
+                    self.label, err
+                )
+            }
+        }
+    }
+}
+
+// Runs `command` with stdout and stderr captured together, publishing its PID
+// to `running` for as long as it's alive so that it can be killed early.
+fn run_captured(
+    command: &Invocation,
+    running: &Mutex<Vec<u32>>,
+    cancelled: &AtomicBool,
+) -> (String, Result<(), Cause>) {
+    let pipe = os::pipe().and_then(|(reader, writer)| {
+        writer
+            .try_clone()
+            .map(|stderr_writer| (reader, writer, stderr_writer))
+    });
+    let (mut reader, writer, stderr_writer) = match pipe {
+        Ok(pipe) => pipe,
+        Err(err) => return (String::new(), Err(Cause::PipeFailed(err))),
+    };
+    // Passing the write ends to `spawn` means they get closed on our side, so
+    // we'll see EOF once the command exits.
+    let child = match Runner::global().spawn(
+        command,
+        Stdio::null(),
+        Stdio::from(writer),
+        Stdio::from(stderr_writer),
+    ) {
+        Ok(child) => child,
+        Err(err) => return (String::new(), Err(Cause::CommandFailed(err))),
+    };
+    let pid = child.id();
+    running
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .push(pid);
+    // If another job failed between us checking `cancelled` and publishing our
+    // PID, then nobody else is going to kill us.
+    if cancelled.load(Ordering::SeqCst) {
+        os::kill_child(pid);
+    }
+    let mut output = Vec::new();
+    let read_result = reader.read_to_end(&mut output);
+    let wait_result = child.wait();
+    running
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .retain(|running| *running != pid);
+    let output = String::from_utf8_lossy(&output).into_owned();
+    let result = wait_result
+        .map(|_| ())
+        .map_err(Cause::CommandFailed)
+        .and_then(|()| read_result.map(|_| ()).map_err(Cause::ReadFailed));
+    (output, result)
+}
+
+// Stops any more jobs from starting, and kills the ones that are running.
+fn cancel(running: &Mutex<Vec<u32>>, cancelled: &AtomicBool) {
+    cancelled.store(true, Ordering::SeqCst);
+    for pid in running.lock().unwrap_or_else(|err| err.into_inner()).iter() {
+        os::kill_child(*pid);
+    }
+}
+
+/// Runs `jobs`, with at most `max_jobs` running at once. Each job's output is
+/// captured and printed in one piece once it's done, so output from different
+/// jobs never gets interleaved. As soon as a job fails, no more jobs are
+/// started and the ones still running are killed.
+pub fn run(jobs: Vec<Job>, max_jobs: usize) -> Result<(), Error> {
+    let worker_count = max_jobs.max(1).min(jobs.len());
+    let queue = Arc::new(Mutex::new(jobs.into_iter().collect::<VecDeque<_>>()));
+    let running = Arc::new(Mutex::new(Vec::new()));
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = mpsc::channel();
+    let workers = (0..worker_count)
+        .map(|_| {
+            let (queue, running, cancelled, tx) = (
+                Arc::clone(&queue),
+                Arc::clone(&running),
+                Arc::clone(&cancelled),
+                tx.clone(),
+            );
+            thread::spawn(move || loop {
+                if cancelled.load(Ordering::SeqCst) {
+                    break;
+                }
+                let job = queue
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .pop_front();
+                if let Some(Job { label, command }) = job {
+                    println!("Building {}...", label);
+                    let (output, result) = run_captured(&command, &running, &cancelled);
+                    // This happens here rather than once the result is
+                    // received, since otherwise we could start the next job
+                    // in the meantime.
+                    if result.is_err() {
+                        cancel(&running, &cancelled);
+                    }
+                    if tx.send((label, output, result)).is_err() {
+                        break;
+                    }
+                } else {
+                    break;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    // Otherwise, we'd never stop waiting for messages.
+    drop(tx);
+    let mut first_error = None;
+    for (label, output, result) in rx {
+        if first_error.is_some() {
+            // Anything finishing after a failure was probably killed by us,
+            // so its output would just be noise.
+            continue;
+        }
+        print!("{}", output);
+        match result {
+            Ok(()) => println!("{} {}", "Finished".green().bold(), label),
+            Err(cause) => {
+                println!("{} {}", "Failed".red().bold(), label);
+                first_error = Some(Error { label, cause });
+            }
+        }
+    }
+    for worker in workers {
+        if worker.join().is_err() {
+            log::error!("a build worker thread panicked");
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn job(label: &str, script: &str) -> Job {
+        Job {
+            label: label.to_owned(),
+            command: Invocation::impure("sh").with_args(&["-c", script]),
+        }
+    }
+
+    #[test]
+    fn failures_kill_the_jobs_still_running() {
+        let start = Instant::now();
+        // `exec`, since otherwise killing the shell would leave `sleep` behind
+        // holding the output pipe open.
+        let err = run(
+            vec![
+                job("slow", "exec sleep 30"),
+                job("broken", "sleep 0.2; exit 1"),
+            ],
+            2,
+        )
+        .unwrap_err();
+        assert_eq!(err.label, "broken");
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn failures_stop_queued_jobs_from_starting() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("started");
+        let err = run(
+            vec![
+                job("broken", "exit 1"),
+                job("queued", &format!("touch {:?}", marker)),
+            ],
+            1,
+        )
+        .unwrap_err();
+        assert_eq!(err.label, "broken");
+        assert!(!marker.exists());
+    }
+}
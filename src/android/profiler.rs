@@ -0,0 +1,179 @@
+//! Drives the profilers that come with Android, for `cargo android profile`.
+
+use super::{
+    adb,
+    config::Config,
+    device::{self, Device, RunError},
+    env::Env,
+};
+use crate::{
+    opts::{NoiseLevel, Profile},
+    profiling::{self, TraceDirFailed},
+    util::{
+        self,
+        cli::{Report, Reportable},
+        CommandError, Invocation, Runner,
+    },
+};
+use std::{
+    fmt::{self, Display},
+    path::PathBuf,
+    str::FromStr,
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Profiler {
+    /// Samples CPU call stacks, which is what you want for finding hot code.
+    Simpleperf,
+    /// Records a system-wide timeline, which is better for jank and
+    /// scheduling problems.
+    Perfetto,
+}
+
+impl Display for Profiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug)]
+pub struct ProfilerInvalid {
+    name: String,
+}
+
+impl Display for ProfilerInvalid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} isn't a profiler; the possible profilers are {:?}",
+            self.name,
+            Profiler::NAMES
+        )
+    }
+}
+
+impl FromStr for Profiler {
+    type Err = ProfilerInvalid;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|profiler| profiler.name() == s)
+            .ok_or_else(|| ProfilerInvalid { name: s.to_owned() })
+    }
+}
+
+impl Profiler {
+    pub const ALL: &'static [Self] = &[Self::Simpleperf, Self::Perfetto];
+
+    pub const NAMES: &'static [&'static str] = &["simpleperf", "perfetto"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Simpleperf => "simpleperf",
+            Self::Perfetto => "perfetto",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Simpleperf => "data",
+            Self::Perfetto => "perfetto-trace",
+        }
+    }
+
+    // `perfetto` can only write to its own dir, and `simpleperf` needs
+    // somewhere the shell user can read back.
+    fn remote_path(self) -> &'static str {
+        match self {
+            Self::Simpleperf => "/data/local/tmp/cargo-mobile.data",
+            Self::Perfetto => "/data/misc/perfetto-traces/cargo-mobile.perfetto-trace",
+        }
+    }
+
+    fn record(self, env: &Env, serial_no: &str, package: &str, duration: u32) -> Invocation {
+        let command = adb::adb(env, serial_no).with_arg("shell");
+        match self {
+            Self::Simpleperf => command
+                .with_args(&["simpleperf", "record", "--app", package, "-g"])
+                .with_arg("--duration")
+                .with_arg(duration.to_string())
+                .with_args(&["-o", self.remote_path()]),
+            Self::Perfetto => command
+                .with_args(&["perfetto", "-o", self.remote_path()])
+                .with_arg("-t")
+                .with_arg(format!("{}s", duration))
+                .with_args(&["-a", package])
+                .with_args(&["sched", "freq", "idle", "am", "wm", "gfx", "view", "input"]),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ProfileError {
+    DeployFailed(RunError),
+    TraceDirFailed(TraceDirFailed),
+    RecordFailed {
+        profiler: Profiler,
+        cause: CommandError,
+    },
+    PullFailed(adb::RunCheckedError),
+}
+
+impl Reportable for ProfileError {
+    fn report(&self) -> Report {
+        match self {
+            Self::DeployFailed(err) => err.report(),
+            Self::TraceDirFailed(err) => Report::error("Failed to save profile", err),
+            Self::RecordFailed { profiler, cause } => Report::action_request(
+                format!("Failed to record profile with `{}`", profiler),
+                format!(
+                    "{}\n\nRelease builds can only be profiled on Android 10 and up, and only if the app's `AndroidManifest.xml` has `<profileable android:shell=\"true\" />` in its `<application>`.",
+                    cause
+                ),
+            ),
+            Self::PullFailed(err) => err.report("Failed to pull profile from device"),
+        }
+    }
+}
+
+/// Deploys a release build to `device`, records it with `profiler` for
+/// `duration` seconds, and pulls the trace back to a new path in
+/// `target/mobile-profiles`, which is returned.
+///
+/// The build only gets debug info if `env` has [`profiling::DEBUG_INFO_VAR`]
+/// set, since that has to reach the cargo that Gradle runs.
+pub fn profile(
+    config: &Config,
+    env: &Env,
+    device: &Device<'_>,
+    noise_level: NoiseLevel,
+    profiler: Profiler,
+    duration: u32,
+) -> Result<PathBuf, ProfileError> {
+    device
+        .deploy(config, env, noise_level, Profile::Release)
+        .map_err(ProfileError::DeployFailed)?;
+    let package = device::package(config);
+    println!(
+        "Recording {} with `{}` for {} seconds...",
+        package, profiler, duration
+    );
+    Runner::global()
+        .stream(&profiler.record(env, device.id(), &package, duration))
+        .map_err(|cause| ProfileError::RecordFailed { profiler, cause })?;
+    let trace_path = profiling::trace_path(config.app(), "android", profiler.extension())
+        .map_err(ProfileError::TraceDirFailed)?;
+    adb::pull(env, device.id(), profiler.remote_path(), &trace_path)
+        .map_err(ProfileError::PullFailed)?;
+    if let Err(err) = util::run(&adb::adb(env, device.id()).with_args(&[
+        "shell",
+        "rm",
+        "-f",
+        profiler.remote_path(),
+    ])) {
+        log::warn!("failed to remove profile from device: {}", err);
+    }
+    Ok(trace_path)
+}
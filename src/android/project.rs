@@ -1,35 +1,42 @@
-use super::{config::Config, env::Env, ndk, target::Target};
+use super::{config::Config, env::Env, java, ndk, target::Target};
 use crate::{
-    dot_cargo,
+    dot_cargo, opts,
     target::TargetTrait as _,
     templating::{self, Pack},
     util::{
         self,
-        cli::{Report, Reportable},
+        cli::{Diagnostics, Report, Reportable, TextWrapper},
         ln,
     },
 };
-use std::{fs, path::PathBuf};
+use std::path::{Path, PathBuf};
 
 pub static TEMPLATE_PACK: &str = "android-studio";
 
 #[derive(Debug)]
 pub enum Error {
-    RustupFailed(bossy::Error),
+    RustupFailed(util::CommandError),
+    PreflightFailed,
     MissingPack(templating::LookupError),
     TemplateProcessingFailed(bicycle::ProcessingError),
     DirectoryCreationFailed {
         path: PathBuf,
         cause: std::io::Error,
     },
-    AssetDirSymlinkFailed(ln::Error),
+    AssetDirLinkFailed(ln::LinkDirError),
     DotCargoGenFailed(ndk::MissingToolError),
+    IconGenFailed(crate::icon::Error),
+    RootDirRelativizeFailed(util::RelativizeError),
 }
 
 impl Reportable for Error {
     fn report(&self) -> Report {
         match self {
-            Self::RustupFailed(err) => Report::error("Failed to `rustup` Android toolchains", err),
+            Self::RustupFailed(err) => err.report("Failed to `rustup` Android toolchains"),
+            Self::PreflightFailed => Report::error(
+                "Android preflight checks failed",
+                "Please fix the errors listed above and try again.",
+            ),
             Self::MissingPack(err) => Report::error("Failed to locate Android template pack", err),
             Self::TemplateProcessingFailed(err) => {
                 Report::error("Android template processing failed", err)
@@ -38,38 +45,101 @@ impl Reportable for Error {
                 format!("Failed to create Android assets directory at {:?}", path),
                 cause,
             ),
-            Self::AssetDirSymlinkFailed(err) => {
-                Report::error("Asset dir couldn't be symlinked into Android project", err)
+            Self::AssetDirLinkFailed(err) => {
+                Report::error("Asset dir couldn't be linked into Android project", err)
             }
             Self::DotCargoGenFailed(err) => {
                 Report::error("Failed to generate Android cargo config", err)
             }
+            Self::IconGenFailed(err) => Report::error("Failed to generate Android app icons", err),
+            Self::RootDirRelativizeFailed(err) => Report::error(
+                "Failed to get the app root relative to the Android project",
+                err,
+            ),
         }
     }
 }
 
-pub fn gen(
+fn preflight(config: &Config, env: &Env, diagnostics: &mut Diagnostics) {
+    match env.ndk.version() {
+        Ok(ndk_version) => {
+            if ndk_version < config.min_ndk_version() {
+                diagnostics.error(
+                    "ndk-too-old",
+                    format!(
+                        "Your NDK is {}, but `{}.min-ndk-version` is set to {}",
+                        ndk_version,
+                        super::NAME,
+                        config.min_ndk_version()
+                    ),
+                    format!(
+                        "Please update your NDK (i.e. via `cargo android install-ndk`), or lower `{}.min-ndk-version` in your {}.",
+                        super::NAME,
+                        crate::config::file_name()
+                    ),
+                )
+            }
+            if let Err(err) = ndk::validate_min_sdk(config.min_sdk_version(), ndk_version) {
+                diagnostics.error(
+                    "min-sdk-too-low",
+                    err,
+                    format!(
+                        "Please raise `{}.min-sdk-version` in your {}, or use an older NDK.",
+                        super::NAME,
+                        crate::config::file_name()
+                    ),
+                )
+            }
+        }
+        Err(err) => log::warn!("failed to check NDK version: {}", err),
+    }
+    match util::has_dependency(config.app().root_dir(), "openssl-sys") {
+        Ok(true) => diagnostics.warn(
+            "openssl-sys",
+            "Your project depends on `openssl-sys`",
+            "OpenSSL isn't part of the NDK, so you'll need to build it for each Android target yourself and point `openssl-sys` at it (i.e. via `OPENSSL_DIR`), or switch to a pure Rust TLS implementation like `rustls`.",
+        ),
+        Ok(false) => (),
+        Err(err) => log::warn!("failed to check for native dependencies: {}", err),
+    }
+    // Gradle 5.1.1 only supports Java 8 and up, and it won't explain that
+    // very clearly itself.
+    match java::require_java_at_least((8, 0, 0)) {
+        Ok(_) => (),
+        Err(java::RequireJavaError::VersionFailed(err)) => {
+            log::warn!("failed to check Java version: {}", err)
+        }
+        Err(err) => diagnostics.warn(
+            "java-too-old",
+            "Your Java version is too old for Gradle",
+            format!(
+                "{} Please install a newer JDK and make sure it's on your `PATH`.",
+                err
+            ),
+        ),
+    }
+}
+
+/// Renders the Android Studio project templates into `dest`, which is
+/// normally the project dir, but can also be a staging dir to compare against.
+/// The app icons are generated here too, if `app.icon` is set.
+pub fn render(
     config: &Config,
-    env: &Env,
     bike: &bicycle::Bicycle,
-    filter: &templating::Filter,
-    dot_cargo: &mut dot_cargo::DotCargo,
+    dest: &Path,
+    filter: impl FnMut(&bicycle::Action) -> bool,
 ) -> Result<(), Error> {
-    println!("Installing Android toolchains...");
-    Target::install_all().map_err(Error::RustupFailed)?;
-    println!("Generating Android Studio project...");
     let src = Pack::lookup_platform(TEMPLATE_PACK)
         .map_err(Error::MissingPack)?
         .expect_local();
-    let dest = config.project_dir();
-    bike.filter_and_process(
+    let root_dir_rel = util::relativize_path(config.app().root_dir(), config.project_dir())
+        .map_err(Error::RootDirRelativizeFailed)?;
+    templating::filter_and_process(
+        bike,
         src,
-        &dest,
+        dest,
         |map| {
-            map.insert(
-                "root-dir-rel",
-                util::relativize_path(config.app().root_dir(), config.project_dir()),
-            );
+            map.insert("root-dir-rel", &root_dir_rel);
             map.insert("targets", Target::all().values().collect::<Vec<_>>());
             map.insert("target-names", Target::all().keys().collect::<Vec<_>>());
             map.insert(
@@ -80,17 +150,50 @@ pub fn gen(
                     .collect::<Vec<_>>(),
             );
         },
-        filter.fun(),
+        filter,
     )
     .map_err(Error::TemplateProcessingFailed)?;
+    if let Some(icon) = config.app().icon() {
+        super::icon::gen(icon, dest).map_err(Error::IconGenFailed)?;
+    }
+    Ok(())
+}
+
+pub fn gen(
+    config: &Config,
+    env: &Env,
+    bike: &bicycle::Bicycle,
+    filter: &templating::Filter,
+    dot_cargo: &mut dot_cargo::DotCargo,
+    wrapper: &TextWrapper,
+    force_sync: opts::ForceSync,
+) -> Result<(), Error> {
+    println!("Installing Android toolchains...");
+    Target::install_all(config.app().root_dir()).map_err(Error::RustupFailed)?;
+    let mut diagnostics = Diagnostics::new();
+    preflight(config, env, &mut diagnostics);
+    if diagnostics.report(wrapper) {
+        return Err(Error::PreflightFailed);
+    }
+    println!("Generating Android Studio project...");
+    let dest = config.project_dir();
+    render(config, bike, &dest, filter.fun())?;
 
     let dest = dest.join("app/src/main/assets/");
-    fs::create_dir_all(&dest).map_err(|cause| Error::DirectoryCreationFailed {
+    util::create_dir_all(&dest).map_err(|cause| Error::DirectoryCreationFailed {
         path: dest.clone(),
         cause,
     })?;
-    ln::force_symlink_relative(config.app().asset_dir(), dest, ln::TargetStyle::Directory)
-        .map_err(Error::AssetDirSymlinkFailed)?;
+    ln::link_dir_or_copy(
+        &config.app().asset_dir(),
+        &dest,
+        config.app().hard_copy(),
+        &config
+            .project_dir()
+            .join(util::ASSET_SYNC_MANIFEST_FILE_NAME),
+        force_sync.yes(),
+    )
+    .map_err(Error::AssetDirLinkFailed)?;
 
     {
         for target in Target::all().values() {
@@ -3,24 +3,34 @@ use super::{
     env::Env,
     jnilibs::{self, JniLibs},
     ndk,
+    parallel::{self, Job},
 };
 use crate::{
+    config::hooks::{self, Stage},
     dot_cargo::DotCargoTarget,
     opts::{ForceColor, NoiseLevel, Profile},
     target::TargetTrait,
     util::{
+        self,
         cli::{Report, Reportable},
-        CargoCommand,
+        output::{self, Event},
+        CargoCommand, CommandError, Invocation, ManifestPathError, Runner,
     },
 };
 use once_cell_regex::exports::once_cell::sync::OnceCell;
 use serde::Serialize;
-use std::{collections::BTreeMap, fmt, io, str};
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    path::{Path, PathBuf},
+    str,
+};
 
 #[derive(Clone, Copy, Debug)]
 pub enum CargoMode {
     Check,
     Build,
+    Test,
 }
 
 impl fmt::Display for CargoMode {
@@ -28,6 +38,7 @@ impl fmt::Display for CargoMode {
         match self {
             CargoMode::Check => write!(f, "check"),
             CargoMode::Build => write!(f, "build"),
+            CargoMode::Test => write!(f, "test"),
         }
     }
 }
@@ -37,6 +48,7 @@ impl CargoMode {
         match self {
             CargoMode::Check => "check",
             CargoMode::Build => "build",
+            CargoMode::Test => "test",
         }
     }
 }
@@ -44,19 +56,40 @@ impl CargoMode {
 #[derive(Debug)]
 pub enum CompileLibError {
     MissingTool(ndk::MissingToolError),
+    NdkVersionInvalid(ndk::Error),
+    ManifestPathInvalid(ManifestPathError),
+    EnvFileFailed(crate::env::EnvFileError),
     CargoFailed {
         mode: CargoMode,
-        cause: bossy::Error,
+        cause: CommandError,
     },
+    CheckCollectFailed(util::BuildAndCollectError),
+    CheckFailed(util::CheckOutput),
 }
 
 impl Reportable for CompileLibError {
     fn report(&self) -> Report {
         match self {
             Self::MissingTool(err) => Report::error("Failed to locate required build tool", err),
+            Self::NdkVersionInvalid(err @ ndk::Error::VersionTooLow { .. }) => {
+                Report::action_request(
+                    "Your NDK is too old for this project",
+                    format!(
+                        "{}; please update it, or lower `{}.min-ndk-version` in your {}.",
+                        err,
+                        super::NAME,
+                        crate::config::file_name()
+                    ),
+                )
+            }
+            Self::NdkVersionInvalid(err) => err.report(),
+            Self::ManifestPathInvalid(err) => Report::error("Failed to run cargo", err),
+            Self::EnvFileFailed(err) => Report::error("Failed to run cargo", err),
             Self::CargoFailed { mode, cause } => {
-                Report::error(format!("`Failed to run `cargo {}`", mode), cause)
+                cause.report(&format!("Failed to run `cargo {}`", mode))
             }
+            Self::CheckCollectFailed(err) => Report::error("Failed to run `cargo check`", err),
+            Self::CheckFailed(output) => Report::error("Code doesn't compile", output),
         }
     }
 }
@@ -87,14 +120,18 @@ impl Reportable for SymlinkLibsError {
 #[derive(Debug)]
 pub enum BuildError {
     BuildFailed(CompileLibError),
+    JobFailed(parallel::Error),
     SymlinkLibsFailed(SymlinkLibsError),
+    HookFailed(hooks::Error),
 }
 
 impl Reportable for BuildError {
     fn report(&self) -> Report {
         match self {
             Self::BuildFailed(err) => err.report(),
+            Self::JobFailed(err) => Report::error("Failed to build libraries", err),
             Self::SymlinkLibsFailed(err) => err.report(),
+            Self::HookFailed(err) => err.report(),
         }
     }
 }
@@ -169,11 +206,11 @@ impl<'a> TargetTrait<'a> for Target<'a> {
 }
 
 impl<'a> Target<'a> {
-    fn clang_triple(&self) -> &'a str {
+    pub fn clang_triple(&self) -> &'a str {
         self.clang_triple_override.unwrap_or_else(|| self.triple)
     }
 
-    fn binutils_triple(&self) -> &'a str {
+    pub fn binutils_triple(&self) -> &'a str {
         self.binutils_triple_override.unwrap_or_else(|| self.triple)
     }
 
@@ -191,6 +228,13 @@ impl<'a> Target<'a> {
             .binutil_path(ndk::Binutil::Ar, self.binutils_triple())?
             .display()
             .to_string();
+        if let Ok(tool_dir) = env.ndk.tool_dir() {
+            if let Err(err) =
+                ndk::android_linker_path(&tool_dir, self.triple, config.min_sdk_version())
+            {
+                log::warn!("{}", err);
+            }
+        }
         // Using clang as the linker seems to be the only way to get the right library search paths...
         let linker = env
             .ndk
@@ -212,7 +256,7 @@ impl<'a> Target<'a> {
         })
     }
 
-    fn compile_lib(
+    fn cargo_command(
         &self,
         config: &Config,
         metadata: &Metadata,
@@ -221,45 +265,97 @@ impl<'a> Target<'a> {
         force_color: ForceColor,
         profile: Profile,
         mode: CargoMode,
-    ) -> Result<(), CompileLibError> {
+    ) -> Result<Invocation, CompileLibError> {
         let min_sdk_version = config.min_sdk_version();
         // Force color, since gradle would otherwise give us uncolored output
         // (which Android Studio makes red, which is extra gross!)
         let color = if force_color.yes() { "always" } else { "auto" };
-        CargoCommand::new(mode.as_str())
+        let command = CargoCommand::new(mode.as_str())
             .with_verbose(noise_level.pedantic())
             .with_package(Some(config.app().name()))
             .with_manifest_path(Some(config.app().manifest_path()))
-            .with_target(Some(self.triple))
+            .with_target_reconciled(Some(self.triple), config.app().root_dir())
             .with_no_default_features(metadata.no_default_features())
-            .with_features(metadata.features())
-            .with_release(profile.release())
+            .with_features(metadata.features());
+        env.ndk
+            .ensure_version_at_least(config.min_ndk_version())
+            .map_err(CompileLibError::NdkVersionInvalid)?;
+        Ok(metadata
+            .cargo()
+            .apply(command, profile)
             .into_command_pure(env)
-            .with_env_var("ANDROID_NATIVE_API_LEVEL", min_sdk_version.to_string())
-            .with_env_var(
-                "TARGET_AR",
-                env.ndk
-                    .binutil_path(ndk::Binutil::Ar, self.binutils_triple())
-                    .map_err(CompileLibError::MissingTool)?,
+            .map_err(CompileLibError::ManifestPathInvalid)?
+            .with_env_vars(
+                crate::env::load_target_env_files(config.app().root_dir(), self.triple)
+                    .map_err(CompileLibError::EnvFileFailed)?,
             )
-            .with_env_var(
-                "TARGET_CC",
-                env.ndk
-                    .compiler_path(ndk::Compiler::Clang, self.clang_triple(), min_sdk_version)
-                    .map_err(CompileLibError::MissingTool)?,
-            )
-            .with_env_var(
-                "TARGET_CXX",
+            .with_env_var("ANDROID_NATIVE_API_LEVEL", min_sdk_version.to_string())
+            .with_env_vars(
                 env.ndk
-                    .compiler_path(ndk::Compiler::Clangxx, self.clang_triple(), min_sdk_version)
+                    .cargo_env(self, min_sdk_version)
                     .map_err(CompileLibError::MissingTool)?,
             )
-            .with_args(&["--color", color])
-            .run_and_wait()
-            .map_err(|cause| CompileLibError::CargoFailed { mode, cause })?;
+            .with_args(&["--color", color]))
+    }
+
+    fn compile_lib(
+        &self,
+        config: &Config,
+        metadata: &Metadata,
+        env: &Env,
+        noise_level: NoiseLevel,
+        force_color: ForceColor,
+        profile: Profile,
+        mode: CargoMode,
+    ) -> Result<(), CompileLibError> {
+        let command = self.cargo_command(
+            config,
+            metadata,
+            env,
+            noise_level,
+            force_color,
+            profile,
+            mode,
+        )?;
+        Runner::global()
+            .stream(&command)
+            .map_err(|cause| CompileLibError::CargoFailed {
+                mode,
+                cause: cause.with_verbose_hint(noise_level, NoiseLevel::FranklyQuitePedantic),
+            })?;
         Ok(())
     }
 
+    /// A `cargo test --no-run` for this target, which builds the test
+    /// harnesses without trying to run them on the host.
+    pub(super) fn test_command(
+        &self,
+        config: &Config,
+        metadata: &Metadata,
+        env: &Env,
+        noise_level: NoiseLevel,
+        force_color: ForceColor,
+    ) -> Result<Invocation, CompileLibError> {
+        self.cargo_command(
+            config,
+            metadata,
+            env,
+            noise_level,
+            force_color,
+            Profile::Debug,
+            CargoMode::Test,
+        )
+        .map(|command| command.with_arg("--no-run"))
+    }
+
+    // Concurrent builds sharing a target dir would just end up waiting on
+    // each other for cargo's build directory lock.
+    fn parallel_target_dir(&self, config: &Config) -> PathBuf {
+        config
+            .app()
+            .prefix_path(format!("target/android-jobs/{}", self.triple))
+    }
+
     pub fn check(
         &self,
         config: &Config,
@@ -268,7 +364,7 @@ impl<'a> Target<'a> {
         noise_level: NoiseLevel,
         force_color: ForceColor,
     ) -> Result<(), CompileLibError> {
-        self.compile_lib(
+        let command = self.cargo_command(
             config,
             metadata,
             env,
@@ -276,27 +372,40 @@ impl<'a> Target<'a> {
             force_color,
             Profile::Debug,
             CargoMode::Check,
-        )
+        )?;
+        let output =
+            util::check_and_collect(command).map_err(CompileLibError::CheckCollectFailed)?;
+        if output.success {
+            Ok(())
+        } else {
+            Err(CompileLibError::CheckFailed(output))
+        }
     }
 
+    /// Returns the path of the lib that was built, which is what gets linked
+    /// into `jniLibs`.
     pub fn symlink_libs(
         &self,
         config: &Config,
+        metadata: &Metadata,
         ndk: &ndk::Env,
         profile: Profile,
-    ) -> Result<(), SymlinkLibsError> {
+        target_dir: &Path,
+    ) -> Result<PathBuf, SymlinkLibsError> {
         let jnilibs =
             JniLibs::create(config, *self).map_err(SymlinkLibsError::JniLibsCreationFailed)?;
 
-        let src = config.app().prefix_path(format!(
-            "target/{}/{}/{}",
-            &self.triple,
-            profile.as_str(),
-            config.so_name(),
-        ));
+        let src = target_dir
+            .join(&self.triple)
+            .join(metadata.cargo().profile_dir(profile))
+            .join(config.so_name());
         jnilibs
             .symlink_lib(&src)
             .map_err(SymlinkLibsError::SymlinkFailed)?;
+        output::emit(&Event::Artifact {
+            kind: "lib",
+            path: &src,
+        });
 
         let needs_cxx_shared = ndk
             .required_libs(&src, self.binutils_triple())
@@ -312,7 +421,25 @@ impl<'a> Target<'a> {
                 .map_err(SymlinkLibsError::SymlinkFailed)?;
         }
 
-        Ok(())
+        Ok(src)
+    }
+
+    fn run_hooks(
+        &self,
+        config: &Config,
+        stage: Stage,
+        profile: Profile,
+        artifact: Option<&Path>,
+    ) -> Result<(), BuildError> {
+        let mut context = hooks::Context::new("android", Some(self.triple), profile.as_str());
+        if let Some(artifact) = artifact {
+            context = context.with_artifact(artifact);
+        }
+        config
+            .app()
+            .hooks()
+            .run(stage, &context)
+            .map_err(BuildError::HookFailed)
     }
 
     pub fn build(
@@ -324,6 +451,7 @@ impl<'a> Target<'a> {
         force_color: ForceColor,
         profile: Profile,
     ) -> Result<(), BuildError> {
+        self.run_hooks(config, Stage::PreBuild, profile, None)?;
         self.compile_lib(
             config,
             metadata,
@@ -334,7 +462,74 @@ impl<'a> Target<'a> {
             CargoMode::Build,
         )
         .map_err(BuildError::BuildFailed)?;
-        self.symlink_libs(config, &env.ndk, profile)
-            .map_err(BuildError::SymlinkLibsFailed)
+        let lib = self
+            .symlink_libs(
+                config,
+                metadata,
+                &env.ndk,
+                profile,
+                &config.app().prefix_path("target"),
+            )
+            .map_err(BuildError::SymlinkLibsFailed)?;
+        self.run_hooks(config, Stage::PostBuild, profile, Some(&lib))
+    }
+
+    /// Builds `targets`, running up to `jobs` cargo invocations at once. Each
+    /// concurrent build gets its own target dir, so this trades some disk
+    /// space (and rebuilding host dependencies once per target) for speed.
+    pub fn build_all(
+        targets: &[&Self],
+        config: &Config,
+        metadata: &Metadata,
+        env: &Env,
+        noise_level: NoiseLevel,
+        force_color: ForceColor,
+        profile: Profile,
+        jobs: usize,
+    ) -> Result<(), BuildError> {
+        if jobs <= 1 || targets.len() <= 1 {
+            for target in targets {
+                target.build(config, metadata, env, noise_level, force_color, profile)?;
+            }
+            return Ok(());
+        }
+        for target in targets {
+            target.run_hooks(config, Stage::PreBuild, profile, None)?;
+        }
+        let jobs_to_run = targets
+            .iter()
+            .map(|target| {
+                target
+                    .cargo_command(
+                        config,
+                        metadata,
+                        env,
+                        noise_level,
+                        force_color,
+                        profile,
+                        CargoMode::Build,
+                    )
+                    .map(|command| Job {
+                        label: target.triple.to_owned(),
+                        command: command
+                            .with_env_var("CARGO_TARGET_DIR", target.parallel_target_dir(config)),
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(BuildError::BuildFailed)?;
+        parallel::run(jobs_to_run, jobs).map_err(BuildError::JobFailed)?;
+        for target in targets {
+            let lib = target
+                .symlink_libs(
+                    config,
+                    metadata,
+                    &env.ndk,
+                    profile,
+                    &target.parallel_target_dir(config),
+                )
+                .map_err(BuildError::SymlinkLibsFailed)?;
+            target.run_hooks(config, Stage::PostBuild, profile, Some(&lib))?;
+        }
+        Ok(())
     }
 }
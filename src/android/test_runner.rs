@@ -0,0 +1,171 @@
+//! Runs test harnesses on a device or emulator, for `cargo android test`.
+
+use super::{
+    adb,
+    config::{Config, Metadata},
+    device::Device,
+    env::Env,
+    ndk,
+    target::CompileLibError,
+};
+use crate::{
+    opts::{ForceColor, NoiseLevel},
+    util::{
+        self,
+        cli::{Report, Reportable},
+        CommandError, Runner,
+    },
+};
+use std::path::Path;
+
+// The only place the shell user can both write to and run things from.
+static REMOTE_DIR: &str = "/data/local/tmp/cargo-mobile-tests";
+
+#[derive(Debug)]
+pub enum TestError {
+    BuildFailed(CompileLibError),
+    CollectFailed(util::BuildAndCollectError),
+    CompileFailed,
+    NoTests,
+    RequiredLibsFailed(ndk::RequiredLibsError),
+    LibcxxSharedPathFailed(ndk::MissingToolError),
+    PushFailed {
+        name: String,
+        cause: adb::RunCheckedError,
+    },
+    RunFailed {
+        name: String,
+        cause: CommandError,
+    },
+    TestsFailed {
+        failed: Vec<String>,
+    },
+}
+
+impl Reportable for TestError {
+    fn report(&self) -> Report {
+        match self {
+            Self::BuildFailed(err) => err.report(),
+            Self::CollectFailed(err) => Report::error("Failed to build tests", err),
+            Self::CompileFailed => Report::error(
+                "Failed to build tests",
+                "`cargo test --no-run` failed; see above for the errors.",
+            ),
+            Self::NoTests => Report::error(
+                "Failed to build tests",
+                "`cargo test --no-run` succeeded, but didn't build any test harnesses.",
+            ),
+            Self::RequiredLibsFailed(err) => err.report(),
+            Self::LibcxxSharedPathFailed(err) => {
+                Report::error("Failed to locate \"libc++_shared.so\"", err)
+            }
+            Self::PushFailed { name, cause } => {
+                cause.report(&format!("Failed to push {:?} to device", name))
+            }
+            Self::RunFailed { name, cause } => {
+                Report::error(format!("Failed to run {:?} on device", name), cause)
+            }
+            Self::TestsFailed { failed } => Report::error(
+                "Tests failed on device",
+                format!("These test harnesses exited unsuccessfully: {:?}", failed),
+            ),
+        }
+    }
+}
+
+// Single quotes are the only thing that need escaping within single quotes.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .expect("developer error: test harness path has no file name")
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Builds the app's tests for `device`, and then runs each test harness on it
+/// in turn, passing along `args`. Output is streamed as it comes, and every
+/// harness gets run even if an earlier one fails; the error says which ones
+/// did.
+pub fn test(
+    config: &Config,
+    metadata: &Metadata,
+    env: &Env,
+    device: &Device<'_>,
+    noise_level: NoiseLevel,
+    force_color: ForceColor,
+    args: &[String],
+) -> Result<(), TestError> {
+    let target = device.target();
+    println!("Building tests for {}...", target.triple);
+    let command = target
+        .test_command(config, metadata, env, noise_level, force_color)
+        .map_err(TestError::BuildFailed)?;
+    let output = util::collect_build_output(command).map_err(TestError::CollectFailed)?;
+    if !output.success {
+        return Err(TestError::CompileFailed);
+    }
+    if output.tests.is_empty() {
+        return Err(TestError::NoTests);
+    }
+    // Tests link against the same libs the app does, so they might need the
+    // shared C++ runtime pushed alongside them.
+    let mut needs_cxx_shared = false;
+    for test in &output.tests {
+        needs_cxx_shared |= env
+            .ndk
+            .required_libs(test, target.binutils_triple())
+            .map_err(TestError::RequiredLibsFailed)?
+            .contains("libc++_shared.so");
+    }
+    let mut pushes = output.tests.clone();
+    if needs_cxx_shared {
+        pushes.push(
+            env.ndk
+                .libcxx_shared_path(*target)
+                .map_err(TestError::LibcxxSharedPathFailed)?,
+        );
+    }
+    for local in &pushes {
+        let name = file_name(local);
+        adb::push(env, device.id(), local, &format!("{}/{}", REMOTE_DIR, name))
+            .map_err(|cause| TestError::PushFailed { name, cause })?;
+    }
+    let args = args
+        .iter()
+        .map(|arg| shell_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut failed = Vec::new();
+    for test in &output.tests {
+        let name = file_name(test);
+        println!("Running {} on {}...", name, device);
+        // `adb shell` passes along the exit status on Android 7 and up.
+        let result =
+            Runner::global().stream(&adb::adb(env, device.id()).with_arg("shell").with_arg(
+                format!(
+                    "cd {dir} && chmod +x {name} && LD_LIBRARY_PATH={dir} ./{name} {args}",
+                    dir = REMOTE_DIR,
+                    name = shell_quote(&name),
+                    args = args,
+                ),
+            ));
+        match result {
+            Ok(_) => (),
+            Err(err) if err.status().is_some() => failed.push(name),
+            Err(cause) => return Err(TestError::RunFailed { name, cause }),
+        }
+    }
+    if let Err(err) =
+        util::run(&adb::adb(env, device.id()).with_args(&["shell", "rm", "-rf", REMOTE_DIR]))
+    {
+        log::warn!("failed to remove tests from device: {}", err);
+    }
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(TestError::TestsFailed { failed })
+    }
+}
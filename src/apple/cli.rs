@@ -1,29 +1,36 @@
 use crate::{
     apple::{
         config::{Config, Metadata},
-        device::{Device, RunError},
-        ios_deploy, rust_version_check,
+        device::{self, Device, RunError, SimulatorRunError},
+        ios_deploy, logs,
+        platform::PlatformKind,
+        plist, profiler, rust_version_check, simctl,
         target::{ArchiveError, BuildError, CheckError, CompileLibError, ExportError, Target},
+        test_runner::{self, TestError},
         NAME,
     },
     config::{
+        hooks::{self, Stage},
         metadata::{self, Metadata as OmniMetadata},
         Config as OmniConfig, LoadOrGenError,
     },
     define_device_prompt,
     device::PromptError,
     env::{Env, Error as EnvError},
-    opts, os,
-    target::{call_for_targets_with_fallback, TargetInvalid, TargetTrait as _},
+    opts, profiling,
+    target::{get_targets_in, TargetInvalid},
     util::{
         self,
         cli::{
             self, Exec, GlobalFlags, Report, Reportable, TextWrapper, VERSION_LONG, VERSION_SHORT,
         },
+        output::{self, Event},
         prompt,
     },
+    version::{self, Stamp},
+    watch,
 };
-use std::{collections::HashMap, ffi::OsStr, path::PathBuf};
+use std::{collections::HashMap, ffi::OsStr, fs, io, path::PathBuf};
 use structopt::{clap::AppSettings, StructOpt};
 
 #[derive(Debug, StructOpt)]
@@ -47,10 +54,6 @@ impl Input {
     }
 }
 
-fn macos_from_platform(platform: &str) -> bool {
-    platform == "macOS"
-}
-
 fn profile_from_configuration(configuration: &str) -> opts::Profile {
     if configuration == "release" {
         opts::Profile::Release
@@ -59,48 +62,153 @@ fn profile_from_configuration(configuration: &str) -> opts::Profile {
     }
 }
 
+#[derive(Clone, Copy, Debug, StructOpt)]
+pub struct Platform {
+    #[structopt(
+        long = "platform",
+        help = "Platform to build for, which needs to be enabled in `apple.platforms` unless it's iOS or macOS",
+        default_value = "ios",
+        possible_values = PlatformKind::NAMES
+    )]
+    platform: PlatformKind,
+}
+
 #[derive(Clone, Debug, StructOpt)]
 pub enum Command {
     #[structopt(name = "open", about = "Open project in Xcode")]
     Open,
     #[structopt(name = "check", about = "Checks if code compiles for target(s)")]
     Check {
-        #[structopt(name = "targets", default_value = Target::DEFAULT_KEY, possible_values = Target::name_list())]
+        #[structopt(name = "targets", possible_values = Target::all_names())]
         targets: Vec<String>,
+        #[structopt(flatten)]
+        platform: Platform,
+    },
+    #[structopt(
+        name = "clean",
+        about = "Removes build artifacts for target(s), leaving other targets alone"
+    )]
+    Clean {
+        #[structopt(name = "targets", possible_values = Target::all_names())]
+        targets: Vec<String>,
+        #[structopt(flatten)]
+        platform: Platform,
+        #[structopt(flatten)]
+        profile: cli::Profile,
     },
     #[structopt(name = "build", about = "Builds static libraries for target(s)")]
     Build {
-        #[structopt(name = "targets", default_value = Target::DEFAULT_KEY, possible_values = Target::name_list())]
+        #[structopt(name = "targets", possible_values = Target::all_names())]
         targets: Vec<String>,
         #[structopt(flatten)]
+        platform: Platform,
+        #[structopt(flatten)]
         profile: cli::Profile,
+        #[structopt(flatten)]
+        build_number: cli::BuildNumber,
     },
     #[structopt(name = "archive", about = "Builds and archives for targets(s)")]
     Archive {
-        #[structopt(name = "targets", default_value = Target::DEFAULT_KEY, possible_values = Target::name_list())]
+        #[structopt(name = "targets", possible_values = Target::all_names())]
         targets: Vec<String>,
         #[structopt(flatten)]
+        platform: Platform,
+        #[structopt(flatten)]
         profile: cli::Profile,
+        #[structopt(flatten)]
+        build_number: cli::BuildNumber,
+        #[structopt(
+            long = "export",
+            help = "Export the archive, signed as configured in `apple.signing`",
+            possible_values = &["ipa"]
+        )]
+        export: Option<String>,
     },
     #[structopt(name = "run", about = "Deploys IPA to connected device")]
     Run {
         #[structopt(flatten)]
         profile: cli::Profile,
+        #[structopt(flatten)]
+        device: cli::Device,
+        #[structopt(flatten)]
+        build_number: cli::BuildNumber,
+        #[structopt(
+            long = "simulator",
+            help = "Run on the iOS simulator with this name or UDID instead"
+        )]
+        simulator: Option<String>,
+        #[structopt(
+            long = "watch",
+            help = "Rebuild and restart the app whenever your project changes"
+        )]
+        watch: bool,
+    },
+    #[structopt(
+        name = "profile",
+        about = "Profiles a release build on a connected device"
+    )]
+    Profile {
+        #[structopt(flatten)]
+        device: cli::Device,
+        #[structopt(flatten)]
+        duration: cli::ProfileDuration,
+        #[structopt(
+            long = "template",
+            help = "Instruments template to record with, as listed by `xcrun xctrace list templates`",
+            default_value = profiler::DEFAULT_TEMPLATE
+        )]
+        template: String,
+        #[structopt(
+            long = "pull",
+            help = "Path in the app's container to download once recording's done, like `Documents/results.json`"
+        )]
+        pull: Vec<String>,
+    },
+    #[structopt(name = "test", about = "Runs tests on the iOS simulator")]
+    Test {
+        #[structopt(
+            long = "simulator",
+            help = "Name or UDID of the iOS simulator to run on"
+        )]
+        simulator: String,
+        #[structopt(
+            name = "ARGS",
+            help = "Args to pass to each test harness, i.e. a filter for test names",
+            last = true
+        )]
+        args: Vec<String>,
+    },
+    #[structopt(
+        name = "log",
+        about = "Streams the app's logs from a device or simulator"
+    )]
+    Log {
+        #[structopt(flatten)]
+        device: cli::Device,
+        #[structopt(
+            long = "simulator",
+            help = "Stream from the iOS simulator with this name or UDID instead"
+        )]
+        simulator: Option<String>,
     },
     #[structopt(name = "list", about = "Lists connected devices")]
     List,
+    #[structopt(name = "devices", about = "Shows details for connected devices")]
+    Devices,
     #[structopt(
         name = "xcode-script",
         about = "Compiles static lib (should only be called by Xcode!)",
         setting = AppSettings::Hidden
     )]
     XcodeScript {
+        #[structopt(long = "platform", help = "Value of `PLATFORM_DISPLAY_NAME` env var")]
+        platform: String,
         #[structopt(
-            long = "platform",
-            help = "Value of `PLATFORM_DISPLAY_NAME` env var",
-            parse(from_str = macos_from_platform),
+            long = "effective-platform-name",
+            help = "Value of `EFFECTIVE_PLATFORM_NAME` env var",
+            default_value = ""
         )]
-        macos: bool,
+        effective_platform_name: String,
         #[structopt(long = "sdk-root", help = "Value of `SDKROOT` env var")]
         sdk_root: PathBuf,
         #[structopt(
@@ -135,20 +243,36 @@ pub enum Error {
     MetadataFailed(metadata::Error),
     Unsupported,
     ProjectDirAbsent { project_dir: PathBuf },
-    OpenFailed(bossy::Error),
+    OpenFailed(util::OpenInError),
+    VersionStampFailed(version::Error),
+    PlatformNotEnabled(PlatformKind),
+    ArchiveUnsupported(PlatformKind),
+    ExportUnsupported(PlatformKind),
     CheckFailed(CheckError),
+    CleanFailed(util::RunCargoError),
     BuildFailed(BuildError),
     ArchiveFailed(ArchiveError),
+    ExportOptionsWriteFailed { path: PathBuf, cause: io::Error },
     ExportFailed(ExportError),
+    IpaMissing { old: PathBuf, new: PathBuf },
     RunFailed(RunError),
+    ProfileFailed(profiler::ProfileError),
+    TestFailed(TestError),
+    SimulatorLookupFailed(simctl::FindError),
+    SimulatorRunFailed(SimulatorRunError),
+    WatchFailed(watch::Error),
+    LogFailed(logs::LogError),
     ListFailed(ios_deploy::DeviceListError),
     NoHomeDir(util::NoHomeDir),
     CargoEnvFailed(bossy::Error),
+    CargoPathFailed(util::env::PathInvalid),
+    XcodePlatformInvalid { platform: String },
     SdkRootInvalid { sdk_root: PathBuf },
     IncludeDirInvalid { include_dir: PathBuf },
     MacosSdkRootInvalid { macos_sdk_root: PathBuf },
     ArchInvalid { arch: String },
     CompileLibFailed(CompileLibError),
+    HookFailed(hooks::Error),
 }
 
 impl Reportable for Error {
@@ -166,14 +290,51 @@ impl Reportable for Error {
                 format!("Xcode project directory {:?} doesn't exist.", project_dir),
             ),
             Self::OpenFailed(err) => Report::error("Failed to open project in Xcode", err),
+            Self::VersionStampFailed(err) => err.report(),
+            Self::PlatformNotEnabled(platform) => Report::action_request(
+                format!("{} isn't enabled for this project", platform),
+                format!(
+                    "Add {:?} to `{}.platforms` in your {}, then run `cargo mobile init` and try again.",
+                    platform.name(),
+                    NAME,
+                    crate::config::file_name()
+                ),
+            ),
+            Self::ArchiveUnsupported(platform) => Report::error(
+                format!("Can't archive for {}", platform),
+                "There's no app to archive; link the lib into your own app's target instead.",
+            ),
+            Self::ExportUnsupported(platform) => Report::error(
+                format!("Can't export an IPA for {}", platform),
+                "Only iOS and tvOS archives can be exported as an IPA.",
+            ),
             Self::CheckFailed(err) => err.report(),
+            Self::CleanFailed(err) => err.report("Failed to run `cargo clean`"),
             Self::BuildFailed(err) => err.report(),
             Self::ArchiveFailed(err) => err.report(),
+            Self::ExportOptionsWriteFailed { path, cause } => {
+                Report::error(format!("Failed to write export options to {:?}", path), cause)
+            }
             Self::ExportFailed(err) => err.report(),
+            Self::IpaMissing { old, new } => Report::error(
+                "Exported IPA wasn't found",
+                format!("Neither {:?} nor {:?} exist.", old, new),
+            ),
             Self::RunFailed(err) => err.report(),
+            Self::ProfileFailed(err) => err.report(),
+            Self::TestFailed(err) => err.report(),
+            Self::SimulatorLookupFailed(err) => Report::error("Failed to find simulator", err),
+            Self::SimulatorRunFailed(err) => err.report(),
+            Self::WatchFailed(err) => err.report(),
+            Self::LogFailed(err) => Report::error("Failed to stream logs", err),
             Self::ListFailed(err) => err.report(),
             Self::NoHomeDir(err) => Report::error("Failed to load cargo env profile", err),
             Self::CargoEnvFailed(err) => Report::error("Failed to load cargo env profile", err),
+            Self::CargoPathFailed(err) => Report::error("Failed to add cargo to `PATH`", err),
+            Self::XcodePlatformInvalid { platform } => Report::error(
+                "Platform specified by Xcode was invalid",
+                format!("{:?} isn't a known platform", platform),
+            ),
             Self::SdkRootInvalid { sdk_root } => Report::error(
                 "SDK root provided by Xcode was invalid",
                 format!("{:?} doesn't exist or isn't a directory", sdk_root),
@@ -191,6 +352,7 @@ impl Reportable for Error {
                 format!("{:?} isn't a known arch", arch),
             ),
             Self::CompileLibFailed(err) => err.report(),
+            Self::HookFailed(err) => err.report(),
         }
     }
 }
@@ -204,8 +366,25 @@ impl Exec for Input {
 
     fn exec(self, wrapper: &TextWrapper) -> Result<(), Self::Report> {
         define_device_prompt!(ios_deploy::device_list, ios_deploy::DeviceListError, iOS);
-        fn detect_target_ok<'a>(env: &Env) -> Option<&'a Target<'a>> {
-            device_prompt(env).map(|device| device.target()).ok()
+        fn for_targets(
+            config: &Config,
+            platform: PlatformKind,
+            targets: &[String],
+            f: impl Fn(&Target) -> Result<(), Error>,
+        ) -> Result<(), Error> {
+            if !config.platform_enabled(platform) {
+                return Err(Error::PlatformNotEnabled(platform));
+            }
+            let targets = get_targets_in(
+                Target::all_for(platform),
+                Target::default_key_for(platform),
+                targets.iter(),
+            )
+            .map_err(Error::TargetInvalid)?;
+            for target in targets {
+                f(target)?;
+            }
+            Ok(())
         }
 
         fn with_config(
@@ -213,8 +392,14 @@ impl Exec for Input {
             wrapper: &TextWrapper,
             f: impl FnOnce(&Config, &Metadata) -> Result<(), Error>,
         ) -> Result<(), Error> {
-            let (config, _origin) = OmniConfig::load_or_gen(".", non_interactive, wrapper)
-                .map_err(Error::ConfigFailed)?;
+            let (config, _origin) = OmniConfig::load_or_gen(
+                ".",
+                non_interactive,
+                &Default::default(),
+                &prompt::Stdin,
+                wrapper,
+            )
+            .map_err(Error::ConfigFailed)?;
             let metadata =
                 OmniMetadata::load(&config.app().root_dir()).map_err(Error::MetadataFailed)?;
             if metadata.apple().supported() {
@@ -234,8 +419,60 @@ impl Exec for Input {
             }
         }
 
+        fn stamp_version(config: &Config, build_number: Option<u32>) -> Result<(), Error> {
+            let stamp = Stamp::new(config.app().root_dir(), build_number)
+                .map_err(Error::VersionStampFailed)?;
+            plist::stamp(config, &stamp).map_err(Error::VersionStampFailed)
+        }
+
+        fn run_pre_run_hooks(
+            config: &Config,
+            target: &Target,
+            profile: opts::Profile,
+        ) -> Result<(), Error> {
+            config
+                .app()
+                .hooks()
+                .run(
+                    Stage::PreRun,
+                    &hooks::Context::new("ios", Some(target.triple), profile.as_str()),
+                )
+                .map_err(Error::HookFailed)
+        }
+
+        fn export_ipa(
+            config: &Config,
+            env: &Env,
+            noise_level: opts::NoiseLevel,
+            target: &Target,
+        ) -> Result<(), Error> {
+            let path = config.generated_export_plist_path();
+            util::create_dir_all(config.archive_dir())
+                .and_then(|()| fs::write(&path, plist::export_options(config)))
+                .map_err(|cause| Error::ExportOptionsWriteFailed {
+                    path: path.clone(),
+                    cause,
+                })?;
+            println!(
+                "Exporting archive using the {} method...",
+                config.signing().export_method().as_str()
+            );
+            target
+                .export(config, env, noise_level, &path)
+                .map_err(Error::ExportFailed)?;
+            let ipa = config
+                .ipa_path()
+                .map_err(|(old, new)| Error::IpaMissing { old, new })?;
+            println!("Exported IPA to {:?}", ipa);
+            output::emit(&Event::Artifact {
+                kind: "ipa",
+                path: &ipa,
+            });
+            Ok(())
+        }
+
         fn open_in_xcode(config: &Config) -> Result<(), Error> {
-            os::open_file_with("Xcode", config.project_dir()).map_err(Error::OpenFailed)
+            util::open_in(util::OpenTarget::Xcode, &config.project_dir()).map_err(Error::OpenFailed)
         }
 
         let version_check = || rust_version_check(wrapper).map_err(Error::RustVersionCheckFailed);
@@ -245,6 +482,7 @@ impl Exec for Input {
                 GlobalFlags {
                     noise_level,
                     non_interactive,
+                    ..
                 },
             command,
         } = self;
@@ -257,78 +495,210 @@ impl Exec for Input {
                     open_in_xcode(config)
                 })
             }
-            Command::Check { targets } => {
+            Command::Check {
+                targets,
+                platform: Platform { platform },
+            } => {
                 version_check()?;
                 with_config(non_interactive, wrapper, |config, metadata| {
-                    call_for_targets_with_fallback(
-                        targets.iter(),
-                        &detect_target_ok,
-                        &env,
-                        |target: &Target| {
-                            target
-                                .check(config, metadata, &env, noise_level)
-                                .map_err(Error::CheckFailed)
-                        },
-                    )
-                    .map_err(Error::TargetInvalid)?
+                    for_targets(config, platform, &targets, |target| {
+                        target
+                            .check(config, metadata, &env, noise_level)
+                            .map_err(Error::CheckFailed)
+                    })
                 })
             }
+            Command::Clean {
+                targets,
+                platform: Platform { platform },
+                profile: cli::Profile { profile },
+            } => with_config(non_interactive, wrapper, |config, _| {
+                for_targets(config, platform, &targets, |target| {
+                    util::cargo_clean_target(config.app().root_dir(), target.triple, profile)
+                        .map_err(Error::CleanFailed)
+                })
+            }),
             Command::Build {
                 targets,
+                platform: Platform { platform },
                 profile: cli::Profile { profile },
+                build_number: cli::BuildNumber { build_number },
             } => with_config(non_interactive, wrapper, |config, _| {
                 version_check()?;
                 ensure_init(config)?;
-                call_for_targets_with_fallback(
-                    targets.iter(),
-                    &detect_target_ok,
-                    &env,
-                    |target: &Target| {
-                        target
-                            .build(config, &env, noise_level, profile)
-                            .map_err(Error::BuildFailed)
-                    },
-                )
-                .map_err(Error::TargetInvalid)?
+                stamp_version(config, build_number)?;
+                for_targets(config, platform, &targets, |target| {
+                    target
+                        .build(config, &env, noise_level, profile)
+                        .map_err(Error::BuildFailed)
+                })
             }),
             Command::Archive {
                 targets,
+                platform: Platform { platform },
                 profile: cli::Profile { profile },
+                build_number: cli::BuildNumber { build_number },
+                export,
             } => with_config(non_interactive, wrapper, |config, _| {
+                if !platform.has_app() {
+                    return Err(Error::ArchiveUnsupported(platform));
+                }
+                if export.is_some() && !matches!(platform, PlatformKind::Ios | PlatformKind::Tvos) {
+                    return Err(Error::ExportUnsupported(platform));
+                }
                 version_check()?;
                 ensure_init(config)?;
-                call_for_targets_with_fallback(
-                    targets.iter(),
-                    &detect_target_ok,
-                    &env,
-                    |target: &Target| {
-                        target
-                            .build(config, &env, noise_level, profile)
-                            .map_err(Error::BuildFailed)?;
-                        target
-                            .archive(config, &env, noise_level, profile)
-                            .map_err(Error::ArchiveFailed)
-                    },
-                )
-                .map_err(Error::TargetInvalid)?
+                stamp_version(config, build_number)?;
+                for_targets(config, platform, &targets, |target| {
+                    target
+                        .build(config, &env, noise_level, profile)
+                        .map_err(Error::BuildFailed)?;
+                    target
+                        .archive(config, &env, noise_level, profile)
+                        .map_err(Error::ArchiveFailed)?;
+                    if export.is_some() {
+                        export_ipa(config, &env, noise_level, target)?;
+                    }
+                    Ok(())
+                })
+            }),
+            Command::Run {
+                profile: cli::Profile { profile },
+                build_number: cli::BuildNumber { build_number },
+                simulator: Some(simulator),
+                watch,
+                ..
+            } => with_config(non_interactive, wrapper, |config, _| {
+                version_check()?;
+                ensure_init(config)?;
+                stamp_version(config, build_number)?;
+                let simulator =
+                    simctl::find_available(&simulator).map_err(Error::SimulatorLookupFailed)?;
+                run_pre_run_hooks(config, device::simulator_target(), profile)?;
+                if watch {
+                    watch::watch(
+                        wrapper,
+                        config.app().root_dir(),
+                        &[config.project_dir()],
+                        || {
+                            device::deploy_to_simulator(
+                                config,
+                                &env,
+                                noise_level,
+                                profile,
+                                &simulator,
+                            )
+                        },
+                    )
+                    .map_err(Error::WatchFailed)
+                } else {
+                    device::run_on_simulator(config, &env, noise_level, profile, &simulator)
+                        .map_err(Error::SimulatorRunFailed)
+                }
             }),
             Command::Run {
                 profile: cli::Profile { profile },
+                device: cli::Device { device },
+                build_number: cli::BuildNumber { build_number },
+                simulator: None,
+                watch,
             } => with_config(non_interactive, wrapper, |config, _| {
                 version_check()?;
                 ensure_init(config)?;
-                device_prompt(&env)
-                    .map_err(Error::DevicePromptFailed)?
-                    .run(config, &env, noise_level, non_interactive, profile)
-                    .map_err(Error::RunFailed)
+                stamp_version(config, build_number)?;
+                let device = device_prompt(&env, device.as_deref(), non_interactive)
+                    .map_err(Error::DevicePromptFailed)?;
+                run_pre_run_hooks(config, device.target(), profile)?;
+                if watch {
+                    println!(
+                        "Logs aren't shown while watching; run `cargo apple log` to see them."
+                    );
+                    watch::watch(
+                        wrapper,
+                        config.app().root_dir(),
+                        &[config.project_dir()],
+                        || device.deploy(config, &env, noise_level, profile),
+                    )
+                    .map_err(Error::WatchFailed)
+                } else {
+                    device
+                        .run(config, &env, noise_level, non_interactive, profile)
+                        .map_err(Error::RunFailed)
+                }
+            }),
+            Command::Profile {
+                device: cli::Device { device },
+                duration: cli::ProfileDuration { duration },
+                template,
+                pull,
+            } => {
+                let (key, value) = profiling::DEBUG_INFO_VAR;
+                let env = env.with_var(key, value);
+                with_config(non_interactive, wrapper, |config, _| {
+                    version_check()?;
+                    ensure_init(config)?;
+                    let device = device_prompt(&env, device.as_deref(), non_interactive)
+                        .map_err(Error::DevicePromptFailed)?;
+                    run_pre_run_hooks(config, device.target(), opts::Profile::Release)?;
+                    let saved = profiler::profile(
+                        config,
+                        &env,
+                        &device,
+                        noise_level,
+                        &template,
+                        duration,
+                        &pull,
+                    )
+                    .map_err(Error::ProfileFailed)?;
+                    for path in saved {
+                        profiling::report_trace(&path);
+                    }
+                    Ok(())
+                })
+            }
+            Command::Test { simulator, args } => {
+                with_config(non_interactive, wrapper, |config, metadata| {
+                    version_check()?;
+                    let simulator =
+                        simctl::find_available(&simulator).map_err(Error::SimulatorLookupFailed)?;
+                    test_runner::test(config, metadata, &env, noise_level, &simulator, &args)
+                        .map_err(Error::TestFailed)
+                })
+            }
+            Command::Log {
+                simulator: Some(simulator),
+                ..
+            } => with_config(non_interactive, wrapper, |config, _| {
+                let simulator =
+                    simctl::find_available(&simulator).map_err(Error::SimulatorLookupFailed)?;
+                logs::simulator_log(&simulator.udid, config.app().name()).map_err(Error::LogFailed)
+            }),
+            Command::Log {
+                device: cli::Device { device },
+                simulator: None,
+            } => with_config(non_interactive, wrapper, |config, _| {
+                let device = device_prompt(&env, device.as_deref(), non_interactive)
+                    .map_err(Error::DevicePromptFailed)?;
+                logs::device_log(device.id(), config.app().name()).map_err(Error::LogFailed)
             }),
             Command::List => ios_deploy::device_list(&env)
                 .map_err(Error::ListFailed)
                 .map(|device_list| {
                     prompt::list_display_only(device_list.iter(), device_list.len());
                 }),
+            Command::Devices => ios_deploy::device_list(&env)
+                .map_err(Error::ListFailed)
+                .map(|device_list| {
+                    println!("Connected iOS devices:");
+                    let summaries = device_list.iter().map(Device::summary).collect::<Vec<_>>();
+                    for summary in &summaries {
+                        output::emit(&Event::Device(summary));
+                    }
+                    prompt::list_display_only(summaries.iter(), summaries.len());
+                }),
             Command::XcodeScript {
-                macos,
+                platform,
+                effective_platform_name,
                 sdk_root,
                 profile,
                 force_color,
@@ -337,11 +707,18 @@ impl Exec for Input {
                 // The `PATH` env var Xcode gives us is missing any additions
                 // made by the user's profile, so we'll manually add cargo's
                 // `PATH`.
-                let env = env.prepend_to_path(
-                    util::home_dir()
-                        .map_err(Error::NoHomeDir)?
-                        .join(".cargo/bin"),
-                );
+                let platform = PlatformKind::from_xcode(&platform, &effective_platform_name)
+                    .ok_or_else(|| Error::XcodePlatformInvalid {
+                        platform: platform.clone(),
+                    })?;
+
+                let env = env
+                    .prepend_to_path(
+                        util::home_dir()
+                            .map_err(Error::NoHomeDir)?
+                            .join(".cargo/bin"),
+                    )
+                    .map_err(Error::CargoPathFailed)?;
 
                 if !sdk_root.is_dir() {
                     return Err(Error::SdkRootInvalid { sdk_root });
@@ -378,12 +755,18 @@ impl Exec for Input {
                 let isysroot = format!("-isysroot {}", sdk_root.display());
 
                 for arch in arches {
-                    // Set target-specific flags
-                    let triple = match arch.as_str() {
-                        "arm64" => "aarch64_apple_ios",
-                        "x86_64" => "x86_64_apple_ios",
-                        _ => return Err(Error::ArchInvalid { arch }),
+                    // There's only the one macOS target, regardless of which
+                    // arch Xcode asks for.
+                    let target = if platform == PlatformKind::Macos {
+                        &macos_target
+                    } else {
+                        Target::for_arch_in(platform, &arch).ok_or_else(|| Error::ArchInvalid {
+                            arch: arch.to_owned(),
+                        })?
                     };
+
+                    // Set target-specific flags
+                    let triple = target.triple.replace('-', "_");
                     let cflags = format!("CFLAGS_{}", triple);
                     let cxxflags = format!("CFLAGS_{}", triple);
                     let objc_include_path = format!("OBJC_INCLUDE_PATH_{}", triple);
@@ -392,13 +775,6 @@ impl Exec for Input {
                     target_env.insert(cxxflags.as_ref(), isysroot.as_ref());
                     target_env.insert(objc_include_path.as_ref(), include_dir.as_ref());
 
-                    let target = if macos {
-                        &macos_target
-                    } else {
-                        Target::for_arch(&arch).ok_or_else(|| Error::ArchInvalid {
-                            arch: arch.to_owned(),
-                        })?
-                    };
                     target
                         .compile_lib(
                             config,
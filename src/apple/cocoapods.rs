@@ -0,0 +1,71 @@
+use crate::util;
+use std::{io, path::Path};
+
+// Podfiles are Ruby, so names and versions go in single-quoted string literals.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Writes a `Podfile` at `path` that adds `pods` to `target`. Each pod can
+/// optionally have a version requirement, like `~> 1.0`.
+///
+/// CocoaPods won't install pods that aren't used by a concrete target, so
+/// unlike the rest, `target` isn't optional.
+pub fn write_podfile(
+    path: &Path,
+    target: &str,
+    platform_version: &str,
+    pods: &[(String, Option<String>)],
+) -> io::Result<()> {
+    let mut contents = format!(
+        "platform :ios, {}\n\ntarget {} do\n  use_frameworks!\n",
+        quote(platform_version),
+        quote(target)
+    );
+    for (name, version) in pods {
+        contents.push_str(&format!("  pod {}", quote(name)));
+        if let Some(version) = version {
+            contents.push_str(&format!(", {}", quote(version)));
+        }
+        contents.push('\n');
+    }
+    contents.push_str("end\n");
+    util::write_str_if_changed(path, contents).map(|_| ())
+}
+
+/// Runs `pod install` in `dir`, which needs to contain a `Podfile`.
+pub fn pod_install(dir: &Path) -> bossy::Result<()> {
+    bossy::Command::impure_parse("pod install")
+        .with_arg("--project-directory")
+        .with_arg(dir)
+        .run_and_wait()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn podfiles_list_each_pod_with_its_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Podfile");
+        let pods = vec![
+            ("Firebase/Analytics".to_owned(), Some("~> 8.0".to_owned())),
+            ("Shinji's Pod".to_owned(), None),
+        ];
+        write_podfile(&path, "app_iOS", "13.0", &pods).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "\
+platform :ios, '13.0'
+
+target 'app_iOS' do
+  use_frameworks!
+  pod 'Firebase/Analytics', '~> 8.0'
+  pod 'Shinji\\'s Pod'
+end
+"
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::util::cli::{Report, Reportable};
+use once_cell_regex::regex_multi_line;
+use std::{
+    fmt::{self, Display},
+    path::Path,
+    str,
+};
+
+#[derive(Debug)]
+pub enum CodesignError {
+    NoIdentityFound { identity: String },
+    CommandFailed(bossy::Error),
+}
+
+impl Display for CodesignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoIdentityFound { identity } => {
+                write!(f, "No signing identity matching {:?} was found", identity)
+            }
+            Self::CommandFailed(err) => write!(f, "`codesign` command failed: {}", err),
+        }
+    }
+}
+
+impl Reportable for CodesignError {
+    fn report(&self) -> Report {
+        let msg = "Failed to codesign app";
+        match self {
+            Self::NoIdentityFound { .. } => Report::action_request(
+                msg,
+                format!(
+                    "{}; you can see which ones are installed by running `security find-identity -v -p codesigning`.",
+                    self
+                ),
+            ),
+            Self::CommandFailed(_) => Report::error(msg, self),
+        }
+    }
+}
+
+pub fn codesign_command(app: &Path, identity: &str, entitlements: Option<&Path>) -> bossy::Command {
+    let mut command = bossy::Command::impure("codesign");
+    command.add_args(&["--force", "--sign", identity]);
+    if let Some(entitlements) = entitlements {
+        command.add_arg("--entitlements").add_arg(entitlements);
+    }
+    command.add_arg(app);
+    command
+}
+
+/// Signs the `.app` bundle at `app` using `identity`, which can be anything
+/// `codesign --sign` accepts (i.e. a common name or a SHA-1 hash).
+pub fn codesign(
+    app: &Path,
+    identity: &str,
+    entitlements: Option<&Path>,
+) -> Result<(), CodesignError> {
+    codesign_command(app, identity, entitlements)
+        .run_and_wait_for_output()
+        .map_err(|err| {
+            let no_identity = err
+                .stderr_str()
+                .and_then(Result::ok)
+                .map_or(false, |stderr| stderr.contains("no identity found"));
+            if no_identity {
+                CodesignError::NoIdentityFound {
+                    identity: identity.to_owned(),
+                }
+            } else {
+                CodesignError::CommandFailed(err)
+            }
+        })?;
+    Ok(())
+}
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Identity {
+    pub hash: String,
+    pub name: String,
+}
+
+#[derive(Debug)]
+pub enum IdentitiesError {
+    CommandFailed(bossy::Error),
+    InvalidUtf8(str::Utf8Error),
+}
+
+impl Display for IdentitiesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CommandFailed(err) => write!(f, "Failed to call `security` command: {}", err),
+            Self::InvalidUtf8(err) => write!(f, "Identity list contained invalid UTF-8: {}", err),
+        }
+    }
+}
+
+impl Reportable for IdentitiesError {
+    fn report(&self) -> Report {
+        Report::error("Failed to list code signing identities", self)
+    }
+}
+
+/// Parses the output of `security find-identity`, which looks like this:
+///
+/// ```text
+///   1) 0123456789ABCDEF0123456789ABCDEF01234567 "Apple Development: Jane Doe (ABCDE12345)"
+///      1 valid identities found
+/// ```
+pub fn parse_identities(raw_list: &str) -> Vec<Identity> {
+    regex_multi_line!(r#"^\s*\d+\)\s+([0-9A-Fa-f]{40})\s+"(.+)"\s*$"#)
+        .captures_iter(raw_list)
+        .map(|caps| Identity {
+            hash: caps[1].to_owned(),
+            name: caps[2].to_owned(),
+        })
+        .collect()
+}
+
+pub fn codesign_identities() -> Result<Vec<Identity>, IdentitiesError> {
+    let output = bossy::Command::impure_parse("security find-identity -v -p codesigning")
+        .run_and_wait_for_output()
+        .map_err(IdentitiesError::CommandFailed)?;
+    let raw_list = output.stdout_str().map_err(IdentitiesError::InvalidUtf8)?;
+    Ok(parse_identities(raw_list))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entitlements_are_only_passed_if_given() {
+        let app = Path::new("build/App.app");
+        assert_eq!(
+            codesign_command(app, "Apple Development", None).display(),
+            "codesign --force --sign Apple Development build/App.app"
+        );
+        assert_eq!(
+            codesign_command(app, "ABCDEF", Some(Path::new("App.entitlements"))).display(),
+            "codesign --force --sign ABCDEF --entitlements App.entitlements build/App.app"
+        );
+    }
+
+    #[test]
+    fn identities_are_parsed_from_find_identity_output() {
+        let raw_list = r#"
+Policy: Code Signing
+  Matching identities
+  1) 0123456789ABCDEF0123456789ABCDEF01234567 "Apple Development: Jane Doe (ABCDE12345)"
+  2) 89abcdef0123456789abcdef0123456789abcdef "Apple Distribution: Example, Inc. (FGHIJ67890)"
+     2 valid identities found
+"#;
+        assert_eq!(
+            parse_identities(raw_list),
+            vec![
+                Identity {
+                    hash: "0123456789ABCDEF0123456789ABCDEF01234567".to_owned(),
+                    name: "Apple Development: Jane Doe (ABCDE12345)".to_owned(),
+                },
+                Identity {
+                    hash: "89abcdef0123456789abcdef0123456789abcdef".to_owned(),
+                    name: "Apple Distribution: Example, Inc. (FGHIJ67890)".to_owned(),
+                },
+            ]
+        );
+        assert!(parse_identities("     0 valid identities found\n").is_empty());
+    }
+}
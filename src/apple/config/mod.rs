@@ -2,8 +2,10 @@ mod raw;
 
 pub use self::raw::*;
 
+use super::platform::PlatformKind;
 use crate::{
-    config::app::App,
+    config::{app::App, metadata::CargoSettings},
+    opts,
     util::{self, cli::Report},
 };
 use serde::{Deserialize, Serialize};
@@ -13,6 +15,145 @@ use std::{
 };
 
 static DEFAULT_PROJECT_DIR: &str = "gen/apple";
+pub static DEFAULT_IOS_DEPLOYMENT_TARGET: &str = "9.0";
+pub static DEFAULT_TVOS_DEPLOYMENT_TARGET: &str = "12.0";
+// `arm64_32` doesn't exist until watchOS 5.
+pub static DEFAULT_WATCHOS_DEPLOYMENT_TARGET: &str = "5.0";
+
+/// How the Xcode project gets generated from `project.yml` and friends.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProjectGenerator {
+    /// Uses XcodeGen if it's installed, and the built-in generator otherwise.
+    Auto,
+    /// Always uses XcodeGen, installing it if needed.
+    Xcodegen,
+    /// Always uses the built-in generator.
+    Native,
+}
+
+impl Default for ProjectGenerator {
+    fn default() -> Self {
+        Self::Auto
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SigningStyle {
+    /// Lets Xcode pick the identity and manage provisioning profiles.
+    Automatic,
+    /// Uses exactly the identity and provisioning profile that are configured.
+    Manual,
+}
+
+impl SigningStyle {
+    /// The value of the `CODE_SIGN_STYLE` build setting.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Automatic => "Automatic",
+            Self::Manual => "Manual",
+        }
+    }
+}
+
+/// How an archive gets exported, which determines where the IPA can go.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExportMethod {
+    /// For App Store Connect, i.e. TestFlight.
+    AppStore,
+    AdHoc,
+    Enterprise,
+    Development,
+}
+
+impl Default for ExportMethod {
+    fn default() -> Self {
+        Self::Development
+    }
+}
+
+impl ExportMethod {
+    /// The value of `method` in `ExportOptions.plist`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::AppStore => "app-store",
+            Self::AdHoc => "ad-hoc",
+            Self::Enterprise => "enterprise",
+            Self::Development => "development",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Signing {
+    team_id: String,
+    identity: Option<String>,
+    provisioning_profile: Option<String>,
+    style: SigningStyle,
+    export_method: ExportMethod,
+}
+
+impl Signing {
+    fn from_raw(development_team: &str, raw: RawSigning) -> Result<Self, Error> {
+        // Manual signing without a profile can't work, so having a profile is
+        // a good sign that manual signing is wanted.
+        let style = raw.style.unwrap_or_else(|| {
+            if raw.provisioning_profile.is_some() {
+                SigningStyle::Manual
+            } else {
+                SigningStyle::Automatic
+            }
+        });
+        if style == SigningStyle::Manual && raw.provisioning_profile.is_none() {
+            return Err(Error::ProvisioningProfileMissing);
+        }
+        Ok(Self {
+            team_id: raw.team_id.unwrap_or_else(|| development_team.to_owned()),
+            identity: raw.identity,
+            provisioning_profile: raw.provisioning_profile,
+            style,
+            export_method: raw.export_method.unwrap_or_default(),
+        })
+    }
+
+    pub fn team_id(&self) -> &str {
+        &self.team_id
+    }
+
+    pub fn identity(&self) -> Option<&str> {
+        self.identity.as_deref()
+    }
+
+    pub fn provisioning_profile(&self) -> Option<&str> {
+        self.provisioning_profile.as_deref()
+    }
+
+    pub fn style(&self) -> SigningStyle {
+        self.style
+    }
+
+    pub fn export_method(&self) -> ExportMethod {
+        self.export_method
+    }
+
+    /// Build settings to pass to `xcodebuild` as `KEY=VALUE` args, which take
+    /// precedence over whatever's in the Xcode project.
+    pub fn build_settings(&self) -> Vec<String> {
+        let mut settings = vec![
+            format!("DEVELOPMENT_TEAM={}", self.team_id),
+            format!("CODE_SIGN_STYLE={}", self.style.as_str()),
+        ];
+        if let Some(identity) = &self.identity {
+            settings.push(format!("CODE_SIGN_IDENTITY={}", identity));
+        }
+        if let Some(profile) = &self.provisioning_profile {
+            settings.push(format!("PROVISIONING_PROFILE_SPECIFIER={}", profile));
+        }
+        settings
+    }
+}
 
 #[derive(Debug, Default, Deserialize)]
 pub struct Platform {
@@ -20,11 +161,13 @@ pub struct Platform {
     features: Option<Vec<String>>,
     #[serde(default)]
     frameworks: Option<Vec<String>>,
+    #[serde(flatten)]
+    cargo: CargoSettings,
 }
 
 impl Platform {
     pub fn no_default_features(&self) -> bool {
-        self.features.is_some()
+        self.cargo.no_default_features(self.features())
     }
 
     pub fn features(&self) -> Option<&[String]> {
@@ -34,6 +177,10 @@ impl Platform {
     pub fn frameworks(&self) -> &[String] {
         self.frameworks.as_deref().unwrap_or_else(|| &[])
     }
+
+    pub fn cargo(&self) -> &CargoSettings {
+        &self.cargo
+    }
 }
 
 const fn default_true() -> bool {
@@ -48,6 +195,10 @@ pub struct Metadata {
     ios: Platform,
     #[serde(default)]
     macos: Platform,
+    #[serde(default)]
+    tvos: Platform,
+    #[serde(default)]
+    watchos: Platform,
 }
 
 impl Default for Metadata {
@@ -56,6 +207,8 @@ impl Default for Metadata {
             supported: true,
             ios: Default::default(),
             macos: Default::default(),
+            tvos: Default::default(),
+            watchos: Default::default(),
         }
     }
 }
@@ -72,6 +225,14 @@ impl Metadata {
     pub fn macos(&self) -> &Platform {
         &self.macos
     }
+
+    pub fn tvos(&self) -> &Platform {
+        &self.tvos
+    }
+
+    pub fn watchos(&self) -> &Platform {
+        &self.watchos
+    }
 }
 
 #[derive(Debug)]
@@ -111,6 +272,8 @@ pub enum Error {
     DevelopmentTeamMissing,
     DevelopmentTeamEmpty,
     ProjectDirInvalid(ProjectDirInvalid),
+    ProvisioningProfileMissing,
+    PlatformNotOptional(PlatformKind),
 }
 
 impl Error {
@@ -127,6 +290,22 @@ impl Error {
                 msg,
                 format!("`{}.project-dir` invalid: {}", super::NAME, err),
             ),
+            Self::PlatformNotOptional(platform) => Report::error(
+                msg,
+                format!(
+                    "`{}.platforms` contains {:?}, which is always enabled",
+                    super::NAME,
+                    platform.name()
+                ),
+            ),
+            Self::ProvisioningProfileMissing => Report::error(
+                msg,
+                format!(
+                    "`{}.signing.style` is \"manual\", but `{}.signing.provisioning-profile` isn't set",
+                    super::NAME,
+                    super::NAME
+                ),
+            ),
         }
     }
 }
@@ -138,6 +317,13 @@ pub struct Config {
     app: App,
     development_team: String,
     project_dir: String,
+    ios_deployment_target: String,
+    platforms: Vec<PlatformKind>,
+    tvos_deployment_target: String,
+    watchos_deployment_target: String,
+    project_generator: ProjectGenerator,
+    #[serde(skip_serializing)]
+    signing: Signing,
 }
 
 impl Config {
@@ -175,10 +361,67 @@ impl Config {
                 Ok(DEFAULT_PROJECT_DIR.to_owned())
             })?;
 
+        let ios_deployment_target = raw.ios_deployment_target.unwrap_or_else(|| {
+            log::info!(
+                "`{}.ios-deployment-target` not set; defaulting to {}",
+                super::NAME,
+                DEFAULT_IOS_DEPLOYMENT_TARGET
+            );
+            DEFAULT_IOS_DEPLOYMENT_TARGET.to_owned()
+        });
+
+        let platforms = raw.platforms.unwrap_or_default();
+        if let Some(platform) = platforms
+            .iter()
+            .find(|platform| !PlatformKind::OPTIONAL.contains(platform))
+        {
+            return Err(Error::PlatformNotOptional(*platform));
+        }
+        let deployment_target = |platform: PlatformKind, raw: Option<String>, default: &str| {
+            raw.unwrap_or_else(|| {
+                if platforms.contains(&platform) {
+                    log::info!(
+                        "`{}.{}-deployment-target` not set; defaulting to {}",
+                        super::NAME,
+                        platform.name(),
+                        default
+                    );
+                }
+                default.to_owned()
+            })
+        };
+        let tvos_deployment_target = deployment_target(
+            PlatformKind::Tvos,
+            raw.tvos_deployment_target,
+            DEFAULT_TVOS_DEPLOYMENT_TARGET,
+        );
+        let watchos_deployment_target = deployment_target(
+            PlatformKind::Watchos,
+            raw.watchos_deployment_target,
+            DEFAULT_WATCHOS_DEPLOYMENT_TARGET,
+        );
+
+        let project_generator = raw.project_generator.unwrap_or_else(|| {
+            log::info!(
+                "`{}.project-generator` not set; defaulting to {:?}",
+                super::NAME,
+                ProjectGenerator::default()
+            );
+            Default::default()
+        });
+
+        let signing = Signing::from_raw(&raw.development_team, raw.signing.unwrap_or_default())?;
+
         Ok(Self {
             app,
             development_team: raw.development_team,
             project_dir,
+            ios_deployment_target,
+            platforms,
+            tvos_deployment_target,
+            watchos_deployment_target,
+            project_generator,
+            signing,
         })
     }
 
@@ -186,6 +429,50 @@ impl Config {
         &self.app
     }
 
+    pub fn development_team(&self) -> &str {
+        &self.development_team
+    }
+
+    pub fn ios_deployment_target(&self) -> &str {
+        &self.ios_deployment_target
+    }
+
+    pub fn tvos_deployment_target(&self) -> &str {
+        &self.tvos_deployment_target
+    }
+
+    pub fn watchos_deployment_target(&self) -> &str {
+        &self.watchos_deployment_target
+    }
+
+    /// The deployment target rustc and Xcode should use for `platform`, if
+    /// it's one we have a setting for.
+    pub fn deployment_target(&self, platform: PlatformKind) -> Option<&str> {
+        match platform {
+            PlatformKind::Ios => Some(&self.ios_deployment_target),
+            PlatformKind::Tvos => Some(&self.tvos_deployment_target),
+            PlatformKind::Watchos => Some(&self.watchos_deployment_target),
+            PlatformKind::Macos | PlatformKind::Catalyst => None,
+        }
+    }
+
+    /// The optional platforms listed in `apple.platforms`.
+    pub fn platforms(&self) -> &[PlatformKind] {
+        &self.platforms
+    }
+
+    pub fn platform_enabled(&self, platform: PlatformKind) -> bool {
+        !PlatformKind::OPTIONAL.contains(&platform) || self.platforms.contains(&platform)
+    }
+
+    pub fn project_generator(&self) -> ProjectGenerator {
+        self.project_generator
+    }
+
+    pub fn signing(&self) -> &Signing {
+        &self.signing
+    }
+
     pub fn project_dir(&self) -> PathBuf {
         self.app.prefix_path(&self.project_dir)
     }
@@ -213,6 +500,12 @@ impl Config {
         self.project_dir().join("ExportOptions.plist")
     }
 
+    /// Where `cargo apple archive --export` writes the `ExportOptions.plist`
+    /// it makes from `apple.signing`.
+    pub fn generated_export_plist_path(&self) -> PathBuf {
+        self.archive_dir().join("ExportOptions.plist")
+    }
+
     pub fn ipa_path(&self) -> Result<PathBuf, (PathBuf, PathBuf)> {
         let path = |tail: &str| self.export_dir().join(format!("{}.ipa", tail));
         let old = path(&self.scheme());
@@ -236,6 +529,42 @@ impl Config {
     }
 
     pub fn scheme(&self) -> String {
-        format!("{}_iOS", self.app.name())
+        self.scheme_for(PlatformKind::Ios)
+    }
+
+    /// Since there's no watchOS app, that scheme just builds the lib.
+    pub fn scheme_for(&self, platform: PlatformKind) -> String {
+        let scheme = format!("{}_{}", self.app.name(), platform.target_suffix());
+        if platform.has_app() {
+            scheme
+        } else {
+            format!("lib_{}", scheme)
+        }
+    }
+
+    /// The name of the archive made for `platform`, without the extension.
+    /// Catalyst uses the iOS scheme, so it needs a name of its own.
+    pub fn archive_name(&self, platform: PlatformKind) -> String {
+        match platform {
+            PlatformKind::Catalyst => format!("{}_maccatalyst", self.scheme_for(platform)),
+            _ => self.scheme_for(platform),
+        }
+    }
+
+    /// This has to match `PRODUCT_BUNDLE_IDENTIFIER` in the Xcode project.
+    pub fn bundle_id(&self) -> String {
+        format!("{}.{}", self.app.reverse_domain(), self.app.name())
+    }
+
+    pub fn simulator_derived_data_dir(&self) -> PathBuf {
+        self.project_dir().join("build/simulator")
+    }
+
+    pub fn simulator_app_path(&self, profile: opts::Profile) -> PathBuf {
+        self.simulator_derived_data_dir().join(format!(
+            "Build/Products/{}-iphonesimulator/{}.app",
+            profile.as_str(),
+            self.app.name()
+        ))
     }
 }
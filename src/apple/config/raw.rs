@@ -1,6 +1,7 @@
+use super::{ExportMethod, ProjectGenerator, SigningStyle};
 use crate::{
-    apple::teams,
-    util::{cli::TextWrapper, prompt},
+    apple::{platform::PlatformKind, teams},
+    util::{cli::TextWrapper, prompt::Prompter},
 };
 use colored::{Color, Colorize as _};
 use serde::{Deserialize, Serialize};
@@ -42,35 +43,76 @@ impl Display for PromptError {
     }
 }
 
+/// Everything here is optional; by default, Xcode signs automatically using
+/// `development-team`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RawSigning {
+    pub team_id: Option<String>,
+    pub identity: Option<String>,
+    pub provisioning_profile: Option<String>,
+    pub style: Option<SigningStyle>,
+    pub export_method: Option<ExportMethod>,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct Raw {
     pub development_team: String,
     pub project_dir: Option<String>,
+    pub ios_deployment_target: Option<String>,
+    pub platforms: Option<Vec<PlatformKind>>,
+    pub tvos_deployment_target: Option<String>,
+    pub watchos_deployment_target: Option<String>,
+    pub project_generator: Option<ProjectGenerator>,
     pub ios_no_default_features: Option<bool>,
     pub ios_features: Option<Vec<String>>,
     pub macos_no_default_features: Option<bool>,
     pub macos_features: Option<Vec<String>>,
+    pub signing: Option<RawSigning>,
 }
 
 impl Raw {
-    pub fn detect() -> Result<Self, DetectError> {
-        let development_teams =
-            teams::find_development_teams().map_err(DetectError::DeveloperTeamLookupFailed)?;
-        Ok(Self {
-            development_team: development_teams
-                .get(0)
-                .map(|development_team| development_team.id.clone())
-                .ok_or_else(|| DetectError::DeveloperTeamsEmpty)?,
+    fn new(development_team: String) -> Self {
+        Self {
+            development_team,
             project_dir: None,
+            ios_deployment_target: None,
+            platforms: None,
+            tvos_deployment_target: None,
+            watchos_deployment_target: None,
+            project_generator: None,
             ios_no_default_features: None,
             ios_features: None,
             macos_no_default_features: None,
             macos_features: None,
-        })
+            signing: None,
+        }
     }
 
-    pub fn prompt(wrapper: &TextWrapper) -> Result<Self, PromptError> {
+    /// Uses `development_team` if it's given, and otherwise picks the first
+    /// team we can find.
+    pub fn detect(development_team: Option<&str>) -> Result<Self, DetectError> {
+        if let Some(development_team) = development_team {
+            return Ok(Self::new(development_team.to_owned()));
+        }
+        let development_teams =
+            teams::find_development_teams().map_err(DetectError::DeveloperTeamLookupFailed)?;
+        development_teams
+            .get(0)
+            .map(|development_team| Self::new(development_team.id.clone()))
+            .ok_or_else(|| DetectError::DeveloperTeamsEmpty)
+    }
+
+    /// Prompts for a development team, unless `development_team` is given.
+    pub fn prompt(
+        wrapper: &TextWrapper,
+        development_team: Option<&str>,
+        prompter: &dyn Prompter,
+    ) -> Result<Self, PromptError> {
+        if let Some(development_team) = development_team {
+            return Ok(Self::new(development_team.to_owned()));
+        }
         let development_team = {
             let development_teams =
                 teams::find_development_teams().map_err(PromptError::DeveloperTeamLookupFailed)?;
@@ -111,12 +153,13 @@ impl Raw {
                     "index".green(),
                     "team ID".cyan(),
                 );
-                let team_input = prompt::default(
-                    "Apple development team",
-                    default_team,
-                    Some(Color::BrightGreen),
-                )
-                .map_err(PromptError::DeveloperTeamPromptFailed)?;
+                let team_input = prompter
+                    .default(
+                        "Apple development team",
+                        default_team,
+                        Some(Color::BrightGreen),
+                    )
+                    .map_err(PromptError::DeveloperTeamPromptFailed)?;
                 let team_id = team_input
                     .parse::<usize>()
                     .ok()
@@ -135,13 +178,6 @@ impl Raw {
                 }
             }
         };
-        Ok(Self {
-            development_team,
-            project_dir: None,
-            ios_no_default_features: None,
-            ios_features: None,
-            macos_no_default_features: None,
-            macos_features: None,
-        })
+        Ok(Self::new(development_team))
     }
 }
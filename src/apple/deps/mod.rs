@@ -2,7 +2,10 @@ mod update;
 mod xcode_plugin;
 
 use self::update::{Outdated, OutdatedError};
-use super::system_profile::{self, DeveloperTools};
+use super::{
+    config::ProjectGenerator,
+    system_profile::{self, DeveloperTools},
+};
 use crate::{
     opts,
     util::{
@@ -40,8 +43,14 @@ pub fn install(
     non_interactive: opts::NonInteractive,
     skip_dev_tools: opts::SkipDevTools,
     reinstall_deps: opts::ReinstallDeps,
+    project_generator: ProjectGenerator,
 ) -> Result<(), Error> {
-    for package in PACKAGES {
+    // `xcodegen` is only installed if it's explicitly asked for, since the
+    // built-in generator can do without it.
+    let packages = PACKAGES.iter().filter(|package| {
+        **package != "xcodegen" || project_generator == ProjectGenerator::Xcodegen
+    });
+    for package in packages {
         let found = util::command_present(package)
             .map_err(|source| Error::PresenceCheckFailed { package, source })?;
         if !found || reinstall_deps.yes() {
@@ -56,22 +65,19 @@ pub fn install(
     }
     let outdated = Outdated::load()?;
     outdated.print_notice();
-    if !outdated.is_empty() && non_interactive.no() {
-        let answer = loop {
-            if let Some(answer) = prompt::yes_no(
-                "Would you like these outdated dependencies to be updated for you?",
-                Some(prompt::YesOrNo::Yes),
-            )? {
-                break answer;
-            }
-        };
-        if answer.yes() {
-            for package in outdated.iter() {
-                bossy::Command::impure_parse("brew upgrade")
-                    .with_arg(package)
-                    .run_and_wait()
-                    .map_err(|source| Error::InstallFailed { package, source })?;
-            }
+    if !outdated.is_empty()
+        && non_interactive.no()
+        && prompt::confirm(
+            "Would you like these outdated dependencies to be updated for you?",
+            true,
+            false,
+        )?
+    {
+        for package in outdated.iter() {
+            bossy::Command::impure_parse("brew upgrade")
+                .with_arg(package)
+                .run_and_wait()
+                .map_err(|source| Error::InstallFailed { package, source })?;
         }
     }
     // we definitely don't want to install this on CI...
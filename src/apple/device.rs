@@ -1,12 +1,19 @@
 use super::{
     config::Config,
     ios_deploy,
+    simctl::Simulator,
     target::{ArchiveError, BuildError, ExportError, Target},
 };
 use crate::{
+    config::hooks::{self, Stage},
+    device::DeviceSummary,
     env::{Env, ExplicitEnv as _},
     opts,
-    util::cli::{Report, Reportable},
+    target::TargetTrait as _,
+    util::{
+        cli::{Report, Reportable},
+        CommandError, Runner,
+    },
 };
 use std::{
     fmt::{self, Display},
@@ -21,6 +28,7 @@ pub enum RunError {
     IpaMissing { old: PathBuf, new: PathBuf },
     UnzipFailed(bossy::Error),
     DeployFailed(ios_deploy::RunAndDebugError),
+    HookFailed(hooks::Error),
 }
 
 impl Reportable for RunError {
@@ -35,15 +43,117 @@ impl Reportable for RunError {
             ),
             Self::UnzipFailed(err) => Report::error("Failed to unzip archive", err),
             Self::DeployFailed(err) => err.report(),
+            Self::HookFailed(err) => err.report(),
         }
     }
 }
 
+#[derive(Debug)]
+pub enum SimulatorRunError {
+    BuildFailed(BuildError),
+    BootFailed(CommandError),
+    AppMissing { app_path: PathBuf },
+    InstallFailed(CommandError),
+    LaunchFailed(CommandError),
+    HookFailed(hooks::Error),
+}
+
+impl Reportable for SimulatorRunError {
+    fn report(&self) -> Report {
+        match self {
+            Self::BuildFailed(err) => err.report(),
+            Self::BootFailed(err) => Report::error("Failed to boot simulator", err),
+            Self::AppMissing { app_path } => Report::error(
+                "App appears to be missing",
+                format!("Build succeeded, but nothing was found at {:?}", app_path),
+            ),
+            Self::InstallFailed(err) => Report::error("Failed to install app on simulator", err),
+            Self::LaunchFailed(err) => Report::error("Failed to launch app on simulator", err),
+            Self::HookFailed(err) => err.report(),
+        }
+    }
+}
+
+/// The target that simulator builds are made for.
+pub fn simulator_target<'a>() -> &'a Target<'a> {
+    Target::all()
+        .get("x86_64")
+        .expect("developer error: no simulator target")
+}
+
+fn install_on_simulator(
+    config: &Config,
+    env: &Env,
+    noise_level: opts::NoiseLevel,
+    profile: opts::Profile,
+    simulator: &Simulator,
+) -> Result<(), SimulatorRunError> {
+    let target = simulator_target();
+    println!("Building app for {}...", simulator);
+    target
+        .build_for_simulator(config, env, noise_level, profile, &simulator.udid)
+        .map_err(SimulatorRunError::BuildFailed)?;
+    let app_path = config.simulator_app_path(profile);
+    if !app_path.is_dir() {
+        return Err(SimulatorRunError::AppMissing { app_path });
+    }
+    println!("Booting simulator...");
+    simulator
+        .boot(Runner::global())
+        .map_err(SimulatorRunError::BootFailed)?;
+    println!("Installing app...");
+    simulator
+        .install(Runner::global(), &app_path)
+        .map_err(SimulatorRunError::InstallFailed)?;
+    config
+        .app()
+        .hooks()
+        .run(
+            Stage::PostInstall,
+            &hooks::Context::new("ios", Some(target.triple), profile.as_str())
+                .with_artifact(app_path),
+        )
+        .map_err(SimulatorRunError::HookFailed)
+}
+
+/// Builds for, boots, installs to, and launches on `simulator`, streaming the
+/// app's output until it exits.
+pub fn run_on_simulator(
+    config: &Config,
+    env: &Env,
+    noise_level: opts::NoiseLevel,
+    profile: opts::Profile,
+    simulator: &Simulator,
+) -> Result<(), SimulatorRunError> {
+    install_on_simulator(config, env, noise_level, profile, simulator)?;
+    println!("Launching app...");
+    simulator
+        .launch(Runner::global(), &config.bundle_id())
+        .map_err(SimulatorRunError::LaunchFailed)
+}
+
+/// Like `run_on_simulator`, but restarts the app if it's already running, and
+/// returns as soon as it's launched.
+pub fn deploy_to_simulator(
+    config: &Config,
+    env: &Env,
+    noise_level: opts::NoiseLevel,
+    profile: opts::Profile,
+    simulator: &Simulator,
+) -> Result<(), SimulatorRunError> {
+    install_on_simulator(config, env, noise_level, profile, simulator)?;
+    println!("Launching app...");
+    simulator
+        .relaunch(Runner::global(), &config.bundle_id())
+        .map_err(SimulatorRunError::LaunchFailed)
+}
+
 #[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Device<'a> {
     id: String,
     name: String,
     model: String,
+    os_version: Option<String>,
     target: &'a Target<'a>,
 }
 
@@ -54,25 +164,50 @@ impl<'a> Display for Device<'a> {
 }
 
 impl<'a> Device<'a> {
-    pub(super) fn new(id: String, name: String, model: String, target: &'a Target<'a>) -> Self {
+    pub(super) fn new(
+        id: String,
+        name: String,
+        model: String,
+        os_version: Option<String>,
+        target: &'a Target<'a>,
+    ) -> Self {
         Self {
             id,
             name,
             model,
+            os_version,
             target,
         }
     }
 
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn target(&self) -> &'a Target<'a> {
         self.target
     }
 
-    pub fn run(
+    pub fn summary(&self) -> DeviceSummary {
+        DeviceSummary {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            model: self.model.clone(),
+            os: "iOS",
+            os_version: self.os_version.clone(),
+            abi: self.target.arch.to_owned(),
+        }
+    }
+
+    fn export_ipa(
         &self,
         config: &Config,
         env: &Env,
         noise_level: opts::NoiseLevel,
-        non_interactive: opts::NonInteractive,
         profile: opts::Profile,
     ) -> Result<(), RunError> {
         // TODO: These steps are run unconditionally, which is slooooooow
@@ -86,7 +221,7 @@ impl<'a> Device<'a> {
             .map_err(RunError::ArchiveFailed)?;
         println!("Exporting app...");
         self.target
-            .export(config, env, noise_level)
+            .export(config, env, noise_level, &config.export_plist_path())
             .map_err(RunError::ExportFailed)?;
         println!("Extracting IPA...");
         bossy::Command::pure("unzip")
@@ -106,8 +241,45 @@ impl<'a> Device<'a> {
             .with_arg(&config.export_dir())
             .run_and_wait()
             .map_err(RunError::UnzipFailed)?;
-        ios_deploy::run_and_debug(config, env, non_interactive, &self.id)
-            .map_err(RunError::DeployFailed)?;
         Ok(())
     }
+
+    pub fn run(
+        &self,
+        config: &Config,
+        env: &Env,
+        noise_level: opts::NoiseLevel,
+        non_interactive: opts::NonInteractive,
+        profile: opts::Profile,
+    ) -> Result<(), RunError> {
+        self.export_ipa(config, env, noise_level, profile)?;
+        ios_deploy::run_and_debug(config, env, non_interactive, &self.id)
+            .map_err(RunError::DeployFailed)
+    }
+
+    /// Builds, installs, and launches the app without attaching a debugger.
+    /// Unlike `run`, this returns as soon as the app's launched.
+    ///
+    /// `ios-deploy` installs and launches in one go, so the `post-install`
+    /// hooks run once the app's already launched. `run` doesn't run them at
+    /// all, since the debugger doesn't detach until the app exits.
+    pub fn deploy(
+        &self,
+        config: &Config,
+        env: &Env,
+        noise_level: opts::NoiseLevel,
+        profile: opts::Profile,
+    ) -> Result<(), RunError> {
+        self.export_ipa(config, env, noise_level, profile)?;
+        ios_deploy::launch(config, env, &self.id).map_err(RunError::DeployFailed)?;
+        config
+            .app()
+            .hooks()
+            .run(
+                Stage::PostInstall,
+                &hooks::Context::new("ios", Some(self.target.triple), profile.as_str())
+                    .with_artifact(config.app_path()),
+            )
+            .map_err(RunError::HookFailed)
+    }
 }
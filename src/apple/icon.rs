@@ -0,0 +1,108 @@
+//! The `AppIcon` asset catalog generated from `app.icon`, which Xcode picks
+//! up along with everything else in `Sources`.
+
+use super::config::Config;
+use crate::{
+    config::app::icon::Icon,
+    icon::{self, Source},
+};
+use serde::Serialize;
+use std::path::Path;
+
+// Each idiom, point size, and scale that Xcode has a slot for. Icons can't be
+// transparent on iOS, so they all get the background filled in.
+static SLOTS: &[(&str, &str, u32)] = &[
+    ("iphone", "20", 2),
+    ("iphone", "20", 3),
+    ("iphone", "29", 2),
+    ("iphone", "29", 3),
+    ("iphone", "40", 2),
+    ("iphone", "40", 3),
+    ("iphone", "60", 2),
+    ("iphone", "60", 3),
+    ("ipad", "20", 1),
+    ("ipad", "20", 2),
+    ("ipad", "29", 1),
+    ("ipad", "29", 2),
+    ("ipad", "40", 1),
+    ("ipad", "40", 2),
+    ("ipad", "76", 1),
+    ("ipad", "76", 2),
+    ("ipad", "83.5", 2),
+    ("ios-marketing", "1024", 1),
+];
+
+#[derive(Serialize)]
+struct Info {
+    author: &'static str,
+    version: u32,
+}
+
+static INFO: Info = Info {
+    author: "cargo-mobile",
+    version: 1,
+};
+
+#[derive(Serialize)]
+struct Image {
+    filename: String,
+    idiom: &'static str,
+    scale: String,
+    size: String,
+}
+
+#[derive(Serialize)]
+struct Contents<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    images: Option<Vec<Image>>,
+    info: &'a Info,
+}
+
+fn write_contents(dir: &Path, contents: &Contents<'_>) -> Result<(), icon::Error> {
+    let mut json = serde_json::to_string_pretty(contents)
+        .expect("developer error: asset catalog contents couldn't be serialized");
+    json.push('\n');
+    icon::write_file(&dir.join("Contents.json"), json)
+}
+
+/// Writes `Assets.xcassets` into the sources of the project rendered at
+/// `dest`.
+pub fn gen(config: &Config, icon: &Icon, dest: &Path) -> Result<(), icon::Error> {
+    let source = Source::load(icon)?;
+    let catalog = dest
+        .join("Sources")
+        .join(config.app().name())
+        .join("Assets.xcassets");
+    write_contents(
+        &catalog,
+        &Contents {
+            images: None,
+            info: &INFO,
+        },
+    )?;
+    let icon_set = catalog.join("AppIcon.appiconset");
+    let mut images = Vec::new();
+    for (idiom, size, scale) in SLOTS {
+        // Some slots are the same size as others, but Xcode is fine with them
+        // sharing a file.
+        let filename = format!("icon-{}@{}x.png", size, scale);
+        let points = size
+            .parse::<f32>()
+            .expect("developer error: icon slot size isn't a number");
+        let pixels = (points * *scale as f32) as u32;
+        icon::write_png(&source.opaque(pixels), &icon_set.join(&filename))?;
+        images.push(Image {
+            filename,
+            idiom,
+            scale: format!("{}x", scale),
+            size: format!("{}x{}", size, size),
+        });
+    }
+    write_contents(
+        &icon_set,
+        &Contents {
+            images: Some(images),
+            info: &INFO,
+        },
+    )
+}
@@ -41,9 +41,18 @@ fn parse_device_list<'a>(output: &bossy::Output) -> Result<BTreeSet<Device<'a>>,
                  device_name,
                  model_arch,
                  model_name,
+                 product_version,
              }| {
                 Target::for_arch(&model_arch)
-                    .map(|target| Device::new(device_identifier, device_name, model_name, target))
+                    .map(|target| {
+                        Device::new(
+                            device_identifier,
+                            device_name,
+                            model_name,
+                            product_version,
+                            target,
+                        )
+                    })
                     .ok_or_else(|| DeviceListError::ArchInvalid(model_arch))
             },
         )
@@ -0,0 +1,46 @@
+use crate::{
+    env::{Env, ExplicitEnv as _},
+    util::cli::{Report, Reportable},
+};
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum DownloadError {
+    DownloadFailed { path: String, cause: bossy::Error },
+}
+
+impl Reportable for DownloadError {
+    fn report(&self) -> Report {
+        match self {
+            Self::DownloadFailed { path, cause } => Report::error(
+                format!("Failed to download {:?} from the app's container", path),
+                cause,
+            ),
+        }
+    }
+}
+
+/// Copies `path` out of the app's container on the device into `to`, where it
+/// keeps its path within the container. `path` can be a file or a dir.
+pub fn download(
+    env: &Env,
+    id: &str,
+    bundle_id: &str,
+    path: &str,
+    to: &Path,
+) -> Result<(), DownloadError> {
+    bossy::Command::pure("ios-deploy")
+        .with_env_vars(env.explicit_env())
+        .with_args(&["--id", id])
+        .with_args(&["--bundle_id", bundle_id])
+        .with_arg(format!("--download={}", path))
+        .with_arg("--to")
+        .with_arg(to)
+        .with_arg("--no-wifi")
+        .run_and_wait_for_output()
+        .map(|_| ())
+        .map_err(|cause| DownloadError::DownloadFailed {
+            path: path.to_owned(),
+            cause,
+        })
+}
@@ -1,7 +1,8 @@
 mod device_list;
+mod download;
 mod run;
 
-pub use self::{device_list::*, run::*};
+pub use self::{device_list::*, download::*, run::*};
 
 use serde::Deserialize;
 use std::path::PathBuf;
@@ -16,6 +17,8 @@ struct DeviceInfo {
     model_arch: String,
     #[serde(rename = "modelName")]
     model_name: String,
+    #[serde(rename = "ProductVersion", default)]
+    product_version: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -41,3 +41,20 @@ pub fn run_and_debug(
         .map(|_| ())
         .map_err(RunAndDebugError::DeployFailed)
 }
+
+/// Installs and launches the app without attaching a debugger, returning as
+/// soon as it's launched.
+pub fn launch(config: &Config, env: &Env, id: &str) -> Result<(), RunAndDebugError> {
+    println!("Deploying app to device...");
+    bossy::Command::pure("ios-deploy")
+        .with_env_vars(env.explicit_env())
+        .with_arg("--justlaunch")
+        .with_args(&["--id", id])
+        .with_arg("--bundle")
+        .with_arg(&config.app_path())
+        .with_arg("--noninteractive")
+        .with_arg("--no-wifi")
+        .run_and_wait()
+        .map(|_| ())
+        .map_err(RunAndDebugError::DeployFailed)
+}
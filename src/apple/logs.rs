@@ -0,0 +1,109 @@
+use crate::util::{self, CommandError, Invocation, Runner};
+use colored::{Color, Colorize as _};
+use once_cell_regex::regex;
+use std::{
+    fmt::{self, Display},
+    io::{self, BufRead as _, BufReader},
+    process::Stdio,
+};
+
+#[derive(Debug)]
+pub enum LogError {
+    SyslogCheckFailed(bossy::Error),
+    SyslogMissing,
+    CommandFailed(CommandError),
+    ReadFailed(io::Error),
+}
+
+impl Display for LogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SyslogCheckFailed(err) => write!(
+                f,
+                "Failed to check if `idevicesyslog` is installed: {}",
+                err
+            ),
+            Self::SyslogMissing => write!(
+                f,
+                "`idevicesyslog` is needed to stream logs from iOS devices; you can install it with `brew install libimobiledevice`"
+            ),
+            Self::CommandFailed(err) => write!(f, "Failed to stream logs: {}", err),
+            Self::ReadFailed(err) => write!(f, "Failed to read log output: {}", err),
+        }
+    }
+}
+
+fn level_color(level: &str) -> Option<Color> {
+    match level {
+        "Debug" | "Db" => Some(Color::BrightBlack),
+        "Info" | "I" => Some(Color::Green),
+        "Warning" => Some(Color::Yellow),
+        "Error" | "E" | "Fault" | "F" => Some(Color::BrightRed),
+        _ => None,
+    }
+}
+
+/// Colorizes a line of `idevicesyslog` output by its level, which looks like
+/// this:
+///
+/// ```text
+/// Oct 24 13:52:01 Janes-iPhone MyApp(libmy_app.dylib)[1234] <Error>: oh no
+/// ```
+pub fn colorize_syslog_line(line: &str) -> String {
+    regex!(r"<(\w+)>:")
+        .captures(line)
+        .and_then(|caps| level_color(&caps[1]))
+        .map_or_else(|| line.to_owned(), |color| line.color(color).to_string())
+}
+
+/// Colorizes a line of `log stream --style compact` output by its type, which
+/// looks like this:
+///
+/// ```text
+/// 2020-10-24 13:52:01.123 E  MyApp[1234:5678] oh no
+/// ```
+pub fn colorize_compact_line(line: &str) -> String {
+    regex!(r"^\S+ \S+ (\w+)\s")
+        .captures(line)
+        .and_then(|caps| level_color(&caps[1]))
+        .map_or_else(|| line.to_owned(), |color| line.color(color).to_string())
+}
+
+fn stream(command: Invocation, colorize: fn(&str) -> String) -> Result<(), LogError> {
+    let mut child = Runner::global()
+        .spawn(&command, Stdio::inherit(), Stdio::piped(), Stdio::inherit())
+        .map_err(LogError::CommandFailed)?;
+    let reader = child
+        .take_stdout()
+        .expect("developer error: log stream stdout not captured");
+    for line in BufReader::new(reader).lines() {
+        println!("{}", colorize(&line.map_err(LogError::ReadFailed)?));
+    }
+    child.wait().map_err(LogError::CommandFailed)?;
+    Ok(())
+}
+
+/// Streams logs for `process` from the device with the given UDID. There's no
+/// first-party tool for this that works across Xcode versions, so this uses
+/// `idevicesyslog` from libimobiledevice.
+pub fn device_log(udid: &str, process: &str) -> Result<(), LogError> {
+    if !util::command_present("idevicesyslog").map_err(LogError::SyslogCheckFailed)? {
+        return Err(LogError::SyslogMissing);
+    }
+    stream(
+        Invocation::impure("idevicesyslog").with_args(&["-u", udid, "--process", process]),
+        colorize_syslog_line,
+    )
+}
+
+/// Streams logs for `process` from the simulator with the given UDID.
+pub fn simulator_log(udid: &str, process: &str) -> Result<(), LogError> {
+    stream(
+        Invocation::impure("xcrun")
+            .with_args(&["simctl", "spawn", udid, "log", "stream"])
+            .with_args(&["--style", "compact", "--level", "debug"])
+            .with_arg("--predicate")
+            .with_arg(format!("process == {:?}", process)),
+        colorize_compact_line,
+    )
+}
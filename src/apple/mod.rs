@@ -1,12 +1,25 @@
 pub mod cli;
+pub mod cocoapods;
+pub mod codesign;
 pub(crate) mod config;
 mod deps;
 mod device;
+mod icon;
 mod ios_deploy;
+mod logs;
+pub(crate) mod platform;
+mod plist;
+mod profiler;
 pub(crate) mod project;
-mod system_profile;
-mod target;
+pub mod simctl;
+pub(crate) mod system_profile;
+pub(crate) mod target;
 mod teams;
+mod test_runner;
+pub mod xcconfig;
+pub mod xcodebuild;
+mod xcodeproj;
+pub mod xctrace;
 
 use crate::util::{
     self,
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+/// An Apple platform we can build for. iOS and macOS are always enabled, and
+/// the rest need to be listed in `apple.platforms`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlatformKind {
+    Ios,
+    Macos,
+    Tvos,
+    Watchos,
+    /// The iOS app built for macOS.
+    Catalyst,
+}
+
+impl Display for PlatformKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug)]
+pub struct PlatformInvalid {
+    name: String,
+}
+
+impl Display for PlatformInvalid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} isn't a platform; the possible platforms are {:?}",
+            self.name,
+            PlatformKind::NAMES
+        )
+    }
+}
+
+impl FromStr for PlatformKind {
+    type Err = PlatformInvalid;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|platform| platform.name() == s)
+            .ok_or_else(|| PlatformInvalid { name: s.to_owned() })
+    }
+}
+
+impl PlatformKind {
+    pub const ALL: &'static [Self] = &[
+        Self::Ios,
+        Self::Macos,
+        Self::Tvos,
+        Self::Watchos,
+        Self::Catalyst,
+    ];
+
+    /// The ones that can be listed in `apple.platforms`.
+    pub const OPTIONAL: &'static [Self] = &[Self::Tvos, Self::Watchos, Self::Catalyst];
+
+    pub const NAMES: &'static [&'static str] = &["ios", "macos", "tvos", "watchos", "catalyst"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ios => "ios",
+            Self::Macos => "macos",
+            Self::Tvos => "tvos",
+            Self::Watchos => "watchos",
+            Self::Catalyst => "catalyst",
+        }
+    }
+
+    /// Parses Xcode's `PLATFORM_DISPLAY_NAME`, which is "macOS" for Catalyst
+    /// builds too; `EFFECTIVE_PLATFORM_NAME` is what tells those apart.
+    pub fn from_xcode(display_name: &str, effective_platform_name: &str) -> Option<Self> {
+        match (display_name, effective_platform_name) {
+            (_, "-maccatalyst") => Some(Self::Catalyst),
+            ("iOS", _) => Some(Self::Ios),
+            ("macOS", _) => Some(Self::Macos),
+            ("tvOS", _) => Some(Self::Tvos),
+            ("watchOS", _) => Some(Self::Watchos),
+            _ => None,
+        }
+    }
+
+    /// The suffix of the Xcode targets for this platform. Catalyst builds the
+    /// iOS targets.
+    pub fn target_suffix(self) -> &'static str {
+        match self {
+            Self::Ios | Self::Catalyst => "iOS",
+            Self::Macos => "macOS",
+            Self::Tvos => "tvOS",
+            Self::Watchos => "watchOS",
+        }
+    }
+
+    /// The SDK to build against for devices.
+    pub fn sdk(self) -> &'static str {
+        match self {
+            Self::Ios => "iphoneos",
+            Self::Macos | Self::Catalyst => "macosx",
+            Self::Tvos => "appletvos",
+            Self::Watchos => "watchos",
+        }
+    }
+
+    /// Whether the Xcode project has an app for this platform. On watchOS,
+    /// there's only the lib, which you can link into your own WatchKit
+    /// extension.
+    pub fn has_app(self) -> bool {
+        !matches!(self, Self::Watchos)
+    }
+
+    /// These targets don't have prebuilt standard libraries, so they can't be
+    /// installed via `rustup` and need `-Z build-std` (and nightly) instead.
+    pub fn needs_build_std(self) -> bool {
+        matches!(self, Self::Tvos | Self::Watchos | Self::Catalyst)
+    }
+
+    /// The environment variable rustc reads the deployment target from.
+    pub fn deployment_target_var(self) -> Option<&'static str> {
+        match self {
+            Self::Ios => Some("IPHONEOS_DEPLOYMENT_TARGET"),
+            Self::Tvos => Some("TVOS_DEPLOYMENT_TARGET"),
+            Self::Watchos => Some("WATCHOS_DEPLOYMENT_TARGET"),
+            Self::Macos | Self::Catalyst => None,
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use super::{
+    config::{Config, SigningStyle},
+    platform::PlatformKind,
+};
+use crate::{
+    util::xml::{self, EditError, TagKind},
+    version::{self, Stamp},
+};
+use std::{fmt::Write as _, io, path::PathBuf};
+
+/// The `Info.plist` for each target that the project generator makes one for.
+pub fn paths(config: &Config) -> Vec<PathBuf> {
+    [PlatformKind::Ios, PlatformKind::Macos, PlatformKind::Tvos]
+        .iter()
+        .filter(|platform| config.platform_enabled(**platform))
+        .map(|platform| {
+            config.project_dir().join(format!(
+                "{}_{}/Info.plist",
+                config.app().name(),
+                platform.target_suffix()
+            ))
+        })
+        .collect()
+}
+
+fn insert(contents: &str, at: usize, indent: &str, key: &str, value: &str) -> String {
+    format!(
+        "{}{}<key>{}</key>\n{}<string>{}</string>\n{}",
+        &contents[..at],
+        indent,
+        xml::escape(key),
+        indent,
+        xml::escape(value),
+        &contents[at..]
+    )
+}
+
+/// Sets `key` in the top-level dict to the string `value`, adding it at the
+/// end of the dict if it isn't there yet. Everything else is left alone.
+pub fn set_string(contents: &str, key: &str, value: &str) -> Result<String, EditError> {
+    let root = xml::root_tag(contents)?;
+    if root.name != "plist" {
+        return Err(EditError::ElementMissing { name: "plist" });
+    }
+    let dict = xml::next_tag(contents, root.end)?
+        .filter(|tag| tag.name == "dict")
+        .ok_or(EditError::ElementMissing { name: "dict" })?;
+    if let TagKind::Empty = dict.kind {
+        return Ok(format!(
+            "{}<dict>\n{}</dict>{}",
+            &contents[..dict.start],
+            insert("", 0, "\t", key, value),
+            &contents[dict.end..]
+        ));
+    }
+    let malformed = |reason: String| EditError::Malformed { reason };
+    let mut depth = 0usize;
+    let mut pos = dict.end;
+    let mut last_key_start = None;
+    loop {
+        let tag =
+            xml::next_tag(contents, pos)?.ok_or(EditError::Unterminated { start: dict.start })?;
+        match tag.kind {
+            // We've reached the end of the top-level dict without finding the
+            // key, so it goes on its own lines right before the end.
+            TagKind::Close if depth == 0 => {
+                let indent = last_key_start
+                    .and_then(|key_start: usize| {
+                        let line_start = contents[..key_start].rfind('\n')? + 1;
+                        Some(&contents[line_start..key_start])
+                    })
+                    .filter(|indent| indent.chars().all(char::is_whitespace))
+                    .unwrap_or("\t");
+                let line_start = contents[..tag.start]
+                    .rfind('\n')
+                    .map(|index| index + 1)
+                    .filter(|&line_start| {
+                        contents[line_start..tag.start]
+                            .chars()
+                            .all(char::is_whitespace)
+                    });
+                return Ok(match line_start {
+                    Some(line_start) => insert(contents, line_start, indent, key, value),
+                    None => insert(
+                        &format!("{}\n{}", &contents[..tag.start], &contents[tag.start..]),
+                        tag.start + 1,
+                        indent,
+                        key,
+                        value,
+                    ),
+                });
+            }
+            TagKind::Close => depth -= 1,
+            TagKind::Open if depth == 0 && tag.name == "key" => {
+                let close = xml::next_tag(contents, tag.end)?
+                    .filter(|close| close.name == "key")
+                    .ok_or_else(|| malformed("A `<key>` is never closed.".to_owned()))?;
+                last_key_start = Some(tag.start);
+                if xml::unescape(&contents[tag.end..close.start]) == key {
+                    let value_tag = xml::next_tag(contents, close.end)?
+                        .ok_or_else(|| malformed(format!("`{}` doesn't have a value.", key)))?;
+                    return match (value_tag.name, value_tag.kind) {
+                        ("string", TagKind::Open) => {
+                            let value_close =
+                                xml::next_tag(contents, value_tag.end)?.ok_or_else(|| {
+                                    malformed(format!("The value of `{}` is never closed.", key))
+                                })?;
+                            Ok(format!(
+                                "{}{}{}",
+                                &contents[..value_tag.end],
+                                xml::escape(value),
+                                &contents[value_close.start..]
+                            ))
+                        }
+                        ("string", TagKind::Empty) => Ok(format!(
+                            "{}<string>{}</string>{}",
+                            &contents[..value_tag.start],
+                            xml::escape(value),
+                            &contents[value_tag.end..]
+                        )),
+                        _ => Err(malformed(format!(
+                            "The value of `{}` is a `<{}>` instead of a `<string>`.",
+                            key, value_tag.name
+                        ))),
+                    };
+                }
+                pos = close.end;
+                continue;
+            }
+            TagKind::Open => depth += 1,
+            TagKind::Empty => (),
+        }
+        pos = tag.end;
+    }
+}
+
+/// Sets `CFBundleShortVersionString` and `CFBundleVersion` in each `Info.plist`
+/// that exists.
+pub fn stamp(config: &Config, stamp: &Stamp) -> Result<(), version::Error> {
+    let build_number = stamp.build_number().to_string();
+    for path in paths(config) {
+        let result = version::rewrite(config.app().root_dir(), &path, |contents| {
+            let contents = set_string(contents, "CFBundleShortVersionString", stamp.version())?;
+            set_string(&contents, "CFBundleVersion", &build_number)
+        });
+        match result {
+            Err(version::Error::ReadFailed { cause, .. })
+                if cause.kind() == io::ErrorKind::NotFound =>
+            {
+                log::info!(
+                    "not stamping version into {:?}, since it doesn't exist",
+                    path
+                );
+            }
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+/// Makes the `ExportOptions.plist` that `xcodebuild -exportArchive` needs to
+/// sign the IPA the way `apple.signing` says to.
+pub fn export_options(config: &Config) -> String {
+    let signing = config.signing();
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n",
+        "<plist version=\"1.0\">\n",
+        "<dict>\n",
+    ));
+    let mut entry = |key: &str, value: &str| {
+        out.push_str(&insert("", 0, "\t", key, value));
+    };
+    entry("method", signing.export_method().as_str());
+    entry("teamID", signing.team_id());
+    entry(
+        "signingStyle",
+        match signing.style() {
+            SigningStyle::Automatic => "automatic",
+            SigningStyle::Manual => "manual",
+        },
+    );
+    if let Some(identity) = signing.identity() {
+        entry("signingCertificate", identity);
+    }
+    if let (SigningStyle::Manual, Some(profile)) = (signing.style(), signing.provisioning_profile())
+    {
+        writeln!(
+            out,
+            "\t<key>provisioningProfiles</key>\n\t<dict>\n\t\t<key>{}</key>\n\t\t<string>{}</string>\n\t</dict>",
+            xml::escape(&config.bundle_id()),
+            xml::escape(profile)
+        )
+        .unwrap();
+    }
+    out.push_str("</dict>\n</plist>\n");
+    out
+}
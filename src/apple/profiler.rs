@@ -0,0 +1,85 @@
+//! Drives Instruments via `xctrace`, for `cargo apple profile`.
+
+use super::{
+    config::Config,
+    device::{Device, RunError},
+    ios_deploy, xctrace,
+};
+use crate::{
+    env::Env,
+    opts::{NoiseLevel, Profile},
+    profiling::{self, TraceDirFailed},
+    util::cli::{Report, Reportable},
+};
+use std::path::PathBuf;
+
+pub static DEFAULT_TEMPLATE: &str = "Time Profiler";
+
+#[derive(Debug)]
+pub enum ProfileError {
+    DeployFailed(RunError),
+    TraceDirFailed(TraceDirFailed),
+    RecordFailed(bossy::Error),
+    DownloadFailed(ios_deploy::DownloadError),
+}
+
+impl Reportable for ProfileError {
+    fn report(&self) -> Report {
+        match self {
+            Self::DeployFailed(err) => err.report(),
+            Self::TraceDirFailed(err) => Report::error("Failed to save profile", err),
+            Self::RecordFailed(err) => Report::error("Failed to record profile via `xctrace`", err),
+            Self::DownloadFailed(err) => err.report(),
+        }
+    }
+}
+
+/// Deploys a release build to `device`, records it with the Instruments
+/// `template` for `duration` seconds, and then downloads each of `pull` from
+/// the app's container. Everything ends up in `target/mobile-profiles`, and
+/// the paths of what got saved are returned, starting with the trace.
+///
+/// The build only gets debug info if `env` has [`profiling::DEBUG_INFO_VAR`]
+/// set, since that has to reach the cargo that Xcode runs.
+pub fn profile(
+    config: &Config,
+    env: &Env,
+    device: &Device<'_>,
+    noise_level: NoiseLevel,
+    template: &str,
+    duration: u32,
+    pull: &[String],
+) -> Result<Vec<PathBuf>, ProfileError> {
+    device
+        .deploy(config, env, noise_level, Profile::Release)
+        .map_err(ProfileError::DeployFailed)?;
+    let trace_path = profiling::trace_path(config.app(), "ios", "trace")
+        .map_err(ProfileError::TraceDirFailed)?;
+    println!(
+        "Recording {} with {:?} for {} seconds...",
+        config.app().name(),
+        template,
+        duration
+    );
+    // The app's process is named after its executable, which is the app name.
+    xctrace::record(
+        env,
+        device.id(),
+        config.app().name(),
+        template,
+        duration,
+        &trace_path,
+    )
+    .map_err(ProfileError::RecordFailed)?;
+    let mut saved = vec![trace_path];
+    if !pull.is_empty() {
+        let files_dir = saved[0].with_extension("");
+        for path in pull {
+            println!("Downloading {:?} from the app's container...", path);
+            ios_deploy::download(env, device.id(), &config.bundle_id(), path, &files_dir)
+                .map_err(ProfileError::DownloadFailed)?;
+        }
+        saved.push(files_dir);
+    }
+    Ok(saved)
+}
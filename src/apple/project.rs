@@ -1,10 +1,11 @@
 use super::{
-    config::{Config, Metadata},
+    config::{Config, Metadata, ProjectGenerator},
     deps, rust_version_check,
     target::Target,
+    teams, xcodeproj,
 };
 use crate::{
-    opts,
+    opts, os,
     target::TargetTrait as _,
     templating::{self, Pack},
     util::{
@@ -19,19 +20,22 @@ pub static TEMPLATE_PACK: &str = "xcode";
 
 #[derive(Debug)]
 pub enum Error {
-    RustupFailed(bossy::Error),
+    RustupFailed(util::CommandError),
     RustVersionCheckFailed(util::RustVersionError),
     DepsInstallFailed(deps::Error),
     MissingPack(templating::LookupError),
     TemplateProcessingFailed(bicycle::ProcessingError),
-    AssetDirSymlinkFailed(ln::Error),
+    AssetDirLinkFailed(ln::LinkDirError),
     XcodegenFailed(bossy::Error),
+    NativeGenFailed(xcodeproj::Error),
+    IconGenFailed(crate::icon::Error),
+    RootDirRelativizeFailed(util::RelativizeError),
 }
 
 impl Reportable for Error {
     fn report(&self) -> Report {
         match self {
-            Self::RustupFailed(err) => Report::error("Failed to `rustup` Apple toolchains", err),
+            Self::RustupFailed(err) => err.report("Failed to `rustup` Apple toolchains"),
             Self::RustVersionCheckFailed(err) => err.report(),
             Self::DepsInstallFailed(err) => {
                 Report::error("Failed to install Apple dependencies", err)
@@ -40,60 +44,105 @@ impl Reportable for Error {
             Self::TemplateProcessingFailed(err) => {
                 Report::error("Xcode template processing failed", err)
             }
-            Self::AssetDirSymlinkFailed(err) => {
-                Report::error("Asset dir couldn't be symlinked into Xcode project", err)
+            Self::AssetDirLinkFailed(err) => {
+                Report::error("Asset dir couldn't be linked into Xcode project", err)
             }
             Self::XcodegenFailed(err) => Report::error("Failed to run `xcodegen`", err),
+            Self::NativeGenFailed(err) => err.report(),
+            Self::IconGenFailed(err) => Report::error("Failed to generate iOS app icons", err),
+            Self::RootDirRelativizeFailed(err) => Report::error(
+                "Failed to get the app root relative to the Xcode project",
+                err,
+            ),
         }
     }
 }
 
-// unprefixed app_root seems pretty dangerous!!
-// TODO: figure out what I meant by that
-pub fn gen(
+// The dirs shown in the Xcode project for browsing, relative to the project
+// dir.
+fn source_dirs(config: &Config, submodule_path: Option<&Path>) -> Result<Vec<PathBuf>, Error> {
+    let rel_prefix = util::relativize_path(config.app().root_dir(), config.project_dir())
+        .map_err(Error::RootDirRelativizeFailed)?;
+    Ok(std::iter::once("src".as_ref())
+        .chain(submodule_path)
+        .map(|path| rel_prefix.join(path))
+        .collect())
+}
+
+/// Renders the Xcode project templates into `dest`, which is normally the
+/// project dir, but can also be a staging dir to compare against. This only
+/// renders `project.yml` and friends (and the app icons, if `app.icon` is
+/// set); see [`generate`] for the rest.
+pub fn render(
     config: &Config,
     metadata: &Metadata,
     submodule_path: Option<&Path>,
     bike: &bicycle::Bicycle,
-    wrapper: &TextWrapper,
-    non_interactive: opts::NonInteractive,
-    skip_dev_tools: opts::SkipDevTools,
-    reinstall_deps: opts::ReinstallDeps,
-    filter: &templating::Filter,
+    dest: &Path,
+    filter: impl FnMut(&bicycle::Action) -> bool,
 ) -> Result<(), Error> {
-    println!("Installing iOS toolchains...");
-    Target::install_all().map_err(Error::RustupFailed)?;
-    rust_version_check(wrapper).map_err(Error::RustVersionCheckFailed)?;
-
-    deps::install(wrapper, non_interactive, skip_dev_tools, reinstall_deps)
-        .map_err(Error::DepsInstallFailed)?;
-
-    let dest = config.project_dir();
-    let rel_prefix = util::relativize_path(config.app().root_dir(), &dest);
-    let source_dirs = std::iter::once("src".as_ref())
-        .chain(submodule_path)
-        .map(|path| rel_prefix.join(path))
-        .collect::<Vec<PathBuf>>();
+    let source_dirs = source_dirs(config, submodule_path)?;
 
     let src = Pack::lookup_platform(TEMPLATE_PACK)
         .map_err(Error::MissingPack)?
         .expect_local();
 
-    bike.filter_and_process(
+    templating::filter_and_process(
+        bike,
         src,
-        &dest,
+        dest,
         |map| {
             map.insert("file-groups", &source_dirs);
             map.insert("ios-frameworks", metadata.ios().frameworks());
             map.insert("macos-frameworks", metadata.macos().frameworks());
+            map.insert("tvos-frameworks", metadata.tvos().frameworks());
         },
-        filter.fun(),
+        filter,
     )
     .map_err(Error::TemplateProcessingFailed)?;
+    if let Some(icon) = config.app().icon() {
+        super::icon::gen(config, icon, dest).map_err(Error::IconGenFailed)?;
+    }
+    Ok(())
+}
 
-    ln::force_symlink_relative(config.app().asset_dir(), &dest, ln::TargetStyle::Directory)
-        .map_err(Error::AssetDirSymlinkFailed)?;
+/// Whether `xcodegen` will be used to generate the project, per
+/// `apple.project-generator`.
+pub fn uses_xcodegen(config: &Config) -> bool {
+    match config.project_generator() {
+        ProjectGenerator::Xcodegen => true,
+        ProjectGenerator::Native => false,
+        ProjectGenerator::Auto => util::command_present("xcodegen").unwrap_or_else(|err| {
+            log::warn!("failed to check if `xcodegen` is installed: {}", err);
+            false
+        }),
+    }
+}
 
+/// Generates the actual Xcode project from the rendered templates in `dest`,
+/// using either `xcodegen` or the built-in generator.
+pub fn generate(
+    config: &Config,
+    metadata: &Metadata,
+    submodule_path: Option<&Path>,
+    dest: &Path,
+) -> Result<(), Error> {
+    if uses_xcodegen(config) {
+        xcodegen(dest)
+    } else {
+        log::info!("generating Xcode project without `xcodegen`");
+        xcodeproj::generate(
+            config,
+            metadata,
+            &source_dirs(config, submodule_path)?,
+            dest,
+        )
+        .map_err(Error::NativeGenFailed)
+    }
+}
+
+// Generates the actual Xcode project from the `project.yml` in `dest`.
+fn xcodegen(dest: &Path) -> Result<(), Error> {
     // Note that Xcode doesn't always reload the project nicely; reopening is
     // often necessary.
     println!("Generating Xcode project...");
@@ -104,3 +153,82 @@ pub fn gen(
         .map_err(Error::XcodegenFailed)?;
     Ok(())
 }
+
+// unprefixed app_root seems pretty dangerous!!
+// TODO: figure out what I meant by that
+pub fn gen(
+    config: &Config,
+    metadata: &Metadata,
+    submodule_path: Option<&Path>,
+    bike: &bicycle::Bicycle,
+    wrapper: &TextWrapper,
+    non_interactive: opts::NonInteractive,
+    skip_dev_tools: opts::SkipDevTools,
+    reinstall_deps: opts::ReinstallDeps,
+    filter: &templating::Filter,
+    force_sync: opts::ForceSync,
+) -> Result<(), Error> {
+    if os::running_under_rosetta() {
+        Report::action_request(
+            "cargo-mobile is running under Rosetta",
+            "Builds will use the x86_64 toolchain and simulator, which can behave differently from native arm64 ones. If you run into confusing errors, try using a native arm64 toolchain instead.",
+        )
+        .print(wrapper);
+    }
+    println!("Installing iOS toolchains...");
+    Target::install_all(config.app().root_dir()).map_err(Error::RustupFailed)?;
+    // The other platforms' targets can't be installed, but `-Z build-std`
+    // needs the source to build their standard libraries from.
+    if let Some(platform) = config
+        .platforms()
+        .iter()
+        .find(|platform| platform.needs_build_std())
+    {
+        println!(
+            "Installing the standard library source, since {} needs to build it...",
+            platform
+        );
+        util::rustup_component_add(util::Runner::global(), "rust-src", None)
+            .map_err(Error::RustupFailed)?;
+    }
+    rust_version_check(wrapper).map_err(Error::RustVersionCheckFailed)?;
+
+    deps::install(
+        wrapper,
+        non_interactive,
+        skip_dev_tools,
+        reinstall_deps,
+        config.project_generator(),
+    )
+    .map_err(Error::DepsInstallFailed)?;
+
+    let dest = config.project_dir();
+    render(config, metadata, submodule_path, bike, &dest, filter.fun())?;
+
+    ln::link_dir_or_copy(
+        &config.app().asset_dir(),
+        &dest,
+        config.app().hard_copy(),
+        &config
+            .project_dir()
+            .join(util::ASSET_SYNC_MANIFEST_FILE_NAME),
+        force_sync.yes(),
+    )
+    .map_err(Error::AssetDirLinkFailed)?;
+
+    generate(config, metadata, submodule_path, &dest)?;
+    match teams::ios_team_id(&dest) {
+        Ok(Some(_)) => (),
+        Ok(None) => Report::action_request(
+            "No development team is configured for the Xcode project",
+            format!(
+                "Code signing will fail until one is set. You can set `{}.development-team` in your {} and then run `cargo mobile init` again.",
+                super::NAME,
+                crate::config::file_name()
+            ),
+        )
+        .print(wrapper),
+        Err(err) => log::warn!("failed to check Xcode project for a development team: {}", err),
+    }
+    Ok(())
+}
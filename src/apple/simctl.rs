@@ -0,0 +1,348 @@
+use crate::util::{CommandError, CommandRunner, Invocation, Runner};
+use once_cell_regex::regex;
+use serde::Deserialize;
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    fmt::{self, Display},
+    path::Path,
+};
+
+#[derive(Debug)]
+pub enum ListError {
+    CommandFailed(CommandError),
+    ParseFailed(serde_json::Error),
+}
+
+impl Display for ListError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CommandFailed(err) => {
+                write!(f, "Failed to list simulators via `simctl`: {}", err)
+            }
+            Self::ParseFailed(err) => write!(f, "Simulator list was invalid: {}", err),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FindError {
+    ListFailed(ListError),
+    NoneAvailable,
+    NotFound {
+        query: String,
+        available: Vec<String>,
+    },
+}
+
+impl Display for FindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ListFailed(err) => write!(f, "{}", err),
+            Self::NoneAvailable => write!(
+                f,
+                "No iOS simulators are available; you can add one in Xcode's \"Devices and Simulators\" window."
+            ),
+            Self::NotFound { query, available } => write!(
+                f,
+                "No iOS simulator named {:?} was found; the available simulators are: {}",
+                query,
+                available.join(", ")
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawSimulator {
+    udid: String,
+    name: String,
+    state: String,
+    #[serde(default = "default_true")]
+    is_available: bool,
+}
+
+const fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+struct RawList {
+    devices: BTreeMap<String, Vec<RawSimulator>>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Simulator {
+    pub udid: String,
+    pub name: String,
+    pub state: String,
+    pub version: (u32, u32),
+}
+
+impl Display for Simulator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (iOS {}.{})",
+            self.name, self.version.0, self.version.1
+        )?;
+        if self.is_booted() {
+            write!(f, " [booted]")?;
+        }
+        Ok(())
+    }
+}
+
+/// Gets the available iOS simulators from `simctl list devices --json` output,
+/// newest iOS version first. Runtimes are keyed like
+/// `com.apple.CoreSimulator.SimRuntime.iOS-14-2`, or like `iOS 12.4` in older
+/// versions of Xcode; anything that isn't iOS (i.e. watchOS) is left out.
+pub fn parse_simulators(raw_list: &[u8]) -> Result<Vec<Simulator>, serde_json::Error> {
+    let runtime_re = regex!(r"iOS[- ](\d+)[-.](\d+)$");
+    let list = serde_json::from_slice::<RawList>(raw_list)?;
+    let mut simulators = list
+        .devices
+        .into_iter()
+        .filter_map(|(runtime, simulators)| {
+            let caps = runtime_re.captures(&runtime)?;
+            let version = (caps[1].parse().ok()?, caps[2].parse().ok()?);
+            Some(
+                simulators
+                    .into_iter()
+                    .filter(|simulator| simulator.is_available)
+                    .map(move |simulator| Simulator {
+                        udid: simulator.udid,
+                        name: simulator.name,
+                        state: simulator.state,
+                        version,
+                    }),
+            )
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+    simulators.sort_by(|a, b| {
+        Reverse(a.version)
+            .cmp(&Reverse(b.version))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    Ok(simulators)
+}
+
+pub fn list() -> Result<Vec<Simulator>, ListError> {
+    list_with(Runner::global())
+}
+
+pub fn list_with(runner: &impl CommandRunner) -> Result<Vec<Simulator>, ListError> {
+    let output = runner
+        .capture(&Invocation::impure_parse(
+            "xcrun simctl list devices available --json",
+        ))
+        .map_err(ListError::CommandFailed)?;
+    parse_simulators(output.stdout()).map_err(ListError::ParseFailed)
+}
+
+/// Picks the simulator matching `query`, which can be either a UDID or a name.
+/// Since the same name is usually used for each installed iOS version, an
+/// already booted simulator is preferred, followed by the newest iOS version.
+pub fn find<'a>(simulators: &'a [Simulator], query: &str) -> Option<&'a Simulator> {
+    simulators
+        .iter()
+        .find(|simulator| simulator.udid.eq_ignore_ascii_case(query))
+        .or_else(|| {
+            let mut matches = simulators
+                .iter()
+                .filter(|simulator| simulator.name.eq_ignore_ascii_case(query));
+            let first = matches.next()?;
+            Some(
+                std::iter::once(first)
+                    .chain(matches)
+                    .find(|simulator| simulator.is_booted())
+                    .unwrap_or(first),
+            )
+        })
+}
+
+/// Like [`find`], but lists the simulators first.
+pub fn find_available(query: &str) -> Result<Simulator, FindError> {
+    find_available_with(Runner::global(), query)
+}
+
+pub fn find_available_with(
+    runner: &impl CommandRunner,
+    query: &str,
+) -> Result<Simulator, FindError> {
+    let simulators = list_with(runner).map_err(FindError::ListFailed)?;
+    if simulators.is_empty() {
+        return Err(FindError::NoneAvailable);
+    }
+    find(&simulators, query)
+        .cloned()
+        .ok_or_else(|| FindError::NotFound {
+            query: query.to_owned(),
+            available: simulators.iter().map(ToString::to_string).collect(),
+        })
+}
+
+impl Simulator {
+    pub fn is_booted(&self) -> bool {
+        self.state == "Booted"
+    }
+
+    /// Boots the simulator if it isn't already running, and brings up the
+    /// Simulator app so it can actually be seen.
+    pub fn boot(&self, runner: &impl CommandRunner) -> Result<(), CommandError> {
+        if self.is_booted() {
+            log::info!("simulator {} is already booted", self);
+        } else {
+            runner
+                .stream(&Invocation::impure("xcrun").with_args(&["simctl", "boot", &self.udid]))?;
+        }
+        runner.stream(&Invocation::impure("open").with_args(&[
+            "-a",
+            "Simulator",
+            "--args",
+            "-CurrentDeviceUDID",
+            &self.udid,
+        ]))?;
+        Ok(())
+    }
+
+    pub fn install(
+        &self,
+        runner: &impl CommandRunner,
+        app_path: &Path,
+    ) -> Result<(), CommandError> {
+        runner.stream(
+            &Invocation::impure("xcrun")
+                .with_args(&["simctl", "install", &self.udid])
+                .with_arg(app_path),
+        )?;
+        Ok(())
+    }
+
+    /// Launches the app with the given bundle ID, terminating it first if it's
+    /// already running. This returns as soon as the app's launched.
+    pub fn relaunch(
+        &self,
+        runner: &impl CommandRunner,
+        bundle_id: &str,
+    ) -> Result<(), CommandError> {
+        runner.stream(&Invocation::impure("xcrun").with_args(&[
+            "simctl",
+            "launch",
+            "--terminate-running-process",
+            &self.udid,
+            bundle_id,
+        ]))?;
+        Ok(())
+    }
+
+    /// Launches the app with the given bundle ID, streaming its stdout and
+    /// stderr until it exits.
+    pub fn launch(&self, runner: &impl CommandRunner, bundle_id: &str) -> Result<(), CommandError> {
+        runner.stream(&Invocation::impure("xcrun").with_args(&[
+            "simctl",
+            "launch",
+            "--console-pty",
+            &self.udid,
+            bundle_id,
+        ]))?;
+        Ok(())
+    }
+
+    /// Runs the executable at `path` on the host inside the simulator, which
+    /// needs to be booted already. The simulator shares the host's
+    /// filesystem, so there's nothing to copy over first. This passes along
+    /// the executable's exit status.
+    pub fn spawn(
+        &self,
+        runner: &impl CommandRunner,
+        path: &Path,
+        args: &[String],
+    ) -> Result<(), CommandError> {
+        runner.stream(
+            &Invocation::impure("xcrun")
+                .with_args(&["simctl", "spawn", &self.udid])
+                .with_arg(path)
+                .with_args(args),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::RecordingRunner;
+
+    static LIST: &str = r#"{
+        "devices": {
+            "com.apple.CoreSimulator.SimRuntime.iOS-14-4": [
+                { "udid": "A", "name": "iPhone 12", "state": "Shutdown", "isAvailable": true },
+                { "udid": "B", "name": "iPhone 8", "state": "Booted", "isAvailable": true },
+                { "udid": "C", "name": "iPhone X", "state": "Shutdown", "isAvailable": false }
+            ],
+            "iOS 12.4": [
+                { "udid": "D", "name": "iPhone 12", "state": "Booted" }
+            ],
+            "com.apple.CoreSimulator.SimRuntime.watchOS-7-2": [
+                { "udid": "E", "name": "Apple Watch", "state": "Shutdown", "isAvailable": true }
+            ],
+            "tvOS 14.3": [
+                { "udid": "F", "name": "Apple TV", "state": "Shutdown" }
+            ]
+        }
+    }"#;
+
+    fn simulator(udid: &str, name: &str, state: &str, version: (u32, u32)) -> Simulator {
+        Simulator {
+            udid: udid.to_owned(),
+            name: name.to_owned(),
+            state: state.to_owned(),
+            version,
+        }
+    }
+
+    #[test]
+    fn both_runtime_formats_are_parsed_and_other_platforms_are_left_out() {
+        assert_eq!(
+            parse_simulators(LIST.as_bytes()).unwrap(),
+            [
+                simulator("A", "iPhone 12", "Shutdown", (14, 4)),
+                simulator("B", "iPhone 8", "Booted", (14, 4)),
+                simulator("D", "iPhone 12", "Booted", (12, 4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn booted_simulators_are_found_first() {
+        let runner = RecordingRunner::new().with_output(LIST);
+        assert_eq!(
+            find_available_with(&runner, "iphone 12").unwrap(),
+            simulator("D", "iPhone 12", "Booted", (12, 4))
+        );
+        assert_eq!(
+            runner.command_lines(),
+            ["xcrun simctl list devices available --json"]
+        );
+        let runner = RecordingRunner::new().with_output(LIST);
+        assert!(matches!(
+            find_available_with(&runner, "iPhone 13"),
+            Err(FindError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn booted_simulators_arent_booted_again() {
+        let runner = RecordingRunner::new();
+        simulator("B", "iPhone 8", "Booted", (14, 4))
+            .boot(&runner)
+            .unwrap();
+        assert_eq!(
+            runner.command_lines(),
+            ["open -a Simulator --args -CurrentDeviceUDID B"]
+        );
+    }
+}
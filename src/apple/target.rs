@@ -1,20 +1,26 @@
 use super::{
-    config::{Config, Metadata},
+    config::{Config, Metadata, Platform, SigningStyle},
+    platform::PlatformKind,
     system_profile::{self, DeveloperTools},
 };
 use crate::{
-    env::{Env, ExplicitEnv as _},
+    config::hooks::{self, Stage},
+    env::{Env, EnvFileError, ExplicitEnv as _},
     opts::{self, ForceColor, NoiseLevel, Profile},
     target::TargetTrait,
     util::{
+        self,
         cli::{Report, Reportable},
-        CargoCommand,
+        output::{self, Event},
+        CargoCommand, CommandError, Invocation, ManifestPathError, Runner,
     },
 };
 use once_cell_regex::exports::once_cell::sync::OnceCell;
 use std::{
     collections::{BTreeMap, HashMap},
     ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
 };
 
 fn verbosity(noise_level: opts::NoiseLevel) -> Option<&'static str> {
@@ -57,14 +63,20 @@ impl Reportable for VersionCheckError {
 #[derive(Debug)]
 pub enum CheckError {
     VersionCheckFailed(VersionCheckError),
-    CargoCheckFailed(bossy::Error),
+    ManifestPathInvalid(ManifestPathError),
+    EnvFileFailed(EnvFileError),
+    CollectFailed(util::BuildAndCollectError),
+    CargoCheckFailed(util::CheckOutput),
 }
 
 impl Reportable for CheckError {
     fn report(&self) -> Report {
         match self {
             Self::VersionCheckFailed(err) => err.report(),
-            Self::CargoCheckFailed(err) => Report::error("Failed to run `cargo check`", err),
+            Self::ManifestPathInvalid(err) => Report::error("Failed to run `cargo check`", err),
+            Self::EnvFileFailed(err) => Report::error("Failed to run `cargo check`", err),
+            Self::CollectFailed(err) => Report::error("Failed to run `cargo check`", err),
+            Self::CargoCheckFailed(output) => Report::error("Code doesn't compile", output),
         }
     }
 }
@@ -72,49 +84,101 @@ impl Reportable for CheckError {
 #[derive(Debug)]
 pub enum CompileLibError {
     VersionCheckFailed(VersionCheckError),
-    CargoBuildFailed(bossy::Error),
+    ManifestPathInvalid(ManifestPathError),
+    EnvFileFailed(EnvFileError),
+    CargoBuildFailed(CommandError),
+    LibCopyFailed {
+        src: PathBuf,
+        dest: PathBuf,
+        cause: io::Error,
+    },
+    HookFailed(hooks::Error),
 }
 
 impl Reportable for CompileLibError {
     fn report(&self) -> Report {
         match self {
             Self::VersionCheckFailed(err) => err.report(),
-            Self::CargoBuildFailed(err) => Report::error("Failed to run `cargo build`", err),
+            Self::ManifestPathInvalid(err) => Report::error("Failed to run `cargo build`", err),
+            Self::EnvFileFailed(err) => Report::error("Failed to run `cargo build`", err),
+            Self::CargoBuildFailed(err) => err.report("Failed to run `cargo build`"),
+            Self::LibCopyFailed { src, dest, cause } => Report::error(
+                format!(
+                    "Failed to copy lib built with a custom profile from {:?} to {:?}",
+                    src, dest
+                ),
+                cause,
+            ),
+            Self::HookFailed(err) => err.report(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TestCommandError {
+    VersionCheckFailed(VersionCheckError),
+    ManifestPathInvalid(ManifestPathError),
+    EnvFileFailed(EnvFileError),
+}
+
+impl Reportable for TestCommandError {
+    fn report(&self) -> Report {
+        match self {
+            Self::VersionCheckFailed(err) => err.report(),
+            Self::ManifestPathInvalid(err) => Report::error("Failed to run `cargo test`", err),
+            Self::EnvFileFailed(err) => Report::error("Failed to run `cargo test`", err),
         }
     }
 }
 
 #[derive(Debug)]
-pub struct BuildError(bossy::Error);
+pub struct BuildError(CommandError);
 
 impl Reportable for BuildError {
     fn report(&self) -> Report {
-        Report::error("Failed to build via `xcodebuild`", &self.0)
+        self.0.report("Failed to build via `xcodebuild`")
     }
 }
 
 #[derive(Debug)]
-pub struct ArchiveError(bossy::Error);
+pub struct ArchiveError(CommandError);
 
 impl Reportable for ArchiveError {
     fn report(&self) -> Report {
-        Report::error("Failed to archive via `xcodebuild`", &self.0)
+        self.0.report("Failed to archive via `xcodebuild`")
     }
 }
 
 #[derive(Debug)]
-pub struct ExportError(bossy::Error);
+pub struct ExportError(CommandError);
 
 impl Reportable for ExportError {
     fn report(&self) -> Report {
-        Report::error("Failed to export archive via `xcodebuild`", &self.0)
+        self.0.report("Failed to export archive via `xcodebuild`")
     }
 }
 
+// `xcodebuild` only gets to be loud if we're being pedantic.
+fn xcodebuild_failed(err: CommandError, noise_level: NoiseLevel) -> CommandError {
+    err.with_verbose_hint(noise_level, NoiseLevel::FranklyQuitePedantic)
+}
+
+// Xcode is only allowed to fiddle with provisioning profiles when it's
+// managing them itself.
+fn signing_args(config: &Config) -> Vec<String> {
+    let signing = config.signing();
+    let mut args = signing.build_settings();
+    if signing.style() == SigningStyle::Automatic {
+        args.push("-allowProvisioningUpdates".to_owned());
+    }
+    args
+}
+
 #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
 pub struct Target<'a> {
     pub triple: &'a str,
     pub arch: &'a str,
+    platform: PlatformKind,
     alias: Option<&'a str>,
     min_xcode_version: Option<((u32, u32), &'static str)>,
 }
@@ -122,35 +186,9 @@ pub struct Target<'a> {
 impl<'a> TargetTrait<'a> for Target<'a> {
     const DEFAULT_KEY: &'static str = "aarch64";
 
+    /// Just the iOS targets; see [`Target::all_for`] for the other platforms.
     fn all() -> &'a BTreeMap<&'a str, Self> {
-        static TARGETS: OnceCell<BTreeMap<&'static str, Target<'static>>> = OnceCell::new();
-        TARGETS.get_or_init(|| {
-            let mut targets = BTreeMap::new();
-            targets.insert(
-                "aarch64",
-                Target {
-                    triple: "aarch64-apple-ios",
-                    arch: "arm64",
-                    alias: Some("arm64e"),
-                    min_xcode_version: None,
-                },
-            );
-            targets.insert(
-                "x86_64",
-                Target {
-                    triple: "x86_64-apple-ios",
-                    arch: "x86_64",
-                    alias: None,
-                    // Simulator only supports Metal as of Xcode 11.0:
-                    // https://developer.apple.com/documentation/metal/developing_metal_apps_that_run_in_simulator?language=objc
-                    // While this doesn't matter if you aren't using Metal,
-                    // it should be fine to be opinionated about this given
-                    // OpenGL's deprecation.
-                    min_xcode_version: Some(((11, 0), "iOS Simulator doesn't support Metal until")),
-                },
-            );
-            targets
-        })
+        Self::all_for(PlatformKind::Ios)
     }
 
     fn triple(&'a self) -> &'a str {
@@ -163,22 +201,127 @@ impl<'a> TargetTrait<'a> for Target<'a> {
 }
 
 impl<'a> Target<'a> {
-    // TODO: Make this cleaner
-    pub fn macos() -> Self {
+    fn new(platform: PlatformKind, triple: &'a str, arch: &'a str) -> Self {
         Self {
-            triple: "x86_64-apple-darwin",
-            arch: "x86_64",
+            triple,
+            arch,
+            platform,
             alias: None,
             min_xcode_version: None,
         }
     }
 
-    pub fn is_macos(&self) -> bool {
-        *self == Self::macos()
+    /// The targets for `platform`, keyed by the names used on the command
+    /// line.
+    pub fn all_for(platform: PlatformKind) -> &'a BTreeMap<&'a str, Self> {
+        static TARGETS: OnceCell<BTreeMap<PlatformKind, BTreeMap<&'static str, Target<'static>>>> =
+            OnceCell::new();
+        TARGETS
+            .get_or_init(|| {
+                let mut targets = BTreeMap::new();
+                let mut ios = BTreeMap::new();
+                ios.insert(
+                    "aarch64",
+                    Target {
+                        alias: Some("arm64e"),
+                        ..Target::new(PlatformKind::Ios, "aarch64-apple-ios", "arm64")
+                    },
+                );
+                ios.insert(
+                    "x86_64",
+                    Target {
+                        // Simulator only supports Metal as of Xcode 11.0:
+                        // https://developer.apple.com/documentation/metal/developing_metal_apps_that_run_in_simulator?language=objc
+                        // While this doesn't matter if you aren't using Metal,
+                        // it should be fine to be opinionated about this given
+                        // OpenGL's deprecation.
+                        min_xcode_version: Some((
+                            (11, 0),
+                            "iOS Simulator doesn't support Metal until",
+                        )),
+                        ..Target::new(PlatformKind::Ios, "x86_64-apple-ios", "x86_64")
+                    },
+                );
+                targets.insert(PlatformKind::Ios, ios);
+                for (platform, platform_targets) in &[
+                    (
+                        PlatformKind::Macos,
+                        &[("x86_64", "x86_64-apple-darwin", "x86_64")][..],
+                    ),
+                    (
+                        PlatformKind::Tvos,
+                        &[
+                            ("aarch64", "aarch64-apple-tvos", "arm64"),
+                            ("x86_64", "x86_64-apple-tvos", "x86_64"),
+                        ][..],
+                    ),
+                    (
+                        PlatformKind::Watchos,
+                        &[
+                            ("arm64_32", "arm64_32-apple-watchos", "arm64_32"),
+                            ("armv7k", "armv7k-apple-watchos", "armv7k"),
+                            ("x86_64", "x86_64-apple-watchos-sim", "x86_64"),
+                        ][..],
+                    ),
+                    (
+                        PlatformKind::Catalyst,
+                        &[
+                            ("aarch64", "aarch64-apple-ios-macabi", "arm64"),
+                            ("x86_64", "x86_64-apple-ios-macabi", "x86_64"),
+                        ][..],
+                    ),
+                ] {
+                    targets.insert(
+                        *platform,
+                        platform_targets
+                            .iter()
+                            .map(|(name, triple, arch)| {
+                                (*name, Target::new(*platform, triple, arch))
+                            })
+                            .collect(),
+                    );
+                }
+                targets
+            })
+            .get(&platform)
+            .expect("developer error: platform had no targets")
+    }
+
+    /// The target used when none are specified for `platform`.
+    pub fn default_key_for(platform: PlatformKind) -> &'static str {
+        match platform {
+            PlatformKind::Ios | PlatformKind::Tvos => Self::DEFAULT_KEY,
+            PlatformKind::Macos | PlatformKind::Catalyst => "x86_64",
+            PlatformKind::Watchos => "arm64_32",
+        }
+    }
+
+    /// Every target name used by any platform, for showing in `--help`.
+    pub fn all_names() -> &'static [&'static str] {
+        static NAMES: OnceCell<Vec<&'static str>> = OnceCell::new();
+        NAMES.get_or_init(|| {
+            let mut names = PlatformKind::ALL
+                .iter()
+                .flat_map(|platform| Target::all_for(*platform).keys().copied())
+                .collect::<Vec<_>>();
+            names.sort();
+            names.dedup();
+            names
+        })
+    }
+
+    pub fn macos() -> Self {
+        *Self::all_for(PlatformKind::Macos)
+            .get("x86_64")
+            .expect("developer error: no macOS target")
     }
 
     pub fn for_arch(arch: &str) -> Option<&'a Self> {
-        Self::all()
+        Self::for_arch_in(PlatformKind::Ios, arch)
+    }
+
+    pub fn for_arch_in(platform: PlatformKind, arch: &str) -> Option<&'a Self> {
+        Self::all_for(platform)
             .values()
             .find(|target| target.arch == arch || target.alias == Some(arch))
     }
@@ -201,27 +344,72 @@ impl<'a> Target<'a> {
             .unwrap_or_else(|| Ok(()))
     }
 
+    fn platform_metadata<'b>(&self, metadata: &'b Metadata) -> &'b Platform {
+        match self.platform {
+            PlatformKind::Ios | PlatformKind::Catalyst => metadata.ios(),
+            PlatformKind::Macos => metadata.macos(),
+            PlatformKind::Tvos => metadata.tvos(),
+            PlatformKind::Watchos => metadata.watchos(),
+        }
+    }
+
+    fn build_std_arg(&self) -> Option<&'static str> {
+        if self.platform.needs_build_std() {
+            Some("-Zbuild-std")
+        } else {
+            None
+        }
+    }
+
+    // Xcode picks the SDK from the scheme, but Catalyst builds need to be
+    // asked for explicitly.
+    fn destination_args(&self, generic: bool) -> Vec<&'static str> {
+        match (self.platform, generic) {
+            (PlatformKind::Catalyst, false) => {
+                vec!["-destination", "platform=macOS,variant=Mac Catalyst"]
+            }
+            (PlatformKind::Catalyst, true) => {
+                vec![
+                    "-destination",
+                    "generic/platform=macOS,variant=Mac Catalyst",
+                ]
+            }
+            (platform, true) => vec!["-sdk", platform.sdk()],
+            (_, false) => Vec::new(),
+        }
+    }
+
     fn cargo(
         &'a self,
         config: &'a Config,
         metadata: &'a Metadata,
         subcommand: &'a str,
     ) -> Result<CargoCommand<'a>, VersionCheckError> {
-        let metadata = if self.is_macos() {
-            metadata.macos()
-        } else {
-            metadata.ios()
-        };
+        let metadata = self.platform_metadata(metadata);
         self.min_xcode_version_satisfied().map(|()| {
             CargoCommand::new(subcommand)
                 .with_package(Some(config.app().name()))
                 .with_manifest_path(Some(config.app().manifest_path()))
-                .with_target(Some(&self.triple))
+                .with_target_reconciled(Some(&self.triple), config.app().root_dir())
                 .with_no_default_features(metadata.no_default_features())
                 .with_features(metadata.features())
         })
     }
 
+    fn env_file_vars(&self, config: &Config) -> Result<Vec<(String, String)>, EnvFileError> {
+        crate::env::load_target_env_files(config.app().root_dir(), self.triple)
+    }
+
+    // rustc reads these directly, since Xcode doesn't pass its build settings
+    // along to us.
+    fn deployment_target_env<'c>(&self, config: &'c Config) -> Option<(&'static str, &'c str)> {
+        self.platform.deployment_target_var().and_then(|var| {
+            config
+                .deployment_target(self.platform)
+                .map(|deployment_target| (var, deployment_target))
+        })
+    }
+
     pub fn check(
         &self,
         config: &Config,
@@ -229,13 +417,48 @@ impl<'a> Target<'a> {
         env: &Env,
         noise_level: NoiseLevel,
     ) -> Result<(), CheckError> {
-        self.cargo(config, metadata, "check")
+        let command = self
+            .cargo(config, metadata, "check")
             .map_err(CheckError::VersionCheckFailed)?
             .with_verbose(noise_level.pedantic())
             .into_command_pure(env)
-            .run_and_wait()
-            .map_err(CheckError::CargoCheckFailed)?;
-        Ok(())
+            .map_err(CheckError::ManifestPathInvalid)?
+            .with_env_vars(
+                self.env_file_vars(config)
+                    .map_err(CheckError::EnvFileFailed)?,
+            )
+            .with_env_vars(self.deployment_target_env(config))
+            .with_args(self.build_std_arg());
+        let output = util::check_and_collect(command).map_err(CheckError::CollectFailed)?;
+        if output.success {
+            Ok(())
+        } else {
+            Err(CheckError::CargoCheckFailed(output))
+        }
+    }
+
+    /// A `cargo test --no-run` for this target, which builds the test
+    /// harnesses without trying to run them on the host.
+    pub fn test_command(
+        &self,
+        config: &Config,
+        metadata: &Metadata,
+        env: &Env,
+        noise_level: NoiseLevel,
+    ) -> Result<Invocation, TestCommandError> {
+        Ok(self
+            .cargo(config, metadata, "test")
+            .map_err(TestCommandError::VersionCheckFailed)?
+            .with_verbose(noise_level.pedantic())
+            .into_command_pure(env)
+            .map_err(TestCommandError::ManifestPathInvalid)?
+            .with_env_vars(
+                self.env_file_vars(config)
+                    .map_err(TestCommandError::EnvFileFailed)?,
+            )
+            .with_env_vars(self.deployment_target_env(config))
+            .with_args(self.build_std_arg())
+            .with_arg("--no-run"))
     }
 
     // NOTE: it's up to Xcode to pass the verbose flag here, so even when
@@ -253,16 +476,57 @@ impl<'a> Target<'a> {
     ) -> Result<(), CompileLibError> {
         // Force color when running from CLI
         let color = if force_color.yes() { "always" } else { "auto" };
-        self.cargo(config, metadata, "build")
+        let cargo = self.platform_metadata(metadata).cargo();
+        let deployment_target = self.deployment_target_env(config);
+        let hooks = config.app().hooks();
+        let context =
+            hooks::Context::new(self.platform.name(), Some(self.triple), profile.as_str());
+        hooks
+            .run(Stage::PreBuild, &context)
+            .map_err(CompileLibError::HookFailed)?;
+        let command = self
+            .cargo(config, metadata, "build")
             .map_err(CompileLibError::VersionCheckFailed)?
-            .with_verbose(noise_level.pedantic())
-            .with_release(profile.release())
+            .with_verbose(noise_level.pedantic());
+        let command = cargo
+            .apply(command, profile)
             .into_command_pure(env)
+            .map_err(CompileLibError::ManifestPathInvalid)?
+            .with_env_vars(
+                self.env_file_vars(config)
+                    .map_err(CompileLibError::EnvFileFailed)?,
+            )
             .with_env_vars(cc_env)
-            .with_args(&["--color", color])
-            .run_and_wait()
-            .map_err(CompileLibError::CargoBuildFailed)?;
-        Ok(())
+            .with_env_vars(deployment_target)
+            .with_args(self.build_std_arg())
+            .with_args(&["--color", color]);
+        Runner::global().stream(&command).map_err(|err| {
+            CompileLibError::CargoBuildFailed(
+                err.with_verbose_hint(noise_level, NoiseLevel::FranklyQuitePedantic),
+            )
+        })?;
+        // The Xcode project looks for the lib in the dir matching the build
+        // configuration, so a lib built with a custom profile needs to be
+        // copied over to where it's expected.
+        let profile_dir = cargo.profile_dir(profile);
+        let lib_name = format!("lib{}.a", util::lib_name_from_crate(config.app().name()));
+        let target_dir = config.app().prefix_path(format!("target/{}", self.triple));
+        let dest_dir = target_dir.join(profile.as_str());
+        let dest = dest_dir.join(&lib_name);
+        if profile_dir != profile.as_str() {
+            let src = target_dir.join(profile_dir).join(&lib_name);
+            log::info!("copying lib {:?} to {:?}", src, dest);
+            util::create_dir_all(&dest_dir)
+                .and_then(|()| fs::copy(&src, &dest))
+                .map_err(|cause| CompileLibError::LibCopyFailed {
+                    src,
+                    dest: dest.clone(),
+                    cause,
+                })?;
+        }
+        hooks
+            .run(Stage::PostBuild, &context.with_artifact(dest))
+            .map_err(CompileLibError::HookFailed)
     }
 
     pub fn build(
@@ -273,19 +537,53 @@ impl<'a> Target<'a> {
         profile: opts::Profile,
     ) -> Result<(), BuildError> {
         let configuration = profile.as_str();
-        bossy::Command::pure("xcodebuild")
+        let command = Invocation::pure("xcodebuild")
+            .with_env_vars(env.explicit_env())
+            .with_env_var("FORCE_COLOR", "--force-color")
+            .with_args(verbosity(noise_level))
+            .with_args(&["-scheme", &config.scheme_for(self.platform)])
+            .with_arg("-workspace")
+            .with_arg(&config.workspace_path())
+            .with_args(self.destination_args(false))
+            .with_args(&["-configuration", configuration])
+            .with_args(&["-arch", self.arch])
+            .with_args(signing_args(config))
+            .with_arg("build");
+        Runner::global()
+            .stream(&command)
+            .map_err(|err| BuildError(xcodebuild_failed(err, noise_level)))?;
+        Ok(())
+    }
+
+    /// Builds for the simulator with the given UDID, putting the `.app` at
+    /// [`Config::simulator_app_path`].
+    pub fn build_for_simulator(
+        &self,
+        config: &Config,
+        env: &Env,
+        noise_level: opts::NoiseLevel,
+        profile: opts::Profile,
+        udid: &str,
+    ) -> Result<(), BuildError> {
+        let configuration = profile.as_str();
+        let command = Invocation::pure("xcodebuild")
             .with_env_vars(env.explicit_env())
             .with_env_var("FORCE_COLOR", "--force-color")
             .with_args(verbosity(noise_level))
             .with_args(&["-scheme", &config.scheme()])
             .with_arg("-workspace")
             .with_arg(&config.workspace_path())
+            .with_args(&["-sdk", "iphonesimulator"])
             .with_args(&["-configuration", configuration])
             .with_args(&["-arch", self.arch])
-            .with_arg("-allowProvisioningUpdates")
-            .with_arg("build")
-            .run_and_wait()
-            .map_err(BuildError)?;
+            .with_arg("-destination")
+            .with_arg(format!("id={}", udid))
+            .with_arg("-derivedDataPath")
+            .with_arg(config.simulator_derived_data_dir())
+            .with_arg("build");
+        Runner::global()
+            .stream(&command)
+            .map_err(|err| BuildError(xcodebuild_failed(err, noise_level)))?;
         Ok(())
     }
 
@@ -297,47 +595,59 @@ impl<'a> Target<'a> {
         profile: opts::Profile,
     ) -> Result<(), ArchiveError> {
         let configuration = profile.as_str();
-        let archive_path = config.archive_dir().join(&config.scheme());
-        bossy::Command::pure("xcodebuild")
+        let archive_path = config
+            .archive_dir()
+            .join(&config.archive_name(self.platform));
+        let command = Invocation::pure("xcodebuild")
             .with_env_vars(env.explicit_env())
             .with_args(verbosity(noise_level))
-            .with_args(&["-scheme", &config.scheme()])
+            .with_args(&["-scheme", &config.scheme_for(self.platform)])
             .with_arg("-workspace")
             .with_arg(&config.workspace_path())
-            .with_args(&["-sdk", "iphoneos"])
+            .with_args(self.destination_args(true))
             .with_args(&["-configuration", configuration])
             .with_args(&["-arch", self.arch])
-            .with_arg("-allowProvisioningUpdates")
+            .with_args(signing_args(config))
             .with_arg("archive")
             .with_arg("-archivePath")
-            .with_arg(&archive_path)
-            .run_and_wait()
-            .map_err(ArchiveError)?;
+            .with_arg(&archive_path);
+        Runner::global()
+            .stream(&command)
+            .map_err(|err| ArchiveError(xcodebuild_failed(err, noise_level)))?;
+        output::emit(&Event::Artifact {
+            kind: "xcarchive",
+            path: &config
+                .archive_dir()
+                .join(format!("{}.xcarchive", config.archive_name(self.platform))),
+        });
         Ok(())
     }
 
+    /// Exports the archive using the options in `export_plist`.
     pub fn export(
         &self,
         config: &Config,
         env: &Env,
         noise_level: opts::NoiseLevel,
+        export_plist: &Path,
     ) -> Result<(), ExportError> {
         // Super fun discrepancy in expectation of `-archivePath` value
         let archive_path = config
             .archive_dir()
-            .join(&format!("{}.xcarchive", config.scheme()));
-        bossy::Command::pure("xcodebuild")
+            .join(&format!("{}.xcarchive", config.archive_name(self.platform)));
+        let command = Invocation::pure("xcodebuild")
             .with_env_vars(env.explicit_env())
             .with_args(verbosity(noise_level))
             .with_arg("-exportArchive")
             .with_arg("-archivePath")
             .with_arg(&archive_path)
             .with_arg("-exportOptionsPlist")
-            .with_arg(&config.export_plist_path())
+            .with_arg(export_plist)
             .with_arg("-exportPath")
-            .with_arg(&config.export_dir())
-            .run_and_wait()
-            .map_err(ExportError)?;
+            .with_arg(&config.export_dir());
+        Runner::global()
+            .stream(&command)
+            .map_err(|err| ExportError(xcodebuild_failed(err, noise_level)))?;
         Ok(())
     }
 }
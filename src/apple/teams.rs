@@ -6,6 +6,8 @@ use openssl::{
 use std::{
     collections::BTreeSet,
     fmt::{self, Display},
+    fs, io,
+    path::Path,
 };
 
 pub fn get_pem_list(name_substr: &str) -> bossy::Result<bossy::Output> {
@@ -82,3 +84,59 @@ pub fn find_development_teams() -> Result<Vec<Team>, Error> {
     }
     Ok(teams.into_iter().collect())
 }
+
+/// Gets the `DEVELOPMENT_TEAM` build setting from pbxproj contents. Empty
+/// values (which is what Xcode writes when you clear the setting) count as not
+/// being set.
+pub fn parse_team_id(pbxproj: &str) -> Option<String> {
+    once_cell_regex::regex!(r#"DEVELOPMENT_TEAM = "?([^";\s]*)"?;"#)
+        .captures_iter(pbxproj)
+        .map(|caps| caps[1].to_owned())
+        .find(|team_id| !team_id.is_empty())
+}
+
+/// Gets the development team configured in the Xcode project in
+/// `project_dir`, if there's a project and it has one.
+pub fn ios_team_id(project_dir: &Path) -> io::Result<Option<String>> {
+    for entry in fs::read_dir(project_dir)? {
+        let path = entry?.path();
+        if path.extension() == Some("xcodeproj".as_ref()) {
+            let pbxproj = fs::read_to_string(path.join("project.pbxproj"))?;
+            return Ok(parse_team_id(&pbxproj));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_first_non_empty_team_id_is_used() {
+        let pbxproj = r#"
+            DEVELOPMENT_TEAM = "";
+            DEVELOPMENT_TEAM = ABCDE12345;
+            DEVELOPMENT_TEAM = "FGHIJ67890";
+        "#;
+        assert_eq!(parse_team_id(pbxproj), Some("ABCDE12345".to_owned()));
+        assert_eq!(parse_team_id(r#"DEVELOPMENT_TEAM = "";"#), None);
+    }
+
+    #[test]
+    fn team_ids_are_read_from_the_xcode_project() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(ios_team_id(dir.path()).unwrap(), None);
+        let project = dir.path().join("app.xcodeproj");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(
+            project.join("project.pbxproj"),
+            "DEVELOPMENT_TEAM = \"FGHIJ67890\";",
+        )
+        .unwrap();
+        assert_eq!(
+            ios_team_id(dir.path()).unwrap(),
+            Some("FGHIJ67890".to_owned())
+        );
+    }
+}
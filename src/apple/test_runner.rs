@@ -0,0 +1,102 @@
+//! Runs test harnesses on the simulator, for `cargo apple test`.
+
+use super::{
+    config::{Config, Metadata},
+    device,
+    simctl::Simulator,
+    target::TestCommandError,
+};
+use crate::{
+    env::Env,
+    opts::NoiseLevel,
+    util::{
+        self,
+        cli::{Report, Reportable},
+        CommandError, Runner,
+    },
+};
+
+#[derive(Debug)]
+pub enum TestError {
+    CommandInvalid(TestCommandError),
+    CollectFailed(util::BuildAndCollectError),
+    CompileFailed,
+    NoTests,
+    BootFailed(CommandError),
+    RunFailed { name: String, cause: CommandError },
+    TestsFailed { failed: Vec<String> },
+}
+
+impl Reportable for TestError {
+    fn report(&self) -> Report {
+        match self {
+            Self::CommandInvalid(err) => err.report(),
+            Self::CollectFailed(err) => Report::error("Failed to build tests", err),
+            Self::CompileFailed => Report::error(
+                "Failed to build tests",
+                "`cargo test --no-run` failed; see above for the errors.",
+            ),
+            Self::NoTests => Report::error(
+                "Failed to build tests",
+                "`cargo test --no-run` succeeded, but didn't build any test harnesses.",
+            ),
+            Self::BootFailed(err) => Report::error("Failed to boot simulator", err),
+            Self::RunFailed { name, cause } => {
+                Report::error(format!("Failed to run {:?} on simulator", name), cause)
+            }
+            Self::TestsFailed { failed } => Report::error(
+                "Tests failed on simulator",
+                format!("These test harnesses exited unsuccessfully: {:?}", failed),
+            ),
+        }
+    }
+}
+
+/// Builds the app's tests for the simulator, and then runs each test harness
+/// on `simulator` in turn, passing along `args`. Output is streamed as it
+/// comes, and every harness gets run even if an earlier one fails; the error
+/// says which ones did.
+pub fn test(
+    config: &Config,
+    metadata: &Metadata,
+    env: &Env,
+    noise_level: NoiseLevel,
+    simulator: &Simulator,
+    args: &[String],
+) -> Result<(), TestError> {
+    let target = device::simulator_target();
+    println!("Building tests for {}...", target.triple);
+    let command = target
+        .test_command(config, metadata, env, noise_level)
+        .map_err(TestError::CommandInvalid)?;
+    let output = util::collect_build_output(command).map_err(TestError::CollectFailed)?;
+    if !output.success {
+        return Err(TestError::CompileFailed);
+    }
+    if output.tests.is_empty() {
+        return Err(TestError::NoTests);
+    }
+    println!("Booting simulator...");
+    simulator
+        .boot(Runner::global())
+        .map_err(TestError::BootFailed)?;
+    let mut failed = Vec::new();
+    for test in &output.tests {
+        let name = test
+            .file_name()
+            .expect("developer error: test harness path has no file name")
+            .to_string_lossy()
+            .into_owned();
+        println!("Running {} on {}...", name, simulator);
+        match simulator.spawn(Runner::global(), test, args) {
+            Ok(()) => (),
+            Err(err) if err.status().is_some() => failed.push(name),
+            Err(cause) => return Err(TestError::RunFailed { name, cause }),
+        }
+    }
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(TestError::TestsFailed { failed })
+    }
+}
@@ -0,0 +1,49 @@
+use crate::util;
+use std::{io, path::Path};
+
+// In `.xcconfig` files, `//` starts a comment even in the middle of a value,
+// which would silently truncate things like URLs. Splitting the slashes with
+// an empty build setting reference gets around that. Other build setting
+// references, like `$(inherited)`, are left alone so they still expand.
+fn escape_value(value: &str) -> String {
+    value.replace("//", "/$()/")
+}
+
+/// Writes `settings` to an `.xcconfig` at `path` as `KEY = VALUE` lines, in the
+/// order given.
+pub fn write_xcconfig(path: &Path, settings: &[(String, String)]) -> io::Result<()> {
+    let mut contents = String::new();
+    for (key, value) in settings {
+        contents.push_str(&format!("{} = {}\n", key, escape_value(value)));
+    }
+    util::write_str_if_changed(path, contents).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn settings_are_written_in_order_with_comment_markers_escaped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cargo-mobile.xcconfig");
+        let settings = [
+            ("PRODUCT_BUNDLE_IDENTIFIER", "com.example.app"),
+            ("HEADER_SEARCH_PATHS", "$(inherited) ../include"),
+            ("DOCS_URL", "https://example.com/docs"),
+        ]
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect::<Vec<_>>();
+        write_xcconfig(&path, &settings).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "\
+PRODUCT_BUNDLE_IDENTIFIER = com.example.app
+HEADER_SEARCH_PATHS = $(inherited) ../include
+DOCS_URL = https:/$()/example.com/docs
+"
+        );
+    }
+}
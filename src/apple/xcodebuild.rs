@@ -0,0 +1,201 @@
+use crate::util::{CommandError, Invocation, Runner};
+use once_cell_regex::{regex, regex_multi_line};
+use std::{
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    str,
+};
+
+#[derive(Debug)]
+pub enum XcodeBuildError {
+    ResultBundleRemovalFailed { path: PathBuf, cause: io::Error },
+    CommandFailed(CommandError),
+    InvalidUtf8(str::Utf8Error),
+}
+
+impl Display for XcodeBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ResultBundleRemovalFailed { path, cause } => write!(
+                f,
+                "Failed to remove old result bundle at {:?}: {}",
+                path, cause
+            ),
+            Self::CommandFailed(err) => write!(f, "{}", err),
+            Self::InvalidUtf8(err) => write!(f, "Build log contained invalid UTF-8: {}", err),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct XcodeBuildResult {
+    pub product_path: Option<PathBuf>,
+    pub result_bundle_path: PathBuf,
+    pub warning_count: usize,
+    pub error_count: usize,
+}
+
+/// Finds the built product and counts diagnostics in an `xcodebuild` log. The
+/// product path comes from the last `.app` that got touched or signed, since
+/// those are the final steps of building one.
+pub fn parse_build_log(log: &str, result_bundle_path: PathBuf) -> XcodeBuildResult {
+    let product_path = regex_multi_line!(r"^(?:Touch|CodeSign) (.+\.app)\s*(?:\(in target.*)?$")
+        .captures_iter(log)
+        .last()
+        .map(|caps| PathBuf::from(caps[1].trim().replace("\\ ", " ")));
+    let warning_count = regex!(r": warning: ").find_iter(log).count();
+    let error_count = regex!(r": error: ").find_iter(log).count();
+    XcodeBuildResult {
+        product_path,
+        result_bundle_path,
+        warning_count,
+        error_count,
+    }
+}
+
+/// Runs `xcodebuild` with `args` from `project_dir`, writing a result bundle to
+/// `build/cargo-mobile.xcresult` within it.
+pub fn xcodebuild(args: &[&str], project_dir: &Path) -> Result<XcodeBuildResult, XcodeBuildError> {
+    let result_bundle_path = project_dir.join("build/cargo-mobile.xcresult");
+    // `xcodebuild` refuses to overwrite an existing result bundle
+    if result_bundle_path.exists() {
+        fs::remove_dir_all(&result_bundle_path).map_err(|cause| {
+            XcodeBuildError::ResultBundleRemovalFailed {
+                path: result_bundle_path.clone(),
+                cause,
+            }
+        })?;
+    }
+    let output = Runner::global()
+        .capture(
+            &Invocation::impure("xcodebuild")
+                .with_args(args)
+                .with_arg("-resultBundlePath")
+                .with_arg(&result_bundle_path)
+                .with_current_dir(project_dir),
+        )
+        .map_err(XcodeBuildError::CommandFailed)?;
+    let log = output.stdout_str().map_err(XcodeBuildError::InvalidUtf8)?;
+    let result = parse_build_log(log, result_bundle_path);
+    log::info!("xcodebuild result: {:?}", result);
+    Ok(result)
+}
+
+#[derive(Debug)]
+pub enum IosSdksError {
+    CommandFailed(CommandError),
+    InvalidUtf8(str::Utf8Error),
+}
+
+impl Display for IosSdksError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CommandFailed(err) => write!(f, "Failed to list SDKs via `xcodebuild`: {}", err),
+            Self::InvalidUtf8(err) => write!(f, "SDK list contained invalid UTF-8: {}", err),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IosSdk {
+    /// What gets passed to `-sdk`, i.e. `iphoneos14.2`.
+    pub name: String,
+    pub version: (u32, u32),
+    pub is_simulator: bool,
+}
+
+/// Gets the iOS SDKs from `xcodebuild -showsdks` output, which looks like this:
+///
+/// ```text
+/// iOS SDKs:
+///     iOS 14.2                          -sdk iphoneos14.2
+///
+/// iOS Simulator SDKs:
+///     Simulator - iOS 14.2              -sdk iphonesimulator14.2
+/// ```
+///
+/// SDKs for other platforms are ignored.
+pub fn parse_ios_sdks(raw_list: &str) -> Vec<IosSdk> {
+    regex_multi_line!(r"-sdk ((iphoneos|iphonesimulator)(\d+)\.(\d+))\s*$")
+        .captures_iter(raw_list)
+        .filter_map(|caps| {
+            Some(IosSdk {
+                name: caps[1].to_owned(),
+                version: (caps[3].parse().ok()?, caps[4].parse().ok()?),
+                is_simulator: &caps[2] == "iphonesimulator",
+            })
+        })
+        .collect()
+}
+
+/// Lists the installed iOS SDKs, both device and simulator.
+pub fn ios_sdks() -> Result<Vec<IosSdk>, IosSdksError> {
+    let output = Runner::global()
+        .capture(&Invocation::impure_parse("xcodebuild -showsdks"))
+        .map_err(IosSdksError::CommandFailed)?;
+    let raw_list = output.stdout_str().map_err(IosSdksError::InvalidUtf8)?;
+    Ok(parse_ios_sdks(raw_list))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_logs_give_the_last_product_and_diagnostic_counts() {
+        let log = r"
+CodeSign /build/Debug-iphoneos/Helper.app (in target 'Helper' from project 'app')
+src/lib.rs:3:9: warning: unused variable: `x`
+main.m:10:5: warning: implicit declaration
+Touch /build/Debug-iphoneos/My\ App.app (in target 'app_iOS' from project 'app')
+main.m:12:1: error: expected ';'
+";
+        let bundle = PathBuf::from("build/cargo-mobile.xcresult");
+        assert_eq!(
+            parse_build_log(log, bundle.clone()),
+            XcodeBuildResult {
+                product_path: Some(PathBuf::from("/build/Debug-iphoneos/My App.app")),
+                result_bundle_path: bundle.clone(),
+                warning_count: 2,
+                error_count: 1,
+            }
+        );
+        assert_eq!(
+            parse_build_log("** BUILD FAILED **\n", bundle.clone()),
+            XcodeBuildResult {
+                result_bundle_path: bundle,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn only_ios_sdks_are_listed() {
+        let raw_list = "\
+iOS SDKs:
+\tiOS 14.2                      \t-sdk iphoneos14.2
+
+iOS Simulator SDKs:
+\tSimulator - iOS 14.2          \t-sdk iphonesimulator14.2
+
+macOS SDKs:
+\tmacOS 11.0                    \t-sdk macosx11.0
+";
+        assert_eq!(
+            parse_ios_sdks(raw_list),
+            vec![
+                IosSdk {
+                    name: "iphoneos14.2".to_owned(),
+                    version: (14, 2),
+                    is_simulator: false,
+                },
+                IosSdk {
+                    name: "iphonesimulator14.2".to_owned(),
+                    version: (14, 2),
+                    is_simulator: true,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,993 @@
+//! A built-in replacement for XcodeGen, which generates the same Xcode project
+//! that XcodeGen generates from our `project.yml`. It's built from the config
+//! rather than from `project.yml`, so edits to `project.yml` only take effect
+//! when XcodeGen is used.
+
+mod pbxproj;
+
+use self::pbxproj::{Dict, Objects, Value};
+use super::{
+    config::{Config, Metadata},
+    platform::PlatformKind,
+};
+use crate::util::{
+    self,
+    cli::{Report, Reportable},
+    xml,
+};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+static CONFIGURATIONS: &[(&str, bool)] = &[("debug", true), ("release", false)];
+
+static IOS_FRAMEWORKS: &[&str] = &[
+    "CoreGraphics",
+    "Metal",
+    "MetalKit",
+    "QuartzCore",
+    "Security",
+    "UIKit",
+];
+
+static MACOS_FRAMEWORKS: &[&str] = &["Metal"];
+
+// Directories that Xcode treats as a single file.
+static PACKAGE_EXTENSIONS: &[&str] = &["bundle", "framework", "xcassets", "xcdatamodeld"];
+
+// rustc doesn't support arm64e yet
+static IOS_ARCHS: &[&str] = &["arm64", "x86_64"];
+
+static TVOS_ARCHS: &[&str] = &["arm64", "x86_64"];
+
+static WATCHOS_ARCHS: &[&str] = &["arm64_32", "armv7k", "x86_64"];
+
+static XCODE_SCRIPT_ARGS: &str = "xcode-script -v --platform ${PLATFORM_DISPLAY_NAME:?} --sdk-root ${SDKROOT:?} --configuration ${CONFIGURATION:?} --effective-platform-name=${EFFECTIVE_PLATFORM_NAME} ${FORCE_COLOR} ${ARCHS:?}";
+
+#[derive(Debug)]
+pub enum Error {
+    SourcesReadFailed { path: PathBuf, cause: io::Error },
+    WriteFailed { path: PathBuf, cause: io::Error },
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::SourcesReadFailed { path, cause } => {
+                Report::error(format!("Failed to read sources in {:?}", path), cause)
+            }
+            Self::WriteFailed { path, cause } => {
+                Report::error(format!("Failed to write {:?}", path), cause)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Phase {
+    Sources,
+    Resources,
+}
+
+fn file_type(path: &Path) -> (&'static str, Option<Phase>) {
+    match path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+    {
+        "c" => ("sourcecode.c.c", Some(Phase::Sources)),
+        "m" => ("sourcecode.c.objc", Some(Phase::Sources)),
+        "mm" => ("sourcecode.cpp.objcpp", Some(Phase::Sources)),
+        "cc" | "cpp" | "cxx" => ("sourcecode.cpp.cpp", Some(Phase::Sources)),
+        "swift" => ("sourcecode.swift", Some(Phase::Sources)),
+        "metal" => ("sourcecode.metal", Some(Phase::Sources)),
+        "h" => ("sourcecode.c.h", None),
+        "hh" | "hpp" => ("sourcecode.cpp.h", None),
+        "storyboard" => ("file.storyboard", Some(Phase::Resources)),
+        "xib" => ("file.xib", Some(Phase::Resources)),
+        "xcassets" => ("folder.assetcatalog", Some(Phase::Resources)),
+        "strings" => ("text.plist.strings", Some(Phase::Resources)),
+        "plist" => ("text.plist.xml", None),
+        "png" => ("image.png", Some(Phase::Resources)),
+        "jpg" | "jpeg" => ("image.jpeg", Some(Phase::Resources)),
+        "json" => ("text.json", Some(Phase::Resources)),
+        "bundle" => ("wrapper.plug-in", Some(Phase::Resources)),
+        "framework" => ("wrapper.framework", None),
+        "xcdatamodeld" => ("wrapper.xcdatamodeld", Some(Phase::Sources)),
+        _ => ("text", None),
+    }
+}
+
+fn is_group(path: &Path) -> bool {
+    path.is_dir()
+        && !path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map_or(false, |ext| PACKAGE_EXTENSIONS.contains(&ext))
+}
+
+// Adds the group for `rel` (relative to the project dir) and everything in it,
+// collecting the files that belong in a build phase along the way.
+fn add_group(
+    objects: &mut Objects,
+    project_dir: &Path,
+    rel: &Path,
+    files: &mut Vec<(String, Phase)>,
+) -> Result<String, Error> {
+    let dir = project_dir.join(rel);
+    let read_failed = |cause| Error::SourcesReadFailed {
+        path: dir.clone(),
+        cause,
+    };
+    let mut entries = fs::read_dir(&dir)
+        .map_err(read_failed)?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(read_failed)?;
+    entries.sort();
+    let mut children = Vec::new();
+    for name in entries {
+        // Dotfiles are things like `.DS_Store`, which Xcode shouldn't see.
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let child = rel.join(&name);
+        if is_group(&project_dir.join(&child)) {
+            children.push(add_group(objects, project_dir, &child, files)?);
+        } else {
+            let (file_type, phase) = file_type(&child);
+            let key = format!("file:{}", child.display());
+            children.push(
+                objects.add(
+                    "PBXFileReference",
+                    &key,
+                    Dict::new()
+                        .with("lastKnownFileType", file_type)
+                        .with("path", name.to_string_lossy().into_owned())
+                        .with("sourceTree", "<group>"),
+                ),
+            );
+            if let Some(phase) = phase {
+                files.push((key, phase));
+            }
+        }
+    }
+    Ok(objects.add(
+        "PBXGroup",
+        &format!("group:{}", rel.display()),
+        Dict::new()
+            .with("children", ids(children))
+            .with(
+                "path",
+                rel.file_name()
+                    .expect("developer error: group had no file name")
+                    .to_string_lossy()
+                    .into_owned(),
+            )
+            .with("sourceTree", "<group>"),
+    ))
+}
+
+fn folder_ref(objects: &mut Objects, path: &Path) -> String {
+    objects.add(
+        "PBXFileReference",
+        &format!("folder:{}", path.display()),
+        Dict::new()
+            .with("lastKnownFileType", "folder")
+            .with(
+                "name",
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| path.display().to_string()),
+            )
+            .with("path", path.display().to_string())
+            .with("sourceTree", "<group>"),
+    )
+}
+
+fn ids(items: impl IntoIterator<Item = String>) -> Value {
+    Value::Array(items.into_iter().map(Value::String).collect())
+}
+
+fn strings(items: &[&str]) -> Value {
+    Value::Array(items.iter().map(|item| Value::from(*item)).collect())
+}
+
+fn configuration_list(
+    objects: &mut Objects,
+    owner: &str,
+    settings: impl Fn(bool) -> Dict,
+) -> String {
+    let configurations = CONFIGURATIONS
+        .iter()
+        .map(|(name, debug)| {
+            objects.add(
+                "XCBuildConfiguration",
+                &format!("configuration:{}:{}", owner, name),
+                Dict::new()
+                    .with("buildSettings", settings(*debug))
+                    .with("name", *name),
+            )
+        })
+        .collect::<Vec<_>>();
+    objects.add(
+        "XCConfigurationList",
+        &format!("configuration-list:{}", owner),
+        Dict::new()
+            .with("buildConfigurations", ids(configurations))
+            .with("defaultConfigurationIsVisible", "0")
+            .with("defaultConfigurationName", "release"),
+    )
+}
+
+// The same base settings XcodeGen gives every project.
+fn project_settings(debug: bool) -> Dict {
+    let settings = Dict::new()
+        .with("ALWAYS_SEARCH_USER_PATHS", "NO")
+        .with("CLANG_ANALYZER_NONNULL", "YES")
+        .with("CLANG_CXX_LANGUAGE_STANDARD", "gnu++14")
+        .with("CLANG_CXX_LIBRARY", "libc++")
+        .with("CLANG_ENABLE_MODULES", "YES")
+        .with("CLANG_ENABLE_OBJC_ARC", "YES")
+        .with("CLANG_ENABLE_OBJC_WEAK", "YES")
+        .with("CLANG_WARN_BOOL_CONVERSION", "YES")
+        .with("CLANG_WARN_DOCUMENTATION_COMMENTS", "YES")
+        .with("CLANG_WARN_EMPTY_BODY", "YES")
+        .with("CLANG_WARN_INFINITE_RECURSION", "YES")
+        .with("CLANG_WARN_UNREACHABLE_CODE", "YES")
+        .with("CLANG_WARN__DUPLICATE_METHOD_MATCH", "YES")
+        .with("COPY_PHASE_STRIP", "NO")
+        .with("ENABLE_STRICT_OBJC_MSGSEND", "YES")
+        .with("GCC_C_LANGUAGE_STANDARD", "gnu11")
+        .with("GCC_NO_COMMON_BLOCKS", "YES")
+        .with("GCC_WARN_64_TO_32_BIT_CONVERSION", "YES")
+        .with("GCC_WARN_UNDECLARED_SELECTOR", "YES")
+        .with("GCC_WARN_UNINITIALIZED_AUTOS", "YES_AGGRESSIVE")
+        .with("GCC_WARN_UNUSED_FUNCTION", "YES")
+        .with("GCC_WARN_UNUSED_VARIABLE", "YES")
+        .with("MTL_FAST_MATH", "YES")
+        .with("PRODUCT_NAME", "$(TARGET_NAME)")
+        .with("SWIFT_VERSION", "5.0");
+    if debug {
+        settings
+            .with("DEBUG_INFORMATION_FORMAT", "dwarf")
+            .with("ENABLE_TESTABILITY", "YES")
+            .with("GCC_DYNAMIC_NO_PIC", "NO")
+            .with("GCC_OPTIMIZATION_LEVEL", "0")
+            .with(
+                "GCC_PREPROCESSOR_DEFINITIONS",
+                strings(&["$(inherited)", "DEBUG=1"]),
+            )
+            .with("MTL_ENABLE_DEBUG_INFO", "INCLUDE_SOURCE")
+            .with("ONLY_ACTIVE_ARCH", "YES")
+            .with("SWIFT_ACTIVE_COMPILATION_CONDITIONS", "DEBUG")
+            .with("SWIFT_OPTIMIZATION_LEVEL", "-Onone")
+    } else {
+        settings
+            .with("DEBUG_INFORMATION_FORMAT", "dwarf-with-dsym")
+            .with("ENABLE_NS_ASSERTIONS", "NO")
+            .with("MTL_ENABLE_DEBUG_INFO", "NO")
+            .with("SWIFT_COMPILATION_MODE", "wholemodule")
+            .with("SWIFT_OPTIMIZATION_LEVEL", "-O")
+            .with("VALIDATE_PRODUCT", "YES")
+    }
+}
+
+struct AppTarget<'a> {
+    platform: PlatformKind,
+    frameworks: Vec<&'a str>,
+    resources: Vec<String>,
+    settings: Dict,
+    info: BTreeMap<&'static str, PlistValue>,
+}
+
+#[derive(Clone, Debug)]
+enum PlistValue {
+    Bool(bool),
+    String(String),
+    Array(Vec<String>),
+}
+
+impl From<&str> for PlistValue {
+    fn from(s: &str) -> Self {
+        Self::String(s.to_owned())
+    }
+}
+
+fn info_plist(properties: &BTreeMap<&'static str, PlistValue>) -> String {
+    let mut info = BTreeMap::new();
+    for (key, value) in &[
+        ("CFBundleDevelopmentRegion", "$(DEVELOPMENT_LANGUAGE)"),
+        ("CFBundleExecutable", "$(EXECUTABLE_NAME)"),
+        ("CFBundleIdentifier", "$(PRODUCT_BUNDLE_IDENTIFIER)"),
+        ("CFBundleInfoDictionaryVersion", "6.0"),
+        ("CFBundleName", "$(PRODUCT_NAME)"),
+        ("CFBundlePackageType", "APPL"),
+        ("CFBundleShortVersionString", "1.0"),
+        ("CFBundleVersion", "1"),
+    ] {
+        info.insert(*key, PlistValue::from(*value));
+    }
+    info.extend(properties.iter().map(|(key, value)| (*key, value.clone())));
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n",
+        "<plist version=\"1.0\">\n",
+        "<dict>\n",
+    ));
+    for (key, value) in info {
+        out.push_str(&format!("\t<key>{}</key>\n", xml::escape(key)));
+        match value {
+            PlistValue::Bool(true) => out.push_str("\t<true/>\n"),
+            PlistValue::Bool(false) => out.push_str("\t<false/>\n"),
+            PlistValue::String(s) => {
+                out.push_str(&format!("\t<string>{}</string>\n", xml::escape(&s)))
+            }
+            PlistValue::Array(items) => {
+                out.push_str("\t<array>\n");
+                for item in items {
+                    out.push_str(&format!("\t\t<string>{}</string>\n", xml::escape(&item)));
+                }
+                out.push_str("\t</array>\n");
+            }
+        }
+    }
+    out.push_str("</dict>\n</plist>\n");
+    out
+}
+
+fn buildable_reference(target_id: &str, target_name: &str, product: &str, project: &str) -> String {
+    format!(
+        r#"<BuildableReference
+               BuildableIdentifier = "primary"
+               BlueprintIdentifier = "{}"
+               BuildableName = "{}"
+               BlueprintName = "{}"
+               ReferencedContainer = "container:{}">
+            </BuildableReference>"#,
+        target_id,
+        xml::escape(product),
+        xml::escape(target_name),
+        xml::escape(project)
+    )
+}
+
+// A lib-only scheme has nothing to launch, so `runnable` is false for those.
+fn scheme(
+    target_id: &str,
+    target_name: &str,
+    product: &str,
+    project: &str,
+    runnable: bool,
+) -> String {
+    let reference = buildable_reference(target_id, target_name, product, project);
+    let runnable = if runnable {
+        format!(
+            r#"
+      <BuildableProductRunnable
+         runnableDebuggingMode = "0">
+         {}
+      </BuildableProductRunnable>"#,
+            reference
+        )
+    } else {
+        String::new()
+    };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<Scheme
+   LastUpgradeVersion = "1200"
+   version = "1.3">
+   <BuildAction
+      parallelizeBuildables = "YES"
+      buildImplicitDependencies = "YES">
+      <BuildActionEntries>
+         <BuildActionEntry
+            buildForTesting = "YES"
+            buildForRunning = "YES"
+            buildForProfiling = "YES"
+            buildForArchiving = "YES"
+            buildForAnalyzing = "YES">
+            {reference}
+         </BuildActionEntry>
+      </BuildActionEntries>
+   </BuildAction>
+   <TestAction
+      buildConfiguration = "debug"
+      selectedDebuggerIdentifier = "Xcode.DebuggerFoundation.Debugger.LLDB"
+      selectedLauncherIdentifier = "Xcode.DebuggerFoundation.Launcher.LLDB"
+      shouldUseLaunchSchemeArgsEnv = "YES">
+      <Testables>
+      </Testables>
+   </TestAction>
+   <LaunchAction
+      buildConfiguration = "debug"
+      selectedDebuggerIdentifier = "Xcode.DebuggerFoundation.Debugger.LLDB"
+      selectedLauncherIdentifier = "Xcode.DebuggerFoundation.Launcher.LLDB"
+      launchStyle = "0"
+      useCustomWorkingDirectory = "NO"
+      ignoresPersistentStateOnLaunch = "NO"
+      debugDocumentVersioning = "YES"
+      debugServiceExtension = "internal"
+      allowLocationSimulation = "YES">{runnable}
+      <EnvironmentVariables>
+         <EnvironmentVariable
+            key = "RUST_BACKTRACE"
+            value = "full"
+            isEnabled = "YES">
+         </EnvironmentVariable>
+         <EnvironmentVariable
+            key = "RUST_LOG"
+            value = "info"
+            isEnabled = "YES">
+         </EnvironmentVariable>
+      </EnvironmentVariables>
+   </LaunchAction>
+   <ProfileAction
+      buildConfiguration = "release"
+      shouldUseLaunchSchemeArgsEnv = "YES"
+      savedToolIdentifier = ""
+      useCustomWorkingDirectory = "NO"
+      debugDocumentVersioning = "YES">{runnable}
+   </ProfileAction>
+   <AnalyzeAction
+      buildConfiguration = "debug">
+   </AnalyzeAction>
+   <ArchiveAction
+      buildConfiguration = "release"
+      revealArchiveInOrganizer = "YES">
+   </ArchiveAction>
+</Scheme>
+"#,
+        reference = reference,
+        runnable = runnable
+    )
+}
+
+static WORKSPACE_CONTENTS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Workspace
+   version = "1.0">
+   <FileRef
+      location = "self:">
+   </FileRef>
+</Workspace>
+"#;
+
+fn add_lib_target(objects: &mut Objects, lib_target_name: &str, settings: Dict) -> String {
+    let configurations = configuration_list(objects, lib_target_name, |_| settings.clone());
+    objects.add(
+        "PBXLegacyTarget",
+        &format!("target:{}", lib_target_name),
+        Dict::new()
+            .with("buildArgumentsString", XCODE_SCRIPT_ARGS)
+            .with("buildConfigurationList", configurations)
+            .with("buildPhases", ids(None))
+            .with("buildToolPath", "${HOME}/.cargo/bin/cargo-apple")
+            .with("buildWorkingDirectory", "$(SRCROOT)/..")
+            .with("dependencies", ids(None))
+            .with("name", lib_target_name)
+            // Passing build settings to cargo results in evil linker errors.
+            .with("passBuildSettingsInEnvironment", "0")
+            .with("productName", lib_target_name),
+    )
+}
+
+// Leaves files that haven't changed alone, since Xcode reloads the project
+// whenever anything in it is touched.
+fn write(path: &Path, contents: &str) -> Result<(), Error> {
+    if fs::read_to_string(path).ok().as_deref() == Some(contents) {
+        log::info!("{:?} is already up to date", path);
+        return Ok(());
+    }
+    let write_failed = |cause| Error::WriteFailed {
+        path: path.to_owned(),
+        cause,
+    };
+    if let Some(parent) = path.parent() {
+        util::create_dir_all(parent).map_err(write_failed)?;
+    }
+    fs::write(path, contents).map_err(write_failed)
+}
+
+/// Generates the Xcode project in `project_dir`, which needs to already
+/// contain the rendered templates. `source_dirs` are shown in the project for
+/// convenience, but aren't part of any target.
+pub fn generate(
+    config: &Config,
+    metadata: &Metadata,
+    source_dirs: &[PathBuf],
+    project_dir: &Path,
+) -> Result<(), Error> {
+    let app = config.app();
+    let name = app.name();
+    let product = format!("{}.app", name);
+    let lib = format!("lib{}.a", util::lib_name_from_crate(name));
+    let project_file_name = format!("{}.xcodeproj", name);
+    let asset_dir = PathBuf::from(
+        app.asset_dir()
+            .file_name()
+            .expect("developer error: asset dir had no file name"),
+    );
+
+    let mut objects = Objects::new();
+    let project_id = pbxproj::id("project");
+
+    let mut source_files = Vec::new();
+    let sources_group = add_group(
+        &mut objects,
+        project_dir,
+        Path::new("Sources"),
+        &mut source_files,
+    )?;
+    let source_dir_refs = source_dirs
+        .iter()
+        .map(|dir| folder_ref(&mut objects, dir))
+        .collect::<Vec<_>>();
+    let asset_dir_ref = folder_ref(&mut objects, &asset_dir);
+    let lib_ref = objects.add(
+        "PBXFileReference",
+        "lib",
+        Dict::new()
+            .with("lastKnownFileType", "archive.ar")
+            .with("path", lib.as_str())
+            .with("sourceTree", "BUILT_PRODUCTS_DIR"),
+    );
+
+    let bundle_id = config.bundle_id();
+    let catalyst = config.platform_enabled(PlatformKind::Catalyst);
+    let search_path = |triple: &str| {
+        format!(
+            "$(inherited) \"{}\"",
+            app.prefix_path(format!("target/{}/$(CONFIGURATION)", triple))
+                .display()
+        )
+    };
+    let app_settings = |platform: PlatformKind| {
+        let settings = Dict::new()
+            .with("DEVELOPMENT_TEAM", config.development_team())
+            .with(
+                "INFOPLIST_FILE",
+                format!("{}_{}/Info.plist", name, platform.target_suffix()),
+            )
+            .with("PRODUCT_BUNDLE_IDENTIFIER", bundle_id.as_str())
+            .with("PRODUCT_NAME", name);
+        match platform {
+            PlatformKind::Ios => {
+                let settings = settings
+                    .with("ARCHS", strings(IOS_ARCHS))
+                    .with("ASSETCATALOG_COMPILER_APPICON_NAME", "AppIcon")
+                    .with("CODE_SIGN_IDENTITY", "iPhone Developer")
+                    .with("ENABLE_BITCODE", "NO")
+                    .with("IPHONEOS_DEPLOYMENT_TARGET", config.ios_deployment_target())
+                    .with(
+                        "LD_RUNPATH_SEARCH_PATHS",
+                        strings(&["$(inherited)", "@executable_path/Frameworks"]),
+                    )
+                    .with(
+                        "LIBRARY_SEARCH_PATHS[sdk=iphoneos*]",
+                        search_path("aarch64-apple-ios"),
+                    )
+                    .with(
+                        "LIBRARY_SEARCH_PATHS[sdk=iphonesimulator*]",
+                        search_path("x86_64-apple-ios"),
+                    )
+                    .with("SDKROOT", "iphoneos")
+                    .with("TARGETED_DEVICE_FAMILY", "1,2")
+                    .with("VALID_ARCHS", IOS_ARCHS.join(" "));
+                if catalyst {
+                    settings
+                        .with("SUPPORTS_MACCATALYST", "YES")
+                        .with(
+                            "LIBRARY_SEARCH_PATHS[sdk=macosx*][arch=arm64]",
+                            search_path("aarch64-apple-ios-macabi"),
+                        )
+                        .with(
+                            "LIBRARY_SEARCH_PATHS[sdk=macosx*][arch=x86_64]",
+                            search_path("x86_64-apple-ios-macabi"),
+                        )
+                } else {
+                    settings
+                }
+            }
+            PlatformKind::Macos => settings
+                .with("COMBINE_HIDPI_IMAGES", "YES")
+                .with(
+                    "LD_RUNPATH_SEARCH_PATHS",
+                    strings(&["$(inherited)", "@executable_path/../Frameworks"]),
+                )
+                .with("LIBRARY_SEARCH_PATHS", search_path("x86_64-apple-darwin"))
+                .with("SDKROOT", "macosx"),
+            PlatformKind::Tvos => settings
+                .with("ARCHS", strings(TVOS_ARCHS))
+                .with("ENABLE_BITCODE", "NO")
+                .with(
+                    "LD_RUNPATH_SEARCH_PATHS",
+                    strings(&["$(inherited)", "@executable_path/Frameworks"]),
+                )
+                .with(
+                    "LIBRARY_SEARCH_PATHS[sdk=appletvos*]",
+                    search_path("aarch64-apple-tvos"),
+                )
+                .with(
+                    "LIBRARY_SEARCH_PATHS[sdk=appletvsimulator*]",
+                    search_path("x86_64-apple-tvos"),
+                )
+                .with("SDKROOT", "appletvos")
+                .with("TARGETED_DEVICE_FAMILY", "3")
+                .with("TVOS_DEPLOYMENT_TARGET", config.tvos_deployment_target())
+                .with("VALID_ARCHS", TVOS_ARCHS.join(" ")),
+            PlatformKind::Watchos | PlatformKind::Catalyst => {
+                unreachable!("developer error: {} doesn't have an app target", platform)
+            }
+        }
+    };
+    let lib_settings = |platform: PlatformKind| match platform {
+        PlatformKind::Ios => {
+            let settings = Dict::new()
+                .with("ARCHS", strings(IOS_ARCHS))
+                .with("ENABLE_BITCODE", "NO")
+                .with("SDKROOT", "iphoneos")
+                .with("VALID_ARCHS", IOS_ARCHS.join(" "));
+            if catalyst {
+                settings.with("SUPPORTS_MACCATALYST", "YES")
+            } else {
+                settings
+            }
+        }
+        PlatformKind::Macos => Dict::new().with("SDKROOT", "macosx"),
+        PlatformKind::Tvos => Dict::new()
+            .with("ARCHS", strings(TVOS_ARCHS))
+            .with("ENABLE_BITCODE", "NO")
+            .with("SDKROOT", "appletvos")
+            .with("VALID_ARCHS", TVOS_ARCHS.join(" ")),
+        PlatformKind::Watchos => Dict::new()
+            .with("ARCHS", strings(WATCHOS_ARCHS))
+            .with("SDKROOT", "watchos")
+            .with("VALID_ARCHS", WATCHOS_ARCHS.join(" "))
+            .with(
+                "WATCHOS_DEPLOYMENT_TARGET",
+                config.watchos_deployment_target(),
+            ),
+        PlatformKind::Catalyst => {
+            unreachable!("developer error: Catalyst builds the iOS targets")
+        }
+    };
+
+    let mut ios_info = BTreeMap::new();
+    ios_info.insert("LSRequiresIPhoneOS", PlistValue::Bool(true));
+    ios_info.insert("UILaunchStoryboardName", PlistValue::from("LaunchScreen"));
+    ios_info.insert(
+        "UIRequiredDeviceCapabilities",
+        PlistValue::Array(vec!["arm64".to_owned(), "metal".to_owned()]),
+    );
+    ios_info.insert(
+        "UISupportedInterfaceOrientations",
+        PlistValue::Array(
+            [
+                "UIInterfaceOrientationPortrait",
+                "UIInterfaceOrientationLandscapeLeft",
+                "UIInterfaceOrientationLandscapeRight",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+        ),
+    );
+    ios_info.insert(
+        "UISupportedInterfaceOrientations~ipad",
+        PlistValue::Array(
+            [
+                "UIInterfaceOrientationPortrait",
+                "UIInterfaceOrientationPortraitUpsideDown",
+                "UIInterfaceOrientationLandscapeLeft",
+                "UIInterfaceOrientationLandscapeRight",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+        ),
+    );
+    let mut macos_info = BTreeMap::new();
+    macos_info.insert("NSHighResolutionCapable", PlistValue::Bool(true));
+
+    let mut app_targets = vec![
+        AppTarget {
+            platform: PlatformKind::Ios,
+            frameworks: IOS_FRAMEWORKS
+                .iter()
+                .copied()
+                .chain(metadata.ios().frameworks().iter().map(String::as_str))
+                .collect(),
+            resources: vec![asset_dir_ref.clone()],
+            settings: app_settings(PlatformKind::Ios),
+            info: ios_info,
+        },
+        AppTarget {
+            platform: PlatformKind::Macos,
+            frameworks: MACOS_FRAMEWORKS
+                .iter()
+                .copied()
+                .chain(metadata.macos().frameworks().iter().map(String::as_str))
+                .collect(),
+            resources: Vec::new(),
+            settings: app_settings(PlatformKind::Macos),
+            info: macos_info,
+        },
+    ];
+    if config.platform_enabled(PlatformKind::Tvos) {
+        let mut tvos_info = BTreeMap::new();
+        tvos_info.insert("LSRequiresIPhoneOS", PlistValue::Bool(true));
+        tvos_info.insert(
+            "UIRequiredDeviceCapabilities",
+            PlistValue::Array(vec!["arm64".to_owned(), "metal".to_owned()]),
+        );
+        app_targets.push(AppTarget {
+            platform: PlatformKind::Tvos,
+            frameworks: IOS_FRAMEWORKS
+                .iter()
+                .copied()
+                .chain(metadata.tvos().frameworks().iter().map(String::as_str))
+                .collect(),
+            resources: vec![asset_dir_ref.clone()],
+            settings: app_settings(PlatformKind::Tvos),
+            info: tvos_info,
+        });
+    }
+
+    let mut framework_refs = BTreeMap::new();
+    let mut targets = Vec::new();
+    let mut product_refs = Vec::new();
+    let mut info_groups = Vec::new();
+    let mut target_attributes = Dict::new();
+    let mut schemes = Vec::new();
+    for target in app_targets {
+        let target_name = format!("{}_{}", name, target.platform.target_suffix());
+
+        let lib_target_name = format!("lib_{}", target_name);
+        let lib_target = add_lib_target(
+            &mut objects,
+            &lib_target_name,
+            lib_settings(target.platform),
+        );
+        let proxy = objects.add(
+            "PBXContainerItemProxy",
+            &format!("proxy:{}", lib_target_name),
+            Dict::new()
+                .with("containerPortal", project_id.as_str())
+                .with("proxyType", "1")
+                .with("remoteGlobalIDString", lib_target.as_str())
+                .with("remoteInfo", lib_target_name.as_str()),
+        );
+        let dependency = objects.add(
+            "PBXTargetDependency",
+            &format!("dependency:{}:{}", target_name, lib_target_name),
+            Dict::new()
+                .with("target", lib_target.as_str())
+                .with("targetProxy", proxy),
+        );
+
+        let build_file = |objects: &mut Objects, file_ref: &str, key: &str| {
+            objects.add(
+                "PBXBuildFile",
+                &format!("build:{}:{}", target_name, key),
+                Dict::new().with("fileRef", file_ref),
+            )
+        };
+        let phase = |objects: &mut Objects, isa: &'static str, files: Vec<String>| {
+            objects.add(
+                isa,
+                &format!("phase:{}:{}", target_name, isa),
+                Dict::new()
+                    .with("buildActionMask", "2147483647")
+                    .with("files", ids(files))
+                    .with("runOnlyForDeploymentPostprocessing", "0"),
+            )
+        };
+        let mut sources = Vec::new();
+        let mut resources = target
+            .resources
+            .iter()
+            .map(|file_ref| build_file(&mut objects, file_ref, file_ref))
+            .collect::<Vec<_>>();
+        for (key, file_phase) in &source_files {
+            let file = build_file(&mut objects, &pbxproj::id(key), key);
+            match file_phase {
+                Phase::Sources => sources.push(file),
+                Phase::Resources => resources.push(file),
+            }
+        }
+        let mut frameworks = vec![build_file(&mut objects, &lib_ref, "lib")];
+        for framework in target.frameworks {
+            let file_ref = framework_refs
+                .entry(framework)
+                .or_insert_with(|| {
+                    objects.add(
+                        "PBXFileReference",
+                        &format!("framework:{}", framework),
+                        Dict::new()
+                            .with("lastKnownFileType", "wrapper.framework")
+                            .with("name", format!("{}.framework", framework))
+                            .with(
+                                "path",
+                                format!("System/Library/Frameworks/{}.framework", framework),
+                            )
+                            .with("sourceTree", "SDKROOT"),
+                    )
+                })
+                .clone();
+            frameworks.push(build_file(
+                &mut objects,
+                &file_ref,
+                &format!("framework:{}", framework),
+            ));
+        }
+        let build_phases = vec![
+            phase(&mut objects, "PBXSourcesBuildPhase", sources),
+            phase(&mut objects, "PBXResourcesBuildPhase", resources),
+            phase(&mut objects, "PBXFrameworksBuildPhase", frameworks),
+        ];
+
+        let product_ref = objects.add(
+            "PBXFileReference",
+            &format!("product:{}", target_name),
+            Dict::new()
+                .with("explicitFileType", "wrapper.application")
+                .with("includeInIndex", "0")
+                .with("path", product.as_str())
+                .with("sourceTree", "BUILT_PRODUCTS_DIR"),
+        );
+        product_refs.push(product_ref.clone());
+
+        let info_path = format!("{}/Info.plist", target_name);
+        let info_ref = objects.add(
+            "PBXFileReference",
+            &format!("file:{}", info_path),
+            Dict::new()
+                .with("lastKnownFileType", "text.plist.xml")
+                .with("path", "Info.plist")
+                .with("sourceTree", "<group>"),
+        );
+        info_groups.push(
+            objects.add(
+                "PBXGroup",
+                &format!("group:{}", target_name),
+                Dict::new()
+                    .with("children", ids(Some(info_ref)))
+                    .with("path", target_name.as_str())
+                    .with("sourceTree", "<group>"),
+            ),
+        );
+        write(&project_dir.join(&info_path), &info_plist(&target.info))?;
+
+        let settings = target.settings;
+        let configurations = configuration_list(&mut objects, &target_name, |_| settings.clone());
+        let app_target = objects.add(
+            "PBXNativeTarget",
+            &format!("target:{}", target_name),
+            Dict::new()
+                .with("buildConfigurationList", configurations)
+                .with("buildPhases", ids(build_phases))
+                .with("buildRules", ids(None))
+                .with("dependencies", ids(Some(dependency)))
+                .with("name", target_name.as_str())
+                .with("productName", target_name.as_str())
+                .with("productReference", product_ref)
+                .with("productType", "com.apple.product-type.application"),
+        );
+        if !config.development_team().is_empty() {
+            target_attributes.insert(
+                app_target.clone(),
+                Dict::new().with("DevelopmentTeam", config.development_team()),
+            );
+        }
+        schemes.push((
+            target_name.clone(),
+            scheme(
+                &app_target,
+                &target_name,
+                &product,
+                &project_file_name,
+                true,
+            ),
+        ));
+        targets.push(app_target);
+        targets.push(lib_target);
+    }
+    // There's no watchOS app, so its lib gets the scheme instead.
+    if config.platform_enabled(PlatformKind::Watchos) {
+        let lib_target_name = config.scheme_for(PlatformKind::Watchos);
+        let lib_target = add_lib_target(
+            &mut objects,
+            &lib_target_name,
+            lib_settings(PlatformKind::Watchos),
+        );
+        schemes.push((
+            lib_target_name.clone(),
+            scheme(
+                &lib_target,
+                &lib_target_name,
+                &lib_target_name,
+                &project_file_name,
+                false,
+            ),
+        ));
+        targets.push(lib_target);
+    }
+
+    let frameworks_group = objects.add(
+        "PBXGroup",
+        "group:Frameworks",
+        Dict::new()
+            .with(
+                "children",
+                ids(framework_refs.values().cloned().chain(Some(lib_ref))),
+            )
+            .with("name", "Frameworks")
+            .with("sourceTree", "<group>"),
+    );
+    let products_group = objects.add(
+        "PBXGroup",
+        "group:Products",
+        Dict::new()
+            .with("children", ids(product_refs))
+            .with("name", "Products")
+            .with("sourceTree", "<group>"),
+    );
+    let main_group = objects.add(
+        "PBXGroup",
+        "group:",
+        Dict::new()
+            .with(
+                "children",
+                ids(std::iter::once(sources_group)
+                    .chain(Some(asset_dir_ref))
+                    .chain(source_dir_refs)
+                    .chain(info_groups)
+                    .chain(Some(frameworks_group))
+                    .chain(Some(products_group.clone()))),
+            )
+            .with("sourceTree", "<group>"),
+    );
+    let project_configurations = configuration_list(&mut objects, "project", project_settings);
+    objects.add(
+        "PBXProject",
+        "project",
+        Dict::new()
+            .with(
+                "attributes",
+                Dict::new()
+                    .with("LastUpgradeCheck", "1200")
+                    .with("TargetAttributes", target_attributes),
+            )
+            .with("buildConfigurationList", project_configurations)
+            .with("compatibilityVersion", "Xcode 10.0")
+            .with("developmentRegion", "en")
+            .with("hasScannedForEncodings", "0")
+            .with("knownRegions", strings(&["Base", "en"]))
+            .with("mainGroup", main_group)
+            .with("productRefGroup", products_group)
+            .with("projectDirPath", "")
+            .with("projectRoot", "")
+            .with("targets", ids(targets)),
+    );
+
+    println!("Generating Xcode project...");
+    let project_path = project_dir.join(&project_file_name);
+    write(
+        &project_path.join("project.pbxproj"),
+        &objects.render(&project_id),
+    )?;
+    write(
+        &project_path.join("project.xcworkspace/contents.xcworkspacedata"),
+        WORKSPACE_CONTENTS,
+    )?;
+    for (target_name, scheme) in schemes {
+        write(
+            &project_path.join(format!("xcshareddata/xcschemes/{}.xcscheme", target_name)),
+            &scheme,
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,156 @@
+//! Writes `project.pbxproj` files, which are old-style (OpenStep) plists
+//! containing a flat table of objects that refer to each other by ID.
+
+use crate::util;
+use fnv::FnvHasher;
+use std::{collections::BTreeMap, fmt::Write as _, hash::Hasher as _};
+
+/// Xcode 10.0 and later can open projects with this version.
+const OBJECT_VERSION: u32 = 51;
+
+/// Makes a stable object ID from `key`, so regenerating an unchanged project
+/// gives an identical file, and a changed one only differs where it changed.
+pub fn id(key: &str) -> String {
+    let mut hasher = FnvHasher::default();
+    hasher.write(key.as_bytes());
+    let high = hasher.finish();
+    // Xcode IDs are 96 bits, so a second pass fills in the rest.
+    hasher.write(&[0]);
+    let low = hasher.finish() as u32;
+    format!("{:016X}{:08X}", high, low)
+}
+
+#[derive(Clone, Debug)]
+pub enum Value {
+    String(String),
+    Array(Vec<Value>),
+    Dict(Dict),
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Self::String(s.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Self::String(s)
+    }
+}
+
+impl From<Vec<String>> for Value {
+    fn from(items: Vec<String>) -> Self {
+        Self::Array(items.into_iter().map(Value::String).collect())
+    }
+}
+
+impl From<Dict> for Value {
+    fn from(dict: Dict) -> Self {
+        Self::Dict(dict)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Dict(BTreeMap<String, Value>);
+
+impl Dict {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.insert(key, value);
+        self
+    }
+
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        self.0.insert(key.into(), value.into());
+    }
+}
+
+fn write_value(out: &mut String, value: &Value, indent: usize) {
+    match value {
+        Value::String(s) => out.push_str(&util::pbxproj_escape(s)),
+        Value::Array(items) => {
+            out.push_str("(\n");
+            for item in items {
+                push_indent(out, indent + 1);
+                write_value(out, item, indent + 1);
+                out.push_str(",\n");
+            }
+            push_indent(out, indent);
+            out.push(')');
+        }
+        Value::Dict(dict) => {
+            out.push_str("{\n");
+            write_entries(out, dict.0.iter(), indent + 1);
+            push_indent(out, indent);
+            out.push('}');
+        }
+    }
+}
+
+fn write_entries<'a>(
+    out: &mut String,
+    entries: impl Iterator<Item = (&'a String, &'a Value)>,
+    indent: usize,
+) {
+    for (key, value) in entries {
+        push_indent(out, indent);
+        out.push_str(&util::pbxproj_escape(key));
+        out.push_str(" = ");
+        write_value(out, value, indent);
+        out.push_str(";\n");
+    }
+}
+
+fn push_indent(out: &mut String, indent: usize) {
+    out.extend(std::iter::repeat('\t').take(indent));
+}
+
+/// The objects making up a project, keyed by ID.
+#[derive(Debug, Default)]
+pub struct Objects {
+    // Grouped by `isa` like Xcode does it, which keeps diffs readable.
+    sections: BTreeMap<&'static str, BTreeMap<String, Dict>>,
+}
+
+impl Objects {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds an object with the ID made from `key` and returns that ID.
+    pub fn add(&mut self, isa: &'static str, key: &str, fields: Dict) -> String {
+        let id = id(key);
+        self.sections
+            .entry(isa)
+            .or_default()
+            .insert(id.clone(), fields);
+        id
+    }
+
+    /// Renders the whole project file, with `root` as the `PBXProject`.
+    pub fn render(&self, root: &str) -> String {
+        let mut out = String::new();
+        out.push_str("// !$*UTF8*$!\n{\n");
+        out.push_str("\tarchiveVersion = 1;\n\tclasses = {\n\t};\n");
+        writeln!(out, "\tobjectVersion = {};", OBJECT_VERSION).unwrap();
+        out.push_str("\tobjects = {\n");
+        for (isa, objects) in &self.sections {
+            writeln!(out, "\n/* Begin {} section */", isa).unwrap();
+            for (id, fields) in objects {
+                writeln!(out, "\t\t{} = {{", id).unwrap();
+                writeln!(out, "\t\t\tisa = {};", isa).unwrap();
+                write_entries(&mut out, fields.0.iter(), 3);
+                out.push_str("\t\t};\n");
+            }
+            writeln!(out, "/* End {} section */", isa).unwrap();
+        }
+        out.push_str("\t};\n");
+        writeln!(out, "\trootObject = {};", root).unwrap();
+        out.push_str("}\n");
+        out
+    }
+}
@@ -0,0 +1,113 @@
+use crate::env::{Env, ExplicitEnv as _};
+use once_cell_regex::regex;
+use std::{
+    fmt::{self, Display},
+    path::Path,
+    str,
+};
+
+#[derive(Debug)]
+pub enum DeviceUdidsError {
+    CommandFailed(bossy::Error),
+    InvalidUtf8(str::Utf8Error),
+}
+
+impl Display for DeviceUdidsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CommandFailed(err) => write!(f, "Failed to list devices via `xctrace`: {}", err),
+            Self::InvalidUtf8(err) => write!(f, "Device list contained invalid UTF-8: {}", err),
+        }
+    }
+}
+
+/// Gets the UDIDs of physical devices from `xctrace list devices` output,
+/// which looks like this:
+///
+/// ```text
+/// == Devices ==
+/// Jane's Mac (01234567-89AB-CDEF-0123-456789ABCDEF)
+/// Jane's iPhone (14.2) (00008030-001A2D4C0AB8802E)
+///
+/// == Simulators ==
+/// iPhone 8 (14.2) (5A2F1B6E-4C6D-4C6B-9E3B-1B8D4A9C2E7F)
+/// ```
+///
+/// The host Mac is listed as a device too, but it doesn't have an OS version.
+pub fn parse_device_udids(raw_list: &str) -> Vec<String> {
+    let device_re = regex!(r"^.+ \([\d.]+\) \(([0-9A-Fa-f-]+)\)$");
+    raw_list
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| *line != "== Devices ==")
+        .skip(1)
+        .take_while(|line| !line.starts_with("=="))
+        .filter_map(|line| device_re.captures(line).map(|caps| caps[1].to_owned()))
+        .collect()
+}
+
+/// Gets the UDIDs of connected physical iOS devices, leaving out simulators.
+pub fn ios_device_udids() -> Result<Vec<String>, DeviceUdidsError> {
+    let output = bossy::Command::impure_parse("xcrun xctrace list devices")
+        .run_and_wait_for_output()
+        .map_err(DeviceUdidsError::CommandFailed)?;
+    // `xctrace` has been known to print the list to stderr instead of stdout
+    let raw_list = if output.stdout().is_empty() {
+        output.stderr_str()
+    } else {
+        output.stdout_str()
+    }
+    .map_err(DeviceUdidsError::InvalidUtf8)?;
+    Ok(parse_device_udids(raw_list))
+}
+
+/// Records `process` on the device with `id` for `duration` seconds, using
+/// one of Instruments' templates, like "Time Profiler". The process needs to
+/// already be running, and `output` must not exist yet.
+pub fn record(
+    env: &Env,
+    id: &str,
+    process: &str,
+    template: &str,
+    duration: u32,
+    output: &Path,
+) -> bossy::Result<()> {
+    bossy::Command::pure("xcrun")
+        .with_env_vars(env.explicit_env())
+        .with_args(&["xctrace", "record"])
+        .with_args(&["--template", template])
+        .with_args(&["--device", id])
+        .with_args(&["--attach", process])
+        .with_arg("--time-limit")
+        .with_arg(format!("{}s", duration))
+        .with_arg("--output")
+        .with_arg(output)
+        .run_and_wait()
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_physical_devices_are_listed() {
+        let raw_list = "\
+== Devices ==
+Jane's Mac (01234567-89AB-CDEF-0123-456789ABCDEF)
+Jane's iPhone (14.2) (00008030-001A2D4C0AB8802E)
+iPad (3) (13.7) (0123456789abcdef0123456789abcdef01234567)
+
+== Simulators ==
+iPhone 8 (14.2) (5A2F1B6E-4C6D-4C6B-9E3B-1B8D4A9C2E7F)
+";
+        assert_eq!(
+            parse_device_udids(raw_list),
+            vec![
+                "00008030-001A2D4C0AB8802E",
+                "0123456789abcdef0123456789abcdef01234567",
+            ]
+        );
+        assert!(parse_device_udids("== Simulators ==\n").is_empty());
+    }
+}
@@ -1,11 +1,16 @@
-#![cfg(target_os = "macos")]
 #![forbid(unsafe_code)]
 
-use cargo_mobile::{
-    apple::{cli::Input, NAME},
-    util::cli::exec,
-};
-
+#[cfg(target_os = "macos")]
 fn main() {
+    use cargo_mobile::{
+        apple::{cli::Input, NAME},
+        util::cli::exec,
+    };
     exec::<Input>(NAME)
 }
+
+#[cfg(not(target_os = "macos"))]
+fn main() {
+    eprintln!("`cargo apple` is only available on macOS.");
+    std::process::exit(1);
+}
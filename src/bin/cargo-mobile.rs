@@ -1,7 +1,9 @@
 #![forbid(unsafe_code)]
 
 use cargo_mobile::{
-    init, opts, update,
+    bindgen, cache,
+    config::{global, preset::Preset},
+    deinit, doctor, init, opts, regen, update,
     util::{
         self,
         cli::{
@@ -10,6 +12,7 @@ use cargo_mobile::{
     },
     NAME,
 };
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
@@ -44,22 +47,129 @@ pub enum Command {
             parse(from_flag = opts::OpenInEditor::from_bool),
         )]
         open_in_editor: opts::OpenInEditor,
+        #[structopt(
+            long = "force",
+            help = "Recopy all assets, even ones that haven't changed",
+            parse(from_flag = opts::ForceSync::from_bool),
+        )]
+        force_sync: opts::ForceSync,
+        #[structopt(
+            long = "template",
+            help = "Template pack to use, by name, path, or git URL (add `#<branch>` to a URL to pick a branch)"
+        )]
+        template_pack: Option<String>,
         #[structopt(long = "submodule-commit", help = "Template pack commit to checkout")]
         submodule_commit: Option<String>,
+        #[structopt(long = "name", env = "CARGO_MOBILE_NAME", help = "Project name")]
+        name: Option<String>,
+        #[structopt(
+            long = "stylized-name",
+            env = "CARGO_MOBILE_STYLIZED_NAME",
+            help = "Project name as it should appear to users"
+        )]
+        stylized_name: Option<String>,
+        #[structopt(
+            long = "domain",
+            env = "CARGO_MOBILE_DOMAIN",
+            help = "Domain that bundle identifiers are derived from"
+        )]
+        domain: Option<String>,
+        #[structopt(
+            long = "platforms",
+            env = "CARGO_MOBILE_PLATFORMS",
+            help = "Platforms to generate projects for, separated by commas",
+            use_delimiter = true
+        )]
+        platforms: Option<Vec<String>>,
+        #[structopt(
+            long = "development-team",
+            env = "CARGO_MOBILE_DEVELOPMENT_TEAM",
+            help = "Apple development team ID"
+        )]
+        development_team: Option<String>,
+        #[structopt(
+            long = "var",
+            help = "Template variable, as `<name>=<value>`",
+            number_of_values = 1,
+            parse(try_from_str = parse_template_var)
+        )]
+        template_vars: Vec<(String, String)>,
+        #[structopt(
+            long = "defaults",
+            help = "Use the defaults for anything not given by flags, instead of prompting"
+        )]
+        defaults: bool,
     },
     #[structopt(name = "open", about = "Open project in default code editor")]
     Open,
+    #[structopt(
+        name = "devices",
+        about = "Shows details for connected devices on all platforms"
+    )]
+    Devices,
+    #[structopt(name = "doctor", about = "Checks that everything needed is installed")]
+    Doctor {
+        #[structopt(long = "json", help = "Print results as JSON")]
+        json: bool,
+    },
+    #[structopt(
+        name = "regen",
+        about = "Regenerate platform projects to pick up config changes"
+    )]
+    Regen {
+        #[structopt(
+            long = "dry-run",
+            help = "Show what would change without touching anything",
+            parse(from_flag = opts::DryRun::from_bool),
+        )]
+        dry_run: opts::DryRun,
+    },
+    #[structopt(
+        name = "deinit",
+        about = "Removes the files `cargo mobile init` generated, leaving your own alone"
+    )]
+    Deinit {
+        #[structopt(long = "yes", help = "Don't ask before removing anything")]
+        yes: bool,
+    },
+    #[structopt(
+        name = "cache",
+        about = "Manage the cache of templates from git, for working offline"
+    )]
+    Cache(CacheCommand),
+    #[structopt(
+        name = "config",
+        about = "Shows settings from the project and user configs, after layering them"
+    )]
+    Config {
+        #[structopt(long = "show-origin", help = "Show which layer each setting came from")]
+        show_origin: bool,
+    },
+    #[structopt(
+        name = "bindgen",
+        about = "Generate Kotlin and Swift bindings for Rust functions from an interface file"
+    )]
+    Bindgen {
+        #[structopt(
+            long = "interface",
+            help = "Interface file to generate from, instead of `bindings.toml` in the project root"
+        )]
+        interface: Option<PathBuf>,
+        #[structopt(
+            long = "clippy",
+            help = "Run `cargo clippy` on the project after generating"
+        )]
+        clippy: bool,
+    },
     #[structopt(name = "update", about = "Update `cargo-mobile`")]
     Update {
         #[structopt(long = "init", help = "Regenerate project if update succeeds")]
         init: bool,
     },
-    #[cfg_attr(
-        target_os = "macos",
-        structopt(
-            name = "apple",
-            about = "iOS commands (tip: type less by running `cargo apple` instead!)"
-        )
+    #[cfg(target_os = "macos")]
+    #[structopt(
+        name = "apple",
+        about = "iOS commands (tip: type less by running `cargo apple` instead!)"
     )]
     Apple(cargo_mobile::apple::cli::Command),
     #[structopt(
@@ -69,10 +179,43 @@ pub enum Command {
     Android(cargo_mobile::android::cli::Command),
 }
 
+#[derive(Clone, Debug, StructOpt)]
+pub enum CacheCommand {
+    #[structopt(
+        name = "fetch",
+        about = "Caches the template packs given, or the current project's template pack"
+    )]
+    Fetch {
+        #[structopt(
+            name = "PACK",
+            help = "Template packs to cache, by name, path, or git URL"
+        )]
+        packs: Vec<String>,
+        #[structopt(long = "refresh", help = "Fetch again, even if already cached")]
+        refresh: bool,
+    },
+    #[structopt(name = "clear", about = "Deletes everything in the template cache")]
+    Clear,
+}
+
+fn parse_template_var(var: &str) -> Result<(String, String), String> {
+    let mut parts = var.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(name), Some(value)) if !name.is_empty() => Ok((name.to_owned(), value.to_owned())),
+        _ => Err(format!("expected `<name>=<value>`, but got {:?}", var)),
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     InitFailed(init::Error),
     OpenFailed(util::OpenInEditorError),
+    DoctorFailed(doctor::Error),
+    RegenFailed(regen::Error),
+    DeinitFailed(deinit::Error),
+    CacheFailed(cache::Error),
+    ConfigFailed(global::Error),
+    BindgenFailed(bindgen::Error),
     UpdateFailed(update::Error),
     #[cfg(target_os = "macos")]
     AppleFailed(cargo_mobile::apple::cli::Error),
@@ -86,6 +229,12 @@ impl Reportable for Error {
             Self::OpenFailed(err) => {
                 Report::error("Failed to open project in default code editor", err)
             }
+            Self::DoctorFailed(err) => err.report(),
+            Self::RegenFailed(err) => err.report(),
+            Self::DeinitFailed(err) => err.report(),
+            Self::CacheFailed(err) => err.report(),
+            Self::ConfigFailed(err) => err.report(),
+            Self::BindgenFailed(err) => err.report(),
             Self::UpdateFailed(err) => Report::error("Failed to update `cargo-mobile`", err),
             #[cfg(target_os = "macos")]
             Self::AppleFailed(err) => err.report(),
@@ -111,19 +260,75 @@ impl Exec for Input {
                 skip_dev_tools: cli::SkipDevTools { skip_dev_tools },
                 reinstall_deps: cli::ReinstallDeps { reinstall_deps },
                 open_in_editor,
+                force_sync,
+                template_pack,
                 submodule_commit,
+                name,
+                stylized_name,
+                domain,
+                platforms,
+                development_team,
+                template_vars,
+                defaults,
             } => init::exec(
                 wrapper,
-                non_interactive,
+                if defaults {
+                    opts::NonInteractive::Yes
+                } else {
+                    non_interactive
+                },
                 skip_dev_tools,
                 reinstall_deps,
                 open_in_editor,
+                force_sync,
+                Preset {
+                    name,
+                    stylized_name,
+                    domain,
+                    template_pack,
+                    platforms,
+                    development_team,
+                    template_vars: template_vars.into_iter().collect(),
+                },
                 submodule_commit,
                 ".",
             )
             .map(|_| ())
             .map_err(Error::InitFailed),
             Command::Open => util::open_in_editor(".").map_err(Error::OpenFailed),
+            Command::Devices => {
+                cargo_mobile::android::cli::Input::new(
+                    flags,
+                    cargo_mobile::android::cli::Command::Devices,
+                )
+                .exec(wrapper)
+                .map_err(Error::AndroidFailed)?;
+                #[cfg(target_os = "macos")]
+                cargo_mobile::apple::cli::Input::new(
+                    flags,
+                    cargo_mobile::apple::cli::Command::Devices,
+                )
+                .exec(wrapper)
+                .map_err(Error::AppleFailed)?;
+                Ok(())
+            }
+            Command::Doctor { json } => {
+                doctor::exec(wrapper, json, ".").map_err(Error::DoctorFailed)
+            }
+            Command::Regen { dry_run } => {
+                regen::exec(wrapper, dry_run, ".").map_err(Error::RegenFailed)
+            }
+            Command::Deinit { yes } => deinit::exec(wrapper, yes, ".").map_err(Error::DeinitFailed),
+            Command::Cache(CacheCommand::Fetch { packs, refresh }) => {
+                cache::fetch(".", &packs, refresh).map_err(Error::CacheFailed)
+            }
+            Command::Cache(CacheCommand::Clear) => cache::clear().map_err(Error::CacheFailed),
+            Command::Config { show_origin } => {
+                global::show(".", show_origin).map_err(Error::ConfigFailed)
+            }
+            Command::Bindgen { interface, clippy } => {
+                bindgen::exec(wrapper, interface, clippy, ".").map_err(Error::BindgenFailed)
+            }
             Command::Update { init } => {
                 update::update(wrapper).map_err(Error::UpdateFailed)?;
                 if init {
@@ -134,6 +339,8 @@ impl Exec for Input {
                         Default::default(),
                         Default::default(),
                         Default::default(),
+                        Default::default(),
+                        Default::default(),
                         ".",
                     )
                     .map_err(Error::InitFailed)?;
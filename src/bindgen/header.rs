@@ -0,0 +1,35 @@
+use super::interface::Interface;
+
+/// A C header declaring the exports, which works as a Swift bridging header
+/// as well as from Objective-C and C++.
+pub fn generate(interface: &Interface, header: &str, c_prefix: &str) -> String {
+    let mut out = format!(
+        "// {}\n#pragma once\n\n#include <stdbool.h>\n#include <stdint.h>\n\n#ifdef __cplusplus\nextern \"C\" {{\n#endif\n\n",
+        header
+    );
+    for function in interface.functions() {
+        let params = if function.args.is_empty() {
+            "void".to_owned()
+        } else {
+            function
+                .args
+                .iter()
+                .map(|arg| format!("{}{}", arg.ty.c(false), arg.name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let ret = function.returns.map_or("void ", |ty| ty.c(true));
+        out.push_str(&format!(
+            "{}{}_{}({});\n",
+            ret, c_prefix, function.name, params
+        ));
+    }
+    if interface.returns_strings() {
+        out.push_str(&format!(
+            "\n// Frees a string returned by any of the other functions.\nvoid {}_free_string(char *s);\n",
+            c_prefix
+        ));
+    }
+    out.push_str("\n#ifdef __cplusplus\n}\n#endif\n");
+    out
+}
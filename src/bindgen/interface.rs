@@ -0,0 +1,250 @@
+use serde::Deserialize;
+use std::{
+    collections::HashSet,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub static DEFAULT_CLASS: &str = "Bindings";
+
+pub static DEFAULT_RUST_OUT: &str = "src/bindings.rs";
+
+/// Everything that can cross the boundary. Strings are copied each way, so
+/// nobody on either side has to worry about who owns them.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Type {
+    Bool,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    String,
+}
+
+impl Type {
+    pub fn rust(self) -> &'static str {
+        match self {
+            Self::Bool => "bool",
+            Self::I8 => "i8",
+            Self::I16 => "i16",
+            Self::I32 => "i32",
+            Self::I64 => "i64",
+            Self::F32 => "f32",
+            Self::F64 => "f64",
+            Self::String => "String",
+        }
+    }
+
+    pub fn jni(self) -> &'static str {
+        match self {
+            Self::Bool => "jboolean",
+            Self::I8 => "jbyte",
+            Self::I16 => "jshort",
+            Self::I32 => "jint",
+            Self::I64 => "jlong",
+            Self::F32 => "jfloat",
+            Self::F64 => "jdouble",
+            Self::String => "jstring",
+        }
+    }
+
+    pub fn kotlin(self) -> &'static str {
+        match self {
+            Self::Bool => "Boolean",
+            Self::I8 => "Byte",
+            Self::I16 => "Short",
+            Self::I32 => "Int",
+            Self::I64 => "Long",
+            Self::F32 => "Float",
+            Self::F64 => "Double",
+            Self::String => "String",
+        }
+    }
+
+    /// The C type, which differs for strings depending on whether they're
+    /// passed in (and borrowed) or returned (and owned by the caller). This
+    /// comes with whatever space should go before a name.
+    pub fn c(self, returned: bool) -> &'static str {
+        match self {
+            Self::Bool => "bool ",
+            Self::I8 => "int8_t ",
+            Self::I16 => "int16_t ",
+            Self::I32 => "int32_t ",
+            Self::I64 => "int64_t ",
+            Self::F32 => "float ",
+            Self::F64 => "double ",
+            Self::String if returned => "char *",
+            Self::String => "const char *",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Arg {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: Type,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Function {
+    pub name: String,
+    #[serde(default)]
+    pub args: Vec<Arg>,
+    pub returns: Option<Type>,
+}
+
+impl Function {
+    pub fn uses_strings(&self) -> bool {
+        self.returns == Some(Type::String) || self.args.iter().any(|arg| arg.ty == Type::String)
+    }
+}
+
+#[derive(Debug)]
+pub enum InterfaceError {
+    ReadFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
+    ParseFailed {
+        path: PathBuf,
+        cause: toml::de::Error,
+    },
+    NameInvalid {
+        name: String,
+    },
+    FunctionDuplicated {
+        name: String,
+    },
+    ArgDuplicated {
+        function: String,
+        name: String,
+    },
+}
+
+impl Display for InterfaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ReadFailed { path, cause } => {
+                write!(f, "Failed to read interface file {:?}: {}", path, cause)
+            }
+            Self::ParseFailed { path, cause } => {
+                write!(f, "Failed to parse interface file {:?}: {}", path, cause)
+            }
+            Self::NameInvalid { name } => write!(
+                f,
+                "{:?} isn't a valid name; names can only contain lowercase ASCII letters, digits, and underscores, and can't start with a digit.",
+                name
+            ),
+            Self::FunctionDuplicated { name } => {
+                write!(f, "The function {:?} is declared more than once.", name)
+            }
+            Self::ArgDuplicated { function, name } => write!(
+                f,
+                "The function {:?} has more than one arg named {:?}.",
+                function, name
+            ),
+        }
+    }
+}
+
+// These end up as identifiers in Rust, Kotlin, and C, so snake case is the
+// only style that's safe everywhere.
+fn validate_name(name: &str) -> Result<(), InterfaceError> {
+    let valid = name
+        .chars()
+        .next()
+        .map_or(false, |c| c.is_ascii_lowercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(InterfaceError::NameInvalid {
+            name: name.to_owned(),
+        })
+    }
+}
+
+/// The functions to generate bindings for, which are implemented in Rust as
+/// plain functions in `module`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Interface {
+    module: Option<String>,
+    class: Option<String>,
+    rust_out: Option<String>,
+    #[serde(default, rename = "function")]
+    functions: Vec<Function>,
+}
+
+impl Interface {
+    pub fn load(path: &Path) -> Result<Self, InterfaceError> {
+        let contents = fs::read_to_string(path).map_err(|cause| InterfaceError::ReadFailed {
+            path: path.to_owned(),
+            cause,
+        })?;
+        let interface =
+            toml::from_str::<Self>(&contents).map_err(|cause| InterfaceError::ParseFailed {
+                path: path.to_owned(),
+                cause,
+            })?;
+        let mut functions = HashSet::new();
+        for function in &interface.functions {
+            validate_name(&function.name)?;
+            if !functions.insert(&function.name) {
+                return Err(InterfaceError::FunctionDuplicated {
+                    name: function.name.clone(),
+                });
+            }
+            let mut args = HashSet::new();
+            for arg in &function.args {
+                validate_name(&arg.name)?;
+                if !args.insert(&arg.name) {
+                    return Err(InterfaceError::ArgDuplicated {
+                        function: function.name.clone(),
+                        name: arg.name.clone(),
+                    });
+                }
+            }
+        }
+        Ok(interface)
+    }
+
+    /// The path the implementations are called through, i.e. `crate::api`.
+    pub fn module_path(&self) -> String {
+        match self.module.as_deref() {
+            Some(module) => format!("crate::{}", module),
+            None => "crate".to_owned(),
+        }
+    }
+
+    /// The name of the Kotlin object the wrappers go in.
+    pub fn class(&self) -> &str {
+        self.class.as_deref().unwrap_or(DEFAULT_CLASS)
+    }
+
+    /// Where the exports go, relative to the app root.
+    pub fn rust_out(&self) -> &str {
+        self.rust_out.as_deref().unwrap_or(DEFAULT_RUST_OUT)
+    }
+
+    pub fn functions(&self) -> &[Function] {
+        &self.functions
+    }
+
+    pub fn uses_strings(&self) -> bool {
+        self.functions.iter().any(Function::uses_strings)
+    }
+
+    pub fn returns_strings(&self) -> bool {
+        self.functions
+            .iter()
+            .any(|function| function.returns == Some(Type::String))
+    }
+}
@@ -0,0 +1,34 @@
+use super::interface::Interface;
+
+/// A Kotlin object with an `external fun` for each function, which loads the
+/// app's lib the first time it's used.
+pub fn generate(interface: &Interface, header: &str, package: &str, lib_name: &str) -> String {
+    use heck::MixedCase as _;
+    let mut out = format!(
+        "// {}\npackage {}\n\nobject {} {{\n    init {{\n        System.loadLibrary(\"{}\")\n    }}\n",
+        header,
+        package,
+        interface.class(),
+        lib_name
+    );
+    for function in interface.functions() {
+        let params = function
+            .args
+            .iter()
+            .map(|arg| format!("{}: {}", arg.name.to_mixed_case(), arg.ty.kotlin()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let ret = function
+            .returns
+            .map(|ty| format!(": {}", ty.kotlin()))
+            .unwrap_or_default();
+        out.push_str(&format!(
+            "\n    @JvmStatic\n    external fun {}({}){}\n",
+            function.name.to_mixed_case(),
+            params,
+            ret
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
@@ -0,0 +1,191 @@
+//! Generates the glue between Rust and the platform languages from one
+//! interface file, so that the symbol names on both sides can't drift apart.
+
+#[cfg(target_os = "macos")]
+mod header;
+mod interface;
+mod kotlin;
+mod rust;
+
+pub use self::interface::{Interface, InterfaceError};
+
+use crate::{
+    config::{self, Config},
+    util::{
+        self,
+        cli::{Report, Reportable, TextWrapper},
+        output::{self, Event},
+    },
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub static DEFAULT_INTERFACE: &str = "bindings.toml";
+
+#[derive(Debug)]
+pub enum Error {
+    ConfigLoadFailed(config::LoadOrGenError),
+    ConfigMissing,
+    InterfaceInvalid(InterfaceError),
+    ReadFailed { path: PathBuf, cause: io::Error },
+    WriteFailed { path: PathBuf, cause: io::Error },
+    FormatFailed(util::CommandError),
+    ClippyFailed(util::RunCargoError),
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::ConfigLoadFailed(err) => err.report(),
+            Self::ConfigMissing => Report::action_request(
+                format!("No {} was found", config::file_name()),
+                "Bindings are generated into your project, so run `cargo mobile init` first.",
+            ),
+            Self::InterfaceInvalid(err) => Report::error("Failed to load bindings interface", err),
+            Self::ReadFailed { path, cause } => {
+                Report::error(format!("Failed to read {:?}", path), cause)
+            }
+            Self::WriteFailed { path, cause } => {
+                Report::error(format!("Failed to write {:?}", path), cause)
+            }
+            Self::FormatFailed(err) => err.report("Failed to format generated Rust bindings"),
+            Self::ClippyFailed(err) => err.report("Failed to run `cargo clippy`"),
+        }
+    }
+}
+
+// Only writes files that actually changed, so that nothing gets rebuilt for
+// no reason. Returns whether `path` was written.
+fn write(path: &Path, contents: &str) -> Result<bool, Error> {
+    match fs::read_to_string(path) {
+        Ok(current) if current == contents => return Ok(false),
+        Ok(_) => (),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+        Err(cause) => {
+            return Err(Error::ReadFailed {
+                path: path.to_owned(),
+                cause,
+            })
+        }
+    }
+    let write_failed = |cause| Error::WriteFailed {
+        path: path.to_owned(),
+        cause,
+    };
+    if let Some(parent) = path.parent() {
+        util::create_dir_all(parent).map_err(write_failed)?;
+    }
+    fs::write(path, contents).map_err(write_failed)?;
+    Ok(true)
+}
+
+// Runs the generated Rust through `rustfmt` before it gets compared against
+// what's already there, since a file that's formatted after being written
+// would otherwise look changed every time.
+fn format_rust(path: &Path, contents: String) -> Result<String, Error> {
+    let scratch = path.with_extension("rs.fmt");
+    write(&scratch, &contents)?;
+    let formatted = util::rustfmt_file(&scratch)
+        .map_err(Error::FormatFailed)
+        .and_then(|()| {
+            fs::read_to_string(&scratch).map_err(|cause| Error::ReadFailed {
+                path: scratch.clone(),
+                cause,
+            })
+        });
+    if let Err(err) = fs::remove_file(&scratch) {
+        log::warn!("failed to remove {:?}: {}", scratch, err);
+    }
+    formatted
+}
+
+/// Generates bindings from `interface` (or `bindings.toml` in the app root):
+/// the Rust exports, a Kotlin object wrapping them in the Android project,
+/// and a C header for them in the Xcode project. Platform projects that
+/// haven't been generated yet are skipped. If `clippy` is set, the project is
+/// linted with the new bindings afterward.
+pub fn exec(
+    wrapper: &TextWrapper,
+    interface: Option<PathBuf>,
+    clippy: bool,
+    cwd: impl AsRef<Path>,
+) -> Result<(), Error> {
+    let config = Config::load(cwd)
+        .map_err(Error::ConfigLoadFailed)?
+        .ok_or(Error::ConfigMissing)?;
+    let app = config.app();
+    let interface_path = interface.unwrap_or_else(|| app.prefix_path(DEFAULT_INTERFACE));
+    let interface = Interface::load(&interface_path).map_err(Error::InterfaceInvalid)?;
+    let header = format!(
+        "Generated by `cargo mobile bindgen` from {:?}; changes will be overwritten.",
+        interface_path
+            .file_name()
+            .expect("developer error: interface path had no file name")
+    );
+    let lib_name = util::lib_name_from_crate(app.name());
+    let package = format!("{}.{}", app.reverse_domain(), app.name_snake());
+
+    let rust_out = app.prefix_path(interface.rust_out());
+    let rust = format_rust(
+        &rust_out,
+        rust::generate(&interface, &header, &package, &lib_name),
+    )?;
+    let mut outputs = vec![(rust_out, rust)];
+    if config.android().project_dir_exists() {
+        outputs.push((
+            config
+                .android()
+                .project_dir()
+                .join("app/src/main/java")
+                .join(package.replace('.', "/"))
+                .join(format!("{}.kt", interface.class())),
+            kotlin::generate(&interface, &header, &package, &lib_name),
+        ));
+    }
+    #[cfg(target_os = "macos")]
+    if config.apple().project_dir_exists() {
+        outputs.push((
+            config
+                .apple()
+                .project_dir()
+                .join("Sources")
+                .join(app.name())
+                .join("bindings/Bridging-Header.h"),
+            header::generate(&interface, &header, &lib_name),
+        ));
+    }
+
+    let mut changed = 0;
+    for (path, contents) in &outputs {
+        let rel = path.strip_prefix(app.root_dir()).unwrap_or(path);
+        if write(path, contents)? {
+            changed += 1;
+            println!("  {} (updated)", rel.display());
+        }
+        output::emit(&Event::Artifact {
+            kind: "bindings",
+            path,
+        });
+    }
+    if changed > 0 {
+        Report::victory(
+            format!(
+                "Generated bindings for {} functions",
+                interface.functions().len()
+            ),
+            format!(
+                "Make sure {:?} is declared as a module in your crate, and that `jni` is in your Android dependencies.",
+                interface.rust_out()
+            ),
+        )
+        .print(wrapper);
+    } else {
+        println!("Bindings are already up-to-date!");
+    }
+    if clippy {
+        util::clippy_check(app.root_dir(), false).map_err(Error::ClippyFailed)?;
+    }
+    Ok(())
+}
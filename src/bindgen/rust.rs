@@ -0,0 +1,192 @@
+use super::interface::{Function, Interface, Type};
+
+// Escapes a Java name the way JNI expects it in symbol names.
+fn jni_mangle(name: &str) -> String {
+    let mut mangled = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '.' | '/' => mangled.push('_'),
+            '_' => mangled.push_str("_1"),
+            ';' => mangled.push_str("_2"),
+            '[' => mangled.push_str("_3"),
+            c if c.is_ascii_alphanumeric() => mangled.push(c),
+            c => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    mangled.push_str(&format!("_0{:04x}", unit));
+                }
+            }
+        }
+    }
+    mangled
+}
+
+/// The name the JVM looks up when `class` (in `package`) calls `method`.
+pub fn jni_symbol(package: &str, class: &str, method: &str) -> String {
+    format!(
+        "Java_{}_{}_{}",
+        jni_mangle(package),
+        jni_mangle(class),
+        jni_mangle(method)
+    )
+}
+
+fn call(interface: &Interface, function: &Function) -> String {
+    format!(
+        "{}::{}({})",
+        interface.module_path(),
+        function.name,
+        function
+            .args
+            .iter()
+            .map(|arg| arg.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+fn jni_export(interface: &Interface, function: &Function, package: &str, method: &str) -> String {
+    let env = if function.uses_strings() {
+        "env"
+    } else {
+        "_env"
+    };
+    let mut params = vec![
+        format!("{}: JNIEnv<'a>", env),
+        "_class: JClass<'a>".to_owned(),
+    ];
+    let mut body = String::new();
+    for arg in &function.args {
+        match arg.ty {
+            Type::String => {
+                params.push(format!("{}: JString<'a>", arg.name));
+                body.push_str(&format!(
+                    "        let {name}: String = env\n            .get_string({name})\n            .expect(\"`{function}` was passed an invalid string\")\n            .into();\n",
+                    name = arg.name,
+                    function = function.name,
+                ));
+            }
+            Type::Bool => {
+                params.push(format!("{}: jboolean", arg.name));
+                body.push_str(&format!(
+                    "        let {name} = {name} != 0;\n",
+                    name = arg.name
+                ));
+            }
+            ty => params.push(format!("{}: {}", arg.name, ty.jni())),
+        }
+    }
+    let call = call(interface, function);
+    let (ret, tail) = match function.returns {
+        None => (String::new(), format!("        {};\n", call)),
+        Some(Type::String) => (
+            " -> jstring".to_owned(),
+            format!(
+                "        env.new_string({})\n            .expect(\"`{}` returned a string that couldn't be passed to Java\")\n            .into_inner()\n",
+                call, function.name
+            ),
+        ),
+        Some(Type::Bool) => (
+            " -> jboolean".to_owned(),
+            format!("        jboolean::from({})\n", call),
+        ),
+        Some(ty) => (format!(" -> {}", ty.jni()), format!("        {}\n", call)),
+    };
+    format!(
+        "    #[no_mangle]\n    pub extern \"system\" fn {symbol}<'a>({params}){ret} {{\n{body}{tail}    }}\n",
+        symbol = jni_symbol(package, interface.class(), method),
+        params = params.join(", "),
+        ret = ret,
+        body = body,
+        tail = tail,
+    )
+}
+
+fn c_export(interface: &Interface, function: &Function, prefix: &str) -> String {
+    let mut params = Vec::new();
+    let mut body = String::new();
+    for arg in &function.args {
+        match arg.ty {
+            Type::String => {
+                params.push(format!("{}: *const c_char", arg.name));
+                body.push_str(&format!(
+                    "        let {name} = CStr::from_ptr({name}).to_string_lossy().into_owned();\n",
+                    name = arg.name,
+                ));
+            }
+            ty => params.push(format!("{}: {}", arg.name, ty.rust())),
+        }
+    }
+    let call = call(interface, function);
+    let (ret, tail) = match function.returns {
+        None => (String::new(), format!("        {};\n", call)),
+        Some(Type::String) => (
+            " -> *mut c_char".to_owned(),
+            format!(
+                "        CString::new({})\n            .expect(\"`{}` returned a string containing a nul byte\")\n            .into_raw()\n",
+                call, function.name
+            ),
+        ),
+        Some(ty) => (format!(" -> {}", ty.rust()), format!("        {}\n", call)),
+    };
+    // Functions that don't take strings have nothing to be unsafe about.
+    let unsafety = if function.args.iter().any(|arg| arg.ty == Type::String) {
+        "unsafe "
+    } else {
+        ""
+    };
+    format!(
+        "    #[no_mangle]\n    pub {unsafety}extern \"C\" fn {prefix}_{name}({params}){ret} {{\n{body}{tail}    }}\n",
+        unsafety = unsafety,
+        prefix = prefix,
+        name = function.name,
+        params = params.join(", "),
+        ret = ret,
+        body = body,
+        tail = tail,
+    )
+}
+
+/// The Rust side of the bindings: JNI exports on Android, and C exports
+/// everywhere else. These just convert the args and call the
+/// implementations, so an implementation that doesn't match the interface is
+/// a compile error rather than a crash.
+pub fn generate(interface: &Interface, header: &str, package: &str, c_prefix: &str) -> String {
+    use heck::MixedCase as _;
+    let mut out = format!(
+        "// {}\n#![allow(clippy::all, non_snake_case, unused_imports)]\n\n#[cfg(target_os = \"android\")]\nmod jni_exports {{\n",
+        header
+    );
+    if interface.uses_strings() {
+        out.push_str("    use jni::{\n        objects::{JClass, JString},\n        sys::*,\n        JNIEnv,\n    };\n");
+    } else {
+        out.push_str("    use jni::{objects::JClass, sys::*, JNIEnv};\n");
+    }
+    for function in interface.functions() {
+        out.push('\n');
+        out.push_str(&jni_export(
+            interface,
+            function,
+            package,
+            &function.name.to_mixed_case(),
+        ));
+    }
+    out.push_str("}\n\n#[cfg(not(target_os = \"android\"))]\nmod c_exports {\n");
+    if interface.uses_strings() {
+        out.push_str(
+            "    use std::{\n        ffi::{CStr, CString},\n        os::raw::c_char,\n    };\n",
+        );
+    }
+    for function in interface.functions() {
+        out.push('\n');
+        out.push_str(&c_export(interface, function, c_prefix));
+    }
+    if interface.returns_strings() {
+        out.push_str(&format!(
+            "\n    /// Frees a string returned by any of the other functions.\n    #[no_mangle]\n    pub unsafe extern \"C\" fn {}_free_string(s: *mut c_char) {{\n        if !s.is_null() {{\n            drop(CString::from_raw(s));\n        }}\n    }}\n",
+            c_prefix
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
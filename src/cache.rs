@@ -0,0 +1,86 @@
+use crate::{
+    config::{self, Config},
+    templating::{LookupError, Pack},
+    util::{
+        self,
+        cli::{Report, Reportable},
+        output::{self, Event},
+        template_cache::{self, CacheMode},
+    },
+};
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum Error {
+    Offline,
+    ConfigLoadFailed(config::LoadOrGenError),
+    ConfigMissing,
+    LookupFailed { name: String, cause: LookupError },
+    FetchFailed(template_cache::Error),
+    ClearFailed(template_cache::Error),
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::Offline => Report::action_request(
+                "Can't fetch templates while offline",
+                "Run `cargo mobile cache fetch` without `--offline` (or `CARGO_NET_OFFLINE`).",
+            ),
+            Self::ConfigLoadFailed(err) => err.report(),
+            Self::ConfigMissing => Report::action_request(
+                format!("No {} was found", config::file_name()),
+                "Pass the template packs to fetch, or run this from a project to fetch its template pack.",
+            ),
+            Self::LookupFailed { name, cause } => {
+                Report::error(format!("Failed to find template pack {:?}", name), cause)
+            }
+            Self::FetchFailed(err) => Report::error("Failed to fetch templates", err),
+            Self::ClearFailed(err) => Report::error("Failed to clear template cache", err),
+        }
+    }
+}
+
+fn fetch_pack(pack: &Pack, mode: CacheMode) -> Result<(), Error> {
+    for checkout in pack.fetch(mode).map_err(Error::FetchFailed)? {
+        println!("Cached templates at {:?}", checkout);
+        output::emit(&Event::Artifact {
+            kind: "template-cache",
+            path: &checkout,
+        });
+    }
+    Ok(())
+}
+
+/// Fills the template cache with everything `packs` need from git, so that
+/// later runs work with `--offline`. With no `packs`, this fetches whatever
+/// the project in `cwd` uses.
+pub fn fetch(cwd: impl AsRef<Path>, packs: &[String], refresh: bool) -> Result<(), Error> {
+    if util::git_offline() {
+        return Err(Error::Offline);
+    }
+    let mode = if refresh {
+        CacheMode::Refresh
+    } else {
+        CacheMode::Normal
+    };
+    if packs.is_empty() {
+        let config = Config::load(cwd)
+            .map_err(Error::ConfigLoadFailed)?
+            .ok_or(Error::ConfigMissing)?;
+        fetch_pack(config.app().template_pack(), mode)
+    } else {
+        for name in packs {
+            let pack = Pack::lookup_app(name, None).map_err(|cause| Error::LookupFailed {
+                name: name.clone(),
+                cause,
+            })?;
+            fetch_pack(&pack, mode)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn clear() -> Result<(), Error> {
+    template_cache::clear_template_cache().map_err(Error::ClearFailed)
+}
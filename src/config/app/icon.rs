@@ -0,0 +1,93 @@
+use crate::util;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+};
+
+pub static KEY: &str = "icon";
+
+pub static DEFAULT_BACKGROUND: &str = "#FFFFFF";
+
+#[derive(Debug)]
+pub struct BackgroundInvalid {
+    background: String,
+}
+
+impl Display for BackgroundInvalid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} isn't a color; colors are given as hex, i.e. \"#1E90FF\"",
+            self.background
+        )
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Raw {
+    pub source: String,
+    pub background: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Rgb(pub [u8; 3]);
+
+impl Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b] = self.0;
+        write!(f, "#{:02X}{:02X}{:02X}", r, g, b)
+    }
+}
+
+impl Rgb {
+    fn parse(s: &str) -> Option<Self> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        Some(Self([channel(0)?, channel(2)?, channel(4)?]))
+    }
+}
+
+/// A single big image that every platform's icons are generated from.
+#[derive(Clone, Debug)]
+pub struct Icon {
+    source: PathBuf,
+    background: Rgb,
+}
+
+impl Icon {
+    pub fn from_raw(root_dir: &Path, raw: Raw) -> Result<Self, BackgroundInvalid> {
+        let background = match raw.background {
+            Some(background) => {
+                Rgb::parse(&background).ok_or_else(|| BackgroundInvalid { background })?
+            }
+            None => {
+                log::info!(
+                    "`{}.{}.background` not set; defaulting to {}",
+                    super::KEY,
+                    KEY,
+                    DEFAULT_BACKGROUND
+                );
+                Rgb::parse(DEFAULT_BACKGROUND).expect("developer error: default background invalid")
+            }
+        };
+        Ok(Self {
+            source: util::prefix_path(root_dir, raw.source),
+            background,
+        })
+    }
+
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    /// What goes behind the icon wherever it can't be transparent, which is
+    /// Android's adaptive icon background layer and everywhere on iOS.
+    pub fn background(&self) -> Rgb {
+        self.background
+    }
+}
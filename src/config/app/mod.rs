@@ -1,9 +1,12 @@
 mod common_email_providers;
+pub mod icon;
 pub mod name;
 mod raw;
 
 pub use self::raw::*;
 
+use self::icon::Icon;
+use super::hooks::Hooks;
 use crate::{
     templating::{self, Pack},
     util::{self, cli::Report},
@@ -19,6 +22,10 @@ pub static DEFAULT_TEMPLATE_PACK: &str = "brainstorm";
 #[cfg(not(feature = "brainium"))]
 pub static DEFAULT_TEMPLATE_PACK: &str = "bevy";
 
+/// Everything `app.platforms` can contain. `apple` is allowed everywhere, even
+/// though it only does anything on macOS, so that configs stay portable.
+pub static PLATFORMS: &[&str] = &["android", "apple"];
+
 #[derive(Debug)]
 pub enum Error {
     NameInvalid(name::Invalid),
@@ -34,6 +41,13 @@ pub enum Error {
         root_dir: PathBuf,
     },
     TemplatePackNotFound(templating::LookupError),
+    PlatformUnknown {
+        platform: String,
+    },
+    DirModeInvalid {
+        mode: u32,
+    },
+    IconBackgroundInvalid(icon::BackgroundInvalid),
 }
 
 impl Error {
@@ -67,6 +81,24 @@ impl Error {
                 ),
             ),
             Self::TemplatePackNotFound(err) => Report::error(msg, err),
+            Self::PlatformUnknown { platform } => Report::error(
+                msg,
+                format!(
+                    "`{}.platforms` invalid: {:?} isn't a platform; the possible platforms are {:?}",
+                    KEY, platform, PLATFORMS
+                ),
+            ),
+            Self::DirModeInvalid { mode } => Report::error(
+                msg,
+                format!(
+                    "`{}.dir-mode` invalid: {:#o} isn't a permission mode, which can be at most 0o7777",
+                    KEY, mode
+                ),
+            ),
+            Self::IconBackgroundInvalid(err) => Report::error(
+                msg,
+                format!("`{}.{}.background` invalid: {}", KEY, icon::KEY, err),
+            ),
         }
     }
 }
@@ -79,12 +111,27 @@ pub struct App {
     stylized_name: String,
     domain: String,
     asset_dir: PathBuf,
+    hard_copy: bool,
+    #[serde(skip)]
+    dir_mode: Option<u32>,
+    platforms: Option<Vec<String>>,
     #[serde(skip)]
     template_pack: Pack,
+    #[serde(skip)]
+    hooks: Hooks,
+    #[serde(skip)]
+    icon: Option<Icon>,
 }
 
 impl App {
-    pub fn from_raw(root_dir: PathBuf, raw: Raw) -> Result<Self, Error> {
+    /// Template packs that are named rather than given by URL or path are
+    /// looked for in `registry` before the built-in packs.
+    pub fn from_raw(
+        root_dir: PathBuf,
+        raw: Raw,
+        registry: Option<&Path>,
+        hooks: Hooks,
+    ) -> Result<Self, Error> {
         assert!(root_dir.is_absolute(), "root must be absolute");
 
         let name = name::validate(raw.name).map_err(Error::NameInvalid)?;
@@ -146,7 +193,29 @@ impl App {
         #[cfg(not(feature = "brainium"))]
         let template_pack = &raw.template_pack;
 
-        let template_pack = Pack::lookup_app(template_pack).map_err(Error::TemplatePackNotFound)?;
+        let template_pack =
+            Pack::lookup_app(template_pack, registry).map_err(Error::TemplatePackNotFound)?;
+
+        if let Some(platform) = raw
+            .platforms
+            .iter()
+            .flatten()
+            .find(|platform| !PLATFORMS.contains(&platform.as_str()))
+        {
+            return Err(Error::PlatformUnknown {
+                platform: platform.clone(),
+            });
+        }
+
+        if let Some(mode) = raw.dir_mode.filter(|mode| *mode > 0o7777) {
+            return Err(Error::DirModeInvalid { mode });
+        }
+
+        let icon = raw
+            .icon
+            .map(|icon| Icon::from_raw(&root_dir, icon))
+            .transpose()
+            .map_err(Error::IconBackgroundInvalid)?;
 
         Ok(Self {
             root_dir,
@@ -154,7 +223,12 @@ impl App {
             stylized_name,
             domain,
             asset_dir,
+            hard_copy: raw.hard_copy.unwrap_or_default(),
+            dir_mode: raw.dir_mode,
+            platforms: raw.platforms,
             template_pack,
+            hooks,
+            icon,
         })
     }
 
@@ -162,6 +236,16 @@ impl App {
         &self.root_dir
     }
 
+    pub fn hooks(&self) -> &Hooks {
+        &self.hooks
+    }
+
+    /// What app icons are generated from, if `app.icon` is set. Otherwise,
+    /// the projects just keep the icons from the templates.
+    pub fn icon(&self) -> Option<&Icon> {
+        self.icon.as_ref()
+    }
+
     pub fn prefix_path(&self, path: impl AsRef<Path>) -> PathBuf {
         util::prefix_path(self.root_dir(), path)
     }
@@ -200,7 +284,27 @@ impl App {
         self.root_dir().join(&self.asset_dir)
     }
 
+    /// Whether to copy things into generated projects instead of linking
+    /// them, for filesystems where links don't work (or don't work well).
+    pub fn hard_copy(&self) -> bool {
+        self.hard_copy
+    }
+
+    /// The mode that generated directories get, if `app.dir-mode` is set.
+    /// Otherwise, they're left to the umask.
+    pub fn dir_mode(&self) -> Option<u32> {
+        self.dir_mode
+    }
+
     pub fn template_pack(&self) -> &Pack {
         &self.template_pack
     }
+
+    /// Whether projects should be generated for `platform`, which is the case
+    /// for every platform unless `app.platforms` says otherwise.
+    pub fn platform_enabled(&self, platform: &str) -> bool {
+        self.platforms
+            .as_ref()
+            .map_or(true, |platforms| platforms.iter().any(|p| p == platform))
+    }
 }
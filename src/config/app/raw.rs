@@ -1,7 +1,8 @@
-use super::{common_email_providers::COMMON_EMAIL_PROVIDERS, name};
+use super::{common_email_providers::COMMON_EMAIL_PROVIDERS, icon, name};
 use crate::{
+    config::{global, preset::Preset},
     templating,
-    util::{cli::TextWrapper, prompt, Git},
+    util::{self, cli::TextWrapper, prompt::Prompter, Git},
 };
 use colored::{Color, Colorize as _};
 use heck::{KebabCase as _, TitleCase as _};
@@ -9,17 +10,31 @@ use serde::{Deserialize, Serialize};
 use std::{
     env,
     fmt::{self, Display},
-    io,
-    path::PathBuf,
+    fs, io,
+    path::{Path, PathBuf},
 };
 
 #[derive(Debug)]
 enum DefaultDomainError {
-    FailedToGetGitEmailAddr(bossy::Error),
+    FailedToGetGitEmailAddr(util::CommandError),
     EmailAddrInvalidUtf8(std::str::Utf8Error),
     FailedToParseEmailAddr,
 }
 
+impl Display for DefaultDomainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FailedToGetGitEmailAddr(err) => {
+                write!(f, "Failed to get email address from git: {}", err)
+            }
+            Self::EmailAddrInvalidUtf8(err) => {
+                write!(f, "Email address contained invalid UTF-8: {}", err)
+            }
+            Self::FailedToParseEmailAddr => write!(f, "Email address didn't contain a domain."),
+        }
+    }
+}
+
 fn default_domain() -> Result<Option<String>, DefaultDomainError> {
     let output = Git::new(".".as_ref())
         .user_email()
@@ -43,6 +58,17 @@ fn default_domain() -> Result<Option<String>, DefaultDomainError> {
     )
 }
 
+// If there's already a crate here, its name is a better guess than the dir's.
+fn cargo_package_name(dir: &Path) -> Option<String> {
+    let manifest = fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+    toml::from_str::<toml::Value>(&manifest)
+        .ok()?
+        .get("package")?
+        .get("name")?
+        .as_str()
+        .map(ToOwned::to_owned)
+}
+
 #[derive(Debug)]
 pub enum DefaultsError {
     CurrentDirFailed(io::Error),
@@ -73,6 +99,7 @@ struct Defaults {
     name: Option<String>,
     stylized_name: String,
     domain: String,
+    template_pack: String,
 }
 
 impl Defaults {
@@ -84,13 +111,27 @@ impl Defaults {
         let dir_name = dir_name
             .to_str()
             .ok_or_else(|| DefaultsError::CurrentDirInvalidUtf8(cwd.clone()))?;
+        let package_name = cargo_package_name(&cwd);
+        let base_name = package_name.as_deref().unwrap_or(dir_name);
         Ok(Self {
-            name: name::transliterate(&dir_name.to_kebab_case()),
-            stylized_name: dir_name.to_title_case(),
-            domain: default_domain()
-                .ok()
-                .flatten()
+            name: name::transliterate(&base_name.to_kebab_case()),
+            stylized_name: base_name.to_title_case(),
+            // The user config knows better than a guess based on email.
+            domain: global::get()
+                .domain()
+                .map(ToOwned::to_owned)
+                .or_else(|| match default_domain() {
+                    Ok(domain) => domain,
+                    Err(err) => {
+                        log::info!("not guessing domain from email: {}", err);
+                        None
+                    }
+                })
                 .unwrap_or_else(|| "example.com".to_owned()),
+            template_pack: global::get()
+                .template_pack()
+                .unwrap_or(super::DEFAULT_TEMPLATE_PACK)
+                .to_owned(),
         })
     }
 }
@@ -118,6 +159,7 @@ pub enum PromptError {
     DomainPromptFailed(io::Error),
     ListTemplatePacksFailed(templating::ListError),
     TemplatePackPromptFailed(io::Error),
+    PlatformsPromptFailed(io::Error),
 }
 
 impl Display for PromptError {
@@ -133,6 +175,9 @@ impl Display for PromptError {
             Self::TemplatePackPromptFailed(err) => {
                 write!(f, "Failed to prompt for template pack: {}", err)
             }
+            Self::PlatformsPromptFailed(err) => {
+                write!(f, "Failed to prompt for platforms: {}", err)
+            }
         }
     }
 }
@@ -144,33 +189,83 @@ pub struct Raw {
     pub stylized_name: Option<String>,
     pub domain: String,
     pub asset_dir: Option<String>,
+    pub hard_copy: Option<bool>,
+    pub dir_mode: Option<u32>,
+    pub platforms: Option<Vec<String>>,
     #[cfg(feature = "brainium")]
     pub template_pack: Option<String>,
     #[cfg(not(feature = "brainium"))]
     pub template_pack: String,
+    pub icon: Option<icon::Raw>,
 }
 
 impl Raw {
-    pub fn detect() -> Result<Self, DetectError> {
+    /// Uses whatever `preset` gives, and detects everything else.
+    pub fn detect(preset: &Preset) -> Result<Self, DetectError> {
         let defaults = Defaults::new().map_err(DetectError::DefaultsFailed)?;
-        Ok(Self {
-            name: defaults.name.ok_or_else(|| DetectError::NameNotDetected)?,
-            stylized_name: Some(defaults.stylized_name),
-            domain: defaults.domain,
+        let name = preset
+            .name
+            .clone()
+            .or(defaults.name)
+            .ok_or_else(|| DetectError::NameNotDetected)?;
+        let mut raw = Self {
+            name,
+            stylized_name: Some(
+                preset
+                    .stylized_name
+                    .clone()
+                    .unwrap_or(defaults.stylized_name),
+            ),
+            domain: preset.domain.clone().unwrap_or(defaults.domain),
             asset_dir: None,
+            hard_copy: None,
+            dir_mode: None,
+            platforms: preset.platforms.clone(),
             #[cfg(feature = "brainium")]
-            template_pack: None,
+            template_pack: Some(defaults.template_pack)
+                .filter(|pack| pack != super::DEFAULT_TEMPLATE_PACK),
             #[cfg(not(feature = "brainium"))]
-            template_pack: super::DEFAULT_TEMPLATE_PACK.to_owned(),
-        })
+            template_pack: defaults.template_pack,
+            icon: None,
+        };
+        if let Some(template_pack) = &preset.template_pack {
+            raw.set_template_pack(template_pack);
+        }
+        Ok(raw)
     }
 
-    pub fn prompt(wrapper: &TextWrapper) -> Result<Self, PromptError> {
+    /// Prompts for everything that `preset` doesn't give.
+    pub fn prompt(
+        wrapper: &TextWrapper,
+        preset: &Preset,
+        prompter: &dyn Prompter,
+    ) -> Result<Self, PromptError> {
         let defaults = Defaults::new().map_err(PromptError::DefaultsFailed)?;
-        let (name, default_stylized) = Self::prompt_name(wrapper, &defaults)?;
-        let stylized_name = Self::prompt_stylized_name(&name, default_stylized)?;
-        let domain = Self::prompt_domain(wrapper, &defaults)?;
-        let template_pack = Self::prompt_template_pack(wrapper)?;
+        let (name, default_stylized) = if let Some(name) = &preset.name {
+            (name.clone(), None)
+        } else {
+            Self::prompt_name(wrapper, &defaults, prompter)?
+        };
+        let stylized_name = if let Some(stylized_name) = &preset.stylized_name {
+            stylized_name.clone()
+        } else {
+            Self::prompt_stylized_name(&name, default_stylized, prompter)?
+        };
+        let domain = if let Some(domain) = &preset.domain {
+            domain.clone()
+        } else {
+            Self::prompt_domain(wrapper, &defaults, prompter)?
+        };
+        let platforms = if preset.platforms.is_some() {
+            preset.platforms.clone()
+        } else {
+            Self::prompt_platforms(wrapper, prompter)?
+        };
+        let template_pack = if let Some(template_pack) = &preset.template_pack {
+            template_pack.clone()
+        } else {
+            Self::prompt_template_pack(wrapper, &defaults.template_pack, prompter)?
+        };
         #[cfg(feature = "brainium")]
         let template_pack = Some(template_pack).filter(|pack| pack != super::DEFAULT_TEMPLATE_PACK);
         Ok(Self {
@@ -178,26 +273,56 @@ impl Raw {
             stylized_name: Some(stylized_name),
             domain,
             asset_dir: None,
+            hard_copy: None,
+            dir_mode: None,
+            platforms,
             template_pack,
+            icon: None,
         })
     }
+
+    /// The template pack name or URL, accounting for the default.
+    #[cfg(feature = "brainium")]
+    pub fn template_pack(&self) -> &str {
+        self.template_pack
+            .as_deref()
+            .unwrap_or(super::DEFAULT_TEMPLATE_PACK)
+    }
+
+    /// The template pack name or URL.
+    #[cfg(not(feature = "brainium"))]
+    pub fn template_pack(&self) -> &str {
+        &self.template_pack
+    }
+
+    #[cfg(feature = "brainium")]
+    pub fn set_template_pack(&mut self, template_pack: &str) {
+        self.template_pack = Some(template_pack.to_owned());
+    }
+
+    #[cfg(not(feature = "brainium"))]
+    pub fn set_template_pack(&mut self, template_pack: &str) {
+        self.template_pack = template_pack.to_owned();
+    }
 }
 
 impl Raw {
     fn prompt_name(
         wrapper: &TextWrapper,
         defaults: &Defaults,
+        prompter: &dyn Prompter,
     ) -> Result<(String, Option<String>), PromptError> {
         let mut default_name = defaults.name.clone();
         let mut rejected = None;
         let mut default_stylized = None;
         let name = loop {
-            let response = prompt::default(
-                "Project name",
-                default_name.as_ref().map(|s| s.as_str()),
-                None,
-            )
-            .map_err(PromptError::NamePromptFailed)?;
+            let response = prompter
+                .default(
+                    "Project name",
+                    default_name.as_ref().map(|s| s.as_str()),
+                    None,
+                )
+                .map_err(PromptError::NamePromptFailed)?;
             match name::validate(response.clone()) {
                 Ok(response) => {
                     if default_name == Some(response.clone()) {
@@ -229,16 +354,23 @@ impl Raw {
     fn prompt_stylized_name(
         name: &str,
         default_stylized: Option<String>,
+        prompter: &dyn Prompter,
     ) -> Result<String, PromptError> {
         let stylized = default_stylized
             .unwrap_or_else(|| name.replace("-", " ").replace("_", " ").to_title_case());
-        prompt::default("Stylized name", Some(&stylized), None)
+        prompter
+            .default("Stylized name", Some(&stylized), None)
             .map_err(PromptError::StylizedNamePromptFailed)
     }
 
-    fn prompt_domain(wrapper: &TextWrapper, defaults: &Defaults) -> Result<String, PromptError> {
+    fn prompt_domain(
+        wrapper: &TextWrapper,
+        defaults: &Defaults,
+        prompter: &dyn Prompter,
+    ) -> Result<String, PromptError> {
         Ok(loop {
-            let response = prompt::default("Domain", Some(&defaults.domain), None)
+            let response = prompter
+                .default("Domain", Some(&defaults.domain), None)
                 .map_err(PromptError::DomainPromptFailed)?;
             if publicsuffix::Domain::has_valid_syntax(&response) {
                 break response;
@@ -256,13 +388,65 @@ impl Raw {
         })
     }
 
-    pub fn prompt_template_pack(wrapper: &TextWrapper) -> Result<String, PromptError> {
-        let packs = templating::list_app_packs().map_err(PromptError::ListTemplatePacksFailed)?;
+    // Picking every platform is the same as not picking at all, which is best
+    // left implicit so that new platforms get picked up automatically.
+    fn prompt_platforms(
+        wrapper: &TextWrapper,
+        prompter: &dyn Prompter,
+    ) -> Result<Option<Vec<String>>, PromptError> {
+        let all = super::PLATFORMS.join(", ");
+        loop {
+            let response = prompter
+                .default("Platforms", Some(&all), None)
+                .map_err(PromptError::PlatformsPromptFailed)?;
+            let platforms = response
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|platform| !platform.is_empty())
+                .map(ToOwned::to_owned)
+                .collect::<Vec<_>>();
+            let unknown = platforms
+                .iter()
+                .filter(|platform| !super::PLATFORMS.contains(&platform.as_str()))
+                .collect::<Vec<_>>();
+            if platforms.is_empty() || !unknown.is_empty() {
+                println!(
+                    "{}",
+                    wrapper
+                        .fill(&format!(
+                            "Uh-oh, you need to pick from {}.",
+                            util::list_display(super::PLATFORMS)
+                        ))
+                        .bright_magenta()
+                );
+            } else if super::PLATFORMS
+                .iter()
+                .all(|platform| platforms.iter().any(|p| p == platform))
+            {
+                break Ok(None);
+            } else {
+                break Ok(Some(platforms));
+            }
+        }
+    }
+
+    pub fn prompt_template_pack(
+        wrapper: &TextWrapper,
+        default: &str,
+        prompter: &dyn Prompter,
+    ) -> Result<String, PromptError> {
+        // There's no config yet, so only the built-in packs can be listed;
+        // anything else can be picked with `init --template`, or by making it
+        // the default in the user config.
+        let mut packs =
+            templating::list_app_packs(None).map_err(PromptError::ListTemplatePacksFailed)?;
+        if !packs.iter().any(|pack| pack == default) {
+            packs.push(default.to_owned());
+        }
         let mut default_pack = None;
         println!("Detected template packs:");
         for (index, pack) in packs.iter().enumerate() {
-            let default = pack == super::DEFAULT_TEMPLATE_PACK;
-            if default {
+            let is_default = pack == default;
+            if is_default {
                 default_pack = Some(index.to_string());
                 println!(
                     "{}",
@@ -274,17 +458,15 @@ impl Raw {
                 println!("  [{}] {}", index.to_string().green(), pack);
             }
         }
-        if packs.is_empty() {
-            println!("  -- none --");
-        }
         loop {
             println!("  Enter an {} for a template pack above.", "index".green(),);
-            let pack_input = prompt::default(
-                "Template pack",
-                default_pack.as_deref(),
-                Some(Color::BrightGreen),
-            )
-            .map_err(PromptError::TemplatePackPromptFailed)?;
+            let pack_input = prompter
+                .default(
+                    "Template pack",
+                    default_pack.as_deref(),
+                    Some(Color::BrightGreen),
+                )
+                .map_err(PromptError::TemplatePackPromptFailed)?;
             let pack_name = pack_input
                 .parse::<usize>()
                 .ok()
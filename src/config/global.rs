@@ -0,0 +1,382 @@
+//! The user config, which holds defaults that apply to every project, i.e.
+//! the domain to use when generating new ones and where the Android SDK is.
+//!
+//! Settings are resolved in layers, where the project's config beats flags and
+//! environment variables (which only fill in what a new project gets), which
+//! beat the user config, which beats the built-in defaults. `cargo mobile
+//! config --show-origin` shows which layer won.
+
+use super::{app, file_name, validate, LoadError, Raw as ProjectRaw};
+use crate::{
+    android,
+    util::{
+        self,
+        cli::{Report, Reportable},
+    },
+};
+use once_cell_regex::exports::once_cell::sync::OnceCell;
+use serde::Deserialize;
+use std::{
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+static GLOBAL: OnceCell<Global> = OnceCell::new();
+
+#[derive(Debug)]
+pub enum Error {
+    UserLoadFailed(LoadError),
+    ProjectLoadFailed(LoadError),
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::UserLoadFailed(err) => Report::error("Failed to load user config", err),
+            Self::ProjectLoadFailed(err) => Report::error("Failed to load project config", err),
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/cargo-mobile/config.toml`, falling back to `~/.config`
+/// when `XDG_CONFIG_HOME` isn't set.
+pub fn path() -> Result<PathBuf, util::NoHomeDir> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .map(Ok)
+        .unwrap_or_else(|| util::home_dir().map(|home| home.join(".config")))
+        .map(|dir| dir.join(env!("CARGO_PKG_NAME")).join("config.toml"))
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+impl Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Always => write!(f, "always"),
+            Self::Never => write!(f, "never"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct AppRaw {
+    domain: Option<String>,
+    template_pack: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct AndroidRaw {
+    sdk_root: Option<String>,
+    ndk_home: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct TerminalRaw {
+    color: Option<Color>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Raw {
+    #[serde(default)]
+    app: AppRaw,
+    #[serde(default)]
+    android: AndroidRaw,
+    #[serde(default)]
+    terminal: TerminalRaw,
+}
+
+/// Which layer a setting came from.
+#[derive(Clone, Debug)]
+pub enum Layer {
+    Env(&'static str),
+    Project(PathBuf),
+    User(PathBuf),
+    Default,
+}
+
+impl Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Env(var) => write!(f, "env:{}", var),
+            Self::Project(path) => write!(f, "project:{}", path.display()),
+            Self::User(path) => write!(f, "user:{}", path.display()),
+            Self::Default => write!(f, "default"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Global {
+    path: Option<PathBuf>,
+    raw: Raw,
+}
+
+impl Global {
+    /// Loads the user config, which is empty if there isn't one.
+    pub fn load() -> Result<Self, LoadError> {
+        let path = match path() {
+            Ok(path) => path,
+            Err(err) => {
+                log::info!("not loading user config: {}", err);
+                return Ok(Self::default());
+            }
+        };
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                log::info!("no user config found at {:?}", path);
+                return Ok(Self::default());
+            }
+            Err(cause) => return Err(LoadError::ReadFailed { path, cause }),
+        };
+        let value =
+            toml::from_str::<toml::Value>(&source).map_err(|cause| LoadError::ParseFailed {
+                path: path.clone(),
+                cause,
+            })?;
+        validate::validate_global(&source, &value).map_err(|problems| LoadError::Invalid {
+            path: path.clone(),
+            problems,
+        })?;
+        let raw = toml::from_str::<Raw>(&source).map_err(|cause| LoadError::ParseFailed {
+            path: path.clone(),
+            cause,
+        })?;
+        log::info!("loaded user config from {:?}: {:#?}", path, raw);
+        Ok(Self {
+            path: Some(path),
+            raw,
+        })
+    }
+
+    fn layer(&self) -> Layer {
+        Layer::User(
+            self.path
+                .clone()
+                .expect("developer error: user config had settings but no path"),
+        )
+    }
+
+    fn expand(path: &str) -> PathBuf {
+        util::expand_home(path).unwrap_or_else(|_| PathBuf::from(path))
+    }
+
+    /// The domain new projects default to.
+    pub fn domain(&self) -> Option<&str> {
+        self.raw.app.domain.as_deref()
+    }
+
+    /// The template pack new projects default to.
+    pub fn template_pack(&self) -> Option<&str> {
+        self.raw.app.template_pack.as_deref()
+    }
+
+    /// Where the Android SDK is, for when `ANDROID_SDK_ROOT` isn't set.
+    pub fn android_sdk_root(&self) -> Option<PathBuf> {
+        self.raw.android.sdk_root.as_deref().map(Self::expand)
+    }
+
+    /// Where the NDK is, for when none of the NDK environment variables are
+    /// set.
+    pub fn android_ndk_home(&self) -> Option<PathBuf> {
+        self.raw.android.ndk_home.as_deref().map(Self::expand)
+    }
+
+    /// Whether to color output, which `NO_COLOR` and `CLICOLOR_FORCE` can
+    /// override.
+    pub fn color(&self) -> (Color, Layer) {
+        if std::env::var_os("NO_COLOR").map_or(false, |value| !value.is_empty()) {
+            (Color::Never, Layer::Env("NO_COLOR"))
+        } else if std::env::var_os("CLICOLOR_FORCE")
+            .map_or(false, |value| !value.is_empty() && value != *"0")
+        {
+            (Color::Always, Layer::Env("CLICOLOR_FORCE"))
+        } else if let Some(color) = self.raw.terminal.color {
+            (color, self.layer())
+        } else {
+            (Color::Auto, Layer::Default)
+        }
+    }
+}
+
+/// Loads the user config for everything else to use. If this is never called,
+/// the user config is treated as empty.
+pub fn init() -> Result<(), Error> {
+    let global = Global::load().map_err(Error::UserLoadFailed)?;
+    if GLOBAL.set(global).is_err() {
+        log::warn!("user config was already loaded");
+    }
+    Ok(())
+}
+
+pub fn get() -> &'static Global {
+    GLOBAL.get_or_init(Default::default)
+}
+
+/// A resolved setting, from whichever layer won.
+#[derive(Debug)]
+pub struct Setting {
+    key: &'static str,
+    value: Option<String>,
+    layer: Layer,
+}
+
+impl Setting {
+    fn new(key: &'static str, value: Option<(String, Layer)>) -> Self {
+        let (value, layer) = value.map_or((None, Layer::Default), |(value, layer)| {
+            (Some(value), layer)
+        });
+        Self { key, value, layer }
+    }
+
+    pub fn key(&self) -> &str {
+        self.key
+    }
+
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+
+    pub fn layer(&self) -> &Layer {
+        &self.layer
+    }
+}
+
+impl Display for Setting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{} = {:?}", self.key, value),
+            None => write!(f, "{} isn't set", self.key),
+        }
+    }
+}
+
+fn env(vars: &[&'static str]) -> Option<(String, Layer)> {
+    vars.iter().find_map(|var| {
+        std::env::var(var)
+            .ok()
+            .filter(|value| !value.is_empty())
+            .map(|value| (value, Layer::Env(var)))
+    })
+}
+
+fn path_value(path: PathBuf) -> String {
+    path.display().to_string()
+}
+
+/// Resolves every setting the user config can hold, as seen from `cwd`. If
+/// there's a project there, its config wins for the settings it has; if not,
+/// this shows what `init` would use.
+pub fn resolve(cwd: impl AsRef<Path>) -> Result<Vec<Setting>, Error> {
+    let global = get();
+    let user = |value: Option<String>| value.map(|value| (value, global.layer()));
+    let project = ProjectRaw::load(cwd).map_err(Error::ProjectLoadFailed)?;
+    let project = project.map(|(root_dir, raw)| (Layer::Project(root_dir.join(file_name())), raw));
+    let in_project = |f: fn(&app::Raw) -> String| {
+        project
+            .as_ref()
+            .map(|(layer, raw)| (f(&raw.app), layer.clone()))
+    };
+    Ok(vec![
+        Setting::new(
+            "app.domain",
+            in_project(|app| app.domain.clone())
+                .or_else(|| env(&["CARGO_MOBILE_DOMAIN"]))
+                .or_else(|| user(global.domain().map(ToOwned::to_owned))),
+        ),
+        Setting::new(
+            "app.template-pack",
+            in_project(|app| app.template_pack().to_owned())
+                .or_else(|| user(global.template_pack().map(ToOwned::to_owned)))
+                .or_else(|| Some((app::DEFAULT_TEMPLATE_PACK.to_owned(), Layer::Default))),
+        ),
+        Setting::new(
+            "android.sdk-root",
+            env(&["ANDROID_SDK_ROOT", "ANDROID_HOME"])
+                .or_else(|| user(global.android_sdk_root().map(path_value)))
+                .or_else(|| {
+                    android::env::default_sdk_root()
+                        .map(|sdk_root| (path_value(sdk_root), Layer::Default))
+                }),
+        ),
+        Setting::new(
+            "android.ndk-home",
+            env(android::ndk::NDK_HOME_VARS)
+                .or_else(|| user(global.android_ndk_home().map(path_value))),
+        ),
+        {
+            let (color, layer) = global.color();
+            Setting::new("terminal.color", Some((color.to_string(), layer)))
+        },
+    ])
+}
+
+/// Prints every setting from [`resolve`], along with which layer it came from
+/// if `show_origin` is set.
+pub fn show(cwd: impl AsRef<Path>, show_origin: bool) -> Result<(), Error> {
+    for setting in resolve(cwd)? {
+        if show_origin {
+            println!("{}\t{}", setting.layer(), setting);
+        } else {
+            println!("{}", setting);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn domain(cwd: &Path) -> (Option<String>, String) {
+        let setting = resolve(cwd)
+            .unwrap()
+            .into_iter()
+            .find(|setting| setting.key() == "app.domain")
+            .unwrap();
+        (
+            setting.value().map(ToOwned::to_owned),
+            setting.layer().to_string(),
+        )
+    }
+
+    #[test]
+    fn the_project_config_beats_the_env() {
+        // Only the CLI reads this otherwise, which the tests never go through
+        std::env::set_var("CARGO_MOBILE_DOMAIN", "env.example.org");
+        let project = tempfile::tempdir().unwrap();
+        fs::write(
+            project.path().join(file_name()),
+            "[app]\nname = \"my-app\"\ndomain = \"project.example.org\"\ntemplate-pack = \"bevy\"\n",
+        )
+        .unwrap();
+        let empty = tempfile::tempdir().unwrap();
+        let (in_project, outside_of_project) = (domain(project.path()), domain(empty.path()));
+        std::env::remove_var("CARGO_MOBILE_DOMAIN");
+        assert_eq!(in_project.0.as_deref(), Some("project.example.org"));
+        // The temp dir can be reached through a symlink, i.e. on macOS
+        assert!(in_project.1.starts_with("project:"));
+        assert!(in_project.1.ends_with(&*file_name()));
+        assert_eq!(
+            outside_of_project,
+            (
+                Some("env.example.org".to_owned()),
+                "env:CARGO_MOBILE_DOMAIN".to_owned()
+            )
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! Commands run at points in the build lifecycle, which is how asset
+//! pipelines, code generators, and upload steps get plugged in.
+
+use crate::util::{
+    self,
+    cli::{Report, Reportable},
+    Invocation,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+pub static KEY: &str = "hooks";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Stage {
+    PreBuild,
+    PostBuild,
+    PreRun,
+    PostInstall,
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Stage {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::PreBuild => "pre-build",
+            Self::PostBuild => "post-build",
+            Self::PreRun => "pre-run",
+            Self::PostInstall => "post-install",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Hook {
+    /// Run with the platform's shell, i.e. `sh -c` or `cmd /C`.
+    Command(String),
+    /// A binary in the app's workspace, run with `cargo run --bin`.
+    Plugin { bin: String },
+}
+
+impl Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Command(command) => write!(f, "{}", command),
+            Self::Plugin { bin } => write!(f, "cargo run --bin {}", bin),
+        }
+    }
+}
+
+impl Hook {
+    fn command(&self) -> Invocation {
+        match self {
+            Self::Command(command) => util::shell_invocation(command),
+            Self::Plugin { bin } => {
+                Invocation::impure("cargo").with_args(&["run", "--quiet", "--bin", bin.as_str()])
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    SerializeFailed(serde_json::Error),
+    HookFailed {
+        stage: Stage,
+        hook: String,
+        cause: util::RunWithStdinError,
+    },
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::SerializeFailed(err) => Report::error("Failed to serialize hook context", err),
+            Self::HookFailed { stage, hook, cause } => {
+                Report::error(format!("`{}.{}` hook `{}` failed", KEY, stage, hook), cause)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Raw {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre_build: Vec<Hook>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_build: Vec<Hook>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre_run: Vec<Hook>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_install: Vec<Hook>,
+}
+
+/// What hooks get told about the build. This is given to them as JSON on
+/// stdin, and as `CARGO_MOBILE_*` env vars for the sake of shell scripts.
+#[derive(Debug, Serialize)]
+pub struct Context<'a> {
+    pub platform: &'a str,
+    pub triple: Option<&'a str>,
+    pub profile: &'a str,
+    pub artifacts: Vec<PathBuf>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(platform: &'a str, triple: Option<&'a str>, profile: &'a str) -> Self {
+        Self {
+            platform,
+            triple,
+            profile,
+            artifacts: Vec::new(),
+        }
+    }
+
+    pub fn with_artifact(mut self, artifact: impl Into<PathBuf>) -> Self {
+        self.artifacts.push(artifact.into());
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    stage: &'static str,
+    #[serde(flatten)]
+    context: &'a Context<'a>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Hooks {
+    root_dir: PathBuf,
+    raw: Raw,
+}
+
+impl Hooks {
+    pub fn from_raw(root_dir: &Path, raw: Option<Raw>) -> Self {
+        Self {
+            root_dir: root_dir.to_owned(),
+            raw: raw.unwrap_or_default(),
+        }
+    }
+
+    pub fn get(&self, stage: Stage) -> &[Hook] {
+        match stage {
+            Stage::PreBuild => &self.raw.pre_build,
+            Stage::PostBuild => &self.raw.post_build,
+            Stage::PreRun => &self.raw.pre_run,
+            Stage::PostInstall => &self.raw.post_install,
+        }
+    }
+
+    /// Runs the hooks for `stage` in order from the app root, stopping at the
+    /// first one that fails.
+    pub fn run(&self, stage: Stage, context: &Context<'_>) -> Result<(), Error> {
+        let hooks = self.get(stage);
+        if hooks.is_empty() {
+            return Ok(());
+        }
+        let input = serde_json::to_vec(&Payload {
+            stage: stage.as_str(),
+            context,
+        })
+        .map_err(Error::SerializeFailed)?;
+        let artifacts = context
+            .artifacts
+            .iter()
+            .map(|artifact| artifact.display().to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        for hook in hooks {
+            println!("Running `{}` hook `{}`...", stage, hook);
+            let command = hook
+                .command()
+                .with_current_dir(&self.root_dir)
+                .with_env_var("CARGO_MOBILE_HOOK", stage.as_str())
+                .with_env_var("CARGO_MOBILE_PLATFORM", context.platform)
+                .with_env_var("CARGO_MOBILE_TRIPLE", context.triple.unwrap_or_default())
+                .with_env_var("CARGO_MOBILE_PROFILE", context.profile)
+                // One per line, since paths can contain just about anything
+                // else.
+                .with_env_var("CARGO_MOBILE_ARTIFACTS", &artifacts);
+            util::run_with_stdin(&command, &input, Stdio::inherit(), Stdio::inherit()).map_err(
+                |cause| Error::HookFailed {
+                    stage,
+                    hook: hook.to_string(),
+                    cause,
+                },
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_hooks_are_reported_and_stop_the_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let hooks = Hooks::from_raw(
+            dir.path(),
+            Some(Raw {
+                pre_build: vec![
+                    Hook::Command("echo ran > first.txt".to_owned()),
+                    Hook::Command("exit 3".to_owned()),
+                    Hook::Command("echo ran > third.txt".to_owned()),
+                ],
+                ..Default::default()
+            }),
+        );
+        let context = Context::new("android", Some("aarch64-linux-android"), "debug");
+        match hooks.run(Stage::PreBuild, &context) {
+            Err(Error::HookFailed { stage, hook, .. }) => {
+                assert_eq!(stage, Stage::PreBuild);
+                assert_eq!(hook, "exit 3");
+            }
+            other => panic!("expected the second hook to fail, but got {:?}", other),
+        }
+        // Hooks run from the app root, and stop at the first failure
+        assert!(dir.path().join("first.txt").is_file());
+        assert!(!dir.path().join("third.txt").exists());
+        // Other stages are left alone
+        hooks.run(Stage::PostBuild, &context).unwrap();
+    }
+}
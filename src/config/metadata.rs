@@ -1,6 +1,13 @@
-use crate::util::cli::{Report, Reportable};
+use crate::{
+    opts::Profile,
+    util::{
+        cli::{Report, Reportable},
+        CargoCommand,
+    },
+};
 use serde::Deserialize;
 use std::{
+    collections::BTreeMap,
     fs, io,
     path::{Path, PathBuf},
 };
@@ -32,6 +39,65 @@ impl Reportable for Error {
     }
 }
 
+/// Per-platform cargo settings, which live alongside `features` in each
+/// platform's metadata table.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct CargoSettings {
+    no_default_features: Option<bool>,
+    debug_profile: Option<String>,
+    release_profile: Option<String>,
+    rustflags: Vec<String>,
+    config: BTreeMap<String, toml::Value>,
+}
+
+impl CargoSettings {
+    /// Setting `features` has always implied `no-default-features`, so that's
+    /// still what happens unless it's set explicitly.
+    pub fn no_default_features(&self, features: Option<&[String]>) -> bool {
+        self.no_default_features
+            .unwrap_or_else(|| features.is_some())
+    }
+
+    /// The custom cargo profile to use in place of `profile`, if any.
+    pub fn profile(&self, profile: Profile) -> Option<&str> {
+        match profile {
+            Profile::Debug => self.debug_profile.as_deref(),
+            Profile::Release => self.release_profile.as_deref(),
+        }
+    }
+
+    /// The name of the directory in `target/<triple>` that cargo puts build
+    /// output in for `profile`. Cargo's `dev` profile is special-cased to use
+    /// `debug`, which is also the directory for every profile in the default
+    /// setup.
+    pub fn profile_dir(&self, profile: Profile) -> &str {
+        match self.profile(profile) {
+            Some("dev") => "debug",
+            Some(custom) => custom,
+            None => profile.as_str(),
+        }
+    }
+
+    pub fn rustflags(&self) -> &[String] {
+        &self.rustflags
+    }
+
+    pub fn config(&self) -> &BTreeMap<String, toml::Value> {
+        &self.config
+    }
+
+    pub fn apply<'a>(&'a self, command: CargoCommand<'a>, profile: Profile) -> CargoCommand<'a> {
+        self.config.iter().fold(
+            command
+                .with_release(profile.release())
+                .with_profile(self.profile(profile))
+                .with_rustflags(Some(&self.rustflags)),
+            |command, (key, value)| command.with_config_override(key, value.clone()),
+        )
+    }
+}
+
 #[derive(Debug, Default, Deserialize)]
 pub struct Metadata {
     #[cfg(target_os = "macos")]
@@ -64,6 +130,7 @@ impl Metadata {
         Ok(cargo_toml.package.metadata.unwrap_or_default())
     }
 
+    #[cfg(target_os = "macos")]
     pub fn apple(&self) -> &crate::apple::config::Metadata {
         &self.apple
     }
@@ -72,3 +139,31 @@ impl Metadata {
         &self.android
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_overrides_are_passed_with_their_types() {
+        let settings = toml::from_str::<CargoSettings>(
+            "[config]\n\"build.jobs\" = 2\n\"profile.dev.lto\" = \"thin\"\n",
+        )
+        .unwrap();
+        let command = settings
+            .apply(CargoCommand::new("build"), Profile::Debug)
+            .into_command_impure()
+            .unwrap();
+        let args = command
+            .args()
+            .iter()
+            .map(|arg| arg.to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--config", "build.jobs=2"]));
+        assert!(args
+            .windows(2)
+            .any(|pair| pair == ["--config", "profile.dev.lto=\"thin\""]));
+    }
+}
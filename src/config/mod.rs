@@ -1,15 +1,23 @@
 pub mod app;
+pub mod global;
+pub mod hooks;
 pub mod metadata;
+pub mod preset;
 mod raw;
+pub mod template;
+mod validate;
 
-use self::{app::App, raw::*};
+use self::{app::App, hooks::Hooks, preset::Preset, raw::*, template::Template};
 #[cfg(target_os = "macos")]
 use crate::apple;
 use crate::{
     android,
     opts::NonInteractive,
-    templating,
-    util::cli::{Report, Reportable, TextWrapper},
+    templating::{self, Pack},
+    util::{
+        cli::{Report, Reportable, TextWrapper},
+        prompt::Prompter,
+    },
 };
 use serde::Serialize;
 use std::{
@@ -24,6 +32,7 @@ pub fn file_name() -> String {
 
 #[derive(Debug)]
 pub enum FromRawError {
+    TemplateConfigInvalid(template::Error),
     AppConfigInvalid(app::Error),
     #[cfg(target_os = "macos")]
     AppleConfigInvalid(apple::config::Error),
@@ -33,6 +42,7 @@ pub enum FromRawError {
 impl FromRawError {
     pub fn report(&self, msg: &str) -> Report {
         match self {
+            Self::TemplateConfigInvalid(err) => err.report(msg),
             Self::AppConfigInvalid(err) => err.report(msg),
             #[cfg(target_os = "macos")]
             Self::AppleConfigInvalid(err) => err.report(msg),
@@ -44,6 +54,7 @@ impl FromRawError {
 #[derive(Debug)]
 pub enum GenError {
     PromptFailed(PromptError),
+    TemplateVarPromptFailed(template::PromptError),
     DetectFailed(DetectError),
     CanonicalizeFailed(io::Error),
     FromRawFailed(FromRawError),
@@ -55,6 +66,7 @@ impl Reportable for GenError {
         let msg = "Failed to generate config";
         match self {
             Self::PromptFailed(err) => err.report(),
+            Self::TemplateVarPromptFailed(err) => Report::error(msg, err),
             Self::DetectFailed(err) => err.report(),
             Self::CanonicalizeFailed(err) => {
                 Report::error(msg, format!("Failed to canonicalize root dir: {}", err))
@@ -101,6 +113,7 @@ impl Origin {
 #[serde(rename_all = "kebab-case")]
 pub struct Config {
     app: App,
+    template: Template,
     #[cfg(target_os = "macos")]
     apple: apple::config::Config,
     android: android::config::Config,
@@ -108,7 +121,14 @@ pub struct Config {
 
 impl Config {
     fn from_raw(root_dir: PathBuf, raw: Raw) -> Result<Self, FromRawError> {
-        let app = App::from_raw(root_dir, raw.app).map_err(FromRawError::AppConfigInvalid)?;
+        let mut template = Template::from_raw(&root_dir, raw.template)
+            .map_err(FromRawError::TemplateConfigInvalid)?;
+        let hooks = Hooks::from_raw(&root_dir, raw.hooks);
+        let app = App::from_raw(root_dir, raw.app, template.registry(), hooks)
+            .map_err(FromRawError::AppConfigInvalid)?;
+        template
+            .resolve_vars(app.template_pack())
+            .map_err(FromRawError::TemplateConfigInvalid)?;
         #[cfg(target_os = "macos")]
         let apple = apple::config::Config::from_raw(app.clone(), raw.apple)
             .map_err(FromRawError::AppleConfigInvalid)?;
@@ -116,6 +136,7 @@ impl Config {
             .map_err(FromRawError::AndroidConfigInvalid)?;
         Ok(Self {
             app,
+            template,
             #[cfg(target_os = "macos")]
             apple,
             android,
@@ -125,17 +146,33 @@ impl Config {
     fn gen(
         cwd: impl AsRef<Path>,
         non_interactive: NonInteractive,
+        preset: &Preset,
+        prompter: &dyn Prompter,
         wrapper: &TextWrapper,
     ) -> Result<Self, GenError> {
-        let raw = if non_interactive.no() {
-            Raw::prompt(wrapper).map_err(GenError::PromptFailed)
+        let mut raw = if non_interactive.no() {
+            Raw::prompt(wrapper, preset, prompter).map_err(GenError::PromptFailed)
         } else {
-            Raw::detect().map_err(GenError::DetectFailed)
+            Raw::detect(preset).map_err(GenError::DetectFailed)
         }?;
         let root_dir = cwd
             .as_ref()
             .canonicalize()
             .map_err(GenError::CanonicalizeFailed)?;
+        if non_interactive.no() {
+            // If the pack can't be found, `from_raw` will tell us all about it.
+            let pack = template::registry(&root_dir, raw.template.as_ref())
+                .ok()
+                .and_then(|registry| {
+                    Pack::lookup_app(raw.app.template_pack(), registry.as_deref()).ok()
+                });
+            if let Some(pack) = pack {
+                raw.template
+                    .get_or_insert_with(Default::default)
+                    .prompt_missing_vars(&pack, prompter)
+                    .map_err(GenError::TemplateVarPromptFailed)?;
+            }
+        }
         let config =
             Self::from_raw(root_dir.clone(), raw.clone()).map_err(GenError::FromRawFailed)?;
         log::info!("generated config: {:#?}", config);
@@ -143,13 +180,45 @@ impl Config {
         Ok(config)
     }
 
+    /// Loads the config, or returns `None` if there isn't one.
+    pub fn load(cwd: impl AsRef<Path>) -> Result<Option<Self>, LoadOrGenError> {
+        Raw::load(cwd)
+            .map_err(LoadOrGenError::LoadFailed)?
+            .map(|(root_dir, raw)| {
+                Self::from_raw(root_dir.clone(), raw).map_err(|cause| {
+                    LoadOrGenError::FromRawFailed {
+                        path: root_dir,
+                        cause,
+                    }
+                })
+            })
+            .transpose()
+    }
+
+    /// Loads the config, or generates one if there isn't one yet. Anything
+    /// `preset` gives is used instead of prompting for it when generating, and
+    /// `prompter` answers the rest. When loading, the preset's template pack
+    /// overrides `app.template-pack`; everything else in it is ignored.
     pub fn load_or_gen(
         cwd: impl AsRef<Path>,
         non_interactive: NonInteractive,
+        preset: &Preset,
+        prompter: &dyn Prompter,
         wrapper: &TextWrapper,
     ) -> Result<(Self, Origin), LoadOrGenError> {
         let cwd = cwd.as_ref();
-        if let Some((root_dir, raw)) = Raw::load(cwd).map_err(LoadOrGenError::LoadFailed)? {
+        if let Some((root_dir, mut raw)) = Raw::load(cwd).map_err(LoadOrGenError::LoadFailed)? {
+            if let Some(template_pack) = preset.template_pack.as_deref() {
+                if template_pack != raw.app.template_pack() {
+                    log::warn!(
+                        "using template pack {:?} instead of {:?}; set `{}.template-pack` to make this permanent",
+                        template_pack,
+                        raw.app.template_pack(),
+                        app::KEY,
+                    );
+                    raw.app.set_template_pack(template_pack);
+                }
+            }
             Self::from_raw(root_dir.clone(), raw)
                 .map(|config| (config, Origin::Loaded))
                 .map_err(|cause| LoadOrGenError::FromRawFailed {
@@ -157,7 +226,7 @@ impl Config {
                     cause,
                 })
         } else {
-            Self::gen(cwd, non_interactive, wrapper)
+            Self::gen(cwd, non_interactive, preset, prompter, wrapper)
                 .map(|config| (config, Origin::FreshlyMinted))
                 .map_err(LoadOrGenError::GenFailed)
         }
@@ -171,6 +240,10 @@ impl Config {
         &self.app
     }
 
+    pub fn template(&self) -> &Template {
+        &self.template
+    }
+
     #[cfg(target_os = "macos")]
     pub fn apple(&self) -> &apple::config::Config {
         &self.apple
@@ -0,0 +1,15 @@
+use std::collections::BTreeMap;
+
+/// Answers given up front, i.e. via flags or environment variables, which are
+/// used as-is instead of being prompted for or detected when generating a
+/// config. This is what makes it possible to run `init` from a script.
+#[derive(Clone, Debug, Default)]
+pub struct Preset {
+    pub name: Option<String>,
+    pub stylized_name: Option<String>,
+    pub domain: Option<String>,
+    pub template_pack: Option<String>,
+    pub platforms: Option<Vec<String>>,
+    pub development_team: Option<String>,
+    pub template_vars: BTreeMap<String, String>,
+}
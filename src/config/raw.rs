@@ -1,9 +1,13 @@
-use super::app;
+use super::{app, hooks, preset::Preset, template, validate};
 #[cfg(target_os = "macos")]
 use crate::apple;
 use crate::{
     android,
-    util::cli::{Report, Reportable, TextWrapper},
+    util::{
+        self,
+        cli::{Report, Reportable, TextWrapper},
+        prompt::Prompter,
+    },
 };
 use serde::{Deserialize, Serialize};
 
@@ -67,6 +71,10 @@ pub enum LoadError {
         path: PathBuf,
         cause: toml::de::Error,
     },
+    Invalid {
+        path: PathBuf,
+        problems: validate::Problems,
+    },
 }
 
 impl Display for LoadError {
@@ -83,6 +91,9 @@ impl Display for LoadError {
             Self::ParseFailed { path, cause } => {
                 write!(f, "Failed to parse config file at {:?}: {}", path, cause)
             }
+            Self::Invalid { path, problems } => {
+                write!(f, "Config file at {:?} is invalid:\n{}", path, problems)
+            }
         }
     }
 }
@@ -106,30 +117,43 @@ impl Reportable for WriteError {
 #[serde(rename_all = "kebab-case")]
 pub struct Raw {
     pub app: app::Raw,
+    pub template: Option<template::Raw>,
+    pub hooks: Option<hooks::Raw>,
     #[cfg(target_os = "macos")]
     pub apple: Option<apple::config::Raw>,
     pub android: Option<android::config::Raw>,
 }
 
 impl Raw {
-    pub fn prompt(wrapper: &TextWrapper) -> Result<Self, PromptError> {
-        let app = app::Raw::prompt(wrapper).map_err(PromptError::AppFailed)?;
+    pub fn prompt(
+        wrapper: &TextWrapper,
+        preset: &Preset,
+        prompter: &dyn Prompter,
+    ) -> Result<Self, PromptError> {
+        let app = app::Raw::prompt(wrapper, preset, prompter).map_err(PromptError::AppFailed)?;
         #[cfg(target_os = "macos")]
-        let apple = apple::config::Raw::prompt(wrapper).map_err(PromptError::AppleFailed)?;
+        let apple =
+            apple::config::Raw::prompt(wrapper, preset.development_team.as_deref(), prompter)
+                .map_err(PromptError::AppleFailed)?;
         Ok(Self {
             app,
+            template: template::Raw::from_preset(preset),
+            hooks: None,
             #[cfg(target_os = "macos")]
             apple: Some(apple),
             android: None,
         })
     }
 
-    pub fn detect() -> Result<Self, DetectError> {
-        let app = app::Raw::detect().map_err(DetectError::AppFailed)?;
+    pub fn detect(preset: &Preset) -> Result<Self, DetectError> {
+        let app = app::Raw::detect(preset).map_err(DetectError::AppFailed)?;
         #[cfg(target_os = "macos")]
-        let apple = apple::config::Raw::detect().map_err(DetectError::AppleFailed)?;
+        let apple = apple::config::Raw::detect(preset.development_team.as_deref())
+            .map_err(DetectError::AppleFailed)?;
         Ok(Self {
             app,
+            template: template::Raw::from_preset(preset),
+            hooks: None,
             #[cfg(target_os = "macos")]
             apple: Some(apple),
             android: None,
@@ -159,11 +183,23 @@ impl Raw {
             .map_err(LoadError::DiscoverFailed)?
             .map(|root_dir| {
                 let path = root_dir.join(super::file_name());
-                let bytes = fs::read(&path).map_err(|cause| LoadError::ReadFailed {
+                let source = fs::read_to_string(&path).map_err(|cause| LoadError::ReadFailed {
                     path: path.clone(),
                     cause,
                 })?;
-                toml::from_slice::<Self>(&bytes)
+                // Validating first means that typos get reported all at once,
+                // rather than as whatever serde happens to trip over first.
+                let value = toml::from_str::<toml::Value>(&source).map_err(|cause| {
+                    LoadError::ParseFailed {
+                        path: path.clone(),
+                        cause,
+                    }
+                })?;
+                validate::validate(&source, &value).map_err(|problems| LoadError::Invalid {
+                    path: path.clone(),
+                    problems,
+                })?;
+                toml::from_str::<Self>(&source)
                     .map(|raw| (root_dir, raw))
                     .map_err(|cause| LoadError::ParseFailed {
                         path: path.clone(),
@@ -177,6 +213,80 @@ impl Raw {
         let bytes = toml::to_vec(self).map_err(WriteError::SerializeFailed)?;
         let path = root_dir.join(super::file_name());
         log::info!("writing config to {:?}", path);
-        fs::write(path, bytes).map_err(WriteError::WriteFailed)
+        util::write_str_if_changed(path, bytes)
+            .map(|_| ())
+            .map_err(WriteError::WriteFailed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::prompt::Scripted;
+
+    fn wrapper() -> TextWrapper {
+        TextWrapper::with_splitter(80, textwrap::NoHyphenation)
+    }
+
+    // Giving the template pack and development team up front keeps these from
+    // depending on what's installed or what machine they're run on.
+    fn preset() -> Preset {
+        Preset {
+            template_pack: Some("bevy".to_owned()),
+            development_team: Some("ABCDE12345".to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn init_prompts_are_answered_in_order() {
+        let prompter = Scripted::new(vec!["my-app", "", "not a domain", "example.org", "android"]);
+        let raw = Raw::prompt(&wrapper(), &preset(), &prompter).unwrap();
+        assert_eq!(raw.app.name, "my-app");
+        // Empty answers pick the default, which is derived from the name
+        assert_eq!(raw.app.stylized_name.as_deref(), Some("My App"));
+        // Invalid answers get asked again
+        assert_eq!(raw.app.domain, "example.org");
+        assert_eq!(raw.app.platforms, Some(vec!["android".to_owned()]));
+        assert_eq!(raw.app.template_pack(), "bevy");
+        assert_eq!(prompter.remaining(), 0);
+    }
+
+    #[test]
+    fn presets_are_never_prompted_for() {
+        let preset = Preset {
+            name: Some("my-app".to_owned()),
+            stylized_name: Some("My Great App".to_owned()),
+            domain: Some("example.org".to_owned()),
+            platforms: Some(vec!["ios".to_owned()]),
+            template_vars: vec![("color".to_owned(), "red".to_owned())]
+                .into_iter()
+                .collect(),
+            ..preset()
+        };
+        // Any prompt at all would run out of answers and fail
+        let raw = Raw::prompt(&wrapper(), &preset, &Scripted::new(Vec::<String>::new())).unwrap();
+        assert_eq!(raw.app.name, "my-app");
+        assert_eq!(raw.app.stylized_name.as_deref(), Some("My Great App"));
+        assert_eq!(raw.app.domain, "example.org");
+        assert_eq!(raw.app.platforms, Some(vec!["ios".to_owned()]));
+        assert!(raw.template.is_some());
+    }
+
+    // This is what `--defaults` and `--non-interactive` do, which doesn't take
+    // a prompter at all.
+    #[test]
+    fn non_interactive_init_uses_presets_and_detected_defaults() {
+        let raw = Raw::detect(&Preset {
+            domain: Some("example.org".to_owned()),
+            ..preset()
+        })
+        .unwrap();
+        // Tests run from the package root, so this comes from our Cargo.toml
+        assert_eq!(raw.app.name, env!("CARGO_PKG_NAME"));
+        assert_eq!(raw.app.domain, "example.org");
+        assert_eq!(raw.app.platforms, None);
+        assert_eq!(raw.app.template_pack(), "bevy");
+        assert!(raw.template.is_none());
     }
 }
@@ -0,0 +1,170 @@
+use super::preset::Preset;
+use crate::{
+    templating::Pack,
+    util::{self, cli::Report, prompt::Prompter},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    io,
+    path::{Path, PathBuf},
+};
+
+pub static KEY: &str = "template";
+
+#[derive(Debug)]
+pub enum Error {
+    NoHomeDir(util::NoHomeDir),
+    RegistryMissing { registry: PathBuf },
+    VarsMissing { vars: Vec<String> },
+}
+
+impl Error {
+    pub fn report(&self, msg: &str) -> Report {
+        match self {
+            Self::NoHomeDir(err) => Report::error(msg, err),
+            Self::RegistryMissing { registry } => Report::error(
+                msg,
+                format!("`{}.registry` {:?} doesn't exist", KEY, registry),
+            ),
+            Self::VarsMissing { vars } => Report::action_request(
+                msg,
+                format!(
+                    "The template pack requires {}, so please set {} in `{}.vars`.",
+                    util::list_display(
+                        &vars
+                            .iter()
+                            .map(|var| format!("`{}`", var))
+                            .collect::<Vec<_>>()
+                    ),
+                    if vars.len() == 1 { "it" } else { "them" },
+                    KEY,
+                ),
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PromptError {
+    var: String,
+    cause: io::Error,
+}
+
+impl Display for PromptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Failed to prompt for template variable `{}`: {}",
+            self.var, self.cause
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Raw {
+    pub registry: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vars: BTreeMap<String, String>,
+}
+
+impl Raw {
+    /// Only returns something if `preset` sets any variables, so that we don't
+    /// write an empty `[template]` section.
+    pub fn from_preset(preset: &Preset) -> Option<Self> {
+        if preset.template_vars.is_empty() {
+            None
+        } else {
+            Some(Self {
+                registry: None,
+                vars: preset.template_vars.clone(),
+            })
+        }
+    }
+
+    /// Prompts for any variables `pack` requires that aren't already set.
+    pub fn prompt_missing_vars(
+        &mut self,
+        pack: &Pack,
+        prompter: &dyn Prompter,
+    ) -> Result<(), PromptError> {
+        for (name, var) in pack.vars() {
+            if var.required() && !self.vars.contains_key(name) {
+                let label = var.description.as_deref().unwrap_or(name);
+                let value = prompter
+                    .default(label, None, None)
+                    .map_err(|cause| PromptError {
+                        var: name.to_owned(),
+                        cause,
+                    })?;
+                self.vars.insert(name.to_owned(), value);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Resolves `template.registry`, which can be relative to the app root.
+pub fn registry(root_dir: &Path, raw: Option<&Raw>) -> Result<Option<PathBuf>, Error> {
+    raw.and_then(|raw| raw.registry.as_deref())
+        .map(|registry| {
+            util::expand_home(registry)
+                .map(|registry| root_dir.join(registry))
+                .map_err(Error::NoHomeDir)
+        })
+        .transpose()
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Template {
+    registry: Option<PathBuf>,
+    vars: BTreeMap<String, String>,
+}
+
+impl Template {
+    pub fn from_raw(root_dir: &Path, raw: Option<Raw>) -> Result<Self, Error> {
+        let registry = registry(root_dir, raw.as_ref())?;
+        if let Some(registry) = &registry {
+            if !registry.is_dir() {
+                return Err(Error::RegistryMissing {
+                    registry: registry.clone(),
+                });
+            }
+        }
+        Ok(Self {
+            registry,
+            vars: raw.map(|raw| raw.vars).unwrap_or_default(),
+        })
+    }
+
+    pub fn registry(&self) -> Option<&Path> {
+        self.registry.as_deref()
+    }
+
+    /// Fills in defaults for the variables `pack` declares, and checks that
+    /// the required ones are all set.
+    pub fn resolve_vars(&mut self, pack: &Pack) -> Result<(), Error> {
+        let mut missing = Vec::new();
+        for (name, var) in pack.vars() {
+            if !self.vars.contains_key(name) {
+                if let Some(default) = &var.default {
+                    self.vars.insert(name.to_owned(), default.clone());
+                } else {
+                    missing.push(name.to_owned());
+                }
+            }
+        }
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::VarsMissing { vars: missing })
+        }
+    }
+
+    pub fn vars(&self) -> &BTreeMap<String, String> {
+        &self.vars
+    }
+}
@@ -0,0 +1,831 @@
+use super::app::name;
+use crate::android::ndk;
+use std::fmt::{self, Display};
+
+// The oldest iOS version Xcode 12 can deploy to. There's no upper bound, since
+// we'd otherwise reject every new release until we got around to bumping it.
+const MIN_IOS_DEPLOYMENT_TARGET: u32 = 9;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Kind {
+    String,
+    Bool,
+    Integer,
+    StringArray,
+    Table,
+    // Anything goes, i.e. for user-defined template vars.
+    Any,
+}
+
+impl Kind {
+    fn matches(self, value: &toml::Value) -> bool {
+        match self {
+            Self::String => value.is_str(),
+            Self::Bool => value.is_bool(),
+            Self::Integer => value.is_integer(),
+            Self::StringArray => value
+                .as_array()
+                .map_or(false, |array| array.iter().all(toml::Value::is_str)),
+            Self::Table => value.is_table(),
+            Self::Any => true,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Self::String => "a string",
+            Self::Bool => "`true` or `false`",
+            Self::Integer => "an integer",
+            Self::StringArray => "an array of strings",
+            Self::Table => "a table",
+            Self::Any => "anything",
+        }
+    }
+}
+
+struct Field {
+    key: &'static str,
+    kind: Kind,
+    required: bool,
+}
+
+const fn field(key: &'static str, kind: Kind) -> Field {
+    Field {
+        key,
+        kind,
+        required: false,
+    }
+}
+
+const fn required(key: &'static str, kind: Kind) -> Field {
+    Field {
+        key,
+        kind,
+        required: true,
+    }
+}
+
+// This needs to be kept in sync with the `Raw` config structs! Tables that
+// aren't listed here (i.e. `template.vars`) aren't checked for unknown keys.
+static SCHEMA: &[(&str, &[Field])] = &[
+    (
+        "",
+        &[
+            required("app", Kind::Table),
+            field("template", Kind::Table),
+            field("hooks", Kind::Table),
+            field("apple", Kind::Table),
+            field("android", Kind::Table),
+        ],
+    ),
+    (
+        "app",
+        &[
+            required("name", Kind::String),
+            field("stylized-name", Kind::String),
+            required("domain", Kind::String),
+            field("asset-dir", Kind::String),
+            field("hard-copy", Kind::Bool),
+            field("dir-mode", Kind::Integer),
+            field("platforms", Kind::StringArray),
+            Field {
+                key: "template-pack",
+                kind: Kind::String,
+                required: cfg!(not(feature = "brainium")),
+            },
+            field("icon", Kind::Table),
+        ],
+    ),
+    (
+        "app.icon",
+        &[
+            required("source", Kind::String),
+            field("background", Kind::String),
+        ],
+    ),
+    (
+        "template",
+        &[field("registry", Kind::String), field("vars", Kind::Any)],
+    ),
+    (
+        "hooks",
+        // Each of these is an array of hooks, which `check_hooks` looks at.
+        &[
+            field("pre-build", Kind::Any),
+            field("post-build", Kind::Any),
+            field("pre-run", Kind::Any),
+            field("post-install", Kind::Any),
+        ],
+    ),
+    (
+        "apple",
+        &[
+            required("development-team", Kind::String),
+            field("project-dir", Kind::String),
+            field("ios-deployment-target", Kind::String),
+            field("platforms", Kind::StringArray),
+            field("tvos-deployment-target", Kind::String),
+            field("watchos-deployment-target", Kind::String),
+            field("project-generator", Kind::String),
+            field("ios-no-default-features", Kind::Bool),
+            field("ios-features", Kind::StringArray),
+            field("macos-no-default-features", Kind::Bool),
+            field("macos-features", Kind::StringArray),
+            field("signing", Kind::Table),
+        ],
+    ),
+    (
+        "apple.signing",
+        &[
+            field("team-id", Kind::String),
+            field("identity", Kind::String),
+            field("provisioning-profile", Kind::String),
+            field("style", Kind::String),
+            field("export-method", Kind::String),
+        ],
+    ),
+    (
+        "android",
+        &[
+            field("min-sdk-version", Kind::Integer),
+            field("min-ndk-version", Kind::String),
+            field("vulkan-validation", Kind::Bool),
+            field("project-dir", Kind::String),
+            field("no-default-features", Kind::Bool),
+            field("features", Kind::StringArray),
+            field("signing", Kind::Table),
+            field("gradle", Kind::Table),
+        ],
+    ),
+    (
+        "android.signing",
+        &[
+            required("keystore", Kind::String),
+            required("key-alias", Kind::String),
+            field("store-password-env", Kind::String),
+            field("key-password-env", Kind::String),
+        ],
+    ),
+    (
+        "android.gradle",
+        &[
+            field("offline", Kind::Bool),
+            field("daemon", Kind::Bool),
+            field("build-cache", Kind::Bool),
+            field("user-home", Kind::String),
+            field("jvm-args", Kind::StringArray),
+            field("properties", Kind::Table),
+        ],
+    ),
+];
+
+// The same goes for the user config, which only holds defaults.
+static GLOBAL_SCHEMA: &[(&str, &[Field])] = &[
+    (
+        "",
+        &[
+            field("app", Kind::Table),
+            field("android", Kind::Table),
+            field("terminal", Kind::Table),
+        ],
+    ),
+    (
+        "app",
+        &[
+            field("domain", Kind::String),
+            field("template-pack", Kind::String),
+        ],
+    ),
+    (
+        "android",
+        &[
+            field("sdk-root", Kind::String),
+            field("ndk-home", Kind::String),
+        ],
+    ),
+    ("terminal", &[field("color", Kind::String)]),
+];
+
+static JAVA_KEYWORDS: &[&str] = &[
+    "abstract",
+    "assert",
+    "boolean",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extends",
+    "false",
+    "final",
+    "finally",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "implements",
+    "import",
+    "instanceof",
+    "int",
+    "interface",
+    "long",
+    "native",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "short",
+    "static",
+    "strictfp",
+    "super",
+    "switch",
+    "synchronized",
+    "this",
+    "throw",
+    "throws",
+    "transient",
+    "true",
+    "try",
+    "void",
+    "volatile",
+    "while",
+];
+
+#[derive(Debug)]
+pub struct Problem {
+    key: String,
+    line: Option<usize>,
+    msg: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        write!(f, "`{}`: {}", self.key, self.msg)
+    }
+}
+
+#[derive(Debug)]
+pub struct Problems(Vec<Problem>);
+
+impl Display for Problems {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, problem) in self.0.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+fn join(table: &str, key: &str) -> String {
+    if table.is_empty() {
+        key.to_owned()
+    } else {
+        format!("{}.{}", table, key)
+    }
+}
+
+fn unquote(s: &str) -> &str {
+    s.trim().trim_matches(|c| c == '"' || c == '\'')
+}
+
+// Finds the line `key` is set on within `table`, or the line of the table
+// header if the key isn't set on its own line (i.e. it's in an inline table or
+// missing entirely). Lines are numbered from 1, like editors do.
+fn find_line(source: &str, table: &str, key: Option<&str>) -> Option<usize> {
+    let mut current = String::new();
+    let mut header_line = None;
+    for (index, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[') {
+            current = header
+                .trim_start_matches('[')
+                .split(']')
+                .next()
+                .unwrap_or_default()
+                .split('.')
+                .map(unquote)
+                .collect::<Vec<_>>()
+                .join(".");
+            if current == table {
+                header_line = Some(index + 1);
+            }
+        } else if let Some(key) = key {
+            let found = current == table
+                && line
+                    .split('=')
+                    .next()
+                    .map_or(false, |lhs| line.contains('=') && unquote(lhs) == key);
+            if found {
+                return Some(index + 1);
+            }
+        }
+    }
+    header_line
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substituted = prev + if a == *b { 0 } else { 1 };
+            prev = row[j + 1];
+            row[j + 1] = substituted.min(prev + 1).min(row[j] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn suggest<'a>(key: &str, fields: &'a [Field]) -> Option<&'a str> {
+    fields
+        .iter()
+        .map(|field| (edit_distance(key, field.key), field.key))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, key)| key)
+}
+
+fn java_identifier(segment: &str) -> bool {
+    let mut chars = segment.chars();
+    chars.next().map_or(false, |c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !JAVA_KEYWORDS.contains(&segment)
+}
+
+struct Validator<'a> {
+    schema: &'static [(&'static str, &'static [Field])],
+    source: &'a str,
+    problems: Vec<Problem>,
+}
+
+impl<'a> Validator<'a> {
+    fn problem(&mut self, table: &str, key: &str, msg: impl Into<String>) {
+        self.problems.push(Problem {
+            key: join(table, key),
+            line: find_line(self.source, table, Some(key)),
+            msg: msg.into(),
+        });
+    }
+
+    fn check_table(&mut self, table: &str, value: &toml::value::Table) {
+        let fields = match self.schema.iter().find(|(name, _)| *name == table) {
+            Some((_, fields)) => *fields,
+            None => return,
+        };
+        for (key, value) in value {
+            if let Some(field) = fields.iter().find(|field| field.key == key) {
+                if !field.kind.matches(value) {
+                    self.problem(
+                        table,
+                        key,
+                        format!("expected {}, but found {}", field.kind.describe(), value),
+                    );
+                } else if let toml::Value::Table(value) = value {
+                    self.check_table(&join(table, key), value);
+                }
+            } else {
+                let msg = if let Some(suggested) = suggest(key, fields) {
+                    format!("unknown key; did you mean `{}`?", suggested)
+                } else {
+                    "unknown key".to_owned()
+                };
+                self.problem(table, key, msg);
+            }
+        }
+        for field in fields.iter().filter(|field| field.required) {
+            if !value.contains_key(field.key) {
+                self.problems.push(Problem {
+                    key: join(table, field.key),
+                    line: find_line(self.source, table, None),
+                    msg: "missing, but it's required".to_owned(),
+                });
+            }
+        }
+    }
+
+    fn check_app(&mut self, app: &toml::value::Table) {
+        let name = app.get("name").and_then(toml::Value::as_str);
+        let domain = app.get("domain").and_then(toml::Value::as_str);
+        let name = name.and_then(|name| match name::validate(name) {
+            Ok(name) => Some(name),
+            Err(err) => {
+                self.problem("app", "name", err.to_string());
+                None
+            }
+        });
+        let domain = domain.and_then(|domain| {
+            if publicsuffix::Domain::has_valid_syntax(domain) {
+                Some(domain)
+            } else {
+                self.problem(
+                    "app",
+                    "domain",
+                    format!("{:?} isn't valid domain syntax", domain),
+                );
+                None
+            }
+        });
+        if let (Some(name), Some(domain)) = (name, domain) {
+            self.check_identifiers(name, domain);
+        }
+    }
+
+    // The app name and domain together make up the iOS bundle identifier and
+    // the Android package name, which are both pickier than either is alone.
+    fn check_identifiers(&mut self, name: &str, domain: &str) {
+        use heck::SnekCase as _;
+        let reverse_domain = domain.split('.').rev().collect::<Vec<_>>().join(".");
+        let bundle_id_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '.';
+        let bundle_id = format!("{}.{}", reverse_domain, name);
+        if !bundle_id.chars().all(bundle_id_char) {
+            let key = if name.chars().all(bundle_id_char) {
+                "domain"
+            } else {
+                "name"
+            };
+            self.problem(
+                "app",
+                key,
+                format!(
+                    "the iOS bundle identifier would be {:?}, but only letters, numbers, hyphens, and periods are allowed in one",
+                    bundle_id
+                ),
+            );
+        }
+        let name_snake = name.to_snek_case();
+        let package = format!("{}.{}", reverse_domain, name_snake);
+        for segment in reverse_domain.split('.') {
+            if !java_identifier(segment) {
+                self.problem(
+                    "app",
+                    "domain",
+                    format!(
+                        "the Android package name would be {:?}, but {:?} isn't a valid Java package segment; segments need to start with a letter, can only contain letters, numbers, and underscores, and can't be Java keywords",
+                        package, segment
+                    ),
+                );
+            }
+        }
+        if !java_identifier(&name_snake) {
+            self.problem(
+                "app",
+                "name",
+                format!(
+                    "the Android package name would be {:?}, but {:?} is a Java keyword",
+                    package, name_snake
+                ),
+            );
+        }
+    }
+
+    // A hook is either a shell command or a table naming a plugin binary.
+    fn check_hooks(&mut self, hooks: &toml::value::Table) {
+        for (stage, value) in hooks {
+            let valid = value.as_array().map_or(false, |hooks| {
+                hooks.iter().all(|hook| {
+                    hook.is_str()
+                        || hook.as_table().map_or(false, |table| {
+                            table.len() == 1 && table.get("bin").map_or(false, toml::Value::is_str)
+                        })
+                })
+            });
+            if !valid {
+                self.problem(
+                    "hooks",
+                    stage,
+                    "expected an array of hooks, where each one is either a shell command or a table like `{ bin = \"my-plugin\" }`",
+                );
+            }
+        }
+    }
+
+    fn check_android(&mut self, android: &toml::value::Table) {
+        if let Some(version) = android
+            .get("min-sdk-version")
+            .and_then(toml::Value::as_integer)
+        {
+            // The NDK we'll actually build with isn't known until later, so
+            // this only checks against the oldest one we support, or against
+            // `min-ndk-version` if that's set.
+            let ndk_version = android
+                .get("min-ndk-version")
+                .and_then(toml::Value::as_str)
+                .and_then(|version| version.parse().ok())
+                .unwrap_or(ndk::MIN_NDK_VERSION);
+            let min_supported = ndk::min_supported_sdk(ndk_version).unwrap_or(1);
+            if version < i64::from(min_supported) {
+                self.problem(
+                    "android",
+                    "min-sdk-version",
+                    format!(
+                        "{} isn't a supported API level; NDK {} needs it to be at least {}",
+                        version, ndk_version, min_supported
+                    ),
+                );
+            }
+        }
+        if let Some(version) = android.get("min-ndk-version").and_then(toml::Value::as_str) {
+            if version.parse::<ndk::Version>().is_err() {
+                self.problem(
+                    "android",
+                    "min-ndk-version",
+                    format!(
+                        "{:?} isn't a valid NDK version; it should look like \"r21e\" or \"21.4\"",
+                        version
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_apple(&mut self, apple: &toml::value::Table) {
+        if let Some(target) = apple
+            .get("ios-deployment-target")
+            .and_then(toml::Value::as_str)
+        {
+            let mut parts = target.split('.').map(str::parse::<u32>);
+            let major = parts.next().and_then(Result::ok);
+            let rest_valid = parts.all(|part| part.is_ok());
+            match major {
+                Some(major) if rest_valid && major >= MIN_IOS_DEPLOYMENT_TARGET => (),
+                Some(_) if rest_valid => self.problem(
+                    "apple",
+                    "ios-deployment-target",
+                    format!(
+                        "iOS {} isn't supported; it needs to be at least {}.0",
+                        target, MIN_IOS_DEPLOYMENT_TARGET
+                    ),
+                ),
+                _ => self.problem(
+                    "apple",
+                    "ios-deployment-target",
+                    format!("{:?} isn't a version number, like \"12.0\"", target),
+                ),
+            }
+        }
+        for key in &["tvos-deployment-target", "watchos-deployment-target"] {
+            if let Some(target) = apple.get(*key).and_then(toml::Value::as_str) {
+                if !target.split('.').all(|part| part.parse::<u32>().is_ok()) {
+                    self.problem(
+                        "apple",
+                        key,
+                        format!("{:?} isn't a version number, like \"12.0\"", target),
+                    );
+                }
+            }
+        }
+        if let Some(platforms) = apple.get("platforms").and_then(toml::Value::as_array) {
+            for platform in platforms.iter().filter_map(toml::Value::as_str) {
+                if !["tvos", "watchos", "catalyst"].contains(&platform) {
+                    self.problem(
+                        "apple",
+                        "platforms",
+                        format!(
+                            "{:?} isn't an optional platform; it needs to be \"tvos\", \"watchos\", or \"catalyst\"",
+                            platform
+                        ),
+                    );
+                }
+            }
+        }
+        if let Some(generator) = apple.get("project-generator").and_then(toml::Value::as_str) {
+            if !["auto", "xcodegen", "native"].contains(&generator) {
+                self.problem(
+                    "apple",
+                    "project-generator",
+                    format!(
+                        "{:?} isn't a project generator; it needs to be \"auto\", \"xcodegen\", or \"native\"",
+                        generator
+                    ),
+                );
+            }
+        }
+        if let Some(signing) = apple.get("signing").and_then(toml::Value::as_table) {
+            self.check_apple_signing(signing);
+        }
+    }
+
+    fn check_apple_signing(&mut self, signing: &toml::value::Table) {
+        let style = signing.get("style").and_then(toml::Value::as_str);
+        if let Some(style) = style {
+            if !["automatic", "manual"].contains(&style) {
+                self.problem(
+                    "apple.signing",
+                    "style",
+                    format!(
+                        "{:?} isn't a signing style; it needs to be \"automatic\" or \"manual\"",
+                        style
+                    ),
+                );
+            }
+        }
+        if style == Some("manual") && !signing.contains_key("provisioning-profile") {
+            self.problem(
+                "apple.signing",
+                "provisioning-profile",
+                "manual signing needs a provisioning profile",
+            );
+        }
+        if let Some(method) = signing.get("export-method").and_then(toml::Value::as_str) {
+            if !["app-store", "ad-hoc", "enterprise", "development"].contains(&method) {
+                self.problem(
+                    "apple.signing",
+                    "export-method",
+                    format!(
+                        "{:?} isn't an export method; it needs to be \"app-store\", \"ad-hoc\", \"enterprise\", or \"development\"",
+                        method
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Checks the config file `source` for every problem we can find, rather than
+/// stopping at the first one.
+pub fn validate(source: &str, value: &toml::Value) -> Result<(), Problems> {
+    let mut validator = Validator {
+        schema: SCHEMA,
+        source,
+        problems: Vec::new(),
+    };
+    if let Some(root) = value.as_table() {
+        validator.check_table("", root);
+        let table = |key| root.get(key).and_then(toml::Value::as_table);
+        if let Some(app) = table("app") {
+            validator.check_app(app);
+        }
+        if let Some(hooks) = table("hooks") {
+            validator.check_hooks(hooks);
+        }
+        if let Some(android) = table("android") {
+            validator.check_android(android);
+        }
+        if let Some(apple) = table("apple") {
+            validator.check_apple(apple);
+        }
+    }
+    if validator.problems.is_empty() {
+        Ok(())
+    } else {
+        Err(Problems(validator.problems))
+    }
+}
+
+/// Like [`validate`], but for the user config.
+pub fn validate_global(source: &str, value: &toml::Value) -> Result<(), Problems> {
+    let mut validator = Validator {
+        schema: GLOBAL_SCHEMA,
+        source,
+        problems: Vec::new(),
+    };
+    if let Some(root) = value.as_table() {
+        validator.check_table("", root);
+        let table = |key| root.get(key).and_then(toml::Value::as_table);
+        if let Some(domain) = table("app")
+            .and_then(|app| app.get("domain"))
+            .and_then(toml::Value::as_str)
+        {
+            if !publicsuffix::Domain::has_valid_syntax(domain) {
+                validator.problem(
+                    "app",
+                    "domain",
+                    format!("{:?} isn't valid domain syntax", domain),
+                );
+            }
+        }
+        if let Some(color) = table("terminal")
+            .and_then(|terminal| terminal.get("color"))
+            .and_then(toml::Value::as_str)
+        {
+            if !["auto", "always", "never"].contains(&color) {
+                validator.problem(
+                    "terminal",
+                    "color",
+                    format!(
+                        "{:?} isn't a color choice; it needs to be \"auto\", \"always\", or \"never\"",
+                        color
+                    ),
+                );
+            }
+        }
+    }
+    if validator.problems.is_empty() {
+        Ok(())
+    } else {
+        Err(Problems(validator.problems))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static VALID: &str = r#"[app]
+name = "my-app"
+domain = "example.org"
+template-pack = "bevy"
+"#;
+
+    fn problems(source: &str) -> Vec<String> {
+        let value = source.parse::<toml::Value>().unwrap();
+        match validate(source, &value) {
+            Ok(()) => Vec::new(),
+            Err(problems) => problems.0.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn valid_configs_have_no_problems() {
+        assert_eq!(problems(VALID), Vec::<String>::new());
+        // TOML's octal integers are the natural way to write these
+        assert_eq!(
+            problems(&format!("{}dir-mode = 0o755\n", VALID)),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn unknown_keys_get_suggestions() {
+        let source = format!(
+            "{}\n[android]\nmin-sdk-verison = 24\nflavor = \"free\"\n",
+            VALID
+        );
+        // Keys are visited in whatever order the table keeps them in
+        let mut problems = problems(&source);
+        problems.sort();
+        assert_eq!(
+            problems,
+            vec![
+                "line 7: `android.min-sdk-verison`: unknown key; did you mean `min-sdk-version`?",
+                "line 8: `android.flavor`: unknown key",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_keys_are_reported_on_their_table_header() {
+        let source = "\n[app]\nname = \"my-app\"\ntemplate-pack = \"bevy\"\n";
+        assert_eq!(
+            problems(source),
+            vec!["line 2: `app.domain`: missing, but it's required"]
+        );
+    }
+
+    #[test]
+    fn min_sdk_versions_cant_be_older_than_the_ndk_supports() {
+        let check = |extra: &str| problems(&format!("{}\n[android]\n{}\n", VALID, extra));
+        assert_eq!(
+            check("min-sdk-version = 15"),
+            vec!["line 7: `android.min-sdk-version`: 15 isn't a supported API level; NDK r19 needs it to be at least 16"]
+        );
+        assert_eq!(check("min-sdk-version = 16"), Vec::<String>::new());
+        // API levels newer than we know about are fine
+        assert_eq!(check("min-sdk-version = 99"), Vec::<String>::new());
+        // Newer NDKs don't go back as far
+        assert_eq!(
+            check("min-sdk-version = 19\nmin-ndk-version = \"r26\""),
+            vec!["line 7: `android.min-sdk-version`: 19 isn't a supported API level; NDK r26 needs it to be at least 21"]
+        );
+    }
+
+    #[test]
+    fn ios_deployment_targets_cant_be_older_than_xcode_supports() {
+        let check = |target: &str| {
+            problems(&format!(
+                "{}\n[apple]\ndevelopment-team = \"ABCDE12345\"\nios-deployment-target = {:?}\n",
+                VALID, target
+            ))
+        };
+        assert_eq!(
+            check("8.0"),
+            vec!["line 8: `apple.ios-deployment-target`: iOS 8.0 isn't supported; it needs to be at least 9.0"]
+        );
+        assert_eq!(check("9.0"), Vec::<String>::new());
+        // Releases newer than we know about are fine
+        assert_eq!(check("15.2"), Vec::<String>::new());
+        assert_eq!(
+            check("fourteen"),
+            vec!["line 8: `apple.ios-deployment-target`: \"fourteen\" isn't a version number, like \"12.0\""]
+        );
+    }
+}
@@ -0,0 +1,78 @@
+//! Removes what `cargo mobile init` generated, for when a project shouldn't be
+//! a mobile project anymore.
+
+use crate::{
+    config::{self, Config},
+    generated::{self, GenerationManifest},
+    util::{
+        cli::{Report, Reportable, TextWrapper},
+        prompt,
+    },
+};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub enum Error {
+    ConfigLoadFailed(config::LoadOrGenError),
+    ConfigMissing,
+    ManifestLoadFailed { path: PathBuf, cause: io::Error },
+    PromptFailed(io::Error),
+    RemoveFailed(io::Error),
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::ConfigLoadFailed(err) => err.report(),
+            Self::ConfigMissing => Report::action_request(
+                format!("No {} was found", config::file_name()),
+                "There's nothing to remove, since this isn't a cargo-mobile project.",
+            ),
+            Self::ManifestLoadFailed { path, cause } => Report::error(
+                format!("Failed to load generation manifest {:?}", path),
+                cause,
+            ),
+            Self::PromptFailed(err) => Report::error("Failed to prompt for confirmation", err),
+            Self::RemoveFailed(err) => Report::error("Failed to remove generated files", err),
+        }
+    }
+}
+
+/// Removes everything listed in the project's generation manifest, after
+/// asking first unless `yes` is set. Nothing is removed without asking if
+/// there's nobody around to answer.
+pub fn exec(wrapper: &TextWrapper, yes: bool, cwd: impl AsRef<Path>) -> Result<(), Error> {
+    let config = Config::load(cwd)
+        .map_err(Error::ConfigLoadFailed)?
+        .ok_or(Error::ConfigMissing)?;
+    let root_dir = config.app().root_dir();
+    let manifest =
+        GenerationManifest::load(root_dir).map_err(|cause| Error::ManifestLoadFailed {
+            path: GenerationManifest::path(root_dir),
+            cause,
+        })?;
+    for path in manifest.paths() {
+        println!("  {}", path.display());
+    }
+    let question = format!(
+        "Remove these {} generated paths? Your own files won't be touched.",
+        manifest.paths().len()
+    );
+    if !prompt::confirm(&question, false, yes).map_err(Error::PromptFailed)? {
+        println!("Nothing was removed.");
+        return Ok(());
+    }
+    generated::remove_generated(root_dir).map_err(Error::RemoveFailed)?;
+    Report::victory(
+        "Generated files removed",
+        format!(
+            "Your config in {} and your `.cargo` config were left alone; remove them yourself if you don't need them anymore.",
+            config::file_name()
+        ),
+    )
+    .print(wrapper);
+    Ok(())
+}
@@ -1,11 +1,17 @@
 use crate::util::cli::{Report, Reportable};
-use std::{fmt::Debug, io};
+use serde::Serialize;
+use std::{
+    fmt::{self, Debug, Display},
+    io,
+};
 
 #[derive(Debug)]
 pub enum PromptErrorCause<T: Reportable> {
     DetectionFailed(T),
     PromptFailed(io::Error),
     NoneDetected,
+    NotFound { query: String },
+    Ambiguous { count: usize },
 }
 
 #[derive(Debug)]
@@ -25,6 +31,17 @@ impl<T: Reportable> Reportable for PromptError<T> {
                 format!("Failed to prompt for {} device", self.name),
                 format!("No connected {} devices detected", self.name),
             ),
+            PromptErrorCause::NotFound { query } => Report::action_request(
+                format!("No connected {} device matches {:?}", self.name, query),
+                "Use the `devices` subcommand to see the IDs and names of connected devices.",
+            ),
+            PromptErrorCause::Ambiguous { count } => Report::action_request(
+                format!(
+                    "{} {} devices are connected, so one needs to be picked",
+                    count, self.name
+                ),
+                "Since this is running non-interactively, please specify one using `--device`.",
+            ),
         }
     }
 }
@@ -45,15 +62,83 @@ impl<T: Reportable> PromptError<T> {
     pub fn none_detected(name: &'static str) -> Self {
         Self::new(name, PromptErrorCause::NoneDetected)
     }
+
+    pub fn not_found(name: &'static str, query: &str) -> Self {
+        Self::new(
+            name,
+            PromptErrorCause::NotFound {
+                query: query.to_owned(),
+            },
+        )
+    }
+
+    pub fn ambiguous(name: &'static str, count: usize) -> Self {
+        Self::new(name, PromptErrorCause::Ambiguous { count })
+    }
+}
+
+/// The details shown for each device by the `devices` subcommands.
+#[derive(Clone, Debug, Serialize)]
+pub struct DeviceSummary {
+    pub id: String,
+    pub name: String,
+    pub model: String,
+    pub os: &'static str,
+    pub os_version: Option<String>,
+    pub abi: String,
+}
+
+impl Display for DeviceSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({})\n      id: {}\n      os: {}",
+            self.name, self.model, self.id, self.os
+        )?;
+        if let Some(os_version) = &self.os_version {
+            write!(f, " {}", os_version)?;
+        }
+        write!(f, "\n      abi: {}", self.abi)
+    }
 }
 
+/// Defines `device_prompt`, which picks a connected device. If `query` is
+/// given, it picks the device with that ID or name; otherwise, it picks the
+/// only device, or prompts if there are several. Prompting is an error when
+/// running non-interactively, since there's no sensible way to choose.
 #[macro_export]
 macro_rules! define_device_prompt {
     ($func:path, $e:ty, $name:ident) => {
-        fn device_prompt<'a>(env: &'_ Env) -> Result<Device<'a>, $crate::device::PromptError<$e>> {
+        fn device_prompt<'a>(
+            env: &'_ Env,
+            query: Option<&str>,
+            non_interactive: $crate::opts::NonInteractive,
+        ) -> Result<Device<'a>, $crate::device::PromptError<$e>> {
             let device_list = $func(env).map_err(|cause| {
                 $crate::device::PromptError::detection_failed(stringify!($name), cause)
             })?;
+            if let Some(query) = query {
+                let device = device_list
+                    .into_iter()
+                    .find(|device| {
+                        device.id() == query || device.name().eq_ignore_ascii_case(query)
+                    })
+                    .ok_or_else(|| {
+                        $crate::device::PromptError::not_found(stringify!($name), query)
+                    })?;
+                println!(
+                    "Using specified device: {} with target {:?}",
+                    device,
+                    device.target().triple,
+                );
+                return Ok(device);
+            }
+            if device_list.len() > 1 && non_interactive.yes() {
+                return Err($crate::device::PromptError::ambiguous(
+                    stringify!($name),
+                    device_list.len(),
+                ));
+            }
             if device_list.len() > 0 {
                 let index = if device_list.len() > 1 {
                     prompt::list(
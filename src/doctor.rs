@@ -0,0 +1,352 @@
+#[cfg(target_os = "macos")]
+use crate::apple;
+use crate::{
+    android,
+    config::Config,
+    env::ExplicitEnv as _,
+    generated,
+    target::TargetTrait as _,
+    util::{
+        self,
+        cli::{Report, Reportable, TextWrapper},
+        output::{self, Event},
+    },
+};
+use colored::Colorize as _;
+use serde::Serialize;
+use std::{fmt::Display, io, path::Path};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Checks {
+    checks: Vec<Check>,
+}
+
+impl Checks {
+    fn push(&mut self, name: &'static str, status: Status, detail: impl Display) {
+        let check = Check {
+            name,
+            status,
+            detail: detail.to_string(),
+        };
+        log::info!("doctor check {:?}", check);
+        self.checks.push(check);
+    }
+
+    fn pass(&mut self, name: &'static str, detail: impl Display) {
+        self.push(name, Status::Pass, detail)
+    }
+
+    fn warn(&mut self, name: &'static str, detail: impl Display) {
+        self.push(name, Status::Warn, detail)
+    }
+
+    fn fail(&mut self, name: &'static str, detail: impl Display) {
+        self.push(name, Status::Fail, detail)
+    }
+
+    fn tool(&mut self, name: &'static str, missing: Status, hint: &str) {
+        // `command_present` would need `command` to exist as a program, which
+        // it usually only does on macOS.
+        if util::resolve_program(name).is_some() {
+            self.pass(name, "installed")
+        } else {
+            self.push(name, missing, format!("not installed; {}", hint))
+        }
+    }
+
+    pub fn checks(&self) -> &[Check] {
+        &self.checks
+    }
+
+    pub fn failed(&self) -> usize {
+        self.checks
+            .iter()
+            .filter(|check| check.status == Status::Fail)
+            .count()
+    }
+
+    pub fn print(&self, wrapper: &TextWrapper) {
+        for check in &self.checks {
+            let label = match check.status {
+                Status::Pass => "pass".green(),
+                Status::Warn => "warn".yellow(),
+                Status::Fail => "fail".red(),
+            };
+            println!(
+                "{}",
+                wrapper.fill(&format!("[{}] {}: {}", label, check.name, check.detail))
+            );
+        }
+    }
+}
+
+fn check_rust(checks: &mut Checks) {
+    match util::RustVersion::check() {
+        Ok(version) => checks.pass("rustc", version),
+        Err(err) => checks.fail("rustc", err),
+    }
+    let installed = match util::rustup_installed_targets() {
+        Ok(installed) => installed,
+        Err(err) => {
+            checks.fail("rustup targets", format!("failed to list targets: {}", err));
+            return;
+        }
+    };
+    let triples = android::target::Target::all()
+        .values()
+        .map(|target| target.triple());
+    #[cfg(target_os = "macos")]
+    let triples = triples.chain(
+        apple::target::Target::all()
+            .values()
+            .map(|target| target.triple()),
+    );
+    let missing = triples
+        .filter(|triple| !installed.iter().any(|installed| installed == triple))
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        checks.pass("rustup targets", "all mobile targets are installed");
+    } else {
+        checks.warn(
+            "rustup targets",
+            format!(
+                "missing {}; `cargo mobile init` will install them",
+                util::list_display(&missing)
+            ),
+        );
+    }
+}
+
+fn check_android(checks: &mut Checks) {
+    match android::java::java_version() {
+        Ok((major, minor, patch)) => {
+            checks.pass("java", format!("version {}.{}.{}", major, minor, patch));
+        }
+        Err(err) => checks.fail("java", err),
+    }
+    match android::env::Env::new() {
+        Ok(env) => {
+            checks.pass("android sdk", format!("found at {:?}", env.sdk_root()));
+            match env.ndk.version() {
+                Ok(version) => checks.pass(
+                    "android ndk",
+                    format!("{} found at {:?}", version, env.ndk.home()),
+                ),
+                Err(err) => checks.fail("android ndk", err),
+            }
+            let adb = env.sdk_root().join("platform-tools").join("adb");
+            let adb_works = bossy::Command::pure(&adb)
+                .with_env_vars(env.explicit_env())
+                .with_arg("version")
+                .run_and_wait_for_output()
+                .is_ok();
+            if adb_works {
+                checks.pass("adb", format!("found at {:?}", adb));
+            } else {
+                checks.fail(
+                    "adb",
+                    format!(
+                        "{:?} didn't work; install the SDK's platform tools to fix this",
+                        adb
+                    ),
+                );
+            }
+        }
+        Err(err) => checks.fail("android sdk", err),
+    }
+    checks.tool(
+        "gradle",
+        Status::Pass,
+        "generated projects use the Gradle wrapper, so it's not required",
+    );
+}
+
+#[cfg(target_os = "macos")]
+fn check_apple(checks: &mut Checks) {
+    match apple::system_profile::DeveloperTools::new() {
+        Ok(tools) => checks.pass(
+            "xcode",
+            format!("version {}.{}", tools.version.0, tools.version.1),
+        ),
+        Err(err) => checks.fail("xcode", err),
+    }
+    match bossy::Command::impure_parse("xcode-select -p").run_and_wait_for_output() {
+        Ok(output) => checks.pass(
+            "xcode command line tools",
+            format!(
+                "selected at {}",
+                String::from_utf8_lossy(output.stdout()).trim()
+            ),
+        ),
+        Err(_) => checks.fail(
+            "xcode command line tools",
+            "not installed; run `xcode-select --install` to fix this",
+        ),
+    }
+    checks.tool(
+        "xcodegen",
+        Status::Pass,
+        "the built-in Xcode project generator will be used instead",
+    );
+    checks.tool(
+        "ios-deploy",
+        Status::Warn,
+        "`cargo mobile init` will install it",
+    );
+}
+
+fn check_generated(checks: &mut Checks, root_dir: &Path, current_template_ref: &str) {
+    let name = "generated projects";
+    match generated::generation_is_stale(root_dir, current_template_ref) {
+        Ok(false) => checks.pass(name, "generated from the installed templates"),
+        Ok(true) => match generated::stale_generated_paths(root_dir, current_template_ref) {
+            Ok(paths) => checks.warn(
+                name,
+                format!(
+                    "the templates were updated since this project was generated, so {} generated file{} might be outdated; run `cargo mobile regen` to update them",
+                    paths.len(),
+                    if paths.len() == 1 { "" } else { "s" },
+                ),
+            ),
+            Err(err) => checks.fail(name, format!("failed to list generated files: {}", err)),
+        },
+        // Nothing was generated yet, or it was generated before we kept track.
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            log::info!("no generation manifest found in {:?}", root_dir)
+        }
+        Err(err) => checks.fail(
+            name,
+            format!("failed to load the generation manifest: {}", err),
+        ),
+    }
+}
+
+/// Checks everything cargo-mobile needs, using the same detection that the
+/// rest of cargo-mobile does. If `cwd` is in a project, this also checks if
+/// its generated projects are up-to-date with the installed templates.
+pub fn run_checks(cwd: impl AsRef<Path>) -> Checks {
+    let mut checks = Checks::default();
+    check_rust(&mut checks);
+    check_android(&mut checks);
+    #[cfg(target_os = "macos")]
+    check_apple(&mut checks);
+    match Config::load(cwd) {
+        Ok(Some(config)) => match generated::current_template_ref() {
+            Ok(template_ref) => {
+                check_generated(&mut checks, config.app().root_dir(), &template_ref)
+            }
+            Err(err) => log::warn!("failed to get current template version: {}", err),
+        },
+        Ok(None) => log::info!("not in a project, so not checking generated projects"),
+        Err(err) => log::warn!("failed to load config: {:?}", err),
+    }
+    checks
+}
+
+#[derive(Debug)]
+pub enum Error {
+    SerializeFailed(serde_json::Error),
+    ChecksFailed(usize),
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::SerializeFailed(err) => Report::error("Failed to serialize doctor results", err),
+            Self::ChecksFailed(count) => Report::error(
+                "Your environment isn't ready yet",
+                format!(
+                    "{} check{} failed; see above for how to fix {}.",
+                    count,
+                    if *count == 1 { "" } else { "s" },
+                    if *count == 1 { "it" } else { "them" },
+                ),
+            ),
+        }
+    }
+}
+
+pub fn exec(wrapper: &TextWrapper, json: bool, cwd: impl AsRef<Path>) -> Result<(), Error> {
+    let checks = run_checks(cwd);
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&checks).map_err(Error::SerializeFailed)?
+        );
+    } else {
+        checks.print(wrapper);
+    }
+    for check in checks.checks() {
+        output::emit(&Event::Check(check));
+    }
+    match checks.failed() {
+        0 => Ok(()),
+        count => Err(Error::ChecksFailed(count)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generated::GenerationManifest;
+
+    fn manifest_with_ref(root: &Path, template_ref: &str) {
+        let mut manifest = GenerationManifest::default();
+        manifest.add(root, "gen/android/build.gradle.kts");
+        manifest.add(root, "gen/android/settings.gradle.kts");
+        manifest.set_template_ref(template_ref);
+        manifest.write(root).unwrap();
+    }
+
+    #[test]
+    fn outdated_generations_are_warned_about() {
+        let dir = tempfile::tempdir().unwrap();
+        manifest_with_ref(dir.path(), "old");
+        let mut checks = Checks::default();
+        check_generated(&mut checks, dir.path(), "new");
+        assert_eq!(checks.checks()[0].status, Status::Warn);
+        assert!(checks.checks()[0].detail.contains("2 generated files"));
+    }
+
+    #[test]
+    fn current_generations_pass() {
+        let dir = tempfile::tempdir().unwrap();
+        manifest_with_ref(dir.path(), "new");
+        let mut checks = Checks::default();
+        check_generated(&mut checks, dir.path(), "new");
+        assert_eq!(checks.checks()[0].status, Status::Pass);
+    }
+
+    #[test]
+    fn projects_without_a_manifest_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut checks = Checks::default();
+        check_generated(&mut checks, dir.path(), "new");
+        assert!(checks.checks().is_empty());
+    }
+
+    #[test]
+    fn tools_are_looked_up_on_the_path() {
+        let mut checks = Checks::default();
+        checks.tool("cargo", Status::Fail, "install it");
+        checks.tool("cargo-mobile-nonexistent", Status::Warn, "install it");
+        assert_eq!(checks.checks()[0].status, Status::Pass);
+        assert_eq!(checks.checks()[1].status, Status::Warn);
+        assert_eq!(checks.checks()[1].detail, "not installed; install it");
+    }
+}
@@ -1,21 +1,21 @@
 use crate::{
     config::app::App,
-    util::cli::{Report, Reportable},
+    util::{
+        self,
+        cli::{Report, Reportable},
+    },
 };
 use serde::{Deserialize, Serialize};
-use std::{collections::BTreeMap, fs, io, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+use toml::value::{Table, Value};
+use toml_edit::{Document, Item};
 
 #[derive(Debug)]
 pub enum LoadError {
-    DirCreationFailed {
-        path: PathBuf,
-        cause: io::Error,
-    },
-    MigrateFailed {
-        from: PathBuf,
-        to: PathBuf,
-        cause: io::Error,
-    },
     ReadFailed {
         path: PathBuf,
         cause: io::Error,
@@ -24,22 +24,15 @@ pub enum LoadError {
         path: PathBuf,
         cause: toml::de::Error,
     },
+    ParseFailed {
+        path: PathBuf,
+        cause: toml_edit::TomlError,
+    },
 }
 
 impl Reportable for LoadError {
     fn report(&self) -> Report {
         match self {
-            Self::DirCreationFailed { path, cause } => Report::error(
-                format!("Failed to create \".cargo\" directory at {:?}", path),
-                cause,
-            ),
-            Self::MigrateFailed { from, to, cause } => Report::error(
-                format!(
-                    "Failed to rename cargo config from old style {:?} to new style {:?}",
-                    from, to
-                ),
-                cause,
-            ),
             Self::ReadFailed { path, cause } => Report::error(
                 format!("Failed to read cargo config from {:?}", path),
                 cause,
@@ -48,6 +41,9 @@ impl Reportable for LoadError {
                 format!("Failed to deserialize cargo config at {:?}", path),
                 cause,
             ),
+            Self::ParseFailed { path, cause } => {
+                Report::error(format!("Failed to parse cargo config at {:?}", path), cause)
+            }
         }
     }
 }
@@ -55,8 +51,19 @@ impl Reportable for LoadError {
 #[derive(Debug)]
 pub enum WriteError {
     SerializeFailed(toml::ser::Error),
-    DirCreationFailed { path: PathBuf, cause: io::Error },
-    WriteFailed { path: PathBuf, cause: io::Error },
+    DirCreationFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
+    MigrateFailed {
+        from: PathBuf,
+        to: PathBuf,
+        cause: io::Error,
+    },
+    WriteFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
 }
 
 impl Reportable for WriteError {
@@ -67,6 +74,13 @@ impl Reportable for WriteError {
                 format!("Failed to create \".cargo\" directory at {:?}", path),
                 cause,
             ),
+            Self::MigrateFailed { from, to, cause } => Report::error(
+                format!(
+                    "Failed to rename cargo config from old style {:?} to new style {:?}",
+                    from, to
+                ),
+                cause,
+            ),
             Self::WriteFailed { path, cause } => {
                 Report::error(format!("Failed to write cargo config to {:?}", path), cause)
             }
@@ -106,29 +120,43 @@ pub struct DotCargo {
     target: BTreeMap<String, DotCargoTarget>,
 }
 
-impl DotCargo {
-    fn create_dir_and_get_path(app: &App) -> Result<PathBuf, (PathBuf, io::Error)> {
-        let dir = app.prefix_path(".cargo");
-        fs::create_dir_all(&dir)
-            .map(|()| dir.join("config.toml"))
-            .map_err(|cause| (dir, cause))
+// Cargo still reads the old-style `config` (and prefers it if both exist), so
+// we read it too, but only ever write `config.toml`.
+fn old_style_path(root_dir: &Path) -> PathBuf {
+    root_dir.join(".cargo").join("config")
+}
+
+fn load_path(root_dir: &Path) -> PathBuf {
+    let old_style = old_style_path(root_dir);
+    if old_style.is_file() {
+        old_style
+    } else {
+        root_dir.join(".cargo").join("config.toml")
     }
+}
 
+fn write_path(root_dir: &Path) -> Result<PathBuf, WriteError> {
+    let dir = root_dir.join(".cargo");
+    util::create_dir_all(&dir).map_err(|cause| WriteError::DirCreationFailed {
+        path: dir.clone(),
+        cause,
+    })?;
+    let path = dir.join("config.toml");
+    let old_style = old_style_path(root_dir);
+    if old_style.is_file() {
+        // Migrate from old-style cargo config
+        fs::rename(&old_style, &path).map_err(|cause| WriteError::MigrateFailed {
+            from: old_style,
+            to: path.clone(),
+            cause,
+        })?;
+    }
+    Ok(path)
+}
+
+impl DotCargo {
     pub fn load(app: &App) -> Result<Self, LoadError> {
-        let path = Self::create_dir_and_get_path(app)
-            .map_err(|(path, cause)| LoadError::DirCreationFailed { path, cause })?;
-        let old_style = path
-            .parent()
-            .expect("developer error: cargo config path had no parent")
-            .join("config");
-        if old_style.is_file() {
-            // Migrate from old-style cargo config
-            std::fs::rename(&old_style, &path).map_err(|cause| LoadError::MigrateFailed {
-                from: old_style,
-                to: path.clone(),
-                cause,
-            })?;
-        }
+        let path = load_path(app.root_dir());
         if path.is_file() {
             let bytes = fs::read(&path).map_err(|cause| LoadError::ReadFailed {
                 path: path.clone(),
@@ -152,9 +180,335 @@ impl DotCargo {
     }
 
     pub fn write(self, app: &App) -> Result<(), WriteError> {
-        let path = Self::create_dir_and_get_path(app)
-            .map_err(|(path, cause)| WriteError::DirCreationFailed { path, cause })?;
+        let path = write_path(app.root_dir())?;
         let ser = toml::to_string_pretty(&self).map_err(WriteError::SerializeFailed)?;
-        fs::write(&path, ser).map_err(|cause| WriteError::WriteFailed { path, cause })
+        util::write_str_if_changed(&path, ser)
+            .map(|_| ())
+            .map_err(|cause| WriteError::WriteFailed { path, cause })
+    }
+}
+
+/// Gets `build.target` from the cargo config that applies to `dir`. Like cargo,
+/// this checks `dir` and then each of its ancestors, with the nearest config
+/// that sets it winning. Unlike [`DotCargo::load`], this never touches the
+/// filesystem beyond reading.
+pub fn default_cargo_target(dir: &Path) -> io::Result<Option<String>> {
+    for ancestor in dir.ancestors() {
+        for name in &["config.toml", "config"] {
+            let path = ancestor.join(".cargo").join(name);
+            if !path.is_file() {
+                continue;
+            }
+            let table = toml::from_slice::<Table>(&fs::read(&path)?)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            if let Some(target) = table
+                .get("build")
+                .and_then(|build| build.get("target"))
+                .and_then(Value::as_str)
+            {
+                log::info!("found default target {:?} in {:?}", target, path);
+                return Ok(Some(target.to_owned()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+// New tables are made inline if their parent is, and are otherwise implicit, so
+// that i.e. setting `target.<triple>.runner` doesn't leave behind an empty
+// `[target]` header.
+fn table_mut<'a>(parent: &'a mut Item, key: &str) -> &'a mut Item {
+    match parent.get(key) {
+        Some(item) if item.is_table_like() => (),
+        existing => {
+            if existing.is_some() {
+                log::warn!(
+                    "cargo config key {:?} wasn't a table, so it's being replaced with one",
+                    key
+                );
+            }
+            parent[key] = if parent.is_inline_table() {
+                toml_edit::value(toml_edit::InlineTable::new())
+            } else {
+                let mut table = toml_edit::Table::new();
+                table.set_implicit(true);
+                Item::Table(table)
+            };
+        }
+    }
+    &mut parent[key]
+}
+
+/// Unlike [`DotCargo`], this keeps the whole cargo config around as a
+/// `toml_edit` document, so that any settings we don't know about (along with
+/// comments and formatting) survive being written back out.
+#[derive(Debug)]
+pub struct CargoConfig {
+    root_dir: PathBuf,
+    path: PathBuf,
+    document: Document,
+}
+
+impl CargoConfig {
+    /// This only reads; the `.cargo` dir isn't created and old-style configs
+    /// aren't migrated until [`CargoConfig::write`].
+    pub fn load(root_dir: &Path) -> Result<Self, LoadError> {
+        let path = load_path(root_dir);
+        let document = if path.is_file() {
+            let contents = fs::read_to_string(&path).map_err(|cause| LoadError::ReadFailed {
+                path: path.clone(),
+                cause,
+            })?;
+            contents
+                .parse::<Document>()
+                .map_err(|cause| LoadError::ParseFailed {
+                    path: path.clone(),
+                    cause,
+                })?
+        } else {
+            Default::default()
+        };
+        Ok(Self {
+            root_dir: root_dir.to_owned(),
+            path,
+            document,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn target_runner(&self, triple: &str) -> Option<Vec<String>> {
+        // cargo accepts the runner as either a single string or an array of
+        // program + args.
+        let runner = self.document.get("target")?.get(triple)?.get("runner")?;
+        if let Some(runner) = runner.as_str() {
+            Some(vec![runner.to_owned()])
+        } else {
+            runner
+                .as_array()?
+                .iter()
+                .map(|arg| arg.as_str().map(ToOwned::to_owned))
+                .collect()
+        }
+    }
+
+    pub fn set_target_runner(&mut self, triple: &str, runner: &[String]) {
+        let target = table_mut(table_mut(self.document.as_item_mut(), "target"), triple);
+        target["runner"] = toml_edit::value(
+            runner
+                .iter()
+                .map(String::as_str)
+                .collect::<toml_edit::Array>(),
+        );
+    }
+
+    /// Writes to `config.toml`, creating the `.cargo` dir and migrating any
+    /// old-style `config` as needed.
+    pub fn write(&mut self) -> Result<(), WriteError> {
+        self.path = write_path(&self.root_dir)?;
+        util::write_str_if_changed(&self.path, self.document.to_string())
+            .map(|_| ())
+            .map_err(|cause| WriteError::WriteFailed {
+                path: self.path.clone(),
+                cause,
+            })
+    }
+}
+
+#[derive(Debug)]
+pub enum SetTargetRunnerError {
+    LoadFailed(LoadError),
+    WriteFailed(WriteError),
+}
+
+impl Reportable for SetTargetRunnerError {
+    fn report(&self) -> Report {
+        match self {
+            Self::LoadFailed(err) => err.report(),
+            Self::WriteFailed(err) => err.report(),
+        }
+    }
+}
+
+pub fn read_cargo_config(root_dir: &Path) -> Result<CargoConfig, LoadError> {
+    CargoConfig::load(root_dir)
+}
+
+pub fn set_target_runner(
+    root_dir: &Path,
+    triple: &str,
+    runner: &[String],
+) -> Result<(), SetTargetRunnerError> {
+    let mut config = CargoConfig::load(root_dir).map_err(SetTargetRunnerError::LoadFailed)?;
+    config.set_target_runner(triple, runner);
+    config.write().map_err(SetTargetRunnerError::WriteFailed)
+}
+
+/// Gets the runner that makes `cargo run --target <triple>` hand off to the
+/// matching cargo-mobile subcommand, if `triple` is a mobile one.
+pub fn mobile_runner(triple: &str) -> Option<Vec<String>> {
+    let program = if triple.contains("-linux-android") {
+        "cargo-android"
+    } else if triple.contains("-apple-ios") {
+        "cargo-apple"
+    } else {
+        return None;
+    };
+    Some(vec![program.to_owned(), "run".to_owned()])
+}
+
+/// Points the cargo config's runner for `triple` at cargo-mobile, leaving the
+/// config untouched if it's already set that way. Returns whether anything
+/// changed; non-mobile triples are left alone.
+pub fn ensure_target_runner(root_dir: &Path, triple: &str) -> Result<bool, SetTargetRunnerError> {
+    let runner = if let Some(runner) = mobile_runner(triple) {
+        runner
+    } else {
+        log::info!("{:?} isn't a mobile target, so no runner is needed", triple);
+        return Ok(false);
+    };
+    let mut config = CargoConfig::load(root_dir).map_err(SetTargetRunnerError::LoadFailed)?;
+    if config.target_runner(triple).as_ref() == Some(&runner) {
+        Ok(false)
+    } else {
+        log::info!(
+            "setting runner for {:?} to {:?} in {:?}",
+            triple,
+            runner,
+            config.path()
+        );
+        config.set_target_runner(triple, &runner);
+        config
+            .write()
+            .map(|()| true)
+            .map_err(SetTargetRunnerError::WriteFailed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_target_is_read_from_the_nearest_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("app");
+        fs::create_dir_all(nested.join(".cargo")).unwrap();
+        fs::create_dir_all(dir.path().join(".cargo")).unwrap();
+        fs::write(
+            dir.path().join(".cargo/config.toml"),
+            "[build]\ntarget = \"aarch64-linux-android\"\n",
+        )
+        .unwrap();
+        fs::write(nested.join(".cargo/config.toml"), "[build]\njobs = 2\n").unwrap();
+        assert_eq!(
+            default_cargo_target(&nested).unwrap().as_deref(),
+            Some("aarch64-linux-android")
+        );
+    }
+
+    #[test]
+    fn no_default_target_without_build_target() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(".cargo")).unwrap();
+        fs::write(dir.path().join(".cargo/config"), "[build]\njobs = 2\n").unwrap();
+        // An ancestor of the tempdir could set one, but that'd be weird.
+        assert_eq!(default_cargo_target(dir.path()).unwrap(), None);
+    }
+
+    #[test]
+    fn target_runner_is_only_written_when_it_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let triple = "aarch64-linux-android";
+        assert!(ensure_target_runner(dir.path(), triple).unwrap());
+        let config = CargoConfig::load(dir.path()).unwrap();
+        assert_eq!(config.target_runner(triple), mobile_runner(triple));
+        assert!(!ensure_target_runner(dir.path(), triple).unwrap());
+        set_target_runner(dir.path(), triple, &["something-else".to_owned()]).unwrap();
+        assert!(ensure_target_runner(dir.path(), triple).unwrap());
+        let config = CargoConfig::load(dir.path()).unwrap();
+        assert_eq!(config.target_runner(triple), mobile_runner(triple));
+    }
+
+    #[test]
+    fn unrelated_settings_survive_setting_a_runner() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(".cargo")).unwrap();
+        let original = "\
+# Keep builds from eating the whole machine
+[build]
+jobs = 2
+
+[target.aarch64-linux-android]
+linker = \"aarch64-linux-android21-clang\"
+
+[env]
+FOO = \"bar\"
+";
+        fs::write(dir.path().join(".cargo/config.toml"), original).unwrap();
+        let runner = vec!["cargo-android".to_owned(), "run".to_owned()];
+        set_target_runner(dir.path(), "aarch64-linux-android", &runner).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join(".cargo/config.toml")).unwrap(),
+            original.replace(
+                "linker = \"aarch64-linux-android21-clang\"\n",
+                "linker = \"aarch64-linux-android21-clang\"\nrunner = [\"cargo-android\", \"run\"]\n",
+            )
+        );
+        let config = read_cargo_config(dir.path()).unwrap();
+        assert_eq!(config.target_runner("aarch64-linux-android"), Some(runner));
+    }
+
+    #[test]
+    fn new_runners_get_their_own_table() {
+        let dir = tempfile::tempdir().unwrap();
+        set_target_runner(dir.path(), "aarch64-apple-ios", &["cargo-apple".to_owned()]).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join(".cargo/config.toml")).unwrap(),
+            "[target.aarch64-apple-ios]\nrunner = [\"cargo-apple\"]\n"
+        );
+    }
+
+    #[test]
+    fn loading_only_reads() {
+        let dir = tempfile::tempdir().unwrap();
+        read_cargo_config(dir.path()).unwrap();
+        assert!(!dir.path().join(".cargo").exists());
+
+        fs::create_dir_all(dir.path().join(".cargo")).unwrap();
+        fs::write(
+            dir.path().join(".cargo/config"),
+            "[target.aarch64-linux-android]\nrunner = \"my-runner\"\n",
+        )
+        .unwrap();
+        let config = read_cargo_config(dir.path()).unwrap();
+        assert_eq!(
+            config.target_runner("aarch64-linux-android"),
+            Some(vec!["my-runner".to_owned()])
+        );
+        assert!(dir.path().join(".cargo/config").is_file());
+        assert!(!dir.path().join(".cargo/config.toml").exists());
+    }
+
+    #[test]
+    fn old_style_configs_are_migrated_on_write() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(".cargo")).unwrap();
+        fs::write(dir.path().join(".cargo/config"), "[build]\njobs = 2\n").unwrap();
+        assert!(ensure_target_runner(dir.path(), "aarch64-linux-android").unwrap());
+        assert!(!dir.path().join(".cargo/config").exists());
+        assert_eq!(
+            fs::read_to_string(dir.path().join(".cargo/config.toml")).unwrap(),
+            "[build]\njobs = 2\n\n[target.aarch64-linux-android]\nrunner = [\"cargo-android\", \"run\"]\n"
+        );
+    }
+
+    #[test]
+    fn non_mobile_targets_get_no_runner() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!ensure_target_runner(dir.path(), "x86_64-unknown-linux-gnu").unwrap());
+        assert!(!dir.path().join(".cargo/config.toml").exists());
     }
 }
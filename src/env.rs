@@ -1,8 +1,13 @@
-use crate::util::cli::{Report, Reportable};
+use crate::util::{
+    cli::{Report, Reportable},
+    env::{EnvBuilder, PathInvalid},
+    Invocation,
+};
 use std::{
-    ffi::OsStr,
+    ffi::{OsStr, OsString},
     fmt::{self, Debug, Display},
-    path::Path,
+    fs, io,
+    path::{Path, PathBuf},
 };
 
 pub trait ExplicitEnv: Debug {
@@ -12,7 +17,7 @@ pub trait ExplicitEnv: Debug {
 #[derive(Debug)]
 pub enum Error {
     HomeNotSet(std::env::VarError),
-    PathNotSet(std::env::VarError),
+    PathNotSet,
 }
 
 impl Display for Error {
@@ -23,10 +28,9 @@ impl Display for Error {
                 "The `HOME` environment variable isn't set, which is pretty weird: {}",
                 err
             ),
-            Self::PathNotSet(err) => write!(
+            Self::PathNotSet => write!(
                 f,
-                "The `PATH` environment variable isn't set, which is super weird: {}",
-                err
+                "The `PATH` environment variable isn't set, which is super weird."
             ),
         }
     }
@@ -40,45 +44,328 @@ impl Reportable for Error {
 
 #[derive(Debug)]
 pub struct Env {
-    home: String,
-    path: String,
-    term: Option<String>,
-    ssh_auth_sock: Option<String>,
+    vars: EnvBuilder,
 }
 
 impl Env {
     pub fn new() -> Result<Self, Error> {
-        let home = std::env::var("HOME").map_err(Error::HomeNotSet)?;
-        let path = std::env::var("PATH").map_err(Error::PathNotSet)?;
-        let term = std::env::var("TERM").ok();
-        let ssh_auth_sock = std::env::var("SSH_AUTH_SOCK").ok();
-        Ok(Self {
-            home,
-            path,
-            term,
-            ssh_auth_sock,
-        })
+        let home = std::env::var("HOME")
+            .or_else(|err| {
+                // Windows doesn't set `HOME`, and this is its closest equivalent
+                if cfg!(windows) {
+                    std::env::var("USERPROFILE")
+                } else {
+                    Err(err)
+                }
+            })
+            .map_err(Error::HomeNotSet)?;
+        let path = std::env::var_os("PATH").ok_or(Error::PathNotSet)?;
+        let mut vars = EnvBuilder::new()
+            .with_var("HOME", home)
+            .with_var("PATH", path);
+        for key in &["TERM", "SSH_AUTH_SOCK"] {
+            if let Some(value) = std::env::var_os(key) {
+                vars.set_var(*key, value);
+            }
+        }
+        // Cargo's profile overrides have to survive being passed through
+        // Gradle and Xcode, since that's how `profile` gets debug info into
+        // release builds.
+        for (key, value) in std::env::vars_os() {
+            if let Some(key) = key.to_str().filter(|key| key.starts_with("CARGO_PROFILE_")) {
+                vars.set_var(key, value);
+            }
+        }
+        Ok(Self { vars })
+    }
+
+    pub fn path(&self) -> &OsStr {
+        self.vars
+            .get("PATH")
+            .expect("developer error: `PATH` missing from base environment")
     }
 
-    pub fn path(&self) -> &str {
-        &self.path
+    pub fn with_var(self, key: impl Into<String>, value: impl Into<OsString>) -> Self {
+        Self {
+            vars: self.vars.with_var(key, value),
+        }
     }
 
-    pub fn prepend_to_path(mut self, path: impl AsRef<Path>) -> Self {
-        self.path = format!("{}:{}", path.as_ref().display(), self.path);
-        self
+    pub fn prepend_to_path(self, path: impl Into<PathBuf>) -> Result<Self, PathInvalid> {
+        Ok(Self {
+            vars: self.vars.prepend_path("PATH", path)?,
+        })
     }
 }
 
 impl ExplicitEnv for Env {
     fn explicit_env(&self) -> Vec<(&str, &std::ffi::OsStr)> {
-        let mut env = vec![("HOME", self.home.as_ref()), ("PATH", self.path.as_ref())];
-        if let Some(term) = self.term.as_ref() {
-            env.push(("TERM", term.as_ref()));
+        self.vars.explicit_env()
+    }
+}
+
+static SECRET_MARKERS: &[&str] = &["PASSWORD", "PASSWD", "SECRET", "TOKEN", "API_KEY", "AUTH"];
+
+// We don't want debug logs to become a way to leak credentials.
+fn redact<'a>(key: &str, value: &'a OsStr) -> std::borrow::Cow<'a, str> {
+    let key = key.to_ascii_uppercase();
+    if SECRET_MARKERS.iter().any(|marker| key.contains(marker)) {
+        "<redacted>".into()
+    } else {
+        value.to_string_lossy()
+    }
+}
+
+/// Logs what `invocation` runs, where, and how its environment differs from
+/// ours, which is invaluable when a build works in a shell but not through
+/// us. This only does anything if debug logging is enabled.
+pub fn log_command_env(invocation: &Invocation) {
+    if !log::log_enabled!(log::Level::Debug) {
+        return;
+    }
+    let command_line = invocation.command_line();
+    match invocation.current_dir() {
+        Some(current_dir) => log::debug!("running `{}` in {:?}", command_line, current_dir),
+        None => log::debug!("running `{}`", command_line),
+    }
+    let program = invocation.program().to_string_lossy();
+    let explicit = invocation.env_vars();
+    for (key, value) in explicit {
+        let key = key.to_string_lossy();
+        match std::env::var_os(&*key) {
+            Some(ref parent) if parent == value => (),
+            Some(_) => log::debug!(
+                "`{}` env changes {}={:?}",
+                program,
+                key,
+                redact(&key, value)
+            ),
+            None => log::debug!("`{}` env adds {}={:?}", program, key, redact(&key, value)),
         }
-        if let Some(ssh_auth_sock) = self.ssh_auth_sock.as_ref() {
-            env.push(("SSH_AUTH_SOCK", ssh_auth_sock.as_ref()));
+    }
+    // Anything we don't explicitly pass along is only lost if it's pure.
+    if invocation.is_pure() {
+        for (key, _) in std::env::vars_os() {
+            if !explicit
+                .iter()
+                .any(|(explicit_key, _)| *explicit_key == key)
+            {
+                log::debug!("`{}` env removes {}", program, key.to_string_lossy());
+            }
         }
-        env
+    }
+}
+
+fn parse_env_value(raw: &str) -> String {
+    let raw = raw.trim();
+    let quoted = |quote| raw.len() >= 2 && raw.starts_with(quote) && raw.ends_with(quote);
+    if quoted('\'') {
+        // Single quotes are completely literal
+        raw[1..raw.len() - 1].to_owned()
+    } else if quoted('"') {
+        let mut value = String::with_capacity(raw.len());
+        let mut chars = raw[1..raw.len() - 1].chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some(escaped) => value.push(escaped),
+                    None => value.push(c),
+                }
+            } else {
+                value.push(c);
+            }
+        }
+        value
+    } else {
+        // Unquoted values can have trailing comments
+        raw.split(" #")
+            .next()
+            .unwrap_or_default()
+            .trim_end()
+            .to_owned()
+    }
+}
+
+/// Parses a `.env` file of `KEY=VALUE` lines, which can optionally be prefixed
+/// with `export`. Blank lines and `#` comments are ignored.
+pub fn load_env_file(path: &Path) -> io::Result<Vec<(String, String)>> {
+    let contents = fs::read_to_string(path)?;
+    let mut vars = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").map_or(line, str::trim_start);
+        let mut parts = line.splitn(2, '=');
+        let key = parts.next().unwrap_or_default().trim();
+        let value = parts.next().filter(|_| !key.is_empty()).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "line {} of {:?} isn't of the form `KEY=VALUE`",
+                    index + 1,
+                    path
+                ),
+            )
+        })?;
+        vars.push((key.to_owned(), parse_env_value(value)));
+    }
+    log::info!("loaded {} env vars from {:?}", vars.len(), path);
+    Ok(vars)
+}
+
+#[derive(Debug)]
+pub struct EnvFileError {
+    path: PathBuf,
+    cause: io::Error,
+}
+
+impl Display for EnvFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to load env file {:?}: {}", self.path, self.cause)
+    }
+}
+
+/// Loads `.env` and then `.env.<triple>` from `root_dir`, so that target-specific
+/// values win. Neither file has to exist.
+pub fn load_target_env_files(
+    root_dir: &Path,
+    triple: &str,
+) -> Result<Vec<(String, String)>, EnvFileError> {
+    let mut vars = Vec::new();
+    for name in &[".env".to_owned(), format!(".env.{}", triple)] {
+        let path = root_dir.join(name);
+        if path.is_file() {
+            vars.extend(load_env_file(&path).map_err(|cause| EnvFileError { path, cause })?);
+        }
+    }
+    Ok(vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_file_handles_quotes_comments_and_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        fs::write(
+            &path,
+            r#"# API keys
+export API_KEY=abc123
+
+GREETING="hello \"world\"\n"
+LITERAL='no $expansion\n'
+FEATURE=on # trailing comment
+EMPTY=
+"#,
+        )
+        .unwrap();
+        let vars = load_env_file(&path).unwrap();
+        let expected = [
+            ("API_KEY", "abc123"),
+            ("GREETING", "hello \"world\"\n"),
+            ("LITERAL", "no $expansion\\n"),
+            ("FEATURE", "on"),
+            ("EMPTY", ""),
+        ];
+        assert_eq!(
+            vars,
+            expected
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn env_file_rejects_lines_without_a_value() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        fs::write(&path, "JUST_A_KEY\n").unwrap();
+        let err = load_env_file(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn target_env_file_wins_over_the_shared_one() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".env"), "MODE=shared\nKEY=1\n").unwrap();
+        fs::write(
+            dir.path().join(".env.aarch64-linux-android"),
+            "MODE=android\n",
+        )
+        .unwrap();
+        let vars = load_target_env_files(dir.path(), "aarch64-linux-android").unwrap();
+        let mode = vars.iter().rev().find(|(key, _)| key == "MODE").unwrap();
+        assert_eq!(mode.1, "android");
+        assert_eq!(
+            load_target_env_files(dir.path(), "aarch64-apple-ios")
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn secrets_are_redacted_from_env_logs() {
+        let value = OsStr::new("hunter2");
+        assert_eq!(redact("KEYSTORE_PASSWORD", value), "<redacted>");
+        assert_eq!(redact("github_token", value), "<redacted>");
+        assert_eq!(redact("ANDROID_NDK_HOME", value), "hunter2");
+    }
+
+    #[test]
+    fn secrets_are_redacted_from_logged_commands() {
+        use once_cell_regex::exports::once_cell::sync::Lazy;
+        use std::sync::Mutex;
+
+        static LOGGED: Lazy<Mutex<Vec<String>>> = Lazy::new(Default::default);
+
+        struct Capture;
+
+        impl log::Log for Capture {
+            fn enabled(&self, _metadata: &log::Metadata<'_>) -> bool {
+                true
+            }
+
+            fn log(&self, record: &log::Record<'_>) {
+                LOGGED
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .push(record.args().to_string());
+            }
+
+            fn flush(&self) {}
+        }
+
+        static CAPTURE: Capture = Capture;
+        log::set_logger(&CAPTURE).expect("nothing else in the tests installs a logger");
+        log::set_max_level(log::LevelFilter::Debug);
+        // Other tests can log at the same time, so this only looks at what's
+        // about our own made-up program.
+        let program = "redaction-test-program";
+        log_command_env(
+            &Invocation::impure(program)
+                .with_env_var("KEYSTORE_PASSWORD", "hunter2")
+                .with_env_var("REDACTION_TEST_MODE", "visible"),
+        );
+        let logged = LOGGED
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|line| line.contains(program))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert!(logged.iter().all(|line| !line.contains("hunter2")));
+        assert!(logged
+            .iter()
+            .any(|line| line.contains("KEYSTORE_PASSWORD=\"<redacted>\"")));
+        assert!(logged
+            .iter()
+            .any(|line| line.contains("REDACTION_TEST_MODE=\"visible\"")));
     }
 }
@@ -0,0 +1,274 @@
+use fnv::FnvHasher;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    hash::Hasher as _,
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+pub static FILE_NAME: &str = ".mobile-generated.toml";
+
+/// Records which files were generated by `cargo mobile init`, so that they can
+/// later be removed without risking anything the user made themselves. Paths
+/// are stored relative to the project root.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct GenerationManifest {
+    paths: Vec<PathBuf>,
+    #[serde(default)]
+    template_ref: Option<String>,
+    /// Hashes of each rendered template file as of the last time we wrote it,
+    /// which is how `cargo mobile regen` tells if the user modified it since.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    files: BTreeMap<PathBuf, String>,
+}
+
+impl GenerationManifest {
+    pub fn path(root: &Path) -> PathBuf {
+        root.join(FILE_NAME)
+    }
+
+    /// Adds `path`, which can be either absolute (within `root`) or relative to
+    /// `root`.
+    pub fn add(&mut self, root: &Path, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        let path = path.strip_prefix(root).unwrap_or(path).to_owned();
+        if !self.paths.contains(&path) {
+            self.paths.push(path);
+        }
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    pub fn set_template_ref(&mut self, template_ref: impl Into<String>) {
+        self.template_ref = Some(template_ref.into());
+    }
+
+    /// Which version of the templates these paths were generated from. This is
+    /// `None` for manifests written before we started recording it.
+    pub fn template_ref(&self) -> Option<&str> {
+        self.template_ref.as_deref()
+    }
+
+    /// The hash recorded for the file at `path`, which is relative to the
+    /// project root.
+    pub fn file_hash(&self, path: &Path) -> Option<&str> {
+        self.files.get(path).map(String::as_str)
+    }
+
+    pub fn set_file_hash(&mut self, path: impl Into<PathBuf>, hash: impl Into<String>) {
+        self.files.insert(path.into(), hash.into());
+    }
+
+    pub fn load(root: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(Self::path(root))?;
+        toml::from_str(&contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn write(&self, root: &Path) -> io::Result<()> {
+        let contents = toml::to_string_pretty(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        crate::util::write_str_if_changed(Self::path(root), contents).map(|_| ())
+    }
+}
+
+pub fn hash_contents(contents: &[u8]) -> String {
+    let mut hasher = FnvHasher::default();
+    hasher.write(contents);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Identifies the installed version of the platform templates. Templates are
+/// installed from a plain copy rather than a checkout, so this is a hash of
+/// their contents instead of a commit.
+pub fn current_template_ref() -> io::Result<String> {
+    let dir = crate::templating::platform_pack_dir()
+        .map_err(|err| io::Error::new(io::ErrorKind::NotFound, err.to_string()))?;
+    crate::util::hash_dir(&dir, &[])
+}
+
+/// Checks if the project at `root` was generated from a different version of
+/// the templates than `current_template_ref`. Projects that didn't record a
+/// version are always considered stale.
+pub fn generation_is_stale(root: &Path, current_template_ref: &str) -> io::Result<bool> {
+    let manifest = GenerationManifest::load(root)?;
+    let stale = manifest.template_ref() != Some(current_template_ref);
+    log::info!(
+        "generated from templates {:?}, and current templates are {:?}",
+        manifest.template_ref(),
+        current_template_ref
+    );
+    Ok(stale)
+}
+
+/// Lists the generated paths that regenerating from `current_template_ref`
+/// could change, which is all of them if the generation is stale and none of
+/// them otherwise.
+pub fn stale_generated_paths(root: &Path, current_template_ref: &str) -> io::Result<Vec<PathBuf>> {
+    let manifest = GenerationManifest::load(root)?;
+    if manifest.template_ref() == Some(current_template_ref) {
+        Ok(Vec::new())
+    } else {
+        Ok(manifest
+            .paths()
+            .iter()
+            .map(|path| root.join(path))
+            .collect())
+    }
+}
+
+// Anything absolute or with a `..` could point outside of the project, which
+// means somebody tampered with the manifest.
+fn is_contained(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+// Symlinks are removed themselves rather than followed, since i.e. the asset
+// dir is symlinked into the Xcode project and we definitely don't want to
+// delete the user's assets.
+fn remove_no_follow(path: &Path) -> io::Result<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            remove_no_follow(&entry?.path())?;
+        }
+        fs::remove_dir(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+// Removes the dirs between `path` and `root` that are left empty, so that
+// removing every file in a generated project removes the project too.
+fn remove_empty_parents(root: &Path, path: &Path) -> io::Result<()> {
+    for dir in path.ancestors().skip(1) {
+        if dir == root || !dir.starts_with(root) {
+            break;
+        }
+        match fs::read_dir(dir).map(|mut entries| entries.next().is_none()) {
+            Ok(true) => fs::remove_dir(dir)?,
+            Ok(false) => break,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+/// Removes exactly the paths listed in the project's generation manifest, and
+/// then the manifest itself. Nothing outside of the manifest gets touched,
+/// aside from dirs that end up empty.
+pub fn remove_generated(root: &Path) -> io::Result<()> {
+    let manifest = GenerationManifest::load(root)?;
+    if let Some(path) = manifest.paths().iter().find(|path| !is_contained(path)) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "refusing to remove {:?}, since it isn't within the project root",
+                path
+            ),
+        ));
+    }
+    for path in manifest.paths() {
+        let path = root.join(path);
+        log::info!("removing generated path {:?}", path);
+        remove_no_follow(&path)?;
+    }
+    for path in manifest.paths() {
+        remove_empty_parents(root, &root.join(path))?;
+    }
+    fs::remove_file(GenerationManifest::path(root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generations_from_other_templates_are_stale() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let mut manifest = GenerationManifest::default();
+        manifest.add(root, "gen/android/build.gradle.kts");
+        manifest.write(root).unwrap();
+        // Not recording a version at all counts as stale
+        assert!(generation_is_stale(root, "abc").unwrap());
+        manifest.set_template_ref("old");
+        manifest.write(root).unwrap();
+        assert!(generation_is_stale(root, "abc").unwrap());
+        assert_eq!(
+            stale_generated_paths(root, "abc").unwrap(),
+            vec![root.join("gen/android/build.gradle.kts")]
+        );
+        assert!(!generation_is_stale(root, "old").unwrap());
+        assert!(stale_generated_paths(root, "old").unwrap().is_empty());
+    }
+
+    #[test]
+    fn only_generated_files_are_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let project = root.join("gen/android");
+        fs::create_dir_all(project.join("app/src")).unwrap();
+        fs::write(project.join("build.gradle.kts"), "").unwrap();
+        fs::write(project.join("app/src/Main.kt"), "").unwrap();
+        fs::write(project.join("app/keystore.properties"), "mine").unwrap();
+        fs::write(root.join("Cargo.toml"), "").unwrap();
+        let mut manifest = GenerationManifest::default();
+        manifest.add(root, project.join("build.gradle.kts"));
+        manifest.add(root, "gen/android/app/src/Main.kt");
+        manifest.write(root).unwrap();
+
+        remove_generated(root).unwrap();
+        assert!(!project.join("build.gradle.kts").exists());
+        // Emptied out, so it goes too
+        assert!(!project.join("app/src").exists());
+        assert_eq!(
+            fs::read_to_string(project.join("app/keystore.properties")).unwrap(),
+            "mine"
+        );
+        assert!(root.join("Cargo.toml").exists());
+        assert!(!GenerationManifest::path(root).exists());
+    }
+
+    #[test]
+    fn paths_outside_the_root_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("app");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(dir.path().join("precious"), "").unwrap();
+        let mut manifest = GenerationManifest::default();
+        manifest.add(&root, "../precious");
+        manifest.write(&root).unwrap();
+        assert_eq!(
+            remove_generated(&root).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert!(dir.path().join("precious").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_removed_without_following_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("assets")).unwrap();
+        fs::write(root.join("assets/icon.png"), "").unwrap();
+        fs::create_dir_all(root.join("gen/apple")).unwrap();
+        std::os::unix::fs::symlink("../../assets", root.join("gen/apple/assets")).unwrap();
+        let mut manifest = GenerationManifest::default();
+        manifest.add(root, "gen/apple/assets");
+        manifest.write(root).unwrap();
+        remove_generated(root).unwrap();
+        assert!(!root.join("gen").exists());
+        assert!(root.join("assets/icon.png").exists());
+    }
+}
@@ -0,0 +1,189 @@
+//! Turning the one image in `app.icon` into every icon each platform wants,
+//! so that nobody has to export them all by hand.
+
+use crate::{
+    config::app::icon::{Icon, Rgb},
+    util,
+};
+use image::{
+    imageops::{self, FilterType},
+    ImageError, ImageFormat, Rgba, RgbaImage,
+};
+use std::{
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+// Smaller sources work too, but the biggest icons will be upscaled from them.
+static RECOMMENDED_SIZE: u32 = 1024;
+
+#[derive(Debug)]
+pub enum Error {
+    LoadFailed {
+        path: PathBuf,
+        cause: ImageError,
+    },
+    NotSquare {
+        path: PathBuf,
+        width: u32,
+        height: u32,
+    },
+    DirCreationFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
+    PngWriteFailed {
+        path: PathBuf,
+        cause: ImageError,
+    },
+    WriteFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LoadFailed { path, cause } => {
+                write!(f, "Failed to load icon source {:?}: {}", path, cause)
+            }
+            Self::NotSquare {
+                path,
+                width,
+                height,
+            } => write!(
+                f,
+                "Icon source {:?} is {}x{}, but it needs to be square.",
+                path, width, height
+            ),
+            Self::DirCreationFailed { path, cause } => {
+                write!(f, "Failed to create icon dir {:?}: {}", path, cause)
+            }
+            Self::PngWriteFailed { path, cause } => {
+                write!(f, "Failed to write icon {:?}: {}", path, cause)
+            }
+            Self::WriteFailed { path, cause } => write!(f, "Failed to write {:?}: {}", path, cause),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug)]
+pub struct Source {
+    image: RgbaImage,
+    background: Rgb,
+}
+
+impl Source {
+    pub fn load(icon: &Icon) -> Result<Self, Error> {
+        let path = icon.source();
+        let image = image::open(path)
+            .map_err(|cause| Error::LoadFailed {
+                path: path.to_owned(),
+                cause,
+            })?
+            .into_rgba8();
+        let (width, height) = image.dimensions();
+        if width != height {
+            return Err(Error::NotSquare {
+                path: path.to_owned(),
+                width,
+                height,
+            });
+        }
+        if width < RECOMMENDED_SIZE {
+            log::warn!(
+                "icon source {:?} is only {}x{}, so some icons will be blurry; {}x{} or bigger is best",
+                path,
+                width,
+                height,
+                RECOMMENDED_SIZE,
+                RECOMMENDED_SIZE
+            );
+        }
+        Ok(Self {
+            image,
+            background: icon.background(),
+        })
+    }
+
+    pub fn background(&self) -> Rgb {
+        self.background
+    }
+
+    /// The whole source, scaled to `size` pixels square.
+    pub fn resized(&self, size: u32) -> RgbaImage {
+        imageops::resize(&self.image, size, size, FilterType::Lanczos3)
+    }
+
+    /// The source scaled to `inner` pixels square, and centered in a
+    /// transparent square of `size` pixels.
+    pub fn padded(&self, size: u32, inner: u32) -> RgbaImage {
+        let mut canvas = RgbaImage::new(size, size);
+        let offset = (size - inner) / 2;
+        imageops::overlay(&mut canvas, &self.resized(inner), offset, offset);
+        canvas
+    }
+
+    /// Like [`Source::resized`], but with the background filled in, for
+    /// places where icons can't be transparent.
+    pub fn opaque(&self, size: u32) -> RgbaImage {
+        let [r, g, b] = self.background.0;
+        let mut image = self.resized(size);
+        for pixel in image.pixels_mut() {
+            let Rgba([fg_r, fg_g, fg_b, a]) = *pixel;
+            let a = u32::from(a);
+            let blend = |fg: u8, bg: u8| {
+                ((u32::from(fg) * a + u32::from(bg) * (255 - a) + 127) / 255) as u8
+            };
+            *pixel = Rgba([blend(fg_r, r), blend(fg_g, g), blend(fg_b, b), 255]);
+        }
+        image
+    }
+
+    /// Like [`Source::resized`], but masked to a circle. The edge is
+    /// antialiased, since otherwise it looks jagged at small sizes.
+    pub fn round(&self, size: u32) -> RgbaImage {
+        let mut image = self.resized(size);
+        let radius = size as f32 / 2.0;
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let dx = x as f32 + 0.5 - radius;
+            let dy = y as f32 + 0.5 - radius;
+            let coverage = (radius - dx.hypot(dy) + 0.5).clamp(0.0, 1.0);
+            pixel.0[3] = (f32::from(pixel.0[3]) * coverage).round() as u8;
+        }
+        image
+    }
+}
+
+fn create_parent(path: &Path) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        util::create_dir_all(parent).map_err(|cause| Error::DirCreationFailed {
+            path: parent.to_owned(),
+            cause,
+        })?;
+    }
+    Ok(())
+}
+
+pub fn write_png(image: &RgbaImage, path: &Path) -> Result<(), Error> {
+    create_parent(path)?;
+    image
+        .save_with_format(path, ImageFormat::Png)
+        .map_err(|cause| Error::PngWriteFailed {
+            path: path.to_owned(),
+            cause,
+        })
+}
+
+/// For the files that go along with the icons, like `Contents.json`.
+pub fn write_file(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), Error> {
+    create_parent(path)?;
+    fs::write(path, contents).map_err(|cause| Error::WriteFailed {
+        path: path.to_owned(),
+        cause,
+    })
+}
@@ -5,12 +5,19 @@ use crate::{
     config::{
         self,
         metadata::{self, Metadata},
+        preset::Preset,
         Config,
     },
-    dot_cargo, opts, project, templating,
+    dot_cargo,
+    generated::{self, GenerationManifest},
+    opts, project, regen,
+    target::TargetTrait as _,
+    templating,
     util::{
         self,
         cli::{Report, Reportable, TextWrapper},
+        output::{self, Event},
+        prompt,
     },
 };
 use std::{
@@ -58,6 +65,11 @@ pub enum Error {
     AndroidEnvFailed(android::env::Error),
     AndroidInitFailed(android::project::Error),
     DotCargoWriteFailed(dot_cargo::WriteError),
+    TargetRunnerSetFailed(dot_cargo::SetTargetRunnerError),
+    GenerationManifestWriteFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
     DotFirstInitDeleteFailed {
         path: PathBuf,
         cause: io::Error,
@@ -83,6 +95,8 @@ impl Reportable for Error {
             #[cfg(target_os = "macos")]
             Self::AppleInitFailed(err) => err.report(),
             Self::DotCargoWriteFailed(err) => err.report(),
+            Self::TargetRunnerSetFailed(err) => err.report(),
+            Self::GenerationManifestWriteFailed { path, cause } => Report::error(format!("Failed to write generation manifest {:?}", path), cause),
             Self::DotFirstInitDeleteFailed { path, cause } => Report::action_request(format!("Failed to delete first init dot file {:?}; the project generated successfully, but `cargo mobile init` will have unexpected results unless you manually delete this file!", path), cause),
             Self::OpenInEditorFailed(err) => Report::error("Failed to open project in editor (your project generated successfully though, so no worries!)", err),
         }
@@ -93,14 +107,19 @@ pub fn exec(
     wrapper: &TextWrapper,
     non_interactive: opts::NonInteractive,
     skip_dev_tools: opts::SkipDevTools,
+    #[cfg_attr(not(target_os = "macos"), allow(unused_variables))]
     reinstall_deps: opts::ReinstallDeps,
     open_in_editor: opts::OpenInEditor,
+    force_sync: opts::ForceSync,
+    preset: Preset,
     submodule_commit: Option<String>,
     cwd: impl AsRef<Path>,
 ) -> Result<Config, Error> {
     let cwd = cwd.as_ref();
     let (config, config_origin) =
-        Config::load_or_gen(cwd, non_interactive, wrapper).map_err(Error::ConfigLoadOrGenFailed)?;
+        Config::load_or_gen(cwd, non_interactive, &preset, &prompt::Stdin, wrapper)
+            .map_err(Error::ConfigLoadOrGenFailed)?;
+    util::set_default_dir_mode(config.app().dir_mode());
     let dot_first_init_path = config.app().root_dir().join(DOT_FIRST_INIT_FILE_NAME);
     let dot_first_init_exists = {
         let dot_first_init_exists = dot_first_init_path.exists();
@@ -128,7 +147,7 @@ pub fn exec(
 
     let asset_dir = config.app().asset_dir();
     if !asset_dir.is_dir() {
-        fs::create_dir_all(&asset_dir)
+        util::create_dir_all(&asset_dir)
             .map_err(|cause| Error::AssetDirCreationFailed { asset_dir, cause })?;
     }
     if skip_dev_tools.no()
@@ -163,7 +182,9 @@ pub fn exec(
 
     // Generate Xcode project
     #[cfg(target_os = "macos")]
-    if metadata.apple().supported() {
+    if !config.app().platform_enabled(apple::NAME) {
+        println!("Skipping iOS init, since it's not in `app.platforms`");
+    } else if metadata.apple().supported() {
         apple::project::gen(
             config.apple(),
             metadata.apple(),
@@ -174,6 +195,7 @@ pub fn exec(
             skip_dev_tools,
             reinstall_deps,
             &filter,
+            force_sync,
         )
         .map_err(Error::AppleInitFailed)?;
     } else {
@@ -181,12 +203,20 @@ pub fn exec(
     }
 
     // Generate Android Studio project
-    if metadata.android().supported() {
+    if !config.app().platform_enabled(android::NAME) {
+        println!("Skipping Android init, since it's not in `app.platforms`");
+    } else if metadata.android().supported() {
         match android::env::Env::new() {
-            Ok(env) => {
-                android::project::gen(config.android(), &env, &bike, &filter, &mut dot_cargo)
-                    .map_err(Error::AndroidInitFailed)?
-            }
+            Ok(env) => android::project::gen(
+                config.android(),
+                &env,
+                &bike,
+                &filter,
+                &mut dot_cargo,
+                wrapper,
+                force_sync,
+            )
+            .map_err(Error::AndroidInitFailed)?,
             Err(err) => {
                 if err.sdk_or_ndk_issue() {
                     Report::action_request(
@@ -208,6 +238,78 @@ pub fn exec(
     dot_cargo
         .write(config.app())
         .map_err(Error::DotCargoWriteFailed)?;
+    {
+        // This way, `cargo run --target <triple>` ends up running on a device
+        // instead of failing to run an executable for the wrong platform.
+        let mut mobile_triples = Vec::new();
+        #[cfg(target_os = "macos")]
+        if config.app().platform_enabled(apple::NAME) && metadata.apple().supported() {
+            mobile_triples.extend(apple::target::Target::all().values().map(|t| t.triple));
+        }
+        if config.app().platform_enabled(android::NAME) && metadata.android().supported() {
+            mobile_triples.extend(android::target::Target::all().values().map(|t| t.triple));
+        }
+        for triple in mobile_triples {
+            dot_cargo::ensure_target_runner(config.app().root_dir(), triple)
+                .map_err(Error::TargetRunnerSetFailed)?;
+        }
+    }
+    {
+        // Only what's in the platform projects is recorded, since everything
+        // else we generate is likely to get modified by the user. The rendered
+        // templates get added by `regen::record`, so this is just whatever
+        // else we put there.
+        let root_dir = config.app().root_dir();
+        let mut manifest = GenerationManifest::default();
+        let asset_dir_name = config
+            .app()
+            .asset_dir()
+            .file_name()
+            .expect("developer error: asset dir had no file name")
+            .to_owned();
+        let mut add_if_exists = |path: PathBuf| {
+            if fs::symlink_metadata(&path).is_ok() {
+                manifest.add(root_dir, path);
+            }
+        };
+        #[cfg(target_os = "macos")]
+        if config.apple().project_dir_exists() {
+            let project_dir = config.apple().project_dir();
+            add_if_exists(project_dir.join(format!("{}.xcodeproj", config.app().name())));
+            add_if_exists(project_dir.join(&asset_dir_name));
+            add_if_exists(project_dir.join(util::ASSET_SYNC_MANIFEST_FILE_NAME));
+            output::emit(&Event::Project {
+                platform: apple::NAME,
+                path: &config.apple().project_dir(),
+            });
+        }
+        if config.android().project_dir_exists() {
+            let project_dir = config.android().project_dir();
+            add_if_exists(
+                project_dir
+                    .join("app/src/main/assets")
+                    .join(&asset_dir_name),
+            );
+            add_if_exists(project_dir.join(util::ASSET_SYNC_MANIFEST_FILE_NAME));
+            output::emit(&Event::Project {
+                platform: android::NAME,
+                path: &config.android().project_dir(),
+            });
+        }
+        match generated::current_template_ref() {
+            Ok(template_ref) => manifest.set_template_ref(template_ref),
+            Err(err) => log::warn!("failed to get current template version: {}", err),
+        }
+        if let Err(err) = regen::record(&config, &metadata, &mut manifest) {
+            log::warn!("failed to record generated file hashes: {:?}", err);
+        }
+        manifest
+            .write(root_dir)
+            .map_err(|cause| Error::GenerationManifestWriteFailed {
+                path: GenerationManifest::path(root_dir),
+                cause,
+            })?;
+    }
     if dot_first_init_exists {
         log::info!("deleting first init dot file at {:?}", dot_first_init_path);
         fs::remove_file(&dot_first_init_path).map_err(|cause| Error::DotFirstInitDeleteFailed {
@@ -3,17 +3,28 @@
 pub mod android;
 #[cfg(target_os = "macos")]
 pub mod apple;
+pub mod bindgen;
+pub mod cache;
 pub mod config;
+pub mod deinit;
 pub mod device;
-mod dot_cargo;
+pub mod doctor;
+pub mod dot_cargo;
 pub mod env;
+pub mod generated;
+pub mod icon;
 pub mod init;
 pub mod opts;
 pub mod os;
+pub mod prepared;
+pub mod profiling;
 mod project;
+pub mod regen;
 pub mod target;
 mod templating;
 pub mod update;
 pub mod util;
+pub mod version;
+pub mod watch;
 
 pub static NAME: &str = "mobile";
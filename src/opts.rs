@@ -40,12 +40,44 @@ impl NoiseLevel {
 
 yes_or_no!(ForceColor);
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+    Human,
+    Json,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self::Human
+    }
+}
+
+impl OutputFormat {
+    pub const NAMES: &'static [&'static str] = &["human", "json"];
+
+    // clap has already checked this against `NAMES`.
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "json" => Self::Json,
+            _ => Self::Human,
+        }
+    }
+
+    pub fn json(self) -> bool {
+        matches!(self, Self::Json)
+    }
+}
+
 yes_or_no!(SkipDevTools);
 
 yes_or_no!(ReinstallDeps);
 
 yes_or_no!(OpenInEditor);
 
+yes_or_no!(ForceSync);
+
+yes_or_no!(DryRun);
+
 #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum Profile {
     Debug,
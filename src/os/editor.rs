@@ -0,0 +1,60 @@
+use std::{
+    fmt::{self, Display},
+    path::Path,
+};
+
+#[derive(Debug)]
+pub enum DetectEditorError {
+    NotSet,
+}
+
+impl Display for DetectEditorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotSet => write!(f, "Neither `VISUAL` nor `EDITOR` is set."),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum OpenFileError {
+    LaunchFailed(bossy::Error),
+}
+
+impl Display for OpenFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LaunchFailed(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Outside of macOS, there's no reliable way to ask which app edits Rust
+/// code, so we go with whatever `VISUAL` or `EDITOR` says.
+#[derive(Debug)]
+pub struct Application {
+    command: String,
+}
+
+impl Application {
+    pub fn detect_editor() -> Result<Self, DetectEditorError> {
+        ["VISUAL", "EDITOR"]
+            .iter()
+            .find_map(|var| {
+                std::env::var(var)
+                    .ok()
+                    .filter(|command| !command.trim().is_empty())
+            })
+            .map(|command| Self { command })
+            .ok_or(DetectEditorError::NotSet)
+    }
+
+    pub fn open_file(&self, path: impl AsRef<Path>) -> Result<(), OpenFileError> {
+        // These often include flags, i.e. `code --wait`
+        bossy::Command::impure_parse(&self.command)
+            .with_arg(path.as_ref())
+            .run_and_wait()
+            .map_err(OpenFileError::LaunchFailed)?;
+        Ok(())
+    }
+}
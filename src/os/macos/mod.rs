@@ -102,3 +102,36 @@ pub fn open_file_with(
         .run_and_wait()?;
     Ok(())
 }
+
+// `sysctl.proc_translated` is 1 under Rosetta, 0 when running natively on
+// Apple Silicon, and doesn't exist at all on Intel Macs.
+fn parse_proc_translated(raw: &str) -> bool {
+    raw.trim() == "1"
+}
+
+/// Checks if we're an x86_64 process being translated by Rosetta.
+pub fn running_under_rosetta() -> bool {
+    bossy::Command::impure_parse("sysctl -in sysctl.proc_translated")
+        .run_and_wait_for_output()
+        .map(|output| parse_proc_translated(&String::from_utf8_lossy(output.stdout())))
+        .unwrap_or_else(|err| {
+            log::info!(
+                "failed to check for Rosetta, assuming it's not in use: {}",
+                err
+            );
+            false
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_a_translated_process_is_under_rosetta() {
+        assert!(parse_proc_translated("1\n"));
+        assert!(!parse_proc_translated("0\n"));
+        // Intel Macs don't have the sysctl at all
+        assert!(!parse_proc_translated(""));
+    }
+}
@@ -1,10 +1,15 @@
 #![allow(unsafe_code)]
 
+#[cfg(not(target_os = "macos"))]
+mod editor;
 #[cfg(target_os = "macos")]
 mod macos;
+mod pipe;
+mod signal;
+mod stdio;
 
+#[cfg(not(target_os = "macos"))]
+pub use self::editor::*;
 #[cfg(target_os = "macos")]
 pub use self::macos::*;
-
-#[cfg(not(target_os = "macos"))]
-compile_error!("Host platform not yet supported by cargo-mobile! We'd love if you made a PR to add support for this platform ❤️");
+pub use self::{pipe::*, signal::*, stdio::*};
@@ -0,0 +1,58 @@
+use std::{fs::File, io};
+
+/// Creates an anonymous pipe, returning its `(reader, writer)` ends.
+///
+/// Both ends are close-on-exec, so children only get whichever end is
+/// explicitly passed to them as stdio. `pipe2` sets that atomically, since
+/// otherwise a child spawned on another thread in the meantime would inherit
+/// both ends, and we'd never see EOF until it exited.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn pipe() -> io::Result<(File, File)> {
+    use std::os::unix::io::FromRawFd as _;
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // We now own both of these, and nothing else will close them
+    Ok(unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) })
+}
+
+/// Creates an anonymous pipe, returning its `(reader, writer)` ends.
+///
+/// Both ends are close-on-exec, so children only get whichever end is
+/// explicitly passed to them as stdio. There's no `pipe2` here, so there's a
+/// brief window where a child spawned on another thread could inherit them.
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+pub fn pipe() -> io::Result<(File, File)> {
+    use std::os::unix::io::FromRawFd as _;
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // We now own both of these, and nothing else will close them
+    let (reader, writer) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+    for fd in &fds {
+        if unsafe { libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok((reader, writer))
+}
+
+/// Creates an anonymous pipe, returning its `(reader, writer)` ends.
+///
+/// Neither end is inheritable, so children only get whichever end is
+/// explicitly passed to them as stdio.
+#[cfg(windows)]
+pub fn pipe() -> io::Result<(File, File)> {
+    use std::{os::windows::io::FromRawHandle as _, ptr};
+    let (mut reader, mut writer) = (ptr::null_mut(), ptr::null_mut());
+    // Passing no security attributes makes both handles non-inheritable
+    if unsafe { winapi::um::namedpipeapi::CreatePipe(&mut reader, &mut writer, ptr::null_mut(), 0) }
+        == 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    // We now own both of these, and nothing else will close them
+    Ok(unsafe { (File::from_raw_handle(reader), File::from_raw_handle(writer)) })
+}
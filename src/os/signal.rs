@@ -0,0 +1,159 @@
+use once_cell_regex::exports::once_cell::sync::Lazy;
+use std::sync::Mutex;
+
+static CHILDREN: Lazy<Mutex<Vec<u32>>> = Lazy::new(Default::default);
+
+/// Keeps a child process registered for cleanup until dropped.
+#[derive(Debug)]
+pub struct ChildGuard {
+    pid: u32,
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        let mut children = CHILDREN.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(index) = children.iter().position(|pid| *pid == self.pid) {
+            children.swap_remove(index);
+        }
+    }
+}
+
+/// Registers a running child to be killed if we receive `SIGINT` or `SIGTERM`
+/// (or on Windows, if the console tells us to stop).
+pub fn register_child(pid: u32) -> ChildGuard {
+    log::debug!("registering child process {}", pid);
+    CHILDREN
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .push(pid);
+    ChildGuard { pid }
+}
+
+/// Forcibly kills a child process. It still needs to be waited on afterward.
+#[cfg(unix)]
+pub fn kill_child(pid: u32) {
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) } != 0 {
+        log::warn!("failed to kill child process {}", pid);
+    }
+}
+
+/// Forcibly kills a child process. It still needs to be waited on afterward.
+#[cfg(windows)]
+pub fn kill_child(pid: u32) {
+    use winapi::um::{handleapi, processthreadsapi, winnt};
+    let killed = unsafe {
+        let process = processthreadsapi::OpenProcess(winnt::PROCESS_TERMINATE, 0, pid);
+        if process.is_null() {
+            false
+        } else {
+            let killed = processthreadsapi::TerminateProcess(process, 1) != 0;
+            handleapi::CloseHandle(process);
+            killed
+        }
+    };
+    if !killed {
+        log::warn!("failed to kill child process {}", pid);
+    }
+}
+
+// This isn't strictly async-signal-safe, but `try_lock` at least can't
+// deadlock if we were interrupted while holding the lock.
+#[cfg(unix)]
+fn kill_children() {
+    if let Ok(children) = CHILDREN.try_lock() {
+        for pid in children.iter() {
+            unsafe {
+                libc::kill(*pid as libc::pid_t, libc::SIGTERM);
+            }
+        }
+    }
+}
+
+// Console control handlers run on their own thread, so unlike on Unix, we're
+// free to block here.
+#[cfg(windows)]
+fn kill_children() {
+    let children = CHILDREN.lock().unwrap_or_else(|err| err.into_inner());
+    for pid in children.iter() {
+        kill_child(*pid);
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn handle_signal(signal: libc::c_int) {
+    kill_children();
+    unsafe { libc::_exit(128 + signal) }
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn handle_ctrl(_ctrl_type: winapi::shared::minwindef::DWORD) -> i32 {
+    kill_children();
+    // This lets the default handler take it from here, which exits.
+    0
+}
+
+/// Makes sure that interrupting us doesn't leave orphaned builds behind.
+#[cfg(unix)]
+pub fn install_signal_handlers() {
+    let handler = handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    for signal in &[libc::SIGINT, libc::SIGTERM] {
+        if unsafe { libc::signal(*signal, handler) } == libc::SIG_ERR {
+            log::error!("failed to install handler for signal {}", signal);
+        }
+    }
+}
+
+/// Makes sure that interrupting us doesn't leave orphaned builds behind.
+#[cfg(windows)]
+pub fn install_signal_handlers() {
+    if unsafe { winapi::um::consoleapi::SetConsoleCtrlHandler(Some(handle_ctrl), 1) } == 0 {
+        log::error!(
+            "failed to install console control handler: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn children_are_only_registered_while_guarded() {
+        // Nothing real has this pid, and other tests registering their own
+        // children won't get in the way.
+        let pid = u32::MAX - 1;
+        let registered = || CHILDREN.lock().unwrap().contains(&pid);
+        let guard = register_child(pid);
+        assert!(registered());
+        drop(guard);
+        assert!(!registered());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn registered_children_are_terminated() {
+        use std::{
+            os::unix::process::ExitStatusExt as _,
+            process::Command,
+            time::{Duration, Instant},
+        };
+        let mut child = Command::new("sleep").arg("60").spawn().unwrap();
+        let _guard = register_child(child.id());
+        // Children that other tests are running at the same time are set
+        // aside, so that they don't get terminated along with ours.
+        let others = {
+            let mut children = CHILDREN.lock().unwrap();
+            let (ours, others): (Vec<_>, Vec<_>) =
+                children.drain(..).partition(|pid| *pid == child.id());
+            *children = ours;
+            others
+        };
+        let start = Instant::now();
+        kill_children();
+        CHILDREN.lock().unwrap().extend(others);
+        let status = child.wait().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+    }
+}
@@ -0,0 +1,64 @@
+use std::{
+    fs::File,
+    io::{self, Write as _},
+};
+
+/// Points stdout at stderr, returning a handle to what stdout used to be.
+/// Anything printed to stdout afterwards, including by children that inherit
+/// it, ends up on stderr.
+///
+/// The returned handle is close-on-exec, so children can't write to the
+/// original stdout.
+#[cfg(unix)]
+pub fn divert_stdout() -> io::Result<File> {
+    use std::os::unix::io::FromRawFd as _;
+    let fd = unsafe { libc::fcntl(libc::STDOUT_FILENO, libc::F_DUPFD_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // We now own this, and nothing else will close it
+    let original = unsafe { File::from_raw_fd(fd) };
+    // Otherwise, anything that's still buffered would go to the wrong place.
+    io::stdout().flush()?;
+    if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(original)
+}
+
+/// Points stdout at stderr, returning a handle to what stdout used to be.
+/// Anything printed to stdout afterwards, including by children that inherit
+/// it, ends up on stderr.
+///
+/// The returned handle isn't inheritable, so children can't write to the
+/// original stdout.
+#[cfg(windows)]
+pub fn divert_stdout() -> io::Result<File> {
+    use std::{os::windows::io::FromRawHandle as _, ptr};
+    use winapi::um::{handleapi, processenv, processthreadsapi, winbase, winnt};
+    let mut original = ptr::null_mut();
+    let duplicated = unsafe {
+        let process = processthreadsapi::GetCurrentProcess();
+        handleapi::DuplicateHandle(
+            process,
+            processenv::GetStdHandle(winbase::STD_OUTPUT_HANDLE),
+            process,
+            &mut original,
+            0,
+            0,
+            winnt::DUPLICATE_SAME_ACCESS,
+        )
+    };
+    if duplicated == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // We now own this, and nothing else will close it
+    let original = unsafe { File::from_raw_handle(original) };
+    // Otherwise, anything that's still buffered would go to the wrong place.
+    io::stdout().flush()?;
+    let stderr = unsafe { processenv::GetStdHandle(winbase::STD_ERROR_HANDLE) };
+    if unsafe { processenv::SetStdHandle(winbase::STD_OUTPUT_HANDLE, stderr) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(original)
+}
@@ -0,0 +1,70 @@
+use crate::{
+    android,
+    env::{self, ExplicitEnv as _},
+    util::{
+        cli::{Report, Reportable},
+        Invocation,
+    },
+};
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ToolKind {
+    Cargo,
+    Gradle,
+    #[cfg(target_os = "macos")]
+    Xcodebuild,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    EnvFailed(env::Error),
+    AndroidEnvFailed(android::env::Error),
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::EnvFailed(err) => err.report(),
+            Self::AndroidEnvFailed(err) => err.report(),
+        }
+    }
+}
+
+/// Gets a command for `kind` that runs in `project_dir` with the same
+/// environment we'd use ourselves, so that you can run whatever you want
+/// without having to replicate our setup. For `Gradle`, `project_dir` is the
+/// Android Studio project containing `gradlew`.
+pub fn prepared_command(kind: ToolKind, project_dir: &Path) -> Result<Invocation, Error> {
+    let command = match kind {
+        ToolKind::Cargo => Invocation::pure("cargo")
+            .with_env_vars(env::Env::new().map_err(Error::EnvFailed)?.explicit_env()),
+        ToolKind::Gradle => Invocation::pure(project_dir.join(android::gradlew_file_name()))
+            .with_env_vars(
+                android::env::Env::new()
+                    .map_err(Error::AndroidEnvFailed)?
+                    .explicit_env(),
+            )
+            .with_arg("--project-dir")
+            .with_arg(project_dir),
+        #[cfg(target_os = "macos")]
+        ToolKind::Xcodebuild => Invocation::pure("xcodebuild")
+            .with_env_vars(env::Env::new().map_err(Error::EnvFailed)?.explicit_env()),
+    };
+    Ok(command.with_current_dir(project_dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prepared_cargo_runs_with_our_env_in_the_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let command = prepared_command(ToolKind::Cargo, dir.path()).unwrap();
+        assert_eq!(command.program(), "cargo");
+        assert!(command.is_pure());
+        assert_eq!(command.current_dir(), Some(dir.path()));
+        assert!(command.env_vars().iter().any(|(key, _)| key == "PATH"));
+    }
+}
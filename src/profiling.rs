@@ -0,0 +1,68 @@
+//! What `cargo android profile` and `cargo apple profile` have in common.
+
+use crate::{
+    config::app::App,
+    util::output::{self, Event},
+};
+use std::{
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Cargo's own override for giving release builds debug info, without which
+/// profilers can't tell one Rust function from another. This goes on the env
+/// of the whole build, since it's Gradle or Xcode that actually runs cargo.
+pub static DEBUG_INFO_VAR: (&str, &str) = ("CARGO_PROFILE_RELEASE_DEBUG", "true");
+
+#[derive(Debug)]
+pub struct TraceDirFailed {
+    dir: PathBuf,
+    cause: io::Error,
+}
+
+impl Display for TraceDirFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Failed to create profile dir {:?}: {}",
+            self.dir, self.cause
+        )
+    }
+}
+
+impl std::error::Error for TraceDirFailed {}
+
+pub fn profiles_dir(app: &App) -> PathBuf {
+    app.prefix_path("target/mobile-profiles")
+}
+
+/// Gets a path in [`profiles_dir`] to save a new trace to. Traces are named
+/// after when they were taken, so that they don't clobber each other.
+pub fn trace_path(app: &App, platform: &str, extension: &str) -> Result<PathBuf, TraceDirFailed> {
+    let dir = profiles_dir(app);
+    fs::create_dir_all(&dir).map_err(|cause| TraceDirFailed {
+        dir: dir.clone(),
+        cause,
+    })?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or_default();
+    Ok(dir.join(format!(
+        "{}-{}-{}.{}",
+        app.name(),
+        platform,
+        timestamp,
+        extension
+    )))
+}
+
+pub fn report_trace(path: &Path) {
+    println!("Saved profile to {:?}", path);
+    output::emit(&Event::Artifact {
+        kind: "profile",
+        path,
+    });
+}
@@ -3,14 +3,14 @@ use crate::{
     templating::{self, FancyPackResolveError},
     util::{
         cli::{Report, Reportable},
-        Git,
+        CommandError, Git,
     },
 };
 use std::path::PathBuf;
 
 #[derive(Debug)]
 pub enum Error {
-    GitInitFailed(bossy::Error),
+    GitInitFailed(CommandError),
     TemplatePackResolveFailed(FancyPackResolveError),
     ProcessingFailed {
         src: PathBuf,
@@ -22,7 +22,7 @@ pub enum Error {
 impl Reportable for Error {
     fn report(&self) -> Report {
         match self {
-            Self::GitInitFailed(err) => Report::error("Failed to initialize git", err),
+            Self::GitInitFailed(err) => err.report("Failed to initialize git"),
             Self::TemplatePackResolveFailed(err) => {
                 Report::error("Failed to resolve template pack", err)
             }
@@ -55,12 +55,13 @@ pub fn gen(
     log::info!("template pack chain: {:#?}", pack_chain);
     for pack in pack_chain {
         log::info!("traversing template pack {:#?}", pack);
-        bike.filter_and_process(&pack, &root, |_| (), filter.fun())
-            .map_err(|cause| Error::ProcessingFailed {
+        templating::filter_and_process(bike, &pack, &root, |_| (), filter.fun()).map_err(
+            |cause| Error::ProcessingFailed {
                 src: pack.to_owned(),
                 dest: root.to_owned(),
                 cause,
-            })?;
+            },
+        )?;
     }
     Ok(())
 }
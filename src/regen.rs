@@ -0,0 +1,388 @@
+use crate::android;
+#[cfg(target_os = "macos")]
+use crate::apple;
+use crate::{
+    config::{
+        self,
+        metadata::{self, Metadata},
+        Config,
+    },
+    generated::{self, GenerationManifest},
+    opts,
+    util::{
+        self,
+        cli::{Report, Reportable, TextWrapper},
+    },
+};
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub enum Error {
+    ConfigLoadFailed(config::LoadOrGenError),
+    ConfigMissing,
+    MetadataFailed(metadata::Error),
+    ManifestLoadFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
+    StagingFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
+    #[cfg(target_os = "macos")]
+    AppleRenderFailed(apple::project::Error),
+    AndroidRenderFailed(android::project::Error),
+    ReadFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
+    WriteFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
+    #[cfg(target_os = "macos")]
+    ProjectGenFailed(apple::project::Error),
+    ManifestWriteFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::ConfigLoadFailed(err) => err.report(),
+            Self::ConfigMissing => Report::action_request(
+                format!("No {} was found", config::file_name()),
+                "There's nothing to regenerate yet; run `cargo mobile init` to generate a project.",
+            ),
+            Self::MetadataFailed(err) => err.report(),
+            Self::ManifestLoadFailed { path, cause } => Report::error(
+                format!("Failed to load generation manifest {:?}", path),
+                cause,
+            ),
+            Self::StagingFailed { path, cause } => {
+                Report::error(format!("Failed to prepare staging dir {:?}", path), cause)
+            }
+            #[cfg(target_os = "macos")]
+            Self::AppleRenderFailed(err) => err.report(),
+            Self::AndroidRenderFailed(err) => err.report(),
+            Self::ReadFailed { path, cause } => {
+                Report::error(format!("Failed to read {:?}", path), cause)
+            }
+            Self::WriteFailed { path, cause } => {
+                Report::error(format!("Failed to write {:?}", path), cause)
+            }
+            #[cfg(target_os = "macos")]
+            Self::ProjectGenFailed(err) => err.report(),
+            Self::ManifestWriteFailed { path, cause } => Report::error(
+                format!("Failed to write generation manifest {:?}", path),
+                cause,
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Outcome {
+    UpToDate,
+    Created,
+    Updated,
+    // The user changed the file since we last wrote it, so the new version
+    // goes next to it instead.
+    Conflicted,
+    // The user deleted the file since we last wrote it, which we respect.
+    Deleted,
+}
+
+impl Outcome {
+    fn describe(self) -> Option<&'static str> {
+        match self {
+            Self::UpToDate => None,
+            Self::Created => Some("created"),
+            Self::Updated => Some("updated"),
+            Self::Conflicted => Some("modified locally"),
+            Self::Deleted => Some("deleted locally"),
+        }
+    }
+}
+
+struct Staged {
+    name: &'static str,
+    project_dir: PathBuf,
+    staged_dir: PathBuf,
+}
+
+fn staging_dir() -> PathBuf {
+    util::temp_dir().join(format!("regen-{}", std::process::id()))
+}
+
+// Renders the templates for each platform that has a project into `staging`,
+// without touching the real projects at all.
+fn stage(
+    config: &Config,
+    metadata: &Metadata,
+    bike: &bicycle::Bicycle,
+    staging: &Path,
+) -> Result<Vec<Staged>, Error> {
+    let mut staged = Vec::new();
+    #[cfg(target_os = "macos")]
+    if metadata.apple().supported() && config.apple().project_dir_exists() {
+        let staged_dir = staging.join(apple::NAME);
+        apple::project::render(
+            config.apple(),
+            metadata.apple(),
+            config.app().template_pack().submodule_path(),
+            bike,
+            &staged_dir,
+            |_| true,
+        )
+        .map_err(Error::AppleRenderFailed)?;
+        staged.push(Staged {
+            name: apple::NAME,
+            project_dir: config.apple().project_dir(),
+            staged_dir,
+        });
+    }
+    if metadata.android().supported() && config.android().project_dir_exists() {
+        let staged_dir = staging.join(android::NAME);
+        android::project::render(config.android(), bike, &staged_dir, |_| true)
+            .map_err(Error::AndroidRenderFailed)?;
+        staged.push(Staged {
+            name: android::NAME,
+            project_dir: config.android().project_dir(),
+            staged_dir,
+        });
+    }
+    Ok(staged)
+}
+
+fn staged_files(dir: &Path, rel: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in util::sorted_dir_entries(dir)? {
+        let rel = rel.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            staged_files(&entry.path(), &rel, files)?;
+        } else {
+            files.push(rel);
+        }
+    }
+    Ok(())
+}
+
+fn with_staged<T>(
+    config: &Config,
+    metadata: &Metadata,
+    f: impl FnOnce(&[Staged]) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let staging = staging_dir();
+    if staging.exists() {
+        fs::remove_dir_all(&staging).map_err(|cause| Error::StagingFailed {
+            path: staging.clone(),
+            cause,
+        })?;
+    }
+    let result =
+        stage(config, metadata, &config.build_a_bike(), &staging).and_then(|staged| f(&staged));
+    if let Err(err) = fs::remove_dir_all(&staging) {
+        log::warn!("failed to remove staging dir {:?}: {}", staging, err);
+    }
+    result
+}
+
+fn read_if_exists(path: &Path) -> Result<Option<Vec<u8>>, Error> {
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(cause) => Err(Error::ReadFailed {
+            path: path.to_owned(),
+            cause,
+        }),
+    }
+}
+
+fn read_staged(staged: &Staged) -> Result<Vec<(PathBuf, Vec<u8>)>, Error> {
+    let mut files = Vec::new();
+    staged_files(&staged.staged_dir, Path::new(""), &mut files).map_err(|cause| {
+        Error::ReadFailed {
+            path: staged.staged_dir.clone(),
+            cause,
+        }
+    })?;
+    files
+        .into_iter()
+        .map(|rel| {
+            let path = staged.staged_dir.join(&rel);
+            fs::read(&path)
+                .map(|contents| (rel, contents))
+                .map_err(|cause| Error::ReadFailed { path, cause })
+        })
+        .collect()
+}
+
+/// Records the generated files that currently match what the templates render,
+/// along with their hashes, so that a later `regen` can tell if they were
+/// modified. This is meant to be called right after generating.
+pub fn record(
+    config: &Config,
+    metadata: &Metadata,
+    manifest: &mut GenerationManifest,
+) -> Result<(), Error> {
+    let root_dir = config.app().root_dir();
+    with_staged(config, metadata, |staged| {
+        for platform in staged {
+            for (rel, new) in read_staged(platform)? {
+                let dest = platform.project_dir.join(&rel);
+                if read_if_exists(&dest)?.as_ref() == Some(&new) {
+                    let key = dest.strip_prefix(root_dir).unwrap_or(&dest);
+                    manifest.add(root_dir, key);
+                    manifest.set_file_hash(key, generated::hash_contents(&new));
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+fn new_path(dest: &Path) -> PathBuf {
+    let mut path = OsString::from(dest);
+    path.push(".new");
+    path.into()
+}
+
+fn copy(src: &Path, dest: &Path) -> Result<(), Error> {
+    let write_failed = |cause| Error::WriteFailed {
+        path: dest.to_owned(),
+        cause,
+    };
+    if let Some(parent) = dest.parent() {
+        util::create_dir_all(parent).map_err(write_failed)?;
+    }
+    // `fs::copy` brings the permissions along, which matters for `gradlew`.
+    fs::copy(src, dest).map(|_| ()).map_err(write_failed)
+}
+
+/// Re-renders the platform project templates and applies the changes to the
+/// existing projects. Files the user modified since they were generated are
+/// left alone, with the new versions written next to them as `.new` files.
+pub fn exec(
+    wrapper: &TextWrapper,
+    dry_run: opts::DryRun,
+    cwd: impl AsRef<Path>,
+) -> Result<(), Error> {
+    let config = Config::load(cwd)
+        .map_err(Error::ConfigLoadFailed)?
+        .ok_or(Error::ConfigMissing)?;
+    util::set_default_dir_mode(config.app().dir_mode());
+    let root_dir = config.app().root_dir();
+    let metadata = Metadata::load(root_dir).map_err(Error::MetadataFailed)?;
+    let mut manifest = match GenerationManifest::load(root_dir) {
+        Ok(manifest) => manifest,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            log::info!("no generation manifest found; assuming nothing is known to be unmodified");
+            Default::default()
+        }
+        Err(cause) => {
+            return Err(Error::ManifestLoadFailed {
+                path: GenerationManifest::path(root_dir),
+                cause,
+            })
+        }
+    };
+    let (changed, conflicted) = with_staged(&config, &metadata, |staged| {
+        let (mut changed, mut conflicted) = (0, 0);
+        for platform in staged {
+            let mut platform_changed = false;
+            for (rel, new) in read_staged(platform)? {
+                let src = platform.staged_dir.join(&rel);
+                let dest = platform.project_dir.join(&rel);
+                let key = dest.strip_prefix(root_dir).unwrap_or(&dest).to_owned();
+                let base = manifest.file_hash(&key);
+                let outcome = match read_if_exists(&dest)? {
+                    None if base.is_some() => Outcome::Deleted,
+                    None => Outcome::Created,
+                    Some(current) if current == new => Outcome::UpToDate,
+                    Some(current) if base == Some(generated::hash_contents(&current).as_str()) => {
+                        Outcome::Updated
+                    }
+                    Some(_) => Outcome::Conflicted,
+                };
+                if let Some(description) = outcome.describe() {
+                    println!("  {} ({})", key.display(), description);
+                }
+                if dry_run.no() {
+                    match outcome {
+                        Outcome::Created | Outcome::Updated => {
+                            copy(&src, &dest)?;
+                            manifest.add(root_dir, &key);
+                        }
+                        Outcome::Conflicted => copy(&src, &new_path(&dest))?,
+                        Outcome::UpToDate | Outcome::Deleted => (),
+                    }
+                    if outcome != Outcome::Deleted {
+                        manifest.set_file_hash(key, generated::hash_contents(&new));
+                    }
+                }
+                match outcome {
+                    Outcome::Created | Outcome::Updated => {
+                        changed += 1;
+                        platform_changed = true;
+                    }
+                    Outcome::Conflicted => conflicted += 1,
+                    Outcome::UpToDate | Outcome::Deleted => (),
+                }
+            }
+            log::info!("{} project changed: {}", platform.name, platform_changed);
+            #[cfg(target_os = "macos")]
+            if platform.name == apple::NAME && platform_changed && dry_run.no() {
+                apple::project::generate(
+                    config.apple(),
+                    metadata.apple(),
+                    config.app().template_pack().submodule_path(),
+                    &platform.project_dir,
+                )
+                .map_err(Error::ProjectGenFailed)?;
+            }
+        }
+        Ok((changed, conflicted))
+    })?;
+    if dry_run.no() {
+        match generated::current_template_ref() {
+            Ok(template_ref) => manifest.set_template_ref(template_ref),
+            Err(err) => log::warn!("failed to get current template version: {}", err),
+        }
+        manifest
+            .write(root_dir)
+            .map_err(|cause| Error::ManifestWriteFailed {
+                path: GenerationManifest::path(root_dir),
+                cause,
+            })?;
+    }
+    let verb = if dry_run.yes() { "would be" } else { "were" };
+    if conflicted > 0 {
+        Report::action_request(
+            format!(
+                "{} generated files {} changed, but {} have local modifications",
+                changed, verb, conflicted
+            ),
+            if dry_run.yes() {
+                "Without `--dry-run`, the regenerated versions of the modified files will be written next to them with a `.new` extension."
+            } else {
+                "The regenerated versions of the modified files are next to them with a `.new` extension; merge in whatever you need, and then delete the `.new` files."
+            },
+        )
+        .print(wrapper);
+    } else if changed > 0 {
+        Report::victory(
+            format!("{} generated files {} updated", changed, verb),
+            "Your platform projects are in sync with your config.",
+        )
+        .print(wrapper);
+    } else {
+        println!("Platform projects are already up-to-date!");
+    }
+    Ok(())
+}
@@ -3,6 +3,8 @@ use once_cell_regex::exports::once_cell::sync::OnceCell;
 use std::{
     collections::BTreeMap,
     fmt::{self, Debug, Display},
+    path::Path,
+    process::ExitStatus,
 };
 
 pub trait TargetTrait<'a>: Debug + Sized {
@@ -36,18 +38,25 @@ pub trait TargetTrait<'a>: Debug + Sized {
 
     fn arch(&'a self) -> &'a str;
 
-    fn install(&'a self) -> bossy::Result<bossy::ExitStatus> {
+    fn install(&'a self) -> Result<ExitStatus, util::CommandError> {
         util::rustup_add(self.triple())
     }
 
-    fn install_all() -> bossy::Result<()>
+    /// Adds all targets, unless the project at `root_dir` has a fingerprint
+    /// showing that's already been done for the current toolchain.
+    fn install_all(root_dir: &Path) -> Result<(), util::CommandError>
     where
         Self: 'a,
     {
-        for target in Self::all().values() {
-            target.install()?;
-        }
-        Ok(())
+        let triples = Self::all()
+            .values()
+            .map(|target| target.triple())
+            .collect::<Vec<_>>();
+        util::rustup_add_all_fingerprinted(
+            util::Runner::global(),
+            &triples,
+            &util::rustup_fingerprint_path(root_dir),
+        )
     }
 }
 
@@ -67,6 +76,13 @@ impl Display for TargetInvalid {
     }
 }
 
+fn find_target<'a, T>(all: &'a BTreeMap<&'a str, T>, name: &str) -> Result<&'a T, TargetInvalid> {
+    all.get(name).ok_or_else(|| TargetInvalid {
+        name: name.to_owned(),
+        possible: all.keys().map(|key| key.to_string()).collect(),
+    })
+}
+
 pub fn get_targets<'a, Iter, I, T, U>(
     targets: Iter,
     // we use `dyn` so the type doesn't need to be known when this is `None`
@@ -80,12 +96,7 @@ where
     let targets_empty = targets.len() == 0;
     Ok(if !targets_empty {
         targets
-            .map(|name| {
-                T::for_name(name.as_ref()).ok_or_else(|| TargetInvalid {
-                    name: name.as_ref().to_owned(),
-                    possible: T::all().keys().map(|key| key.to_string()).collect(),
-                })
-            })
+            .map(|name| find_target(T::all(), name.as_ref()))
             .collect::<Result<_, _>>()?
     } else {
         let target = fallback
@@ -98,6 +109,28 @@ where
     })
 }
 
+/// Like [`get_targets`], but picks from `all` instead of [`TargetTrait::all`],
+/// which is for when each platform has its own set of targets. `default_key`
+/// is used if no targets are given.
+pub fn get_targets_in<'a, Iter, I, T>(
+    all: &'a BTreeMap<&'a str, T>,
+    default_key: &str,
+    targets: Iter,
+) -> Result<Vec<&'a T>, TargetInvalid>
+where
+    Iter: ExactSizeIterator<Item = &'a I>,
+    I: AsRef<str> + 'a,
+{
+    if targets.len() == 0 {
+        log::info!("falling back on default target ({})", default_key);
+        find_target(all, default_key).map(|target| vec![target])
+    } else {
+        targets
+            .map(|name| find_target(all, name.as_ref()))
+            .collect()
+    }
+}
+
 pub fn call_for_targets_with_fallback<'a, Iter, I, T, U, E, F>(
     targets: Iter,
     fallback: &'a dyn Fn(U) -> Option<&'a T>,
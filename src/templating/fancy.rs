@@ -2,10 +2,12 @@ use super::{LookupError, Pack};
 use crate::util::{
     self,
     submodule::{self, Submodule},
+    template_cache::{self, CacheMode},
     Git,
 };
 use serde::Deserialize;
 use std::{
+    collections::BTreeMap,
     fmt::{self, Display},
     fs, io,
     path::{Path, PathBuf},
@@ -59,20 +61,45 @@ impl Display for FancyPackResolveError {
     }
 }
 
+/// A variable a template pack expects to be set in `template.vars`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PackVar {
+    pub description: Option<String>,
+    /// Variables without a default are required.
+    pub default: Option<String>,
+}
+
+impl PackVar {
+    pub fn required(&self) -> bool {
+        self.default.is_none()
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct FancyPack {
     path: PathBuf,
     base: Option<Box<Pack>>,
     submodule: Option<Submodule>,
+    vars: BTreeMap<String, PackVar>,
+    // The git URL and reference this pack was checked out from, if any.
+    remote: Option<(String, Option<String>)>,
 }
 
 impl FancyPack {
-    pub fn parse(path: impl AsRef<Path>) -> Result<Self, FancyPackParseError> {
+    /// Parses the pack spec at `path`. A relative `path` in the spec is
+    /// resolved against `relative_to` if it's given, which is how packs from
+    /// git repos point into their own checkout.
+    pub fn parse(
+        path: impl AsRef<Path>,
+        relative_to: Option<&Path>,
+    ) -> Result<Self, FancyPackParseError> {
         #[derive(Deserialize)]
         struct Raw {
             path: PathBuf,
             base: Option<String>,
             submodule: Option<Submodule>,
+            #[serde(default)]
+            vars: BTreeMap<String, PackVar>,
         }
 
         let path = path.as_ref();
@@ -87,6 +114,10 @@ impl FancyPack {
             })?
         };
         let real_path = util::expand_home(&raw.path).map_err(FancyPackParseError::NoHomeDir)?;
+        let real_path = match relative_to {
+            Some(root) if real_path.is_relative() => root.join(real_path),
+            _ => real_path,
+        };
         let this = Self {
             path: real_path,
             base: raw
@@ -103,15 +134,62 @@ impl FancyPack {
                 .map_err(FancyPackParseError::BaseFailed)?
                 .map(Box::new),
             submodule: raw.submodule,
+            vars: raw.vars,
+            remote: None,
         };
         log::info!("template pack {:#?}", this);
         Ok(this)
     }
 
+    pub(super) fn with_remote(mut self, url: &str, reference: Option<&str>) -> Self {
+        self.remote = Some((url.to_owned(), reference.map(ToOwned::to_owned)));
+        self
+    }
+
     pub fn submodule_path(&self) -> Option<&Path> {
         self.submodule.as_ref().map(|submodule| submodule.path())
     }
 
+    /// Makes sure everything this pack and its bases get from git is in the
+    /// template cache, returning the cached checkouts.
+    pub fn fetch(&self, mode: CacheMode) -> Result<Vec<PathBuf>, template_cache::Error> {
+        let mut checkouts = self
+            .base
+            .as_ref()
+            .map(|base| base.fetch(mode))
+            .transpose()?
+            .unwrap_or_default();
+        if let Some((url, reference)) = &self.remote {
+            checkouts.push(template_cache::cached_template_checkout(
+                util::Runner::global(),
+                url,
+                reference.as_deref(),
+                mode,
+            )?);
+        }
+        if let Some(submodule) = &self.submodule {
+            checkouts.push(template_cache::cached_template_checkout(
+                util::Runner::global(),
+                submodule.remote(),
+                None,
+                mode,
+            )?);
+        }
+        Ok(checkouts)
+    }
+
+    /// The variables declared by this pack and its bases, with this pack's
+    /// declarations taking precedence.
+    pub fn vars(&self) -> BTreeMap<&str, &PackVar> {
+        let mut vars = self
+            .base
+            .as_ref()
+            .map(|base| base.vars())
+            .unwrap_or_default();
+        vars.extend(self.vars.iter().map(|(name, var)| (name.as_str(), var)));
+        vars
+    }
+
     pub fn resolve(
         &self,
         git: Git<'_>,
@@ -1,5 +1,5 @@
 use crate::{
-    config::{app, Config},
+    config::{app, template, Config},
     util::{self, Git},
 };
 use bicycle::{
@@ -94,6 +94,17 @@ fn reverse_domain(
         .map_err(Into::into)
 }
 
+fn pbxproj_escape(
+    helper: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    out.write(&util::pbxproj_escape(get_str(helper)))
+        .map_err(Into::into)
+}
+
 fn app_root<'a>(ctx: &'a Context) -> Result<&'a str, RenderError> {
     let app_root = ctx
         .data()
@@ -172,6 +183,7 @@ pub fn init(config: Option<&Config>) -> Bicycle {
             helpers.insert("quote-and-join", Box::new(quote_and_join));
             helpers.insert("snake-case", Box::new(snake_case));
             helpers.insert("reverse-domain", Box::new(reverse_domain));
+            helpers.insert("pbxproj-escape", Box::new(pbxproj_escape));
             if config.is_some() {
                 // don't mix these up or very bad things will happen to all of us
                 helpers.insert("prefix-path", Box::new(prefix_path));
@@ -183,6 +195,7 @@ pub fn init(config: Option<&Config>) -> Bicycle {
             let mut map = JsonMap::default();
             if let Some(config) = config {
                 map.insert(app::KEY, config.app());
+                map.insert(template::KEY, config.template());
                 map.insert("author", detect_author());
                 #[cfg(target_os = "macos")]
                 map.insert(crate::apple::NAME, config.apple());
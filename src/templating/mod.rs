@@ -1,11 +1,18 @@
 mod fancy;
 mod filter;
 mod init;
+mod process;
 
-pub use self::{fancy::*, filter::*, init::*};
+pub use self::{fancy::*, filter::*, init::*, process::*};
 
-use crate::util::{self, Git};
+use crate::util::{
+    self,
+    repo::{self, RepoSource},
+    template_cache::{self, CacheMode},
+    Git,
+};
 use std::{
+    collections::BTreeMap,
     fmt::{self, Display},
     fs, io,
     path::{Path, PathBuf},
@@ -15,7 +22,11 @@ use std::{
 // always be at the top of the list.
 static BRAINIUM: &[&str] = &["brainstorm", "brainstorm-demo"];
 
-fn platform_pack_dir() -> Result<PathBuf, util::NoHomeDir> {
+/// The spec a template pack repo needs at its root, which is parsed just like
+/// the `.toml` packs in a registry. Its `path` is relative to the repo.
+pub static REMOTE_PACK_SPEC_FILE_NAME: &str = "pack.toml";
+
+pub(crate) fn platform_pack_dir() -> Result<PathBuf, util::NoHomeDir> {
     util::install_dir().map(|dir| dir.join("templates/platforms"))
 }
 
@@ -31,6 +42,12 @@ pub enum LookupError {
         tried_toml: PathBuf,
         tried: PathBuf,
     },
+    PathNotFound(PathBuf),
+    CheckoutFailed(template_cache::Error),
+    RemoteSpecMissing {
+        url: String,
+        tried: PathBuf,
+    },
     FancyPackParseFailed(FancyPackParseError),
 }
 
@@ -47,11 +64,38 @@ impl Display for LookupError {
                 "Didn't find {:?} template pack at {:?} or {:?}",
                 name, tried_toml, tried
             ),
+            Self::PathNotFound(path) => write!(f, "No template pack exists at {:?}", path),
+            Self::CheckoutFailed(err) => write!(f, "{}", err),
+            Self::RemoteSpecMissing { url, tried } => write!(
+                f,
+                "Template pack repo {:?} doesn't have a `{}` at its root (tried {:?})",
+                url, REMOTE_PACK_SPEC_FILE_NAME, tried
+            ),
             Self::FancyPackParseFailed(err) => write!(f, "{}", err),
         }
     }
 }
 
+// Packs can be named by git URL, optionally followed by `#<branch-or-tag>`.
+fn remote_source(name: &str) -> Option<(&str, Option<&str>)> {
+    let mut parts = name.splitn(2, '#');
+    let url = parts.next().unwrap_or(name);
+    let reference = parts.next().filter(|reference| !reference.is_empty());
+    match repo::parse_repo_source(url) {
+        RepoSource::Http(_) | RepoSource::Git(_) | RepoSource::Ssh(_) => Some((url, reference)),
+        RepoSource::Local(_) => None,
+    }
+}
+
+// Anything with a path separator in it is a path rather than a pack name.
+fn local_path(name: &str) -> Option<PathBuf> {
+    if name.contains('/') || name.contains(std::path::MAIN_SEPARATOR) {
+        Some(util::expand_home(name).unwrap_or_else(|_| name.into()))
+    } else {
+        None
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Pack {
     Simple(PathBuf),
@@ -86,21 +130,69 @@ impl Pack {
                     tried: path,
                 })
         }?;
+        Self::from_path(path)
+    }
+
+    fn from_path(path: PathBuf) -> Result<Self, LookupError> {
         if path.extension() == Some("toml".as_ref()) {
-            let pack = FancyPack::parse(path).map_err(LookupError::FancyPackParseFailed)?;
+            let pack = FancyPack::parse(path, None).map_err(LookupError::FancyPackParseFailed)?;
             Ok(Pack::Fancy(pack))
         } else {
             Ok(Pack::Simple(path))
         }
     }
 
+    fn lookup_remote(url: &str, reference: Option<&str>) -> Result<Self, LookupError> {
+        let checkout = template_cache::cached_template_checkout(
+            util::Runner::global(),
+            url,
+            reference,
+            CacheMode::current(),
+        )
+        .map_err(LookupError::CheckoutFailed)?;
+        let spec = checkout.join(REMOTE_PACK_SPEC_FILE_NAME);
+        if !spec.is_file() {
+            return Err(LookupError::RemoteSpecMissing {
+                url: url.to_owned(),
+                tried: spec,
+            });
+        }
+        log::info!("found template pack spec for {:?} at {:?}", url, spec);
+        let pack =
+            FancyPack::parse(&spec, Some(&checkout)).map_err(LookupError::FancyPackParseFailed)?;
+        Ok(Pack::Fancy(pack.with_remote(url, reference)))
+    }
+
     pub fn lookup_platform(name: &str) -> Result<Self, LookupError> {
         platform_pack_dir()
             .map_err(LookupError::NoHomeDir)
             .and_then(|dir| Self::lookup(dir, name))
     }
 
-    pub fn lookup_app(name: &str) -> Result<Self, LookupError> {
+    /// Looks up an app template pack, which can be a git URL, a path to a
+    /// pack, or the name of a pack in `registry` or the built-in packs (in
+    /// that order).
+    pub fn lookup_app(name: &str, registry: Option<&Path>) -> Result<Self, LookupError> {
+        if let Some((url, reference)) = remote_source(name) {
+            return Self::lookup_remote(url, reference);
+        }
+        if let Some(path) = local_path(name) {
+            return if path.exists() {
+                Self::from_path(path)
+            } else {
+                Err(LookupError::PathNotFound(path))
+            };
+        }
+        if let Some(registry) = registry {
+            match Self::lookup(registry, name) {
+                Err(LookupError::MissingPack { .. }) => log::info!(
+                    "template pack \"{}\" isn't in registry {:?}; checking built-in packs",
+                    name,
+                    registry
+                ),
+                result => return result,
+            }
+        }
         app_pack_dir()
             .map_err(LookupError::NoHomeDir)
             .and_then(|dir| Self::lookup(dir, name))
@@ -122,6 +214,25 @@ impl Pack {
         }
     }
 
+    /// Caches whatever this pack needs from git; simple packs are always
+    /// local, so there's nothing to fetch for them.
+    pub fn fetch(&self, mode: CacheMode) -> Result<Vec<PathBuf>, template_cache::Error> {
+        if let Self::Fancy(pack) = self {
+            pack.fetch(mode)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// The variables this pack declares; simple packs can't declare any.
+    pub fn vars(&self) -> BTreeMap<&str, &PackVar> {
+        if let Self::Fancy(pack) = self {
+            pack.vars()
+        } else {
+            Default::default()
+        }
+    }
+
     pub fn resolve(
         &self,
         git: Git<'_>,
@@ -162,15 +273,13 @@ impl Display for ListError {
     }
 }
 
-pub fn list_app_packs() -> Result<Vec<String>, ListError> {
-    let dir = app_pack_dir().map_err(ListError::NoHomeDir)?;
-    let mut packs = Vec::new();
-    for entry in fs::read_dir(&dir).map_err(|cause| ListError::DirReadFailed {
-        dir: dir.clone(),
+fn list_packs_in(dir: &Path, packs: &mut Vec<String>) -> Result<(), ListError> {
+    for entry in fs::read_dir(dir).map_err(|cause| ListError::DirReadFailed {
+        dir: dir.to_owned(),
         cause,
     })? {
         let entry = entry.map_err(|cause| ListError::DirEntryReadFailed {
-            dir: dir.clone(),
+            dir: dir.to_owned(),
             cause,
         })?;
         if let Some(name) = entry.path().file_stem() {
@@ -180,6 +289,17 @@ pub fn list_app_packs() -> Result<Vec<String>, ListError> {
             }
         }
     }
+    Ok(())
+}
+
+/// Lists the names of the built-in app template packs, along with any in
+/// `registry`.
+pub fn list_app_packs(registry: Option<&Path>) -> Result<Vec<String>, ListError> {
+    let mut packs = Vec::new();
+    if let Some(registry) = registry {
+        list_packs_in(registry, &mut packs)?;
+    }
+    list_packs_in(&app_pack_dir().map_err(ListError::NoHomeDir)?, &mut packs)?;
     packs.sort_unstable();
     packs.dedup();
     Ok(if cfg!(feature = "brainium") {
@@ -0,0 +1,116 @@
+use crate::util;
+use bicycle::{Action, Bicycle, JsonMap, ProcessingError};
+use std::{fs, io, path::Path};
+
+// Copies `src` to `dest` unless they're already the same. `fs::copy` brings
+// the permissions along, which matters for `gradlew`.
+fn copy_if_changed(src: &Path, dest: &Path) -> io::Result<()> {
+    let contents = fs::read(src)?;
+    match fs::read(dest) {
+        Ok(existing) if existing == contents => {
+            log::info!("contents of {:?} are unchanged; skipping copy", dest);
+            Ok(())
+        }
+        _ => fs::copy(src, dest).map(|_| ()),
+    }
+}
+
+fn process_action(
+    bike: &Bicycle,
+    action: &Action,
+    insert_data: impl Fn(&mut JsonMap),
+) -> Result<(), ProcessingError> {
+    log::info!("{:#?}", action);
+    match action {
+        Action::CreateDirectory { dest } => {
+            util::create_dir_all(dest).map_err(|cause| ProcessingError::DirectoryCreationFailed {
+                dest: dest.clone(),
+                cause,
+            })
+        }
+        Action::CopyFile { src, dest } => {
+            copy_if_changed(src, dest).map_err(|cause| ProcessingError::FileCopyFailed {
+                src: src.clone(),
+                dest: dest.clone(),
+                cause,
+            })
+        }
+        Action::WriteTemplate { src, dest } => {
+            let template =
+                fs::read_to_string(src).map_err(|cause| ProcessingError::TemplateReadFailed {
+                    src: src.clone(),
+                    cause,
+                })?;
+            let rendered = bike.render(&template, insert_data).map_err(|cause| {
+                ProcessingError::TemplateRenderFailed {
+                    src: src.clone(),
+                    cause,
+                }
+            })?;
+            util::write_str_if_changed(dest, rendered)
+                .map(|_| ())
+                .map_err(|cause| ProcessingError::TemplateWriteFailed {
+                    src: src.clone(),
+                    dest: dest.clone(),
+                    cause,
+                })
+        }
+    }
+}
+
+/// Like [`Bicycle::filter_and_process`], but files that would be written with
+/// exactly what they already contain are left alone, so regenerating a project
+/// doesn't touch the mtime of everything in it.
+pub fn filter_and_process(
+    bike: &Bicycle,
+    src: impl AsRef<Path>,
+    dest: impl AsRef<Path>,
+    insert_data: impl Fn(&mut JsonMap),
+    mut filter: impl FnMut(&Action) -> bool,
+) -> Result<(), ProcessingError> {
+    let src = src.as_ref();
+    let actions = bicycle::traverse(
+        src,
+        dest,
+        |path| bike.transform_path(path, &insert_data),
+        bicycle::DEFAULT_TEMPLATE_EXT,
+    )
+    .map_err(|cause| ProcessingError::TraversalFailed {
+        src: src.to_owned(),
+        cause,
+    })?;
+    for action in actions.iter().filter(|action| filter(action)) {
+        process_action(bike, action, &insert_data)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unchanged_output_is_left_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dest) = (dir.path().join("src"), dir.path().join("dest"));
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("greeting.txt.hbs"), "Hello {{name}}!").unwrap();
+        fs::write(src.join("plain.txt"), "plain").unwrap();
+        let bike = Bicycle::default();
+        let insert_data = |map: &mut JsonMap| map.insert("name", "Shinji");
+        filter_and_process(&bike, &src, &dest, insert_data, |_| true).unwrap();
+        let (greeting, plain) = (dest.join("greeting.txt"), dest.join("plain.txt"));
+        assert_eq!(fs::read_to_string(&greeting).unwrap(), "Hello Shinji!");
+        assert_eq!(fs::read_to_string(&plain).unwrap(), "plain");
+
+        let modified = |path: &Path| fs::metadata(path).unwrap().modified().unwrap();
+        let before = (modified(&greeting), modified(&plain));
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        filter_and_process(&bike, &src, &dest, insert_data, |_| true).unwrap();
+        assert_eq!((modified(&greeting), modified(&plain)), before);
+
+        fs::write(src.join("plain.txt"), "changed").unwrap();
+        filter_and_process(&bike, &src, &dest, insert_data, |_| true).unwrap();
+        assert_eq!(fs::read_to_string(&plain).unwrap(), "changed");
+    }
+}
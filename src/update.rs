@@ -2,6 +2,7 @@ use crate::util::{
     self,
     cli::{Report, TextWrapper},
     repo::{self, Repo},
+    CommandError, Invocation, Runner,
 };
 use std::{
     fmt::{self, Display},
@@ -21,7 +22,7 @@ pub enum Error {
     StatusFailed(repo::Error),
     MarkerCreateFailed { path: PathBuf, cause: io::Error },
     UpdateFailed(repo::Error),
-    InstallFailed(bossy::Error),
+    InstallFailed(CommandError),
     MarkerDeleteFailed { path: PathBuf, cause: io::Error },
 }
 
@@ -71,14 +72,15 @@ pub fn update(wrapper: &TextWrapper) -> Result<(), Error> {
         repo.update("https://github.com/BrainiumLLC/cargo-mobile")
             .map_err(Error::UpdateFailed)?;
         println!("Installing updated `cargo-mobile`...");
-        bossy::Command::impure_parse("cargo install --force --path")
+        let command = Invocation::impure_parse("cargo install --force --path")
             .with_arg(repo.path())
             .with_parsed_args("--no-default-features --features")
             // Using `with_arg` instead of `with_args`/`with_parsed_args` here
             // is intentional; we want the feature list to be treated as a
             // single argument.
-            .with_arg(ENABLED_FEATURES.join(" "))
-            .run_and_wait()
+            .with_arg(ENABLED_FEATURES.join(" "));
+        Runner::global()
+            .stream(&command)
             .map_err(Error::InstallFailed)?;
         fs::remove_file(&marker).map_err(|cause| Error::MarkerDeleteFailed {
             path: marker.to_owned(),
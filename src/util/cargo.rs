@@ -1,32 +1,133 @@
-use crate::env::ExplicitEnv;
-use std::path::PathBuf;
+use super::{cli::Report, CommandError, CommandRunner, Invocation, Runner};
+use crate::{env::ExplicitEnv, opts::Profile, os};
+use std::{
+    fmt::{self, Display},
+    fs,
+    io::{self, BufRead as _, BufReader},
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+use toml::value::{Table, Value};
+
+/// The host environment variables that survive [`CargoCommand::isolated`].
+pub static ESSENTIAL_ENV_VARS: &[&str] = &[
+    "HOME",
+    "PATH",
+    "TERM",
+    "USER",
+    "CARGO_HOME",
+    "RUSTUP_HOME",
+    "RUSTUP_TOOLCHAIN",
+    "SSH_AUTH_SOCK",
+];
+
+#[derive(Debug)]
+pub struct ManifestPathError {
+    manifest_path: PathBuf,
+}
+
+impl Display for ManifestPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The manifest path {:?} doesn't point to an existing file.",
+            self.manifest_path
+        )
+    }
+}
+
+// `--config` only takes a single line, and `toml` displays tables as whole
+// sections, so we render them as inline tables ourselves.
+fn inline_toml(value: &Value) -> String {
+    match value {
+        Value::Table(table) => {
+            let entries = table
+                .iter()
+                .map(|(key, value)| {
+                    let bare = !key.is_empty()
+                        && key
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+                    let key = if bare {
+                        key.clone()
+                    } else {
+                        Value::String(key.clone()).to_string()
+                    };
+                    format!("{} = {}", key, inline_toml(value))
+                })
+                .collect::<Vec<_>>();
+            format!("{{ {} }}", entries.join(", "))
+        }
+        Value::Array(array) => format!(
+            "[{}]",
+            array.iter().map(inline_toml).collect::<Vec<_>>().join(", ")
+        ),
+        _ => value.to_string(),
+    }
+}
 
 #[derive(Debug)]
 pub struct CargoCommand<'a> {
+    toolchain: Option<&'a str>,
     subcommand: &'a str,
     verbose: bool,
     package: Option<&'a str>,
     manifest_path: Option<PathBuf>,
+    target_dir: Option<PathBuf>,
+    isolated_target_dir_base: Option<PathBuf>,
     target: Option<&'a str>,
+    default_target: Option<String>,
     no_default_features: bool,
     features: Option<&'a [String]>,
     release: bool,
+    profile: Option<&'a str>,
+    rustflags: Option<&'a [String]>,
+    locked: bool,
+    jobs: Option<NonZeroU32>,
+    config_overrides: Vec<String>,
+    essential_env_vars: Option<&'a [&'a str]>,
+    plain_output: bool,
 }
 
 impl<'a> CargoCommand<'a> {
     pub fn new(subcommand: &'a str) -> Self {
         Self {
+            toolchain: Default::default(),
             subcommand,
             verbose: Default::default(),
             package: Default::default(),
             manifest_path: Default::default(),
+            target_dir: Default::default(),
+            isolated_target_dir_base: Default::default(),
             target: Default::default(),
+            default_target: Default::default(),
             no_default_features: Default::default(),
             features: Default::default(),
             release: Default::default(),
+            profile: Default::default(),
+            rustflags: Default::default(),
+            locked: Default::default(),
+            jobs: Default::default(),
+            config_overrides: Default::default(),
+            essential_env_vars: Default::default(),
+            plain_output: Default::default(),
         }
     }
 
+    /// Turns this into a `cargo check`, which catches compile errors without
+    /// the cost of codegen and linking.
+    pub fn check(mut self) -> Self {
+        self.subcommand = "check";
+        self
+    }
+
+    /// Uses a specific rustup toolchain, i.e. `cargo +<toolchain>`.
+    pub fn with_toolchain(mut self, toolchain: &'a str) -> Self {
+        self.toolchain = Some(toolchain);
+        self
+    }
+
     pub fn with_verbose(mut self, verbose: bool) -> Self {
         self.verbose = verbose;
         self
@@ -37,16 +138,88 @@ impl<'a> CargoCommand<'a> {
         self
     }
 
+    /// Passes `--manifest-path`, which is how we target a crate that lives in
+    /// a subdir (i.e. in a monorepo). See [`CargoCommand::check_manifest_path`]
+    /// for catching bad paths before cargo does.
     pub fn with_manifest_path(mut self, manifest_path: Option<PathBuf>) -> Self {
+        if let Some(manifest_path) = &manifest_path {
+            if manifest_path.file_name() != Some("Cargo.toml".as_ref()) {
+                log::warn!(
+                    "manifest path {:?} isn't named `Cargo.toml`, which probably isn't intended",
+                    manifest_path
+                );
+            }
+        }
         self.manifest_path = manifest_path;
         self
     }
 
+    /// Checks that the manifest path (if any) points to an existing file, so
+    /// we can fail with a clear error instead of whatever cargo says.
+    pub fn check_manifest_path(&self) -> Result<(), ManifestPathError> {
+        match &self.manifest_path {
+            Some(manifest_path) if !manifest_path.is_file() => Err(ManifestPathError {
+                manifest_path: manifest_path.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Redirects build output via `--target-dir`. Since cargo gives the flag
+    /// precedence over `CARGO_TARGET_DIR`, this wins even if that's inherited
+    /// from the host environment.
+    pub fn with_target_dir(mut self, target_dir: Option<PathBuf>) -> Self {
+        self.target_dir = target_dir;
+        self
+    }
+
+    /// Uses a subdir of `base` as the target dir, with a name derived from the
+    /// target triple and feature set. This keeps builds with different
+    /// features from clobbering each other's output, which otherwise causes
+    /// confusing rebuilds. An explicit [`CargoCommand::with_target_dir`] takes
+    /// precedence over this.
+    pub fn with_isolated_target_dir(mut self, base: Option<PathBuf>) -> Self {
+        self.isolated_target_dir_base = base;
+        self
+    }
+
     pub fn with_target(mut self, target: Option<&'a str>) -> Self {
         self.target = target;
         self
     }
 
+    /// Like [`CargoCommand::with_target`], but leaves out `--target` if it'd
+    /// just be the same as the `build.target` cargo config for `dir`. Since
+    /// setting `build.target` has the same effect as passing `--target`, this
+    /// makes no difference to the build.
+    pub fn with_target_reconciled(mut self, target: Option<&'a str>, dir: &Path) -> Self {
+        let default = crate::dot_cargo::default_cargo_target(dir);
+        self.target = match (target, &default) {
+            (Some(target), Ok(Some(default))) if target == default => {
+                log::info!(
+                    "omitting `--target {}`, since it's already the default",
+                    target
+                );
+                None
+            }
+            (Some(target), Ok(Some(default))) => {
+                log::info!(
+                    "`--target {}` overrides the default target {:?}",
+                    target,
+                    default
+                );
+                Some(target)
+            }
+            (target, Err(err)) => {
+                log::warn!("failed to get default target from cargo config: {}", err);
+                target
+            }
+            (target, Ok(_)) => target,
+        };
+        self.default_target = default.ok().flatten();
+        self
+    }
+
     pub fn with_no_default_features(mut self, no_default_features: bool) -> Self {
         self.no_default_features = no_default_features;
         self
@@ -62,8 +235,89 @@ impl<'a> CargoCommand<'a> {
         self
     }
 
-    fn into_command_inner(self, mut command: bossy::Command) -> bossy::Command {
+    /// Builds with a custom profile via `--profile`, which takes precedence over
+    /// [`CargoCommand::with_release`].
+    pub fn with_profile(mut self, profile: Option<&'a str>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Adds to the target's `rustflags` via a `--config` override, which gets
+    /// merged with any rustflags from config files rather than replacing them
+    /// like `RUSTFLAGS` would.
+    pub fn with_rustflags(mut self, rustflags: Option<&'a [String]>) -> Self {
+        self.rustflags = rustflags;
+        self
+    }
+
+    /// Passes `--locked`, which makes cargo fail instead of updating a stale
+    /// `Cargo.lock`.
+    pub fn with_locked(mut self, locked: bool) -> Self {
+        self.locked = locked;
+        self
+    }
+
+    /// Limits build parallelism via `-j`. `None` leaves it up to cargo, which
+    /// uses one job per CPU.
+    pub fn with_jobs(mut self, jobs: Option<NonZeroU32>) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Passes `--config KEY=VALUE`, which overrides a config value without
+    /// touching any config files. `value` keeps its TOML type, so i.e. `&str`
+    /// values are quoted while integers and bools aren't. This can be called
+    /// multiple times to override multiple values.
+    pub fn with_config_override(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.config_overrides
+            .push(format!("{}={}", key, inline_toml(&value.into())));
+        self
+    }
+
+    /// Don't inherit the host environment, aside from [`ESSENTIAL_ENV_VARS`].
+    /// This keeps things like a stray `RUSTFLAGS` in the user's shell from
+    /// leaking into cross builds.
+    pub fn isolated(self) -> Self {
+        self.with_essential_env_vars(ESSENTIAL_ENV_VARS)
+    }
+
+    /// Like [`CargoCommand::isolated`], but with a custom set of host
+    /// environment variables to pass through.
+    pub fn with_essential_env_vars(mut self, essential_env_vars: &'a [&'a str]) -> Self {
+        self.essential_env_vars = Some(essential_env_vars);
+        self
+    }
+
+    /// Disables colors and progress bars, which would otherwise end up mixed
+    /// into any output that gets captured and parsed.
+    pub fn plain_output(mut self) -> Self {
+        self.plain_output = true;
+        self
+    }
+
+    fn into_command_inner(self, mut command: Invocation) -> Invocation {
+        if let Some(toolchain) = self.toolchain {
+            command.add_arg(format!("+{}", toolchain));
+        }
         command.add_arg(self.subcommand);
+        if let Some(rustflags) = self.rustflags.filter(|rustflags| !rustflags.is_empty()) {
+            // `target.<triple>.rustflags` beats `build.rustflags`, so these
+            // have to go there even if `--target` was left out.
+            let key = self.target.or(self.default_target.as_deref()).map_or_else(
+                || "build.rustflags".to_owned(),
+                |target| format!("target.{}.rustflags", target),
+            );
+            let value = Value::Array(rustflags.iter().cloned().map(Value::String).collect());
+            command.add_args(&["--config", &format!("{}={}", key, value)]);
+        }
+        if self.plain_output {
+            command = command
+                .with_args(&["--color", "never"])
+                .with_env_var("CARGO_TERM_PROGRESS_WHEN", "never");
+        }
+        for config_override in &self.config_overrides {
+            command.add_args(&["--config", config_override]);
+        }
         if self.verbose {
             command.add_arg("-vv");
         }
@@ -71,11 +325,26 @@ impl<'a> CargoCommand<'a> {
             command.add_args(&["--package", package]);
         }
         if let Some(manifest_path) = self.manifest_path {
-            if !manifest_path.exists() {
-                log::error!("manifest path {:?} doesn't exist!", manifest_path);
-            }
             command.add_arg("--manifest-path").add_arg(manifest_path);
         }
+        // Closures capture all of `self` in edition 2018, which would conflict
+        // with moving `self.target_dir` out.
+        // The suffix has to follow the target cargo will actually build for,
+        // even when `--target` was left out in favor of the cargo config.
+        let target = self.target.or(self.default_target.as_deref());
+        let features = self.features;
+        let isolated_target_dir_base = self.isolated_target_dir_base;
+        let target_dir = self.target_dir.or_else(|| {
+            isolated_target_dir_base.map(|base| {
+                base.join(target_dir_suffix(
+                    target.unwrap_or("host"),
+                    features.unwrap_or_default(),
+                ))
+            })
+        });
+        if let Some(target_dir) = target_dir {
+            command.add_arg("--target-dir").add_arg(target_dir);
+        }
         if let Some(target) = self.target {
             // We used to use `util::host_target_triple` to avoid explicitly
             // specifying the default target triple here, since specifying it
@@ -93,17 +362,1202 @@ impl<'a> CargoCommand<'a> {
         if let Some(features) = self.features {
             command.add_args(&["--features", &features.join(" ")]);
         }
-        if self.release {
+        if let Some(profile) = self.profile {
+            if self.release {
+                log::info!("using profile {:?} instead of `--release`", profile);
+            }
+            command.add_args(&["--profile", profile]);
+        } else if self.release {
             command.add_arg("--release");
         }
+        if self.locked {
+            command.add_arg("--locked");
+        }
+        if let Some(jobs) = self.jobs {
+            command.add_args(&["-j", &jobs.to_string()]);
+        }
         command
     }
 
-    pub fn into_command_impure(self) -> bossy::Command {
-        self.into_command_inner(bossy::Command::impure("cargo"))
+    fn base_command(&self) -> Invocation {
+        if let Some(essential_env_vars) = self.essential_env_vars {
+            super::allowlisted_command("cargo", essential_env_vars)
+        } else {
+            Invocation::impure("cargo")
+        }
+    }
+
+    /// Builds the command, failing if the manifest path doesn't exist (see
+    /// [`CargoCommand::check_manifest_path`]).
+    pub fn into_command_impure(self) -> Result<Invocation, ManifestPathError> {
+        self.check_manifest_path()?;
+        let command = self.base_command();
+        Ok(self.into_command_inner(command))
+    }
+
+    /// Like [`CargoCommand::into_command_impure`], but only passes `env`
+    /// along.
+    pub fn into_command_pure(
+        self,
+        env: &impl ExplicitEnv,
+    ) -> Result<Invocation, ManifestPathError> {
+        self.check_manifest_path()?;
+        // `pure` is already isolated, but any essential vars requested on top
+        // of the explicit env still get passed through.
+        let command = if self.essential_env_vars.is_some() {
+            self.base_command()
+        } else {
+            Invocation::pure("cargo")
+        }
+        .with_env_vars(env.explicit_env());
+        Ok(self.into_command_inner(command))
+    }
+
+    /// Runs the command with `--message-format=json-render-diagnostics` and
+    /// collects the paths of the artifacts it produced. Diagnostics are still
+    /// printed as usual, and a failed build isn't an error; check
+    /// [`BuildOutput::success`] instead.
+    pub fn build_and_collect(self) -> Result<BuildOutput, BuildAndCollectError> {
+        let command = self
+            .into_command_impure()
+            .map_err(BuildAndCollectError::ManifestPathInvalid)?;
+        collect_build_output(command)
+    }
+}
+
+/// Like [`CargoCommand::build_and_collect`], but for a cargo command that's
+/// already been set up, which is how platform-specific env gets in.
+pub fn collect_build_output(command: Invocation) -> Result<BuildOutput, BuildAndCollectError> {
+    let mut output = BuildOutput::default();
+    let exited_ok = collect_messages(command, "json-render-diagnostics", |line| {
+        output.add_message(line)
+    })?;
+    output.success &= exited_ok;
+    Ok(output)
+}
+
+/// Runs a `cargo check` command with `--message-format=json`, printing each
+/// diagnostic as it comes in and collecting them. Like
+/// [`collect_build_output`], failing to compile isn't an error; check
+/// [`CheckOutput::success`] instead.
+pub fn check_and_collect(command: Invocation) -> Result<CheckOutput, BuildAndCollectError> {
+    let mut output = CheckOutput::default();
+    let exited_ok = collect_messages(command, "json", |line| output.add_message(line))?;
+    output.success &= exited_ok;
+    Ok(output)
+}
+
+// Feeds each line of cargo's JSON messages to `add_message`, and then returns
+// whether cargo exited successfully.
+fn collect_messages(
+    command: Invocation,
+    message_format: &str,
+    mut add_message: impl FnMut(&str),
+) -> Result<bool, BuildAndCollectError> {
+    let (reader, writer) = os::pipe().map_err(BuildAndCollectError::PipeFailed)?;
+    // Passing the write end to `spawn` means it gets closed on our side, so
+    // we'll see EOF once cargo exits.
+    let child = Runner::global()
+        .spawn(
+            &command.with_arg(format!("--message-format={}", message_format)),
+            Stdio::inherit(),
+            Stdio::from(writer),
+            Stdio::inherit(),
+        )
+        .map_err(BuildAndCollectError::CommandFailed)?;
+    for line in BufReader::new(reader).lines() {
+        let line = line.map_err(BuildAndCollectError::ReadFailed)?;
+        add_message(&line);
+    }
+    match child.wait() {
+        Ok(_) => Ok(true),
+        // A non-zero exit just means the build failed, which the caller
+        // can find out from the messages.
+        Err(err) if err.status().is_some() => Ok(false),
+        Err(err) => Err(BuildAndCollectError::CommandFailed(err)),
+    }
+}
+
+fn parse_message(line: &str) -> Option<serde_json::Value> {
+    serde_json::from_str::<serde_json::Value>(line)
+        .map_err(|err| log::warn!("failed to parse cargo message {:?}: {}", line, err))
+        .ok()
+}
+
+fn build_finished(message: &serde_json::Value) -> Option<bool> {
+    if message.get("reason").and_then(|reason| reason.as_str()) == Some("build-finished") {
+        Some(
+            message
+                .get("success")
+                .and_then(|success| success.as_bool())
+                .unwrap_or_default(),
+        )
+    } else {
+        None
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BuildOutput {
+    pub artifacts: Vec<PathBuf>,
+    /// The test harnesses that got built, i.e. by `cargo test --no-run`.
+    pub tests: Vec<PathBuf>,
+    pub success: bool,
+}
+
+impl BuildOutput {
+    fn add_message(&mut self, line: &str) {
+        let message = match parse_message(line) {
+            Some(message) => message,
+            None => return,
+        };
+        if let Some(success) = build_finished(&message) {
+            self.success = success;
+            return;
+        }
+        match message.get("reason").and_then(|reason| reason.as_str()) {
+            Some("compiler-artifact") => {
+                if let Some(filenames) = message.get("filenames").and_then(|f| f.as_array()) {
+                    self.artifacts.extend(
+                        filenames
+                            .iter()
+                            .filter_map(|filename| filename.as_str())
+                            .map(PathBuf::from),
+                    );
+                }
+                let test = message
+                    .pointer("/profile/test")
+                    .and_then(|test| test.as_bool())
+                    .unwrap_or_default();
+                if let Some(executable) = message
+                    .get("executable")
+                    .and_then(|executable| executable.as_str())
+                    .filter(|_| test)
+                {
+                    self.tests.push(PathBuf::from(executable));
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// A compiler diagnostic from cargo's JSON messages.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Diagnostic {
+    /// i.e. `"error"` or `"warning"`
+    pub level: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        // This also covers "error: internal compiler error"
+        self.level.starts_with("error")
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CheckOutput {
+    pub diagnostics: Vec<Diagnostic>,
+    pub success: bool,
+}
+
+impl CheckOutput {
+    fn add_message(&mut self, line: &str) {
+        let message = match parse_message(line) {
+            Some(message) => message,
+            None => return,
+        };
+        if let Some(success) = build_finished(&message) {
+            self.success = success;
+            return;
+        }
+        if message.get("reason").and_then(|reason| reason.as_str()) != Some("compiler-message") {
+            return;
+        }
+        let message = match message.get("message") {
+            Some(message) => message,
+            None => return,
+        };
+        let field = |key: &str| {
+            message
+                .get(key)
+                .and_then(|value| value.as_str())
+                .unwrap_or_default()
+        };
+        // Since cargo isn't rendering these itself, we have to.
+        eprint!("{}", field("rendered"));
+        self.diagnostics.push(Diagnostic {
+            level: field("level").to_owned(),
+            message: field("message").to_owned(),
+        });
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.is_error())
+    }
+}
+
+impl Display for CheckOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors = self.errors().collect::<Vec<_>>();
+        if errors.is_empty() {
+            write!(f, "`cargo check` failed without reporting any errors.")
+        } else {
+            write!(f, "`cargo check` found {} error(s):", errors.len())?;
+            for error in errors {
+                write!(f, "\n- {}", error.message)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BuildAndCollectError {
+    ManifestPathInvalid(ManifestPathError),
+    PipeFailed(io::Error),
+    CommandFailed(CommandError),
+    ReadFailed(io::Error),
+}
+
+impl Display for BuildAndCollectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ManifestPathInvalid(err) => write!(f, "{}", err),
+            Self::PipeFailed(err) => write!(f, "Failed to create output pipe: {}", err),
+            Self::CommandFailed(err) => write!(f, "Failed to run cargo: {}", err),
+            Self::ReadFailed(err) => write!(f, "Failed to read cargo output: {}", err),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct JobsInvalid {
+    input: String,
+}
+
+impl Display for JobsInvalid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} isn't a valid job count; expected \"auto\" or a number greater than 0",
+            self.input
+        )
+    }
+}
+
+/// Parses a job count for [`CargoCommand::with_jobs`], where `"auto"` means to
+/// use cargo's default.
+pub fn parse_jobs(input: &str) -> Result<Option<NonZeroU32>, JobsInvalid> {
+    if input.eq_ignore_ascii_case("auto") {
+        Ok(None)
+    } else {
+        input
+            .parse::<NonZeroU32>()
+            .map(Some)
+            .map_err(|_| JobsInvalid {
+                input: input.to_owned(),
+            })
+    }
+}
+
+#[derive(Debug)]
+pub enum HasDependencyError {
+    ManifestPathInvalid(ManifestPathError),
+    CommandFailed(CommandError),
+    ParseFailed(serde_json::Error),
+}
+
+impl Display for HasDependencyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ManifestPathInvalid(err) => write!(f, "{}", err),
+            Self::CommandFailed(err) => write!(f, "Failed to run `cargo metadata`: {}", err),
+            Self::ParseFailed(err) => write!(f, "Failed to parse `cargo metadata` output: {}", err),
+        }
+    }
+}
+
+/// Checks whether a package named `crate_name` is anywhere in the resolved
+/// dependency graph described by `cargo metadata --format-version 1` output.
+/// Hyphens and underscores are treated as the same, like cargo does.
+pub fn metadata_has_dependency(raw: &[u8], crate_name: &str) -> Result<bool, serde_json::Error> {
+    let normalize = |name: &str| name.replace('-', "_");
+    let crate_name = normalize(crate_name);
+    let metadata = serde_json::from_slice::<serde_json::Value>(raw)?;
+    Ok(metadata
+        .get("packages")
+        .and_then(|packages| packages.as_array())
+        .map_or(false, |packages| {
+            packages.iter().any(|package| {
+                package
+                    .get("name")
+                    .and_then(|name| name.as_str())
+                    .map_or(false, |name| normalize(name) == crate_name)
+            })
+        }))
+}
+
+/// Checks whether the crate at `manifest_dir` depends on `crate_name`, even
+/// transitively.
+pub fn has_dependency(manifest_dir: &Path, crate_name: &str) -> Result<bool, HasDependencyError> {
+    let command = CargoCommand::new("metadata")
+        .with_manifest_path(Some(manifest_dir.join("Cargo.toml")))
+        .into_command_impure()
+        .map_err(HasDependencyError::ManifestPathInvalid)?
+        .with_args(&["--format-version", "1"]);
+    let output = Runner::global()
+        .capture(&command)
+        .map_err(HasDependencyError::CommandFailed)?;
+    metadata_has_dependency(output.stdout(), crate_name).map_err(HasDependencyError::ParseFailed)
+}
+
+#[derive(Debug)]
+pub enum LibCrateTypesError {
+    ManifestPathInvalid(ManifestPathError),
+    CommandFailed(CommandError),
+    ParseFailed(serde_json::Error),
+}
+
+impl Display for LibCrateTypesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ManifestPathInvalid(err) => write!(f, "{}", err),
+            Self::CommandFailed(err) => write!(f, "Failed to run `cargo metadata`: {}", err),
+            Self::ParseFailed(err) => write!(f, "Failed to parse `cargo metadata` output: {}", err),
+        }
+    }
+}
+
+/// Gets the crate types of the `lib` target of the package with the manifest
+/// at `manifest_path`, according to `cargo metadata --format-version 1` output.
+/// If there's only one package, it's used regardless of `manifest_path`. The
+/// result is empty if there's no `lib` target.
+pub fn metadata_lib_crate_types(
+    raw: &[u8],
+    manifest_path: &Path,
+) -> Result<Vec<String>, serde_json::Error> {
+    let metadata = serde_json::from_slice::<serde_json::Value>(raw)?;
+    let packages = metadata
+        .get("packages")
+        .and_then(|packages| packages.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let package = if packages.len() == 1 {
+        packages.first()
+    } else {
+        packages.iter().find(|package| {
+            package
+                .get("manifest_path")
+                .and_then(|path| path.as_str())
+                .map_or(false, |path| Path::new(path) == manifest_path)
+        })
+    };
+    Ok(package
+        .and_then(|package| package.get("targets"))
+        .and_then(|targets| targets.as_array())
+        .and_then(|targets| {
+            targets.iter().find(|target| {
+                target
+                    .get("kind")
+                    .and_then(|kind| kind.as_array())
+                    .map_or(false, |kind| {
+                        // Any of the lib crate types makes this the lib target
+                        kind.iter().any(|kind| {
+                            kind.as_str().map_or(false, |kind| {
+                                ["lib", "rlib", "dylib", "cdylib", "staticlib", "proc-macro"]
+                                    .contains(&kind)
+                            })
+                        })
+                    })
+            })
+        })
+        .and_then(|target| target.get("crate_types"))
+        .and_then(|crate_types| crate_types.as_array())
+        .map(|crate_types| {
+            crate_types
+                .iter()
+                .filter_map(|crate_type| crate_type.as_str().map(ToOwned::to_owned))
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// Gets the crate types of the lib target of the crate at `manifest_dir`.
+/// Since this asks cargo, workspace inheritance and such are already taken
+/// into account.
+pub fn lib_crate_types(manifest_dir: &Path) -> Result<Vec<String>, LibCrateTypesError> {
+    let manifest_path = manifest_dir.join("Cargo.toml");
+    let command = CargoCommand::new("metadata")
+        .with_manifest_path(Some(manifest_path.clone()))
+        .into_command_impure()
+        .map_err(LibCrateTypesError::ManifestPathInvalid)?
+        .with_args(&["--format-version", "1", "--no-deps"]);
+    let output = Runner::global()
+        .capture(&command)
+        .map_err(LibCrateTypesError::CommandFailed)?;
+    // cargo reports canonical paths
+    let manifest_path = manifest_path.canonicalize().unwrap_or(manifest_path);
+    metadata_lib_crate_types(output.stdout(), &manifest_path)
+        .map_err(LibCrateTypesError::ParseFailed)
+}
+
+/// Formats a generated Rust file in place. Not having `rustfmt` installed
+/// isn't an error; the file just stays unformatted.
+pub fn rustfmt_file(path: &Path) -> Result<(), CommandError> {
+    rustfmt_with(Runner::global(), &rustfmt_command(path), path)
+}
+
+fn rustfmt_command(path: &Path) -> Invocation {
+    Invocation::impure("rustfmt")
+        .with_args(&["--edition", "2018"])
+        .with_arg(path)
+}
+
+fn rustfmt_with(
+    runner: &impl CommandRunner,
+    command: &Invocation,
+    path: &Path,
+) -> Result<(), CommandError> {
+    match runner.capture(command) {
+        Ok(_) => Ok(()),
+        Err(err) => match err {
+            CommandError::NotFound { .. } => {
+                log::warn!(
+                    "`rustfmt` isn't installed, so {:?} won't be formatted; you can install it with `rustup component add rustfmt`",
+                    path
+                );
+                Ok(())
+            }
+            err => Err(err),
+        },
+    }
+}
+
+#[derive(Debug)]
+pub enum RunCargoError {
+    ManifestPathInvalid(ManifestPathError),
+    CommandFailed(CommandError),
+}
+
+impl Display for RunCargoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ManifestPathInvalid(err) => write!(f, "{}", err),
+            Self::CommandFailed(err) => write!(f, "{}", err),
+        }
     }
+}
+
+impl RunCargoError {
+    pub fn report(&self, msg: &str) -> Report {
+        match self {
+            Self::ManifestPathInvalid(err) => Report::error(msg, err),
+            Self::CommandFailed(err) => err.report(msg),
+        }
+    }
+}
+
+/// Runs `cargo clippy` on the crate at `manifest_dir`, optionally treating
+/// warnings as errors.
+pub fn clippy_check(manifest_dir: &Path, deny_warnings: bool) -> Result<(), RunCargoError> {
+    let mut command = CargoCommand::new("clippy")
+        .with_manifest_path(Some(manifest_dir.join("Cargo.toml")))
+        .into_command_impure()
+        .map_err(RunCargoError::ManifestPathInvalid)?;
+    if deny_warnings {
+        command.add_args(&["--", "-D", "warnings"]);
+    }
+    Runner::global()
+        .stream(&command)
+        .map_err(RunCargoError::CommandFailed)?;
+    Ok(())
+}
+
+/// Gets a target dir name that's unique to `triple` and `features`. The order
+/// of `features` doesn't matter.
+pub fn target_dir_suffix(triple: &str, features: &[String]) -> String {
+    use std::hash::Hasher as _;
+    let mut features = features.iter().map(String::as_str).collect::<Vec<_>>();
+    features.sort_unstable();
+    features.dedup();
+    let mut hasher = fnv::FnvHasher::default();
+    hasher.write(triple.as_bytes());
+    for feature in features {
+        hasher.write(&[0]);
+        hasher.write(feature.as_bytes());
+    }
+    format!("{}-{:016x}", triple, hasher.finish())
+}
+
+fn clean_target_command(
+    manifest_dir: &Path,
+    triple: &str,
+    profile: Profile,
+) -> Result<Invocation, ManifestPathError> {
+    CargoCommand::new("clean")
+        .with_manifest_path(Some(manifest_dir.join("Cargo.toml")))
+        .with_target(Some(triple))
+        .with_release(profile.release())
+        .into_command_impure()
+}
+
+/// Removes the build artifacts for a single target triple and profile, leaving
+/// everything else in the target dir alone.
+pub fn cargo_clean_target(
+    manifest_dir: &Path,
+    triple: &str,
+    profile: Profile,
+) -> Result<(), RunCargoError> {
+    let command = clean_target_command(manifest_dir, triple, profile)
+        .map_err(RunCargoError::ManifestPathInvalid)?;
+    Runner::global()
+        .stream(&command)
+        .map_err(RunCargoError::CommandFailed)?;
+    Ok(())
+}
+
+/// Fails if `Cargo.lock` in `manifest_dir` is missing or out of date, without
+/// changing it.
+pub fn verify_lockfile_unchanged(manifest_dir: &Path) -> Result<(), RunCargoError> {
+    let command = CargoCommand::new("metadata")
+        .with_manifest_path(Some(manifest_dir.join("Cargo.toml")))
+        .with_locked(true)
+        .into_command_impure()
+        .map_err(RunCargoError::ManifestPathInvalid)?
+        .with_args(&["--format-version", "1"]);
+    // We only care about the exit status, not the metadata itself
+    super::run(&command).map_err(RunCargoError::CommandFailed)
+}
+
+fn parse_cargo_list(raw_list: &str) -> impl Iterator<Item = &str> {
+    // The first line is just an "Installed Commands:" header
+    raw_list
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().next())
+}
+
+/// Checks if `cargo <name>` is available, according to `cargo --list`.
+pub fn cargo_subcommand_installed(name: &str) -> Result<bool, CommandError> {
+    let output = Runner::global().capture(&Invocation::impure_parse("cargo --list"))?;
+    let raw_list = String::from_utf8_lossy(output.stdout());
+    let installed = parse_cargo_list(&raw_list).any(|subcommand| subcommand == name);
+    log::info!("`cargo {}` installed: {}", name, installed);
+    Ok(installed)
+}
+
+/// The command to run to get `name` to show up in `cargo --list`.
+pub fn suggest_install(name: &str) -> String {
+    format!("cargo install cargo-{}", name)
+}
+
+#[derive(Debug)]
+pub enum MergeCargoTomlError {
+    ReadFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
+    ParseFailed {
+        path: PathBuf,
+        cause: toml_edit::TomlError,
+    },
+    FragmentParseFailed(toml_edit::TomlError),
+    Conflict {
+        key: String,
+    },
+    WriteFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
+}
+
+impl Display for MergeCargoTomlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ReadFailed { path, cause } => write!(f, "Failed to read {:?}: {}", path, cause),
+            Self::ParseFailed { path, cause } => write!(f, "Failed to parse {:?}: {}", path, cause),
+            Self::FragmentParseFailed(err) => {
+                write!(f, "Failed to parse manifest fragment: {}", err)
+            }
+            Self::Conflict { key } => write!(
+                f,
+                "`{}` is already set to a different value, so it can't be merged",
+                key
+            ),
+            Self::WriteFailed { path, cause } => {
+                write!(f, "Failed to write {:?}: {}", path, cause)
+            }
+        }
+    }
+}
+
+// Values are compared by what they mean rather than how they're written, so
+// `"1"` and `'1'` are the same.
+fn same_value(a: &toml_edit::Value, b: &toml_edit::Value) -> bool {
+    let parse = |value: &toml_edit::Value| {
+        toml::from_str::<Table>(&format!("value = {}", value))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+    };
+    parse(a).is_some() && parse(a) == parse(b)
+}
+
+// Tables parsed from the fragment know where they were in the fragment, which
+// would put them in the wrong spot in the manifest. Rebuilding them leaves it
+// up to `toml_edit` to put them after their parent.
+fn detach(item: &toml_edit::Item) -> toml_edit::Item {
+    use toml_edit::{ArrayOfTables, Item};
+    fn detach_table(src: &toml_edit::Table) -> toml_edit::Table {
+        let mut table = toml_edit::Table::new();
+        table.set_implicit(src.is_implicit());
+        table.set_dotted(src.is_dotted());
+        *table.decor_mut() = src.decor().clone();
+        for (key, item) in src.iter() {
+            let (key, _) = src
+                .get_key_value(key)
+                .expect("developer error: iterated key was missing");
+            table.insert_formatted(key, detach(item));
+        }
+        table
+    }
+    match item {
+        Item::Table(table) => Item::Table(detach_table(table)),
+        Item::ArrayOfTables(array) => {
+            let mut detached = ArrayOfTables::new();
+            for table in array.iter() {
+                detached.push(detach_table(table));
+            }
+            Item::ArrayOfTables(detached)
+        }
+        Item::Value(_) | Item::None => item.clone(),
+    }
+}
+
+fn merge_tables(
+    dest: &mut dyn toml_edit::TableLike,
+    src: &dyn toml_edit::TableLike,
+    key_path: &str,
+) -> Result<(), MergeCargoTomlError> {
+    for (key, src_item) in src.iter() {
+        let full_key = if key_path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", key_path, key)
+        };
+        match dest.get_mut(key) {
+            None => {
+                let (formatted_key, _) = src
+                    .get_key_value(key)
+                    .expect("developer error: iterated key was missing");
+                dest.entry_format(formatted_key).or_insert(detach(src_item));
+            }
+            Some(dest_item) => {
+                if let (Some(dest_table), Some(src_table)) =
+                    (dest_item.as_table_like_mut(), src_item.as_table_like())
+                {
+                    merge_tables(dest_table, src_table, &full_key)?;
+                } else {
+                    let same = match (dest_item.as_value(), src_item.as_value()) {
+                        (Some(dest_value), Some(src_value)) => same_value(dest_value, src_value),
+                        _ => false,
+                    };
+                    if !same {
+                        return Err(MergeCargoTomlError::Conflict { key: full_key });
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Deep-merges the TOML in `fragment` into the manifest at `manifest`, which is
+/// how we add mobile-specific dependencies without clobbering anything else.
+/// Setting a key that's already set to a different value is an error.
+///
+/// The manifest is edited in place, so its comments and formatting survive.
+pub fn merge_cargo_toml(manifest: &Path, fragment: &str) -> Result<(), MergeCargoTomlError> {
+    let contents =
+        fs::read_to_string(manifest).map_err(|cause| MergeCargoTomlError::ReadFailed {
+            path: manifest.to_owned(),
+            cause,
+        })?;
+    let mut document = contents.parse::<toml_edit::Document>().map_err(|cause| {
+        MergeCargoTomlError::ParseFailed {
+            path: manifest.to_owned(),
+            cause,
+        }
+    })?;
+    let fragment = fragment
+        .parse::<toml_edit::Document>()
+        .map_err(MergeCargoTomlError::FragmentParseFailed)?;
+    merge_tables(document.as_table_mut(), fragment.as_table(), "")?;
+    super::write_str_if_changed(manifest, document.to_string())
+        .map(|_| ())
+        .map_err(|cause| MergeCargoTomlError::WriteFailed {
+            path: manifest.to_owned(),
+            cause,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_manifest_path_is_rejected_before_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest_path = dir.path().join("Cargo.toml");
+        let err = CargoCommand::new("build")
+            .with_manifest_path(Some(manifest_path.clone()))
+            .into_command_impure()
+            .unwrap_err();
+        assert_eq!(err.manifest_path, manifest_path);
+        fs::write(&manifest_path, "").unwrap();
+        let command = CargoCommand::new("build")
+            .with_manifest_path(Some(manifest_path))
+            .into_command_impure()
+            .unwrap();
+        assert!(command.command_line().contains("--manifest-path"));
+    }
+
+    #[test]
+    fn clean_target_is_scoped_to_the_triple_and_profile() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "").unwrap();
+        let command =
+            clean_target_command(dir.path(), "aarch64-apple-ios", Profile::Release).unwrap();
+        let args = command.args();
+        assert_eq!(args[0], "clean");
+        let target = args.iter().position(|arg| arg == "--target").unwrap();
+        assert_eq!(args[target + 1], "aarch64-apple-ios");
+        assert!(args.iter().any(|arg| arg == "--release"));
+        let command =
+            clean_target_command(dir.path(), "aarch64-apple-ios", Profile::Debug).unwrap();
+        assert!(!command.args().iter().any(|arg| arg == "--release"));
+    }
+
+    #[test]
+    fn rustfmt_formats_the_file_in_place() {
+        let runner = crate::util::RecordingRunner::new();
+        let path = Path::new("src/bindings.rs");
+        rustfmt_with(&runner, &rustfmt_command(path), path).unwrap();
+        assert_eq!(
+            runner.command_lines(),
+            vec!["rustfmt --edition 2018 src/bindings.rs"]
+        );
+    }
+
+    #[test]
+    fn missing_rustfmt_is_only_a_warning() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bindings.rs");
+        // Nothing can be found on an empty `PATH`
+        let command = rustfmt_command(&path).with_env_var("PATH", dir.path());
+        rustfmt_with(Runner::global(), &command, &path).unwrap();
+    }
+
+    #[test]
+    fn config_overrides_keep_their_types() {
+        let table = toml::from_str::<Table>("debug = 1\n\"opt.level\" = \"s\"").unwrap();
+        let command = CargoCommand::new("build")
+            .with_config_override("build.jobs", 2)
+            .with_config_override("net.offline", true)
+            .with_config_override("build.target-dir", "target dir")
+            .with_config_override("build.rustdocflags", vec!["-D", "warnings"])
+            .with_config_override("profile.dev", table)
+            .into_command_impure()
+            .unwrap();
+        let overrides = command
+            .args()
+            .windows(2)
+            .filter(|pair| pair[0] == "--config")
+            .map(|pair| pair[1].to_str().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            overrides,
+            vec![
+                "build.jobs=2",
+                "net.offline=true",
+                "build.target-dir=\"target dir\"",
+                "build.rustdocflags=[\"-D\", \"warnings\"]",
+                "profile.dev={ debug = 1, \"opt.level\" = \"s\" }",
+            ]
+        );
+    }
+
+    #[test]
+    fn check_swaps_the_subcommand() {
+        let command = CargoCommand::new("build")
+            .check()
+            .into_command_impure()
+            .unwrap();
+        assert_eq!(command.args()[0], "check");
+    }
+
+    #[test]
+    fn check_output_collects_diagnostics() {
+        let mut output = CheckOutput::default();
+        for line in &[
+            r#"{"reason":"compiler-artifact","filenames":[]}"#,
+            r#"{"reason":"compiler-message","message":{"level":"warning","message":"unused variable: `x`","rendered":""}}"#,
+            r#"{"reason":"compiler-message","message":{"level":"error","message":"mismatched types","rendered":""}}"#,
+            "not json",
+            r#"{"reason":"build-finished","success":false}"#,
+        ] {
+            output.add_message(line);
+        }
+        assert!(!output.success);
+        assert_eq!(output.diagnostics.len(), 2);
+        let errors = output.errors().collect::<Vec<_>>();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "mismatched types");
+        assert!(output.to_string().contains("1 error(s)"));
+    }
+
+    #[test]
+    fn reconciled_target_keeps_the_isolated_target_dir_suffix() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(".cargo")).unwrap();
+        fs::write(
+            dir.path().join(".cargo/config.toml"),
+            "[build]\ntarget = \"aarch64-linux-android\"\n",
+        )
+        .unwrap();
+        let base = dir.path().join("target");
+        let command = CargoCommand::new("build")
+            .with_target_reconciled(Some("aarch64-linux-android"), dir.path())
+            .with_isolated_target_dir(Some(base.clone()))
+            .into_command_impure()
+            .unwrap();
+        let args = command.args();
+        assert!(!args.iter().any(|arg| arg == "--target"));
+        let target_dir = base.join(target_dir_suffix("aarch64-linux-android", &[]));
+        assert!(args.iter().any(|arg| *arg == *target_dir.as_os_str()));
+    }
+
+    #[test]
+    fn reconciled_targets_still_get_their_rustflags() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join(".cargo")).unwrap();
+        fs::write(
+            dir.path().join(".cargo/config.toml"),
+            "[build]\ntarget = \"aarch64-linux-android\"\n",
+        )
+        .unwrap();
+        let rustflags = ["-Clink-arg=-landroid".to_owned()];
+        let command = CargoCommand::new("build")
+            .with_target_reconciled(Some("aarch64-linux-android"), dir.path())
+            .with_rustflags(Some(&rustflags))
+            .into_command_impure()
+            .unwrap();
+        assert_eq!(
+            command.args()[1..3],
+            [
+                "--config",
+                r#"target.aarch64-linux-android.rustflags=["-Clink-arg=-landroid"]"#
+            ]
+        );
+    }
+
+    #[test]
+    fn merged_manifests_keep_their_comments_and_formatting() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("Cargo.toml");
+        let original = r#"[package]
+name = "my-app" # keep this name
+version = "0.1.0"
+
+# Our own deps
+[dependencies]
+log  =  "0.4"
+serde = { version = "1", features = ["derive"] }
+"#;
+        fs::write(&manifest, original).unwrap();
+        merge_cargo_toml(
+            &manifest,
+            r#"
+[dependencies]
+log = '0.4'
+
+[target.'cfg(target_os = "android")'.dependencies]
+ndk-glue = "0.2"
+"#,
+        )
+        .unwrap();
+        let merged = fs::read_to_string(&manifest).unwrap();
+        assert_eq!(
+            merged,
+            format!(
+                "{}
+[target.'cfg(target_os = \"android\")'.dependencies]\nndk-glue = \"0.2\"\n",
+                original
+            )
+        );
+        // Merging again is a no-op
+        merge_cargo_toml(&manifest, "[dependencies]\nlog = \"0.4\"\n").unwrap();
+        assert_eq!(fs::read_to_string(&manifest).unwrap(), merged);
+    }
+
+    #[test]
+    fn conflicting_values_arent_merged() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("Cargo.toml");
+        let original = "[dependencies]\nlog = \"0.4\"\n";
+        fs::write(&manifest, original).unwrap();
+        let err = merge_cargo_toml(&manifest, "[dependencies]\nlog = \"0.3\"\n").unwrap_err();
+        assert!(
+            matches!(&err, MergeCargoTomlError::Conflict { key } if key == "dependencies.log"),
+            "{}",
+            err
+        );
+        assert_eq!(fs::read_to_string(&manifest).unwrap(), original);
+    }
+
+    #[test]
+    fn installed_subcommands_are_read_from_cargo_list() {
+        let raw_list = "\
+Installed Commands:
+    add                  Add dependencies to a Cargo.toml manifest file
+    b                    alias: build
+    ndk
+";
+        let subcommands = parse_cargo_list(raw_list).collect::<Vec<_>>();
+        assert_eq!(subcommands, vec!["add", "b", "ndk"]);
+        assert_eq!(suggest_install("ndk"), "cargo install cargo-ndk");
+    }
+
+    #[test]
+    fn job_counts_are_passed_along_unless_auto() {
+        let jobs = parse_jobs("4").unwrap();
+        assert_eq!(jobs, NonZeroU32::new(4));
+        let command = CargoCommand::new("build")
+            .with_jobs(jobs)
+            .into_command_impure()
+            .unwrap();
+        assert_eq!(command.args()[1..], ["-j", "4"]);
+        assert_eq!(parse_jobs("AUTO").unwrap(), None);
+        let command = CargoCommand::new("build")
+            .with_jobs(None)
+            .into_command_impure()
+            .unwrap();
+        assert_eq!(command.args().len(), 1);
+        for invalid in &["0", "-1", "many", ""] {
+            assert!(parse_jobs(invalid).is_err(), "{:?} was accepted", invalid);
+        }
+    }
+
+    #[test]
+    fn stale_lockfiles_are_caught_without_being_updated() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("Cargo.toml");
+        fs::write(
+            &manifest,
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\nedition = \"2018\"\n",
+        )
+        .unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        let command = CargoCommand::new("build")
+            .with_locked(true)
+            .into_command_impure()
+            .unwrap();
+        assert_eq!(command.args()[1..], ["--locked"]);
+
+        // There's no lockfile at all yet, which `--locked` won't fix for us
+        assert!(verify_lockfile_unchanged(dir.path()).is_err());
+        assert!(!dir.path().join("Cargo.lock").exists());
+        fs::write(
+            dir.path().join("Cargo.lock"),
+            "[[package]]\nname = \"app\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        verify_lockfile_unchanged(dir.path()).unwrap();
+    }
+
+    #[test]
+    fn build_output_collects_artifacts_and_test_harnesses() {
+        let mut output = BuildOutput::default();
+        for line in &[
+            r#"{"reason":"compiler-artifact","profile":{"test":false},"filenames":["/t/libapp.so","/t/libapp.rlib"],"executable":null}"#,
+            r#"{"reason":"compiler-artifact","profile":{"test":true},"filenames":["/t/app-1234"],"executable":"/t/app-1234"}"#,
+            r#"{"reason":"compiler-artifact","profile":{"test":false},"filenames":["/t/cli"],"executable":"/t/cli"}"#,
+            r#"{"reason":"compiler-message","message":{}}"#,
+            "Compiling app v0.1.0",
+            r#"{"reason":"build-finished","success":true}"#,
+        ] {
+            output.add_message(line);
+        }
+        assert_eq!(
+            output,
+            BuildOutput {
+                artifacts: ["/t/libapp.so", "/t/libapp.rlib", "/t/app-1234", "/t/cli"]
+                    .iter()
+                    .map(PathBuf::from)
+                    .collect(),
+                tests: vec![PathBuf::from("/t/app-1234")],
+                success: true,
+            }
+        );
+    }
+
+    #[test]
+    fn dependencies_are_found_anywhere_in_the_graph() {
+        let metadata =
+            br#"{"packages":[{"name":"app"},{"name":"reqwest"},{"name":"openssl-sys"}]}"#;
+        assert!(metadata_has_dependency(metadata, "openssl_sys").unwrap());
+        assert!(metadata_has_dependency(metadata, "reqwest").unwrap());
+        assert!(!metadata_has_dependency(metadata, "openssl").unwrap());
+        assert!(metadata_has_dependency(b"not json", "app").is_err());
+    }
+
+    #[test]
+    fn plain_output_turns_off_colors_and_progress() {
+        let command = CargoCommand::new("metadata")
+            .plain_output()
+            .into_command_impure()
+            .unwrap();
+        assert_eq!(command.args(), ["metadata", "--color", "never"]);
+        assert!(command
+            .env_vars()
+            .iter()
+            .any(|(key, value)| key == "CARGO_TERM_PROGRESS_WHEN" && value == "never"));
+        let command = CargoCommand::new("metadata").into_command_impure().unwrap();
+        assert_eq!(command.args(), ["metadata"]);
+        assert!(command.env_vars().is_empty());
+    }
+
+    #[test]
+    fn lib_crate_types_come_from_the_right_package() {
+        let metadata = br#"{
+            "packages": [
+                {
+                    "manifest_path": "/work/tool/Cargo.toml",
+                    "targets": [{ "kind": ["bin"], "crate_types": ["bin"] }]
+                },
+                {
+                    "manifest_path": "/work/app/Cargo.toml",
+                    "targets": [
+                        { "kind": ["bin"], "crate_types": ["bin"] },
+                        { "kind": ["staticlib", "cdylib"], "crate_types": ["staticlib", "cdylib"] }
+                    ]
+                }
+            ]
+        }"#;
+        assert_eq!(
+            metadata_lib_crate_types(metadata, Path::new("/work/app/Cargo.toml")).unwrap(),
+            ["staticlib", "cdylib"]
+        );
+        assert!(
+            metadata_lib_crate_types(metadata, Path::new("/work/tool/Cargo.toml"))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            metadata_lib_crate_types(br#"{ "packages": [] }"#, Path::new("Cargo.toml"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn isolated_commands_only_pass_through_essential_env_vars() {
+        let keys = |command: &Invocation| {
+            command
+                .env_vars()
+                .iter()
+                .map(|(key, _)| key.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+        // Nothing else in the tests compiles anything, so this can't leak into
+        // them either.
+        std::env::set_var("RUSTFLAGS", "-Cstray-host-flag");
+        let command = CargoCommand::new("build")
+            .isolated()
+            .into_command_impure()
+            .unwrap();
+        std::env::remove_var("RUSTFLAGS");
+        assert!(command.is_pure());
+        assert!(keys(&command)
+            .iter()
+            .all(|key| ESSENTIAL_ENV_VARS.contains(&key.as_str())));
+        assert!(command
+            .env_vars()
+            .iter()
+            .all(|(key, value)| key != "RUSTFLAGS" && value != "-Cstray-host-flag"));
+        let env = crate::util::env::EnvBuilder::new().with_var("ANDROID_NDK_HOME", "/ndk");
+        let command = CargoCommand::new("build")
+            .with_essential_env_vars(&["PATH"])
+            .into_command_pure(&env)
+            .unwrap();
+        assert!(command.is_pure());
+        assert_eq!(keys(&command), ["PATH", "ANDROID_NDK_HOME"]);
+        assert!(!CargoCommand::new("build")
+            .into_command_impure()
+            .unwrap()
+            .is_pure());
+    }
+
+    #[test]
+    fn target_dirs_are_passed_as_a_flag() {
+        let command = CargoCommand::new("build")
+            .with_target_dir(Some(PathBuf::from("/tmp/target")))
+            .into_command_impure()
+            .unwrap();
+        assert_eq!(command.args()[1..], ["--target-dir", "/tmp/target"]);
+        // cargo prefers the flag over the env var, so an inherited
+        // `CARGO_TARGET_DIR` can't send the build anywhere else.
+        let env = crate::util::env::EnvBuilder::new().with_var("CARGO_TARGET_DIR", "/elsewhere");
+        let command = CargoCommand::new("build")
+            .with_target_dir(Some(PathBuf::from("/tmp/target")))
+            .into_command_pure(&env)
+            .unwrap();
+        assert_eq!(command.args()[1..], ["--target-dir", "/tmp/target"]);
+        assert!(command
+            .env_vars()
+            .iter()
+            .any(|(key, value)| key == "CARGO_TARGET_DIR" && value == "/elsewhere"));
+    }
+
+    #[test]
+    fn isolated_target_dirs_depend_on_the_target_and_features() {
+        let features = ["metal".to_owned(), "vulkan".to_owned()];
+        let reordered = ["vulkan".to_owned(), "metal".to_owned(), "metal".to_owned()];
+        assert_eq!(
+            target_dir_suffix("aarch64-apple-ios", &features),
+            target_dir_suffix("aarch64-apple-ios", &reordered)
+        );
+        assert_ne!(
+            target_dir_suffix("aarch64-apple-ios", &features),
+            target_dir_suffix("aarch64-apple-ios", &features[..1])
+        );
+        assert_ne!(
+            target_dir_suffix("aarch64-apple-ios", &features),
+            target_dir_suffix("x86_64-apple-ios", &features)
+        );
 
-    pub fn into_command_pure(self, env: &impl ExplicitEnv) -> bossy::Command {
-        self.into_command_inner(bossy::Command::pure("cargo").with_env_vars(env.explicit_env()))
+        let base = PathBuf::from("/tmp/target");
+        let command = CargoCommand::new("build")
+            .with_isolated_target_dir(Some(base.clone()))
+            .with_target(Some("aarch64-apple-ios"))
+            .with_features(Some(&features))
+            .into_command_impure()
+            .unwrap();
+        let target_dir = base.join(target_dir_suffix("aarch64-apple-ios", &features));
+        assert_eq!(
+            command.args()[1..3],
+            ["--target-dir".as_ref(), target_dir.as_os_str()]
+        );
+        let command = CargoCommand::new("build")
+            .with_isolated_target_dir(Some(base))
+            .with_target_dir(Some(PathBuf::from("/tmp/explicit")))
+            .into_command_impure()
+            .unwrap();
+        assert_eq!(command.args()[1..], ["--target-dir", "/tmp/explicit"]);
     }
 }
@@ -1,6 +1,8 @@
-use crate::{opts, util};
+use super::output::{self, Event};
+use crate::{config::global, opts, os, util};
 use colored::Colorize as _;
 use once_cell_regex::exports::once_cell::sync::Lazy;
+use serde::Serialize;
 use std::fmt::{Debug, Display};
 use structopt::{
     clap::{self, AppSettings},
@@ -41,6 +43,13 @@ pub struct GlobalFlags {
         parse(from_occurrences = opts::NoiseLevel::from_occurrences),
     )]
     pub noise_level: opts::NoiseLevel,
+    #[structopt(
+        short = "q",
+        long = "quiet",
+        help = "Hide the output of commands, unless they fail",
+        global = true
+    )]
+    pub quiet: bool,
     #[structopt(
         short = "y",
         long = "non-interactive",
@@ -49,6 +58,21 @@ pub struct GlobalFlags {
         parse(from_flag = opts::NonInteractive::from_bool),
     )]
     pub non_interactive: opts::NonInteractive,
+    #[structopt(
+        long = "format",
+        help = "Print events as JSON lines on stdout, with everything else on stderr",
+        global = true,
+        default_value = "human",
+        possible_values = opts::OutputFormat::NAMES,
+        parse(from_str = opts::OutputFormat::from_name),
+    )]
+    pub format: opts::OutputFormat,
+    #[structopt(
+        long = "offline",
+        help = "Don't touch the network; templates have to be in the template cache",
+        global = true
+    )]
+    pub offline: bool,
 }
 
 #[derive(Clone, Copy, Debug, StructOpt)]
@@ -71,6 +95,15 @@ pub struct ReinstallDeps {
     pub reinstall_deps: opts::ReinstallDeps,
 }
 
+#[derive(Clone, Debug, StructOpt)]
+pub struct Device {
+    #[structopt(
+        long = "device",
+        help = "ID or name of the device to use, as shown by the `devices` subcommand"
+    )]
+    pub device: Option<String>,
+}
+
 #[derive(Clone, Copy, Debug, StructOpt)]
 pub struct Profile {
     #[structopt(
@@ -81,9 +114,29 @@ pub struct Profile {
     pub profile: opts::Profile,
 }
 
+#[derive(Clone, Copy, Debug, StructOpt)]
+pub struct BuildNumber {
+    #[structopt(
+        long = "build-number",
+        help = "Build number to stamp into the app, instead of incrementing the last one"
+    )]
+    pub build_number: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug, StructOpt)]
+pub struct ProfileDuration {
+    #[structopt(
+        long = "duration",
+        help = "How many seconds to record for",
+        default_value = "10"
+    )]
+    pub duration: u32,
+}
+
 pub type TextWrapper = textwrap::Wrapper<'static, textwrap::NoHyphenation>;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum Label {
     Error,
     ActionRequest,
@@ -115,7 +168,7 @@ impl Label {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Report {
     label: Label,
     msg: String,
@@ -181,6 +234,103 @@ pub trait Reportable: Debug {
     fn report(&self) -> Report;
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub fix_hint: String,
+}
+
+/// Collects problems found while checking things over, so they can be shown
+/// as one list at the end instead of getting lost in the rest of the output.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    entries: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn push(
+        &mut self,
+        severity: Severity,
+        code: &'static str,
+        message: impl Display,
+        fix_hint: impl Display,
+    ) {
+        let diagnostic = Diagnostic {
+            severity,
+            code,
+            message: message.to_string(),
+            fix_hint: fix_hint.to_string(),
+        };
+        log::info!("recorded diagnostic {:?}", diagnostic);
+        output::emit(&Event::Diagnostic(&diagnostic));
+        self.entries.push(diagnostic);
+    }
+
+    pub fn warn(&mut self, code: &'static str, message: impl Display, fix_hint: impl Display) {
+        self.push(Severity::Warning, code, message, fix_hint)
+    }
+
+    pub fn error(&mut self, code: &'static str, message: impl Display, fix_hint: impl Display) {
+        self.push(Severity::Error, code, message, fix_hint)
+    }
+
+    pub fn entries(&self) -> &[Diagnostic] {
+        &self.entries
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.entries
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
+    }
+
+    /// Prints everything that was collected, errors first, and returns `true`
+    /// if there were any errors.
+    pub fn report(&self, wrapper: &TextWrapper) -> bool {
+        let (errors, warnings) = (self.count(Severity::Error), self.count(Severity::Warning));
+        if errors + warnings > 0 {
+            println!(
+                "Found {} issue{} ({} error{}, {} warning{}):",
+                errors + warnings,
+                if errors + warnings == 1 { "" } else { "s" },
+                errors,
+                if errors == 1 { "" } else { "s" },
+                warnings,
+                if warnings == 1 { "" } else { "s" },
+            );
+        }
+        for severity in &[Severity::Error, Severity::Warning] {
+            for diagnostic in self
+                .entries
+                .iter()
+                .filter(|diagnostic| diagnostic.severity == *severity)
+            {
+                let msg = format!("[{}] {}", diagnostic.code, diagnostic.message);
+                match severity {
+                    Severity::Error => Report::error(msg, &diagnostic.fix_hint),
+                    Severity::Warning => Report::action_request(msg, &diagnostic.fix_hint),
+                }
+                .print(wrapper);
+            }
+        }
+        errors > 0
+    }
+}
+
 pub trait Exec: Debug + StructOpt {
     type Report: Reportable;
 
@@ -230,6 +380,10 @@ impl Exit {
         match self {
             Self::Report(report) => {
                 report.print(&wrapper);
+                output::emit(&Event::Result {
+                    success: report.exit_code() == 0,
+                    report: Some(&report),
+                });
                 std::process::exit(report.label.exit_code().into())
             }
             Self::Clap(err) => err.exit(),
@@ -248,8 +402,62 @@ pub fn exec<E: Exec>(name: &str) {
     Exit::main(|wrapper| {
         let args = get_args(name);
         let input = E::from_iter_safe(&args).map_err(Exit::Clap)?;
-        init_logging(input.global_flags().noise_level);
+        let flags = input.global_flags();
+        init_logging(flags.noise_level);
+        output::init_global(flags.format)
+            .map_err(|err| Exit::Report(Report::error("Failed to set up JSON output", err)))?;
+        util::Runner::init_global(util::Verbosity::from_flags(flags.noise_level, flags.quiet));
+        util::set_git_offline(flags.offline);
+        os::install_signal_handlers();
+        global::init().map_err(Exit::report)?;
+        let (color, layer) = global::get().color();
+        if flags.format.json() {
+            log::info!("JSON output is on; disabling color");
+            colored::control::set_override(false);
+        } else if color != global::Color::Auto {
+            log::info!("color is set to {} by {}", color, layer);
+            colored::control::set_override(color == global::Color::Always);
+        } else if util::is_ci() {
+            log::info!("detected CI environment; disabling color");
+            colored::control::set_override(false);
+        }
         log::debug!("raw args: {:#?}", args);
-        input.exec(wrapper).map_err(Exit::report)
+        let result = input.exec(wrapper).map_err(Exit::report);
+        if result.is_ok() {
+            output::emit(&Event::Result {
+                success: true,
+                report: None,
+            });
+        }
+        if let Some(path) = util::shell_log_path() {
+            if let Err(err) = util::Runner::global().write_shell_log(&path) {
+                log::error!("failed to write shell log to {:?}: {}", path, err);
+            }
+        }
+        result
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagnostics_are_counted_by_severity() {
+        let wrapper = TextWrapper::with_splitter(80, textwrap::NoHyphenation);
+        let mut diagnostics = Diagnostics::new();
+        assert!(!diagnostics.report(&wrapper));
+        diagnostics.warn("old-ndk", "NDK r19 is pretty old", "Update it.");
+        assert!(!diagnostics.report(&wrapper));
+        diagnostics.error("no-sdk", "`ANDROID_SDK_ROOT` isn't set", "Set it.");
+        assert_eq!(diagnostics.count(Severity::Warning), 1);
+        assert_eq!(diagnostics.count(Severity::Error), 1);
+        let codes = diagnostics
+            .entries()
+            .iter()
+            .map(|diagnostic| diagnostic.code)
+            .collect::<Vec<_>>();
+        assert_eq!(codes, ["old-ndk", "no-sdk"]);
+        assert!(diagnostics.report(&wrapper));
+    }
+}
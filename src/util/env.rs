@@ -0,0 +1,156 @@
+//! Building up environment variables for the commands we spawn, without any
+//! lossy conversions along the way. `PATH`-like vars are split and joined
+//! with [`std::env::split_paths`] and [`std::env::join_paths`], so they use
+//! whatever separator the host does and survive non-UTF-8 entries.
+
+use crate::env::ExplicitEnv;
+use std::{
+    collections::HashSet,
+    ffi::{OsStr, OsString},
+    fmt::{self, Display},
+    path::PathBuf,
+};
+
+#[derive(Debug)]
+pub struct PathInvalid {
+    key: String,
+    cause: std::env::JoinPathsError,
+}
+
+impl Display for PathInvalid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Failed to add to `{}`, since an entry can't be joined: {}",
+            self.key, self.cause
+        )
+    }
+}
+
+impl std::error::Error for PathInvalid {}
+
+#[derive(Clone, Debug, Default)]
+pub struct EnvBuilder {
+    // In the order they were first set, which keeps debug logs stable.
+    vars: Vec<(String, OsString)>,
+}
+
+impl EnvBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Starts from the vars `env` would already set.
+    pub fn from_explicit(env: &impl ExplicitEnv) -> Self {
+        env.explicit_env()
+            .into_iter()
+            .fold(Self::new(), |builder, (key, value)| {
+                builder.with_var(key, value)
+            })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&OsStr> {
+        self.vars
+            .iter()
+            .find(|(existing, _)| existing == key)
+            .map(|(_, value)| value.as_os_str())
+    }
+
+    pub fn set_var(&mut self, key: impl Into<String>, value: impl Into<OsString>) {
+        let key = key.into();
+        let value = value.into();
+        match self.vars.iter_mut().find(|(existing, _)| *existing == key) {
+            Some((_, existing)) => *existing = value,
+            None => self.vars.push((key, value)),
+        }
+    }
+
+    pub fn with_var(mut self, key: impl Into<String>, value: impl Into<OsString>) -> Self {
+        self.set_var(key, value);
+        self
+    }
+
+    /// The entries of the `PATH`-like var `key`. If we haven't set it, this
+    /// starts from the value in our own environment.
+    pub fn paths(&self, key: &str) -> Vec<PathBuf> {
+        match self.get(key) {
+            Some(value) => std::env::split_paths(value).collect(),
+            None => std::env::var_os(key)
+                .map(|value| std::env::split_paths(&value).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn set_paths(
+        &mut self,
+        key: &str,
+        paths: impl IntoIterator<Item = PathBuf>,
+    ) -> Result<(), PathInvalid> {
+        let value = std::env::join_paths(paths).map_err(|cause| PathInvalid {
+            key: key.to_owned(),
+            cause,
+        })?;
+        self.set_var(key, value);
+        Ok(())
+    }
+
+    /// Puts `entries` at the front of `key`, in order.
+    pub fn prepend_paths(
+        mut self,
+        key: &str,
+        entries: impl IntoIterator<Item = impl Into<PathBuf>>,
+    ) -> Result<Self, PathInvalid> {
+        let paths = entries
+            .into_iter()
+            .map(Into::into)
+            .chain(self.paths(key))
+            .collect::<Vec<_>>();
+        self.set_paths(key, paths)?;
+        Ok(self)
+    }
+
+    pub fn prepend_path(self, key: &str, entry: impl Into<PathBuf>) -> Result<Self, PathInvalid> {
+        self.prepend_paths(key, std::iter::once(entry))
+    }
+
+    /// Puts `entries` at the end of `key`, in order.
+    pub fn append_paths(
+        mut self,
+        key: &str,
+        entries: impl IntoIterator<Item = impl Into<PathBuf>>,
+    ) -> Result<Self, PathInvalid> {
+        let mut paths = self.paths(key);
+        paths.extend(entries.into_iter().map(Into::into));
+        self.set_paths(key, paths)?;
+        Ok(self)
+    }
+
+    pub fn append_path(self, key: &str, entry: impl Into<PathBuf>) -> Result<Self, PathInvalid> {
+        self.append_paths(key, std::iter::once(entry))
+    }
+
+    /// Drops repeated entries from `key`, keeping the first occurrence, since
+    /// that's the one that wins anyway.
+    pub fn dedupe_paths(mut self, key: &str) -> Result<Self, PathInvalid> {
+        let mut seen = HashSet::new();
+        let paths = self
+            .paths(key)
+            .into_iter()
+            .filter(|path| seen.insert(path.clone()))
+            .collect::<Vec<_>>();
+        self.set_paths(key, paths)?;
+        Ok(self)
+    }
+
+    pub fn vars(&self) -> impl Iterator<Item = (&str, &OsStr)> {
+        self.vars
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_os_str()))
+    }
+}
+
+impl ExplicitEnv for EnvBuilder {
+    fn explicit_env(&self) -> Vec<(&str, &OsStr)> {
+        self.vars().collect()
+    }
+}
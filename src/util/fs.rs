@@ -0,0 +1,466 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use sha2::{Digest as _, Sha256};
+use std::{
+    fs,
+    io::{self, Read as _, Seek as _, SeekFrom},
+    path::{Component, Path, PathBuf},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+// 0 means no mode was set, since a dir nobody can enter wouldn't be useful.
+static DEFAULT_DIR_MODE: AtomicU32 = AtomicU32::new(0);
+
+/// Sets the permissions that [`create_dir_all`] gives to any directories it
+/// creates, regardless of the umask. `None` goes back to respecting it. This
+/// only has an effect on Unix.
+pub fn set_default_dir_mode(mode: Option<u32>) {
+    DEFAULT_DIR_MODE.store(mode.unwrap_or(0), Ordering::SeqCst);
+}
+
+pub fn default_dir_mode() -> Option<u32> {
+    Some(DEFAULT_DIR_MODE.load(Ordering::SeqCst)).filter(|mode| *mode != 0)
+}
+
+/// Like [`fs::create_dir_all`], but any directories that get created are given
+/// the mode set via [`set_default_dir_mode`]. Directories that already exist
+/// are left alone.
+pub fn create_dir_all(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    #[cfg(unix)]
+    {
+        if let Some(mode) = default_dir_mode() {
+            use std::os::unix::fs::{DirBuilderExt as _, PermissionsExt as _};
+            let missing = path
+                .ancestors()
+                .take_while(|ancestor| !ancestor.as_os_str().is_empty() && !ancestor.exists())
+                .map(ToOwned::to_owned)
+                .collect::<Vec<_>>();
+            fs::DirBuilder::new()
+                .recursive(true)
+                .mode(mode)
+                .create(path)?;
+            // The mode passed to `DirBuilder` still gets masked by the umask,
+            // so we have to set it explicitly to get exactly what was asked for.
+            for dir in missing {
+                log::debug!("setting mode of {:?} to {:o}", dir, mode);
+                fs::set_permissions(&dir, fs::Permissions::from_mode(mode))?;
+            }
+            return Ok(());
+        }
+    }
+    fs::create_dir_all(path)
+}
+
+/// Writes `contents` to `path`, unless the file at `path` already has exactly
+/// those contents. Returns `true` if a write happened.
+///
+/// Leaving unchanged files alone keeps their mtimes intact, which spares build
+/// systems (and git) from thinking anything happened.
+pub fn write_str_if_changed(
+    path: impl AsRef<Path>,
+    contents: impl AsRef<[u8]>,
+) -> io::Result<bool> {
+    let (path, contents) = (path.as_ref(), contents.as_ref());
+    match fs::read(path) {
+        Ok(existing) if existing == contents => {
+            log::info!("contents of {:?} are unchanged; skipping write", path);
+            Ok(false)
+        }
+        Ok(_) => fs::write(path, contents).map(|()| true),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            fs::write(path, contents).map(|()| true)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Writes `contents` to `path` and makes it executable, which generated
+/// scripts need to be. The file is written to a temporary sibling first and
+/// then renamed into place, so nothing ever sees a half-written (or
+/// non-executable) script.
+pub fn write_executable(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut tmp_name = path
+        .file_name()
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{:?} doesn't have a file name", path),
+            )
+        })?
+        .to_owned();
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+    fs::write(&tmp, contents)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt as _;
+        fs::set_permissions(&tmp, fs::Permissions::from_mode(0o755))?;
+    }
+    fs::rename(&tmp, path).map_err(|err| {
+        // Don't leave the temp file lying around, though this is best-effort
+        let _ = fs::remove_file(&tmp);
+        err
+    })
+}
+
+/// Returns the entries in `dir`, sorted lexically by file name.
+///
+/// `read_dir` order is platform-dependent, so anything that wants consistent
+/// output (or logs) across machines should walk using this instead.
+pub fn sorted_dir_entries(dir: impl AsRef<Path>) -> io::Result<Vec<fs::DirEntry>> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    Ok(entries)
+}
+
+/// Recursively copies the contents of `src` into `dest`, creating `dest` if it
+/// doesn't exist yet. Entries are always processed in sorted order.
+pub fn copy_dir_all(src: impl AsRef<Path>, dest: impl AsRef<Path>) -> io::Result<()> {
+    let (src, dest) = (src.as_ref(), dest.as_ref());
+    create_dir_all(dest)?;
+    for entry in sorted_dir_entries(src)? {
+        log::debug!("copying {:?} into {:?}", entry.path(), dest);
+        let dest = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_all(entry.path(), dest)?;
+        } else {
+            fs::copy(entry.path(), dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Appends any of `entries` that aren't already in the `.gitignore` at
+/// `gitignore`, creating it if needed. Existing lines are left untouched.
+pub fn ensure_gitignore_entries(gitignore: &Path, entries: &[&str]) -> io::Result<()> {
+    let mut contents = match fs::read_to_string(gitignore) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+    let mut missing = Vec::new();
+    for entry in entries {
+        let present = contents.lines().any(|line| line.trim() == entry.trim());
+        if !present && !missing.contains(entry) {
+            missing.push(*entry);
+        }
+    }
+    if missing.is_empty() {
+        return Ok(());
+    }
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    for entry in missing {
+        log::info!("adding {:?} to {:?}", entry, gitignore);
+        contents.push_str(entry);
+        contents.push('\n');
+    }
+    write_str_if_changed(gitignore, contents).map(|_| ())
+}
+
+fn hash_dir_inner(
+    root: &Path,
+    dir: &Path,
+    ignore: &Gitignore,
+    hasher: &mut Sha256,
+) -> io::Result<()> {
+    for entry in sorted_dir_entries(dir)? {
+        let path = entry.path();
+        let is_dir = entry.file_type()?.is_dir();
+        if ignore.matched(&path, is_dir).is_ignore() {
+            continue;
+        }
+        // Separators are normalized so the same tree hashes the same anywhere
+        let rel_path = path
+            .strip_prefix(root)
+            .expect("developer error: walked outside of hash root")
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(component) => Some(component.to_string_lossy()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        hasher.update(rel_path.as_bytes());
+        if is_dir {
+            hasher.update(b"/");
+            hash_dir_inner(root, &path, ignore, hasher)?;
+        } else {
+            let contents = fs::read(&path)?;
+            hasher.update(&(contents.len() as u64).to_le_bytes());
+            hasher.update(&contents);
+        }
+    }
+    Ok(())
+}
+
+/// Computes a fingerprint of the paths and contents in `dir`, skipping any
+/// paths matching the gitignore-style globs in `ignore`. The same tree always
+/// produces the same SHA-256 hex digest, regardless of platform.
+pub fn hash_dir(dir: &Path, ignore: &[&str]) -> io::Result<String> {
+    let invalid_glob = |err| io::Error::new(io::ErrorKind::InvalidInput, err);
+    let mut builder = GitignoreBuilder::new(dir);
+    for glob in ignore {
+        builder.add_line(None, glob).map_err(invalid_glob)?;
+    }
+    let ignore = builder.build().map_err(invalid_glob)?;
+    let mut hasher = Sha256::new();
+    hash_dir_inner(dir, dir, &ignore, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Returns the last `lines` lines of the file at `path`, reading backward from
+/// the end so that huge logs don't need to be loaded in full.
+pub fn tail_file(path: &Path, lines: usize) -> io::Result<String> {
+    const CHUNK_SIZE: u64 = 8 * 1024;
+    if lines == 0 {
+        return Ok(String::new());
+    }
+    let mut file = fs::File::open(path)?;
+    let mut pos = file.seek(SeekFrom::End(0))?;
+    let mut buf = Vec::new();
+    loop {
+        // A trailing newline ends the last line rather than starting a new one,
+        // so we need to see one more newline than that to have enough lines.
+        let body = match buf.split_last() {
+            Some((b'\n', body)) => body,
+            _ => &buf,
+        };
+        let newlines = body.iter().filter(|byte| **byte == b'\n').count();
+        if pos == 0 || newlines >= lines {
+            break;
+        }
+        let chunk_size = CHUNK_SIZE.min(pos);
+        pos -= chunk_size;
+        file.seek(SeekFrom::Start(pos))?;
+        let mut chunk = vec![0; chunk_size as usize];
+        file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&buf);
+        buf = chunk;
+    }
+    let text = String::from_utf8_lossy(&buf);
+    let mut tail = text.lines().rev().take(lines).collect::<Vec<_>>();
+    tail.reverse();
+    Ok(tail.join("\n"))
+}
+
+/// Restores the process's working directory when dropped.
+#[derive(Debug)]
+pub struct CwdGuard {
+    original: PathBuf,
+}
+
+impl CwdGuard {
+    /// Remembers the current working directory, without changing it.
+    pub fn new() -> io::Result<Self> {
+        std::env::current_dir().map(|original| Self { original })
+    }
+
+    /// Remembers the current working directory, and then changes it to `dir`.
+    pub fn enter(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let guard = Self::new()?;
+        std::env::set_current_dir(dir)?;
+        Ok(guard)
+    }
+
+    pub fn original(&self) -> &Path {
+        &self.original
+    }
+}
+
+impl Drop for CwdGuard {
+    fn drop(&mut self) {
+        if let Err(err) = std::env::set_current_dir(&self.original) {
+            log::error!(
+                "failed to restore working directory to {:?}: {}",
+                self.original,
+                err
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dir_hashes_change_with_contents_but_not_ignored_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "fn main() {}").unwrap();
+        let hash = hash_dir(dir.path(), &["target/"]).unwrap();
+        assert_eq!(hash.len(), 64);
+        assert!(hash.bytes().all(|byte| byte.is_ascii_hexdigit()));
+        fs::create_dir_all(dir.path().join("target")).unwrap();
+        fs::write(dir.path().join("target/junk"), "").unwrap();
+        assert_eq!(hash_dir(dir.path(), &["target/"]).unwrap(), hash);
+        fs::write(dir.path().join("src/lib.rs"), "fn main() { }").unwrap();
+        assert_ne!(hash_dir(dir.path(), &["target/"]).unwrap(), hash);
+    }
+
+    #[test]
+    fn identical_dirs_hash_the_same() {
+        // Creating things in a different order makes it a lot less likely
+        // that both get walked in the same order by accident.
+        let create = |names: &[&str]| {
+            let dir = tempfile::tempdir().unwrap();
+            for name in names {
+                let path = dir.path().join(name);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(&path, name).unwrap();
+            }
+            dir
+        };
+        let a = create(&["src/lib.rs", "src/bin/cli.rs", "Cargo.toml", "build.rs"]);
+        let b = create(&["build.rs", "Cargo.toml", "src/bin/cli.rs", "src/lib.rs"]);
+        assert_eq!(
+            hash_dir(a.path(), &[]).unwrap(),
+            hash_dir(b.path(), &[]).unwrap()
+        );
+        fs::write(b.path().join("README.md"), "").unwrap();
+        assert_ne!(
+            hash_dir(a.path(), &[]).unwrap(),
+            hash_dir(b.path(), &[]).unwrap()
+        );
+    }
+
+    #[test]
+    fn unchanged_contents_arent_rewritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        assert!(write_str_if_changed(&path, "a = 1\n").unwrap());
+        let modified = || fs::metadata(&path).unwrap().modified().unwrap();
+        let before = modified();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!write_str_if_changed(&path, "a = 1\n").unwrap());
+        assert_eq!(modified(), before);
+        assert!(write_str_if_changed(&path, "a = 2\n").unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a = 2\n");
+    }
+
+    #[test]
+    fn gitignore_entries_are_only_added_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let gitignore = dir.path().join(".gitignore");
+        fs::write(&gitignore, "/target\n  .cargo  ").unwrap();
+        ensure_gitignore_entries(&gitignore, &["/target", ".cargo", "/gen", "/gen"]).unwrap();
+        let expected = "/target\n  .cargo  \n/gen\n";
+        assert_eq!(fs::read_to_string(&gitignore).unwrap(), expected);
+        ensure_gitignore_entries(&gitignore, &["/gen"]).unwrap();
+        assert_eq!(fs::read_to_string(&gitignore).unwrap(), expected);
+
+        let missing = dir.path().join("app/.gitignore");
+        fs::create_dir_all(missing.parent().unwrap()).unwrap();
+        ensure_gitignore_entries(&missing, &["/gen"]).unwrap();
+        assert_eq!(fs::read_to_string(&missing).unwrap(), "/gen\n");
+    }
+
+    #[test]
+    fn dirs_are_walked_and_copied_in_sorted_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("b/nested")).unwrap();
+        for name in &["c", "a", "B"] {
+            fs::write(src.join(name), name).unwrap();
+        }
+        fs::write(src.join("b/nested/file"), "nested").unwrap();
+        let names = sorted_dir_entries(&src)
+            .unwrap()
+            .iter()
+            .map(|entry| entry.file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["B", "a", "b", "c"]);
+
+        let dest = dir.path().join("dest/copy");
+        copy_dir_all(&src, &dest).unwrap();
+        assert_eq!(fs::read_to_string(dest.join("a")).unwrap(), "a");
+        assert_eq!(
+            fs::read_to_string(dest.join("b/nested/file")).unwrap(),
+            "nested"
+        );
+    }
+
+    #[test]
+    fn tail_file_reads_back_across_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("build.log");
+        // Long enough to need several chunks
+        let lines = (0..5000).map(|n| format!("line {}", n)).collect::<Vec<_>>();
+        fs::write(&path, format!("{}\n", lines.join("\n"))).unwrap();
+        assert_eq!(
+            tail_file(&path, 3).unwrap(),
+            "line 4997\nline 4998\nline 4999"
+        );
+        assert_eq!(tail_file(&path, 2000).unwrap(), lines[3000..].join("\n"));
+        assert_eq!(tail_file(&path, 0).unwrap(), "");
+        // Asking for exactly as many lines as there are gets all of them
+        assert_eq!(tail_file(&path, 5000).unwrap(), lines.join("\n"));
+
+        fs::write(&path, "only\ntwo").unwrap();
+        assert_eq!(tail_file(&path, 10).unwrap(), "only\ntwo");
+        // The last line counts even without a trailing newline
+        fs::write(&path, lines.join("\n")).unwrap();
+        assert_eq!(
+            tail_file(&path, 3).unwrap(),
+            "line 4997\nline 4998\nline 4999"
+        );
+        assert_eq!(tail_file(&path, 5000).unwrap(), lines.join("\n"));
+        fs::write(&path, "").unwrap();
+        assert_eq!(tail_file(&path, 10).unwrap(), "");
+    }
+
+    // Actually changing the working dir would pull it out from under every
+    // other test running alongside this one, so this sticks to what can be
+    // checked without that.
+    #[test]
+    fn cwd_guard_leaves_the_cwd_alone_if_entering_fails() {
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(CwdGuard::new().unwrap().original(), cwd);
+        let dir = tempfile::tempdir().unwrap();
+        assert!(CwdGuard::enter(dir.path().join("missing")).is_err());
+        assert_eq!(std::env::current_dir().unwrap(), cwd);
+    }
+
+    #[test]
+    fn executables_are_written_whole_and_executable() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gradlew");
+        fs::write(&path, "old").unwrap();
+        write_executable(&path, b"#!/bin/sh\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "#!/bin/sh\n");
+        assert!(!dir.path().join("gradlew.tmp").exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+        assert_eq!(
+            write_executable(Path::new("/"), b"").unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn created_dirs_get_the_default_mode() {
+        use std::os::unix::fs::PermissionsExt as _;
+        let dir = tempfile::tempdir().unwrap();
+        let existing = dir.path().join("existing");
+        fs::create_dir(&existing).unwrap();
+        fs::set_permissions(&existing, fs::Permissions::from_mode(0o755)).unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        // This is global, so anything else creating dirs in the meantime gets
+        // this mode too, but that's harmless.
+        set_default_dir_mode(Some(0o750));
+        let result = create_dir_all(existing.join("a/b"));
+        set_default_dir_mode(None);
+        result.unwrap();
+        assert_eq!(mode(&existing), 0o755);
+        assert_eq!(mode(&existing.join("a")), 0o750);
+        assert_eq!(mode(&existing.join("a/b")), 0o750);
+        assert_eq!(default_dir_mode(), None);
+    }
+}
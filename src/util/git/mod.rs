@@ -1,7 +1,29 @@
 pub mod repo;
 pub mod submodule;
+pub mod template_cache;
 
-use std::{fs, io, path::Path};
+use super::{CommandError, Invocation, Output, PipeError, Runner};
+use std::{
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Keeps git from touching the network, so anything we'd normally clone or
+/// fetch has to come from the template cache (or already be checked out).
+pub fn set_git_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+/// Whether we're offline, either via `--offline` or cargo's own
+/// `CARGO_NET_OFFLINE`.
+pub fn git_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+        || std::env::var_os("CARGO_NET_OFFLINE").map_or(false, |value| value == *"true")
+}
 
 #[derive(Clone, Copy, Debug)]
 pub struct Git<'a> {
@@ -17,19 +39,17 @@ impl<'a> Git<'a> {
         self.root
     }
 
-    pub fn command(&self) -> bossy::Command {
-        bossy::Command::impure("git")
-            .with_arg("-C")
-            .with_arg(self.root)
+    pub fn command(&self) -> Invocation {
+        Invocation::impure("git").with_arg("-C").with_arg(self.root)
     }
 
-    pub fn command_parse(&self, arg_str: impl AsRef<str>) -> bossy::Command {
+    pub fn command_parse(&self, arg_str: impl AsRef<str>) -> Invocation {
         self.command().with_parsed_args(arg_str)
     }
 
-    pub fn init(&self) -> bossy::Result<()> {
+    pub fn init(&self) -> Result<(), CommandError> {
         if !self.root.join(".git").exists() {
-            self.command().with_arg("init").run_and_wait()?;
+            super::run(&self.command().with_arg("init"))?;
         }
         Ok(())
     }
@@ -52,15 +72,249 @@ impl<'a> Git<'a> {
         }
     }
 
-    pub fn user_name(&self) -> bossy::Result<bossy::Output> {
-        self.command()
-            .with_args(&["config", "user.name"])
-            .run_and_wait_for_output()
+    pub fn config_set(&self, key: &str, value: &str, global: bool) -> Result<(), CommandError> {
+        let mut command = self.command();
+        command.add_arg("config");
+        if global {
+            command.add_arg("--global");
+        }
+        super::run(command.add_args(&[key, value]))
+    }
+
+    /// Gets the value of `key`, or `None` if it isn't set.
+    pub fn config_get(&self, key: &str) -> Result<Option<String>, CommandError> {
+        match Runner::global().capture(&self.command().with_args(&["config", "--get", key])) {
+            Ok(output) => Ok(Some(
+                String::from_utf8_lossy(output.stdout()).trim().to_owned(),
+            )),
+            // `git config --get` exits with 1 when the key isn't set
+            Err(err) if err.exit_code() == Some(1) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn user_name(&self) -> Result<Output, CommandError> {
+        Runner::global().capture(&self.command().with_args(&["config", "user.name"]))
+    }
+
+    pub fn user_email(&self) -> Result<Output, CommandError> {
+        Runner::global().capture(&self.command().with_args(&["config", "user.email"]))
     }
+}
+
+#[derive(Debug)]
+pub enum ArchiveExtractError {
+    DestCreationFailed { dest: PathBuf, cause: io::Error },
+    PipeFailed(PipeError),
+    WorktreeAddFailed(CommandError),
+    CopyFailed { dest: PathBuf, cause: io::Error },
+    WorktreeRemoveFailed(CommandError),
+}
+
+impl Display for ArchiveExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DestCreationFailed { dest, cause } => {
+                write!(f, "Failed to create directory {:?}: {}", dest, cause)
+            }
+            Self::PipeFailed(err) => write!(f, "Failed to extract archive: {}", err),
+            Self::WorktreeAddFailed(err) => write!(f, "Failed to add worktree: {}", err),
+            Self::CopyFailed { dest, cause } => {
+                write!(f, "Failed to copy worktree to {:?}: {}", dest, cause)
+            }
+            Self::WorktreeRemoveFailed(err) => write!(f, "Failed to remove worktree: {}", err),
+        }
+    }
+}
+
+/// Extracts the tree at `treeish` in `repo` into `dest`, leaving out the
+/// repo's history (and `.git` entirely).
+pub fn git_archive_extract(
+    repo: &Path,
+    treeish: &str,
+    dest: &Path,
+) -> Result<(), ArchiveExtractError> {
+    fs::create_dir_all(dest).map_err(|cause| ArchiveExtractError::DestCreationFailed {
+        dest: dest.to_owned(),
+        cause,
+    })?;
+    let git = Git::new(repo);
+    // `command_present` goes through `command -v`, which outside of macOS
+    // usually only exists as a shell builtin.
+    if super::resolve_program("tar").is_some() {
+        super::pipe(
+            &git.command().with_args(&["archive", treeish]),
+            &Invocation::impure("tar")
+                .with_args(&["-x", "-C"])
+                .with_arg(dest),
+        )
+        .map_err(ArchiveExtractError::PipeFailed)?;
+    } else {
+        log::warn!("`tar` not found; falling back to copying from a worktree");
+        let worktree = super::temp_dir().join(format!("archive-worktree-{}", std::process::id()));
+        super::run(
+            &git.command()
+                .with_args(&["worktree", "add", "--detach"])
+                .with_arg(&worktree)
+                .with_arg(treeish),
+        )
+        .map_err(ArchiveExtractError::WorktreeAddFailed)?;
+        let copy_result = super::copy_dir_all(&worktree, dest)
+            // A worktree's `.git` is just a file pointing back at the repo
+            .and_then(|()| fs::remove_file(dest.join(".git")))
+            .map_err(|cause| ArchiveExtractError::CopyFailed {
+                dest: dest.to_owned(),
+                cause,
+            });
+        // We always want to clean up the worktree, but the copy error is more
+        // relevant if both fail.
+        let remove_result = super::run(
+            &git.command()
+                .with_args(&["worktree", "remove", "--force"])
+                .with_arg(&worktree),
+        )
+        .map_err(ArchiveExtractError::WorktreeRemoveFailed);
+        copy_result?;
+        remove_result?;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum WorktreeError {
+    AlreadyExists { path: PathBuf },
+    CommandFailed(CommandError),
+}
 
-    pub fn user_email(&self) -> bossy::Result<bossy::Output> {
-        self.command()
-            .with_args(&["config", "user.email"])
-            .run_and_wait_for_output()
+impl Display for WorktreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AlreadyExists { path } => write!(
+                f,
+                "Can't add a worktree at {:?}, since something's already there.",
+                path
+            ),
+            Self::CommandFailed(err) => write!(f, "`git worktree` command failed: {}", err),
+        }
+    }
+}
+
+/// Adds a worktree of `repo` at `path`, checking out `branch` if given (or a
+/// new branch named after `path` otherwise, which is what git does).
+pub fn git_worktree_add(
+    repo: &Path,
+    path: &Path,
+    branch: Option<&str>,
+) -> Result<(), WorktreeError> {
+    let already_exists = || WorktreeError::AlreadyExists {
+        path: path.to_owned(),
+    };
+    if path.exists() {
+        return Err(already_exists());
+    }
+    let mut command = Git::new(repo).command();
+    command.add_args(&["worktree", "add"]).add_arg(path);
+    if let Some(branch) = branch {
+        command.add_arg(branch);
+    }
+    super::run(&command).map_err(|err| {
+        if err.stderr_tail().contains("already exists") {
+            already_exists()
+        } else {
+            WorktreeError::CommandFailed(err)
+        }
+    })?;
+    Ok(())
+}
+
+pub fn git_worktree_remove(repo: &Path, path: &Path) -> Result<(), WorktreeError> {
+    super::run(
+        &Git::new(repo)
+            .command()
+            .with_args(&["worktree", "remove"])
+            .with_arg(path),
+    )
+    .map_err(WorktreeError::CommandFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git_installed() -> bool {
+        super::super::resolve_program("git").is_some()
+    }
+
+    // A repo with a single commit containing `templates/app.hbs`.
+    fn repo_with_commit(root: &Path) -> Git<'_> {
+        let git = Git::new(root);
+        git.init().unwrap();
+        git.config_set("user.name", "Shinji Ikari", false).unwrap();
+        git.config_set("user.email", "shinji@example.com", false)
+            .unwrap();
+        fs::create_dir_all(root.join("templates")).unwrap();
+        fs::write(root.join("templates/app.hbs"), "{{app.name}}").unwrap();
+        super::super::run(&git.command_parse("add templates")).unwrap();
+        super::super::run(&git.command_parse("commit -m init")).unwrap();
+        git
+    }
+
+    #[test]
+    fn config_values_can_be_set_and_read_back() {
+        if !git_installed() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let git = Git::new(dir.path());
+        git.init().unwrap();
+        assert_eq!(git.config_get("cargo-mobile.template").unwrap(), None);
+        git.config_set("cargo-mobile.template", "bevy", false)
+            .unwrap();
+        assert_eq!(
+            git.config_get("cargo-mobile.template").unwrap().as_deref(),
+            Some("bevy")
+        );
+    }
+
+    #[test]
+    fn archives_are_extracted_without_history() {
+        if !git_installed() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        repo_with_commit(&repo);
+        // Uncommitted changes don't make it in
+        fs::write(repo.join("templates/app.hbs"), "changed").unwrap();
+        let dest = dir.path().join("dest/templates");
+        git_archive_extract(&repo, "HEAD", &dest).unwrap();
+        assert_eq!(
+            fs::read_to_string(dest.join("templates/app.hbs")).unwrap(),
+            "{{app.name}}"
+        );
+        assert!(!dest.join(".git").exists());
+    }
+
+    #[test]
+    fn worktrees_refuse_to_clobber_existing_paths() {
+        if !git_installed() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        repo_with_commit(&repo);
+        let worktree = dir.path().join("feature");
+        git_worktree_add(&repo, &worktree, None).unwrap();
+        assert!(worktree.join("templates/app.hbs").is_file());
+        let err = git_worktree_add(&repo, &worktree, None).unwrap_err();
+        assert!(
+            matches!(err, WorktreeError::AlreadyExists { .. }),
+            "{}",
+            err
+        );
+        git_worktree_remove(&repo, &worktree).unwrap();
+        assert!(!worktree.exists());
     }
 }
@@ -1,23 +1,65 @@
-use crate::util::{self, Git};
+use crate::util::{self, git_offline, CommandError, Git, Runner};
+use once_cell_regex::regex;
 use std::{
-    ffi::OsStr,
     fmt::{self, Display},
     io,
     path::{Path, PathBuf},
 };
+use url::Url;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RepoSource {
+    Http(Url),
+    /// A `git://` URL, which is unauthenticated like HTTP but has its own
+    /// protocol.
+    Git(Url),
+    Ssh(String),
+    Local(PathBuf),
+}
+
+/// Classifies a repo location the same way `git clone` would.
+pub fn parse_repo_source(input: &str) -> RepoSource {
+    // Drive letters would otherwise look like a URL scheme or an scp-like
+    // host, i.e. `C:\templates\pack`.
+    if regex!(r"^[a-zA-Z]:").is_match(input) {
+        return RepoSource::Local(input.into());
+    }
+    if let Ok(url) = Url::parse(input) {
+        match url.scheme() {
+            "http" | "https" => return RepoSource::Http(url),
+            "git" => return RepoSource::Git(url),
+            "ssh" | "git+ssh" => return RepoSource::Ssh(input.to_owned()),
+            // `to_file_path` takes care of percent-decoding for us
+            "file" => {
+                if let Ok(path) = url.to_file_path() {
+                    return RepoSource::Local(path);
+                }
+            }
+            _ => (),
+        }
+    }
+    // Git treats anything with a colon before the first slash as an scp-like
+    // SSH location, i.e. `[user@]host:path`.
+    if regex!(r"^(?:[^@/:]+@)?[^@/:]+:").is_match(input) {
+        RepoSource::Ssh(input.to_owned())
+    } else {
+        RepoSource::Local(util::expand_home(input).unwrap_or_else(|_| input.into()))
+    }
+}
 
 #[derive(Debug)]
 pub enum Error {
     NoHomeDir(util::NoHomeDir),
-    FetchFailed(bossy::Error),
-    RevParseLocalFailed(bossy::Error),
-    RevParseRemoteFailed(bossy::Error),
-    LogFailed(bossy::Error),
+    FetchFailed(CommandError),
+    RevParseLocalFailed(CommandError),
+    RevParseRemoteFailed(CommandError),
+    LogFailed(CommandError),
     LogOutputInvalidUtf8(std::str::Utf8Error),
     ParentDirCreationFailed { path: PathBuf, cause: io::Error },
-    CloneFailed(bossy::Error),
-    ResetFailed(bossy::Error),
-    CleanFailed(bossy::Error),
+    CloneFailed(CommandError),
+    ResetFailed(CommandError),
+    CleanFailed(CommandError),
+    Offline { path: PathBuf },
 }
 
 impl Display for Error {
@@ -39,6 +81,11 @@ impl Display for Error {
             Self::CloneFailed(err) => write!(f, "Failed to clone repo: {}", err),
             Self::ResetFailed(err) => write!(f, "Failed to reset repo: {}", err),
             Self::CleanFailed(err) => write!(f, "Failed to clean repo: {}", err),
+            Self::Offline { path } => write!(
+                f,
+                "Can't clone or update the repo at {:?} while offline.",
+                path
+            ),
         }
     }
 }
@@ -82,18 +129,23 @@ impl Repo {
     pub fn status(&self) -> Result<Status, Error> {
         let status = if !self.path().is_dir() {
             Status::Stale
+        } else if git_offline() {
+            log::info!(
+                "assuming repo at {:?} is fresh, since we're offline",
+                self.path()
+            );
+            Status::Fresh
         } else {
             let git = self.git();
-            git.command_parse("fetch origin")
-                .run_and_wait()
+            let runner = Runner::global();
+            runner
+                .stream(&git.command_parse("fetch origin"))
                 .map_err(Error::FetchFailed)?;
-            let local = git
-                .command_parse("rev-parse HEAD")
-                .run_and_wait_for_output()
+            let local = runner
+                .capture(&git.command_parse("rev-parse HEAD"))
                 .map_err(Error::RevParseLocalFailed)?;
-            let remote = git
-                .command_parse("rev-parse @{u}")
-                .run_and_wait_for_output()
+            let remote = runner
+                .capture(&git.command_parse("rev-parse @{u}"))
                 .map_err(Error::RevParseRemoteFailed)?;
             if local.stdout() != remote.stdout() {
                 Status::Stale
@@ -105,10 +157,8 @@ impl Repo {
     }
 
     pub fn latest_message(&self) -> Result<String, Error> {
-        let output = self
-            .git()
-            .command_parse("log -1 --pretty=%B")
-            .run_and_wait_for_output()
+        let output = Runner::global()
+            .capture(&self.git().command_parse("log -1 --pretty=%B"))
             .map_err(Error::LogFailed)?;
         output
             .stdout_str()
@@ -116,8 +166,13 @@ impl Repo {
             .map_err(Error::LogOutputInvalidUtf8)
     }
 
-    pub fn update(&self, url: impl AsRef<OsStr>) -> Result<(), Error> {
+    pub fn update(&self, source: &str) -> Result<(), Error> {
         let path = self.path();
+        if git_offline() {
+            return Err(Error::Offline {
+                path: path.to_owned(),
+            });
+        }
         if !path.is_dir() {
             let parent = self
                 .path()
@@ -131,11 +186,17 @@ impl Repo {
                     }
                 })?;
             }
-            Git::new(parent)
-                .command_parse("clone --depth 1 --single-branch")
-                .with_arg(url)
-                .with_arg(path)
-                .run_and_wait()
+            let mut command = Git::new(parent).command_parse("clone --single-branch");
+            match parse_repo_source(source) {
+                RepoSource::Http(url) | RepoSource::Git(url) => {
+                    command.add_args(&["--depth", "1", url.as_str()])
+                }
+                RepoSource::Ssh(location) => command.add_args(&["--depth", "1", location.as_str()]),
+                // Shallow clones are ignored for plain local paths anyway
+                RepoSource::Local(local) => command.add_arg(local),
+            };
+            Runner::global()
+                .stream(command.add_arg(path))
                 .map_err(Error::CloneFailed)?;
         } else {
             println!(
@@ -147,19 +208,71 @@ impl Repo {
                 )
                 .display()
             );
-            self.git()
-                .command_parse("fetch --depth 1")
-                .run_and_wait()
+            let runner = Runner::global();
+            runner
+                .stream(&self.git().command_parse("fetch --depth 1"))
                 .map_err(Error::FetchFailed)?;
-            self.git()
-                .command_parse("reset --hard origin/master")
-                .run_and_wait()
+            util::run(&self.git().command_parse("reset --hard origin/master"))
                 .map_err(Error::ResetFailed)?;
-            self.git()
-                .command_parse("clean -dfx --exclude /target")
-                .run_and_wait()
+            util::run(&self.git().command_parse("clean -dfx --exclude /target"))
                 .map_err(Error::CleanFailed)?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repo_sources_are_classified_like_git_does() {
+        let url = |url: &str| Url::parse(url).unwrap();
+        let cases = vec![
+            (
+                "https://github.com/a/b.git",
+                RepoSource::Http(url("https://github.com/a/b.git")),
+            ),
+            (
+                "http://example.com/b",
+                RepoSource::Http(url("http://example.com/b")),
+            ),
+            (
+                "git://example.com/b.git",
+                RepoSource::Git(url("git://example.com/b.git")),
+            ),
+            (
+                "ssh://git@github.com/a/b.git",
+                RepoSource::Ssh("ssh://git@github.com/a/b.git".to_owned()),
+            ),
+            (
+                "git@github.com:a/b.git",
+                RepoSource::Ssh("git@github.com:a/b.git".to_owned()),
+            ),
+            (
+                "example.com:b.git",
+                RepoSource::Ssh("example.com:b.git".to_owned()),
+            ),
+            (
+                r"C:\templates\pack",
+                RepoSource::Local(r"C:\templates\pack".into()),
+            ),
+            ("d:/templates", RepoSource::Local("d:/templates".into())),
+            ("templates/pack", RepoSource::Local("templates/pack".into())),
+            // A colon after a slash doesn't make it scp-like
+            ("./a:b", RepoSource::Local("./a:b".into())),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse_repo_source(input), expected, "{}", input);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_urls_are_local_paths() {
+        assert_eq!(
+            parse_repo_source("file:///tmp/my%20pack"),
+            RepoSource::Local("/tmp/my pack".into())
+        );
+    }
+}
@@ -1,4 +1,9 @@
-use super::Git;
+use super::{
+    git_offline,
+    template_cache::{self, CacheMode},
+    Git,
+};
+use crate::util::{self, CommandError, Invocation, Runner};
 use once_cell_regex::regex;
 use serde::{Deserialize, Serialize};
 use std::{
@@ -13,9 +18,11 @@ pub enum Cause {
     IndexCheckFailed(io::Error),
     InitCheckFailed(io::Error),
     PathInvalidUtf8,
-    AddFailed(bossy::Error),
-    InitFailed(bossy::Error),
-    CheckoutFailed { commit: String, cause: bossy::Error },
+    CacheFailed(template_cache::Error),
+    CacheCloneFailed(CommandError),
+    AddFailed(CommandError),
+    InitFailed(CommandError),
+    CheckoutFailed { commit: String, cause: CommandError },
 }
 
 #[derive(Debug)]
@@ -47,6 +54,16 @@ impl Display for Error {
                 "Submodule path {:?} wasn't valid utf-8.",
                 self.submodule.path,
             ),
+            Cause::CacheFailed(err) => write!(
+                f,
+                "Failed to get submodule {:?} from the template cache: {}",
+                self.submodule.name().unwrap(), err,
+            ),
+            Cause::CacheCloneFailed(err) => write!(
+                f,
+                "Failed to clone submodule {:?} from the template cache into {:?}: {}",
+                self.submodule.name().unwrap(), self.submodule.path, err,
+            ),
             Cause::AddFailed(err) => write!(
                 f,
                 "Failed to add submodule {:?} with remote {:?} and path {:?}: {}",
@@ -94,10 +111,49 @@ impl Submodule {
         })
     }
 
+    pub fn remote(&self) -> &str {
+        &self.remote
+    }
+
     pub fn path(&self) -> &Path {
         &self.path
     }
 
+    // `git submodule add` adopts a repo that's already at the path instead
+    // of cloning, so cloning our cached copy there first means adding the
+    // submodule doesn't need the network.
+    fn clone_from_cache(&self, path: &Path) -> Result<(), Error> {
+        let error = |cause| Error {
+            submodule: self.clone(),
+            cause,
+        };
+        let cached = template_cache::cached_template_checkout(
+            util::Runner::global(),
+            &self.remote,
+            None,
+            CacheMode::Frozen,
+        )
+        .map_err(|cause| error(Cause::CacheFailed(cause)))?;
+        log::info!("cloning submodule from cache at {:?}", cached);
+        util::run(
+            &Invocation::impure("git")
+                .with_arg("clone")
+                .with_arg(&cached)
+                .with_arg(path),
+        )
+        .map_err(|cause| error(Cause::CacheCloneFailed(cause)))?;
+        // Otherwise, the submodule would point at the cache rather than the
+        // real remote.
+        util::run(&Git::new(path).command().with_args(&[
+            "remote",
+            "set-url",
+            "origin",
+            &self.remote,
+        ]))
+        .map_err(|cause| error(Cause::CacheCloneFailed(cause)))?;
+        Ok(())
+    }
+
     fn in_index(&self, git: Git<'_>, name: &str) -> io::Result<bool> {
         git.modules().map(|modules| {
             modules
@@ -128,10 +184,21 @@ impl Submodule {
                 submodule: self.clone(),
                 cause: Cause::PathInvalidUtf8,
             })?;
+            let path = git.root().join(&self.path);
+            let use_cache = git_offline() || template_cache::is_cached(&self.remote, None);
+            if use_cache && !path.exists() {
+                self.clone_from_cache(&path)?;
+            }
             log::info!("adding submodule: {:#?}", self);
-            git.command()
-                .with_args(&["submodule", "add", "--name", &name, &self.remote, path_str])
-                .run_and_wait()
+            Runner::global()
+                .stream(&git.command().with_args(&[
+                    "submodule",
+                    "add",
+                    "--name",
+                    &name,
+                    &self.remote,
+                    path_str,
+                ]))
                 .map_err(|cause| Error {
                     submodule: self.clone(),
                     cause: Cause::AddFailed(cause),
@@ -146,9 +213,11 @@ impl Submodule {
         };
         if !initialized {
             log::info!("initializing submodule: {:#?}", self);
-            git.command()
-                .with_parsed_args("submodule update --init --recursive")
-                .run_and_wait()
+            Runner::global()
+                .stream(
+                    &git.command()
+                        .with_parsed_args("submodule update --init --recursive"),
+                )
                 .map_err(|cause| Error {
                     submodule: self.clone(),
                     cause: Cause::InitFailed(cause),
@@ -163,17 +232,15 @@ impl Submodule {
                 commit,
                 path
             );
-            Git::new(&path)
-                .command()
-                .with_args(&["checkout", commit])
-                .run_and_wait()
-                .map_err(|cause| Error {
+            util::run(&Git::new(&path).command().with_args(&["checkout", commit])).map_err(
+                |cause| Error {
                     submodule: self.clone(),
                     cause: Cause::CheckoutFailed {
                         commit: commit.to_owned(),
                         cause,
                     },
-                })?;
+                },
+            )?;
         }
         Ok(())
     }
@@ -0,0 +1,263 @@
+use crate::util::{self, git_offline, CommandError, CommandRunner, Invocation};
+use fnv::FnvHasher;
+use std::{
+    fmt::{self, Display},
+    fs,
+    hash::Hasher as _,
+    io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CacheMode {
+    /// Only fetch if there's nothing cached yet.
+    Normal,
+    /// Always fetch, replacing whatever's cached.
+    Refresh,
+    /// Never fetch, which is what you want with `--frozen`/`--offline`.
+    Frozen,
+}
+
+impl CacheMode {
+    /// What to use when nothing asked for a particular mode, which depends on
+    /// whether we're offline.
+    pub fn current() -> Self {
+        if git_offline() {
+            Self::Frozen
+        } else {
+            Self::Normal
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    NoHomeDir(util::NoHomeDir),
+    NotCached { url: String, reference: String },
+    RemoveFailed { path: PathBuf, cause: io::Error },
+    DirCreationFailed { path: PathBuf, cause: io::Error },
+    CloneFailed(CommandError),
+    RenameFailed { path: PathBuf, cause: io::Error },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoHomeDir(err) => write!(f, "{}", err),
+            Self::NotCached { url, reference } => write!(
+                f,
+                "Templates from {:?} at {:?} aren't cached, and fetching is disabled; run `cargo mobile cache fetch` while online first.",
+                url, reference
+            ),
+            Self::RemoveFailed { path, cause } => {
+                write!(f, "Failed to remove cached templates at {:?}: {}", path, cause)
+            }
+            Self::DirCreationFailed { path, cause } => {
+                write!(f, "Failed to create directory {:?}: {}", path, cause)
+            }
+            Self::CloneFailed(err) => write!(f, "Failed to clone templates: {}", err),
+            Self::RenameFailed { path, cause } => write!(
+                f,
+                "Failed to move cloned templates into place at {:?}: {}",
+                path, cause
+            ),
+        }
+    }
+}
+
+/// `CARGO_MOBILE_TEMPLATE_CACHE` can point this somewhere else, i.e. at a
+/// cache that's checked in or restored by CI.
+pub fn template_cache_dir() -> Result<PathBuf, util::NoHomeDir> {
+    match std::env::var_os("CARGO_MOBILE_TEMPLATE_CACHE") {
+        Some(dir) if !dir.is_empty() => Ok(PathBuf::from(dir)),
+        _ => util::install_dir().map(|dir| dir.join("template-cache")),
+    }
+}
+
+// URLs are full of characters that don't belong in file names, so the entry
+// is named after a hash instead.
+fn cache_key(url: &str, reference: Option<&str>) -> String {
+    let mut hasher = FnvHasher::default();
+    hasher.write(url.as_bytes());
+    if let Some(reference) = reference {
+        hasher.write(&[0]);
+        hasher.write(reference.as_bytes());
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+/// Whether there's already a checkout of `reference` from `url` in the cache.
+pub fn is_cached(url: &str, reference: Option<&str>) -> bool {
+    template_cache_dir().map_or(false, |dir| dir.join(cache_key(url, reference)).is_dir())
+}
+
+/// Gets a checkout of `reference` (or the default branch, if that's `None`)
+/// from the template repo at `url`, only cloning it if it isn't already cached
+/// (or if `mode` says otherwise).
+pub fn cached_template_checkout(
+    runner: &impl CommandRunner,
+    url: &str,
+    reference: Option<&str>,
+    mode: CacheMode,
+) -> Result<PathBuf, Error> {
+    let cache_dir = template_cache_dir().map_err(Error::NoHomeDir)?;
+    checkout_in(runner, &cache_dir, url, reference, mode)
+}
+
+fn checkout_in(
+    runner: &impl CommandRunner,
+    cache_dir: &Path,
+    url: &str,
+    reference: Option<&str>,
+    mode: CacheMode,
+) -> Result<PathBuf, Error> {
+    let path = cache_dir.join(cache_key(url, reference));
+    let cached = path.is_dir();
+    match mode {
+        CacheMode::Normal | CacheMode::Frozen if cached => {
+            log::info!("using cached templates for {:?} at {:?}", url, path);
+            return Ok(path);
+        }
+        CacheMode::Frozen => {
+            return Err(Error::NotCached {
+                url: url.to_owned(),
+                reference: reference.unwrap_or("HEAD").to_owned(),
+            })
+        }
+        _ => (),
+    }
+    fs::create_dir_all(cache_dir).map_err(|cause| Error::DirCreationFailed {
+        path: cache_dir.to_owned(),
+        cause,
+    })?;
+    // Cloning somewhere else first means an interrupted clone never looks
+    // like a valid cache entry, and a failed refresh leaves the old one be.
+    let tmp = cache_dir.join(format!("{}.tmp", cache_key(url, reference)));
+    remove_if_present(&tmp)?;
+    let mut command = Invocation::impure("git");
+    command.add_args(&["clone", "--depth", "1"]);
+    if let Some(reference) = reference {
+        command.add_args(&["--branch", reference]);
+    }
+    runner
+        .stream(command.add_arg(url).add_arg(&tmp))
+        .map_err(Error::CloneFailed)?;
+    remove_if_present(&path)?;
+    fs::rename(&tmp, &path).map_err(|cause| Error::RenameFailed {
+        path: path.clone(),
+        cause,
+    })?;
+    Ok(path)
+}
+
+fn remove_if_present(path: &Path) -> Result<(), Error> {
+    if path.is_dir() {
+        fs::remove_dir_all(path).map_err(|cause| Error::RemoveFailed {
+            path: path.to_owned(),
+            cause,
+        })?;
+    }
+    Ok(())
+}
+
+pub fn clear_template_cache() -> Result<(), Error> {
+    let cache_dir = template_cache_dir().map_err(Error::NoHomeDir)?;
+    if cache_dir.is_dir() {
+        log::info!("clearing template cache at {:?}", cache_dir);
+        fs::remove_dir_all(&cache_dir).map_err(|cause| Error::RemoveFailed {
+            path: cache_dir,
+            cause,
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{Output, RecordingRunner};
+    use std::process::ExitStatus;
+
+    static URL: &str = "https://example.com/templates.git";
+
+    // Stands in for `git clone`, creating the checkout it was asked for if the
+    // clone was supposed to succeed.
+    #[derive(Default)]
+    struct FakeClone(RecordingRunner);
+
+    impl CommandRunner for FakeClone {
+        fn stream(&self, invocation: &Invocation) -> Result<ExitStatus, CommandError> {
+            let status = self.0.stream(invocation)?;
+            let dest = invocation.args().last().expect("clone had no args");
+            fs::create_dir_all(dest).unwrap();
+            Ok(status)
+        }
+
+        fn capture(&self, invocation: &Invocation) -> Result<Output, CommandError> {
+            self.0.capture(invocation)
+        }
+    }
+
+    #[test]
+    fn second_checkout_uses_the_cache() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let runner = FakeClone::default();
+        let first = checkout_in(
+            &runner,
+            cache_dir.path(),
+            URL,
+            Some("v1"),
+            CacheMode::Normal,
+        )
+        .unwrap();
+        assert!(first.is_dir());
+        let clones = runner.0.command_lines();
+        assert_eq!(clones.len(), 1);
+        assert!(clones[0].starts_with(&format!("git clone --depth 1 --branch v1 {} ", URL)));
+        let second = checkout_in(
+            &runner,
+            cache_dir.path(),
+            URL,
+            Some("v1"),
+            CacheMode::Normal,
+        )
+        .unwrap();
+        assert_eq!(first, second);
+        assert_eq!(runner.0.calls().len(), 1);
+    }
+
+    #[test]
+    fn frozen_without_a_cache_is_an_error() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let runner = FakeClone::default();
+        let result = checkout_in(&runner, cache_dir.path(), URL, None, CacheMode::Frozen);
+        assert!(matches!(result, Err(Error::NotCached { .. })));
+        assert!(runner.0.calls().is_empty());
+    }
+
+    #[test]
+    fn failed_refresh_keeps_the_old_checkout() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let path = cache_dir.path().join(cache_key(URL, None));
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("marker"), "old").unwrap();
+        let runner = FakeClone(RecordingRunner::new().with_failure(128, "fatal: no network\n"));
+        let result = checkout_in(&runner, cache_dir.path(), URL, None, CacheMode::Refresh);
+        assert!(matches!(result, Err(Error::CloneFailed(_))));
+        assert!(path.join("marker").is_file());
+    }
+
+    #[test]
+    fn refresh_replaces_the_old_checkout() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let path = cache_dir.path().join(cache_key(URL, None));
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("marker"), "old").unwrap();
+        let runner = FakeClone::default();
+        let refreshed =
+            checkout_in(&runner, cache_dir.path(), URL, None, CacheMode::Refresh).unwrap();
+        assert_eq!(refreshed, path);
+        assert!(!path.join("marker").exists());
+        assert_eq!(runner.0.calls().len(), 1);
+    }
+}
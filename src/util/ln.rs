@@ -1,5 +1,6 @@
 use std::{
     fmt::{self, Display},
+    fs, io,
     path::{Path, PathBuf},
 };
 
@@ -50,10 +51,30 @@ impl Display for TargetStyle {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Parents {
+    Require,
+    Create,
+}
+
+impl Display for Parents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Require => write!(f, "parent directories required"),
+            Self::Create => write!(f, "parent directory creation enabled"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ErrorCause {
     MissingFileName,
-    CommandFailed(bossy::Error),
+    CreateParentsFailed { dir: PathBuf, cause: io::Error },
+    WouldLoop { link: PathBuf },
+    ClobberFailed(io::Error),
+    SymlinkFailed(io::Error),
+    HardLinkFailed(io::Error),
+    RelativizeFailed(super::RelativizeError),
 }
 
 impl Display for ErrorCause {
@@ -62,7 +83,18 @@ impl Display for ErrorCause {
             Self::MissingFileName => {
                 write!(f, "Neither the source nor target contained a file name.",)
             }
-            Self::CommandFailed(err) => write!(f, "`ln` command failed: {}", err),
+            Self::CreateParentsFailed { dir, cause } => {
+                write!(f, "Failed to create parent directory {:?}: {}", dir, cause)
+            }
+            Self::WouldLoop { link } => write!(
+                f,
+                "The link at {:?} would point to one of its own ancestors, creating a loop.",
+                link
+            ),
+            Self::ClobberFailed(err) => write!(f, "Failed to remove existing file: {}", err),
+            Self::SymlinkFailed(err) => write!(f, "Failed to create symlink: {}", err),
+            Self::HardLinkFailed(err) => write!(f, "Failed to create hard link: {}", err),
+            Self::RelativizeFailed(err) => write!(f, "Failed to relativize source: {}", err),
         }
     }
 }
@@ -94,6 +126,7 @@ pub struct Call<'a> {
     source: &'a Path,
     target: &'a Path,
     target_style: TargetStyle,
+    parents: Parents,
 }
 
 impl<'a> Call<'a> {
@@ -124,54 +157,349 @@ impl<'a> Call<'a> {
             source,
             target,
             target_style,
+            parents: Parents::Require,
         })
     }
 
-    pub fn exec(self) -> Result<(), Error> {
-        let mut command = bossy::Command::impure("ln");
-        command.add_arg("-h"); // don't follow symlinks
-        if let LinkType::Symbolic = self.link_type {
-            command.add_arg("-s");
-        }
-        match self.force {
-            Clobber::FileOnly => {
-                command.add_arg("-f");
-            }
-            Clobber::FileOrDirectory => {
-                command.add_arg("-F");
-            }
-            _ => (),
-        }
-        // For the target to be interpreted as a directory, it must end in a
-        // trailing slash. We can't append one using `join` or `push`, since it
-        // would be interpreted as an absolute path and result in the target
-        // being replaced with it: https://github.com/rust-lang/rust/issues/16507
-        let target_override = if self.target_style == TargetStyle::Directory
-            && (!self.target.ends_with("/") || self.target.as_os_str().is_empty())
-        {
-            Some(format!("{}/", self.target.display()))
-        } else {
-            None
+    /// With [`Parents::Create`], any missing parent directories of the link
+    /// are created before linking. Otherwise, linking into a directory that
+    /// doesn't exist fails.
+    pub fn with_parents(mut self, parents: Parents) -> Self {
+        self.parents = parents;
+        self
+    }
+
+    fn create_parents(&self) -> Result<(), Error> {
+        // With a directory target, the link goes inside of the target, so
+        // that's the directory that needs to exist.
+        let dir = match self.target_style {
+            TargetStyle::File => self.target.parent(),
+            TargetStyle::Directory => Some(self.target),
         };
-        command.add_arg(self.source);
-        if let Some(target) = target_override.as_ref() {
-            command.add_arg(target);
-        } else {
-            command.add_arg(self.target);
+        if let Some(dir) = dir.filter(|dir| !dir.as_os_str().is_empty()) {
+            super::create_dir_all(dir).map_err(|cause| Error {
+                link_type: self.link_type,
+                force: self.force,
+                source: self.source.to_owned(),
+                target: self.target.to_owned(),
+                target_style: self.target_style,
+                cause: ErrorCause::CreateParentsFailed {
+                    dir: dir.to_owned(),
+                    cause,
+                },
+            })?;
+        }
+        Ok(())
+    }
+
+    fn link_path(&self) -> PathBuf {
+        match self.target_style {
+            TargetStyle::File => self.target.to_owned(),
+            TargetStyle::Directory => self.target.join(
+                self.source
+                    .file_name()
+                    .expect("developer error: directory-style link source had no file name"),
+            ),
         }
-        command.run_and_wait().map_err(|err| Error {
+    }
+
+    fn error(&self, cause: ErrorCause) -> Error {
+        Error {
             link_type: self.link_type,
             force: self.force,
             source: self.source.to_owned(),
-            target: if let Some(target) = target_override {
-                target.into()
-            } else {
-                self.target.to_owned()
-            },
+            target: self.target.to_owned(),
             target_style: self.target_style,
-            cause: ErrorCause::CommandFailed(err),
-        })?;
-        Ok(())
+            cause,
+        }
+    }
+
+    pub fn exec(self) -> Result<(), Error> {
+        let link = self.link_path();
+        if self.link_type == LinkType::Symbolic && is_circular_symlink(self.source, &link) {
+            return Err(self.error(ErrorCause::WouldLoop { link }));
+        }
+        if self.parents == Parents::Create {
+            self.create_parents()?;
+        }
+        clobber(&link, self.force).map_err(|err| self.error(ErrorCause::ClobberFailed(err)))?;
+        log::debug!(
+            "creating {} link at {:?} pointing to {:?}",
+            self.link_type,
+            link,
+            self.source
+        );
+        match self.link_type {
+            LinkType::Symbolic => symlink(self.source, &link)
+                .map_err(|err| self.error(ErrorCause::SymlinkFailed(err))),
+            LinkType::Hard => fs::hard_link(self.source, &link)
+                .map_err(|err| self.error(ErrorCause::HardLinkFailed(err))),
+        }
+    }
+}
+
+// Gets rid of anything at `link` that `force` allows us to replace. This
+// mirrors `ln -h`, so a symlink to a directory is treated as a file.
+fn clobber(link: &Path, force: Clobber) -> io::Result<()> {
+    match fs::symlink_metadata(link) {
+        Ok(metadata) => match force {
+            Clobber::Never => Ok(()),
+            Clobber::FileOnly if metadata.is_dir() => Ok(()),
+            // Like `ln -F`, this only works on empty directories.
+            Clobber::FileOrDirectory if metadata.is_dir() => fs::remove_dir(link),
+            _ => remove_link(link),
+        },
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(unix)]
+fn symlink_raw(source: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(source, link)
+}
+
+#[cfg(windows)]
+fn symlink_raw(source: &Path, link: &Path) -> io::Result<()> {
+    // Windows needs to know up front whether the link is to a directory, which
+    // we find out by resolving the source the same way the link will be.
+    let resolved = link
+        .parent()
+        .map(|parent| parent.join(source))
+        .unwrap_or_else(|| source.to_owned());
+    if resolved.is_dir() {
+        std::os::windows::fs::symlink_dir(source, link)
+    } else {
+        std::os::windows::fs::symlink_file(source, link)
+    }
+}
+
+#[cfg(unix)]
+fn remove_link(link: &Path) -> io::Result<()> {
+    fs::remove_file(link)
+}
+
+// A symlink to a directory is a directory as far as Windows is concerned, so it
+// has to be removed like one.
+#[cfg(windows)]
+fn remove_link(link: &Path) -> io::Result<()> {
+    use std::os::windows::fs::FileTypeExt as _;
+    if fs::symlink_metadata(link)?.file_type().is_symlink_dir() {
+        fs::remove_dir(link)
+    } else {
+        fs::remove_file(link)
+    }
+}
+
+#[cfg(unix)]
+fn symlink(source: &Path, link: &Path) -> io::Result<()> {
+    symlink_raw(source, link)
+}
+
+#[cfg(windows)]
+fn symlink(source: &Path, link: &Path) -> io::Result<()> {
+    // Without developer mode or admin rights, creating symlinks fails with
+    // `ERROR_PRIVILEGE_NOT_HELD`, so we settle for a copy.
+    const ERROR_PRIVILEGE_NOT_HELD: i32 = 1314;
+    match symlink_raw(source, link) {
+        Err(err) if err.raw_os_error() == Some(ERROR_PRIVILEGE_NOT_HELD) => {
+            let resolved = link
+                .parent()
+                .map(|parent| parent.join(source))
+                .unwrap_or_else(|| source.to_owned());
+            log::warn!(
+                "not allowed to create symlinks, so {:?} will be copied to {:?} instead",
+                resolved,
+                link
+            );
+            if resolved.is_dir() {
+                super::copy_dir_all(&resolved, link)
+            } else {
+                fs::copy(&resolved, link).map(|_| ())
+            }
+        }
+        result => result,
+    }
+}
+
+/// Checks if a symlink at `link` pointing to `source` would point to itself or
+/// one of its ancestors, which would send anything walking through it into an
+/// infinite loop. Like the link itself, a relative `source` is resolved
+/// relative to the directory containing `link`.
+pub fn is_circular_symlink(source: &Path, link: &Path) -> bool {
+    let absolute = |path: &Path| {
+        if path.is_absolute() {
+            Some(path.to_owned())
+        } else {
+            std::env::current_dir().ok().map(|cwd| cwd.join(path))
+        }
+    };
+    let link = if let Some(link) = absolute(link) {
+        super::normalize_lexically(&link)
+    } else {
+        log::warn!(
+            "couldn't get working directory to check {:?} for loops",
+            link
+        );
+        return false;
+    };
+    let source = super::normalize_lexically(
+        &link
+            .parent()
+            .map(|parent| parent.join(source))
+            .unwrap_or_else(|| source.to_owned()),
+    );
+    link.starts_with(&source)
+}
+
+/// Checks if symlinks can be created in `dir`, which isn't the case on
+/// filesystems like FAT and exFAT. The probe link is always cleaned up.
+pub fn filesystem_supports_symlinks(dir: impl AsRef<Path>) -> bool {
+    let probe = dir.as_ref().join(format!(
+        ".cargo-mobile-symlink-probe-{}",
+        std::process::id()
+    ));
+    let target = Path::new("cargo-mobile-symlink-probe-target");
+    let supported = symlink_raw(target, &probe)
+        .and_then(|()| std::fs::read_link(&probe))
+        .map(|read| read == target)
+        .unwrap_or_else(|err| {
+            log::info!("failed to create symlink probe at {:?}: {}", probe, err);
+            false
+        });
+    if let Err(err) = std::fs::remove_file(&probe) {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            log::error!("failed to remove symlink probe at {:?}: {}", probe, err);
+        }
+    }
+    log::info!(
+        "filesystem at {:?} {} symlinks",
+        dir.as_ref(),
+        if supported {
+            "supports"
+        } else {
+            "doesn't support"
+        }
+    );
+    supported
+}
+
+#[derive(Debug)]
+pub enum LinkDirError {
+    SymlinkFailed(Error),
+    DestWithinSrc {
+        src: PathBuf,
+        dest: PathBuf,
+    },
+    CopyFailed {
+        src: PathBuf,
+        dest: PathBuf,
+        cause: io::Error,
+    },
+}
+
+impl Display for LinkDirError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SymlinkFailed(err) => write!(f, "{}", err),
+            Self::DestWithinSrc { src, dest } => write!(
+                f,
+                "Refusing to copy {:?} to {:?}, since that's inside of it.",
+                src, dest
+            ),
+            Self::CopyFailed { src, dest, cause } => {
+                write!(
+                    f,
+                    "Failed to copy or link {:?} to {:?}: {}",
+                    src, dest, cause
+                )
+            }
+        }
+    }
+}
+
+/// Symlinks the dir at `src` into `dest`, i.e. so that the asset dir shows up
+/// in a generated project. If the filesystem at `dest` doesn't support
+/// symlinks, the files get hardlinked instead, which still avoids copying them
+/// as long as both are on the same filesystem. If `hard_copy` is set, the dir
+/// is synced into `dest`, tracked by the sync manifest at `manifest_path`;
+/// `force_sync` copies everything regardless of what that manifest says.
+pub fn link_dir_or_copy(
+    src: &Path,
+    dest: &Path,
+    hard_copy: bool,
+    manifest_path: &Path,
+    force_sync: bool,
+) -> Result<(), LinkDirError> {
+    let symlinks_supported = !hard_copy && filesystem_supports_symlinks(dest);
+    link_dir_with(
+        src,
+        dest,
+        hard_copy,
+        symlinks_supported,
+        manifest_path,
+        force_sync,
+    )
+}
+
+fn link_dir_with(
+    src: &Path,
+    dest: &Path,
+    hard_copy: bool,
+    symlinks_supported: bool,
+    manifest_path: &Path,
+    force_sync: bool,
+) -> Result<(), LinkDirError> {
+    if !hard_copy && symlinks_supported {
+        return force_symlink_relative(src, dest, TargetStyle::Directory, Parents::Require)
+            .map_err(LinkDirError::SymlinkFailed);
+    }
+    let dest = dest.join(
+        src.file_name()
+            .expect("developer error: asset dir had no file name"),
+    );
+    let copy_failed = |cause| LinkDirError::CopyFailed {
+        src: src.to_owned(),
+        dest: dest.clone(),
+        cause,
+    };
+    // A link left over from running in symlink mode would have us copying
+    // every file in `src` onto itself, which truncates them.
+    match fs::symlink_metadata(&dest) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            log::info!("removing asset dir symlink at {:?} before copying", dest);
+            remove_link(&dest).map_err(copy_failed)?;
+        }
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(copy_failed(err)),
+        _ => (),
+    }
+    let canonical = |path: &Path| super::canonicalize_existing(path).ok();
+    if let (Some(canonical_src), Some(canonical_dest)) = (canonical(src), canonical(&dest)) {
+        if canonical_dest.starts_with(&canonical_src) {
+            return Err(LinkDirError::DestWithinSrc {
+                src: src.to_owned(),
+                dest,
+            });
+        }
+    }
+    if hard_copy {
+        log::info!("hard copy mode is on, so copying {:?} to {:?}", src, dest);
+        super::sync_dir(src, &dest, manifest_path, force_sync)
+            .map(|_| ())
+            .map_err(copy_failed)
+    } else {
+        log::warn!(
+            "symlinks aren't supported here, so {:?} will be hardlinked into {:?} instead",
+            src,
+            dest
+        );
+        // Relinking everything is cheap, and this way nothing that was removed
+        // from `src` is left behind.
+        match fs::remove_dir_all(&dest) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(copy_failed(err)),
+            _ => (),
+        }
+        link_tree(src, &dest, LinkStrategy::Hardlink).map_err(copy_failed)
     }
 }
 
@@ -179,6 +507,7 @@ pub fn force_symlink(
     source: impl AsRef<Path>,
     target: impl AsRef<Path>,
     target_style: TargetStyle,
+    parents: Parents,
 ) -> Result<(), Error> {
     Call::new(
         LinkType::Symbolic,
@@ -187,6 +516,7 @@ pub fn force_symlink(
         target.as_ref(),
         target_style,
     )?
+    .with_parents(parents)
     .exec()
 }
 
@@ -194,12 +524,25 @@ pub fn force_symlink_relative(
     abs_source: impl AsRef<Path>,
     abs_target: impl AsRef<Path>,
     target_style: TargetStyle,
+    parents: Parents,
 ) -> Result<(), Error> {
     let (abs_source, abs_target) = (abs_source.as_ref(), abs_target.as_ref());
-    let rel_source = super::relativize_path(abs_source, abs_target);
+    let rel_source = super::relativize_path(abs_source, abs_target).map_err(|cause| Error {
+        link_type: LinkType::Symbolic,
+        force: Clobber::FileOnly,
+        source: abs_source.to_owned(),
+        target: abs_target.to_owned(),
+        target_style,
+        cause: ErrorCause::RelativizeFailed(cause),
+    })?;
     if target_style == TargetStyle::Directory && rel_source.file_name().is_none() {
         if let Some(file_name) = abs_source.file_name() {
-            force_symlink(rel_source, abs_target.join(file_name), TargetStyle::File)
+            force_symlink(
+                rel_source,
+                abs_target.join(file_name),
+                TargetStyle::File,
+                parents,
+            )
         } else {
             Err(Error {
                 link_type: LinkType::Symbolic,
@@ -211,6 +554,380 @@ pub fn force_symlink_relative(
             })
         }
     } else {
-        force_symlink(rel_source, abs_target, target_style)
+        force_symlink(rel_source, abs_target, target_style, parents)
+    }
+}
+
+fn force_symlink_native(source: &Path, target: &Path) -> io::Result<()> {
+    // Same clobbering behavior as `ln -f`: files and links get replaced, but
+    // directories are left alone (and will make the symlink call fail).
+    match fs::symlink_metadata(target) {
+        Ok(metadata) if !metadata.is_dir() => remove_link(target)?,
+        Ok(_) => (),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+        Err(err) => return Err(err),
+    }
+    symlink(source, target)
+}
+
+// Per-link logs get overwhelming when creating links in bulk, so this just
+// tallies things up for a single summary at the end.
+#[derive(Debug, Default)]
+struct LinkSummary {
+    root: Option<PathBuf>,
+    created: usize,
+    failed: usize,
+}
+
+impl LinkSummary {
+    fn add(&mut self, target: &Path, created: bool) {
+        let parent = target.parent().unwrap_or(target);
+        match &mut self.root {
+            Some(root) => while !parent.starts_with(root.as_path()) && root.pop() {},
+            None => self.root = Some(parent.to_owned()),
+        }
+        if created {
+            self.created += 1;
+        } else {
+            self.failed += 1;
+        }
+    }
+
+    fn log(&self) {
+        if let Some(root) = &self.root {
+            log::info!(
+                "created {} symlinks under {:?} ({} failed)",
+                self.created,
+                root,
+                self.failed
+            );
+        }
+    }
+}
+
+/// Creates many relative symlinks without spawning an `ln` for each one. Each
+/// pair is `(abs_source, abs_target)`, where `abs_target` is the path of the
+/// link itself; results are returned in the same order as `links`.
+///
+/// Only a summary is logged at the info level; individual links are logged at
+/// the trace level.
+pub fn batch_force_symlink_relative(links: &[(PathBuf, PathBuf)]) -> Vec<Result<(), Error>> {
+    let mut summary = LinkSummary::default();
+    let results = links
+        .iter()
+        .map(|(abs_source, abs_target)| {
+            let parent = abs_target
+                .parent()
+                .expect("developer error: symlink target was at root");
+            let error = |source: &Path, cause| Error {
+                link_type: LinkType::Symbolic,
+                force: Clobber::FileOnly,
+                source: source.to_owned(),
+                target: abs_target.to_owned(),
+                target_style: TargetStyle::File,
+                cause,
+            };
+            let result = super::relativize_path(abs_source, parent)
+                .map_err(|cause| error(abs_source, ErrorCause::RelativizeFailed(cause)))
+                .and_then(|rel_source| {
+                    log::trace!("symlinking {:?} to {:?}", abs_target, rel_source);
+                    force_symlink_native(&rel_source, abs_target)
+                        .map_err(|cause| error(&rel_source, ErrorCause::SymlinkFailed(cause)))
+                });
+            summary.add(abs_target, result.is_ok());
+            result
+        })
+        .collect();
+    summary.log();
+    results
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LinkStrategy {
+    Symlink,
+    Hardlink,
+    Copy,
+}
+
+impl Display for LinkStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Symlink => write!(f, "symlink"),
+            Self::Hardlink => write!(f, "hardlink"),
+            Self::Copy => write!(f, "copy"),
+        }
+    }
+}
+
+#[cfg(unix)]
+const CROSS_DEVICE: i32 = libc::EXDEV;
+// `ERROR_NOT_SAME_DEVICE`
+#[cfg(windows)]
+const CROSS_DEVICE: i32 = 17;
+
+fn link_file(src: &Path, dest: &Path, strategy: LinkStrategy) -> io::Result<()> {
+    // Whatever was there could be a hardlink to `src` from an earlier run, so
+    // it needs to go rather than be copied over, which would truncate `src`.
+    match fs::symlink_metadata(dest) {
+        Ok(metadata) if !metadata.is_dir() => remove_link(dest)?,
+        Ok(_) => (),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+        Err(err) => return Err(err),
+    }
+    match strategy {
+        LinkStrategy::Symlink => symlink(src, dest),
+        LinkStrategy::Hardlink => {
+            match fs::hard_link(src, dest) {
+                // Hardlinks can't cross filesystems
+                Err(err) if err.raw_os_error() == Some(CROSS_DEVICE) => {
+                    log::debug!(
+                        "can't hardlink {:?} to {:?} across filesystems; copying instead",
+                        src,
+                        dest
+                    );
+                    fs::copy(src, dest).map(|_| ())
+                }
+                result => result,
+            }
+        }
+        LinkStrategy::Copy => fs::copy(src, dest).map(|_| ()),
+    }
+}
+
+/// Recreates the tree at `src` in `dest`, with each file linked (or copied)
+/// according to `strategy`. Directories are always created as real
+/// directories, which keeps tools that refuse to follow symlinked dirs happy.
+pub fn link_tree(src: &Path, dest: &Path, strategy: LinkStrategy) -> io::Result<()> {
+    // Symlinks need absolute sources, since they're resolved relative to the
+    // link's own location.
+    let src = src.canonicalize()?;
+    log::info!("linking tree {:?} into {:?} ({})", src, dest, strategy);
+    link_tree_inner(&src, dest, strategy)
+}
+
+fn link_tree_inner(src: &Path, dest: &Path, strategy: LinkStrategy) -> io::Result<()> {
+    super::create_dir_all(dest)?;
+    for entry in super::sorted_dir_entries(src)? {
+        let dest = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            link_tree_inner(&entry.path(), &dest, strategy)?;
+        } else {
+            link_file(&entry.path(), &dest, strategy)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asset_dir(root: &Path) -> PathBuf {
+        let src = root.join("assets");
+        fs::create_dir_all(src.join("sounds")).unwrap();
+        fs::write(src.join("icon.png"), "icon").unwrap();
+        fs::write(src.join("sounds/beep.ogg"), "beep").unwrap();
+        src
+    }
+
+    #[test]
+    fn hard_copy_syncs_the_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = asset_dir(dir.path());
+        let dest = dir.path().join("gen");
+        fs::create_dir_all(&dest).unwrap();
+        let manifest_path = dest.join(".asset-sync");
+        link_dir_or_copy(&src, &dest, true, &manifest_path, false).unwrap();
+        let copied = dest.join("assets");
+        let file_type = fs::symlink_metadata(&copied).unwrap().file_type();
+        assert!(file_type.is_dir() && !file_type.is_symlink());
+        assert_eq!(
+            fs::read_to_string(copied.join("sounds/beep.ogg")).unwrap(),
+            "beep"
+        );
+        assert!(manifest_path.exists());
+    }
+
+    #[test]
+    fn dirs_are_hardlinked_without_symlink_support() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = asset_dir(dir.path());
+        let dest = dir.path().join("gen");
+        fs::create_dir_all(dest.join("assets")).unwrap();
+        fs::write(dest.join("assets/removed.png"), "").unwrap();
+        let manifest_path = dest.join(".asset-sync");
+        link_dir_with(&src, &dest, false, false, &manifest_path, false).unwrap();
+        let linked = dest.join("assets");
+        assert_eq!(fs::read_to_string(linked.join("icon.png")).unwrap(), "icon");
+        assert!(!linked.join("removed.png").exists());
+        assert!(!manifest_path.exists());
+    }
+
+    #[test]
+    fn trees_are_linked_with_each_strategy() {
+        for strategy in [
+            LinkStrategy::Symlink,
+            LinkStrategy::Hardlink,
+            LinkStrategy::Copy,
+        ] {
+            let dir = tempfile::tempdir().unwrap();
+            let src = asset_dir(dir.path());
+            let dest = dir.path().join("linked");
+            link_tree(&src, &dest, strategy).unwrap();
+            // Dirs are always real dirs
+            assert!(!fs::symlink_metadata(dest.join("sounds"))
+                .unwrap()
+                .file_type()
+                .is_symlink());
+            let file = dest.join("sounds/beep.ogg");
+            assert_eq!(fs::read_to_string(&file).unwrap(), "beep", "{}", strategy);
+            let file_type = fs::symlink_metadata(&file).unwrap().file_type();
+            assert_eq!(file_type.is_symlink(), strategy == LinkStrategy::Symlink);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hardlinks_share_an_inode() {
+        use std::os::unix::fs::MetadataExt as _;
+        let dir = tempfile::tempdir().unwrap();
+        let src = asset_dir(dir.path());
+        let dest = dir.path().join("linked");
+        link_tree(&src, &dest, LinkStrategy::Hardlink).unwrap();
+        let inode = |path: PathBuf| fs::metadata(path).unwrap().ino();
+        assert_eq!(inode(src.join("icon.png")), inode(dest.join("icon.png")));
+        // Copying over the hardlinks replaces them instead of writing through
+        link_tree(&src, &dest, LinkStrategy::Copy).unwrap();
+        assert_ne!(inode(src.join("icon.png")), inode(dest.join("icon.png")));
+        assert_eq!(fs::read_to_string(src.join("icon.png")).unwrap(), "icon");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dirs_are_symlinked_when_supported() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = asset_dir(dir.path());
+        let dest = dir.path().join("gen");
+        fs::create_dir_all(&dest).unwrap();
+        let manifest_path = dest.join(".asset-sync");
+        link_dir_or_copy(&src, &dest, false, &manifest_path, false).unwrap();
+        let link = dest.join("assets");
+        assert_eq!(fs::read_link(&link).unwrap(), Path::new("../assets"));
+        assert_eq!(fs::read_to_string(link.join("icon.png")).unwrap(), "icon");
+        assert!(!manifest_path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn batched_symlinks_are_relative_and_fail_independently() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let src = asset_dir(&root);
+        let dest = root.join("gen/libs");
+        fs::create_dir_all(dest.join("in-the-way")).unwrap();
+        fs::write(dest.join("beep.ogg"), "stale").unwrap();
+        let results = batch_force_symlink_relative(&[
+            (src.join("icon.png"), dest.join("icon.png")),
+            (src.join("icon.png"), dest.join("in-the-way")),
+            (src.join("sounds/beep.ogg"), dest.join("beep.ogg")),
+        ]);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        assert!(results[2].is_ok());
+        assert_eq!(
+            fs::read_link(dest.join("icon.png")).unwrap(),
+            Path::new("../../assets/icon.png")
+        );
+        assert_eq!(fs::read_to_string(dest.join("beep.ogg")).unwrap(), "beep");
+        assert!(dest.join("in-the-way").is_dir());
+    }
+
+    #[test]
+    fn link_summaries_are_rooted_at_the_common_dir() {
+        let jni_libs = Path::new("/gen/android/app/jniLibs");
+        let mut summary = LinkSummary::default();
+        summary.add(&jni_libs.join("arm64-v8a/libapp.so"), true);
+        summary.add(&jni_libs.join("x86_64/libapp.so"), true);
+        summary.add(&jni_libs.join("x86/libapp.so"), false);
+        assert_eq!(summary.root.as_deref(), Some(jni_libs));
+        assert_eq!((summary.created, summary.failed), (2, 1));
+        summary.add(Path::new("/assets/icon.png"), true);
+        assert_eq!(summary.root.as_deref(), Some(Path::new("/")));
+    }
+
+    #[test]
+    fn links_into_their_own_source_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let at = |path: &str| dir.path().join(path);
+        assert!(is_circular_symlink(&at("app"), &at("app/gen/app")));
+        assert!(is_circular_symlink("../..".as_ref(), &at("app/gen/link")));
+        assert!(!is_circular_symlink("../a".as_ref(), &at("gen/a")));
+        assert!(!is_circular_symlink(&at("app"), &at("apple/app")));
+
+        let app = at("app");
+        fs::create_dir_all(app.join("gen")).unwrap();
+        let err = force_symlink(
+            &app,
+            app.join("gen"),
+            TargetStyle::Directory,
+            Parents::Create,
+        )
+        .unwrap_err();
+        assert!(matches!(err.cause, ErrorCause::WouldLoop { .. }));
+        assert!(!app.join("gen/app").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn missing_parents_are_only_created_if_asked_for() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = asset_dir(dir.path());
+        let target = dir.path().join("gen/apple");
+        let err = force_symlink_relative(&src, &target, TargetStyle::Directory, Parents::Require)
+            .unwrap_err();
+        assert!(matches!(err.cause, ErrorCause::SymlinkFailed(_)));
+        assert!(!target.exists());
+        force_symlink_relative(&src, &target, TargetStyle::Directory, Parents::Create).unwrap();
+        assert_eq!(
+            fs::read_link(target.join("assets")).unwrap(),
+            Path::new("../../assets")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn switching_to_hard_copy_replaces_the_old_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = asset_dir(dir.path());
+        let dest = dir.path().join("gen");
+        fs::create_dir_all(&dest).unwrap();
+        let manifest_path = dest.join(".asset-sync");
+        link_dir_with(&src, &dest, false, true, &manifest_path, false).unwrap();
+        assert!(fs::symlink_metadata(dest.join("assets"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        link_dir_with(&src, &dest, true, true, &manifest_path, false).unwrap();
+        assert_eq!(fs::read_to_string(src.join("icon.png")).unwrap(), "icon");
+        assert_eq!(
+            fs::read_to_string(src.join("sounds/beep.ogg")).unwrap(),
+            "beep"
+        );
+        let copied = dest.join("assets");
+        assert!(!fs::symlink_metadata(&copied)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(copied.join("icon.png")).unwrap(), "icon");
+    }
+
+    #[test]
+    fn assets_arent_copied_into_themselves() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = asset_dir(dir.path());
+        let manifest_path = dir.path().join(".asset-sync");
+        let err = link_dir_with(&src, &src, true, true, &manifest_path, false).unwrap_err();
+        assert!(matches!(err, LinkDirError::DestWithinSrc { .. }), "{}", err);
+        assert!(!src.join("assets").exists());
     }
 }
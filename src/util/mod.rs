@@ -1,19 +1,37 @@
 mod cargo;
 pub mod cli;
+pub mod env;
+mod fs;
 mod git;
 pub mod ln;
+pub mod output;
 mod path;
 pub mod prompt;
+mod runner;
+mod sandbox;
+mod sync;
+pub mod xml;
 
-pub use self::{cargo::*, git::*, path::*};
+pub use self::{cargo::*, fs::*, git::*, path::*, runner::*, sandbox::*, sync::*};
 
 use self::cli::{Report, Reportable};
-use crate::os;
-use once_cell_regex::{exports::regex::Captures, regex};
+use crate::{opts::NoiseLevel, os};
+use once_cell_regex::{
+    exports::{
+        once_cell::sync::Lazy,
+        regex::{Captures, Regex},
+    },
+    regex,
+};
 use std::{
+    ffi::{OsStr, OsString},
     fmt::{self, Display},
-    io::{self, Write},
+    io::{self, Read as _, Write},
     path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+    sync::{mpsc, Mutex},
+    thread,
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 
@@ -37,14 +55,312 @@ pub fn list_display(list: &[impl Display]) -> String {
     }
 }
 
+/// Gets the name cargo gives a crate's library artifacts, i.e. `my-lib` builds
+/// `libmy_lib.so`.
+pub fn lib_name_from_crate(crate_name: &str) -> String {
+    crate_name.replace('-', "_")
+}
+
 pub fn reverse_domain(domain: &str) -> String {
     domain.split('.').rev().collect::<Vec<_>>().join(".")
 }
 
-pub fn rustup_add(triple: &str) -> bossy::Result<bossy::ExitStatus> {
-    bossy::Command::impure("rustup")
-        .with_args(&["target", "add", triple])
-        .run_and_wait()
+/// Derives an Android `versionCode` from a `(major, minor, patch)` version as
+/// `major * 1_000_000 + minor * 1_000 + patch`, so that codes always increase
+/// along with the version.
+///
+/// Since minor and patch versions only get 3 digits each, they're clamped to
+/// 999, and the result is clamped to 2_100_000_000, which is the largest code
+/// Google Play accepts. A warning is logged whenever clamping happens, since
+/// ordering is no longer guaranteed past that point.
+pub fn android_version_code((major, minor, patch): (u32, u32, u32)) -> u32 {
+    const MAX_COMPONENT: u32 = 999;
+    const MAX_VERSION_CODE: u64 = 2_100_000_000;
+    let clamp = |name, value: u32| {
+        if value > MAX_COMPONENT {
+            log::warn!(
+                "{} version {} is too large to fit in a `versionCode`; clamping to {}",
+                name,
+                value,
+                MAX_COMPONENT
+            );
+            MAX_COMPONENT
+        } else {
+            value
+        }
+    };
+    let code = u64::from(major) * 1_000_000
+        + u64::from(clamp("minor", minor)) * 1_000
+        + u64::from(clamp("patch", patch));
+    if code > MAX_VERSION_CODE {
+        log::warn!(
+            "`versionCode` {} is larger than the maximum of {}; clamping",
+            code,
+            MAX_VERSION_CODE
+        );
+        MAX_VERSION_CODE as u32
+    } else {
+        code as u32
+    }
+}
+
+/// Makes `s` safe to use as a value in an Xcode project file. Strings made up
+/// solely of characters that are safe unquoted are returned unchanged.
+pub fn pbxproj_escape(s: &str) -> String {
+    let safe = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_$/:.-".contains(c));
+    if safe {
+        s.to_owned()
+    } else {
+        let mut escaped = String::with_capacity(s.len() + 2);
+        escaped.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => escaped.push_str("\\\""),
+                '\\' => escaped.push_str("\\\\"),
+                '\n' => escaped.push_str("\\n"),
+                '\t' => escaped.push_str("\\t"),
+                _ => escaped.push(c),
+            }
+        }
+        escaped.push('"');
+        escaped
+    }
+}
+
+static CI_ENV_VARS: &[&str] = &[
+    "CI",
+    "CONTINUOUS_INTEGRATION",
+    "GITHUB_ACTIONS",
+    "GITLAB_CI",
+    "BUILDKITE",
+    "CIRCLECI",
+    "TRAVIS",
+    "TF_BUILD",
+    "JENKINS_URL",
+    "TEAMCITY_VERSION",
+];
+
+/// Checks if we seem to be running on CI, which means there's nobody around to
+/// answer prompts or appreciate colors.
+pub fn is_ci() -> bool {
+    is_ci_in(|key| std::env::var_os(key))
+}
+
+fn is_ci_in(var_os: impl Fn(&str) -> Option<OsString>) -> bool {
+    CI_ENV_VARS.iter().any(|key| {
+        var_os(key).map_or(false, |value| {
+            !value.is_empty() && value != *"false" && value != *"0"
+        })
+    })
+}
+
+pub fn rustup_add(triple: &str) -> Result<ExitStatus, CommandError> {
+    rustup_add_all(Runner::global(), &[triple])
+}
+
+// What rustup complains about when a previous install was interrupted.
+static RUSTUP_PARTIAL_INSTALL_MARKERS: &[&str] = &[
+    "partially installed",
+    "could not install",
+    "could not rename component file",
+];
+
+fn rustup_partially_installed(err: &CommandError) -> bool {
+    let stderr = err.stderr_tail();
+    RUSTUP_PARTIAL_INSTALL_MARKERS
+        .iter()
+        .any(|marker| stderr.contains(marker))
+}
+
+fn rustup_targets(subcommand: &str, triples: &[&str]) -> Invocation {
+    Invocation::impure("rustup")
+        .with_args(&["target", subcommand])
+        .with_args(triples)
+}
+
+/// Adds all of `triples` using a single `rustup` call. If that fails because
+/// an earlier install was interrupted, the targets are removed and then added
+/// again, once. Progress is shown as usual, since this can take a while.
+pub fn rustup_add_all(
+    runner: &impl CommandRunner,
+    triples: &[&str],
+) -> Result<ExitStatus, CommandError> {
+    let command = rustup_targets("add", triples);
+    match runner.stream(&command) {
+        Err(err) if rustup_partially_installed(&err) => {
+            log::warn!(
+                "rustup state for {:?} seems to be left over from an interrupted install; repairing",
+                triples
+            );
+            if let Err(err) = rustup_repair(runner, triples) {
+                log::warn!("failed to remove targets {:?}: {}", triples, err);
+            }
+            runner.stream(&command)
+        }
+        result => result,
+    }
+}
+
+pub fn rustup_fingerprint_path(root_dir: &Path) -> PathBuf {
+    root_dir.join(format!("target/.{}/rustup-fingerprint", crate::NAME))
+}
+
+// The fingerprint is the toolchain's version on the first line, followed by
+// the sorted installed triples on the rest.
+fn rustup_fingerprint(toolchain: &str, triples: &[&str]) -> String {
+    let mut triples = triples.to_vec();
+    triples.sort_unstable();
+    let mut fingerprint = format!("{}\n", toolchain);
+    for triple in triples {
+        fingerprint.push_str(triple);
+        fingerprint.push('\n');
+    }
+    fingerprint
+}
+
+/// Like [`rustup_add_all`], but skips asking `rustup` at all if the fingerprint
+/// at `fingerprint_path` says that `triples` were already added for the
+/// current toolchain. A toolchain change invalidates the fingerprint.
+pub fn rustup_add_all_fingerprinted(
+    runner: &impl CommandRunner,
+    triples: &[&str],
+    fingerprint_path: &Path,
+) -> Result<(), CommandError> {
+    // If we can't tell what toolchain we're on, then we can't trust the
+    // fingerprint, but `rustup` will still do the right thing.
+    let toolchain = RustVersion::check()
+        .map(|version| version.to_string())
+        .map_err(|err| log::warn!("failed to get toolchain for rustup fingerprint: {}", err))
+        .ok();
+    let fingerprint = toolchain
+        .as_ref()
+        .map(|toolchain| rustup_fingerprint(toolchain, triples));
+    if let Some(fingerprint) = &fingerprint {
+        if std::fs::read_to_string(fingerprint_path).ok().as_ref() == Some(fingerprint) {
+            log::info!(
+                "rustup fingerprint at {:?} is up-to-date; skipping `rustup target add`",
+                fingerprint_path
+            );
+            return Ok(());
+        }
+    }
+    rustup_add_all(runner, triples)?;
+    if let Some(fingerprint) = fingerprint {
+        let write_result = fingerprint_path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::write(fingerprint_path, fingerprint));
+        if let Err(err) = write_result {
+            log::warn!(
+                "failed to write rustup fingerprint to {:?}: {}",
+                fingerprint_path,
+                err
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Removes `triples`, which gets rid of any partially installed state.
+pub fn rustup_repair(
+    runner: &impl CommandRunner,
+    triples: &[&str],
+) -> Result<ExitStatus, CommandError> {
+    runner.stream(&rustup_targets("remove", triples))
+}
+
+fn rustup_component_args<'a>(subcommand: &'a str, toolchain: Option<&'a str>) -> Vec<&'a str> {
+    let mut args = vec!["component", subcommand];
+    if let Some(toolchain) = toolchain {
+        args.extend_from_slice(&["--toolchain", toolchain]);
+    }
+    args
+}
+
+/// Checks if `component` shows up in `rustup component list --installed`.
+/// Installed components have the host triple appended (i.e.
+/// `rust-src` vs `llvm-tools-preview-x86_64-apple-darwin`), so that suffix is
+/// allowed. The host triple comes from the `rustc` component, which is always
+/// installed; just allowing any suffix would make `rust` match `rust-src`.
+pub fn rustup_component_installed(
+    runner: &impl CommandRunner,
+    component: &str,
+    toolchain: Option<&str>,
+) -> Result<bool, CommandError> {
+    let mut args = rustup_component_args("list", toolchain);
+    args.push("--installed");
+    let output = runner.capture(&Invocation::impure("rustup").with_args(&args))?;
+    let raw_list = String::from_utf8_lossy(output.stdout());
+    let components = raw_list.lines().map(str::trim).collect::<Vec<_>>();
+    let host = components
+        .iter()
+        .find_map(|component| component.strip_prefix("rustc-"));
+    let installed = components.iter().any(|installed| {
+        *installed == component
+            || host.map_or(false, |host| {
+                *installed == format!("{}-{}", component, host)
+            })
+    });
+    log::info!("rustup component {:?} installed: {}", component, installed);
+    Ok(installed)
+}
+
+/// Gets the targets in `rustup target list --installed`.
+pub fn rustup_installed_targets() -> Result<Vec<String>, CommandError> {
+    let output =
+        Runner::global().capture(&Invocation::impure_parse("rustup target list --installed"))?;
+    Ok(String::from_utf8_lossy(output.stdout())
+        .lines()
+        .map(|line| line.trim().to_owned())
+        .filter(|line| !line.is_empty())
+        .collect())
+}
+
+/// Adds `component`, unless it's already installed.
+pub fn rustup_component_add(
+    runner: &impl CommandRunner,
+    component: &str,
+    toolchain: Option<&str>,
+) -> Result<(), CommandError> {
+    if !rustup_component_installed(runner, component, toolchain)? {
+        let mut args = rustup_component_args("add", toolchain);
+        args.push(component);
+        runner.stream(&Invocation::impure("rustup").with_args(&args))?;
+    }
+    Ok(())
+}
+
+/// Lists the names of installed toolchains, as understood by `cargo +<name>`.
+pub fn rustup_toolchains(runner: &impl CommandRunner) -> Result<Vec<String>, CommandError> {
+    let output = runner.capture(&Invocation::impure_parse("rustup toolchain list"))?;
+    Ok(String::from_utf8_lossy(output.stdout())
+        .lines()
+        // Lines look like `stable-x86_64-apple-darwin (default)`
+        .filter_map(|line| line.split_whitespace().next())
+        // ...unless there aren't any, in which case it says `no installed toolchains`
+        .filter(|name| *name != "no")
+        .map(ToOwned::to_owned)
+        .collect())
+}
+
+/// Gets the text of every capture group in the first match of `re` in `body`,
+/// in order. Groups that didn't participate in the match are `None`. The
+/// whole match (group 0) isn't included.
+pub fn extract_captures<'a>(re: &Regex, body: &'a str) -> Option<Vec<Option<&'a str>>> {
+    re.captures(body).map(|caps| {
+        caps.iter()
+            .skip(1)
+            .map(|group| group.map(|group| group.as_str()))
+            .collect()
+    })
+}
+
+/// Gets the text of the capture group called `name` in the first match of `re`
+/// in `body`.
+pub fn extract_named<'a>(re: &Regex, body: &'a str, name: &str) -> Option<&'a str> {
+    re.captures(body)?.name(name).map(|group| group.as_str())
 }
 
 #[derive(Debug)]
@@ -198,8 +514,364 @@ impl RustVersion {
     }
 }
 
-pub fn prepend_to_path(path: impl Display, base_path: impl Display) -> String {
-    format!("{}:{}", path, base_path)
+/// Prepends each of `entries` to the current `PATH`, in order, and then drops
+/// any duplicates (keeping the first occurrence). Entries that don't exist are
+/// skipped with a warning, since a typo'd SDK path would otherwise just show up
+/// later as a confusing "tool not found" failure.
+pub fn finalize_path(entries: &[PathBuf]) -> io::Result<OsString> {
+    let entries = entries
+        .iter()
+        .filter(|entry| {
+            let exists = entry.is_dir();
+            if !exists {
+                log::warn!("not adding {:?} to `PATH`, since it doesn't exist", entry);
+            }
+            exists
+        })
+        .cloned();
+    env::EnvBuilder::new()
+        .prepend_paths("PATH", entries)
+        .and_then(|env| env.dedupe_paths("PATH"))
+        .map(|env| env.get("PATH").unwrap_or_default().to_owned())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
+
+static COMMAND_TIMINGS: Lazy<Mutex<Vec<(String, Duration)>>> = Lazy::new(Default::default);
+
+/// How long each command run via a [`Runner`] took, which is handy for figuring out what's making things slow.
+#[derive(Debug)]
+pub struct CommandTimings;
+
+impl CommandTimings {
+    fn record(command: String, elapsed: Duration) {
+        log::debug!("`{}` took {:?}", command, elapsed);
+        COMMAND_TIMINGS
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push((command, elapsed));
+    }
+
+    /// Every recorded `(command, duration)` pair so far, in the order the
+    /// commands finished.
+    pub fn get() -> Vec<(String, Duration)> {
+        COMMAND_TIMINGS
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+}
+
+/// An invocation that runs `script` using the platform's shell, i.e. `sh -c` or
+/// `cmd /C`.
+pub fn shell_invocation(script: &str) -> Invocation {
+    #[cfg(not(windows))]
+    let command = Invocation::impure("sh").with_args(&["-c", script]);
+    #[cfg(windows)]
+    let command = Invocation::impure("cmd").with_args(&["/C", script]);
+    command
+}
+
+/// Runs `script` using the platform's shell, from within `dir`.
+///
+/// This executes arbitrary commands, so `script` should only ever come from a
+/// source the user already trusts, i.e. their own project or template.
+pub fn run_in_shell(script: &str, dir: &Path) -> Result<(), CommandError> {
+    log::info!("running {:?} in shell from {:?}", script, dir);
+    Runner::global().stream(&shell_invocation(script).with_current_dir(dir))?;
+    Ok(())
+}
+
+/// An error from running a command, which knows whether it failed because the
+/// program itself wasn't installed, so that we can tell users what to install.
+#[derive(Debug)]
+pub enum CommandError {
+    NotFound { program: String },
+    Failed(bossy::Error),
+    FailedWithContext(Box<CommandContext>),
+}
+
+impl CommandError {
+    pub fn new(program: &str, err: bossy::Error) -> Self {
+        let not_found = std::error::Error::source(&err)
+            .and_then(|source| source.downcast_ref::<io::Error>())
+            .map_or(false, |source| source.kind() == io::ErrorKind::NotFound);
+        if not_found {
+            Self::NotFound {
+                program: program.to_owned(),
+            }
+        } else {
+            Self::Failed(err)
+        }
+    }
+
+    /// Like [`CommandError::new`], but for a command run via a [`Runner`],
+    /// which keeps everything needed to explain the failure.
+    pub fn from_context(program: &str, context: CommandContext) -> Self {
+        match &context.cause {
+            CommandCause::SpawnFailed(err) if err.kind() == io::ErrorKind::NotFound => {
+                Self::NotFound {
+                    program: program.to_owned(),
+                }
+            }
+            _ => Self::FailedWithContext(Box::new(context)),
+        }
+    }
+
+    /// Attaches a suggestion for what to do about the failure, which is shown
+    /// after everything else. This does nothing if there's no context to
+    /// attach it to.
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        if let Self::FailedWithContext(context) = &mut self {
+            context.hint = Some(hint.into());
+        }
+        self
+    }
+
+    /// Suggests running with more `-v`s, unless `noise_level` is already at
+    /// least `needed`.
+    pub fn with_verbose_hint(self, noise_level: NoiseLevel, needed: NoiseLevel) -> Self {
+        if noise_level >= needed {
+            self
+        } else {
+            let flag = if needed.pedantic() {
+                "-vv"
+            } else {
+                "--verbose"
+            };
+            self.with_hint(format!("run with `{}` for more details", flag))
+        }
+    }
+
+    /// The status the command exited with, if it ran and exited at all.
+    pub fn status(&self) -> Option<ExitStatus> {
+        match self {
+            Self::NotFound { .. } => None,
+            Self::Failed(err) => err.status(),
+            Self::FailedWithContext(context) => context.cause.status(),
+        }
+    }
+
+    /// The exit code of the command, if it ran and exited on its own.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.status().and_then(|status| status.code())
+    }
+
+    /// The last lines of the command's stderr, which is empty if it never got
+    /// to run or didn't write anything there.
+    pub fn stderr_tail(&self) -> std::borrow::Cow<'_, str> {
+        match self {
+            Self::NotFound { .. } => "".into(),
+            Self::Failed(err) => {
+                runner::tail_lines(err.stderr_str().and_then(Result::ok).unwrap_or_default()).into()
+            }
+            Self::FailedWithContext(context) => context.stderr_tail.as_str().into(),
+        }
+    }
+
+    pub fn report(&self, msg: &str) -> Report {
+        match self {
+            Self::NotFound { program } => Report::action_request(
+                msg,
+                format!(
+                    "`{}` wasn't found; please install it and make sure it's on your `PATH`.",
+                    program
+                ),
+            ),
+            Self::Failed(err) => Report::error(msg, err),
+            Self::FailedWithContext(context) => Report::error(msg, context),
+        }
+    }
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound { program } => write!(f, "`{}` wasn't found", program),
+            Self::Failed(err) => write!(f, "{}", err),
+            Self::FailedWithContext(context) => write!(f, "{}", context),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// What went wrong with a command run via a [`Runner`].
+#[derive(Debug)]
+pub enum CommandCause {
+    SpawnFailed(io::Error),
+    WaitFailed(io::Error),
+    Exited(ExitStatus),
+}
+
+impl CommandCause {
+    pub fn status(&self) -> Option<ExitStatus> {
+        match self {
+            Self::Exited(status) => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+impl Display for CommandCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SpawnFailed(err) => write!(f, "Failed to start: {}", err),
+            Self::WaitFailed(err) => write!(f, "Failed to wait for it to exit: {}", err),
+            Self::Exited(status) => write!(f, "It exited with {}", status),
+        }
+    }
+}
+
+/// Everything needed to make sense of a failed command without re-running it.
+#[derive(Debug)]
+pub struct CommandContext {
+    pub command: String,
+    pub current_dir: Option<PathBuf>,
+    pub stderr_tail: String,
+    pub hint: Option<String>,
+    pub cause: CommandCause,
+}
+
+impl CommandContext {
+    pub fn exit_code(&self) -> Option<i32> {
+        self.cause.status().and_then(|status| status.code())
+    }
+}
+
+impl Display for CommandContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` failed", self.command)?;
+        if let Some(code) = self.exit_code() {
+            write!(f, " (exit {})", code)?;
+        }
+        if let Some(current_dir) = &self.current_dir {
+            write!(f, " in {:?}", current_dir)?;
+        }
+        // The exit code says everything the cause would've, but otherwise, we
+        // might've been killed by a signal or never gotten to run at all.
+        if self.exit_code().is_none() {
+            write!(f, ": {}", self.cause)?;
+        }
+        if !self.stderr_tail.is_empty() {
+            write!(f, "\nLast lines of stderr:\n{}", self.stderr_tail)?;
+        }
+        if let Some(hint) = &self.hint {
+            write!(f, "\nhint: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs `command` via the [global runner](Runner::global), and on failure
+/// returns an error that includes the command line, the directory it ran in,
+/// and the end of its stderr. Output is captured rather than inherited, so
+/// this is best for commands whose output only matters when something goes
+/// wrong.
+pub fn run(command: &Invocation) -> Result<(), CommandError> {
+    Runner::global().capture(command).map(|_| ())
+}
+
+/// Whether a failure looks like it was caused by something flaky (i.e. the
+/// network) rather than something that'll fail the same way every time, like
+/// a compile error. Status codes only count when they're clearly from an HTTP
+/// response, since otherwise i.e. `src/lib.rs:503` would look flaky too.
+pub fn is_transient(error: &CommandError, stderr: &str) -> bool {
+    match error {
+        // No amount of retrying will install it for us
+        CommandError::NotFound { .. } => false,
+        CommandError::Failed(_) | CommandError::FailedWithContext(_) => regex!(
+            r"(?i)timed? ?out|connection (?:reset|refused|closed)|could not resolve host|temporary failure in name resolution|network is unreachable|rate limit|too many requests|HTTP/?\S* (?:429|50[234])\b|status(?: code)?:? (?:429|50[234])\b|\b(?:429|50[234]) (?:bad gateway|service unavailable|gateway time-?out)|spurious network error"
+        )
+        .is_match(stderr),
+    }
+}
+
+/// Runs the command built by `command`, retrying up to `attempts` times in
+/// total, but only as long as the failures look [transient](is_transient).
+/// The first retry waits for `first_delay`, and each one after that waits
+/// twice as long as the last.
+pub fn run_retrying_transient(
+    runner: &impl CommandRunner,
+    attempts: u32,
+    first_delay: Duration,
+    mut command: impl FnMut() -> Invocation,
+) -> Result<Output, CommandError> {
+    let mut attempt = 1;
+    let mut delay = first_delay;
+    loop {
+        let command = command();
+        match runner.capture(&command) {
+            Ok(output) => return Ok(output),
+            Err(err) => {
+                let stderr = err.stderr_tail().into_owned();
+                if attempt >= attempts || !is_transient(&err, &stderr) {
+                    return Err(err);
+                }
+                log::warn!(
+                    "`{}` failed with what looks like a transient error (attempt {} of {}); retrying in {:?}",
+                    command.program().to_string_lossy(),
+                    attempt,
+                    attempts,
+                    delay
+                );
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StripError {
+    InvalidObject { lib: PathBuf },
+    CommandFailed(bossy::Error),
+}
+
+impl Display for StripError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidObject { lib } => {
+                write!(
+                    f,
+                    "{:?} isn't a valid object file, so it can't be stripped",
+                    lib
+                )
+            }
+            Self::CommandFailed(err) => write!(f, "Failed to strip symbols: {}", err),
+        }
+    }
+}
+
+pub fn strip_command(lib: &Path, target_strip_tool: Option<&Path>) -> bossy::Command {
+    let strip_tool = target_strip_tool.unwrap_or_else(|| Path::new("strip"));
+    bossy::Command::impure(strip_tool)
+        .with_arg("--strip-debug")
+        .with_arg(lib)
+}
+
+/// Strips debug symbols from `lib`, using `target_strip_tool` if provided (i.e.
+/// the NDK's `strip`) and the system `strip` otherwise.
+pub fn strip_symbols(lib: &Path, target_strip_tool: Option<&Path>) -> Result<(), StripError> {
+    strip_command(lib, target_strip_tool)
+        .run_and_wait_for_output()
+        .map_err(|err| {
+            let invalid_object = err
+                .stderr_str()
+                .and_then(Result::ok)
+                .map_or(false, |stderr| {
+                    stderr.contains("not a valid object file")
+                        || stderr.contains("file format not recognized")
+                });
+            if invalid_object {
+                StripError::InvalidObject {
+                    lib: lib.to_owned(),
+                }
+            } else {
+                StripError::CommandFailed(err)
+            }
+        })?;
+    Ok(())
 }
 
 pub fn command_path(name: &str) -> bossy::Result<bossy::Output> {
@@ -208,6 +880,96 @@ pub fn command_path(name: &str) -> bossy::Result<bossy::Output> {
         .run_and_wait_for_output()
 }
 
+/// Gets a command for `program` that only inherits the env vars in `allow`,
+/// which makes its behavior independent of whatever else is in the host
+/// environment.
+pub fn allowlisted_command(program: impl AsRef<OsStr>, allow: &[&str]) -> Invocation {
+    Invocation::pure(program).with_env_vars(
+        allow
+            .iter()
+            .filter_map(|key| std::env::var_os(key).map(|value| (*key, value))),
+    )
+}
+
+/// Runs `program` with `args`, passing through only the env vars in `allow`.
+pub fn run_with_env_allowlist(
+    program: impl AsRef<OsStr>,
+    args: &[&str],
+    allow: &[&str],
+) -> Result<ExitStatus, CommandError> {
+    Runner::global().stream(&allowlisted_command(program, allow).with_args(args))
+}
+
+// Keyed on the `PATH` each program was looked up on as well as its name.
+static RESOLVED_PROGRAMS: Lazy<
+    Mutex<std::collections::HashMap<(String, Option<OsString>), Option<PathBuf>>>,
+> = Lazy::new(Default::default);
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt as _;
+    std::fs::metadata(path)
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or_default()
+}
+
+// Windows doesn't have an executable bit; that's what `PATHEXT` is for.
+#[cfg(windows)]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(unix)]
+fn program_candidates(dir: &Path, name: &str) -> Vec<PathBuf> {
+    vec![dir.join(name)]
+}
+
+// `cargo` could be `cargo.exe`, `cargo.cmd`, etc., and the shell tries each of
+// the extensions in `PATHEXT` in order.
+#[cfg(windows)]
+fn program_candidates(dir: &Path, name: &str) -> Vec<PathBuf> {
+    if Path::new(name).extension().is_some() {
+        return vec![dir.join(name)];
+    }
+    std::env::var("PATHEXT")
+        .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_owned())
+        .split(';')
+        .filter(|ext| !ext.is_empty())
+        .map(|ext| dir.join(format!("{}{}", name, ext)))
+        .collect()
+}
+
+/// Finds the absolute path `name` resolves to on our `PATH`, the same way a
+/// shell would.
+pub fn resolve_program(name: &str) -> Option<PathBuf> {
+    resolve_program_in(name, std::env::var_os("PATH").as_deref())
+}
+
+/// Like [`resolve_program`], but looks on `paths` instead of our own `PATH`.
+/// Results are cached for each `PATH`, so a program only gets looked for once
+/// per `PATH` it's run with.
+pub fn resolve_program_in(name: &str, paths: Option<&OsStr>) -> Option<PathBuf> {
+    // Anything that already has a path component doesn't get looked up
+    if Path::new(name).components().count() > 1 {
+        return Some(PathBuf::from(name));
+    }
+    let mut resolved = RESOLVED_PROGRAMS
+        .lock()
+        .expect("developer error: resolved programs mutex poisoned");
+    resolved
+        .entry((name.to_owned(), paths.map(ToOwned::to_owned)))
+        .or_insert_with(|| {
+            let path = paths.and_then(|paths| {
+                std::env::split_paths(paths)
+                    .flat_map(|dir| program_candidates(&dir, name))
+                    .find(|path| path.is_absolute() && is_executable(path))
+            });
+            log::info!("resolved program {:?} on {:?} to {:?}", name, paths, path);
+            path
+        })
+        .clone()
+}
+
 pub fn command_present(name: &str) -> bossy::Result<bool> {
     command_path(name).map(|_path| true).or_else(|err| {
         if let Some(1) = err.status().and_then(|status| status.code()) {
@@ -220,10 +982,11 @@ pub fn command_present(name: &str) -> bossy::Result<bool> {
 
 #[derive(Debug)]
 pub enum PipeError {
-    TxCommandFailed(bossy::Error),
-    RxCommandFailed(bossy::Error),
+    TxCommandFailed(CommandError),
+    RxCommandFailed(CommandError),
     PipeFailed(io::Error),
-    WaitFailed(bossy::Error),
+    WaitFailed(CommandError),
+    TimedOut(Duration),
 }
 
 impl Display for PipeError {
@@ -235,26 +998,26 @@ impl Display for PipeError {
             Self::WaitFailed(err) => {
                 write!(f, "Failed to wait for receiving command to exit: {}", err)
             }
+            Self::TimedOut(timeout) => write!(f, "Pipeline didn't finish within {:?}", timeout),
         }
     }
 }
 
-pub fn pipe(mut tx_command: bossy::Command, rx_command: bossy::Command) -> Result<bool, PipeError> {
-    let tx_output = tx_command
-        .run_and_wait_for_output()
+pub fn pipe(tx_command: &Invocation, rx_command: &Invocation) -> Result<bool, PipeError> {
+    let runner = Runner::global();
+    let tx_output = runner
+        .capture(tx_command)
         .map_err(PipeError::TxCommandFailed)?;
     if !tx_output.stdout().is_empty() {
-        let mut rx_command = rx_command
-            .with_stdin_piped()
-            .with_stdout(bossy::Stdio::inherit())
-            .run()
+        let mut rx_child = runner
+            .spawn(rx_command, Stdio::piped(), Stdio::inherit(), Stdio::piped())
             .map_err(PipeError::RxCommandFailed)?;
-        let pipe_result = rx_command
-            .stdin()
+        let pipe_result = rx_child
+            .take_stdin()
             .expect("developer error: `rx_command` stdin not captured")
             .write_all(tx_output.stdout())
             .map_err(PipeError::PipeFailed);
-        let wait_result = rx_command.wait_for_output().map_err(PipeError::WaitFailed);
+        let wait_result = rx_child.wait_for_output().map_err(PipeError::WaitFailed);
         // We try to wait even if the pipe failed, but the pipe error has higher
         // priority than the wait error, since it's likely to be more relevant.
         pipe_result?;
@@ -265,6 +1028,330 @@ pub fn pipe(mut tx_command: bossy::Command, rx_command: bossy::Command) -> Resul
     }
 }
 
+#[derive(Debug)]
+pub enum RunWithStdinError {
+    CommandFailed(CommandError),
+    WriteFailed(io::Error),
+}
+
+impl Display for RunWithStdinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CommandFailed(err) => write!(f, "Failed to run command: {}", err),
+            Self::WriteFailed(err) => write!(f, "Failed to write to command's stdin: {}", err),
+        }
+    }
+}
+
+/// Runs `command` with `input` as its stdin. Whatever of `stdout` and `stderr`
+/// is piped ends up in the returned output.
+/// A child that exits without reading all of `input` isn't an error in itself,
+/// much like `head` isn't; its exit status is what decides that.
+pub fn run_with_stdin(
+    command: &Invocation,
+    input: &[u8],
+    stdout: Stdio,
+    stderr: Stdio,
+) -> Result<Output, RunWithStdinError> {
+    let mut child = Runner::global()
+        .spawn(command, Stdio::piped(), stdout, stderr)
+        .map_err(RunWithStdinError::CommandFailed)?;
+    let mut writer = child
+        .take_stdin()
+        .expect("developer error: stdin not captured");
+    // We need to write on another thread, since otherwise a child that fills
+    // its stdout before reading all of its input would deadlock us.
+    let input = input.to_vec();
+    let writer = thread::spawn(move || {
+        // `writer` is dropped at the end of this, which closes the child's stdin
+        writer.write_all(&input)
+    });
+    let wait_result = child
+        .wait_for_output()
+        .map_err(RunWithStdinError::CommandFailed);
+    let write_result = writer
+        .join()
+        .expect("developer error: stdin writer thread panicked")
+        .or_else(|err| {
+            if err.kind() == io::ErrorKind::BrokenPipe {
+                log::info!("command closed its stdin before reading all of its input");
+                Ok(())
+            } else {
+                Err(err)
+            }
+        })
+        .map_err(RunWithStdinError::WriteFailed);
+    // Same as in `pipe`, the write error is likely to be more relevant.
+    write_result?;
+    wait_result
+}
+
+#[derive(Debug)]
+pub enum CaptureCappedError {
+    PipeFailed(io::Error),
+    CommandFailed(CommandError),
+    ReadFailed(io::Error),
+}
+
+impl Display for CaptureCappedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PipeFailed(err) => write!(f, "Failed to create stdout pipe: {}", err),
+            Self::CommandFailed(err) => write!(f, "Failed to run command: {}", err),
+            Self::ReadFailed(err) => write!(f, "Failed to read command's stdout: {}", err),
+        }
+    }
+}
+
+/// Runs `command`, capturing at most `max_bytes` of its stdout. Anything past
+/// that is read and thrown away, so a chatty child won't block on a full pipe
+/// (or make us run out of memory). The returned flag is `true` if the output
+/// was truncated.
+pub fn run_and_capture_capped(
+    command: &Invocation,
+    max_bytes: usize,
+) -> Result<(String, bool), CaptureCappedError> {
+    let (mut reader, writer) = os::pipe().map_err(CaptureCappedError::PipeFailed)?;
+    let child = Runner::global()
+        .spawn(
+            command,
+            Stdio::inherit(),
+            Stdio::from(writer),
+            Stdio::inherit(),
+        )
+        .map_err(CaptureCappedError::CommandFailed)?;
+    // `spawn` consumed our copy of the write end, so we'll see EOF once the
+    // child exits.
+    let mut captured = Vec::new();
+    let read_result = (&mut reader)
+        .take(max_bytes as u64)
+        .read_to_end(&mut captured)
+        .and_then(|_| io::copy(&mut reader, &mut io::sink()));
+    // We need to reap the child either way.
+    let wait_result = child.wait().map_err(CaptureCappedError::CommandFailed);
+    let discarded = read_result.map_err(CaptureCappedError::ReadFailed)?;
+    wait_result?;
+    if discarded > 0 {
+        log::warn!(
+            "command output exceeded {} bytes; discarded the remaining {} bytes",
+            max_bytes,
+            discarded
+        );
+    }
+    // The cap could've landed in the middle of a character.
+    Ok((
+        String::from_utf8_lossy(&captured).into_owned(),
+        discarded > 0,
+    ))
+}
+
+#[derive(Debug)]
+pub enum CaptureCombinedError {
+    PipeFailed(io::Error),
+    CommandFailed(CommandError),
+    ReadFailed(io::Error),
+}
+
+impl Display for CaptureCombinedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PipeFailed(err) => write!(f, "Failed to create output pipe: {}", err),
+            Self::CommandFailed(err) => write!(f, "Failed to run command: {}", err),
+            Self::ReadFailed(err) => write!(f, "Failed to read command's output: {}", err),
+        }
+    }
+}
+
+// A non-zero exit is reported through the status instead of as an error.
+fn exit_status(result: Result<ExitStatus, CommandError>) -> Result<ExitStatus, CommandError> {
+    result.or_else(|err| err.status().ok_or(err))
+}
+
+/// Runs `command` with both stdout and stderr going into the same pipe, so
+/// the captured output is in the order it was actually written. A non-zero
+/// exit isn't treated as an error; check the returned status instead.
+pub fn run_capture_combined(
+    command: &Invocation,
+) -> Result<(String, ExitStatus), CaptureCombinedError> {
+    let (mut reader, writer) = os::pipe().map_err(CaptureCombinedError::PipeFailed)?;
+    let stderr_writer = writer
+        .try_clone()
+        .map_err(CaptureCombinedError::PipeFailed)?;
+    let child = Runner::global()
+        .spawn(
+            command,
+            Stdio::inherit(),
+            Stdio::from(writer),
+            Stdio::from(stderr_writer),
+        )
+        .map_err(CaptureCombinedError::CommandFailed)?;
+    // `spawn` consumed both of our copies of the write end, so we'll see EOF
+    // once the child exits.
+    let mut output = Vec::new();
+    let read_result = reader.read_to_end(&mut output);
+    let status = exit_status(child.wait()).map_err(CaptureCombinedError::CommandFailed)?;
+    read_result.map_err(CaptureCombinedError::ReadFailed)?;
+    Ok((String::from_utf8_lossy(&output).into_owned(), status))
+}
+
+#[derive(Debug)]
+pub enum RunTeeError {
+    LogCreationFailed { path: PathBuf, cause: io::Error },
+    PipeFailed(io::Error),
+    CommandFailed(CommandError),
+    TeeFailed(io::Error),
+}
+
+impl Display for RunTeeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LogCreationFailed { path, cause } => {
+                write!(f, "Failed to create log file at {:?}: {}", path, cause)
+            }
+            Self::PipeFailed(err) => write!(f, "Failed to create output pipe: {}", err),
+            Self::CommandFailed(err) => write!(f, "Failed to run command: {}", err),
+            Self::TeeFailed(err) => write!(f, "Failed to tee command's output: {}", err),
+        }
+    }
+}
+
+// Copies lines from `reader` to both `terminal` and `log` as they come in.
+fn tee_lines(
+    reader: std::fs::File,
+    mut terminal: impl Write,
+    log: &Mutex<std::fs::File>,
+) -> io::Result<()> {
+    use io::BufRead as _;
+    let mut reader = io::BufReader::new(reader);
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        terminal.write_all(&line)?;
+        terminal.flush()?;
+        let mut log = log.lock().expect("developer error: tee log mutex poisoned");
+        log.write_all(&line)?;
+        log.flush()?;
+        line.clear();
+    }
+    Ok(())
+}
+
+/// Runs `command` with its output shown like normal, while also saving it to
+/// `log_file` (which gets replaced if it already exists). A non-zero exit
+/// isn't treated as an error; check the returned status instead.
+pub fn run_tee(command: &Invocation, log_file: &Path) -> Result<ExitStatus, RunTeeError> {
+    let log = std::fs::File::create(log_file).map_err(|cause| RunTeeError::LogCreationFailed {
+        path: log_file.to_owned(),
+        cause,
+    })?;
+    let log = std::sync::Arc::new(Mutex::new(log));
+    let (stdout_reader, stdout_writer) = os::pipe().map_err(RunTeeError::PipeFailed)?;
+    let (stderr_reader, stderr_writer) = os::pipe().map_err(RunTeeError::PipeFailed)?;
+    let child = Runner::global()
+        .spawn(
+            command,
+            Stdio::inherit(),
+            Stdio::from(stdout_writer),
+            Stdio::from(stderr_writer),
+        )
+        .map_err(RunTeeError::CommandFailed)?;
+    // Each stream gets its own thread, since otherwise a child that fills one
+    // pipe while we're blocked reading the other would deadlock us.
+    let stdout_tee = {
+        let log = std::sync::Arc::clone(&log);
+        thread::spawn(move || tee_lines(stdout_reader, io::stdout(), &log))
+    };
+    let stderr_tee = {
+        let log = std::sync::Arc::clone(&log);
+        thread::spawn(move || tee_lines(stderr_reader, io::stderr(), &log))
+    };
+    let status = exit_status(child.wait()).map_err(RunTeeError::CommandFailed);
+    for tee in vec![stdout_tee, stderr_tee] {
+        tee.join()
+            .expect("developer error: tee thread panicked")
+            .map_err(RunTeeError::TeeFailed)?;
+    }
+    status
+}
+
+enum StageError {
+    PipeFailed(io::Error),
+    WaitFailed(CommandError),
+}
+
+// Feeds `stdin` to the child and waits for it on another thread, killing it if
+// it's still running at `deadline`. Returns `None` on timeout. Otherwise, the
+// child has been reaped by the time this returns.
+fn wait_with_deadline(
+    mut child: Child,
+    stdin: Option<Vec<u8>>,
+    deadline: Instant,
+) -> Option<Result<Output, StageError>> {
+    let pid = child.id();
+    let (tx, rx) = mpsc::channel();
+    let waiter = thread::spawn(move || {
+        let pipe_result = stdin.map_or(Ok(()), |stdin| {
+            child
+                .take_stdin()
+                .expect("developer error: stdin not captured")
+                .write_all(&stdin)
+        });
+        let wait_result = child.wait_for_output();
+        let result = pipe_result
+            .map_err(StageError::PipeFailed)
+            .and_then(|()| wait_result.map_err(StageError::WaitFailed));
+        // The receiver only goes away if we've already timed out
+        let _ = tx.send(result);
+    });
+    let result = rx
+        .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        .ok();
+    if result.is_none() {
+        log::warn!("child process {} timed out; killing it", pid);
+        os::kill_child(pid);
+        // Anything the child spawned could still be holding its output pipes
+        // open, and the waiter can't reap the child until those close. It'll
+        // get there on its own, so we don't wait for it.
+    } else {
+        waiter
+            .join()
+            .expect("developer error: pipe waiter thread panicked");
+    }
+    result
+}
+
+/// Like [`pipe`], but kills the pipeline if it takes longer than `timeout`.
+pub fn pipe_with_timeout(
+    tx_command: &Invocation,
+    rx_command: &Invocation,
+    timeout: Duration,
+) -> Result<bool, PipeError> {
+    let runner = Runner::global();
+    let deadline = Instant::now() + timeout;
+    let tx_child = runner
+        .spawn(tx_command, Stdio::inherit(), Stdio::piped(), Stdio::piped())
+        .map_err(PipeError::TxCommandFailed)?;
+    let tx_output = wait_with_deadline(tx_child, None, deadline)
+        .ok_or(PipeError::TimedOut(timeout))?
+        .map_err(|err| match err {
+            StageError::PipeFailed(err) => PipeError::PipeFailed(err),
+            StageError::WaitFailed(err) => PipeError::TxCommandFailed(err),
+        })?;
+    if !tx_output.stdout().is_empty() {
+        let rx_child = runner
+            .spawn(rx_command, Stdio::piped(), Stdio::inherit(), Stdio::piped())
+            .map_err(PipeError::RxCommandFailed)?;
+        wait_with_deadline(rx_child, Some(tx_output.stdout().to_vec()), deadline)
+            .ok_or(PipeError::TimedOut(timeout))?
+            .map_err(|err| match err {
+                StageError::PipeFailed(err) => PipeError::PipeFailed(err),
+                StageError::WaitFailed(err) => PipeError::WaitFailed(err),
+            })?;
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
 #[derive(Debug)]
 pub enum OpenInEditorError {
     DetectFailed(os::DetectEditorError),
@@ -288,6 +1375,98 @@ pub fn open_in_editor(path: impl AsRef<Path>) -> Result<(), OpenInEditorError> {
         .map_err(OpenInEditorError::OpenFailed)
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OpenTarget {
+    Xcode,
+    AndroidStudio,
+    /// Whatever the OS would open the path with on its own.
+    Default,
+}
+
+impl OpenTarget {
+    fn app_name(self) -> &'static str {
+        match self {
+            Self::Xcode => "Xcode",
+            Self::AndroidStudio => "Android Studio",
+            Self::Default => "the default application",
+        }
+    }
+
+    /// The program and args that open `path` with this target, or `None` if
+    /// it isn't available on this platform.
+    pub fn command(self, path: &Path) -> Option<bossy::Command> {
+        #[cfg(target_os = "macos")]
+        let command = match self {
+            Self::Xcode | Self::AndroidStudio => {
+                bossy::Command::impure("open").with_args(&["-a", self.app_name()])
+            }
+            Self::Default => bossy::Command::impure("open"),
+        };
+        #[cfg(all(unix, not(target_os = "macos")))]
+        let command = match self {
+            Self::Xcode => return None,
+            Self::AndroidStudio => bossy::Command::impure("studio"),
+            Self::Default => bossy::Command::impure("xdg-open"),
+        };
+        #[cfg(windows)]
+        let command = match self {
+            Self::Xcode => return None,
+            Self::AndroidStudio => bossy::Command::impure("studio64"),
+            // The empty string is the window title, which `start` would
+            // otherwise take the path as.
+            Self::Default => bossy::Command::impure("cmd").with_args(&["/C", "start", ""]),
+        };
+        Some(command.with_arg(path))
+    }
+}
+
+#[derive(Debug)]
+pub enum OpenInError {
+    Unsupported { target: OpenTarget },
+    NotInstalled { target: OpenTarget },
+    Failed(CommandError),
+}
+
+impl Display for OpenInError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported { target } => {
+                write!(f, "{} isn't available on this platform.", target.app_name())
+            }
+            Self::NotInstalled { target } => write!(
+                f,
+                "{} doesn't seem to be installed; please install it and try again.",
+                target.app_name()
+            ),
+            Self::Failed(err) => write!(f, "Failed to open path: {}", err),
+        }
+    }
+}
+
+/// Opens `path` in an IDE (or whatever's appropriate), i.e. to look at a
+/// freshly generated project.
+pub fn open_in(target: OpenTarget, path: &Path) -> Result<(), OpenInError> {
+    let mut command = target
+        .command(path)
+        .ok_or(OpenInError::Unsupported { target })?;
+    let program = command.display().to_owned();
+    command.run_and_wait_for_output().map_err(|err| {
+        // `open -a` exits with 1 and says so when the app doesn't exist
+        let app_missing = err
+            .stderr_str()
+            .and_then(Result::ok)
+            .map_or(false, |stderr| {
+                stderr.contains("Unable to find application")
+            });
+        match CommandError::new(&program, err) {
+            _ if app_missing => OpenInError::NotInstalled { target },
+            CommandError::NotFound { .. } => OpenInError::NotInstalled { target },
+            err => OpenInError::Failed(err),
+        }
+    })?;
+    Ok(())
+}
+
 #[derive(Debug, Error)]
 pub enum InstalledCommitMsgError {
     #[error(transparent)]
@@ -306,3 +1485,544 @@ pub fn installed_commit_msg() -> Result<Option<String>, InstalledCommitMsgError>
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_target_commands() {
+        let path = Path::new("/tmp/app");
+        let display = |target: OpenTarget| {
+            target
+                .command(path)
+                .map(|command| command.display().to_owned())
+        };
+        if cfg!(target_os = "macos") {
+            assert_eq!(
+                display(OpenTarget::Xcode).unwrap(),
+                "open -a Xcode /tmp/app"
+            );
+            assert_eq!(
+                display(OpenTarget::AndroidStudio).unwrap(),
+                "open -a Android Studio /tmp/app"
+            );
+            assert_eq!(display(OpenTarget::Default).unwrap(), "open /tmp/app");
+        } else if cfg!(windows) {
+            assert!(display(OpenTarget::Xcode).is_none());
+            assert_eq!(
+                display(OpenTarget::AndroidStudio).unwrap(),
+                "studio64 /tmp/app"
+            );
+            assert_eq!(
+                display(OpenTarget::Default).unwrap(),
+                "cmd /C start  /tmp/app"
+            );
+        } else {
+            assert!(display(OpenTarget::Xcode).is_none());
+            assert_eq!(
+                display(OpenTarget::AndroidStudio).unwrap(),
+                "studio /tmp/app"
+            );
+            assert_eq!(display(OpenTarget::Default).unwrap(), "xdg-open /tmp/app");
+        }
+    }
+
+    #[test]
+    fn open_in_missing_app_is_not_installed() {
+        if cfg!(target_os = "macos") || cfg!(windows) {
+            return;
+        }
+        // `studio` is about as likely to be on CI machines as Xcode is
+        let dir = std::env::temp_dir();
+        if resolve_program("studio").is_none() {
+            assert!(matches!(
+                open_in(OpenTarget::AndroidStudio, &dir),
+                Err(OpenInError::NotInstalled {
+                    target: OpenTarget::AndroidStudio
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn rustup_add_all_uses_one_call() {
+        let runner = RecordingRunner::new();
+        rustup_add_all(&runner, &["aarch64-linux-android", "x86_64-linux-android"]).unwrap();
+        assert_eq!(
+            runner.command_lines(),
+            vec!["rustup target add aarch64-linux-android x86_64-linux-android"]
+        );
+    }
+
+    #[test]
+    fn rustup_add_all_repairs_partial_installs_once() {
+        let partial = "error: component 'rust-std' for target 'aarch64-apple-ios' was \
+                       automatically added because it is required for toolchain 'stable', \
+                       but it is partially installed\n";
+        let runner = RecordingRunner::new()
+            .with_failure(1, partial)
+            .with_output("")
+            .with_failure(1, partial);
+        let err = rustup_add_all(&runner, &["aarch64-apple-ios"]).unwrap_err();
+        assert!(rustup_partially_installed(&err));
+        assert_eq!(
+            runner.command_lines(),
+            vec![
+                "rustup target add aarch64-apple-ios",
+                "rustup target remove aarch64-apple-ios",
+                "rustup target add aarch64-apple-ios",
+            ]
+        );
+    }
+
+    #[test]
+    fn rustup_add_all_passes_other_failures_through() {
+        let runner =
+            RecordingRunner::new().with_failure(1, "error: toolchain 'stable' is not installed\n");
+        let err = rustup_add_all(&runner, &["aarch64-apple-ios"]).unwrap_err();
+        assert_eq!(
+            err.stderr_tail(),
+            "error: toolchain 'stable' is not installed"
+        );
+        assert_eq!(runner.command_lines().len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_error_has_the_command_line_dir_and_stderr_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = run(&Invocation::impure("sh")
+            .with_args(&["-c", "echo progress; echo 'error: it broke' >&2; exit 7"])
+            .with_current_dir(dir.path()))
+        .unwrap_err();
+        assert_eq!(err.exit_code(), Some(7));
+        let message = err.to_string();
+        assert!(
+            message.starts_with(
+                "`sh -c 'echo progress; echo '\\''error: it broke'\\'' >&2; exit 7'` failed (exit 7)"
+            ),
+            "unexpected message: {}",
+            message
+        );
+        assert!(message.contains(&format!("{:?}", dir.path())));
+        assert!(message.ends_with("Last lines of stderr:\nerror: it broke"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn combined_capture_keeps_the_streams_in_order() {
+        let (output, status) = run_capture_combined(&Invocation::impure("sh").with_args(&[
+            "-c",
+            "echo out1; echo err1 >&2; echo out2; echo err2 >&2; exit 2",
+        ]))
+        .unwrap();
+        assert_eq!(output, "out1\nerr1\nout2\nerr2\n");
+        assert_eq!(status.code(), Some(2));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipelines_that_take_too_long_are_killed() {
+        let start = Instant::now();
+        let err = pipe_with_timeout(
+            &Invocation::impure("echo").with_arg("crash"),
+            &Invocation::impure("sh").with_args(&["-c", "cat; sleep 10"]),
+            Duration::from_millis(200),
+        )
+        .unwrap_err();
+        assert!(matches!(err, PipeError::TimedOut(_)), "{}", err);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn children_can_close_stdin_early() {
+        // Way more than fits in a pipe buffer, so the writer is guaranteed to
+        // still be writing when the child stops reading.
+        let input = vec![b'y'; 1 << 20];
+        let output = run_with_stdin(
+            &Invocation::impure("sh").with_args(&["-c", "head -c 1 >/dev/null; echo done"]),
+            &input,
+            Stdio::piped(),
+            Stdio::null(),
+        )
+        .unwrap();
+        assert_eq!(output.stdout_str().unwrap(), "done\n");
+        // The exit status still decides if it worked
+        let err = run_with_stdin(
+            &Invocation::impure("sh").with_args(&["-c", "head -c 1 >/dev/null; exit 3"]),
+            &input,
+            Stdio::null(),
+            Stdio::null(),
+        )
+        .unwrap_err();
+        assert!(
+            matches!(err, RunWithStdinError::CommandFailed(_)),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn only_http_status_codes_are_transient() {
+        let err = || {
+            RecordingRunner::new()
+                .with_failure(1, "")
+                .capture(&Invocation::impure("curl"))
+                .unwrap_err()
+        };
+        assert!(!is_transient(
+            &err(),
+            "error[E0308]: mismatched types\n --> src/lib.rs:503:9"
+        ));
+        assert!(!is_transient(
+            &err(),
+            "error: could not compile `app` (429 warnings)"
+        ));
+        assert!(is_transient(
+            &err(),
+            "curl: (22) The requested URL returned error: HTTP/1.1 503 Service Unavailable"
+        ));
+        assert!(is_transient(
+            &err(),
+            "error: failed to download from `https://static.crates.io/crates/x`: failed to get successful HTTP response, got status code 503"
+        ));
+        assert!(is_transient(&err(), "remote: 502 Bad Gateway"));
+    }
+
+    #[test]
+    fn transient_failures_are_retried_with_backoff() {
+        let runner = RecordingRunner::new()
+            .with_failure(1, "HTTP/2 503")
+            .with_failure(1, "HTTP/2 503")
+            .with_output("ok");
+        let start = std::time::Instant::now();
+        let output = run_retrying_transient(&runner, 3, Duration::from_millis(50), || {
+            Invocation::impure("curl")
+        })
+        .unwrap();
+        assert_eq!(output.stdout_str().unwrap(), "ok");
+        assert_eq!(runner.calls().len(), 3);
+        // 50ms, and then 100ms
+        assert!(start.elapsed() >= Duration::from_millis(150));
+
+        let runner = RecordingRunner::new().with_failure(1, "src/lib.rs:503:9: error");
+        run_retrying_transient(&runner, 3, Duration::from_millis(50), || {
+            Invocation::impure("cargo")
+        })
+        .unwrap_err();
+        assert_eq!(runner.calls().len(), 1);
+    }
+
+    #[test]
+    fn git_failures_go_through_run() {
+        if resolve_program("git").is_none() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let git = Git::new(dir.path());
+        git.init().unwrap();
+        let err = run(&git
+            .command()
+            .with_args(&["rev-parse", "--verify", "cargo-mobile-nope"]))
+        .unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("rev-parse --verify cargo-mobile-nope"));
+        assert!(!err.stderr_tail().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipelines_that_finish_in_time_say_if_anything_was_piped() {
+        let timeout = Duration::from_secs(10);
+        let piped = pipe_with_timeout(
+            &Invocation::impure("echo").with_arg("crash"),
+            &Invocation::impure("sh").with_args(&["-c", "grep -q crash"]),
+            timeout,
+        )
+        .unwrap();
+        assert!(piped);
+        // Nothing to pipe, so the receiver never even runs
+        let piped = pipe_with_timeout(
+            &Invocation::impure("true"),
+            &Invocation::impure("cargo-mobile-nonexistent"),
+            timeout,
+        )
+        .unwrap();
+        assert!(!piped);
+    }
+
+    #[test]
+    fn pbxproj_values_are_only_quoted_when_they_need_to_be() {
+        assert_eq!(pbxproj_escape("com.example.app"), "com.example.app");
+        assert_eq!(pbxproj_escape("$(SRCROOT)/gen"), "\"$(SRCROOT)/gen\"");
+        assert_eq!(pbxproj_escape("My App"), "\"My App\"");
+        assert_eq!(pbxproj_escape(""), "\"\"");
+        assert_eq!(
+            pbxproj_escape("say \"hi\"\n\\\tbye"),
+            r#""say \"hi\"\n\\\tbye""#
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shell_hooks_run_from_the_given_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        run_in_shell("echo built > hook.out && test -f hook.out", dir.path()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("hook.out")).unwrap(),
+            "built\n"
+        );
+        assert!(run_in_shell("exit 3", dir.path()).is_err());
+    }
+
+    #[test]
+    fn ci_is_detected_from_any_truthy_ci_var() {
+        let is_ci_with = |vars: &[(&str, &str)]| {
+            is_ci_in(|key| {
+                vars.iter()
+                    .find(|(var, _)| *var == key)
+                    .map(|(_, value)| OsString::from(value))
+            })
+        };
+        assert!(!is_ci_with(&[]));
+        assert!(is_ci_with(&[("CI", "true")]));
+        assert!(is_ci_with(&[("GITHUB_ACTIONS", "1")]));
+        assert!(is_ci_with(&[("JENKINS_URL", "https://ci.example.com")]));
+        assert!(!is_ci_with(&[
+            ("CI", "false"),
+            ("TRAVIS", "0"),
+            ("TF_BUILD", "")
+        ]));
+        assert!(!is_ci_with(&[("NOT_CI", "true")]));
+    }
+
+    #[test]
+    fn missing_programs_are_told_apart_from_failing_ones() {
+        let err = run(&Invocation::impure("cargo-mobile-nonexistent")).unwrap_err();
+        assert!(
+            matches!(&err, CommandError::NotFound { program } if program == "cargo-mobile-nonexistent"),
+            "{}",
+            err
+        );
+        assert_eq!(err.to_string(), "`cargo-mobile-nonexistent` wasn't found");
+        let err =
+            run(&Invocation::impure("cargo").with_arg("cargo-mobile-nonexistent")).unwrap_err();
+        assert!(matches!(err, CommandError::FailedWithContext(_)), "{}", err);
+    }
+
+    #[test]
+    fn strip_uses_the_target_tool_if_given() {
+        let lib = Path::new("libapp.so");
+        assert_eq!(
+            strip_command(lib, None).display(),
+            "strip --strip-debug libapp.so"
+        );
+        let tool = Path::new("/ndk/bin/llvm-strip");
+        assert_eq!(
+            strip_command(lib, Some(tool)).display(),
+            "/ndk/bin/llvm-strip --strip-debug libapp.so"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stripping_non_objects_is_reported_as_such() {
+        if resolve_program("strip").is_none() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("libapp.so");
+        std::fs::write(&lib, "not an object").unwrap();
+        let err = strip_symbols(&lib, None).unwrap_err();
+        assert!(
+            matches!(&err, StripError::InvalidObject { lib: path } if *path == lib),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn rustup_components_are_only_added_if_missing() {
+        let installed =
+            "cargo-x86_64-unknown-linux-gnu\nrust-src\nrustc-x86_64-unknown-linux-gnu\n";
+        let runner = RecordingRunner::new().with_output(installed);
+        rustup_component_add(&runner, "rust-src", None).unwrap();
+        assert_eq!(
+            runner.command_lines(),
+            vec!["rustup component list --installed"]
+        );
+
+        let runner = RecordingRunner::new().with_output(installed);
+        assert!(rustup_component_installed(&runner, "rustc", None).unwrap());
+        let runner = RecordingRunner::new().with_output(installed);
+        assert!(!rustup_component_installed(&runner, "rust", None).unwrap());
+        let runner = RecordingRunner::new().with_output(installed);
+        assert!(!rustup_component_installed(&runner, "rustc-x86_64", None).unwrap());
+
+        let runner = RecordingRunner::new().with_output(installed);
+        rustup_component_add(&runner, "llvm-tools-preview", Some("nightly")).unwrap();
+        assert_eq!(
+            runner.command_lines(),
+            vec![
+                "rustup component list --toolchain nightly --installed",
+                "rustup component add --toolchain nightly llvm-tools-preview",
+            ]
+        );
+    }
+
+    #[test]
+    fn version_codes_increase_with_the_version_until_clamped() {
+        assert_eq!(android_version_code((1, 2, 3)), 1_002_003);
+        assert!(android_version_code((1, 10, 0)) > android_version_code((1, 9, 999)));
+        assert_eq!(android_version_code((0, 1000, 1000)), 999_999);
+        assert_eq!(android_version_code((2100, 0, 1)), 2_100_000_000);
+        assert_eq!(android_version_code((u32::MAX, 0, 0)), 2_100_000_000);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn finished_commands_are_timed() {
+        let command = Invocation::impure("sh").with_args(&["-c", "sleep 0.1 # timed"]);
+        Runner::new(Verbosity::Quiet).capture(&command).unwrap();
+        let timings = CommandTimings::get();
+        let (_, elapsed) = timings
+            .iter()
+            .find(|(line, _)| line.contains("sleep 0.1 # timed"))
+            .expect("command wasn't timed");
+        assert!(*elapsed >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn lib_names_follow_cargo_crate_name_normalization() {
+        assert_eq!(lib_name_from_crate("my-lib"), "my_lib");
+        assert_eq!(lib_name_from_crate("my_lib"), "my_lib");
+        assert_eq!(lib_name_from_crate("a-b-c"), "a_b_c");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn captured_output_is_capped_without_blocking_the_child() {
+        // Way more than fits in a pipe buffer
+        let chatty = Invocation::impure("sh").with_args(&["-c", "yes | head -c 1000000"]);
+        let (output, truncated) = run_and_capture_capped(&chatty, 6).unwrap();
+        assert_eq!(output, "y\ny\ny\n");
+        assert!(truncated);
+        let quiet = Invocation::impure("echo").with_arg("done");
+        let (output, truncated) = run_and_capture_capped(&quiet, 6).unwrap();
+        assert_eq!(output, "done\n");
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn fingerprinted_targets_are_only_added_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let fingerprint_path = rustup_fingerprint_path(dir.path());
+        let triples = ["aarch64-linux-android", "x86_64-linux-android"];
+        let runner = RecordingRunner::new();
+        rustup_add_all_fingerprinted(&runner, &triples, &fingerprint_path).unwrap();
+        assert_eq!(runner.calls().len(), 1);
+        // The order doesn't matter
+        let runner = RecordingRunner::new();
+        let reversed = ["x86_64-linux-android", "aarch64-linux-android"];
+        rustup_add_all_fingerprinted(&runner, &reversed, &fingerprint_path).unwrap();
+        assert!(runner.calls().is_empty());
+        let runner = RecordingRunner::new();
+        rustup_add_all_fingerprinted(&runner, &["aarch64-apple-ios"], &fingerprint_path).unwrap();
+        assert_eq!(runner.calls().len(), 1);
+    }
+
+    #[test]
+    fn only_allowlisted_env_vars_are_passed_through() {
+        let invocation = allowlisted_command("cargo", &["PATH", "CARGO_MOBILE_NOT_SET"]);
+        assert!(invocation.is_pure());
+        let keys = invocation
+            .env_vars()
+            .iter()
+            .map(|(key, _)| key.as_os_str())
+            .collect::<Vec<_>>();
+        assert_eq!(keys, [OsStr::new("PATH")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn allowlisted_runs_dont_see_anything_else() {
+        run_with_env_allowlist(
+            "sh",
+            &["-c", r#"test -n "$PATH" && test -z "$HOME""#],
+            &["PATH"],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn only_capture_groups_are_extracted() {
+        let re = Regex::new(r"(?P<major>\d+)\.(\d+)(?:\.(\d+))?").unwrap();
+        assert_eq!(
+            extract_captures(&re, "ndk 21.4 ok"),
+            Some(vec![Some("21"), Some("4"), None])
+        );
+        assert_eq!(extract_captures(&re, "no version"), None);
+        assert_eq!(extract_named(&re, "ndk 21.4.7075529", "major"), Some("21"));
+        assert_eq!(extract_named(&re, "ndk 21.4", "minor"), None);
+    }
+
+    #[test]
+    fn finalized_paths_lead_with_existing_entries_once_each() {
+        let dir = tempfile::tempdir().unwrap();
+        let [sdk, typo, ndk] = ["sdk", "typo", "ndk"].map(|name| dir.path().join(name));
+        fs::create_dir_all(&sdk).unwrap();
+        fs::create_dir_all(&ndk).unwrap();
+        let path = finalize_path(&[sdk.clone(), typo.clone(), ndk.clone(), sdk.clone()]).unwrap();
+        let paths = std::env::split_paths(&path).collect::<Vec<_>>();
+        assert_eq!(paths[..2], [sdk.clone(), ndk]);
+        assert_eq!(paths.iter().filter(|path| **path == sdk).count(), 1);
+        assert!(!paths.contains(&typo));
+    }
+
+    #[test]
+    fn toolchains_are_listed_by_name() {
+        let runner = RecordingRunner::new().with_output(
+            "stable-x86_64-unknown-linux-gnu (default)\nnightly-2021-06-01-x86_64-unknown-linux-gnu\n",
+        );
+        assert_eq!(
+            rustup_toolchains(&runner).unwrap(),
+            [
+                "stable-x86_64-unknown-linux-gnu",
+                "nightly-2021-06-01-x86_64-unknown-linux-gnu"
+            ]
+        );
+        let runner = RecordingRunner::new().with_output("no installed toolchains\n");
+        assert!(rustup_toolchains(&runner).unwrap().is_empty());
+        let command = cargo::CargoCommand::new("build")
+            .with_toolchain("nightly")
+            .into_command_impure()
+            .unwrap();
+        assert_eq!(command.args(), ["+nightly", "build"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn teed_output_ends_up_in_the_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_file = dir.path().join("build.log");
+        std::fs::write(&log_file, "from an earlier build\n").unwrap();
+        let status = run_tee(
+            &Invocation::impure("sh").with_args(&[
+                "-c",
+                "echo compiling; echo warning: unused >&2; echo finished; exit 3",
+            ]),
+            &log_file,
+        )
+        .unwrap();
+        assert_eq!(status.code(), Some(3));
+        let log = std::fs::read_to_string(&log_file).unwrap();
+        let mut lines = log.lines().collect::<Vec<_>>();
+        // The streams are read separately, so only the order within each one
+        // is certain.
+        assert!(log.find("compiling").unwrap() < log.find("finished").unwrap());
+        lines.sort_unstable();
+        assert_eq!(lines, ["compiling", "finished", "warning: unused"]);
+    }
+}
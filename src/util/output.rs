@@ -0,0 +1,85 @@
+//! Machine-readable output for `--format json`. In that mode, stdout only
+//! gets events, one JSON object per line, and everything meant for humans
+//! (including the output of the commands we run) goes to stderr instead.
+
+use super::cli::{Diagnostic, Report};
+use crate::{device::DeviceSummary, doctor::Check, opts::OutputFormat, os};
+use once_cell_regex::exports::once_cell::sync::OnceCell;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{self, Write as _},
+    path::Path,
+    sync::Mutex,
+};
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event<'a> {
+    /// Something a command produced, like a lib, an APK, or an IPA.
+    Artifact { kind: &'a str, path: &'a Path },
+    /// A connected device, as listed by the `devices` subcommands.
+    Device(&'a DeviceSummary),
+    /// One of the checks `cargo mobile doctor` made.
+    Check(&'a Check),
+    /// A problem found before generating a project.
+    Diagnostic(&'a Diagnostic),
+    /// A platform project that `cargo mobile init` generated.
+    Project { platform: &'a str, path: &'a Path },
+    /// How the command went, which is always the last event. Failures come
+    /// with the report that would've been shown.
+    Result {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        report: Option<&'a Report>,
+    },
+}
+
+static GLOBAL: OnceCell<Output> = OnceCell::new();
+
+#[derive(Debug)]
+struct Output {
+    // Only present in JSON mode, since that's the only time stdout is ours.
+    events: Option<Mutex<File>>,
+}
+
+/// Sets up output in the given format, which in JSON mode means diverting
+/// stdout to stderr. This only works once; later calls are ignored.
+pub fn init_global(format: OutputFormat) -> io::Result<()> {
+    if GLOBAL.get().is_some() {
+        log::warn!("global output was already initialized");
+        return Ok(());
+    }
+    let events = if format.json() {
+        Some(Mutex::new(os::divert_stdout()?))
+    } else {
+        None
+    };
+    // We checked above, and this all happens before anything else runs.
+    let _ = GLOBAL.set(Output { events });
+    Ok(())
+}
+
+pub fn is_json() -> bool {
+    GLOBAL.get().map_or(false, |output| output.events.is_some())
+}
+
+/// Writes `event` to stdout in JSON mode, and does nothing otherwise; the
+/// human-readable version is whatever the caller prints.
+pub fn emit(event: &Event<'_>) {
+    let events = match GLOBAL.get().and_then(|output| output.events.as_ref()) {
+        Some(events) => events,
+        None => return,
+    };
+    let line = match serde_json::to_string(event) {
+        Ok(line) => line,
+        Err(err) => {
+            log::error!("failed to serialize event {:?}: {}", event, err);
+            return;
+        }
+    };
+    let mut events = events.lock().unwrap_or_else(|err| err.into_inner());
+    if let Err(err) = writeln!(events, "{}", line).and_then(|()| events.flush()) {
+        log::error!("failed to write event {:?}: {}", event, err);
+    }
+}
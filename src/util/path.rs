@@ -2,7 +2,7 @@ use path_abs::PathAbs;
 use std::{
     fmt::{self, Display},
     io,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 use thiserror::Error;
 
@@ -70,42 +70,219 @@ pub fn unprefix_path(
         })
 }
 
-fn common_root(abs_src: &Path, abs_dest: &Path) -> PathBuf {
+// The default filesystems on macOS and Windows are case-insensitive.
+const CASE_INSENSITIVE_FS: bool = cfg!(any(target_os = "macos", windows));
+
+fn components_eq(a: Component<'_>, b: Component<'_>) -> bool {
+    if CASE_INSENSITIVE_FS {
+        a.as_os_str().to_string_lossy().to_lowercase()
+            == b.as_os_str().to_string_lossy().to_lowercase()
+    } else {
+        a == b
+    }
+}
+
+/// Like `Path::strip_prefix`, but ignores case on platforms where the
+/// filesystem does.
+pub fn strip_prefix_ci<'a>(path: &'a Path, prefix: &Path) -> Option<&'a Path> {
+    let mut components = path.components();
+    for prefix_component in prefix.components() {
+        match components.next() {
+            Some(component) if components_eq(component, prefix_component) => (),
+            _ => return None,
+        }
+    }
+    Some(components.as_path())
+}
+
+// Returns `None` if the paths don't even share a root, i.e. when they're on
+// different drives on Windows.
+fn common_root(abs_src: &Path, abs_dest: &Path) -> Option<PathBuf> {
     let mut dest_root = abs_dest.to_owned();
     loop {
-        if abs_src.starts_with(&dest_root) {
-            return dest_root;
-        } else {
-            if !dest_root.pop() {
-                unreachable!("`abs_src` and `abs_dest` have no common root");
+        if strip_prefix_ci(abs_src, &dest_root).is_some() {
+            return Some(dest_root);
+        } else if !dest_root.pop() {
+            return None;
+        }
+    }
+}
+
+// Resolves `.` and `..` components without touching the filesystem. Without
+// this, a `..` in `abs_relative_to` would get counted as a component to climb
+// out of, rather than cancelling one out.
+pub(crate) fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            // Popping the root is a no-op, which is exactly what `/..` means
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[derive(Debug)]
+pub enum RelativizeError {
+    NotAbsolute { path: PathBuf },
+    CanonicalizationFailed { path: PathBuf, cause: io::Error },
+}
+
+impl Display for RelativizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAbsolute { path } => write!(
+                f,
+                "Can't compute a relative path from {:?}, since it isn't absolute.",
+                path
+            ),
+            Self::CanonicalizationFailed { path, cause } => {
+                write!(f, "Failed to canonicalize {:?}: {}", path, cause)
+            }
+        }
+    }
+}
+
+// Canonicalizes as much of `path` as exists, so that symlinked dirs along the
+// way get resolved, and then tacks the rest back on. The parts that don't
+// exist yet can't be symlinks, so this is as canonical as it gets.
+pub(crate) fn canonicalize_existing(path: &Path) -> Result<PathBuf, RelativizeError> {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        match existing.canonicalize() {
+            Ok(canonical) => {
+                return Ok(rest
+                    .iter()
+                    .rev()
+                    .fold(canonical, |canonical, component| canonical.join(component)))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                match (existing.parent(), existing.file_name()) {
+                    (Some(parent), Some(file_name)) => {
+                        rest.push(file_name);
+                        existing = parent;
+                    }
+                    // Not even the root exists, so there's nothing to resolve.
+                    _ => return Ok(path.to_owned()),
+                }
+            }
+            Err(cause) => {
+                return Err(RelativizeError::CanonicalizationFailed {
+                    path: existing.to_owned(),
+                    cause,
+                })
             }
         }
     }
 }
 
-/// Transforms `abs_path` to be relative to `abs_relative_to`.
-pub fn relativize_path(abs_path: impl AsRef<Path>, abs_relative_to: impl AsRef<Path>) -> PathBuf {
+/// Transforms `abs_path` to be relative to `abs_relative_to`. Symlinked dirs
+/// are resolved first, but not `abs_path` itself, so that a link to a symlink
+/// still points to the symlink. If there's no relative path between the two,
+/// i.e. they're on different drives, this gives the absolute path instead,
+/// which works just as well as a symlink source.
+pub fn relativize_path(
+    abs_path: impl AsRef<Path>,
+    abs_relative_to: impl AsRef<Path>,
+) -> Result<PathBuf, RelativizeError> {
     let (abs_path, abs_relative_to) = (abs_path.as_ref(), abs_relative_to.as_ref());
-    assert!(abs_path.is_absolute());
-    assert!(abs_relative_to.is_absolute());
-    let (path, relative_to) = {
-        let common_root = common_root(abs_path, abs_relative_to);
-        let path = abs_path.strip_prefix(&common_root).unwrap();
-        let relative_to = abs_relative_to.strip_prefix(&common_root).unwrap();
-        (path, relative_to)
+    for path in &[abs_path, abs_relative_to] {
+        if !path.is_absolute() {
+            return Err(RelativizeError::NotAbsolute {
+                path: path.to_path_buf(),
+            });
+        }
+    }
+    let norm_path = normalize_lexically(abs_path);
+    let norm_path = match (norm_path.parent(), norm_path.file_name()) {
+        (Some(parent), Some(file_name)) => canonicalize_existing(parent)?.join(file_name),
+        _ => norm_path,
+    };
+    let norm_relative_to = canonicalize_existing(&normalize_lexically(abs_relative_to))?;
+    let (path, relative_to) = if let Ok(path) = norm_path.strip_prefix(&norm_relative_to) {
+        // This is the usual case when linking into a subdir, and there's no
+        // need to look for a common root or climb out of anything.
+        (path.to_owned(), PathBuf::new())
+    } else if let Some(common_root) = common_root(&norm_path, &norm_relative_to) {
+        let path = strip_prefix_ci(&norm_path, &common_root).unwrap();
+        let relative_to = strip_prefix_ci(&norm_relative_to, &common_root).unwrap();
+        (path.to_owned(), relative_to.to_owned())
+    } else {
+        log::info!(
+            "{:?} and {:?} have no common root, so using an absolute path",
+            norm_path,
+            norm_relative_to
+        );
+        return Ok(norm_path);
     };
     let mut rel_path = PathBuf::new();
     for _ in 0..relative_to.iter().count() {
         rel_path.push("..");
     }
-    let rel_path = rel_path.join(path);
-    log::info!(
+    let mut rel_path = rel_path.join(path);
+    if rel_path.as_os_str().is_empty() {
+        // An empty path isn't a valid symlink source, so we make "same path"
+        // explicit.
+        rel_path.push(".");
+    }
+    log::debug!(
         "{:?} relative to {:?} is {:?}",
         abs_path,
         abs_relative_to,
         rel_path
     );
-    rel_path
+    Ok(rel_path)
+}
+
+#[derive(Debug)]
+pub enum RpathError {
+    RelativizeFailed(RelativizeError),
+    InvalidUtf8 { path: PathBuf },
+}
+
+impl Display for RpathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RelativizeFailed(err) => write!(f, "Can't compute an rpath: {}", err),
+            Self::InvalidUtf8 { path } => write!(
+                f,
+                "Can't use {:?} as an rpath, since it isn't valid UTF-8.",
+                path
+            ),
+        }
+    }
+}
+
+/// Gets the `@loader_path`-relative path to `to` for something loaded from
+/// `from`, which is the form linker flags like `-rpath` want.
+pub fn rpath_relative(from: &Path, to: &Path) -> Result<String, RpathError> {
+    let rel = relativize_path(to, from).map_err(RpathError::RelativizeFailed)?;
+    if rel.is_absolute() {
+        // Absolute rpaths are fine too; they just aren't portable.
+        return rel
+            .to_str()
+            .map(ToOwned::to_owned)
+            .ok_or_else(|| RpathError::InvalidUtf8 { path: rel.clone() });
+    }
+    let components = rel
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .map(|component| {
+            component
+                .as_os_str()
+                .to_str()
+                .ok_or_else(|| RpathError::InvalidUtf8 { path: rel.clone() })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(std::iter::once("@loader_path")
+        .chain(components)
+        .collect::<Vec<_>>()
+        .join("/"))
 }
 
 #[derive(Debug)]
@@ -161,3 +338,183 @@ pub fn under_root(
 ) -> Result<bool, NormalizationError> {
     normalize_path(root.as_ref().join(path)).map(|norm| norm.starts_with(root))
 }
+
+/// Walks upward from `start` to find the enclosing Cargo project, the same way
+/// git finds `.git`. If some ancestor has our config file alongside its
+/// `Cargo.toml`, the nearest such one wins; otherwise, this is just the
+/// nearest dir with a `Cargo.toml`.
+pub fn find_project_root(start: &Path) -> Option<PathBuf> {
+    let marker = crate::config::file_name();
+    let mut nearest_manifest = None;
+    for dir in start.ancestors() {
+        if dir.join("Cargo.toml").is_file() {
+            if dir.join(&marker).is_file() {
+                log::info!("found project root at {:?}", dir);
+                return Some(dir.to_owned());
+            }
+            if nearest_manifest.is_none() {
+                nearest_manifest = Some(dir.to_owned());
+            }
+        }
+    }
+    log::info!(
+        "no {:?} found above {:?}; using nearest manifest dir {:?}",
+        marker,
+        start,
+        nearest_manifest
+    );
+    nearest_manifest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::fs;
+
+    // Every component is a plain dir name, aside from `link`, which is a
+    // symlink to `a/b` on platforms where we can make one without special
+    // permissions.
+    fn setup(root: &Path) {
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(root.join("a/b"), root.join("link")).unwrap();
+        #[cfg(not(unix))]
+        fs::create_dir_all(root.join("link")).unwrap();
+    }
+
+    fn components() -> impl Strategy<Value = Vec<&'static str>> {
+        prop::collection::vec(prop::sample::select(vec!["a", "b", "c", "link"]), 0..4)
+    }
+
+    proptest! {
+        #[test]
+        fn relative_paths_lead_back_to_the_source(src in components(), dest in components()) {
+            let dir = tempfile::tempdir().unwrap();
+            let root = dir.path().canonicalize().unwrap();
+            setup(&root);
+            let (src, dest) = (root.join(src.join("/")), root.join(dest.join("/")));
+            fs::create_dir_all(&src).unwrap();
+            fs::create_dir_all(&dest).unwrap();
+            let rel = relativize_path(&src, &dest).unwrap();
+            prop_assert!(rel.is_relative());
+            prop_assert_eq!(
+                dest.join(&rel).canonicalize().unwrap(),
+                src.canonicalize().unwrap()
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_dirs_are_climbed_out_of_for_real() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("deep/er/real/gen")).unwrap();
+        fs::create_dir_all(root.join("assets")).unwrap();
+        std::os::unix::fs::symlink(root.join("deep/er/real"), root.join("link")).unwrap();
+        // Lexically this would only be one `..` too many, but `link/gen` is
+        // really 4 dirs down.
+        let dest = root.join("link/gen");
+        let rel = relativize_path(root.join("assets"), &dest).unwrap();
+        assert_eq!(rel, Path::new("../../../../assets"));
+        assert_eq!(dest.join(rel).canonicalize().unwrap(), root.join("assets"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn paths_without_a_common_root_stay_absolute() {
+        let (src, dest) = (Path::new(r"Q:\assets"), Path::new(r"R:\gen\android"));
+        assert_eq!(relativize_path(src, dest).unwrap(), src);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn paths_with_only_the_root_in_common_climb_all_the_way_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().canonicalize().unwrap();
+        let rel = relativize_path("/nonexistent/assets", &dest).unwrap();
+        let climbs = dest.components().count() - 1;
+        assert_eq!(
+            rel,
+            Path::new(&"../".repeat(climbs)).join("nonexistent/assets")
+        );
+    }
+
+    #[test]
+    fn prefixes_are_stripped_by_component_and_case_only_where_the_fs_ignores_it() {
+        let path = Path::new("/Users/Shinji/Projects/app");
+        assert_eq!(
+            strip_prefix_ci(path, Path::new("/Users/Shinji")),
+            Some(Path::new("Projects/app"))
+        );
+        assert_eq!(
+            strip_prefix_ci(path, Path::new("/users/shinji")).is_some(),
+            CASE_INSENSITIVE_FS
+        );
+        // Only whole components count
+        assert_eq!(strip_prefix_ci(path, Path::new("/Users/Shin")), None);
+        assert_eq!(
+            strip_prefix_ci(path, Path::new("/Users/Shinji/Projects/app/src")),
+            None
+        );
+    }
+
+    #[test]
+    fn the_nearest_configured_project_wins_over_nearer_manifests() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let member = root.join("crates/app");
+        fs::create_dir_all(member.join("src")).unwrap();
+        fs::write(root.join("Cargo.toml"), "").unwrap();
+        fs::write(member.join("Cargo.toml"), "").unwrap();
+        assert_eq!(find_project_root(&member.join("src")), Some(member.clone()));
+        fs::write(root.join(crate::config::file_name()), "").unwrap();
+        assert_eq!(
+            find_project_root(&member.join("src")),
+            Some(root.to_owned())
+        );
+        assert_eq!(find_project_root(Path::new("/")), None);
+    }
+
+    #[test]
+    fn rpaths_are_relative_to_the_loader() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let (bin, lib) = (root.join("app/bin"), root.join("app/lib"));
+        assert_eq!(rpath_relative(&bin, &lib).unwrap(), "@loader_path/../lib");
+        assert_eq!(rpath_relative(&root, &lib).unwrap(), "@loader_path/app/lib");
+        assert!(matches!(
+            rpath_relative(Path::new("app/bin"), &lib),
+            Err(RpathError::RelativizeFailed(
+                RelativizeError::NotAbsolute { .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn descendants_are_relativized_without_climbing() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        assert_eq!(
+            relativize_path(root.join("gen/apple/assets"), &root).unwrap(),
+            Path::new("gen/apple/assets")
+        );
+        assert_eq!(relativize_path(&root, &root).unwrap(), Path::new("."));
+    }
+
+    #[test]
+    fn dot_components_cancel_out_instead_of_being_climbed() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let dest = root.join("gen/./apple/../android/app");
+        assert_eq!(
+            relativize_path(root.join("assets"), &dest).unwrap(),
+            Path::new("../../../assets")
+        );
+        assert_eq!(
+            relativize_path(root.join("gen/../assets"), root.join("./assets")).unwrap(),
+            Path::new(".")
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use colored::{Color, Colorize as _};
 use std::{
+    cell::RefCell,
+    collections::VecDeque,
     fmt::Display,
     io::{self, Write},
 };
@@ -38,6 +40,75 @@ pub fn default(
     })
 }
 
+/// Something that can answer prompts. Anything that asks questions should go
+/// through one of these, so that the answers can be given programmatically
+/// instead of typed in.
+pub trait Prompter {
+    /// Asks for a line of input, which is taken to be `default` if it's empty.
+    fn default(
+        &self,
+        msg: &str,
+        default: Option<&str>,
+        default_color: Option<Color>,
+    ) -> io::Result<String>;
+}
+
+/// Asks the person at the terminal.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stdin;
+
+impl Prompter for Stdin {
+    fn default(
+        &self,
+        msg: &str,
+        default: Option<&str>,
+        default_color: Option<Color>,
+    ) -> io::Result<String> {
+        self::default(msg, default, default_color)
+    }
+}
+
+/// Answers prompts with a list of canned responses, in order. An empty
+/// response picks the default, just like pressing enter would.
+#[derive(Debug, Default)]
+pub struct Scripted {
+    answers: RefCell<VecDeque<String>>,
+}
+
+impl Scripted {
+    pub fn new(answers: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            answers: RefCell::new(answers.into_iter().map(Into::into).collect()),
+        }
+    }
+
+    /// How many answers haven't been used yet.
+    pub fn remaining(&self) -> usize {
+        self.answers.borrow().len()
+    }
+}
+
+impl Prompter for Scripted {
+    fn default(
+        &self,
+        msg: &str,
+        default: Option<&str>,
+        _default_color: Option<Color>,
+    ) -> io::Result<String> {
+        let answer = self.answers.borrow_mut().pop_front().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("ran out of scripted answers when asked {:?}", msg),
+            )
+        })?;
+        log::info!("answering {:?} with {:?}", msg, answer);
+        Ok(match default {
+            Some(default) if answer.is_empty() => default.to_owned(),
+            _ => answer,
+        })
+    }
+}
+
 yes_or_no!(YesOrNo);
 
 pub fn yes_no(msg: impl Display, default: Option<YesOrNo>) -> io::Result<Option<YesOrNo>> {
@@ -111,3 +182,55 @@ pub fn list(
         }
     }
 }
+
+/// Asks a yes/no question, unless there's nobody around to answer it.
+///
+/// If `assume_yes` is set, this answers yes without asking. Otherwise, if stdin
+/// isn't a TTY or we're on CI, `default` is returned instead of blocking forever.
+pub fn confirm(question: &str, default: bool, assume_yes: bool) -> io::Result<bool> {
+    let interactive = atty::is(atty::Stream::Stdin) && !super::is_ci();
+    confirm_if(question, default, assume_yes, interactive)
+}
+
+fn confirm_if(
+    question: &str,
+    default: bool,
+    assume_yes: bool,
+    interactive: bool,
+) -> io::Result<bool> {
+    if assume_yes {
+        log::info!("assuming yes for {:?}", question);
+        return Ok(true);
+    }
+    if !interactive {
+        log::info!(
+            "not interactive; using default answer {:?} for {:?}",
+            default,
+            question
+        );
+        return Ok(default);
+    }
+    let default = if default { YesOrNo::Yes } else { YesOrNo::No };
+    loop {
+        if let Some(answer) = yes_no(question, Some(default))? {
+            break Ok(answer.yes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn confirm_uses_the_default_without_a_tty() {
+        assert!(confirm_if("Continue?", true, false, false).unwrap());
+        assert!(!confirm_if("Continue?", false, false, false).unwrap());
+    }
+
+    #[test]
+    fn confirm_assumes_yes_when_asked_to() {
+        assert!(confirm_if("Continue?", false, true, true).unwrap());
+        assert!(confirm_if("Continue?", false, true, false).unwrap());
+    }
+}
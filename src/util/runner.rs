@@ -0,0 +1,826 @@
+use super::{CommandCause, CommandContext, CommandError, CommandTimings};
+use crate::{opts::NoiseLevel, os};
+use once_cell_regex::exports::once_cell::sync::OnceCell;
+use std::{
+    collections::VecDeque,
+    ffi::{OsStr, OsString},
+    fs,
+    io::{self, BufRead as _, BufReader, Write as _},
+    path::{Path, PathBuf},
+    process::{self, ChildStderr, ChildStdin, ChildStdout, ExitStatus, Stdio},
+    str::{self, Utf8Error},
+    sync::Mutex,
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Everything needed to run a program. Unlike a `bossy::Command`, all of this
+/// stays inspectable, which is what lets a [`Runner`] resolve the program, set
+/// the working directory, track the child, and record an exact command line.
+#[derive(Clone, Debug)]
+pub struct Invocation {
+    program: OsString,
+    args: Vec<OsString>,
+    env: Vec<(OsString, OsString)>,
+    pure: bool,
+    current_dir: Option<PathBuf>,
+}
+
+impl Invocation {
+    fn new(program: impl AsRef<OsStr>, pure: bool) -> Self {
+        Self {
+            program: program.as_ref().to_owned(),
+            args: Default::default(),
+            env: Default::default(),
+            pure,
+            current_dir: Default::default(),
+        }
+    }
+
+    /// Runs `program` with our own environment, plus anything added on top.
+    pub fn impure(program: impl AsRef<OsStr>) -> Self {
+        Self::new(program, false)
+    }
+
+    /// Runs `program` with only the env vars that get explicitly added.
+    pub fn pure(program: impl AsRef<OsStr>) -> Self {
+        Self::new(program, true)
+    }
+
+    /// Like [`Invocation::impure`], but the program and args are split from
+    /// `command` on whitespace.
+    pub fn impure_parse(command: impl AsRef<str>) -> Self {
+        let mut words = command.as_ref().split_whitespace();
+        let program = words
+            .next()
+            .expect("developer error: `Invocation::impure_parse` got an empty command");
+        Self::impure(program).with_args(words)
+    }
+
+    pub fn add_arg(&mut self, arg: impl AsRef<OsStr>) -> &mut Self {
+        self.args.push(arg.as_ref().to_owned());
+        self
+    }
+
+    pub fn with_arg(mut self, arg: impl AsRef<OsStr>) -> Self {
+        self.add_arg(arg);
+        self
+    }
+
+    pub fn add_args(&mut self, args: impl IntoIterator<Item = impl AsRef<OsStr>>) -> &mut Self {
+        for arg in args {
+            self.add_arg(arg);
+        }
+        self
+    }
+
+    pub fn with_args(mut self, args: impl IntoIterator<Item = impl AsRef<OsStr>>) -> Self {
+        self.add_args(args);
+        self
+    }
+
+    /// Adds each whitespace-separated word in `arg_str` as its own arg.
+    pub fn add_parsed_args(&mut self, arg_str: impl AsRef<str>) -> &mut Self {
+        self.add_args(arg_str.as_ref().split_whitespace())
+    }
+
+    pub fn with_parsed_args(mut self, arg_str: impl AsRef<str>) -> Self {
+        self.add_parsed_args(arg_str);
+        self
+    }
+
+    pub fn add_env_var(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> &mut Self {
+        self.env
+            .push((key.as_ref().to_owned(), value.as_ref().to_owned()));
+        self
+    }
+
+    pub fn with_env_var(mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> Self {
+        self.add_env_var(key, value);
+        self
+    }
+
+    pub fn add_env_vars(
+        &mut self,
+        vars: impl IntoIterator<Item = (impl AsRef<OsStr>, impl AsRef<OsStr>)>,
+    ) -> &mut Self {
+        for (key, value) in vars {
+            self.add_env_var(key, value);
+        }
+        self
+    }
+
+    pub fn with_env_vars(
+        mut self,
+        vars: impl IntoIterator<Item = (impl AsRef<OsStr>, impl AsRef<OsStr>)>,
+    ) -> Self {
+        self.add_env_vars(vars);
+        self
+    }
+
+    /// Runs the program from within `dir` instead of our own working directory.
+    pub fn with_current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// The program as it was given, i.e. before it's looked up on `PATH`.
+    pub fn program(&self) -> &OsStr {
+        &self.program
+    }
+
+    pub fn args(&self) -> &[OsString] {
+        &self.args
+    }
+
+    /// The env vars explicitly set, in the order they were added.
+    pub fn env_vars(&self) -> &[(OsString, OsString)] {
+        &self.env
+    }
+
+    pub fn is_pure(&self) -> bool {
+        self.pure
+    }
+
+    pub fn current_dir(&self) -> Option<&Path> {
+        self.current_dir.as_deref()
+    }
+
+    /// The program and args with each one quoted as needed, so that it can be
+    /// pasted into a shell as-is.
+    pub fn command_line(&self) -> String {
+        let mut command_line = shell_quote(&self.program);
+        for arg in &self.args {
+            command_line.push(' ');
+            command_line.push_str(&shell_quote(arg));
+        }
+        command_line
+    }
+
+    fn program_name(&self) -> String {
+        self.program.to_string_lossy().into_owned()
+    }
+
+    // The `PATH` the program gets looked up on, which is our own unless the
+    // invocation sets one.
+    fn path_var(&self) -> Option<OsString> {
+        self.env
+            .iter()
+            .rev()
+            .find(|(key, _)| {
+                if cfg!(windows) {
+                    key.to_str()
+                        .map_or(false, |key| key.eq_ignore_ascii_case("PATH"))
+                } else {
+                    key == "PATH"
+                }
+            })
+            .map(|(_, value)| value.clone())
+            .or_else(|| std::env::var_os("PATH"))
+    }
+
+    fn to_std(&self) -> process::Command {
+        let program = self
+            .program
+            .to_str()
+            .and_then(|program| super::resolve_program_in(program, self.path_var().as_deref()));
+        let mut command =
+            process::Command::new(program.as_deref().map_or(&*self.program, Path::as_os_str));
+        command.args(&self.args);
+        if self.pure {
+            command.env_clear();
+        }
+        command.envs(self.env.iter().map(|(key, value)| (key, value)));
+        if let Some(current_dir) = &self.current_dir {
+            command.current_dir(current_dir);
+        }
+        command
+    }
+}
+
+/// The output of a program that ran to completion.
+#[derive(Clone, Debug)]
+pub struct Output {
+    status: ExitStatus,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl Output {
+    pub fn status(&self) -> ExitStatus {
+        self.status
+    }
+
+    pub fn stdout(&self) -> &[u8] {
+        &self.stdout
+    }
+
+    pub fn stdout_str(&self) -> Result<&str, Utf8Error> {
+        str::from_utf8(&self.stdout)
+    }
+
+    pub fn stderr(&self) -> &[u8] {
+        &self.stderr
+    }
+
+    pub fn stderr_str(&self) -> Result<&str, Utf8Error> {
+        str::from_utf8(&self.stderr)
+    }
+}
+
+/// Something that can run an [`Invocation`] to completion. Helpers that run
+/// programs can take one of these, so that they can be exercised without
+/// actually spawning anything.
+pub trait CommandRunner {
+    /// Runs `invocation` with its output shown as it happens.
+    fn stream(&self, invocation: &Invocation) -> Result<ExitStatus, CommandError>;
+
+    /// Runs `invocation` with its output captured.
+    fn capture(&self, invocation: &Invocation) -> Result<Output, CommandError>;
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+impl Default for Verbosity {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+impl Verbosity {
+    pub fn from_flags(noise_level: NoiseLevel, quiet: bool) -> Self {
+        if quiet {
+            Self::Quiet
+        } else if noise_level.polite() {
+            Self::Normal
+        } else {
+            Self::Verbose
+        }
+    }
+}
+
+// Single quotes are the only thing that need escaping within single quotes,
+// and anything made up solely of these characters doesn't need quoting at all.
+fn shell_quote(s: &OsStr) -> String {
+    let s = s.to_string_lossy();
+    let safe = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-+=/.,:@%".contains(c));
+    if safe {
+        s.into_owned()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+// The last few lines of `stderr`, which is usually where the actual error is.
+pub(super) fn tail_lines(stderr: &str) -> String {
+    let mut tail = stderr
+        .lines()
+        .rev()
+        .take(STDERR_TAIL_LINES)
+        .collect::<Vec<_>>();
+    tail.reverse();
+    tail.join("\n")
+}
+
+// Copies lines from `reader` to our stderr (if `show` is set) as they come in,
+// keeping the last few around for error reporting.
+fn tee_stderr(reader: impl io::Read, show: bool) -> io::Result<String> {
+    let mut reader = BufReader::new(reader);
+    let mut tail = VecDeque::with_capacity(STDERR_TAIL_LINES);
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        if show {
+            let mut stderr = io::stderr();
+            stderr.write_all(&line)?;
+            stderr.flush()?;
+        }
+        if tail.len() == STDERR_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(String::from_utf8_lossy(&line).trim_end().to_owned());
+        line.clear();
+    }
+    Ok(Vec::from(tail).join("\n"))
+}
+
+// What's needed to explain a child's failure, once it's been spawned.
+#[derive(Debug)]
+struct Description {
+    program: String,
+    command_line: String,
+    current_dir: Option<PathBuf>,
+}
+
+impl Description {
+    fn error(&self, cause: CommandCause, stderr_tail: String) -> CommandError {
+        CommandError::from_context(
+            &self.program,
+            CommandContext {
+                command: self.command_line.clone(),
+                current_dir: self
+                    .current_dir
+                    .clone()
+                    .or_else(|| std::env::current_dir().ok()),
+                stderr_tail,
+                hint: None,
+                cause,
+            },
+        )
+    }
+
+    fn finish(
+        &self,
+        start: Instant,
+        status: ExitStatus,
+        stderr_tail: String,
+    ) -> Result<(), CommandError> {
+        CommandTimings::record(self.command_line.clone(), start.elapsed());
+        if status.success() {
+            Ok(())
+        } else {
+            Err(self.error(CommandCause::Exited(status), stderr_tail))
+        }
+    }
+}
+
+/// A child spawned by a [`Runner`], which stays registered to be killed if
+/// we're interrupted until it's been waited on.
+#[derive(Debug)]
+pub struct Child {
+    inner: process::Child,
+    description: Description,
+    start: Instant,
+    stderr_tee: Option<JoinHandle<io::Result<String>>>,
+    _guard: os::ChildGuard,
+}
+
+impl Child {
+    pub fn id(&self) -> u32 {
+        self.inner.id()
+    }
+
+    pub fn kill(&mut self) -> io::Result<()> {
+        self.inner.kill()
+    }
+
+    pub fn stdin(&mut self) -> Option<&mut ChildStdin> {
+        self.inner.stdin.as_mut()
+    }
+
+    /// Takes the child's stdin, so that it gets closed once it's dropped.
+    pub fn take_stdin(&mut self) -> Option<ChildStdin> {
+        self.inner.stdin.take()
+    }
+
+    pub fn take_stdout(&mut self) -> Option<ChildStdout> {
+        self.inner.stdout.take()
+    }
+
+    pub fn take_stderr(&mut self) -> Option<ChildStderr> {
+        self.inner.stderr.take()
+    }
+
+    // Shows stderr as it comes in (or doesn't, if `show` isn't set), while
+    // keeping the end of it around in case the child fails.
+    fn tee_stderr(&mut self, show: bool) {
+        if let Some(stderr) = self.inner.stderr.take() {
+            self.stderr_tee = Some(thread::spawn(move || tee_stderr(stderr, show)));
+        }
+    }
+
+    /// Waits for the child to exit, which is an error unless it succeeded.
+    pub fn wait(mut self) -> Result<ExitStatus, CommandError> {
+        let status = self.inner.wait();
+        let stderr_tail = self
+            .stderr_tee
+            .take()
+            .map(|tee| {
+                tee.join()
+                    .expect("developer error: stderr tee thread panicked")
+                    .unwrap_or_else(|err| {
+                        log::warn!(
+                            "failed to read stderr of `{}`: {}",
+                            self.description.command_line,
+                            err
+                        );
+                        Default::default()
+                    })
+            })
+            .unwrap_or_default();
+        let status = status.map_err(|err| {
+            self.description
+                .error(CommandCause::WaitFailed(err), Default::default())
+        })?;
+        self.description
+            .finish(self.start, status, stderr_tail)
+            .map(|()| status)
+    }
+
+    /// Waits for the child to exit, collecting whatever was piped. Like
+    /// [`Child::wait`], this is an error unless the child succeeded.
+    pub fn wait_for_output(self) -> Result<Output, CommandError> {
+        let Self {
+            inner,
+            description,
+            start,
+            _guard,
+            ..
+        } = self;
+        let output = inner
+            .wait_with_output()
+            .map_err(|err| description.error(CommandCause::WaitFailed(err), Default::default()))?;
+        let stderr_tail = if output.status.success() {
+            Default::default()
+        } else {
+            tail_lines(&String::from_utf8_lossy(&output.stderr))
+        };
+        description.finish(start, output.status, stderr_tail)?;
+        Ok(Output {
+            status: output.status,
+            stdout: output.stdout,
+            stderr: output.stderr,
+        })
+    }
+}
+
+static GLOBAL: OnceCell<Runner> = OnceCell::new();
+
+/// Runs commands the same way everywhere: children are tracked so that they
+/// get killed if we're interrupted, output is shown or hidden based on the
+/// [`Verbosity`], failures carry the full invocation, and every command gets
+/// recorded so that a build can be replayed by hand.
+#[derive(Debug, Default)]
+pub struct Runner {
+    verbosity: Verbosity,
+    log: Mutex<Vec<String>>,
+}
+
+impl Runner {
+    pub fn new(verbosity: Verbosity) -> Self {
+        Self {
+            verbosity,
+            log: Default::default(),
+        }
+    }
+
+    /// Sets up the runner returned by [`Runner::global`]. This only works once;
+    /// later calls are ignored.
+    pub fn init_global(verbosity: Verbosity) {
+        if GLOBAL.set(Self::new(verbosity)).is_err() {
+            log::warn!("global runner was already initialized");
+        }
+    }
+
+    /// The process-wide runner, which uses the default verbosity if
+    /// [`Runner::init_global`] was never called.
+    pub fn global() -> &'static Self {
+        GLOBAL.get_or_init(Default::default)
+    }
+
+    pub fn verbosity(&self) -> Verbosity {
+        self.verbosity
+    }
+
+    fn record(&self, invocation: &Invocation) {
+        let command_line = invocation.command_line();
+        let entry = if let Some(current_dir) = invocation.current_dir() {
+            format!(
+                "(cd {} && {})",
+                shell_quote(current_dir.as_os_str()),
+                command_line
+            )
+        } else {
+            command_line
+        };
+        self.log
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(entry);
+    }
+
+    /// Starts `invocation` with the given stdio, without waiting for it.
+    pub fn spawn(
+        &self,
+        invocation: &Invocation,
+        stdin: Stdio,
+        stdout: Stdio,
+        stderr: Stdio,
+    ) -> Result<Child, CommandError> {
+        let command_line = invocation.command_line();
+        if self.verbosity == Verbosity::Verbose {
+            log::info!("running `{}`", command_line);
+        }
+        self.record(invocation);
+        crate::env::log_command_env(invocation);
+        let description = Description {
+            program: invocation.program_name(),
+            command_line,
+            current_dir: invocation.current_dir().map(ToOwned::to_owned),
+        };
+        let start = Instant::now();
+        let inner = invocation
+            .to_std()
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(stderr)
+            .spawn()
+            .map_err(|err| description.error(CommandCause::SpawnFailed(err), Default::default()))?;
+        let _guard = os::register_child(inner.id());
+        Ok(Child {
+            inner,
+            description,
+            start,
+            stderr_tee: None,
+            _guard,
+        })
+    }
+
+    /// Runs `invocation` with its output shown as it happens, unless we're
+    /// being quiet. stderr goes through us either way, so that the end of it
+    /// can be included if the program fails.
+    pub fn stream(&self, invocation: &Invocation) -> Result<ExitStatus, CommandError> {
+        let quiet = self.verbosity == Verbosity::Quiet;
+        let stdout = if quiet {
+            Stdio::null()
+        } else {
+            Stdio::inherit()
+        };
+        let mut child = self.spawn(invocation, Stdio::inherit(), stdout, Stdio::piped())?;
+        child.tee_stderr(!quiet);
+        child.wait().map_err(|err| {
+            // Quiet mode hides everything but the end of stderr.
+            if quiet {
+                err.with_hint("run without `--quiet` to see the full output")
+            } else {
+                err
+            }
+        })
+    }
+
+    /// Runs `invocation` with its output captured, regardless of verbosity.
+    pub fn capture(&self, invocation: &Invocation) -> Result<Output, CommandError> {
+        self.spawn(invocation, Stdio::inherit(), Stdio::piped(), Stdio::piped())?
+            .wait_for_output()
+    }
+
+    /// Every command run so far as a shell script, which can be run to
+    /// reproduce what happened. Environment variables aren't included.
+    pub fn shell_log(&self) -> String {
+        let log = self.log.lock().unwrap_or_else(|err| err.into_inner());
+        let mut script = "#!/bin/sh\nset -e\n".to_owned();
+        for entry in log.iter() {
+            script.push_str(entry);
+            script.push('\n');
+        }
+        script
+    }
+
+    pub fn write_shell_log(&self, path: &Path) -> io::Result<()> {
+        log::info!("writing shell log to {:?}", path);
+        fs::write(path, self.shell_log())
+    }
+}
+
+impl CommandRunner for Runner {
+    fn stream(&self, invocation: &Invocation) -> Result<ExitStatus, CommandError> {
+        Runner::stream(self, invocation)
+    }
+
+    fn capture(&self, invocation: &Invocation) -> Result<Output, CommandError> {
+        Runner::capture(self, invocation)
+    }
+}
+
+// What a process that exited with `code` would've reported.
+#[cfg(all(test, unix))]
+fn exit_status(code: i32) -> ExitStatus {
+    std::os::unix::process::ExitStatusExt::from_raw(code << 8)
+}
+
+// What a process that exited with `code` would've reported.
+#[cfg(all(test, windows))]
+fn exit_status(code: i32) -> ExitStatus {
+    std::os::windows::process::ExitStatusExt::from_raw(code as u32)
+}
+
+/// Records invocations instead of running anything. Each one gets the next
+/// canned result, and once those run out, everything succeeds with no output.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct RecordingRunner {
+    calls: Mutex<Vec<Invocation>>,
+    results: Mutex<VecDeque<(i32, Vec<u8>, Vec<u8>)>>,
+}
+
+#[cfg(test)]
+impl RecordingRunner {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Queues up a successful run that prints `stdout`.
+    pub fn with_output(self, stdout: impl Into<Vec<u8>>) -> Self {
+        self.with_result(0, stdout, "")
+    }
+
+    /// Queues up a run that exits with `code` after printing `stderr`.
+    pub fn with_failure(self, code: i32, stderr: impl Into<Vec<u8>>) -> Self {
+        self.with_result(code, "", stderr)
+    }
+
+    fn with_result(
+        self,
+        code: i32,
+        stdout: impl Into<Vec<u8>>,
+        stderr: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.results
+            .lock()
+            .unwrap()
+            .push_back((code, stdout.into(), stderr.into()));
+        self
+    }
+
+    /// Every invocation that's been run so far, in order.
+    pub fn calls(&self) -> Vec<Invocation> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// The command line of every invocation that's been run so far.
+    pub fn command_lines(&self) -> Vec<String> {
+        self.calls().iter().map(Invocation::command_line).collect()
+    }
+
+    fn run(&self, invocation: &Invocation) -> Result<Output, CommandError> {
+        log::info!("recording `{}`", invocation.command_line());
+        self.calls.lock().unwrap().push(invocation.clone());
+        let (code, stdout, stderr) = self.results.lock().unwrap().pop_front().unwrap_or_default();
+        let status = exit_status(code);
+        let description = Description {
+            program: invocation.program_name(),
+            command_line: invocation.command_line(),
+            current_dir: invocation.current_dir().map(ToOwned::to_owned),
+        };
+        description.finish(
+            Instant::now(),
+            status,
+            tail_lines(&String::from_utf8_lossy(&stderr)),
+        )?;
+        Ok(Output {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+}
+
+#[cfg(test)]
+impl CommandRunner for RecordingRunner {
+    fn stream(&self, invocation: &Invocation) -> Result<ExitStatus, CommandError> {
+        self.run(invocation).map(|output| output.status)
+    }
+
+    fn capture(&self, invocation: &Invocation) -> Result<Output, CommandError> {
+        self.run(invocation)
+    }
+}
+
+/// Where to dump [`Runner::shell_log`] at exit, if anywhere.
+pub fn shell_log_path() -> Option<PathBuf> {
+    std::env::var_os("CARGO_MOBILE_SHELL_LOG").map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_line_quotes_only_what_it_has_to() {
+        let invocation = Invocation::impure("git")
+            .with_args(&["commit", "-m", "it's done", "--author=me@example.com"])
+            .with_arg("");
+        assert_eq!(
+            invocation.command_line(),
+            "git commit -m 'it'\\''s done' --author=me@example.com ''"
+        );
+    }
+
+    #[test]
+    fn parsed_args_are_split_on_whitespace() {
+        let invocation = Invocation::impure_parse("cargo build  --release").with_arg("a b");
+        assert_eq!(invocation.program(), "cargo");
+        assert_eq!(invocation.command_line(), "cargo build --release 'a b'");
+    }
+
+    #[test]
+    fn shell_log_changes_into_the_current_dir() {
+        let runner = Runner::new(Verbosity::Quiet);
+        runner.record(&Invocation::impure("ls"));
+        runner.record(&Invocation::impure("make").with_current_dir("/tmp/my app"));
+        assert_eq!(
+            runner.shell_log(),
+            "#!/bin/sh\nset -e\nls\n(cd '/tmp/my app' && make)\n"
+        );
+    }
+
+    #[test]
+    fn tail_lines_keeps_the_end() {
+        let stderr = (1..=30)
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let tail = tail_lines(&stderr);
+        assert_eq!(tail.lines().count(), STDERR_TAIL_LINES);
+        assert!(tail.starts_with("11\n"));
+        assert!(tail.ends_with("\n30"));
+    }
+
+    #[test]
+    fn recording_runner_hands_out_canned_results_in_order() {
+        let runner = RecordingRunner::new()
+            .with_output("first")
+            .with_failure(2, "error: nope\n");
+        let output = runner.capture(&Invocation::impure("a")).unwrap();
+        assert_eq!(output.stdout_str().unwrap(), "first");
+        let err = runner
+            .stream(&Invocation::impure("b").with_arg("--flag"))
+            .unwrap_err();
+        assert_eq!(err.exit_code(), Some(2));
+        assert_eq!(err.stderr_tail(), "error: nope");
+        assert!(runner.stream(&Invocation::impure("c")).is_ok());
+        assert_eq!(runner.command_lines(), vec!["a", "b --flag", "c"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failures_carry_the_command_line_and_stderr() {
+        let runner = Runner::new(Verbosity::Quiet);
+        let err = runner
+            .capture(
+                &Invocation::impure("sh")
+                    .with_args(&["-c", "echo one >&2; echo two >&2; exit 3"])
+                    .with_current_dir("/"),
+            )
+            .unwrap_err();
+        assert_eq!(err.exit_code(), Some(3));
+        assert_eq!(err.stderr_tail(), "one\ntwo");
+        let message = err.to_string();
+        assert!(message.contains("sh -c 'echo one >&2; echo two >&2; exit 3'"));
+        assert!(message.contains("one\ntwo"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn streaming_keeps_the_stderr_tail() {
+        let runner = Runner::new(Verbosity::Quiet);
+        let err = runner
+            .stream(&Invocation::impure("sh").with_args(&["-c", "echo oops >&2; exit 1"]))
+            .unwrap_err();
+        assert_eq!(err.stderr_tail(), "oops");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn programs_are_spawned_by_absolute_path() {
+        let command = Invocation::impure("sh").to_std();
+        assert!(Path::new(command.get_program()).is_absolute());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn programs_are_looked_up_on_the_invocations_path() {
+        use std::os::unix::fs::PermissionsExt as _;
+        let dir = tempfile::tempdir().unwrap();
+        let tool = dir.path().join("cargo-mobile-fake-tool");
+        fs::write(&tool, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+        let on_our_path = Invocation::impure("cargo-mobile-fake-tool").to_std();
+        assert_eq!(on_our_path.get_program(), "cargo-mobile-fake-tool");
+        let on_its_path = Invocation::pure("cargo-mobile-fake-tool")
+            .with_env_var("PATH", dir.path())
+            .to_std();
+        assert_eq!(on_its_path.get_program(), tool.as_os_str());
+    }
+
+    #[test]
+    fn missing_programs_are_not_found() {
+        let runner = Runner::new(Verbosity::Quiet);
+        let err = runner
+            .capture(&Invocation::pure("cargo-mobile-definitely-not-a-program"))
+            .unwrap_err();
+        assert!(
+            matches!(err, CommandError::NotFound { .. }),
+            "unexpected error: {:?}",
+            err
+        );
+    }
+}
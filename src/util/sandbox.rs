@@ -0,0 +1,151 @@
+use super::ln;
+use std::{
+    fmt::{self, Display},
+    io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug)]
+pub enum SandboxError {
+    EscapesRoot { path: PathBuf, root: PathBuf },
+    WriteFailed { path: PathBuf, cause: io::Error },
+    SymlinkFailed(ln::Error),
+}
+
+impl Display for SandboxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EscapesRoot { path, root } => write!(
+                f,
+                "Refusing to touch {:?}, since it's outside of the project root {:?}.",
+                path, root
+            ),
+            Self::WriteFailed { path, cause } => {
+                write!(f, "Failed to write file at {:?}: {}", path, cause)
+            }
+            Self::SymlinkFailed(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// A directory that everything we generate has to stay within, so that a
+/// template containing something like `../../etc/passwd` can't do any harm.
+#[derive(Clone, Debug)]
+pub struct SandboxRoot {
+    root: PathBuf,
+}
+
+impl SandboxRoot {
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        root.as_ref().canonicalize().map(|root| Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // Anything that already exists is canonicalized, so that symlinks pointing
+    // outside of the root get caught too. Otherwise, we resolve what we can of
+    // the parent and normalize the rest lexically.
+    fn resolve(&self, path: &Path) -> PathBuf {
+        let path = super::normalize_lexically(&self.root.join(path));
+        path.canonicalize()
+            .unwrap_or_else(|_| match (path.parent(), path.file_name()) {
+                (Some(parent), Some(file_name)) => parent
+                    .canonicalize()
+                    .map(|parent| parent.join(file_name))
+                    .unwrap_or_else(|_| path.clone()),
+                _ => path.clone(),
+            })
+    }
+
+    /// Gets the absolute location of `path` (which is relative to the root if
+    /// it isn't absolute), failing if it isn't within the root.
+    pub fn check(&self, path: impl AsRef<Path>) -> Result<PathBuf, SandboxError> {
+        let path = path.as_ref();
+        let resolved = self.resolve(path);
+        if super::strip_prefix_ci(&resolved, &self.root).is_some() {
+            Ok(resolved)
+        } else {
+            log::error!(
+                "{:?} resolved to {:?}, which escapes {:?}",
+                path,
+                resolved,
+                self.root
+            );
+            Err(SandboxError::EscapesRoot {
+                path: path.to_owned(),
+                root: self.root.clone(),
+            })
+        }
+    }
+
+    /// Like [`super::write_str_if_changed`], but only within the root.
+    pub fn write_str_if_changed(
+        &self,
+        path: impl AsRef<Path>,
+        contents: impl AsRef<[u8]>,
+    ) -> Result<bool, SandboxError> {
+        let path = self.check(path)?;
+        super::write_str_if_changed(&path, contents)
+            .map_err(|cause| SandboxError::WriteFailed { path, cause })
+    }
+
+    /// Like [`ln::force_symlink_relative`], but both the link and what it
+    /// points to have to be within the root.
+    pub fn force_symlink_relative(
+        &self,
+        abs_source: impl AsRef<Path>,
+        abs_target: impl AsRef<Path>,
+        target_style: ln::TargetStyle,
+        parents: ln::Parents,
+    ) -> Result<(), SandboxError> {
+        let abs_source = self.check(abs_source)?;
+        let abs_target = self.check(abs_target)?;
+        ln::force_symlink_relative(abs_source, abs_target, target_style, parents)
+            .map_err(SandboxError::SymlinkFailed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn paths_are_kept_within_the_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("app");
+        fs::create_dir_all(&root).unwrap();
+        let sandbox = SandboxRoot::new(&root).unwrap();
+        let root = sandbox.root().to_owned();
+        assert_eq!(
+            sandbox.check("gen/android/build.gradle.kts").unwrap(),
+            root.join("gen/android/build.gradle.kts")
+        );
+        assert!(sandbox.write_str_if_changed("Cargo.toml", "").unwrap());
+        assert!(matches!(
+            sandbox.check("gen/../../precious"),
+            Err(SandboxError::EscapesRoot { .. })
+        ));
+        assert!(matches!(
+            sandbox.write_str_if_changed(dir.path().join("precious"), ""),
+            Err(SandboxError::EscapesRoot { .. })
+        ));
+        assert!(!dir.path().join("precious").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_out_of_the_root_are_caught() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("app");
+        fs::create_dir_all(&root).unwrap();
+        std::os::unix::fs::symlink(dir.path(), root.join("escape")).unwrap();
+        let sandbox = SandboxRoot::new(&root).unwrap();
+        assert!(matches!(
+            sandbox.check("escape/precious"),
+            Err(SandboxError::EscapesRoot { .. })
+        ));
+    }
+}
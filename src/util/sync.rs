@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Component, Path},
+    time::UNIX_EPOCH,
+};
+
+/// The manifest for asset syncing, which lives in each platform's project dir.
+/// It can't live in the synced dir itself, since it'd then get bundled into the
+/// app along with everything else.
+pub static ASSET_SYNC_MANIFEST_FILE_NAME: &str = ".cargo-mobile-asset-sync.json";
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+struct Stamp {
+    len: u64,
+    modified_secs: u64,
+    modified_nanos: u32,
+}
+
+impl Stamp {
+    fn from_metadata(metadata: &fs::Metadata) -> Self {
+        // Filesystems without mtimes just get resynced every time.
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        Self {
+            len: metadata.len(),
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+        }
+    }
+
+    fn known(&self) -> bool {
+        self.modified_secs != 0 || self.modified_nanos != 0
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct SyncManifest {
+    files: BTreeMap<String, Stamp>,
+}
+
+impl SyncManifest {
+    fn load(path: &Path) -> Self {
+        match fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|err| {
+                log::warn!(
+                    "sync manifest {:?} was invalid, so resyncing: {}",
+                    path,
+                    err
+                );
+                Default::default()
+            }),
+            Err(err) => {
+                if err.kind() != io::ErrorKind::NotFound {
+                    log::warn!("failed to read sync manifest {:?}: {}", path, err);
+                }
+                Default::default()
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SyncStats {
+    pub copied: usize,
+    pub skipped: usize,
+    pub removed: usize,
+}
+
+// Separators are normalized so the manifest is the same on every platform.
+fn manifest_key(rel_path: &Path) -> String {
+    rel_path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(component) => Some(component.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn sync_dir_inner(
+    root: &Path,
+    src: &Path,
+    dest: &Path,
+    force: bool,
+    old: &SyncManifest,
+    new: &mut SyncManifest,
+    stats: &mut SyncStats,
+) -> io::Result<()> {
+    super::create_dir_all(dest)?;
+    for entry in super::sorted_dir_entries(src)? {
+        let path = entry.path();
+        let dest = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            sync_dir_inner(root, &path, &dest, force, old, new, stats)?;
+            continue;
+        }
+        let key = manifest_key(
+            path.strip_prefix(root)
+                .expect("developer error: walked outside of sync root"),
+        );
+        let stamp = Stamp::from_metadata(&fs::metadata(&path)?);
+        let unchanged = !force
+            && stamp.known()
+            && old.files.get(&key) == Some(&stamp)
+            && fs::metadata(&dest).map_or(false, |dest| dest.len() == stamp.len);
+        if unchanged {
+            log::debug!("{:?} is unchanged, so not copying it", path);
+            stats.skipped += 1;
+        } else {
+            log::debug!("copying {:?} to {:?}", path, dest);
+            fs::copy(&path, &dest)?;
+            stats.copied += 1;
+        }
+        new.files.insert(key, stamp);
+    }
+    Ok(())
+}
+
+/// Like [`copy_dir_all`](super::copy_dir_all), but only copies files that
+/// changed since the last sync into `dest`, and removes files that were synced
+/// before but are now gone from `src`. Files are considered changed if their
+/// size or modification time differs from what's recorded in the manifest at
+/// `manifest_path`; `force` copies everything regardless.
+pub fn sync_dir(
+    src: impl AsRef<Path>,
+    dest: impl AsRef<Path>,
+    manifest_path: impl AsRef<Path>,
+    force: bool,
+) -> io::Result<SyncStats> {
+    let (src, dest, manifest_path) = (src.as_ref(), dest.as_ref(), manifest_path.as_ref());
+    let old = SyncManifest::load(manifest_path);
+    let mut new = SyncManifest::default();
+    let mut stats = SyncStats::default();
+    sync_dir_inner(src, src, dest, force, &old, &mut new, &mut stats)?;
+    // Only files we synced ourselves are removed, so anything else that ended
+    // up in `dest` is left alone.
+    for key in old.files.keys().filter(|key| !new.files.contains_key(*key)) {
+        let stale = key
+            .split('/')
+            .fold(dest.to_owned(), |path, part| path.join(part));
+        log::debug!("{:?} is gone from {:?}, so removing {:?}", key, src, stale);
+        match fs::remove_file(&stale) {
+            Ok(()) => stats.removed += 1,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err),
+        }
+    }
+    let ser = serde_json::to_string_pretty(&new)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    super::write_str_if_changed(manifest_path, ser)?;
+    log::info!(
+        "synced {:?} to {:?}: {} copied, {} unchanged, {} removed",
+        src,
+        dest,
+        stats.copied,
+        stats.skipped,
+        stats.removed
+    );
+    Ok(stats)
+}
@@ -0,0 +1,208 @@
+//! Just enough XML handling to make small, targeted edits to files like
+//! `AndroidManifest.xml` and `Info.plist`. Everything outside of the parts
+//! being edited is left byte-for-byte as it was, so formatting, comments, and
+//! anything the user added survive.
+
+use std::fmt::{self, Display};
+
+#[derive(Debug)]
+pub enum EditError {
+    ElementMissing { name: &'static str },
+    Unterminated { start: usize },
+    Malformed { reason: String },
+}
+
+impl Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ElementMissing { name } => write!(f, "No `<{}>` element was found.", name),
+            Self::Unterminated { start } => {
+                write!(f, "The tag at byte offset {} is never closed.", start)
+            }
+            Self::Malformed { reason } => write!(f, "{}", reason),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum TagKind {
+    Open,
+    Close,
+    Empty,
+}
+
+/// A tag's position in the source, where `end` is just past the `>`.
+#[derive(Clone, Copy, Debug)]
+pub struct Tag<'a> {
+    pub start: usize,
+    pub end: usize,
+    pub name: &'a str,
+    pub kind: TagKind,
+}
+
+// Finds the `>` that ends the tag starting at `start`, skipping over any in
+// quoted attribute values.
+fn tag_end(s: &str, start: usize) -> Result<usize, EditError> {
+    let mut quote = None;
+    for (index, c) in s[start..].char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return Ok(start + index + 1),
+            _ => (),
+        }
+    }
+    Err(EditError::Unterminated { start })
+}
+
+/// Finds the first tag at or after `from`, skipping comments, processing
+/// instructions, and doctypes.
+pub fn next_tag(s: &str, from: usize) -> Result<Option<Tag<'_>>, EditError> {
+    let mut from = from;
+    loop {
+        let start = match s[from..].find('<') {
+            Some(offset) => from + offset,
+            None => return Ok(None),
+        };
+        let rest = &s[start..];
+        let skip_to = |terminator: &str| {
+            rest.find(terminator)
+                .map(|offset| start + offset + terminator.len())
+                .ok_or(EditError::Unterminated { start })
+        };
+        if rest.starts_with("<!--") {
+            from = skip_to("-->")?;
+        } else if rest.starts_with("<?") {
+            from = skip_to("?>")?;
+        } else if rest.starts_with("<!") {
+            from = tag_end(s, start)?;
+        } else {
+            let end = tag_end(s, start)?;
+            let inner = &s[start + 1..end - 1];
+            let kind = if inner.starts_with('/') {
+                TagKind::Close
+            } else if inner.ends_with('/') {
+                TagKind::Empty
+            } else {
+                TagKind::Open
+            };
+            let name = inner
+                .trim_start_matches('/')
+                .split(|c: char| c.is_whitespace() || c == '/')
+                .next()
+                .unwrap_or_default();
+            return Ok(Some(Tag {
+                start,
+                end,
+                name,
+                kind,
+            }));
+        }
+    }
+}
+
+/// Finds the root element's start tag.
+pub fn root_tag(s: &str) -> Result<Tag<'_>, EditError> {
+    next_tag(s, 0)?.ok_or_else(|| EditError::Malformed {
+        reason: "There's no root element.".to_owned(),
+    })
+}
+
+/// An attribute's position in the source, where `value_start..value_end` is
+/// the value without its quotes.
+#[derive(Clone, Copy, Debug)]
+struct Attr<'a> {
+    name_start: usize,
+    name: &'a str,
+    value_start: usize,
+    value_end: usize,
+}
+
+fn attrs<'a>(s: &'a str, tag: &Tag<'a>) -> Result<Vec<Attr<'a>>, EditError> {
+    let malformed = || EditError::Malformed {
+        reason: format!("The attributes of `<{}>` couldn't be parsed.", tag.name),
+    };
+    let body_end = if let TagKind::Empty = tag.kind {
+        tag.end - 2
+    } else {
+        tag.end - 1
+    };
+    let mut index = tag.start + 1 + tag.name.len();
+    let mut attrs = Vec::new();
+    loop {
+        let rest = &s[index..body_end];
+        let trimmed = rest.trim_start();
+        if trimmed.is_empty() {
+            break;
+        }
+        let name_start = index + (rest.len() - trimmed.len());
+        let eq = trimmed.find('=').ok_or_else(malformed)?;
+        let name = trimmed[..eq].trim_end();
+        let after_eq = trimmed[eq + 1..].trim_start();
+        let quote = after_eq.chars().next().ok_or_else(malformed)?;
+        if quote != '"' && quote != '\'' {
+            return Err(malformed());
+        }
+        let value_start = body_end - after_eq.len() + 1;
+        let value_end = value_start + s[value_start..body_end].find(quote).ok_or_else(malformed)?;
+        attrs.push(Attr {
+            name_start,
+            name,
+            value_start,
+            value_end,
+        });
+        index = value_end + 1;
+    }
+    Ok(attrs)
+}
+
+/// Sets attribute `name` on `tag` to `value`, which is escaped here. If the
+/// attribute isn't there yet, it's added after the last one, matching the
+/// existing layout.
+pub fn set_attr(s: &str, tag: &Tag<'_>, name: &str, value: &str) -> Result<String, EditError> {
+    let attrs = attrs(s, tag)?;
+    let value = escape(value);
+    if let Some(attr) = attrs.iter().find(|attr| attr.name == name) {
+        return Ok(format!(
+            "{}{}{}",
+            &s[..attr.value_start],
+            value,
+            &s[attr.value_end..]
+        ));
+    }
+    let (insert_at, separator) = if let Some(last) = attrs.last() {
+        let line_start = s[..last.name_start].rfind('\n').map(|index| index + 1);
+        let separator = line_start
+            .map(|line_start| &s[line_start..last.name_start])
+            .filter(|indent| indent.chars().all(char::is_whitespace))
+            .map(|indent| format!("\n{}", indent))
+            .unwrap_or_else(|| " ".to_owned());
+        (last.value_end + 1, separator)
+    } else {
+        (tag.start + 1 + tag.name.len(), " ".to_owned())
+    };
+    Ok(format!(
+        "{}{}{}=\"{}\"{}",
+        &s[..insert_at],
+        separator,
+        name,
+        value,
+        &s[insert_at..]
+    ))
+}
+
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+pub fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
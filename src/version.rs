@@ -0,0 +1,214 @@
+use crate::{
+    generated::{self, GenerationManifest},
+    util::{
+        cli::{Report, Reportable},
+        xml,
+    },
+};
+use serde::Deserialize;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub static BUILD_NUMBER_FILE_NAME: &str = ".mobile-build-number";
+
+#[derive(Debug)]
+pub enum Error {
+    CargoTomlReadFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
+    CargoTomlParseFailed {
+        path: PathBuf,
+        cause: toml::de::Error,
+    },
+    VersionMissing {
+        path: PathBuf,
+    },
+    BuildNumberReadFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
+    BuildNumberInvalid {
+        path: PathBuf,
+        contents: String,
+    },
+    BuildNumberWriteFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
+    ReadFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
+    EditFailed {
+        path: PathBuf,
+        cause: xml::EditError,
+    },
+    WriteFailed {
+        path: PathBuf,
+        cause: io::Error,
+    },
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::CargoTomlReadFailed { path, cause } => {
+                Report::error(format!("Failed to read {:?}", path), cause)
+            }
+            Self::CargoTomlParseFailed { path, cause } => {
+                Report::error(format!("Failed to parse {:?}", path), cause)
+            }
+            Self::VersionMissing { path } => Report::action_request(
+                "Failed to get app version",
+                format!("{:?} doesn't have a `package.version`.", path),
+            ),
+            Self::BuildNumberReadFailed { path, cause } => {
+                Report::error(format!("Failed to read build number from {:?}", path), cause)
+            }
+            Self::BuildNumberInvalid { path, contents } => Report::action_request(
+                format!("Build number in {:?} is invalid", path),
+                format!(
+                    "{:?} isn't a non-negative integer; fix it, delete the file to start over at 1, or pass `--build-number`.",
+                    contents
+                ),
+            ),
+            Self::BuildNumberWriteFailed { path, cause } => {
+                Report::error(format!("Failed to write build number to {:?}", path), cause)
+            }
+            Self::ReadFailed { path, cause } => {
+                Report::error(format!("Failed to read {:?}", path), cause)
+            }
+            Self::EditFailed { path, cause } => Report::error(
+                format!("Failed to stamp version into {:?}", path),
+                cause,
+            ),
+            Self::WriteFailed { path, cause } => {
+                Report::error(format!("Failed to write {:?}", path), cause)
+            }
+        }
+    }
+}
+
+fn cargo_version(root_dir: &Path) -> Result<String, Error> {
+    #[derive(Debug, Deserialize)]
+    struct Package {
+        version: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct CargoToml {
+        package: Package,
+    }
+
+    let path = root_dir.join("Cargo.toml");
+    let contents = fs::read_to_string(&path).map_err(|cause| Error::CargoTomlReadFailed {
+        path: path.clone(),
+        cause,
+    })?;
+    toml::from_str::<CargoToml>(&contents)
+        .map_err(|cause| Error::CargoTomlParseFailed {
+            path: path.clone(),
+            cause,
+        })?
+        .package
+        .version
+        .ok_or_else(|| Error::VersionMissing { path })
+}
+
+fn last_build_number(path: &Path) -> Result<u32, Error> {
+    match fs::read_to_string(path) {
+        Ok(contents) => contents
+            .trim()
+            .parse()
+            .map_err(|_| Error::BuildNumberInvalid {
+                path: path.to_owned(),
+                contents: contents.trim().to_owned(),
+            }),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(cause) => Err(Error::BuildNumberReadFailed {
+            path: path.to_owned(),
+            cause,
+        }),
+    }
+}
+
+/// The version to stamp into the platform projects, which is the app's
+/// `Cargo.toml` version along with a build number. The build number becomes
+/// Android's `versionCode` and iOS's `CFBundleVersion`, so it needs to keep
+/// going up.
+#[derive(Clone, Debug)]
+pub struct Stamp {
+    version: String,
+    build_number: u32,
+}
+
+impl Stamp {
+    /// Uses `build_number` if it's given, and otherwise the last build number
+    /// plus one. Either way, the build number is recorded in
+    /// `BUILD_NUMBER_FILE_NAME` so that the next one follows it.
+    pub fn new(root_dir: &Path, build_number: Option<u32>) -> Result<Self, Error> {
+        let version = cargo_version(root_dir)?;
+        let path = root_dir.join(BUILD_NUMBER_FILE_NAME);
+        let build_number = match build_number {
+            Some(build_number) => build_number,
+            None => last_build_number(&path)?.saturating_add(1),
+        };
+        fs::write(&path, format!("{}\n", build_number))
+            .map_err(|cause| Error::BuildNumberWriteFailed { path, cause })?;
+        log::info!("stamping version {} (build {})", version, build_number);
+        Ok(Self {
+            version,
+            build_number,
+        })
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn build_number(&self) -> u32 {
+        self.build_number
+    }
+}
+
+/// Replaces the contents of the generated file at `path` with the result of
+/// `edit`. If the file was unmodified before, it's recorded as still being
+/// unmodified, since `cargo mobile regen` shouldn't mistake our edits for the
+/// user's.
+pub fn rewrite(
+    root_dir: &Path,
+    path: &Path,
+    edit: impl FnOnce(&str) -> Result<String, xml::EditError>,
+) -> Result<(), Error> {
+    let old = fs::read_to_string(path).map_err(|cause| Error::ReadFailed {
+        path: path.to_owned(),
+        cause,
+    })?;
+    let new = edit(&old).map_err(|cause| Error::EditFailed {
+        path: path.to_owned(),
+        cause,
+    })?;
+    if new == old {
+        return Ok(());
+    }
+    fs::write(path, &new).map_err(|cause| Error::WriteFailed {
+        path: path.to_owned(),
+        cause,
+    })?;
+    match GenerationManifest::load(root_dir) {
+        Ok(mut manifest) => {
+            let key = path.strip_prefix(root_dir).unwrap_or(path);
+            if manifest.file_hash(key) == Some(generated::hash_contents(old.as_bytes()).as_str()) {
+                manifest.set_file_hash(key, generated::hash_contents(new.as_bytes()));
+                if let Err(err) = manifest.write(root_dir) {
+                    log::warn!("failed to update generation manifest: {}", err);
+                }
+            }
+        }
+        Err(err) => log::info!("not updating generation manifest: {}", err),
+    }
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use crate::util::cli::{Report, Reportable, TextWrapper};
+use ignore::gitignore::Gitignore;
+use notify::{DebouncedEvent, RecursiveMode, Watcher as _};
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+// Editors tend to write a file several times when saving, so we wait for
+// things to settle down before rebuilding.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+#[derive(Debug)]
+pub enum Error {
+    WatcherFailed(notify::Error),
+    WatchFailed {
+        root_dir: PathBuf,
+        cause: notify::Error,
+    },
+    Disconnected,
+}
+
+impl Reportable for Error {
+    fn report(&self) -> Report {
+        match self {
+            Self::WatcherFailed(err) => Report::error("Failed to start file watcher", err),
+            Self::WatchFailed { root_dir, cause } => {
+                Report::error(format!("Failed to watch {:?} for changes", root_dir), cause)
+            }
+            Self::Disconnected => Report::error(
+                "File watcher stopped unexpectedly",
+                "The watcher's event channel was disconnected",
+            ),
+        }
+    }
+}
+
+struct Filter<'a> {
+    root_dir: &'a Path,
+    ignored: Vec<PathBuf>,
+    gitignore: Gitignore,
+}
+
+impl<'a> Filter<'a> {
+    fn new(root_dir: &'a Path, ignored: &[PathBuf]) -> Self {
+        let (gitignore, err) = Gitignore::new(root_dir.join(".gitignore"));
+        if let Some(err) = err {
+            log::info!("not using `.gitignore` to filter watched files: {}", err);
+        }
+        let ignored = ignored
+            .iter()
+            .cloned()
+            .chain(Some(root_dir.join("target")))
+            .chain(Some(root_dir.join(".git")))
+            .collect();
+        Self {
+            root_dir,
+            ignored,
+            gitignore,
+        }
+    }
+
+    fn relevant(&self, path: &Path) -> bool {
+        if self.ignored.iter().any(|ignored| path.starts_with(ignored)) {
+            false
+        } else if path.starts_with(self.root_dir) {
+            !self
+                .gitignore
+                .matched_path_or_any_parents(path, path.is_dir())
+                .is_ignore()
+        } else {
+            // This can happen with symlinks, and `Gitignore` panics on paths
+            // outside of its root.
+            true
+        }
+    }
+}
+
+fn changed_path(event: &DebouncedEvent) -> Option<&Path> {
+    match event {
+        DebouncedEvent::Create(path)
+        | DebouncedEvent::Write(path)
+        | DebouncedEvent::Remove(path)
+        | DebouncedEvent::Rename(_, path) => Some(path),
+        _ => None,
+    }
+}
+
+fn rerun<R: Reportable>(wrapper: &TextWrapper, f: &mut impl FnMut() -> Result<(), R>) {
+    // A failed build is just part of the iteration loop, so it shouldn't stop
+    // us from watching.
+    if let Err(err) = f() {
+        err.report().print(wrapper);
+    }
+    println!("Watching for changes...");
+}
+
+/// Runs `f`, and then runs it again whenever anything in `root_dir` changes.
+/// Changes to anything in `ignored`, `target`, or `.git`, or to anything
+/// matched by the root `.gitignore`, are ignored. Errors returned by `f` are
+/// reported without stopping, so this only returns if watching fails.
+pub fn watch<R: Reportable>(
+    wrapper: &TextWrapper,
+    root_dir: &Path,
+    ignored: &[PathBuf],
+    mut f: impl FnMut() -> Result<(), R>,
+) -> Result<(), Error> {
+    let filter = Filter::new(root_dir, ignored);
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::watcher(tx, DEBOUNCE).map_err(Error::WatcherFailed)?;
+    watcher
+        .watch(root_dir, RecursiveMode::Recursive)
+        .map_err(|cause| Error::WatchFailed {
+            root_dir: root_dir.to_owned(),
+            cause,
+        })?;
+    rerun(wrapper, &mut f);
+    loop {
+        let event = rx.recv().map_err(|_| Error::Disconnected)?;
+        log::info!("watcher event: {:?}", event);
+        if let Some(path) = changed_path(&event).filter(|path| filter.relevant(path)) {
+            println!(
+                "{} changed; rebuilding...",
+                path.strip_prefix(root_dir).unwrap_or(path).display()
+            );
+            // Anything else that changed in the meantime is covered by this
+            // rebuild.
+            while rx.try_recv().is_ok() {}
+            rerun(wrapper, &mut f);
+        }
+    }
+}